@@ -17,6 +17,12 @@ BUILD_DIR="target/release"
 DIST_DIR="dist"
 VERSION="0.1.0"
 
+# `./build.sh --headless` builds only the CLI, without GUI/display dependencies
+HEADLESS=false
+if [ "$1" = "--headless" ]; then
+    HEADLESS=true
+fi
+
 echo -e "${BLUE}🎵 Desktop Autotune Application Build Script${NC}"
 echo -e "${BLUE}===============================================${NC}"
 echo ""
@@ -29,7 +35,7 @@ if ! command -v cargo &> /dev/null; then
     exit 1
 fi
 
-if ! command -v pkg-config &> /dev/null; then
+if [ "$HEADLESS" = false ] && ! command -v pkg-config &> /dev/null; then
     echo -e "${YELLOW}⚠️  Warning: pkg-config not found. Some GUI dependencies might fail.${NC}"
 fi
 
@@ -45,7 +51,7 @@ mkdir -p "$DIST_DIR"
 # Generate test files if they don't exist
 echo -e "${YELLOW}🎼 Generating test files...${NC}"
 if [ ! -f "test_audio.wav" ] || [ ! -f "test_audio_off_pitch.wav" ]; then
-    cargo run --example generate_test_wav
+    cargo run --no-default-features --bin autotune-cli -- generate
     echo -e "${GREEN}✅ Test files generated${NC}"
 else
     echo -e "${GREEN}✅ Test files already exist${NC}"
@@ -56,20 +62,28 @@ echo ""
 echo -e "${YELLOW}🔨 Building release binaries...${NC}"
 echo "   This may take several minutes on first build..."
 
-# Build GUI application
-echo -e "${BLUE}Building GUI application (autotune-gui)...${NC}"
-cargo build --release --bin autotune-gui
-
-# Build CLI application
-echo -e "${BLUE}Building CLI application (autotune-cli)...${NC}"
-cargo build --release --bin autotune-cli
+if [ "$HEADLESS" = true ]; then
+    # Headless build: CLI only, no eframe/windowing system libraries needed
+    echo -e "${BLUE}Building headless CLI application (autotune-cli)...${NC}"
+    cargo build --release --no-default-features --features object-storage,profiling,scripting --bin autotune-cli
+    CARGO_FEATURES="--no-default-features --features object-storage,profiling,scripting"
+else
+    # Build GUI application
+    echo -e "${BLUE}Building GUI application (autotune-gui)...${NC}"
+    cargo build --release --bin autotune-gui
+
+    # Build CLI application
+    echo -e "${BLUE}Building CLI application (autotune-cli)...${NC}"
+    cargo build --release --bin autotune-cli
+    CARGO_FEATURES=""
+fi
 
 echo -e "${GREEN}✅ Build completed successfully!${NC}"
 echo ""
 
 # Run tests
 echo -e "${YELLOW}🧪 Running tests...${NC}"
-cargo test --release --quiet
+cargo test --release --quiet $CARGO_FEATURES
 echo -e "${GREEN}✅ All tests passed${NC}"
 echo ""
 
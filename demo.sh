@@ -34,7 +34,7 @@ fi
 # Generate test files if needed
 if [ ! -f "$INPUT_FILE" ]; then
     echo -e "${YELLOW}🎼 Generating test audio files...${NC}"
-    cargo run --example generate_test_wav --quiet
+    cargo run --bin autotune-cli --quiet -- generate
     echo -e "${GREEN}✅ Test files generated${NC}"
     echo ""
 fi
@@ -96,7 +96,7 @@ echo ""
 
 # Show available keys
 echo -e "${BLUE}Available Musical Keys:${NC}"
-../target/release/autotune-cli --list-keys
+../target/release/autotune-cli keys
 echo ""
 
 # Summary of generated files
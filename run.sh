@@ -17,7 +17,7 @@ fi
 # Generate test WAV files if they don't exist
 if [ ! -f "test_audio.wav" ] || [ ! -f "test_audio_off_pitch.wav" ]; then
     echo "🎼 Generating test WAV files..."
-    cargo run --example generate_test_wav
+    cargo run --bin autotune-cli -- generate
     echo "✅ Test files generated successfully!"
     echo ""
 fi
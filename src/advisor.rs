@@ -0,0 +1,176 @@
+use std::path::Path;
+
+use crate::classifier::{self, PitchTrack};
+use crate::decoder;
+use crate::keys::{self, KeyMatch};
+use crate::labels;
+use crate::resample;
+use crate::scale::Scale;
+use crate::ui::get_key_name;
+
+// A better-fitting key is only suggested when it fits clearly and by this much more
+const MIN_KEY_SCORE: f32 = 0.5;
+const KEY_MARGIN: f32 = 0.15;
+// Frames at least this loud (about -60 dBFS RMS) count as program, not background
+const AUDIBLE_RMS: f32 = 0.001;
+// Share of the program quieter than the pitch detector listens to before a take counts as
+// recorded too low
+const MAX_QUIET_SHARE: f32 = 0.5;
+// Share of the pitched frames ignored at the bottom, so a few stray detections don't count
+// as the lowest note
+const LOW_OUTLIERS: f32 = 0.02;
+// Periods of a pitch that need to fit in one FFT frame for it to be found reliably
+const PERIODS_PER_FRAME: f32 = 2.0;
+
+/// What a take was measured to be, to check the settings against before a render.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurements {
+    pub sample_rate: u32,
+    // Best-fitting keys, best first
+    pub keys: Vec<KeyMatch>,
+    // Lowest pitch sung, in Hz
+    pub lowest_f0: Option<f32>,
+    // Share of the audible frames too quiet for the pitch detector, 0.0 to 1.0
+    pub quiet_share: f32,
+}
+
+impl Measurements {
+    pub fn of_file(path: &Path) -> Result<Self, String> {
+        let sample_rate = decoder::probe(path)?.sample_rate;
+        Ok(Self::of_track(&keys::track_file(path)?, sample_rate))
+    }
+
+    pub fn of_track(track: &PitchTrack, sample_rate: u32) -> Self {
+        let mut f0s: Vec<f32> = track.frames.iter().filter_map(|frame| frame.f0).collect();
+        f0s.sort_by(f32::total_cmp);
+        let lowest_f0 = (!f0s.is_empty())
+            .then(|| f0s[((f0s.len() - 1) as f32 * LOW_OUTLIERS).round() as usize]);
+
+        let audible: Vec<_> =
+            track.frames.iter().filter(|frame| frame.rms >= AUDIBLE_RMS).collect();
+        let quiet = audible.iter().filter(|frame| frame.is_silent()).count();
+        let quiet_share = quiet as f32 / audible.len().max(1) as f32;
+
+        Self { sample_rate, keys: keys::rank_keys(track), lowest_f0, quiet_share }
+    }
+}
+
+/// The settings a render is about to use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Setup {
+    // Key corrected to, None when a locked note or a custom scale decides instead
+    pub key: Option<usize>,
+    pub fft_size: usize,
+    pub processing_rate: u32,
+}
+
+/// A setting that looks wrong for the take, and the change that would fix it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Advice {
+    pub message: String,
+    pub fix: Option<Fix>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fix {
+    SelectKey(usize),
+    // Resample to this rate for processing instead of working at the file's own
+    ProcessAt(u32),
+}
+
+/// Warnings about `setup` for a take measured as `measured`, empty when it looks right.
+pub fn check(setup: &Setup, measured: &Measurements) -> Vec<Advice> {
+    let mut advice = Vec::new();
+
+    if let (Some(key), Some(best)) = (setup.key, measured.keys.first()) {
+        let selected = measured.keys.iter().find(|m| m.key == key).map_or(-1.0, |m| m.score);
+        // A relative key has the same notes and corrects the same way
+        let same_notes = Scale::from_key(best.key) == Scale::from_key(key);
+        if best.score >= MIN_KEY_SCORE && best.score - selected >= KEY_MARGIN && !same_notes {
+            advice.push(Advice {
+                message: format!(
+                    "Selected {} but the take looks like {}",
+                    get_key_name(key),
+                    get_key_name(best.key)
+                ),
+                fix: Some(Fix::SelectKey(best.key)),
+            });
+        }
+    }
+
+    if measured.quiet_share > MAX_QUIET_SHARE {
+        advice.push(Advice {
+            message: format!(
+                "{:.0}% of the take is below the pitch detector's -40 dBFS threshold and won't \
+                 be corrected. Raise its level or normalize it first",
+                measured.quiet_share * 100.0
+            ),
+            fix: None,
+        });
+    }
+
+    if let Some(lowest) = measured.lowest_f0 {
+        let frame_secs = setup.fft_size as f32 / setup.processing_rate as f32;
+        let lowest_found = PERIODS_PER_FRAME / frame_secs;
+        if lowest < lowest_found {
+            // A lower rate fits more time into the same FFT size
+            let fix = resample::analysis_rate(measured.sample_rate)
+                .filter(|&rate| rate < setup.processing_rate)
+                .map(Fix::ProcessAt);
+            advice.push(Advice {
+                message: format!(
+                    "The take goes down to {} ({:.0} Hz), but a {} point FFT at {} Hz only \
+                     finds pitches down to {:.0} Hz",
+                    labels::note_name(classifier::note_number(lowest).round() as i32),
+                    lowest,
+                    setup.fft_size,
+                    setup.processing_rate,
+                    lowest_found
+                ),
+                fix,
+            });
+        }
+    }
+
+    advice
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn measured(keys: &[(usize, f32)]) -> Measurements {
+        Measurements {
+            sample_rate: 44100,
+            keys: keys.iter().map(|&(key, score)| KeyMatch { key, score }).collect(),
+            lowest_f0: Some(200.0),
+            quiet_share: 0.1,
+        }
+    }
+
+    #[test]
+    fn test_settings_are_checked_against_the_take() {
+        let setup = Setup { key: Some(0), fft_size: 1024, processing_rate: 44100 };
+        assert!(check(&setup, &measured(&[(0, 0.8), (12, 0.7)])).is_empty());
+
+        // D Major fits much better than the selected C Major
+        let advice = check(&setup, &measured(&[(2, 0.85), (0, 0.4)]));
+        assert_eq!(advice.len(), 1);
+        assert_eq!(advice[0].fix, Some(Fix::SelectKey(2)));
+        // A Minor has C Major's notes, so there is nothing to switch
+        assert!(check(&setup, &measured(&[(12, 0.85), (0, 0.4)])).is_empty());
+        // Nor is there with a locked note
+        let locked = Setup { key: None, ..setup };
+        assert!(check(&locked, &measured(&[(2, 0.85), (0, 0.4)])).is_empty());
+
+        let quiet = Measurements { quiet_share: 0.8, ..measured(&[]) };
+        assert_eq!(check(&setup, &quiet)[0].fix, None);
+
+        // 1024 points at 96 kHz are too short for a low voice, which 48 kHz fixes
+        let low = Measurements { sample_rate: 96000, lowest_f0: Some(98.0), ..measured(&[]) };
+        let native = Setup { processing_rate: 96000, ..setup };
+        assert_eq!(check(&native, &low)[0].fix, Some(Fix::ProcessAt(48000)));
+        let adapted = Setup { processing_rate: 48000, ..setup };
+        assert!(check(&adapted, &low).is_empty());
+    }
+}
@@ -0,0 +1,386 @@
+use crate::classifier::{self, PitchTrack};
+
+// Level envelope the timing of the two takes is matched on
+const ENVELOPE_HOP_SECS: f32 = 0.005;
+const ENVELOPE_WINDOW_SECS: f32 = 0.02;
+// Level floor, so room noise doesn't take part in the match
+const FLOOR_DB: f32 = -80.0;
+// Cost of each step off the diagonal, so the timing only moves where the levels ask for it
+const STEP_PENALTY: f32 = 0.05;
+// Envelope hops averaged on each side of the timing curve (~40 ms)
+const LAG_SMOOTHING: usize = 8;
+// Grains the double is rebuilt from, overlapping by half
+const GRAIN_SECS: f32 = 0.025;
+// Grains averaged on each side of the pitch correction curve
+const PITCH_SMOOTHING: usize = 2;
+
+/// How far a double may be moved to match the lead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlignLimits {
+    pub max_shift_secs: f32,
+    // Larger differences are left alone as a different note; 0 disables pitch matching
+    pub max_pitch_cents: f32,
+}
+
+impl Default for AlignLimits {
+    fn default() -> Self {
+        Self { max_shift_secs: 0.06, max_pitch_cents: 50.0 }
+    }
+}
+
+/// What was done to the double.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AlignReport {
+    // Positive where the double was late
+    pub mean_shift_secs: f32,
+    pub max_shift_secs: f32,
+    pub pitch_matched_secs: f32,
+    // Mean size of the pitch corrections that were made
+    pub mean_cents: f32,
+}
+
+/// Tighten `double` against `lead`, both mono at `sample_rate`: its timing is warped to
+/// follow the lead's level envelope, and its pitch is pulled onto the lead's wherever both
+/// are voiced and within `limits.max_pitch_cents`. The result is as long as `double`.
+pub fn align_double(
+    lead: &[f32],
+    double: &[f32],
+    sample_rate: u32,
+    limits: &AlignLimits,
+) -> (Vec<f32>, AlignReport) {
+    let rate = sample_rate as f32;
+    let mut report = AlignReport::default();
+    if double.is_empty() || lead.is_empty() {
+        return (double.to_vec(), report);
+    }
+
+    // Timing: lag of the double behind the lead, per envelope hop of the lead
+    let band = (limits.max_shift_secs.max(0.0) / ENVELOPE_HOP_SECS).round() as usize;
+    let path = warp_path(&level_envelope(lead, rate), &level_envelope(double, rate), band);
+    let lags = average(&path, LAG_SMOOTHING);
+    let lag_secs = |secs: f32| {
+        let position = (secs / ENVELOPE_HOP_SECS).max(0.0);
+        let index = (position as usize).min(lags.len() - 1);
+        let next = lags.get(index + 1).unwrap_or(&lags[index]);
+        (lags[index] + (next - lags[index]) * position.fract().min(1.0)) * ENVELOPE_HOP_SECS
+    };
+    report.mean_shift_secs = lags.iter().sum::<f32>() / lags.len() as f32 * ENVELOPE_HOP_SECS;
+    report.max_shift_secs =
+        lags.iter().fold(0.0f32, |max, lag| max.max(lag.abs())) * ENVELOPE_HOP_SECS;
+
+    let hop = grain_hop(rate);
+    let grains = grain_count(double.len(), rate);
+
+    // Pitch: correction in cents per grain, where both takes are voiced and close enough
+    let cents: Vec<f32> = if limits.max_pitch_cents > 0.0 {
+        let lead_track = classifier::pitch_track(lead, rate);
+        let double_track = classifier::pitch_track(double, rate);
+        let f0 = |track: &PitchTrack, secs: f32| track.at(secs).and_then(|frame| frame.f0);
+        (0..grains)
+            .map(|k| {
+                let secs = (k * hop) as f32 / rate;
+                match (f0(&lead_track, secs), f0(&double_track, secs + lag_secs(secs))) {
+                    (Some(target), Some(sung)) => {
+                        let cents = 1200.0 * (target / sung).log2();
+                        if cents.abs() <= limits.max_pitch_cents {
+                            cents
+                        } else {
+                            0.0
+                        }
+                    }
+                    _ => 0.0,
+                }
+            })
+            .collect()
+    } else {
+        vec![0.0; grains]
+    };
+    let matched: Vec<f32> = cents.iter().copied().filter(|&c| c != 0.0).collect();
+    report.pitch_matched_secs = (matched.len() * hop) as f32 / rate;
+    if !matched.is_empty() {
+        report.mean_cents = matched.iter().map(|c| c.abs()).sum::<f32>() / matched.len() as f32;
+    }
+    let cents = average(&cents, PITCH_SMOOTHING);
+
+    (resynthesize(double, rate, &cents, lag_secs), report)
+}
+
+/// Samples between the grains `resynthesize` rebuilds a signal at `sample_rate` from.
+pub fn grain_hop(sample_rate: f32) -> usize {
+    ((GRAIN_SECS * sample_rate) as usize / 2).max(2)
+}
+
+/// Grains that cover `len` samples at `sample_rate`, the length of the curves
+/// `resynthesize` takes.
+pub fn grain_count(len: usize, sample_rate: f32) -> usize {
+    len / grain_hop(sample_rate) + 2
+}
+
+/// Rebuild `samples` from overlapping grains, one per `grain_hop`: grain `k` is pitch
+/// shifted by `cents[k]` and read `lag_secs` later than where it lands. The result is as
+/// long as `samples`.
+pub fn resynthesize(
+    samples: &[f32],
+    sample_rate: f32,
+    cents: &[f32],
+    lag_secs: impl Fn(f32) -> f32,
+) -> Vec<f32> {
+    let hop = grain_hop(sample_rate);
+    let grain = hop * 2;
+
+    // WSOLA: each grain is read around its warped position at its pitch ratio, nudged to
+    // line up with the natural continuation of the previous one so the overlap doesn't
+    // cancel out
+    let window: Vec<f32> = (0..grain)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / grain as f32).cos())
+        .collect();
+    let tolerance = (hop / 4) as isize;
+    let half = (grain / 2) as f32;
+    let mut output = vec![0.0; (cents.len() + 1) * hop];
+    let mut continuation: Option<f32> = None;
+    for (k, &cents) in cents.iter().enumerate() {
+        let center = k * hop;
+        let ratio = 2f32.powf(cents / 1200.0);
+        let target = center as f32 + lag_secs(center as f32 / sample_rate) * sample_rate;
+        let source = match continuation {
+            Some(natural) => {
+                // Normalized cross-correlation over the overlap
+                let similarity = |offset: isize| {
+                    let (mut cross, mut energy_a, mut energy_b) = (0.0, 0.0, 0.0);
+                    for i in (0..hop).step_by(2) {
+                        let i = i as f32 - half;
+                        let a = sample_at(samples, target + offset as f32 + i);
+                        let b = sample_at(samples, natural + i);
+                        cross += a * b;
+                        energy_a += a * a;
+                        energy_b += b * b;
+                    }
+                    let norm = (energy_a * energy_b).sqrt();
+                    if norm > 0.0 { cross / norm } else { 0.0 }
+                };
+                // Ties, like silence, keep the grain where the timing curve puts it
+                let mut best = (0, similarity(0));
+                for offset in (-tolerance..=tolerance).step_by(2) {
+                    let score = similarity(offset);
+                    if score > best.1 {
+                        best = (offset, score);
+                    }
+                }
+                target + best.0 as f32
+            }
+            None => target,
+        };
+
+        // Output index shifted by `half`, so the first grain can start before the signal
+        for (i, &w) in window.iter().enumerate() {
+            output[center + i] += sample_at(samples, source + (i as f32 - half) * ratio) * w;
+        }
+        continuation = Some(source + hop as f32 * ratio);
+    }
+
+    output.drain(..grain / 2);
+    output.truncate(samples.len());
+    output
+}
+
+// Linearly interpolated sample, zero outside the signal
+fn sample_at(samples: &[f32], position: f32) -> f32 {
+    if position < 0.0 {
+        return 0.0;
+    }
+    let index = position as usize;
+    match (samples.get(index), samples.get(index + 1)) {
+        (Some(&a), Some(&b)) => a + (b - a) * position.fract(),
+        (Some(&a), None) => a,
+        _ => 0.0,
+    }
+}
+
+// Level in dB per envelope hop, relative to the loudest hop and scaled so the floor is -1
+fn level_envelope(samples: &[f32], sample_rate: f32) -> Vec<f32> {
+    let hop = ((ENVELOPE_HOP_SECS * sample_rate) as usize).max(1);
+    let half_window = ((ENVELOPE_WINDOW_SECS * sample_rate) as usize / 2).max(1);
+    let mut energy = vec![0.0f64; samples.len() + 1];
+    for (i, &sample) in samples.iter().enumerate() {
+        energy[i + 1] = energy[i] + (sample as f64) * (sample as f64);
+    }
+
+    let levels: Vec<f32> = (0..samples.len().div_ceil(hop))
+        .map(|i| {
+            let start = (i * hop).saturating_sub(half_window);
+            let end = (i * hop + half_window).min(samples.len());
+            let rms = ((energy[end] - energy[start]) / (end - start) as f64).sqrt() as f32;
+            (20.0 * rms.log10()).max(FLOOR_DB)
+        })
+        .collect();
+    let loudest = levels.iter().copied().fold(FLOOR_DB, f32::max);
+    levels.iter().map(|level| (level - loudest) / -FLOOR_DB).collect()
+}
+
+// Dynamic time warping of two envelopes, at most `band` hops off the diagonal. Returns the
+// lag (double hop - lead hop) of each lead hop along the cheapest path.
+fn warp_path(lead: &[f32], double: &[f32], band: usize) -> Vec<f32> {
+    const DIAGONAL: u8 = 0;
+    const LEAD_STEP: u8 = 1;
+    const DOUBLE_STEP: u8 = 2;
+
+    let width = 2 * band + 1;
+    let mut cost = vec![f32::INFINITY; lead.len() * width];
+    let mut from = vec![DIAGONAL; lead.len() * width];
+    // Matching onsets counts as much as matching levels
+    let slope = |envelope: &[f32], i: usize| {
+        if i > 0 {
+            envelope[i] - envelope[i - 1]
+        } else {
+            0.0
+        }
+    };
+    let double_at = |i: usize, d: usize| (i + d).checked_sub(band).filter(|&j| j < double.len());
+
+    for i in 0..lead.len() {
+        for d in 0..width {
+            let Some(j) = double_at(i, d) else {
+                continue;
+            };
+            let local =
+                (lead[i] - double[j]).abs() + 2.0 * (slope(lead, i) - slope(double, j)).abs();
+            let mut best = (if i == 0 && j == 0 { 0.0 } else { f32::INFINITY }, DIAGONAL);
+            let mut consider = |previous: f32, step: u8| {
+                if previous < best.0 {
+                    best = (previous, step);
+                }
+            };
+            if i > 0 {
+                consider(cost[(i - 1) * width + d], DIAGONAL);
+                if d + 1 < width {
+                    consider(cost[(i - 1) * width + d + 1] + STEP_PENALTY, LEAD_STEP);
+                }
+            }
+            if d > 0 {
+                consider(cost[i * width + d - 1] + STEP_PENALTY, DOUBLE_STEP);
+            }
+            cost[i * width + d] = best.0 + local;
+            from[i * width + d] = best.1;
+        }
+    }
+
+    // The takes may end at different points within the band
+    let last = lead.len() - 1;
+    let mut d = (0..width)
+        .min_by(|&a, &b| cost[last * width + a].total_cmp(&cost[last * width + b]))
+        .unwrap_or(band);
+    let (mut sums, mut counts) = (vec![0.0; lead.len()], vec![0; lead.len()]);
+    let mut i = last;
+    loop {
+        sums[i] += d as f32 - band as f32;
+        counts[i] += 1;
+        match from[i * width + d] {
+            DIAGONAL if i > 0 => i -= 1,
+            LEAD_STEP if i > 0 => {
+                i -= 1;
+                d += 1;
+            }
+            DOUBLE_STEP if d > 0 => d -= 1,
+            _ => break,
+        }
+    }
+    sums.iter().zip(&counts).map(|(sum, &count)| sum / count.max(1) as f32).collect()
+}
+
+/// Moving average over `radius` values on each side.
+pub fn average(values: &[f32], radius: usize) -> Vec<f32> {
+    (0..values.len())
+        .map(|i| {
+            let window = &values[i.saturating_sub(radius)..(i + radius + 1).min(values.len())];
+            window.iter().sum::<f32>() / window.len() as f32
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const RATE: u32 = 44_100;
+
+    // Notes of different lengths with gaps, so the timing is unambiguous
+    fn phrase(frequency: f32, delay_secs: f32) -> Vec<f32> {
+        let notes = [(0.2, 0.3), (0.7, 0.5), (1.4, 0.2), (1.8, 0.6)];
+        let delay = (delay_secs * RATE as f32) as usize;
+        let mut samples = vec![0.0; (2.6 * RATE as f32) as usize];
+        for (start, length) in notes {
+            let start = (start * RATE as f32) as usize + delay;
+            for i in 0..(length * RATE as f32) as usize {
+                let t = i as f32 / RATE as f32;
+                samples[start + i] = 0.5 * (2.0 * PI * frequency * t).sin();
+            }
+        }
+        samples
+    }
+
+    // Lag of `samples` behind `reference` in envelope hops
+    fn residual_lag(reference: &[f32], samples: &[f32]) -> isize {
+        let (a, b) = (level_envelope(reference, RATE as f32), level_envelope(samples, RATE as f32));
+        (-20isize..=20)
+            .min_by(|&x, &y| {
+                let cost = |lag: isize| {
+                    (20..a.len() - 20)
+                        .map(|i| (a[i] - b[(i as isize + lag) as usize]).abs())
+                        .sum::<f32>()
+                };
+                cost(x).total_cmp(&cost(y))
+            })
+            .unwrap()
+    }
+
+    fn frequency(samples: &[f32]) -> f32 {
+        let crossings = samples.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count();
+        crossings as f32 / 2.0 / (samples.len() as f32 / RATE as f32)
+    }
+
+    #[test]
+    fn test_late_double_is_pulled_into_time() {
+        let lead = phrase(220.0, 0.0);
+        let double = phrase(220.0, 0.03);
+        assert_eq!(residual_lag(&lead, &double), 6);
+
+        let (aligned, report) = align_double(&lead, &double, RATE, &AlignLimits::default());
+        assert_eq!(aligned.len(), double.len());
+        assert!(residual_lag(&lead, &aligned).abs() <= 1);
+        assert!((report.mean_shift_secs - 0.03).abs() < 0.01, "{:?}", report);
+        assert!(report.max_shift_secs <= 0.06 + 1e-6);
+
+        // Held to the shift limit
+        let limits = AlignLimits { max_shift_secs: 0.01, ..Default::default() };
+        let (_, report) = align_double(&lead, &double, RATE, &limits);
+        assert!(report.max_shift_secs <= 0.01 + 1e-6, "{:?}", report);
+    }
+
+    #[test]
+    fn test_sharp_double_is_matched_within_limit() {
+        let lead = phrase(220.0, 0.0);
+        let sharp = phrase(220.0 * 2f32.powf(30.0 / 1200.0), 0.0);
+        // Middle of the longest note
+        let note = (1.9 * RATE as f32) as usize..(2.3 * RATE as f32) as usize;
+
+        let (aligned, report) = align_double(&lead, &sharp, RATE, &AlignLimits::default());
+        assert!((frequency(&aligned[note.clone()]) - 220.0).abs() < 1.0);
+        assert!((report.mean_cents - 30.0).abs() < 5.0, "{:?}", report);
+
+        // A bigger difference than the limit is left alone
+        let limits = AlignLimits { max_pitch_cents: 20.0, ..Default::default() };
+        let (untouched, report) = align_double(&lead, &sharp, RATE, &limits);
+        assert!((frequency(&untouched[note.clone()]) - frequency(&sharp[note])).abs() < 1.0);
+        assert_eq!(report.pitch_matched_secs, 0.0);
+    }
+
+    #[test]
+    fn test_identical_takes_are_unchanged() {
+        let lead = phrase(330.0, 0.0);
+        let (aligned, report) = align_double(&lead, &lead, RATE, &AlignLimits::default());
+        assert_eq!(report.max_shift_secs, 0.0);
+        let error = aligned.iter().zip(&lead).map(|(a, b)| (a - b).abs()).fold(0.0, f32::max);
+        assert!(error < 1e-3, "{}", error);
+    }
+}
@@ -0,0 +1,106 @@
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const APP_DIR_NAME: &str = "desktop-autotune-app";
+
+// Dropping this file next to the executable turns on portable mode
+pub const PORTABLE_MARKER: &str = "autotune-portable";
+pub const PORTABLE_FLAG: &str = "--portable";
+const PORTABLE_DATA_DIR: &str = "autotune-data";
+
+// Where finished renders are found again, shared by the GUI and CLI
+pub const RENDER_INDEX_FILE: &str = "renders.json";
+
+/// Where presets, history, and settings are stored.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigLocation {
+    pub dir: PathBuf,
+    pub portable: bool,
+}
+
+impl ConfigLocation {
+    /// Use the portable directory when `portable_flag` is set or the marker file exists
+    /// next to the executable, otherwise the per-user configuration directory.
+    pub fn locate(portable_flag: bool) -> Self {
+        let exe_dir =
+            std::env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+
+        if let Some(exe_dir) = exe_dir {
+            if portable_flag || exe_dir.join(PORTABLE_MARKER).exists() {
+                return Self { dir: exe_dir.join(PORTABLE_DATA_DIR), portable: true };
+            }
+        }
+
+        let dir = user_config_dir().unwrap_or_else(|| PathBuf::from(".")).join(APP_DIR_NAME);
+        Self { dir, portable: false }
+    }
+
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> Option<T> {
+        let path = self.file(name);
+        let contents = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                log::warn!("Ignoring unreadable {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    pub fn save<T: Serialize>(&self, name: &str, value: &T) -> io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_string_pretty(value).map_err(io::Error::other)?;
+
+        // Write then rename so a crash never leaves a half-written file behind
+        let path = self.file(name);
+        let temp_path = path.with_extension("tmp");
+        std::fs::write(&temp_path, json)?;
+        std::fs::rename(&temp_path, &path)
+    }
+}
+
+fn user_config_dir() -> Option<PathBuf> {
+    let home = || std::env::var_os("HOME").map(PathBuf::from);
+
+    if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        home().map(|home| home.join("Library").join("Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| home().map(|home| home.join(".config")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_portable_flag_uses_executable_dir() {
+        let location = ConfigLocation::locate(true);
+        let exe_dir = std::env::current_exe().unwrap().parent().unwrap().to_path_buf();
+        assert!(location.portable);
+        assert_eq!(location.dir, exe_dir.join(PORTABLE_DATA_DIR));
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = std::env::temp_dir().join("autotune-config-test");
+        let location = ConfigLocation { dir: dir.clone(), portable: true };
+
+        location.save("values.json", &vec![1, 2, 3]).unwrap();
+        assert_eq!(location.load::<Vec<i32>>("values.json"), Some(vec![1, 2, 3]));
+        assert_eq!(location.load::<Vec<i32>>("missing.json"), None);
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
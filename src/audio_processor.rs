@@ -1,8 +1,33 @@
-use hound::{WavReader, WavWriter};
-use std::path::PathBuf;
+use hound::WavSpec;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::Sender;
 use std::time::Instant;
-use synthphone_vocals::{AutotuneConfig, AutotuneState, MusicalSettings, process_autotune};
+use synthphone_vocals::{AutotuneConfig, MusicalSettings};
+
+use crate::dynamics::{Compressor, Limit, Meters};
+use crate::effects::ChainOrder;
+use crate::energy::EnergyCurve;
+use crate::engine::{self, Padding, Window};
+use crate::equalizer::Equalizer;
+use crate::fingerprint::{Fingerprint, Fingerprinter};
+use crate::harmony::Interval;
+use crate::labels::Label;
+use crate::loudness::{self, Normalization};
+use crate::midi::Melody;
+use crate::monitor::MonitorFeed;
+use crate::pcm::BitDepth;
+use crate::pipeline::{self, OutputLength, PitchCurves, RenderSettings, Rendered};
+use crate::pitch::{F0Range, PitchAlgorithm};
+use crate::prefilter::Gate;
+use crate::routing::{MonoCheck, Route};
+use crate::scale::Scale;
+use crate::sections::Section;
+use crate::tint::Spectrum;
+use crate::verify::Verification;
+use crate::{decoder, keys, paths, pcm, resample};
 
 #[derive(Debug, Clone)]
 pub enum ProcessingProgress {
@@ -12,275 +37,428 @@ pub enum ProcessingProgress {
 
 #[derive(Debug, Clone)]
 pub enum ProcessingResult {
-    Success { samples_processed: usize, duration_ms: f64 },
+    Success {
+        samples_processed: usize,
+        duration_ms: f64,
+        verification: Verification,
+        // Integrated loudness of the input, in LUFS; the output's is in `verification`
+        input_loudness: Option<f32>,
+        // Input and output pitch over the render
+        pitch: Option<PitchCurves>,
+        // How a stereo render holds up in mono
+        mono: Option<MonoCheck>,
+        // Fallbacks and warnings, to export for a DAW
+        markers: Vec<Label>,
+        // Layers written next to the output
+        stems: Vec<PathBuf>,
+        // Unnormalized float render written next to the output
+        master: Option<PathBuf>,
+    },
     Error(String),
+    // Stopped by the user, with whatever had been rendered by then
+    Cancelled(Option<PartialRender>),
+    // Not rendered, as an identical earlier render was found to offer instead
+    Duplicate {
+        render: PathBuf,
+        fingerprint: Fingerprint,
+    },
+}
+
+/// Audio rendered before a job was stopped, which can still be kept as a shorter file.
+/// It waits in a scratch file until the last copy of this is dropped.
+#[derive(Debug, Clone)]
+pub struct PartialRender {
+    pub render: Arc<Rendered>,
+    pub spec: WavSpec,
+    pub normalization: Normalization,
+    pub limit: Option<Limit>,
+}
+
+impl PartialRender {
+    pub fn duration_secs(&self) -> f32 {
+        self.render.frames as f32 / self.spec.sample_rate as f32
+    }
+
+    /// Normalize and write the partial audio as a complete WAV file.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        self.render.write(path, self.spec, self.normalization, self.limit.as_ref()).map(|_| ())
+    }
+}
+
+/// Lets the UI thread follow and interrupt a render.
+#[derive(Default)]
+pub struct RenderControl {
+    pub cancel: Arc<AtomicBool>,
+    // Receives processed audio as soon as it is final
+    pub monitor: Option<MonitorFeed>,
+    // How far the compressor and the limiter are turning the output down
+    pub meters: Arc<Meters>,
+}
+
+/// How a render runs, as opposed to what it does to the audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub threads: usize,
+    // Window the corrected frames are crossfaded with
+    pub window: Window,
+    // What the frames past the end of the input are filled out with
+    pub padding: Padding,
+    // How the pitch the note is chosen from is found, and the frequencies it is looked for in
+    pub pitch_algorithm: PitchAlgorithm,
+    pub f0_range: Option<F0Range>,
+    // Resample files outside the preferred rates for processing, and back afterwards
+    pub adapt_sample_rate: bool,
+    // Correct vocal fry instead of passing it through
+    pub correct_fry: bool,
+    // Keep less of the correction where the pitch is found less surely
+    pub confidence_strength: bool,
+    // How readily unvoiced frames pass through uncorrected, 0.0 for never
+    pub unvoiced_sensitivity: f32,
+    // Reference vocal whose tone the output is matched to, and how far (0.0 to 1.0)
+    pub tint_reference: Option<PathBuf>,
+    pub tint_amount: f32,
+    // Rate the output is written at, the input's own when None
+    pub output_rate: Option<u32>,
+    // Frames the output is written with
+    pub length: OutputLength,
+    // Sample format and channel count of the output, the input's when None
+    pub output_depth: Option<BitDepth>,
+    pub output_channels: Option<u16>,
+    // Write the dry, corrected, and harmony layers next to the output too
+    pub stems: bool,
+    // Write the unnormalized float render next to the output too
+    pub float_master: bool,
+    // Harmony voices sung with the lead, and the level they are mixed in at
+    pub harmony: Vec<Interval>,
+    pub harmony_level: f32,
+    // Levels the lead and harmony voices go into each channel of stereo output at
+    pub routing: Vec<Route>,
+    // Stretches corrected with musical settings of their own
+    pub sections: Vec<Section>,
+    // Correct loud lines harder than quiet tails, along this curve
+    pub energy: Option<EnergyCurve>,
+    // Order of the effects around the correction, and the settings of those that are on
+    pub chain: ChainOrder,
+    pub high_pass_hz: Option<f32>,
+    pub gate: Option<Gate>,
+    pub eq: Option<Equalizer>,
+    pub compressor: Option<Compressor>,
+    // How the level of the output is set, and the ceiling the limiter holds its peaks
+    // under, in dBFS, with the limiter on
+    pub normalization: Normalization,
+    pub limit_db: Option<f32>,
+}
+
+impl RenderOptions {
+    /// Rate a file at `sample_rate` is processed at.
+    pub fn processing_rate(&self, sample_rate: u32) -> u32 {
+        match resample::analysis_rate(sample_rate) {
+            Some(rate) if self.adapt_sample_rate => rate,
+            _ => sample_rate,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct AudioProcessor;
 
 impl AudioProcessor {
+    /// Read up to `max_seconds` of an input file as mono f32, returning the samples and sample rate.
+    pub fn read_mono_excerpt(
+        input_path: &Path,
+        max_seconds: f32,
+    ) -> Result<(Vec<f32>, u32), String> {
+        let sample_rate = decoder::probe(input_path)?.sample_rate;
+        let max_frames = (max_seconds * sample_rate as f32) as usize;
+        let (mono, _) = decoder::read_mono(input_path, max_frames)?;
+
+        Ok((mono, sample_rate))
+    }
+
+    /// What a render of `input_path` to `output_path` with these settings is made of, to find
+    /// an identical earlier one by. None when it writes side files an earlier render may not
+    /// have left, or an input can't be read.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fingerprint(
+        input_path: &Path,
+        output_path: &Path,
+        config: &AutotuneConfig,
+        settings: &MusicalSettings,
+        scale: Option<Scale>,
+        melody: Option<&Melody>,
+        a4: f32,
+        regions: &[Label],
+        options: &RenderOptions,
+    ) -> Option<Fingerprint> {
+        if options.stems || options.float_master {
+            return None;
+        }
+        let mut fingerprinter = Fingerprinter::new();
+        fingerprinter.file(input_path).ok()?;
+        if let Some(reference) = &options.tint_reference {
+            fingerprinter.file(reference).ok()?;
+        }
+        fingerprinter.settings(&(
+            output_path.extension(),
+            (config.fft_size, config.hop_size, config.sample_rate),
+            (config.pitch_correction_strength, config.transition_speed),
+            (settings.key, settings.note, settings.octave, settings.formant),
+            scale,
+            melody,
+            a4,
+            regions,
+        ));
+        // The threads don't change the audio
+        fingerprinter.settings(&RenderOptions { threads: 0, ..options.clone() });
+        Some(fingerprinter.finish())
+    }
+
+    /// Correct `input_path` into `output_path`, to the notes of `melody` or `scale` if given,
+    /// tuned to `a4`, and only inside `regions` when there are any. Point labels in `regions`
+    /// that name a key change the key from there on.
+    #[allow(clippy::too_many_arguments)]
     pub fn process_file(
-        input_path: &PathBuf,
-        output_path: &PathBuf,
+        input_path: &Path,
+        output_path: &Path,
         config: AutotuneConfig,
         settings: MusicalSettings,
+        scale: Option<Scale>,
+        melody: Option<Melody>,
+        a4: f32,
+        regions: Vec<Label>,
+        options: RenderOptions,
+        mut control: RenderControl,
         progress_sender: Sender<ProcessingProgress>,
     ) -> ProcessingResult {
         let start_time = Instant::now();
+        if let Err(e) = engine::validate_frames(config.fft_size, config.hop_size) {
+            return ProcessingResult::Error(e);
+        }
 
         // Send initial status
         let _ =
             progress_sender.send(ProcessingProgress::Status("Opening input file...".to_string()));
 
-        // Open input WAV file
-        let mut reader = match WavReader::open(input_path) {
-            Ok(reader) => reader,
-            Err(e) => return ProcessingResult::Error(format!("Failed to open input file: {}", e)),
+        // Open input file (WAV, or a compressed format decoded to 16-bit output)
+        let info = match decoder::probe(input_path) {
+            Ok(info) => info,
+            Err(e) => return ProcessingResult::Error(e),
         };
 
-        let spec = reader.spec();
-        log::info!("Input file spec: {:?}", spec);
+        let spec = WavSpec {
+            sample_rate: options.output_rate.unwrap_or(info.sample_rate),
+            channels: options.output_channels.unwrap_or(info.channels),
+            ..info.output_spec()
+        };
+        let spec = options.output_depth.map_or(spec, |depth| depth.apply(spec));
+        log::info!("Input file info: {:?}", info);
 
         // Validate audio format
-        if spec.channels != 1 && spec.channels != 2 {
-            return ProcessingResult::Error(format!(
-                "Unsupported channel count: {}. Only mono and stereo are supported.",
-                spec.channels
-            ));
+        if let Err(e) = info.check_supported() {
+            return ProcessingResult::Error(e);
         }
 
-        if spec.bits_per_sample != 16 && spec.bits_per_sample != 24 && spec.bits_per_sample != 32 {
-            return ProcessingResult::Error(format!(
-                "Unsupported bit depth: {}. Only 16, 24, and 32-bit are supported.",
-                spec.bits_per_sample
-            ));
-        }
-
-        // Read all samples
-        let _ =
-            progress_sender.send(ProcessingProgress::Status("Reading audio data...".to_string()));
-
-        let samples: Result<Vec<i32>, _> = reader.samples().collect();
-        let samples = match samples {
-            Ok(samples) => samples,
-            Err(e) => return ProcessingResult::Error(format!("Failed to read samples: {}", e)),
-        };
-
-        let total_samples = samples.len();
-        log::info!("Read {} samples", total_samples);
-
-        // Convert to f32 and handle channels
-        let _ = progress_sender
-            .send(ProcessingProgress::Status("Converting audio format...".to_string()));
-
-        let mut audio_data = Vec::with_capacity(total_samples);
-        let scale_factor = match spec.bits_per_sample {
-            16 => 1.0 / 32768.0,
-            24 => 1.0 / 8388608.0,
-            32 => 1.0 / 2147483648.0,
-            _ => return ProcessingResult::Error("Unsupported bit depth".to_string()),
+        let tint = match &options.tint_reference {
+            Some(path) => {
+                let _ = progress_sender
+                    .send(ProcessingProgress::Status("Measuring reference tone...".to_string()));
+                match Spectrum::measure_file(path) {
+                    Ok(spectrum) => Some(spectrum),
+                    Err(e) => return ProcessingResult::Error(e),
+                }
+            }
+            None => None,
         };
 
-        for sample in samples {
-            audio_data.push(sample as f32 * scale_factor);
+        // Outside the preferred rates, process at an analysis rate and resample back after
+        let rate = options.processing_rate(info.sample_rate);
+        if rate != info.sample_rate {
+            let _ = progress_sender.send(ProcessingProgress::Status(format!(
+                "Resampling {}Hz -> {}Hz while processing...",
+                info.sample_rate, rate
+            )));
         }
-
-        // Convert stereo to mono if needed (simple average)
-        let mono_data = if spec.channels == 2 {
-            let _ = progress_sender
-                .send(ProcessingProgress::Status("Converting stereo to mono...".to_string()));
-
-            let mut mono = Vec::with_capacity(audio_data.len() / 2);
-            for i in (0..audio_data.len()).step_by(2) {
-                let left = audio_data[i];
-                let right = audio_data.get(i + 1).copied().unwrap_or(0.0);
-                mono.push((left + right) * 0.5);
-            }
-            mono
-        } else {
-            audio_data
+        let settings = RenderSettings {
+            config,
+            window: options.window,
+            padding: options.padding,
+            pitch_algorithm: options.pitch_algorithm,
+            f0_range: options.f0_range,
+            musical: settings,
+            scale,
+            key_changes: keys::key_changes(&regions),
+            sections: options.sections.clone(),
+            melody,
+            processing_rate: rate,
+            output_rate: options.output_rate,
+            length: options.length,
+            threads: options.threads,
+            regions,
+            correct_fry: options.correct_fry,
+            confidence_strength: options.confidence_strength,
+            unvoiced_sensitivity: options.unvoiced_sensitivity,
+            a4,
+            energy: options.energy,
+            chain: options.chain,
+            high_pass_hz: options.high_pass_hz,
+            gate: options.gate,
+            eq: options.eq,
+            compressor: options.compressor,
+            meters: Some(control.meters.clone()),
+            stems: options.stems,
+            harmony: options.harmony.clone(),
+            harmony_level: options.harmony_level,
+            // A mono output has nowhere to route to
+            routing: if spec.channels == 2 {
+                options.routing.clone()
+            } else {
+                Vec::new()
+            },
         };
 
-        let mono_samples = mono_data.len();
-        log::info!("Processing {} mono samples", mono_samples);
-
-        // Update config with correct sample rate
-        let mut processing_config = config;
-        processing_config.sample_rate = spec.sample_rate as f32;
-
-        // Create autotune state
-        let _ = progress_sender
-            .send(ProcessingProgress::Status("Initializing autotune...".to_string()));
-
-        let mut autotune_state = AutotuneState::new(processing_config);
-
-        // Process audio in chunks
+        // Decode, process, and spool the result a block at a time
         let _ = progress_sender.send(ProcessingProgress::Status("Processing audio...".to_string()));
-
-        let fft_size = processing_config.fft_size;
-        let hop_size = processing_config.hop_size;
-
-        let mut processed_audio = Vec::new();
-        let mut chunk_index = 0;
-        let total_chunks = (mono_samples + fft_size - 1) / hop_size;
-
-        // Process overlapping frames
-        let mut input_buffer = vec![0.0f32; fft_size];
-        let mut output_buffer = vec![0.0f32; fft_size];
-
-        let mut sample_pos = 0;
-        while sample_pos + fft_size <= mono_samples {
-            // Fill input buffer
-            input_buffer.copy_from_slice(&mono_data[sample_pos..sample_pos + fft_size]);
-
-            // Process with autotune
-            match process_autotune(
-                &input_buffer,
-                &mut output_buffer,
-                &mut autotune_state,
-                &settings,
-            ) {
-                Ok(_) => {
-                    // Add processed samples to output (overlap-add)
-                    if processed_audio.len() < sample_pos + fft_size {
-                        processed_audio.resize(sample_pos + fft_size, 0.0);
-                    }
-
-                    for (i, &sample) in output_buffer.iter().enumerate() {
-                        processed_audio[sample_pos + i] += sample;
-                    }
-                }
-                Err(e) => {
-                    log::warn!("Autotune processing error at sample {}: {:?}", sample_pos, e);
-
-                    // Fallback to original audio for this chunk
-                    if processed_audio.len() < sample_pos + fft_size {
-                        processed_audio.resize(sample_pos + fft_size, 0.0);
-                    }
-
-                    for (i, &sample) in input_buffer.iter().enumerate() {
-                        processed_audio[sample_pos + i] += sample;
-                    }
-                }
+        control.meters.reset();
+        let limit = options
+            .limit_db
+            .map(|ceiling_db| Limit { ceiling_db, meters: Some(control.meters.clone()) });
+
+        let mut stopped = false;
+        let rendered = pipeline::render(input_path, output_path, &settings, |progress| {
+            if let Some(monitor) = &mut control.monitor {
+                monitor.push(progress.finished);
             }
-
-            // Update progress
-            chunk_index += 1;
-            let progress = chunk_index as f32 / total_chunks as f32;
-            let _ = progress_sender.send(ProcessingProgress::Progress(progress));
-
-            // Advance by hop size for overlap
-            sample_pos += hop_size;
-        }
-
-        // Handle any remaining samples
-        if sample_pos < mono_samples {
-            let remaining = mono_samples - sample_pos;
-            input_buffer.fill(0.0);
-            input_buffer[..remaining].copy_from_slice(&mono_data[sample_pos..]);
-
-            match process_autotune(
-                &input_buffer,
-                &mut output_buffer,
-                &mut autotune_state,
-                &settings,
-            ) {
-                Ok(_) => {
-                    if processed_audio.len() < sample_pos + remaining {
-                        processed_audio.resize(sample_pos + remaining, 0.0);
-                    }
-
-                    for i in 0..remaining {
-                        processed_audio[sample_pos + i] += output_buffer[i];
-                    }
-                }
-                Err(_) => {
-                    // Fallback to original
-                    if processed_audio.len() < sample_pos + remaining {
-                        processed_audio.resize(sample_pos + remaining, 0.0);
-                    }
-
-                    for i in 0..remaining {
-                        processed_audio[sample_pos + i] += mono_data[sample_pos + i];
-                    }
-                }
+            let _ = progress_sender
+                .send(ProcessingProgress::Progress(progress.done as f32 / progress.total as f32));
+            stopped = control.cancel.load(Ordering::Relaxed);
+            if stopped {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
             }
-        }
-
-        // Normalize audio to prevent clipping
-        let _ =
-            progress_sender.send(ProcessingProgress::Status("Normalizing audio...".to_string()));
+        });
+        let mut rendered = match rendered {
+            Ok(rendered) => rendered,
+            Err(e) => return ProcessingResult::Error(e),
+        };
 
-        let max_amplitude = processed_audio.iter().map(|&x| x.abs()).fold(0.0, f32::max);
-        if max_amplitude > 1.0 {
-            let scale = 0.95 / max_amplitude;
-            for sample in &mut processed_audio {
-                *sample *= scale;
-            }
+        // Cut short by the cancel flag
+        if stopped {
+            let partial = (rendered.frames > 0).then(|| PartialRender {
+                render: Arc::new(rendered),
+                spec,
+                normalization: options.normalization,
+                limit,
+            });
+            return ProcessingResult::Cancelled(partial);
         }
 
-        // Convert back to stereo if original was stereo
-        let output_samples = if spec.channels == 2 {
+        log::info!("Processed {} mono samples", rendered.frames);
+        for (sample_pos, e) in &rendered.failed_frames {
+            log::warn!("Autotune processing error at sample {}: {}", sample_pos, e);
+        }
+        if let Some(reference) = &tint {
             let _ = progress_sender
-                .send(ProcessingProgress::Status("Converting to stereo...".to_string()));
+                .send(ProcessingProgress::Status("Matching reference tone...".to_string()));
+            rendered = match rendered.match_tint(output_path, reference, options.tint_amount) {
+                Ok(tinted) => tinted,
+                Err(e) => return ProcessingResult::Error(e),
+            };
+        }
+        let limited = limit.is_some();
+        if let Some(gain) =
+            rendered.normalization_gain(options.normalization, spec.channels, limited)
+        {
+            log::info!("Normalizing output by {:.2}x ({})", gain, options.normalization);
+        }
 
-            let mut stereo = Vec::with_capacity(processed_audio.len() * 2);
-            for sample in processed_audio {
-                stereo.push(sample); // Left channel
-                stereo.push(sample); // Right channel (same as left)
-            }
-            stereo
+        // Write the normalized output WAV, duplicating mono to every channel on the fly, and
+        // re-read it
+        let _ = progress_sender
+            .send(ProcessingProgress::Status("Writing and verifying output file...".to_string()));
+
+        let verification =
+            match rendered.write(output_path, spec, options.normalization, limit.as_ref()) {
+                Ok(verification) => verification,
+                Err(e) => return ProcessingResult::Error(e),
+            };
+        let errors = verification.errors();
+        if !errors.is_empty() {
+            return ProcessingResult::Error(format!(
+                "Output verification failed: {}",
+                errors.join("; ")
+            ));
+        }
+        let stems = if rendered.stems.is_empty() {
+            Vec::new()
         } else {
-            processed_audio
-        };
-
-        // Convert back to integer format
-        let _ =
-            progress_sender.send(ProcessingProgress::Status("Writing output file...".to_string()));
-
-        let output_scale = match spec.bits_per_sample {
-            16 => 32767.0,
-            24 => 8388607.0,
-            32 => 2147483647.0,
-            _ => return ProcessingResult::Error("Unsupported bit depth".to_string()),
-        };
-
-        let integer_samples: Vec<i32> =
-            output_samples.iter().map(|&x| (x * output_scale).round() as i32).collect();
-
-        // Write output WAV file
-        let mut writer = match WavWriter::create(output_path, spec) {
-            Ok(writer) => writer,
-            Err(e) => {
-                return ProcessingResult::Error(format!("Failed to create output file: {}", e));
+            let _ =
+                progress_sender.send(ProcessingProgress::Status("Writing stems...".to_string()));
+            match rendered.write_stems(output_path, spec, options.normalization) {
+                Ok(stems) => stems,
+                Err(e) => return ProcessingResult::Error(format!("Stem export failed: {}", e)),
             }
         };
-
-        for &sample in &integer_samples {
-            if let Err(e) = writer.write_sample(sample) {
-                return ProcessingResult::Error(format!("Failed to write sample: {}", e));
+        let master = if options.float_master {
+            let _ = progress_sender
+                .send(ProcessingProgress::Status("Writing float master...".to_string()));
+            match rendered.write_master(output_path, spec) {
+                Ok(master) => Some(master),
+                Err(e) => {
+                    return ProcessingResult::Error(format!("Float master export failed: {}", e));
+                }
             }
-        }
-
-        if let Err(e) = writer.finalize() {
-            return ProcessingResult::Error(format!("Failed to finalize output file: {}", e));
-        }
+        } else {
+            None
+        };
 
+        let markers =
+            pipeline::problem_markers(&rendered.failed_frames, &settings, &verification.warnings());
         let duration = start_time.elapsed();
         let _ = progress_sender.send(ProcessingProgress::Status("Complete!".to_string()));
 
         ProcessingResult::Success {
-            samples_processed: integer_samples.len(),
+            samples_processed: rendered.frames * spec.channels as usize,
             duration_ms: duration.as_millis() as f64,
+            verification,
+            input_loudness: rendered
+                .input_loudness
+                .map(|lufs| loudness::with_channels(lufs, info.channels)),
+            pitch: rendered.pitch.take(),
+            mono: rendered.mono,
+            markers,
+            stems,
+            master,
         }
     }
 }
 
+/// Scale down to a 0.95 peak if the signal would otherwise clip.
+pub fn normalize(samples: &mut [f32]) {
+    let max_amplitude = samples.iter().map(|&x| x.abs()).fold(0.0, f32::max);
+    if max_amplitude > 1.0 {
+        let scale = 0.95 / max_amplitude;
+        for sample in samples {
+            *sample *= scale;
+        }
+    }
+}
+
+/// Write mono samples to every channel of a new WAV file.
+pub fn write_output(path: &Path, spec: WavSpec, samples: &[f32]) -> Result<(), String> {
+    let writer = paths::create_wav(path, spec)
+        .map_err(|e| format!("Failed to create output file: {}", e))?;
+    let mut dither = pcm::Dither::default();
+    pcm::write_mono_file(writer, |writer| pcm::write_mono(writer, samples, Some(&mut dither)))
+        .map_err(|e| {
+            format!("Failed to write output file (audio written before the error was kept): {}", e)
+        })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::sync::mpsc;
+    use crate::pipeline::ScratchWriter;
     use tempfile::NamedTempFile;
 
     #[test]
@@ -300,10 +478,57 @@ mod tests {
 
     #[test]
     fn test_result_enum() {
-        let success = ProcessingResult::Success { samples_processed: 1000, duration_ms: 500.0 };
+        let verification = Verification {
+            expected_frames: 1000,
+            frames: 1000,
+            sample_peak: 0.5,
+            true_peak: 0.5,
+            loudness: None,
+            non_finite: 0,
+        };
+        let success = ProcessingResult::Success {
+            samples_processed: 1000,
+            duration_ms: 500.0,
+            verification,
+            input_loudness: None,
+            pitch: None,
+            mono: None,
+            markers: Vec::new(),
+            stems: Vec::new(),
+            master: None,
+        };
         assert!(matches!(success, ProcessingResult::Success { .. }));
 
         let error = ProcessingResult::Error("test error".to_string());
         assert!(matches!(error, ProcessingResult::Error(_)));
     }
+
+    #[test]
+    fn test_partial_render_saves_valid_wav() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 1000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let file = NamedTempFile::new().unwrap();
+        let mut scratch = ScratchWriter::create(file.path(), spec.sample_rate, 1).unwrap();
+        scratch.push(&[2.0, -1.0, 0.5, 0.0]).unwrap();
+        let partial = PartialRender {
+            render: Arc::new(scratch.finish(Vec::new()).unwrap()),
+            spec,
+            normalization: Normalization::Clip,
+            limit: None,
+        };
+        assert_eq!(partial.duration_secs(), 0.004);
+
+        partial.save(file.path()).unwrap();
+
+        let mut reader = hound::WavReader::open(file.path()).unwrap();
+        assert_eq!(reader.spec().channels, 2);
+        let mono = pcm::read_mono(&mut reader, usize::MAX).unwrap();
+        assert_eq!(mono.len(), 4);
+        // Normalized like a full render
+        assert!((mono[0] - 0.95).abs() < 1e-3);
+    }
 }
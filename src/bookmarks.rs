@@ -0,0 +1,101 @@
+use serde::{Deserialize, Serialize};
+
+// Dropping one this close to another finds that one instead of making a second
+const SAME_SPOT_SECS: f32 = 0.05;
+
+/// A named spot in the input to jump back to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub secs: f32,
+    pub name: String,
+}
+
+/// Add a bookmark at `secs` to `bookmarks`, kept in time order, named after how many there
+/// have been. Returns its index, or that of the one already there.
+pub fn insert(bookmarks: &mut Vec<Bookmark>, secs: f32) -> usize {
+    if let Some(index) =
+        bookmarks.iter().position(|bookmark| (bookmark.secs - secs).abs() < SAME_SPOT_SECS)
+    {
+        return index;
+    }
+    let index = bookmarks.partition_point(|bookmark| bookmark.secs < secs);
+    // Past the highest number in use, so deleting one doesn't reuse a name still around
+    let number = bookmarks
+        .iter()
+        .filter_map(|bookmark| bookmark.name.strip_prefix("Bookmark ")?.parse::<usize>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    bookmarks.insert(index, Bookmark { secs, name: format!("Bookmark {}", number) });
+    index
+}
+
+/// Read a time typed in as seconds ("90.5"), as a clock ("1:30" or "1:02:30.5"), or with
+/// units the way durations are shown ("1h 2m 5.7s"). A comma works as the decimal
+/// separator too.
+pub fn parse_time(text: &str) -> Result<f32, String> {
+    let trimmed = text.trim();
+    let text = trimmed.replace(',', ".").to_lowercase();
+    let secs = if text.contains(':') {
+        let parts: Vec<&str> = text.split(':').collect();
+        if parts.len() > 3 {
+            None
+        } else {
+            parts.iter().try_fold(0.0, |total, part| Some(total * 60.0 + part.parse::<f32>().ok()?))
+        }
+    } else if text.ends_with(|c: char| c.is_ascii_alphabetic()) {
+        with_units(&text)
+    } else {
+        text.parse().ok()
+    };
+    secs.filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .ok_or_else(|| format!("\"{}\" isn't a time, try 1:02:30, 90.5, or 1h 2m", trimmed))
+}
+
+// Numbers each followed by h, m, or s
+fn with_units(text: &str) -> Option<f32> {
+    let mut total = 0.0;
+    let mut number = String::new();
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        let unit = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => {
+                number.push(c);
+                continue;
+            }
+        };
+        total += number.parse::<f32>().ok()? * unit;
+        number.clear();
+    }
+    number.is_empty().then_some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_times_and_bookmarks() {
+        assert_eq!(parse_time("90.5"), Ok(90.5));
+        assert_eq!(parse_time(" 1:30 "), Ok(90.0));
+        assert_eq!(parse_time("1:02:30,5"), Ok(3750.5));
+        assert_eq!(parse_time("1h 2m 5.5s"), Ok(3725.5));
+        assert_eq!(parse_time("2m"), Ok(120.0));
+        for invalid in ["", "soon", "1:2:3:4", "-5", "5x", "1h 30"] {
+            assert!(parse_time(invalid).is_err(), "{}", invalid);
+        }
+
+        let mut bookmarks = Vec::new();
+        assert_eq!(insert(&mut bookmarks, 60.0), 0);
+        assert_eq!(insert(&mut bookmarks, 10.0), 0);
+        assert_eq!(insert(&mut bookmarks, 10.01), 0);
+        assert_eq!(insert(&mut bookmarks, 30.0), 1);
+        let names: Vec<&str> = bookmarks.iter().map(|bookmark| bookmark.name.as_str()).collect();
+        assert_eq!(names, ["Bookmark 2", "Bookmark 3", "Bookmark 1"]);
+        bookmarks.remove(1);
+        insert(&mut bookmarks, 90.0);
+        assert_eq!(bookmarks[2].name, "Bookmark 3");
+    }
+}
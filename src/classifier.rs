@@ -0,0 +1,808 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+use crate::stretch::fft;
+
+// Frames are analysed at roughly 11kHz, which is plenty for f0 up to 1kHz
+const ANALYSIS_RATE: f32 = 11025.0;
+const FRAME_SIZE: usize = 512;
+const HOP_SIZE: usize = 256;
+const CENTROID_FRAME_SIZE: usize = 512;
+const SILENCE_THRESHOLD: f32 = 0.01; // ~-40 dBFS RMS
+const MIN_F0: f32 = 60.0;
+const MAX_F0: f32 = 1000.0;
+// Pitched frames on each side an octave jump is judged against, about 70ms at the analysis
+// hop. Jumps lasting longer than this are real leaps and are kept
+const OCTAVE_CONTEXT: usize = 3;
+// A pitch this close to a whole number of octaves from its neighbours, in semitones, is
+// taken for an octave error of the detector
+const OCTAVE_TOLERANCE: f32 = 1.0;
+// Peak to RMS ratio above which a frame is made of separate pulses. Sustained tones and
+// noise stay well under it, creaky voice's glottal pulses rise far above
+const FRY_CREST: f32 = 4.0;
+// Pitches below this are creak rather than any note sung in earnest
+const FRY_MAX_F0: f32 = 70.0;
+// Zero-crossing rate, as the frequency of a sine crossing as often, and spectral flatness
+// (0.0 for a pure tone, about 0.56 for white noise) above which a frame is hiss, like a
+// sibilant or a breath, at a sensitivity of 0.5. Both halve at 1.0 and double towards 0.0
+const UNVOICED_CROSSINGS_HZ: f32 = 2000.0;
+const UNVOICED_FLATNESS: f32 = 0.15;
+// Frames quieter than about -60 dBFS RMS are too quiet to hear an artifact in
+const UNVOICED_MIN_RMS: f32 = 0.001;
+// Share of the unpitched frames, from the quietest, whose level is taken for the noise
+// floor: the pauses between phrases rather than the breaths and consonants
+const NOISE_PERCENTILE: f32 = 0.2;
+// Fewest unpitched frames, as a share of all of them, the noise floor is measured from
+const MIN_NOISE_SHARE: f32 = 0.04;
+// Level of digital silence, so an empty frame doesn't put the noise floor at -inf
+const DIGITAL_SILENCE_DB: f32 = -120.0;
+/// Signal-to-noise ratio below which the pitch detector starts losing notes in the noise.
+pub const LOW_SNR_DB: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentClass {
+    Speech,
+    SungVocal,
+    Instrument,
+    Polyphonic,
+}
+
+impl ContentClass {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentClass::Speech => "Speech",
+            ContentClass::SungVocal => "Sung vocal",
+            ContentClass::Instrument => "Instrument",
+            ContentClass::Polyphonic => "Polyphonic",
+        }
+    }
+
+    pub fn suggested_strength(&self) -> f32 {
+        match self {
+            ContentClass::Speech => 0.3,
+            ContentClass::SungVocal => 0.8,
+            ContentClass::Instrument => 0.6,
+            ContentClass::Polyphonic => 0.5,
+        }
+    }
+
+    pub fn suggested_transition(&self) -> f32 {
+        match self {
+            ContentClass::Speech => 0.3,
+            ContentClass::SungVocal => 0.1,
+            ContentClass::Instrument => 0.05,
+            ContentClass::Polyphonic => 0.2,
+        }
+    }
+
+    pub fn warning(&self) -> Option<&'static str> {
+        match self {
+            ContentClass::Speech => {
+                Some("Input looks like speech; pitch correction will mostly sound robotic.")
+            }
+            ContentClass::Polyphonic => Some(
+                "Input looks polyphonic; autotune follows a single pitch and will produce artifacts.",
+            ),
+            ContentClass::SungVocal | ContentClass::Instrument => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ContentFeatures {
+    /// Mean normalized autocorrelation peak over non-silent frames (0.0 to 1.0)
+    pub harmonicity: f32,
+    /// Mean spectral centroid in Hz over non-silent frames
+    pub spectral_centroid: f32,
+    /// Fraction of frames below the silence threshold
+    pub silence_ratio: f32,
+    /// Median frame-to-frame f0 change in semitones
+    pub pitch_jitter: f32,
+    /// Spread between loud and quiet active frames in dB
+    pub dynamic_range_db: f32,
+    /// Level of the noise in the pauses in dBFS, None when there are too few to measure
+    pub noise_floor_db: Option<f32>,
+    /// Level of the loud active frames above the noise floor in dB
+    pub snr_db: Option<f32>,
+}
+
+impl ContentFeatures {
+    /// A warning when the noise is loud enough against the signal to throw off the pitch
+    /// detector.
+    pub fn noise_warning(&self) -> Option<String> {
+        let (floor, snr) = (self.noise_floor_db?, self.snr_db?);
+        (snr < LOW_SNR_DB).then(|| {
+            format!(
+                "The noise floor ({:.0} dBFS) is only {:.0} dB below the signal, enough for the \
+                 pitch detector to lose notes in it. Re-record somewhere quieter, or clean the \
+                 take up with noise reduction before correcting it.",
+                floor, snr
+            )
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Classification {
+    pub class: ContentClass,
+    pub features: ContentFeatures,
+}
+
+/// Level and pitch of one analysis frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchFrame {
+    pub rms: f32,
+    // Normalized autocorrelation peak, 0.0 for silent frames
+    pub harmonicity: f32,
+    // None for silent or unvoiced frames
+    pub f0: Option<f32>,
+}
+
+impl PitchFrame {
+    pub fn is_silent(&self) -> bool {
+        self.rms < SILENCE_THRESHOLD
+    }
+}
+
+/// Frame-by-frame level and pitch of a signal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PitchTrack {
+    pub frames: Vec<PitchFrame>,
+    pub hop_secs: f32,
+    // Center of the first frame
+    pub offset_secs: f32,
+}
+
+impl PitchTrack {
+    /// The frame centered closest to `secs`.
+    pub fn at(&self, secs: f32) -> Option<&PitchFrame> {
+        let index = ((secs - self.offset_secs) / self.hop_secs).round().max(0.0) as usize;
+        self.frames.get(index.min(self.frames.len().saturating_sub(1)))
+    }
+}
+
+/// MIDI note number of a frequency, fractional between notes.
+pub fn note_number(frequency: f32) -> f32 {
+    69.0 + 12.0 * (frequency / 440.0).log2()
+}
+
+/// Track the level and pitch of mono audio at the analysis rate, one frame per hop.
+pub fn pitch_track(samples: &[f32], sample_rate: f32) -> PitchTrack {
+    let mut tracker = PitchTracker::new(sample_rate);
+    tracker.push(samples);
+    tracker.finish()
+}
+
+/// Builds a [`PitchTrack`] from audio pushed a block at a time, so a signal can be tracked
+/// while it streams past.
+pub struct PitchTracker {
+    decimation: usize,
+    analysis_rate: f32,
+    min_lag: usize,
+    max_lag: usize,
+    // Sum and count of the input samples going into the next decimated one
+    sum: f32,
+    summed: usize,
+    // Decimated samples from the start of the next frame on
+    decimated: Vec<f32>,
+    track: PitchTrack,
+}
+
+impl PitchTracker {
+    pub fn new(sample_rate: f32) -> Self {
+        let decimation = ((sample_rate / ANALYSIS_RATE).round() as usize).max(1);
+        let analysis_rate = sample_rate / decimation as f32;
+        Self {
+            decimation,
+            analysis_rate,
+            min_lag: (analysis_rate / MAX_F0).floor().max(2.0) as usize,
+            max_lag: ((analysis_rate / MIN_F0).ceil() as usize).min(FRAME_SIZE / 2),
+            sum: 0.0,
+            summed: 0,
+            decimated: Vec::with_capacity(FRAME_SIZE * 2),
+            track: PitchTrack {
+                frames: Vec::new(),
+                hop_secs: HOP_SIZE as f32 / analysis_rate,
+                offset_secs: FRAME_SIZE as f32 / 2.0 / analysis_rate,
+            },
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        if self.analysis_rate <= 0.0 {
+            return;
+        }
+        for &sample in samples {
+            self.sum += sample;
+            self.summed += 1;
+            if self.summed == self.decimation {
+                self.decimated.push(self.sum / self.decimation as f32);
+                self.sum = 0.0;
+                self.summed = 0;
+            }
+        }
+        self.analyse();
+    }
+
+    /// The track so far, with the last partly decimated sample included and octave errors
+    /// removed.
+    pub fn finish(mut self) -> PitchTrack {
+        if self.summed > 0 {
+            self.decimated.push(self.sum / self.summed as f32);
+            self.analyse();
+        }
+        remove_octave_errors(&mut self.track.frames);
+        self.track
+    }
+
+    // Track every complete frame buffered so far
+    fn analyse(&mut self) {
+        while self.decimated.len() >= FRAME_SIZE {
+            let frame = &self.decimated[..FRAME_SIZE];
+            self.track.frames.push(analyse_frame(
+                frame,
+                self.analysis_rate,
+                self.min_lag,
+                self.max_lag,
+            ));
+            self.decimated.drain(..HOP_SIZE);
+        }
+    }
+}
+
+/// Pitch of one frame of audio at `sample_rate`, or `None` if it is silent or unpitched.
+/// The frame is decimated to the analysis rate first, so this is cheap enough to run on
+/// every hop; frames too short for the lowest pitches only find higher ones.
+pub fn frame_pitch(frame: &[f32], sample_rate: f32) -> Option<f32> {
+    decimated_frame(frame, sample_rate)?.f0
+}
+
+/// Like [`frame_pitch`], looking for a pitch from `min_f0` to `max_f0` Hz only.
+pub fn frame_pitch_in(frame: &[f32], sample_rate: f32, min_f0: f32, max_f0: f32) -> Option<f32> {
+    decimated_frame_in(frame, sample_rate, min_f0, max_f0)?.f0
+}
+
+/// How sure the pitch detector can be of one frame of audio at `sample_rate`, from 0.0 for
+/// silence and noise to 1.0 for a perfectly periodic tone.
+pub fn frame_confidence(frame: &[f32], sample_rate: f32) -> f32 {
+    decimated_frame(frame, sample_rate).map_or(0.0, |analysed| analysed.harmonicity.max(0.0))
+}
+
+/// Whether one frame of audio at `sample_rate` sounds like vocal fry (creak): loud, and made
+/// of separate glottal pulses that are either too irregular to have a pitch or slower than
+/// any sung note. The pitch detector either misses fry or locks onto a subharmonic of it.
+pub fn is_fry(frame: &[f32], sample_rate: f32) -> bool {
+    let Some(analysed) = decimated_frame(frame, sample_rate) else {
+        return false;
+    };
+    let peak = frame.iter().fold(0.0f32, |peak, &x| peak.max(x.abs()));
+    let rms = (frame.iter().map(|&x| x * x).sum::<f32>() / frame.len() as f32).sqrt();
+    !analysed.is_silent()
+        && peak > FRY_CREST * rms
+        && !analysed.f0.is_some_and(|f0| f0 >= FRY_MAX_F0)
+}
+
+/// Whether one frame of audio at `sample_rate` is unvoiced, like a sibilant, a breath, or a
+/// plosive: audible, without a pitch, crossing zero often, and with a flat, noise-like
+/// spectrum. Correcting these hard makes them sound metallic. `sensitivity` (0.0 to 1.0)
+/// lowers both thresholds the higher it is, so more borderline frames count.
+pub fn is_unvoiced(frame: &[f32], sample_rate: f32, sensitivity: f32) -> bool {
+    let rms = (frame.iter().map(|&x| x * x).sum::<f32>() / frame.len().max(1) as f32).sqrt();
+    if rms < UNVOICED_MIN_RMS || frame.len() < 2 {
+        return false;
+    }
+    if decimated_frame(frame, sample_rate).is_some_and(|analysed| analysed.f0.is_some()) {
+        return false;
+    }
+    let scale = 2f32.powf(1.0 - 2.0 * sensitivity.clamp(0.0, 1.0));
+    let crossings = frame.windows(2).filter(|pair| (pair[0] >= 0.0) != (pair[1] >= 0.0)).count();
+    let crossings_hz = crossings as f32 / (frame.len() - 1) as f32 * sample_rate / 2.0;
+    crossings_hz > UNVOICED_CROSSINGS_HZ * scale
+        && spectral_flatness(frame) > UNVOICED_FLATNESS * scale
+}
+
+// Geometric over arithmetic mean of the Hann-windowed power spectrum of `frame`, zero
+// padded to a power of two
+fn spectral_flatness(frame: &[f32]) -> f32 {
+    let size = frame.len().next_power_of_two();
+    let mut re: Vec<f32> = frame
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| x * (0.5 - 0.5 * (2.0 * PI * i as f32 / frame.len() as f32).cos()))
+        .collect();
+    re.resize(size, 0.0);
+    let mut im = vec![0.0; size];
+    fft(&mut re, &mut im, false);
+    let power: Vec<f32> = (1..size / 2).map(|bin| re[bin] * re[bin] + im[bin] * im[bin]).collect();
+    let mean = power.iter().sum::<f32>() / power.len() as f32;
+    if mean <= 0.0 {
+        return 0.0;
+    }
+    let log_mean = power.iter().map(|&p| (p + mean * 1e-9).ln()).sum::<f32>() / power.len() as f32;
+    log_mean.exp() / mean
+}
+
+// Level and pitch of a frame at `sample_rate`, decimated to the analysis rate
+fn decimated_frame(frame: &[f32], sample_rate: f32) -> Option<PitchFrame> {
+    decimated_frame_in(frame, sample_rate, MIN_F0, MAX_F0)
+}
+
+// The same, with a pitch looked for from `min_f0` to `max_f0` only
+fn decimated_frame_in(
+    frame: &[f32],
+    sample_rate: f32,
+    min_f0: f32,
+    max_f0: f32,
+) -> Option<PitchFrame> {
+    if sample_rate <= 0.0 || min_f0 <= 0.0 {
+        return None;
+    }
+    let decimation = ((sample_rate / ANALYSIS_RATE).round() as usize).max(1);
+    let analysis_rate = sample_rate / decimation as f32;
+    let decimated: Vec<f32> = frame
+        .chunks(decimation)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect();
+    let min_lag = (analysis_rate / max_f0).floor().max(2.0) as usize;
+    let max_lag = ((analysis_rate / min_f0).ceil() as usize).min(decimated.len() / 2);
+    if max_lag <= min_lag {
+        return None;
+    }
+    Some(analyse_frame(&decimated, analysis_rate, min_lag, max_lag))
+}
+
+// Level and pitch of a frame already at `analysis_rate`
+fn analyse_frame(frame: &[f32], analysis_rate: f32, min_lag: usize, max_lag: usize) -> PitchFrame {
+    let rms = (frame.iter().map(|&x| x * x).sum::<f32>() / frame.len() as f32).sqrt();
+    if rms < SILENCE_THRESHOLD {
+        return PitchFrame { rms, harmonicity: 0.0, f0: None };
+    }
+    let (peak, lag) = autocorrelation_peak(frame, min_lag, max_lag);
+    PitchFrame { rms, harmonicity: peak, f0: (peak > 0.6).then(|| analysis_rate / lag) }
+}
+
+/// Move isolated octave jumps back onto the pitch around them: a frame whose pitch is a
+/// whole number of octaves from the median of its pitched neighbours is shifted by those
+/// octaves. Hoarse and creaky voices make the detector lock onto a sub- or overtone for a
+/// frame or two.
+pub fn remove_octave_errors(frames: &mut [PitchFrame]) {
+    let mut notes: Vec<Option<f32>> =
+        frames.iter().map(|frame| frame.f0.map(note_number)).collect();
+    for (i, frame) in frames.iter_mut().enumerate() {
+        let Some(note) = notes[i] else {
+            continue;
+        };
+        // Earlier frames are already corrected, so a jump doesn't spill into what follows
+        let before = notes[..i].iter().rev().flatten().take(OCTAVE_CONTEXT);
+        let after = notes[i + 1..].iter().flatten().take(OCTAVE_CONTEXT);
+        let mut context: Vec<f32> = before.chain(after).copied().chain([note]).collect();
+        if context.len() < 3 {
+            continue;
+        }
+        if let Some(reference) = median(&mut context) {
+            let octaves = octave_error(note, reference);
+            frame.f0 = frame.f0.map(|f0| f0 * 2f32.powi(-octaves));
+            notes[i] = Some(note - 12.0 * octaves as f32);
+        }
+    }
+}
+
+/// Removes octave errors from pitches as they arrive, judged against the last few, for
+/// choices that can't wait for the frames after. A jump that lasts longer than a few frames
+/// is taken as a real leap.
+#[derive(Default)]
+pub struct OctaveFilter {
+    // Notes after correction
+    recent: VecDeque<f32>,
+    // Frames in a row moved by octaves
+    jumped: usize,
+}
+
+impl OctaveFilter {
+    /// `note` moved back by whole octaves if it jumped away from the recent notes.
+    pub fn push(&mut self, note: f32) -> f32 {
+        let mut context: Vec<f32> = self.recent.iter().copied().collect();
+        let mut octaves = match median(&mut context) {
+            Some(reference) if context.len() >= 2 => octave_error(note, reference),
+            _ => 0,
+        };
+        if octaves == 0 {
+            self.jumped = 0;
+        } else {
+            self.jumped += 1;
+            if self.jumped > OCTAVE_CONTEXT {
+                octaves = 0;
+                self.recent.clear();
+            }
+        }
+
+        let note = note - 12.0 * octaves as f32;
+        if self.recent.len() == 2 * OCTAVE_CONTEXT {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(note);
+        note
+    }
+}
+
+// Whole octaves `note` is off from `reference` by, or 0 if it isn't an octave error
+fn octave_error(note: f32, reference: f32) -> i32 {
+    let octaves = ((note - reference) / 12.0).round();
+    if (note - reference - 12.0 * octaves).abs() <= OCTAVE_TOLERANCE {
+        octaves as i32
+    } else {
+        0
+    }
+}
+
+/// Classify mono audio. Returns `None` when there is not enough non-silent audio to judge.
+pub fn classify(samples: &[f32], sample_rate: f32) -> Option<Classification> {
+    if sample_rate <= 0.0 {
+        return None;
+    }
+
+    let track = pitch_track(samples, sample_rate);
+    let active: Vec<&PitchFrame> = track.frames.iter().filter(|frame| !frame.is_silent()).collect();
+    if active.len() < 4 {
+        return None;
+    }
+
+    let mut jitter: Vec<f32> = track
+        .frames
+        .windows(2)
+        .filter_map(|pair| match (pair[0].f0, pair[1].f0) {
+            (Some(a), Some(b)) => Some((12.0 * (b / a).log2()).abs()),
+            _ => None,
+        })
+        .collect();
+
+    let mut levels_db: Vec<f32> = active.iter().map(|frame| 20.0 * frame.rms.log10()).collect();
+    levels_db.sort_by(|a, b| a.total_cmp(b));
+    let total_frames = track.frames.len();
+    let signal_db = percentile(&levels_db, 0.95);
+
+    // The noise is what is left where nothing is pitched; a take pitched throughout has no
+    // pauses to hear it in
+    let mut unpitched_db: Vec<f32> = track
+        .frames
+        .iter()
+        .filter(|frame| frame.f0.is_none())
+        .map(|frame| (20.0 * frame.rms.log10()).max(DIGITAL_SILENCE_DB))
+        .collect();
+    unpitched_db.sort_by(|a, b| a.total_cmp(b));
+    let noise_floor_db = (unpitched_db.len() >= 4
+        && unpitched_db.len() as f32 >= MIN_NOISE_SHARE * total_frames as f32)
+        .then(|| percentile(&unpitched_db, NOISE_PERCENTILE));
+    let features = ContentFeatures {
+        harmonicity: active.iter().map(|frame| frame.harmonicity).sum::<f32>()
+            / active.len() as f32,
+        spectral_centroid: mean_spectral_centroid(samples, sample_rate),
+        silence_ratio: (total_frames - active.len()) as f32 / total_frames as f32,
+        pitch_jitter: median(&mut jitter).unwrap_or(f32::MAX),
+        dynamic_range_db: signal_db - percentile(&levels_db, 0.10),
+        noise_floor_db,
+        snr_db: noise_floor_db.map(|floor| signal_db - floor),
+    };
+
+    Some(Classification { class: classify_features(&features), features })
+}
+
+fn classify_features(features: &ContentFeatures) -> ContentClass {
+    if features.harmonicity < 0.5 {
+        ContentClass::Polyphonic
+    } else if features.silence_ratio > 0.2
+        && (features.pitch_jitter > 0.5 || features.harmonicity < 0.7)
+    {
+        ContentClass::Speech
+    } else if features.pitch_jitter < 0.08 || features.spectral_centroid > 3500.0 {
+        ContentClass::Instrument
+    } else {
+        ContentClass::SungVocal
+    }
+}
+
+// Returns the highest normalized autocorrelation value and its (interpolated) lag,
+// preferring the first peak close to the maximum to avoid octave-down errors.
+fn autocorrelation_peak(frame: &[f32], min_lag: usize, max_lag: usize) -> (f32, f32) {
+    let correlations: Vec<f32> = (min_lag..=max_lag)
+        .map(|lag| {
+            let (mut cross, mut energy_a, mut energy_b) = (0.0, 0.0, 0.0);
+            for i in 0..frame.len() - lag {
+                cross += frame[i] * frame[i + lag];
+                energy_a += frame[i] * frame[i];
+                energy_b += frame[i + lag] * frame[i + lag];
+            }
+            let norm = (energy_a * energy_b).sqrt();
+            if norm > 0.0 { cross / norm } else { 0.0 }
+        })
+        .collect();
+
+    let max = correlations.iter().copied().fold(0.0, f32::max);
+    if max <= 0.0 {
+        return (0.0, max_lag as f32);
+    }
+
+    let mut best = correlations.iter().position(|&c| c == max).unwrap_or(0);
+    for i in 1..correlations.len().saturating_sub(1) {
+        let c = correlations[i];
+        if c >= 0.9 * max && c >= correlations[i - 1] && c >= correlations[i + 1] {
+            best = i;
+            break;
+        }
+    }
+
+    // Parabolic interpolation around the chosen peak
+    let mut offset = 0.0;
+    if best > 0 && best + 1 < correlations.len() {
+        let (a, b, c) = (correlations[best - 1], correlations[best], correlations[best + 1]);
+        let denom = a - 2.0 * b + c;
+        if denom.abs() > f32::EPSILON {
+            offset = (0.5 * (a - c) / denom).clamp(-0.5, 0.5);
+        }
+    }
+
+    (correlations[best], (min_lag + best) as f32 + offset)
+}
+
+fn mean_spectral_centroid(samples: &[f32], sample_rate: f32) -> f32 {
+    // Spread a bounded number of frames over the input to keep the naive DFT cheap
+    const MAX_FRAMES: usize = 64;
+
+    if samples.len() < CENTROID_FRAME_SIZE {
+        return 0.0;
+    }
+
+    let available = samples.len() - CENTROID_FRAME_SIZE;
+    let step = (available / MAX_FRAMES).max(CENTROID_FRAME_SIZE);
+    let bin_hz = sample_rate / CENTROID_FRAME_SIZE as f32;
+
+    let mut centroid_sum = 0.0;
+    let mut frames = 0;
+    let mut pos = 0;
+    while pos <= available {
+        let frame = &samples[pos..pos + CENTROID_FRAME_SIZE];
+        let rms = (frame.iter().map(|&x| x * x).sum::<f32>() / frame.len() as f32).sqrt();
+
+        if rms >= SILENCE_THRESHOLD {
+            let (mut weighted, mut total) = (0.0, 0.0);
+            for bin in 1..CENTROID_FRAME_SIZE / 2 {
+                let (mut re, mut im) = (0.0, 0.0);
+                for (n, &x) in frame.iter().enumerate() {
+                    let window =
+                        0.5 - 0.5 * (2.0 * PI * n as f32 / CENTROID_FRAME_SIZE as f32).cos();
+                    let angle = 2.0 * PI * bin as f32 * n as f32 / CENTROID_FRAME_SIZE as f32;
+                    re += x * window * angle.cos();
+                    im -= x * window * angle.sin();
+                }
+                let magnitude = (re * re + im * im).sqrt();
+                weighted += magnitude * bin as f32 * bin_hz;
+                total += magnitude;
+            }
+            if total > 0.0 {
+                centroid_sum += weighted / total;
+                frames += 1;
+            }
+        }
+
+        pos += step;
+    }
+
+    if frames > 0 {
+        centroid_sum / frames as f32
+    } else {
+        0.0
+    }
+}
+
+fn median(values: &mut [f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.total_cmp(b));
+    Some(values[values.len() / 2])
+}
+
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    fn harmonic_tone(frequency: impl Fn(f32) -> f32, seconds: f32) -> Vec<f32> {
+        let mut phase = 0.0;
+        (0..(seconds * SAMPLE_RATE) as usize)
+            .map(|i| {
+                phase += 2.0 * PI * frequency(i as f32 / SAMPLE_RATE) / SAMPLE_RATE;
+                0.5 * (phase.sin() + 0.3 * (2.0 * phase).sin() + 0.15 * (3.0 * phase).sin())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_silence_is_unclassified() {
+        assert!(classify(&vec![0.0; 44100], SAMPLE_RATE).is_none());
+        assert!(classify(&[], SAMPLE_RATE).is_none());
+    }
+
+    #[test]
+    fn test_steady_tone_is_instrument() {
+        let samples = harmonic_tone(|_| 220.0, 2.0);
+        let result = classify(&samples, SAMPLE_RATE).unwrap();
+        assert_eq!(result.class, ContentClass::Instrument);
+        assert!(result.features.harmonicity > 0.9);
+        // No pauses to measure the noise in
+        assert_eq!(result.features.noise_floor_db, None);
+        assert!(result.features.noise_warning().is_none());
+    }
+
+    #[test]
+    fn test_noise_floor_is_measured_in_the_pauses() {
+        let mut seed: u32 = 12345;
+        let mut take_with_noise = |amplitude: f32| -> Vec<f32> {
+            let mut phrase = harmonic_tone(|_| 220.0, 1.0);
+            phrase.extend(vec![0.0; (0.5 * SAMPLE_RATE) as usize]);
+            phrase
+                .iter()
+                .chain(&phrase)
+                .map(|sample| {
+                    seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                    sample + (seed as f32 / u32::MAX as f32 - 0.5) * amplitude
+                })
+                .collect()
+        };
+
+        // Uniform noise 0.001 wide is about -71 dBFS, and -77 of it falls in the band the
+        // analysis hears, ~70 dB under the tone
+        let clean = classify(&take_with_noise(0.001), SAMPLE_RATE).unwrap().features;
+        let floor = clean.noise_floor_db.unwrap();
+        assert!((-80.0..-74.0).contains(&floor), "{}", floor);
+        assert!(clean.snr_db.unwrap() > 60.0);
+        assert!(clean.noise_warning().is_none());
+
+        let noisy = classify(&take_with_noise(0.4), SAMPLE_RATE).unwrap().features;
+        assert!(noisy.snr_db.unwrap() < LOW_SNR_DB, "{:?}", noisy.snr_db);
+        assert!(noisy.noise_warning().is_some());
+    }
+
+    #[test]
+    fn test_vibrato_tone_is_sung_vocal() {
+        // Half a semitone of vibrato at 5Hz
+        let samples =
+            harmonic_tone(|t| 220.0 * 2f32.powf(0.5 * (2.0 * PI * 5.0 * t).sin() / 12.0), 2.0);
+        let result = classify(&samples, SAMPLE_RATE).unwrap();
+        assert_eq!(result.class, ContentClass::SungVocal);
+        assert!(result.class.warning().is_none());
+    }
+
+    #[test]
+    fn test_pitch_track_follows_tone() {
+        let mut samples = vec![0.0; 22050];
+        samples.extend(harmonic_tone(|_| 330.0, 1.0));
+        let track = pitch_track(&samples, SAMPLE_RATE);
+        assert!(track.at(0.25).is_some_and(|frame| frame.f0.is_none()));
+        let f0 = track.at(1.0).and_then(|frame| frame.f0).unwrap();
+        assert!((f0 - 330.0).abs() < 3.0, "{}", f0);
+        // E4
+        assert!((note_number(f0) - 64.0).abs() < 0.2);
+        assert_eq!(note_number(440.0), 69.0);
+        // A single processing frame finds the same pitch
+        let frame_f0 = frame_pitch(&samples[33075..34099], SAMPLE_RATE).unwrap();
+        assert!((frame_f0 - 330.0).abs() < 3.0, "{}", frame_f0);
+        assert!(frame_pitch(&samples[..1024], SAMPLE_RATE).is_none());
+        // Past the end clamps to the last frame
+        assert_eq!(track.at(60.0), track.frames.last());
+    }
+
+    #[test]
+    fn test_streamed_pitch_track_matches_whole() {
+        let samples = harmonic_tone(|t| 220.0 + 40.0 * t, 1.0);
+        let mut tracker = PitchTracker::new(SAMPLE_RATE);
+        // Uneven blocks that don't line up with the decimation or the hop
+        for block in samples.chunks(1001) {
+            tracker.push(block);
+        }
+        assert_eq!(tracker.finish(), pitch_track(&samples, SAMPLE_RATE));
+    }
+
+    #[test]
+    fn test_fry_is_told_from_sung_notes() {
+        // Glottal pulses ringing at a formant, spaced `period(n)` samples apart
+        let pulses = |period: &dyn Fn(usize) -> usize| -> Vec<f32> {
+            let mut samples = vec![0.0; 4096];
+            let (mut start, mut n) = (100, 0);
+            while start < samples.len() {
+                for (i, sample) in samples[start..].iter_mut().take(400).enumerate() {
+                    let t = i as f32 / SAMPLE_RATE;
+                    *sample += 0.8 * (-t * 900.0).exp() * (2.0 * PI * 600.0 * t).sin();
+                }
+                start += period(n);
+                n += 1;
+            }
+            samples
+        };
+
+        // Around 50Hz and unevenly spaced
+        let fry = pulses(&|n| [830, 930, 870][n % 3]);
+        assert!(is_fry(&fry[1024..2048], SAMPLE_RATE));
+        // The same pulses at a steady 147Hz are a low sung note
+        let chest = pulses(&|_| 300);
+        assert!(!is_fry(&chest[1024..2048], SAMPLE_RATE));
+        assert!(!is_fry(&harmonic_tone(|_| 110.0, 0.1)[..1024], SAMPLE_RATE));
+        assert!(!is_fry(&[0.001; 1024], SAMPLE_RATE));
+    }
+
+    #[test]
+    fn test_octave_errors_are_removed() {
+        let frame = |note: Option<f32>| PitchFrame {
+            rms: 0.2,
+            harmonicity: 0.9,
+            f0: note.map(|note| 440.0 * 2f32.powf((note - 69.0) / 12.0)),
+        };
+        let notes = |frames: &[PitchFrame]| -> Vec<Option<i32>> {
+            frames.iter().map(|f| f.f0.map(|f0| note_number(f0).round() as i32)).collect()
+        };
+        // A one frame drop an octave down, a two frame jump two octaves up, a fifth, and
+        // then a real leap an octave up
+        let sung = [
+            69.0, 69.2, 57.1, 69.1, 68.9, 93.0, 93.1, 69.0, 76.0, 69.0, 69.1, 81.0, 81.2, 81.1,
+            81.0, 80.9, 81.0,
+        ];
+        let mut frames: Vec<PitchFrame> = sung.iter().map(|&note| frame(Some(note))).collect();
+        frames.insert(4, frame(None));
+        remove_octave_errors(&mut frames);
+        assert_eq!(
+            notes(&frames),
+            [69, 69, 69, 69, -1, 69, 69, 69, 69, 76, 69, 69, 81, 81, 81, 81, 81, 81]
+                .map(|note| (note >= 0).then_some(note))
+        );
+
+        let mut filter = OctaveFilter::default();
+        let filtered: Vec<i32> =
+            sung.iter().map(|&note| filter.push(note).round() as i32).collect();
+        // The leap is only taken once it has lasted a few frames
+        assert_eq!(filtered, [69, 69, 69, 69, 69, 69, 69, 69, 76, 69, 69, 69, 69, 69, 81, 81, 81]);
+    }
+
+    #[test]
+    fn test_hiss_is_unvoiced() {
+        let mut seed: u32 = 1;
+        let noise: Vec<f32> = (0..1024)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed as f32 / u32::MAX as f32 - 0.5) * 0.2
+            })
+            .collect();
+        // Differenced noise leans to the highs like an "s"
+        let sibilant: Vec<f32> = noise.windows(2).map(|pair| pair[1] - pair[0]).collect();
+        assert!(is_unvoiced(&noise, SAMPLE_RATE, 0.5));
+        assert!(is_unvoiced(&sibilant[..1023], SAMPLE_RATE, 0.5));
+
+        let vowel = harmonic_tone(|_| 220.0, 0.1);
+        assert!(!is_unvoiced(&vowel[..1024], SAMPLE_RATE, 1.0));
+        let quiet: Vec<f32> = noise.iter().map(|x| x * 0.001).collect();
+        assert!(!is_unvoiced(&quiet, SAMPLE_RATE, 1.0));
+        assert!(spectral_flatness(&vowel[..1024]) < 0.05);
+    }
+
+    #[test]
+    fn test_noise_is_polyphonic() {
+        let mut seed: u32 = 12345;
+        let samples: Vec<f32> = (0..44100)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                (seed as f32 / u32::MAX as f32 - 0.5) * 0.5
+            })
+            .collect();
+        let result = classify(&samples, SAMPLE_RATE).unwrap();
+        assert_eq!(result.class, ContentClass::Polyphonic);
+        assert!(result.class.warning().is_some());
+    }
+}
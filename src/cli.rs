@@ -1,34 +1,441 @@
-use clap::Parser;
-use hound::{WavReader, WavWriter};
-use std::path::PathBuf;
-use std::time::Instant;
-use synthphone_vocals::{AutotuneConfig, AutotuneState, MusicalSettings, process_autotune};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use hound::WavSpec;
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+use synthphone_vocals::{AutotuneConfig, MusicalSettings};
+
+use crate::align::{self, AlignLimits};
+use crate::app_config::{self, ConfigLocation};
+use crate::dynamics::{self, Compressor, Limit};
+use crate::effects::ChainOrder;
+use crate::energy::EnergyCurve;
+use crate::engine::{self, Padding, Window};
+use crate::equalizer::Equalizer;
+use crate::failure::{self, Classify, Failed, Failure};
+use crate::fingerprint::{self, Fingerprint, Fingerprinter};
+use crate::format::{self, Format, FormatSettings, SizeUnits};
+use crate::frequencies::{self, FrequencyList};
+use crate::harmony::Interval;
+use crate::intonation::{self, IntonationReport};
+use crate::jobs::{JobTicket, Spool};
+use crate::keys::{KeyChange, KeyMatch};
+use crate::labels::{self, Label};
+use crate::loudness::{self, Normalization};
+use crate::midi::Melody;
+use crate::pcm::BitDepth;
+use crate::phrases::{self, Phrase, SilenceSplit};
+use crate::pipe::{self, RawFormat, RawPcm};
+use crate::pipeline::{self, OutputLength, RenderSettings, ScratchWriter};
+use crate::pitch::{F0Range, PitchAlgorithm};
+use crate::prefilter::{self, Gate};
+use crate::presets::{self, Preset};
+use crate::progress::{self, RenderProgress};
+use crate::project_file::{self, ProjectFile};
+use crate::report::{self, Event, Summary};
+use crate::routing::{self, Route, Voice};
+use crate::scale::Scale;
+use crate::scratch::{self, JobDir, ScratchSettings};
+#[cfg(feature = "scripting")]
+use crate::script;
+use crate::sections;
+use crate::storage::{self, Location};
+use crate::throttle::Throttle;
+use crate::tint::Spectrum;
+use crate::watch::StableFiles;
+use crate::{classifier, decoder, demo_audio, keys, paths, pcm, profiling, resample};
 
 #[derive(Parser)]
 #[command(name = "autotune-cli")]
 #[command(about = "Command-line autotune processor using synthphone_vocals library")]
 #[command(version = "0.1.0")]
 pub struct Cli {
-    /// Input WAV file path
-    #[arg(short, long, value_name = "FILE", required_unless_present = "list_keys")]
+    #[command(subcommand)]
+    pub command: Command,
+
+    /// Enable verbose output
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+
+    /// Keep user presets in the portable data directory next to the executable, like the
+    /// GUI's --portable
+    #[arg(long = app_config::PORTABLE_FLAG.trim_start_matches('-'), global = true)]
+    pub portable: bool,
+
+    /// Read and write audio files in blocks of this many KiB, independent of the FFT and hop
+    /// sizes. Larger blocks help on spinning disks and network storage
+    #[arg(long, default_value_t = paths::DEFAULT_IO_BLOCK_SIZE / 1024, value_name = "KIB", global = true)]
+    pub io_block_size: usize,
+
+    /// Record a Chrome trace of decode, DSP, and encode timings to FILE (open in Perfetto)
+    #[arg(long, value_name = "FILE", global = true)]
+    pub trace_file: Option<PathBuf>,
+
+    /// Show file sizes in steps of 1000 (kB, MB) instead of 1024 (KiB, MiB). Decimals are
+    /// written the way LC_ALL, LC_NUMERIC, or LANG asks for
+    #[arg(long, global = true)]
+    pub si_units: bool,
+
+    /// Keep render spools and staged downloads in DIR, a fast local disk ideally, instead of
+    /// the system's temp directory
+    #[arg(long, value_name = "DIR", global = true)]
+    pub scratch_dir: Option<PathBuf>,
+
+    /// Fail a job rather than let scratch files take up more than this many MiB
+    #[arg(long, value_name = "MIB", global = true)]
+    pub scratch_cap: Option<u64>,
+}
+
+// Parsed once per run, so the size of the process arguments doesn't matter
+#[allow(clippy::large_enum_variant)]
+#[derive(Subcommand)]
+pub enum Command {
+    /// Autotune a file, or queue and run spool jobs. The default when no subcommand is given
+    Process(ProcessArgs),
+    /// Report the content, pitch range, and best-fitting keys of a file
+    Analyze(AnalyzeArgs),
+    /// Autotune every audio file in a folder with the same settings
+    Batch(BatchArgs),
+    /// List the available keys
+    Keys,
+    /// Write an in-tune and an off-pitch test WAV to try correction on
+    Generate(GenerateArgs),
+    /// Render an excerpt once per combination of a few strengths and transition speeds, to
+    /// pick settings by ear
+    Compare(CompareArgs),
+    /// Run a Rhai job script, for automation the options can't express: picking files,
+    /// working out settings from their names, and processing each with its own
+    #[cfg(feature = "scripting")]
+    RunScript(RunScriptArgs),
+}
+
+#[derive(Args)]
+pub struct ProcessArgs {
+    /// Input audio file (WAV, MP3, M4A/AAC) path, http(s) URL, or s3://bucket/key. - reads
+    /// raw PCM from stdin, laid out as --raw-format, --raw-rate, and --raw-channels say
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        required_unless_present_any = [
+            "list_presets",
+            "save_preset",
+            "spool",
+            "project"
+        ]
+    )]
     pub input: Option<PathBuf>,
 
-    /// Output WAV file path
-    #[arg(short, long, value_name = "FILE", required_unless_present = "list_keys")]
+    /// Output WAV file path, presigned http(s) URL, or s3://bucket/key. - writes raw PCM in
+    /// --raw-format to stdout, at the rate and channel count of the render
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        required_unless_present_any = [
+            "list_presets",
+            "save_preset",
+            "spool",
+            "detect_key",
+            "project"
+        ]
+    )]
     pub output: Option<PathBuf>,
 
-    /// Musical key (0-23: C Major, G Major, ..., F Minor)
-    #[arg(short, long, default_value_t = 0, value_name = "KEY")]
+    /// Sample format of audio piped through stdin or stdout: s16le, s24le, s32le, or f32le,
+    /// as ffmpeg names them
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        required_if_eq_any = [("input", pipe::STDIO), ("output", pipe::STDIO)]
+    )]
+    pub raw_format: Option<RawFormat>,
+
+    /// Sample rate of the raw PCM piped into stdin
+    #[arg(long, value_name = "HZ", required_if_eq("input", pipe::STDIO))]
+    pub raw_rate: Option<u32>,
+
+    /// Channels interleaved in the raw PCM piped into stdin
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub raw_channels: u16,
+
+    /// Take the input, output, and settings from a project file saved by the GUI or with
+    /// --save-project. Options given on the command line override the project's
+    #[arg(long, value_name = "FILE.atproj")]
+    pub project: Option<PathBuf>,
+
+    /// Save the input, output, and settings of this run as a project file, to repeat it
+    /// later with --project or open it in the GUI (.atproj is added when it has no extension)
+    #[arg(long, value_name = "FILE.atproj", conflicts_with = "spool")]
+    pub save_project: Option<PathBuf>,
+
+    /// Start from a preset's strength, transition speed, and formant shift (see
+    /// --list-presets). Options given on the command line override the preset's
+    #[arg(short = 'p', long, value_name = "NAME")]
+    pub preset: Option<String>,
+
+    /// Save this run's strength, transition speed, and formant shift as a user preset, shared
+    /// with the GUI. Without --input it exits after saving
+    #[arg(long, value_name = "NAME", conflicts_with = "spool")]
+    pub save_preset: Option<String>,
+
+    /// List the factory and user presets and exit
+    #[arg(long)]
+    pub list_presets: bool,
+
+    /// Report the input, progress, warnings, the summary, and any error as line-delimited
+    /// JSON on stdout instead of text, for scripts and services wrapping the CLI
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "list_presets",
+            "detect_key",
+            "spool",
+            "align_to",
+            "frequencies",
+            "split_files"
+        ]
+    )]
+    pub json: bool,
+
+    /// Copy an identical earlier render of the input, one made from the same audio with the
+    /// same settings under whatever name, instead of rendering it again
+    #[arg(long, conflicts_with_all = ["spool", "align_to", "split_files"])]
+    pub reuse: bool,
+
+    #[command(flatten)]
+    pub tune: TuneArgs,
+
+    /// Correct to the nearest of these frequencies in Hz instead of to notes, for tunings no
+    /// scale describes: a list like 282.5,325.1,373.8, or a text file of them separated by
+    /// spaces or lines (# starts a comment). Nothing is repeated in other octaves
+    #[arg(
+        long,
+        value_name = "HZ,...|FILE",
+        value_parser = FrequencyList::from_arg,
+        conflicts_with_all = [
+            "note",
+            "scale",
+            "detect_key",
+            "align_to",
+            "spool",
+            "regions",
+            "export_labels",
+            "export_markers",
+            "match_tint",
+            "a4",
+            "midi"
+        ]
+    )]
+    pub frequencies: Option<FrequencyList>,
+
+    /// Suggest the keys that best fit the input's pitches. Without --output it exits after
+    /// listing them, with --output the best match is used as the key
+    #[arg(long, conflicts_with_all = ["key", "scale", "spool", "align_to"])]
+    pub detect_key: bool,
+
+    /// Only correct the labelled regions in this Audacity label file, Praat .TextGrid, or
+    /// REAPER marker .csv, and pass everything else through unchanged. Markers named after a
+    /// key, like "Key: Am", change the key from there on
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["spool", "align_to"])]
+    pub regions: Option<PathBuf>,
+
+    /// Only correct the input from this point on, in seconds (like 12.5) or samples (like
+    /// 551250smp). The audio before it passes through unchanged, crossfaded into the
+    /// correction
+    #[arg(
+        long,
+        value_name = "POSITION",
+        value_parser = parse_position,
+        conflicts_with_all = ["spool", "align_to", "frequencies"]
+    )]
+    pub start: Option<Position>,
+
+    /// Only correct the input up to this point, like --start
+    #[arg(
+        long,
+        value_name = "POSITION",
+        value_parser = parse_position,
+        conflicts_with_all = ["spool", "align_to", "frequencies"]
+    )]
+    pub end: Option<Position>,
+
+    /// Split the input into phrases at its silences and correct each of them, leaving the
+    /// silences in between as they are
+    #[arg(
+        long,
+        conflicts_with_all = ["spool", "align_to", "frequencies", "regions", "start", "end"]
+    )]
+    pub split_silence: bool,
+
+    /// With --split-silence, write each phrase to its own numbered file next to the output,
+    /// like <output>_001.wav, instead of rejoining them
+    #[arg(
+        long,
+        requires = "split_silence",
+        conflicts_with_all = ["export_labels", "export_markers", "midi", "sections"]
+    )]
+    pub split_files: bool,
+
+    /// Level below which the input counts as silent when splitting, in dBFS
+    #[arg(
+        long,
+        default_value_t = SilenceSplit::default().threshold_db,
+        value_name = "DB",
+        allow_hyphen_values = true,
+        requires = "split_silence"
+    )]
+    pub silence_threshold: f32,
+
+    /// Shortest silence that separates two phrases, in seconds
+    #[arg(
+        long,
+        default_value_t = SilenceSplit::default().min_silence_secs,
+        value_name = "SECONDS",
+        requires = "split_silence"
+    )]
+    pub min_silence: f32,
+
+    /// Correct sections of the input with settings of their own, for a song that modulates:
+    /// each region of this label file (Audacity, Praat .TextGrid, or REAPER .csv) is one,
+    /// named with the settings that change as name=value, like "Bridge key=Ebm formant=-2".
+    /// key, note, octave, and formant can be set, the rest keep the main settings
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["spool", "align_to", "frequencies"])]
+    pub sections: Option<PathBuf>,
+
+    /// Correct each moment to the note this MIDI file plays at that time instead of to the
+    /// nearest note of the key, for a melody that is known. The key (or --scale) still
+    /// applies in its rests. Drums are ignored and the highest note of a chord is followed
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["spool", "align_to"])]
+    pub midi: Option<PathBuf>,
+
+    /// After processing, write the input's notes and the stretches where no pitch was found
+    /// as labels to FILE: a Praat TextGrid for a .TextGrid extension, Audacity labels
+    /// otherwise
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["spool", "align_to"])]
+    pub export_labels: Option<PathBuf>,
+
+    /// After processing, write markers for the spots that fell back to the dry input and for
+    /// output warnings to FILE: a REAPER marker CSV for a .csv extension, otherwise like
+    /// --export-labels
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["spool", "align_to"])]
+    pub export_markers: Option<PathBuf>,
+
+    /// Also write the layers of the render next to the output, to blend in a DAW:
+    /// <output>_dry.wav with the input and <output>_corrected.wav with the correction
+    /// everywhere, both normalized with the output's gain
+    #[arg(long, conflicts_with_all = ["spool", "align_to", "frequencies"])]
+    pub stems: bool,
+
+    /// Also write the render as it came out, unnormalized 32-bit float, to
+    /// <output>_master.wav: a processing master that keeps all of its headroom for mixing,
+    /// next to the normalized output for listening
+    #[arg(long, conflicts_with_all = ["spool", "align_to"])]
+    pub float_master: bool,
+
+    /// Add harmony voices at these intervals from the corrected lead, counted in the key or
+    /// --scale: 3 is a third above, 5 a fifth, 8 an octave, and negative degrees are below,
+    /// like 3,-8. With --stems each is written as <output>_harmony_<interval>.wav too
+    #[arg(
+        long,
+        value_name = "DEGREES",
+        value_delimiter = ',',
+        allow_hyphen_values = true,
+        conflicts_with_all = ["spool", "align_to", "frequencies"]
+    )]
+    pub harmony: Vec<Interval>,
+
+    /// Level the harmony voices are mixed in at, relative to the lead (0.0-1.0). 0 leaves
+    /// them out of the output, for --stems only
+    #[arg(long, default_value_t = 0.5, value_name = "LEVEL", requires = "harmony")]
+    pub harmony_level: f32,
+
+    /// Route a voice of a stereo output: lead or a --harmony degree, with its levels in the
+    /// left and right channel (0.0-1.0), like 3=1,0.2. A harmony voice's levels scale
+    /// --harmony-level. Repeat for each voice; voices left out are centered
+    #[arg(
+        long = "route",
+        value_name = "VOICE=LEFT,RIGHT",
+        allow_hyphen_values = true,
+        conflicts_with_all = ["spool", "align_to", "frequencies"]
+    )]
+    pub routes: Vec<Route>,
+
+    /// Spread the harmony voices across a stereo output, from 0 (centered) to 1 (hard left
+    /// and right), alternating sides around the lead. --route still sets a voice's levels
+    #[arg(long, value_name = "WIDTH", requires = "harmony")]
+    pub width: Option<f32>,
+
+    /// Job spool directory. With --input/--output the job is queued there, otherwise
+    /// pending job tickets in it are processed
+    #[arg(long, value_name = "DIR", conflicts_with = "match_tint")]
+    pub spool: Option<PathBuf>,
+
+    /// Keep polling the spool for new jobs instead of exiting when it is empty
+    #[arg(long, requires = "spool")]
+    pub watch: bool,
+
+    /// Seconds between spool polls in watch mode
+    #[arg(long, default_value_t = 2, value_name = "SECONDS")]
+    pub poll_interval: u64,
+
+    /// Instead of autotuning, treat the input as a double and tighten it against this lead
+    /// take: its timing and pitch are pulled onto the lead's
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "spool",
+            "scale",
+            "output_rate",
+            "output_depth",
+            "output_channels",
+            "match_tint",
+            "a4"
+        ]
+    )]
+    pub align_to: Option<PathBuf>,
+
+    /// Furthest the double may be moved in time when aligning, in milliseconds
+    #[arg(long, default_value_t = 60.0, value_name = "MS", requires = "align_to")]
+    pub max_shift_ms: f32,
+
+    /// Largest pitch difference to the lead that is corrected when aligning, in cents.
+    /// Larger ones are left alone as different notes (0 = timing only)
+    #[arg(long, default_value_t = 50.0, value_name = "CENTS", requires = "align_to")]
+    pub max_pitch_cents: f32,
+}
+
+/// The settings a file is processed with, shared by `process` and `batch`.
+#[derive(Args)]
+pub struct TuneArgs {
+    /// Musical key, 0-23 (C Major, G Major, ..., F Minor) or a name like Am
+    #[arg(short, long, default_value_t = 0, value_name = "KEY", value_parser = parse_tune_key)]
     pub key: i32,
 
-    /// Note mode (0 = Auto snap to key, 1-12 = specific note)
-    #[arg(short, long, default_value_t = 0, value_name = "NOTE")]
+    /// Note mode: 0 or "auto" snaps to the key, a note name like A or F# (or its number, 1 =
+    /// C to 12 = B) locks everything to that note, in the --octave reference octave
+    #[arg(short, long, default_value_t = 0, value_name = "NOTE", value_parser = parse_note)]
     pub note: i32,
 
+    /// Correct to the nearest of these pitch classes (0 = C, 1 = C#, ..., 11 = B) instead of
+    /// the key's notes, e.g. 0,2,3,5,7,8,10 for C minor or 9,0,2,3,4,7 for A blues
+    #[arg(long, value_name = "NOTES", conflicts_with = "note")]
+    pub scale: Option<Scale>,
+
     /// Octave reference (0-4)
     #[arg(long, default_value_t = 2, value_name = "OCTAVE")]
     pub octave: i32,
 
+    /// Frequency of A4 the notes are tuned to, for recordings at another concert pitch like
+    /// 442 (orchestral), 415 (Baroque), or 432
+    #[arg(long, default_value_t = pipeline::STANDARD_A4, value_name = "HZ")]
+    pub a4: f32,
+
     /// Formant shift in semitones (-12 to +12)
     #[arg(short, long, default_value_t = 0, value_name = "SEMITONES")]
     pub formant: i32,
@@ -41,350 +448,3077 @@ pub struct Cli {
     #[arg(short, long, default_value_t = 0.1, value_name = "SPEED")]
     pub transition: f32,
 
-    /// FFT size (currently only 1024 is supported)
-    #[arg(long, default_value_t = 1024, value_name = "SIZE")]
-    pub fft_size: usize,
+    /// Scale the strength with the level of the voice, so loud lead lines get the full
+    /// --strength and quiet, breathy tails are corrected more gently
+    #[arg(long)]
+    pub energy_strength: bool,
+
+    /// How the strength follows the level with --energy-strength: the level in dBFS up to
+    /// which only the quiet share of the strength is applied, the level from which all of
+    /// it is, the quiet share, and optionally a shape (1 = straight, higher = stays gentle
+    /// longer)
+    #[arg(
+        long,
+        default_value_t = EnergyCurve::default(),
+        value_name = "QUIET_DB,LOUD_DB,SHARE[,SHAPE]",
+        allow_hyphen_values = true,
+        requires = "energy_strength"
+    )]
+    pub energy_curve: EnergyCurve,
+
+    /// FFT size: 512, 1024, 2048, or 4096. Larger frames resolve low voices better, smaller
+    /// ones follow fast runs more closely
+    #[arg(long, default_value_t = engine::DEFAULT_FFT_SIZE, value_name = "SIZE")]
+    pub fft_size: usize,
+
+    /// Hop size between frames, which must divide the FFT size (a quarter of it is typical)
+    #[arg(long, default_value_t = engine::DEFAULT_HOP_SIZE, value_name = "SIZE")]
+    pub hop_size: usize,
+
+    /// Window the corrected frames are crossfaded with: hann, hamming, or blackman
+    #[arg(long, default_value_t = Window::Hann, value_name = "WINDOW")]
+    pub window: Window,
+
+    /// What the last frames, past the end of the input, are filled out with: zero, or
+    /// reflect to mirror the end of the signal so a final held note stays tracked
+    #[arg(long, default_value_t = Padding::Zero, value_name = "PADDING")]
+    pub padding: Padding,
+
+    /// How the pitch is found: fft lets the library pick the note, yin and pyin choose it
+    /// from their own detection, which holds up better on low voices
+    #[arg(long = "pitch-algo", default_value_t = PitchAlgorithm::Fft, value_name = "ALGORITHM")]
+    pub pitch_algorithm: PitchAlgorithm,
+
+    /// Lowest pitch looked for in Hz, keeping a low source from being taken an octave up
+    /// (e.g. 70 for a bass voice, 38 for a bass guitar). Chooses the note like --pitch-algo
+    #[arg(long, value_name = "HZ")]
+    pub f0_min: Option<f32>,
+
+    /// Highest pitch looked for in Hz, keeping a high source from being taken an octave
+    /// down (e.g. 1100 for a soprano)
+    #[arg(long, value_name = "HZ")]
+    pub f0_max: Option<f32>,
+
+    /// Worker threads for processing (0 = all cores). More than one trades exact
+    /// frame-to-frame continuity for speed
+    #[arg(long, default_value_t = 1, value_name = "N")]
+    pub threads: usize,
+
+    /// Limit processing to roughly this many times realtime, leaving CPU for other apps
+    #[arg(long, value_name = "FACTOR")]
+    pub max_speed: Option<f32>,
+
+    /// Process at the file's own sample rate, even outside the 44.1-48kHz range the pitch
+    /// detector is tuned for (normally such files are resampled for processing and back)
+    #[arg(long)]
+    pub native_rate: bool,
+
+    /// Write the output at this sample rate in Hz instead of the input's, converting after
+    /// processing (e.g. 48000 to deliver a 44.1kHz take for video)
+    #[arg(long, value_name = "HZ")]
+    pub output_rate: Option<u32>,
+
+    /// Frames the output is written with: input for exactly as many as the input has (at
+    /// the output rate), or a number of frames to pad it to with silence or cut it off at
+    #[arg(long, default_value_t = OutputLength::Input, value_name = "FRAMES")]
+    pub output_length: OutputLength,
+
+    /// Write the output in this sample format instead of the input's: 16, 24, 32, or float
+    /// (32-bit float). 16 and 24-bit output is dithered
+    #[arg(long, value_name = "DEPTH")]
+    pub output_depth: Option<BitDepth>,
+
+    /// Write the output with this many channels instead of the input's, 1 (mono) or 2
+    /// (stereo, the mono render on both)
+    #[arg(long, value_name = "CHANNELS", value_parser = clap::value_parser!(u16).range(1..=2))]
+    pub output_channels: Option<u16>,
+
+    /// How the level of the output is set: none, clip (only brought down if it would clip),
+    /// peak[:DBFS] (peak at a level, -1 by default), or lufs[:LUFS] (integrated loudness,
+    /// -14 by default, as far as the peak allows), like lufs:-16
+    #[arg(
+        long,
+        default_value_t = Normalization::default(),
+        value_name = "MODE",
+        allow_hyphen_values = true
+    )]
+    pub normalize: Normalization,
+
+    /// Filter out rumble below this frequency in Hz before correcting (20-300, like 80),
+    /// so stands, traffic, and air conditioning aren't taken for pitch
+    #[arg(long, value_name = "HZ")]
+    pub high_pass: Option<f32>,
+
+    /// Gate the input before correcting: mute it while it stays below --gate-threshold, so
+    /// hiss and room noise between phrases aren't taken for pitch
+    #[arg(long)]
+    pub gate: bool,
+
+    /// Level in dBFS below which --gate mutes the input
+    #[arg(
+        long,
+        default_value_t = Gate::default().threshold_db,
+        value_name = "DBFS",
+        allow_hyphen_values = true,
+        requires = "gate"
+    )]
+    pub gate_threshold: f32,
+
+    /// How quickly --gate opens once the input rises above the threshold, in ms
+    #[arg(long, default_value_t = Gate::default().attack_ms, value_name = "MS", requires = "gate")]
+    pub gate_attack: f32,
+
+    /// How quickly --gate closes once the input falls below the threshold, in ms
+    #[arg(long, default_value_t = Gate::default().release_ms, value_name = "MS", requires = "gate")]
+    pub gate_release: f32,
+
+    /// Shape the corrected voice with a parametric EQ of up to 5 bands, each written as
+    /// frequency in Hz, gain in dB, and Q, like 250:-3:1.2,4000:3:0.8 to cut mud and lift
+    /// presence
+    #[arg(long, value_name = "BANDS", allow_hyphen_values = true)]
+    pub eq: Option<Equalizer>,
+
+    /// Compress the corrected voice: the level above --compress-threshold is divided by
+    /// --compress-ratio, so loud lines sit closer to quiet ones
+    #[arg(long)]
+    pub compress: bool,
+
+    /// Level in dBFS above which --compress turns the voice down
+    #[arg(
+        long,
+        default_value_t = Compressor::default().threshold_db,
+        value_name = "DBFS",
+        allow_hyphen_values = true,
+        requires = "compress"
+    )]
+    pub compress_threshold: f32,
+
+    /// How much --compress divides the level above the threshold by, like 3 for 3:1
+    #[arg(
+        long,
+        default_value_t = Compressor::default().ratio,
+        value_name = "RATIO",
+        requires = "compress"
+    )]
+    pub compress_ratio: f32,
+
+    /// How quickly --compress clamps down once the voice rises above the threshold, in ms
+    #[arg(
+        long,
+        default_value_t = Compressor::default().attack_ms,
+        value_name = "MS",
+        requires = "compress"
+    )]
+    pub compress_attack: f32,
+
+    /// How quickly --compress lets go once the voice falls back below the threshold, in ms
+    #[arg(
+        long,
+        default_value_t = Compressor::default().release_ms,
+        value_name = "MS",
+        requires = "compress"
+    )]
+    pub compress_release: f32,
+
+    /// Hold the output's peaks under this ceiling in dBFS (-12 to 0, like -1) with a
+    /// brick-wall limiter at the very end, once the level is set. Clip normalization then
+    /// leaves the level alone, and lufs goes all the way to its target
+    #[arg(long, value_name = "DBFS", allow_hyphen_values = true)]
+    pub limit: Option<f32>,
+
+    /// The order the effects run in, each of them once: high-pass, gate, autotune (the
+    /// correction), eq, and compressor. Those before autotune clean up its input and those
+    /// after it shape its output, so autotune,high-pass,gate,eq,compressor gates the
+    /// corrected output instead
+    #[arg(long, default_value_t = ChainOrder::default(), value_name = "STAGES")]
+    pub chain: ChainOrder,
+
+    /// Correct vocal fry (creak) too. Normally stretches that sound like fry pass through
+    /// unchanged, so a phrase trailing off into creak isn't pulled up into a croak
+    #[arg(long)]
+    pub correct_fry: bool,
+
+    /// Keep less of the correction, in cents, the less confidently a pitch is found in each
+    /// frame, so breathy and noisy stretches are corrected gently instead of all or nothing
+    #[arg(long)]
+    pub confidence_strength: bool,
+
+    /// How readily frames that sound unvoiced, like sibilants, breaths, and plosives, pass
+    /// through uncorrected (0.0-1.0), since hard correction makes them sound metallic.
+    /// Higher catches more borderline frames; 0 corrects them like the rest
+    #[arg(long, default_value_t = pipeline::DEFAULT_UNVOICED_SENSITIVITY, value_name = "AMOUNT")]
+    pub unvoiced_sensitivity: f32,
+
+    /// Match the output's tone to this reference vocal: its long-term spectrum is measured
+    /// and an EQ curve applied after correction, so a tuned demo sits like the reference
+    /// production
+    #[arg(long, value_name = "FILE")]
+    pub match_tint: Option<PathBuf>,
+
+    /// How far the tone is moved toward the reference (0.0-1.0)
+    #[arg(long, default_value_t = 1.0, value_name = "AMOUNT", requires = "match_tint")]
+    pub tint_amount: f32,
+}
+
+#[derive(Args)]
+pub struct AnalyzeArgs {
+    /// Audio file to analyze (WAV, MP3, M4A/AAC)
+    #[arg(value_name = "FILE", required_unless_present = "input_flag")]
+    pub input: Option<PathBuf>,
+
+    /// Audio file to analyze, given like process takes it instead
+    #[arg(short = 'i', long = "input", value_name = "FILE", conflicts_with = "input")]
+    pub input_flag: Option<PathBuf>,
+
+    /// Also write the notes and unpitched stretches as labels to FILE, in the same formats
+    /// as process --export-labels
+    #[arg(long, value_name = "FILE")]
+    pub export_labels: Option<PathBuf>,
+
+    /// Write how far each analysis frame is from the nearest note of the key to FILE as CSV:
+    /// its time, detected pitch, nearest note, and deviation in cents
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+
+    /// Key the pitch is measured against, 0-23 or a name like Am [default: the best-fitting
+    /// key]
+    #[arg(short, long, value_name = "KEY", value_parser = parse_key)]
+    pub key: Option<usize>,
+
+    /// Measure against the nearest of these pitch classes instead of a key's notes, like
+    /// process --scale
+    #[arg(long, value_name = "NOTES", conflicts_with = "key")]
+    pub scale: Option<Scale>,
+}
+
+impl AnalyzeArgs {
+    pub fn input(&self) -> &Path {
+        self.input.as_deref().or(self.input_flag.as_deref()).unwrap_or(Path::new(""))
+    }
+}
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// Folder of audio files to process. Subfolders are left alone
+    #[arg(value_name = "INPUT_DIR")]
+    pub input_dir: PathBuf,
+
+    /// Folder the results are written to as <name>_tuned.wav, created if missing
+    #[arg(value_name = "OUTPUT_DIR")]
+    pub output_dir: PathBuf,
+
+    /// Keep watching the input folder and process files as they appear, each once it has
+    /// been written completely
+    #[arg(long)]
+    pub watch: bool,
+
+    /// Seconds a new file's size and modification time have to stay the same before it
+    /// counts as written completely, in watch mode
+    #[arg(long, default_value_t = 3, value_name = "SECONDS")]
+    pub settle: u64,
+
+    /// Seconds between looks at the input folder in watch mode
+    #[arg(long, default_value_t = 2, value_name = "SECONDS")]
+    pub poll_interval: u64,
+
+    /// Copy identical earlier renders instead of rendering them again, like process --reuse
+    #[arg(long)]
+    pub reuse: bool,
+
+    #[command(flatten)]
+    pub tune: TuneArgs,
+}
+
+#[derive(Args)]
+pub struct CompareArgs {
+    /// Audio file to take the excerpt from (WAV, MP3, M4A/AAC)
+    #[arg(value_name = "FILE")]
+    pub input: PathBuf,
+
+    /// Folder the renders are written to, named after their settings, created if missing
+    #[arg(value_name = "OUTPUT_DIR")]
+    pub output_dir: PathBuf,
+
+    /// Where the excerpt starts, in seconds
+    #[arg(long, default_value_t = 0.0, value_name = "SECONDS")]
+    pub start: f32,
+
+    /// Length of the excerpt in seconds
+    #[arg(long, default_value_t = 10.0, value_name = "SECONDS")]
+    pub length: f32,
+
+    /// Correction strengths to compare, comma separated [default: 0.4,0.7,1.0]
+    #[arg(long, value_name = "LIST", value_delimiter = ',', conflicts_with = "strength")]
+    pub strengths: Vec<f32>,
+
+    /// Transition speeds to compare, comma separated [default: 0.05,0.1,0.3]
+    #[arg(long, value_name = "LIST", value_delimiter = ',', conflicts_with = "transition")]
+    pub transitions: Vec<f32>,
+
+    #[command(flatten)]
+    pub tune: TuneArgs,
+}
+
+#[cfg(feature = "scripting")]
+#[derive(Args)]
+pub struct RunScriptArgs {
+    /// Rhai script to run
+    #[arg(value_name = "SCRIPT")]
+    pub script: PathBuf,
+
+    /// Arguments for the script, in its ARGS array
+    #[arg(trailing_var_arg = true, allow_hyphen_values = true, value_name = "ARGS")]
+    pub args: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct GenerateArgs {
+    /// Folder the test files are written to
+    #[arg(default_value = ".", value_name = "DIR")]
+    pub dir: PathBuf,
+}
+
+/// Processing parameters for one file, shared by the command line and job tickets.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProcessParams {
+    pub key: i32,
+    pub note: i32,
+    pub octave: i32,
+    pub formant: i32,
+    // Overrides the key's notes when set
+    pub scale: Option<Scale>,
+    pub strength: f32,
+    pub transition: f32,
+    pub fft_size: usize,
+    pub hop_size: usize,
+    // Window the corrected frames are crossfaded with
+    pub window: Window,
+    // What the frames past the end of the input are filled out with
+    pub padding: Padding,
+    // How the pitch the note is chosen from is found, and the frequencies it is looked for in
+    pub pitch_algorithm: PitchAlgorithm,
+    pub f0_range: Option<F0Range>,
+    // Concert pitch the notes are tuned to, in Hz
+    pub a4: f32,
+    // Worker threads, 0 for one per core
+    pub threads: usize,
+    // Realtime factor limit, unlimited when None
+    pub max_speed: Option<f32>,
+    // Skip resampling to a preferred analysis rate
+    pub native_rate: bool,
+    // Correct vocal fry instead of passing it through
+    pub correct_fry: bool,
+    // Keep less of the correction where the pitch is found less surely
+    pub confidence_strength: bool,
+    // How readily unvoiced frames pass through uncorrected, 0.0 for never
+    pub unvoiced_sensitivity: f32,
+    // Share of the way the tone is matched to a reference, when there is one
+    pub tint_amount: f32,
+    // Rate the output is written at, the input's own when None
+    pub output_rate: Option<u32>,
+    // Frames the output is written with
+    pub length: OutputLength,
+    // Sample format and channel count the output is written in, the input's when None
+    pub output_depth: Option<BitDepth>,
+    pub output_channels: Option<u16>,
+    // How the level of the output is set
+    pub normalization: Normalization,
+    // Scale the strength with the level of the voice along this curve
+    pub energy: Option<EnergyCurve>,
+    // Order of the effects around the correction
+    pub chain: ChainOrder,
+    // Rumble below this cutoff is filtered out, with the high-pass on
+    pub high_pass_hz: Option<f32>,
+    // The audio is gated like this, with the gate on
+    pub gate: Option<Gate>,
+    // The voice is shaped by these bands, with the EQ on
+    pub eq: Option<Equalizer>,
+    // Loud lines are brought closer to quiet ones, with the compressor on
+    pub compressor: Option<Compressor>,
+    // Peaks are held under this ceiling in dBFS as the output is written, with the limiter
+    // on
+    pub limit_db: Option<f32>,
+}
+
+impl Default for ProcessParams {
+    fn default() -> Self {
+        Self {
+            key: 0,
+            note: 0,
+            octave: 2,
+            formant: 0,
+            scale: None,
+            strength: 0.8,
+            transition: 0.1,
+            fft_size: engine::DEFAULT_FFT_SIZE,
+            hop_size: engine::DEFAULT_HOP_SIZE,
+            window: Window::default(),
+            padding: Padding::default(),
+            pitch_algorithm: PitchAlgorithm::default(),
+            f0_range: None,
+            a4: pipeline::STANDARD_A4,
+            threads: 1,
+            max_speed: None,
+            native_rate: false,
+            correct_fry: false,
+            confidence_strength: false,
+            unvoiced_sensitivity: pipeline::DEFAULT_UNVOICED_SENSITIVITY,
+            tint_amount: 1.0,
+            output_rate: None,
+            length: OutputLength::Input,
+            output_depth: None,
+            output_channels: None,
+            normalization: Normalization::default(),
+            energy: None,
+            chain: ChainOrder::default(),
+            high_pass_hz: None,
+            gate: None,
+            eq: None,
+            compressor: None,
+            limit_db: None,
+        }
+    }
+}
+
+impl ProcessParams {
+    pub fn musical(&self) -> MusicalSettings {
+        MusicalSettings {
+            key: self.key,
+            note: self.note,
+            octave: self.octave,
+            formant: self.formant,
+        }
+    }
+
+    /// The limiter the output is written through, with it on.
+    pub fn limit(&self) -> Option<Limit> {
+        self.limit_db.map(|ceiling_db| Limit { ceiling_db, meters: None })
+    }
+
+    /// The format the output is written in, for an input whose own is `spec`.
+    pub fn output_spec(&self, spec: WavSpec) -> WavSpec {
+        let spec = WavSpec {
+            sample_rate: self.output_rate.unwrap_or(spec.sample_rate),
+            channels: self.output_channels.unwrap_or(spec.channels),
+            ..spec
+        };
+        self.output_depth.map_or(spec, |depth| depth.apply(spec))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.key < 0 || self.key >= 24 {
+            return Err(
+                "Key must be between 0 and 23. Use `autotune-cli keys` to see available keys."
+                    .to_string(),
+            );
+        }
+
+        if self.strength < 0.0 || self.strength > 1.0 {
+            return Err("Pitch correction strength must be between 0.0 and 1.0".to_string());
+        }
+
+        if self.transition < 0.01 || self.transition > 1.0 {
+            return Err("Transition speed must be between 0.01 and 1.0".to_string());
+        }
+
+        if self.formant < -12 || self.formant > 12 {
+            return Err("Formant shift must be between -12 and +12 semitones".to_string());
+        }
+
+        if self.note < 0 || self.note > 12 {
+            return Err(
+                "Note must be auto (0), a note name, or between 1 (C) and 12 (B)".to_string()
+            );
+        }
+
+        if let Some(range) = self.f0_range {
+            range.validate()?;
+        }
+
+        if self.output_rate.is_some_and(|rate| !resample::OUTPUT_RATES.contains(&rate)) {
+            return Err(format!(
+                "Output rate must be between {} and {} Hz",
+                resample::OUTPUT_RATES.start(),
+                resample::OUTPUT_RATES.end()
+            ));
+        }
+        if self.output_channels.is_some_and(|channels| channels != 1 && channels != 2) {
+            return Err("Output channels must be 1 (mono) or 2 (stereo)".to_string());
+        }
+
+        if !(0.0..=1.0).contains(&self.tint_amount) {
+            return Err("Tint amount must be between 0.0 and 1.0".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.unvoiced_sensitivity) {
+            return Err("Unvoiced sensitivity must be between 0.0 and 1.0".to_string());
+        }
+
+        if self.octave < 0 || self.octave > 4 {
+            return Err("Octave must be between 0 and 4".to_string());
+        }
+
+        if !pipeline::A4_RANGE.contains(&self.a4) {
+            return Err(format!(
+                "A4 reference must be between {} and {} Hz",
+                pipeline::A4_RANGE.start(),
+                pipeline::A4_RANGE.end()
+            ));
+        }
+
+        engine::validate_frames(self.fft_size, self.hop_size)?;
+
+        if self.max_speed.is_some_and(|speed| speed.is_nan() || speed <= 0.0) {
+            return Err("Max speed must be greater than 0".to_string());
+        }
+
+        if let Some(curve) = &self.energy {
+            curve.validate()?;
+        }
+        if let Some(hz) = self.high_pass_hz {
+            prefilter::validate_high_pass(hz)?;
+        }
+        if let Some(gate) = &self.gate {
+            gate.validate()?;
+        }
+        if let Some(eq) = &self.eq {
+            eq.validate()?;
+        }
+        if let Some(compressor) = &self.compressor {
+            compressor.validate()?;
+        }
+        if let Some(db) = self.limit_db {
+            dynamics::validate_ceiling(db)?;
+        }
+        self.normalization.validate()?;
+
+        Ok(())
+    }
+}
+
+// A --note value as the library's note mode: 0 for auto, 1 = C up to 12 = B
+fn parse_note(text: &str) -> Result<i32, String> {
+    if text.trim().eq_ignore_ascii_case("auto") {
+        return Ok(0);
+    }
+    if let Ok(note) = text.trim().parse() {
+        return Ok(note);
+    }
+    keys::parse_note_name(text)
+        .map(|pitch_class| pitch_class as i32 + 1)
+        .ok_or_else(|| format!("'{}' is neither auto, a note name, nor a note number", text))
+}
+
+// A --key value as the library's key: its number in `KEY_NAMES`, or its name
+fn parse_key(text: &str) -> Result<usize, String> {
+    match text.trim().parse::<usize>() {
+        Ok(key) if key < KEY_NAMES.len() => Ok(key),
+        Ok(_) => Err("Key must be between 0 and 23".to_string()),
+        Err(_) => keys::parse_key_name(text)
+            .ok_or_else(|| format!("'{}' is neither a key number nor a key name", text)),
+    }
+}
+
+// The same as the key number `ProcessParams` take
+fn parse_tune_key(text: &str) -> Result<i32, String> {
+    parse_key(text).map(|key| key as i32)
+}
+
+/// A point in the input, given in seconds or in samples at the input's rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Position {
+    Secs(f32),
+    Samples(u64),
+}
+
+impl Position {
+    pub fn secs(self, sample_rate: u32) -> f32 {
+        match self {
+            Position::Secs(secs) => secs,
+            Position::Samples(samples) => samples as f32 / sample_rate as f32,
+        }
+    }
+}
+
+// A --start or --end value: seconds with an optional "s", or samples with "smp"
+fn parse_position(text: &str) -> Result<Position, String> {
+    let text = text.trim();
+    let invalid =
+        || format!("'{}' is neither seconds (like 12.5) nor samples (like 44100smp)", text);
+    if let Some(samples) = text.strip_suffix("smp") {
+        return samples.trim().parse().map(Position::Samples).map_err(|_| invalid());
+    }
+    let secs: f32 = text.strip_suffix('s').unwrap_or(text).trim().parse().map_err(|_| invalid())?;
+    if !secs.is_finite() || secs < 0.0 {
+        return Err(invalid());
+    }
+    Ok(Position::Secs(secs))
+}
+
+impl ProcessArgs {
+    pub fn align_limits(&self) -> Result<AlignLimits, String> {
+        if !(0.0..=500.0).contains(&self.max_shift_ms) {
+            return Err("Max shift must be between 0 and 500 ms".to_string());
+        }
+        if !(0.0..=1200.0).contains(&self.max_pitch_cents) {
+            return Err("Max pitch difference must be between 0 and 1200 cents".to_string());
+        }
+        Ok(AlignLimits {
+            max_shift_secs: self.max_shift_ms / 1000.0,
+            max_pitch_cents: self.max_pitch_cents,
+        })
+    }
+
+    /// Take the files and settings of `project` that `matches` didn't get on the command
+    /// line.
+    pub fn apply_project(&mut self, project: &ProjectFile, matches: &ArgMatches) {
+        let take = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        if self.input.is_none() {
+            self.input = project.input.clone();
+        }
+        if self.output.is_none() {
+            self.output = project.output.clone();
+        }
+        if self.midi.is_none() {
+            self.midi = project.midi.clone();
+        }
+        if take("key") {
+            self.tune.key = project.key;
+        }
+        // A note or a scale on the command line replaces the project's choice of either
+        if take("note") && take("scale") {
+            self.tune.note = project.note;
+            self.tune.scale = project.scale;
+        }
+        if take("octave") {
+            self.tune.octave = project.octave;
+        }
+        if take("a4") {
+            self.tune.a4 = project.a4;
+        }
+        if take("formant") {
+            self.tune.formant = project.formant;
+        }
+        if take("strength") {
+            self.tune.strength = project.strength;
+        }
+        if take("transition") {
+            self.tune.transition = project.transition;
+        }
+        if take("fft_size") {
+            self.tune.fft_size = project.fft_size;
+        }
+        if take("hop_size") {
+            self.tune.hop_size = project.hop_size;
+        }
+        if take("window") {
+            self.tune.window = project.window;
+        }
+        if take("padding") {
+            self.tune.padding = project.padding;
+        }
+        if take("pitch_algorithm") {
+            self.tune.pitch_algorithm = project.pitch_algorithm;
+        }
+        // Either bound on the command line replaces the project's range
+        if self.tune.f0_min.is_none() && self.tune.f0_max.is_none() {
+            if let Some(range) = project.f0_range {
+                self.tune.f0_min = Some(range.min);
+                self.tune.f0_max = Some(range.max);
+            }
+        }
+        if take("correct_fry") {
+            self.tune.correct_fry = project.correct_fry;
+        }
+        if take("confidence_strength") {
+            self.tune.confidence_strength = project.confidence_strength;
+        }
+        if take("unvoiced_sensitivity") {
+            self.tune.unvoiced_sensitivity = project.unvoiced_sensitivity;
+        }
+        if self.tune.match_tint.is_none() {
+            self.tune.match_tint = project.tint.clone();
+        }
+        if take("tint_amount") {
+            self.tune.tint_amount = project.tint_amount;
+        }
+        if self.tune.output_rate.is_none() {
+            self.tune.output_rate = project.output_rate;
+        }
+        if take("output_length") {
+            self.tune.output_length = project.length;
+        }
+        if self.tune.output_depth.is_none() {
+            self.tune.output_depth = project.output_depth;
+        }
+        if self.tune.output_channels.is_none() {
+            self.tune.output_channels = project.output_channels;
+        }
+        if take("normalize") {
+            self.tune.normalize = project.normalization;
+        }
+        if take("energy_strength") {
+            self.tune.energy_strength = project.energy.is_some();
+        }
+        if let Some(curve) = project.energy.filter(|_| take("energy_curve")) {
+            self.tune.energy_curve = curve;
+        }
+        if take("chain") {
+            self.tune.chain = project.chain;
+        }
+        if self.tune.high_pass.is_none() {
+            self.tune.high_pass = project.high_pass_hz;
+        }
+        if take("gate") {
+            self.tune.gate = project.gate.is_some();
+        }
+        if let Some(gate) = project.gate {
+            if take("gate_threshold") {
+                self.tune.gate_threshold = gate.threshold_db;
+            }
+            if take("gate_attack") {
+                self.tune.gate_attack = gate.attack_ms;
+            }
+            if take("gate_release") {
+                self.tune.gate_release = gate.release_ms;
+            }
+        }
+        if self.tune.eq.is_none() {
+            self.tune.eq = project.eq;
+        }
+        if take("compress") {
+            self.tune.compress = project.compressor.is_some();
+        }
+        if let Some(compressor) = project.compressor {
+            if take("compress_threshold") {
+                self.tune.compress_threshold = compressor.threshold_db;
+            }
+            if take("compress_ratio") {
+                self.tune.compress_ratio = compressor.ratio;
+            }
+            if take("compress_attack") {
+                self.tune.compress_attack = compressor.attack_ms;
+            }
+            if take("compress_release") {
+                self.tune.compress_release = compressor.release_ms;
+            }
+        }
+        if self.tune.limit.is_none() {
+            self.tune.limit = project.limit_db;
+        }
+    }
+
+    /// Take the settings of `preset` that `matches` didn't get on the command line.
+    pub fn apply_preset(&mut self, preset: &Preset, matches: &ArgMatches) {
+        let take = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        if take("strength") {
+            self.tune.strength = preset.strength;
+        }
+        if take("transition") {
+            self.tune.transition = preset.transition;
+        }
+        if take("formant") {
+            self.tune.formant = preset.formant;
+        }
+    }
+
+    pub fn project_file(&self, params: &ProcessParams) -> ProjectFile {
+        ProjectFile {
+            input: self.input.clone(),
+            output: self.output.clone(),
+            key: params.key,
+            note: params.note,
+            octave: params.octave,
+            formant: params.formant,
+            scale: params.scale,
+            strength: params.strength,
+            transition: params.transition,
+            fft_size: params.fft_size,
+            hop_size: params.hop_size,
+            window: params.window,
+            padding: params.padding,
+            pitch_algorithm: params.pitch_algorithm,
+            f0_range: params.f0_range,
+            a4: params.a4,
+            correct_fry: params.correct_fry,
+            confidence_strength: params.confidence_strength,
+            unvoiced_sensitivity: params.unvoiced_sensitivity,
+            tint: self.tune.match_tint.clone(),
+            tint_amount: params.tint_amount,
+            output_rate: params.output_rate,
+            length: params.length,
+            output_depth: params.output_depth,
+            output_channels: params.output_channels,
+            normalization: params.normalization,
+            energy: params.energy,
+            chain: params.chain,
+            high_pass_hz: params.high_pass_hz,
+            gate: params.gate,
+            eq: params.eq,
+            compressor: params.compressor,
+            limit_db: params.limit_db,
+            midi: self.midi.clone(),
+            ..Default::default()
+        }
+    }
+}
+
+impl TuneArgs {
+    pub fn params(&self) -> ProcessParams {
+        ProcessParams {
+            key: self.key,
+            note: self.note,
+            octave: self.octave,
+            formant: self.formant,
+            scale: self.scale,
+            strength: self.strength,
+            transition: self.transition,
+            fft_size: self.fft_size,
+            hop_size: self.hop_size,
+            window: self.window,
+            padding: self.padding,
+            pitch_algorithm: self.pitch_algorithm,
+            f0_range: (self.f0_min.is_some() || self.f0_max.is_some())
+                .then(|| F0Range::new(self.f0_min, self.f0_max)),
+            a4: self.a4,
+            threads: self.threads,
+            max_speed: self.max_speed,
+            native_rate: self.native_rate,
+            correct_fry: self.correct_fry,
+            confidence_strength: self.confidence_strength,
+            unvoiced_sensitivity: self.unvoiced_sensitivity,
+            tint_amount: self.tint_amount,
+            output_rate: self.output_rate,
+            length: self.output_length,
+            output_depth: self.output_depth,
+            output_channels: self.output_channels,
+            normalization: self.normalize,
+            energy: self.energy_strength.then_some(self.energy_curve),
+            chain: self.chain,
+            high_pass_hz: self.high_pass,
+            gate: self.gate.then_some(Gate {
+                threshold_db: self.gate_threshold,
+                attack_ms: self.gate_attack,
+                release_ms: self.gate_release,
+            }),
+            eq: self.eq,
+            compressor: self.compress.then_some(Compressor {
+                threshold_db: self.compress_threshold,
+                ratio: self.compress_ratio,
+                attack_ms: self.compress_attack,
+                release_ms: self.compress_release,
+            }),
+            limit_db: self.limit,
+        }
+    }
+}
+
+// Best matches listed by --detect-key and analyze
+const KEY_SUGGESTIONS: usize = 3;
+// Share of the pitched frames left out at each end of the range analyze reports, so a few
+// stray detections don't stretch it
+const RANGE_OUTLIERS: f32 = 0.02;
+// Added to the file names batch writes
+const TUNED_SUFFIX: &str = "_tuned";
+// Frames copied at a time when splitting phrases out of the input
+const PHRASE_BLOCK: usize = 1 << 16;
+// What `compare` tries when not told: gentle to hard correction, smooth to snappy
+const COMPARE_STRENGTHS: [f32; 3] = [0.4, 0.7, 1.0];
+const COMPARE_TRANSITIONS: [f32; 3] = [0.05, 0.1, 0.3];
+
+pub const KEY_NAMES: [&str; 24] = [
+    "C Major", "G Major", "D Major", "A Major", "E Major", "B Major", "F# Major", "C# Major",
+    "F Major", "Bb Major", "Eb Major", "Ab Major", "A Minor", "E Minor", "B Minor", "F# Minor",
+    "C# Minor", "G# Minor", "D# Minor", "A# Minor", "D Minor", "G Minor", "C Minor", "F Minor",
+];
+
+pub fn run_cli() -> Result<(), Box<dyn std::error::Error>> {
+    let matches = Cli::command().get_matches_from(with_default_subcommand(std::env::args_os()));
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    if cli.io_block_size == 0 {
+        return Err(Failed::new(Failure::Arguments, "I/O block size must be at least 1 KiB").into());
+    }
+    paths::set_io_block_size(cli.io_block_size * 1024);
+    let size_units = if cli.si_units {
+        SizeUnits::Si
+    } else {
+        SizeUnits::Binary
+    };
+    format::set(Format::new(FormatSettings { size_units, ..Default::default() }));
+    if cli.scratch_cap == Some(0) {
+        return Err(Failed::new(Failure::Arguments, "Scratch cap must be at least 1 MiB").into());
+    }
+    scratch::set(ScratchSettings { dir: cli.scratch_dir, cap_mib: cli.scratch_cap });
+    let config = ConfigLocation::locate(cli.portable);
+    fingerprint::set_index(Some(config.file(app_config::RENDER_INDEX_FILE)));
+
+    // Flushed when dropped at the end of the run
+    let _trace = cli.trace_file.as_deref().map(profiling::start_trace).transpose()?;
+
+    match cli.command {
+        Command::Process(args) => {
+            let matches = matches.subcommand_matches("process").unwrap_or(&matches);
+            run_process(args, matches, cli.verbose, cli.portable)
+        }
+        Command::Analyze(args) => analyze_file(&args),
+        Command::Batch(args) => run_batch(&args, cli.verbose),
+        Command::Keys => {
+            println!("Available Keys:");
+            for (i, name) in KEY_NAMES.iter().enumerate() {
+                println!("  {}: {}", i, name);
+            }
+            Ok(())
+        }
+        Command::Generate(args) => generate_test_files(&args.dir),
+        Command::Compare(args) => run_compare(&args, cli.verbose),
+        #[cfg(feature = "scripting")]
+        Command::RunScript(args) => script::run(&args.script, &args.args, cli.verbose),
+    }
+}
+
+/// `args` with `process` put in front when they don't start with a subcommand, so command
+/// lines from before there were subcommands keep working.
+pub fn with_default_subcommand(args: impl IntoIterator<Item = OsString>) -> Vec<OsString> {
+    let mut args: Vec<OsString> = args.into_iter().collect();
+    let command = Cli::command();
+    // Global options may come before the subcommand, some with a value after them
+    let mut first = 1;
+    while let Some(arg) = args.get(first).and_then(|arg| arg.to_str()) {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, _)) => (name, true),
+            None => (arg, false),
+        };
+        let Some(global) = command.get_arguments().find(|global| {
+            global.is_global_set()
+                && (global.get_long().is_some_and(|long| name.strip_prefix("--") == Some(long))
+                    || global.get_short().is_some_and(|short| name == format!("-{}", short)))
+        }) else {
+            break;
+        };
+        first += if global.get_action().takes_values() && !inline_value { 2 } else { 1 };
+    }
+    let named = args.get(first).and_then(|arg| arg.to_str()).is_some_and(|first| {
+        matches!(first, "help" | "-h" | "--help" | "-V" | "--version")
+            || command.get_subcommands().any(|subcommand| subcommand.get_name() == first)
+    });
+    if args.len() > first && !named {
+        args.insert(1, "process".into());
+    }
+    args
+}
+
+fn run_process(
+    mut args: ProcessArgs,
+    matches: &ArgMatches,
+    verbose: bool,
+    portable: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Nothing but the events goes to stdout with JSON output, and nothing but the audio
+    // when it is piped out
+    let to_stdout = args.output.as_deref().is_some_and(pipe::is_stdio);
+    if to_stdout && args.json {
+        let message = "--json can't share stdout with the piped output";
+        return Err(Failed::new(Failure::Arguments, message).into());
+    }
+    let piped = to_stdout || args.input.as_deref().is_some_and(pipe::is_stdio);
+    let other_mode = args.spool.is_some() || args.align_to.is_some() || args.frequencies.is_some();
+    if piped && (other_mode || args.split_files || args.detect_key) {
+        let message = "Piped audio (-) only works for a plain render";
+        return Err(Failed::new(Failure::Arguments, message).into());
+    }
+    report::set_json(args.json);
+    let verbose = verbose && !args.json && !to_stdout;
+    progress::set_shown(!to_stdout);
+    if let Some(path) = args.project.clone() {
+        let project = ProjectFile::load(&path).or_fail(Failure::Arguments)?;
+        args.apply_project(&project, matches);
+    }
+
+    let config = ConfigLocation::locate(portable);
+    let mut user_presets = presets::load_user_presets(&config);
+    if args.list_presets {
+        list_presets(&user_presets, &config);
+        return Ok(());
+    }
+    // On top of a project, as it was asked for more specifically
+    if let Some(name) = args.preset.clone() {
+        let preset = presets::find(&user_presets, &name)
+            .ok_or_else(|| format!("Unknown preset '{}'. Use --list-presets to see them.", name))
+            .or_fail(Failure::Arguments)?;
+        args.apply_preset(&preset, matches);
+    }
+
+    // Validate arguments
+    let mut params = args.tune.params();
+    params.validate().or_fail(Failure::Arguments)?;
+    if !(0.0..=1.0).contains(&args.harmony_level) {
+        return Err(
+            Failed::new(Failure::Arguments, "Harmony level must be between 0.0 and 1.0").into()
+        );
+    }
+    if !args.harmony.is_empty() && args.harmony_level == 0.0 && !args.stems {
+        report::warning("a harmony level of 0 without --stems leaves the harmony out");
+    }
+    if args.width.is_some_and(|width| !routing::WIDTH_RANGE.contains(&width)) {
+        return Err(Failed::new(Failure::Arguments, "Width must be between 0.0 and 1.0").into());
+    }
+    for route in &args.routes {
+        if let Voice::Harmony(interval) = route.voice {
+            if !args.harmony.contains(&interval) {
+                let message =
+                    format!("--route {}: there is no --harmony voice {}", route, interval.degree());
+                return Err(Failed::new(Failure::Arguments, message).into());
+            }
+        }
+    }
+    let split =
+        SilenceSplit { threshold_db: args.silence_threshold, min_silence_secs: args.min_silence };
+    split.validate().or_fail(Failure::Arguments)?;
+
+    if args.detect_key {
+        let input_path =
+            args.input.as_ref().ok_or("Input file is required").or_fail(Failure::Arguments)?;
+        params.key = detect_key(input_path)? as i32;
+        if args.output.is_none() {
+            return Ok(());
+        }
+    }
+
+    if let Some(name) = &args.save_preset {
+        let preset = Preset::new(name, params.strength, params.transition, params.formant);
+        presets::upsert(&mut user_presets, preset);
+        presets::save_user_presets(&config, &user_presets)?;
+        if !args.json && !to_stdout {
+            println!("Saved preset '{}'", name);
+        }
+        if args.input.is_none() {
+            return Ok(());
+        }
+    }
+
+    if let Some(path) = &args.save_project {
+        // So the GUI recognizes it when it is opened there
+        let path = match path.extension() {
+            Some(_) => path.clone(),
+            None => path.with_extension(project_file::EXTENSION),
+        };
+        args.project_file(&params).save(&path).or_fail(Failure::Write)?;
+        if verbose {
+            println!("Saved project: {}", path.display());
+        }
+    }
+
+    if let Some(spool_dir) = &args.spool {
+        return match (&args.input, &args.output) {
+            (Some(input), Some(output)) => enqueue_job(spool_dir, input, output, params),
+            (None, None) => run_spool(spool_dir, &args, verbose),
+            _ => {
+                let message = "Queueing a job needs both --input and --output";
+                Err(Failed::new(Failure::Arguments, message).into())
+            }
+        };
+    }
+
+    if let Some(lead) = &args.align_to {
+        let limits = args.align_limits().or_fail(Failure::Arguments)?;
+        let input_path =
+            args.input.as_ref().ok_or("Input file is required").or_fail(Failure::Arguments)?;
+        let output_path =
+            args.output.as_ref().ok_or("Output file is required").or_fail(Failure::Arguments)?;
+        align_file(lead, input_path, output_path, &limits, verbose)?;
+        if !verbose {
+            println!("Alignment complete: {} -> {}", input_path.display(), output_path.display());
+        }
+        return Ok(());
+    }
+
+    if let Some(targets) = &args.frequencies {
+        let input_path =
+            args.input.as_ref().ok_or("Input file is required").or_fail(Failure::Arguments)?;
+        let output_path =
+            args.output.as_ref().ok_or("Output file is required").or_fail(Failure::Arguments)?;
+        tune_file(targets, input_path, output_path, &params, verbose)?;
+        if !verbose {
+            println!("Tuning complete: {} -> {}", input_path.display(), output_path.display());
+        }
+        return Ok(());
+    }
+
+    // Validate required arguments, which a project may leave to the command line
+    let required = |what: &str| match &args.project {
+        Some(project) => format!("{} file is required, {} has none", what, project.display()),
+        None => format!("{} file is required", what),
+    };
+    let input_path =
+        args.input.as_ref().ok_or_else(|| required("Input")).or_fail(Failure::Arguments)?;
+    let output_path =
+        args.output.as_ref().ok_or_else(|| required("Output")).or_fail(Failure::Arguments)?;
+
+    if verbose {
+        println!("🎵 Autotune CLI Processor");
+        println!("========================");
+        println!("Input: {}", input_path.display());
+        println!("Output: {}", output_path.display());
+        match params.scale {
+            Some(scale) => println!("Scale: {}", scale),
+            None => println!("Key: {} ({})", params.key, KEY_NAMES[params.key as usize]),
+        }
+        println!(
+            "Note Mode: {}",
+            if params.note == 0 {
+                "Auto".to_string()
+            } else {
+                format!("Locked to {}", labels::NOTE_NAMES[params.note as usize - 1])
+            }
+        );
+        if let Some(midi) = &args.midi {
+            println!("Melody: {}", midi.display());
+        }
+        if !args.harmony.is_empty() {
+            let intervals: Vec<String> = args.harmony.iter().map(Interval::to_string).collect();
+            println!("Harmony: {} at {:.0}%", intervals.join(", "), args.harmony_level * 100.0);
+        }
+        if !args.routes.is_empty() {
+            let routes: Vec<String> = args.routes.iter().map(Route::to_string).collect();
+            println!("Stereo Routing: {}", routes.join(" "));
+        }
+        if let Some(width) = args.width {
+            println!("Stereo Width: {:.0}%", width * 100.0);
+        }
+        println!("Octave: {}", args.tune.octave);
+        println!("A4 Reference: {} Hz", args.tune.a4);
+        println!("Formant Shift: {} semitones", args.tune.formant);
+        println!("Pitch Correction: {:.1}%", args.tune.strength * 100.0);
+        if args.tune.energy_strength {
+            let curve = args.tune.energy_curve;
+            println!(
+                "Energy Strength: {:.0}% of it below {} dBFS, all of it from {} dBFS",
+                curve.quiet_strength * 100.0,
+                curve.quiet_db,
+                curve.loud_db
+            );
+        }
+        println!("Transition Speed: {:.2}", args.tune.transition);
+        if let Some(hz) = args.tune.high_pass {
+            println!("High-Pass: {} Hz", hz);
+        }
+        if args.tune.gate {
+            println!(
+                "Noise Gate: below {} dBFS, {} ms attack, {} ms release",
+                args.tune.gate_threshold, args.tune.gate_attack, args.tune.gate_release
+            );
+        }
+        if let Some(eq) = &args.tune.eq {
+            let bands: Vec<String> = eq
+                .active()
+                .map(|band| format!("{:+} dB at {} Hz (Q {})", band.gain_db, band.freq_hz, band.q))
+                .collect();
+            println!(
+                "EQ: {}",
+                if bands.is_empty() {
+                    "flat".to_string()
+                } else {
+                    bands.join(", ")
+                }
+            );
+        }
+        if args.tune.compress {
+            println!(
+                "Compressor: above {} dBFS at {}:1, {} ms attack, {} ms release",
+                args.tune.compress_threshold,
+                args.tune.compress_ratio,
+                args.tune.compress_attack,
+                args.tune.compress_release
+            );
+        }
+        if let Some(db) = args.tune.limit {
+            println!("Limiter: peaks held under {} dBFS", db);
+        }
+        if args.tune.high_pass.is_some()
+            || args.tune.gate
+            || args.tune.eq.is_some()
+            || args.tune.compress
+        {
+            let stages: Vec<&str> =
+                args.tune.chain.stages().iter().map(|stage| stage.name()).collect();
+            println!("Effects Chain: {}", stages.join(" → "));
+        }
+        if args.tune.unvoiced_sensitivity > 0.0 {
+            println!(
+                "Unvoiced Bypass: sensitivity {:.2}, consonants and breaths pass through",
+                args.tune.unvoiced_sensitivity
+            );
+        }
+        if let Some(max_speed) = args.tune.max_speed {
+            println!("Speed Limit: {:.1}x real-time", max_speed);
+        }
+        println!();
+    }
+
+    let side_files = SideFiles {
+        regions: args.regions.clone(),
+        start: args.start,
+        end: args.end,
+        split: args.split_silence.then_some(split),
+        sections: args.sections.clone(),
+        export: args.export_labels.clone(),
+        markers: args.export_markers.clone(),
+        tint: args.tune.match_tint.clone(),
+        midi: args.midi.clone(),
+        stems: args.stems,
+        float_master: args.float_master,
+        harmony: args.harmony.clone(),
+        harmony_level: args.harmony_level,
+        // The routes asked for take over from the spread
+        routing: args
+            .width
+            .map_or(Vec::new(), |width| routing::spread(&args.harmony, width))
+            .into_iter()
+            .chain(args.routes.iter().copied())
+            .collect(),
+        reuse: args.reuse,
+        raw: args.raw_format.map(|format| RawPcm {
+            format,
+            sample_rate: args.raw_rate,
+            channels: args.raw_channels,
+        }),
+    };
+    if args.split_files {
+        let written = process_phrases(input_path, output_path, &params, &side_files, verbose)?;
+        println!(
+            "Autotune processing complete: {} -> {} phrase file(s)",
+            input_path.display(),
+            written.len()
+        );
+        return Ok(());
+    }
+    process_location(input_path, output_path, &params, &side_files, verbose)?;
+
+    if !verbose && !args.json && !to_stdout {
+        println!(
+            "Autotune processing complete: {} -> {}",
+            input_path.display(),
+            output_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Print the format, content, pitch range, and best-fitting keys of `input`, and write its
+/// notes as labels to `export_labels`.
+fn analyze_file(args: &AnalyzeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let input = args.input();
+    let info = decoder::probe(input).or_fail(Failure::Decode)?;
+    let track = keys::track_file(input).or_fail(Failure::Decode)?;
+    let duration = info.duration_secs().unwrap_or(track.frames.len() as f32 * track.hop_secs);
+    println!("📊 {}", input.display());
+    let size = std::fs::metadata(input)
+        .map_or(String::new(), |file| format!(", {}", format::file_size(file.len())));
+    println!(
+        "Format: {}, {} channel(s), {}-bit, {}{}",
+        format::sample_rate(info.sample_rate as f32),
+        info.channels,
+        info.bits_per_sample,
+        format::duration(duration),
+        size
+    );
+
+    let (excerpt, _) =
+        decoder::read_mono(input, 30 * info.sample_rate as usize).or_fail(Failure::Decode)?;
+    match classifier::classify(&excerpt, info.sample_rate as f32) {
+        Some(classification) => {
+            let class = classification.class;
+            println!("Content: {}", class.label());
+            println!(
+                "  Suggested: strength {:.1}, transition {:.2}",
+                class.suggested_strength(),
+                class.suggested_transition()
+            );
+            if let Some(warning) = class.warning() {
+                println!("  ⚠️  {}", warning);
+            }
+            let features = classification.features;
+            match (features.noise_floor_db, features.snr_db) {
+                (Some(floor), Some(snr)) => {
+                    println!("Noise floor: {:.0} dBFS, SNR {:.0} dB", floor, snr)
+                }
+                _ => println!("Noise floor: no pauses to measure it in"),
+            }
+            if let Some(warning) = features.noise_warning() {
+                println!("  ⚠️  {}", warning);
+            }
+        }
+        None => println!("Content: too short or quiet to classify"),
+    }
+
+    let mut notes: Vec<f32> =
+        track.frames.iter().filter_map(|frame| frame.f0).map(classifier::note_number).collect();
+    let audible = track.frames.iter().filter(|frame| !frame.is_silent()).count();
+    if notes.is_empty() {
+        println!("Pitch: none found");
+    } else {
+        notes.sort_by(f32::total_cmp);
+        let note_at = |share: f32| {
+            let note = notes[((notes.len() - 1) as f32 * share).round() as usize];
+            labels::note_name(note.round() as i32)
+        };
+        println!(
+            "Pitch: {} to {}, centered on {}, pitched {:.0}% of the audible time",
+            note_at(RANGE_OUTLIERS),
+            note_at(1.0 - RANGE_OUTLIERS),
+            note_at(0.5),
+            notes.len() as f32 / audible.max(1) as f32 * 100.0
+        );
+    }
+
+    let key_matches = keys::rank_keys(&track);
+    if key_matches.is_empty() {
+        println!("Keys: not enough pitched audio to suggest one");
+    } else {
+        println!("🎼 Key suggestions:");
+        print_key_suggestions(&key_matches);
+    }
+
+    // Measured against the key asked for, or the one that fits best
+    let key = args.key.or(key_matches.first().map(|best| best.key)).unwrap_or(0);
+    let (scale, against) = match args.scale {
+        Some(scale) => (scale, "--scale"),
+        None => (Scale::from_key(key), KEY_NAMES[key]),
+    };
+    let report = IntonationReport::measure(&track, &scale);
+    match (report.median_deviation(), report.in_tune_share()) {
+        (Some(median), Some(in_tune)) => println!(
+            "Intonation against {}: median {:.0} cents off, {:.0}% within {:.0} cents",
+            against,
+            median,
+            in_tune * 100.0,
+            intonation::IN_TUNE_CENTS
+        ),
+        _ => println!("Intonation: no pitched frames to measure"),
+    }
+
+    if let Some(path) = &args.export_labels {
+        let segments = labels::note_segments(&track);
+        labels::write_file(path, &segments, duration).or_fail(Failure::Write)?;
+        println!("Exported {} labels to {}", segments.len(), path.display());
+    }
+    if let Some(path) = &args.report {
+        report.write_csv(path).or_fail(Failure::Write)?;
+        println!("Wrote the pitch of {} frames to {}", report.frames.len(), path.display());
+    }
+    Ok(())
+}
+
+/// Process every audio file directly in the input folder into the output folder, carrying
+/// on past files that fail.
+fn run_batch(args: &BatchArgs, verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let params = args.tune.params();
+    params.validate().or_fail(Failure::Arguments)?;
+
+    let inputs = batch_inputs(args)?;
+    if inputs.is_empty() && !args.watch {
+        return Err(format!("No audio files in {}", args.input_dir.display()).into());
+    }
+    std::fs::create_dir_all(&args.output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", args.output_dir.display(), e))?;
+
+    let side_files =
+        SideFiles { tint: args.tune.match_tint.clone(), reuse: args.reuse, ..Default::default() };
+    if args.watch {
+        return watch_batch(args, &params, &side_files, verbose);
+    }
+    // How each of the files that failed did
+    let mut failed = Vec::new();
+    for (index, input) in inputs.iter().enumerate() {
+        failure::check_interrupted()?;
+        let output = batch_output(input, &args.output_dir);
+        println!("[{}/{}] {} -> {}", index + 1, inputs.len(), input.display(), output.display());
+        if let Err(e) = process_location(input, &output, &params, &side_files, verbose) {
+            eprintln!("Failed {}: {}", input.display(), e);
+            failure::check_interrupted()?;
+            failed.push(failure::class_of(&*e));
+        }
+    }
+
+    let processed = inputs.len() - failed.len();
+    println!("Batch complete: {} of {} file(s) processed", processed, inputs.len());
+    if let Some(&first) = failed.first() {
+        let message = format!("{} file(s) failed", failed.len());
+        // Files that all failed the same way exit like one of them would
+        return match first.filter(|_| failed.iter().all(|&other| other == first)) {
+            Some(class) => Err(Failed::new(class, message).into()),
+            None => Err(message.into()),
+        };
+    }
+    Ok(())
+}
+
+// The audio files in the batch's input folder, sorted
+fn batch_inputs(args: &BatchArgs) -> Result<Vec<PathBuf>, String> {
+    let same_dir =
+        paths::normalize_for_io(&args.input_dir) == paths::normalize_for_io(&args.output_dir);
+    let mut inputs = audio_files(&args.input_dir)?;
+    // Earlier results in the same folder aren't inputs
+    inputs.retain(|path| {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        !(same_dir && stem.ends_with(TUNED_SUFFIX))
+    });
+    Ok(inputs)
+}
+
+/// The files in `dir` that can be processed, sorted. Subfolders are left alone.
+pub fn audio_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && decoder::is_supported_input(path))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Process the files in the batch's input folder, and those that appear in it later, until
+/// interrupted. Each waits until it has stopped changing, so a file still being copied in
+/// isn't read half written, and one that turns out to have been still written to when it
+/// failed is retried once it settles again.
+fn watch_batch(
+    args: &BatchArgs,
+    params: &ProcessParams,
+    side_files: &SideFiles,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    println!("👀 Watching {} for audio files", args.input_dir.display());
+    let mut files = StableFiles::new(Duration::from_secs(args.settle));
+    loop {
+        // Ctrl-C while waiting is how watching ends
+        if failure::interrupted() {
+            return Ok(());
+        }
+        for input in files.ready(&batch_inputs(args)?, Instant::now()) {
+            let output = batch_output(&input, &args.output_dir);
+            println!("{} -> {}", input.display(), output.display());
+            if let Err(e) = process_location(&input, &output, params, side_files, verbose) {
+                failure::check_interrupted()?;
+                if files.changed_since_ready(&input) {
+                    eprintln!(
+                        "{} was still being written, retrying once it settles",
+                        input.display()
+                    );
+                } else {
+                    eprintln!("Failed {}: {}", input.display(), e);
+                }
+            }
+        }
+        thread::sleep(Duration::from_secs(args.poll_interval.max(1)));
+    }
+}
+
+// Where `batch` writes the result for `input`
+fn batch_output(input: &Path, output_dir: &Path) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    output_dir.join(format!("{}{}.wav", stem, TUNED_SUFFIX))
+}
+
+/// Render the excerpt `args` pick once for every strength and transition speed pair, the
+/// other settings as given, and list what was written where.
+fn run_compare(args: &CompareArgs, verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let strengths = if args.strengths.is_empty() {
+        &COMPARE_STRENGTHS[..]
+    } else {
+        &args.strengths
+    };
+    let transitions = if args.transitions.is_empty() {
+        &COMPARE_TRANSITIONS[..]
+    } else {
+        &args.transitions
+    };
+    let base = args.tune.params();
+    let grid: Vec<ProcessParams> = strengths
+        .iter()
+        .flat_map(|&strength| {
+            transitions.iter().map(move |&transition| ProcessParams {
+                strength,
+                transition,
+                ..base
+            })
+        })
+        .collect();
+    for params in &grid {
+        params.validate().or_fail(Failure::Arguments)?;
+    }
+    if !(args.start >= 0.0 && args.length > 0.0) {
+        let message = "The excerpt needs a start of 0 or later and a length above 0";
+        return Err(Failed::new(Failure::Arguments, message).into());
+    }
+
+    let info = decoder::probe(&args.input).or_fail(Failure::Decode)?;
+    info.check_supported().or_fail(Failure::UnsupportedFormat)?;
+    let spec = info.output_spec();
+    let rate = spec.sample_rate;
+    let frames = |secs: f32| (secs * rate as f32).round() as usize;
+    let (samples, _) = decoder::read_mono(&args.input, frames(args.start + args.length))
+        .or_fail(Failure::Decode)?;
+    let excerpt = samples.get(frames(args.start)..).unwrap_or_default();
+    if excerpt.is_empty() {
+        let message = format!("{} is shorter than {:.1}s", args.input.display(), args.start);
+        return Err(Failed::new(Failure::Arguments, message).into());
+    }
+    let tint = args.tune.match_tint.as_deref().map(Spectrum::measure_file).transpose()?;
+
+    // Resampled once for every render, like `process` does
+    let processing_rate = if base.native_rate {
+        rate
+    } else {
+        resample::analysis_rate(rate).unwrap_or(rate)
+    };
+    let excerpt = if processing_rate == rate {
+        excerpt.to_vec()
+    } else {
+        let len = resample::resampled_len(excerpt.len(), rate, processing_rate);
+        resample::resample(excerpt, rate, processing_rate, len)
+    };
+    let output_rate = base.output_rate.unwrap_or(rate);
+    std::fs::create_dir_all(&args.output_dir)
+        .map_err(|e| format!("Failed to create {}: {}", args.output_dir.display(), e))?;
+
+    println!(
+        "Comparing {:.1}s from {:.1}s of {}",
+        excerpt.len() as f32 / processing_rate as f32,
+        args.start,
+        args.input.display()
+    );
+    for (index, params) in grid.iter().enumerate() {
+        failure::check_interrupted()?;
+        let output = compare_output(&args.input, &args.output_dir, params);
+        println!(
+            "[{}/{}] strength {:.2}, transition {:.2} -> {}",
+            index + 1,
+            grid.len(),
+            params.strength,
+            params.transition,
+            output.display()
+        );
+        let settings = render_settings(params, processing_rate, Vec::new(), None, Vec::new());
+        let processed =
+            pipeline::process_samples(&excerpt, &settings, |_| ControlFlow::Continue(()))
+                .or_fail(Failure::Processing)?;
+        for (sample_pos, e) in &processed.failed_frames {
+            report::warning(format!("Processing error at sample {}: {}", sample_pos, e));
+        }
+        let tuned = if output_rate == processing_rate {
+            processed.samples
+        } else {
+            let len =
+                resample::resampled_len(processed.samples.len(), processing_rate, output_rate);
+            resample::resample(&processed.samples, processing_rate, output_rate, len)
+        };
+        let tint = tint.as_ref().map(|reference| (reference, params.tint_amount));
+        let spec = params.output_spec(spec);
+        let limit = params.limit();
+        write_whole(&tuned, &output, spec, params.normalization, limit.as_ref(), tint, verbose)?;
+    }
+    println!("Compare complete: {} render(s) in {}", grid.len(), args.output_dir.display());
+    Ok(())
+}
+
+// Where `compare` writes the render with `params`' strength and transition speed
+fn compare_output(input: &Path, output_dir: &Path, params: &ProcessParams) -> PathBuf {
+    let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+    output_dir.join(format!("{}_s{:.2}_t{:.2}.wav", stem, params.strength, params.transition))
+}
+
+/// Write the in-tune scale and the off-pitch version of it the welcome tour uses into `dir`.
+fn generate_test_files(dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let in_tune = dir.join("test_audio.wav");
+    demo_audio::write_test_tones(&in_tune)?;
+    println!("In-tune C major scale: {}", in_tune.display());
+
+    let off_pitch = dir.join("test_audio_off_pitch.wav");
+    demo_audio::write_off_pitch_demo(&off_pitch)?;
+    println!("Off-pitch version to correct: {}", off_pitch.display());
+    for (note_idx, (&frequency, &base)) in
+        demo_audio::off_pitch_notes().iter().zip(demo_audio::BASE_NOTES.iter()).enumerate()
+    {
+        let deviation = ((frequency / base - 1.0) * 1200.0).round();
+        println!("  Note {}: {:.2}Hz ({:+.0} cents)", note_idx + 1, frequency, deviation);
+    }
+    Ok(())
+}
+
+fn list_presets(user_presets: &[Preset], config: &ConfigLocation) {
+    let print = |preset: &Preset| {
+        println!(
+            "  {}: strength {:.2}, transition {:.2}, formant {:+}",
+            preset.name, preset.strength, preset.transition, preset.formant
+        );
+    };
+    println!("Factory Presets:");
+    presets::factory_presets().iter().for_each(print);
+    let location = if config.portable { " (portable)" } else { "" };
+    println!("User Presets in {}{}:", config.dir.display(), location);
+    if user_presets.is_empty() {
+        println!("  none yet, save one with --save-preset NAME");
+    }
+    user_presets.iter().for_each(print);
+}
+
+/// Print the keys that fit `input` best and return the best one.
+fn detect_key(input: &Path) -> Result<usize, Box<dyn std::error::Error>> {
+    let matches = keys::detect_file(input).or_fail(Failure::Decode)?;
+    let best = matches.first().ok_or("Not enough pitched audio to detect a key")?.key;
+    println!("🎼 Key suggestions for {}:", input.display());
+    print_key_suggestions(&matches);
+    Ok(best)
+}
+
+fn print_key_suggestions(matches: &[KeyMatch]) {
+    for (rank, key_match) in matches.iter().take(KEY_SUGGESTIONS).enumerate() {
+        println!(
+            "  {}. {} (key {}, match {:.2})",
+            rank + 1,
+            KEY_NAMES[key_match.key],
+            key_match.key,
+            key_match.score
+        );
+    }
+}
+
+fn enqueue_job(
+    spool_dir: &Path,
+    input: &Path,
+    output: &Path,
+    params: ProcessParams,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let spool = Spool::open(spool_dir)?;
+    let stem = input.file_stem().map(|stem| stem.to_string_lossy()).unwrap_or_default();
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_nanos();
+    let mut ticket =
+        JobTicket::new(format!("{}-{}", nanos, stem), input.to_path_buf(), output.to_path_buf());
+    ticket.params = params;
+
+    let path = spool.enqueue(&ticket)?;
+    println!("Queued job {}: {}", ticket.id, path.display());
+    Ok(())
+}
+
+/// Process all pending job tickets in `spool_dir`, and keep waiting for more in watch mode.
+fn run_spool(
+    spool_dir: &Path,
+    args: &ProcessArgs,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let spool = Spool::open(spool_dir)?;
+
+    let recovered = spool.recover_interrupted()?;
+    if recovered > 0 {
+        report::warning(format!("Re-queued {} job(s) interrupted by a previous run", recovered));
+    }
 
-    /// Hop size (overlap factor)
-    #[arg(long, default_value_t = 256, value_name = "SIZE")]
-    pub hop_size: usize,
+    if verbose {
+        println!("📥 Processing jobs from {}", spool_dir.display());
+    }
 
-    /// Enable verbose output
-    #[arg(short, long)]
-    pub verbose: bool,
+    loop {
+        // Ctrl-C while waiting is how watching ends
+        if failure::interrupted() {
+            return Ok(());
+        }
+        for ticket_path in spool.pending()? {
+            // Another worker may have claimed the ticket first
+            let Some(ticket) = spool.claim(&ticket_path)? else {
+                continue;
+            };
+            run_job(&spool, ticket, verbose)?;
+            failure::check_interrupted()?;
+        }
 
-    /// List available keys and exit
-    #[arg(long)]
-    pub list_keys: bool,
+        if !args.watch {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(args.poll_interval.max(1)));
+    }
 }
 
-pub const KEY_NAMES: [&str; 24] = [
-    "C Major", "G Major", "D Major", "A Major", "E Major", "B Major", "F# Major", "C# Major",
-    "F Major", "Bb Major", "Eb Major", "Ab Major", "A Minor", "E Minor", "B Minor", "F# Minor",
-    "C# Minor", "G# Minor", "D# Minor", "A# Minor", "D Minor", "G Minor", "C Minor", "F Minor",
-];
+fn run_job(spool: &Spool, ticket: JobTicket, verbose: bool) -> std::io::Result<()> {
+    let result = ticket.params.validate().and_then(|()| {
+        process_location(
+            &ticket.input,
+            &ticket.output,
+            &ticket.params,
+            &SideFiles::default(),
+            verbose,
+        )
+        .map_err(|e| e.to_string())
+    });
+    // Left running, so the next run re-queues it
+    if failure::interrupted() {
+        return Ok(());
+    }
 
-pub fn run_cli() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+    match &result {
+        Ok(()) => println!("Job {} done: {}", ticket.id, ticket.output.display()),
+        Err(e) => eprintln!("Job {} failed: {}", ticket.id, e),
+    }
+    spool.finish(ticket, result)
+}
+
+/// Files read and written alongside processing, all local.
+#[derive(Debug, Clone, Default)]
+pub struct SideFiles {
+    // Regions to correct, everything when there is none
+    pub regions: Option<PathBuf>,
+    // Stretch of the input corrected, within the regions if there are any
+    pub start: Option<Position>,
+    pub end: Option<Position>,
+    // Correct the phrases between the input's silences instead
+    pub split: Option<SilenceSplit>,
+    // Sections with musical settings of their own
+    pub sections: Option<PathBuf>,
+    // Where the input's note segments go
+    pub export: Option<PathBuf>,
+    // Where fallbacks and warnings are marked
+    pub markers: Option<PathBuf>,
+    // Recording whose tone the output is matched to
+    pub tint: Option<PathBuf>,
+    // MIDI melody the notes are corrected to
+    pub midi: Option<PathBuf>,
+    // Write the dry, corrected, and harmony layers next to the output too
+    pub stems: bool,
+    // Write the unnormalized float render next to the output too
+    pub float_master: bool,
+    // Harmony voices sung with the lead, and the level they are mixed in at
+    pub harmony: Vec<Interval>,
+    pub harmony_level: f32,
+    // Levels the lead and harmony voices go into each channel of a stereo output at
+    pub routing: Vec<Route>,
+    // Copy an identical earlier render instead of rendering again
+    pub reuse: bool,
+    // How audio piped through stdin or stdout is laid out
+    pub raw: Option<RawPcm>,
+}
 
-    if cli.list_keys {
-        println!("Available Keys:");
-        for (i, name) in KEY_NAMES.iter().enumerate() {
-            println!("  {}: {}", i, name);
+/// Like [`process_file`], but either side may be a remote location that is staged locally.
+pub fn process_location(
+    input: &Path,
+    output: &Path,
+    params: &ProcessParams,
+    side_files: &SideFiles,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let input = Location::from_path(input).or_fail(Failure::Arguments)?;
+    let output = Location::from_path(output).or_fail(Failure::Arguments)?;
+    if let Location::Local(path) = &input {
+        if !decoder::is_supported_input(path) {
+            let message = format!(
+                "Unsupported input format: {}. Supported formats: {}",
+                path.display(),
+                decoder::INPUT_EXTENSIONS.join(", ")
+            );
+            return Err(Failed::new(Failure::UnsupportedFormat, message).into());
         }
-        return Ok(());
     }
-
-    // Validate arguments
-    if cli.key < 0 || cli.key >= 24 {
-        return Err("Key must be between 0 and 23. Use --list-keys to see available keys.".into());
+    if side_files.stems && !output.is_local() {
+        let message = "Stems can only be written next to a local output";
+        return Err(Failed::new(Failure::Arguments, message).into());
+    }
+    if side_files.float_master && !output.is_local() {
+        let message = "A float master can only be written next to a local output";
+        return Err(Failed::new(Failure::Arguments, message).into());
     }
+    // Staged copies are only needed for the duration of the job, and go with the directory
+    let staging = JobDir::create("staging").or_fail(Failure::Write)?;
+    if verbose && !input.is_local() {
+        println!("☁️  Fetching {}...", input);
+    }
+    let piped = [&input, &output].contains(&&Location::Stdio);
+    let raw = match side_files.raw {
+        Some(raw) => Some(raw),
+        None if piped => {
+            let message = "Piped audio needs its layout, with --raw-format";
+            return Err(Failed::new(Failure::Arguments, message).into());
+        }
+        None => None,
+    };
+    let local_input = match (&input, raw) {
+        (Location::Stdio, Some(raw)) => {
+            let path = staging.path().join("stdin.wav");
+            pipe::receive(&raw, &path).or_fail(Failure::Decode)?;
+            path
+        }
+        _ => storage::fetch(&input, staging.path())?,
+    };
+    let local_output = storage::output_path(&output, staging.path())?;
 
-    if cli.strength < 0.0 || cli.strength > 1.0 {
-        return Err("Pitch correction strength must be between 0.0 and 1.0".into());
+    // Renders to local files are remembered by what they are made of, so an identical one
+    // can be found again under any name
+    let identity = output
+        .is_local()
+        .then(|| render_fingerprint(&local_input, &local_output, params, side_files))
+        .flatten();
+    if let Some((identity, render)) = identity.and_then(|id| Some((id, fingerprint::find(id)?))) {
+        if report::json() {
+            let reused = side_files.reuse;
+            report::emit(&Event::Duplicate { render: render.clone(), reused });
+        } else if side_files.reuse {
+            println!("♻️  Copying the identical render {} instead of rendering", render.display());
+        } else {
+            println!(
+                "♻️  An identical render is already at {}, --reuse copies it instead",
+                render.display()
+            );
+        }
+        if side_files.reuse {
+            return fingerprint::reuse(&render, &local_output, identity).or_fail(Failure::Write);
+        }
     }
 
-    if cli.transition < 0.01 || cli.transition > 1.0 {
-        return Err("Transition speed must be between 0.01 and 1.0".into());
+    process_file(&local_input, &local_output, params, side_files, verbose)?;
+    if let Some(identity) = identity {
+        if let Err(e) = fingerprint::record(identity, &local_output) {
+            report::warning(format!("The render won't be found for --reuse: {}", e));
+        }
+    }
+    match (&output, raw) {
+        (Location::Stdio, Some(raw)) => {
+            pipe::send(&local_output, raw.format).or_fail(Failure::Write)
+        }
+        _ => {
+            if verbose && !output.is_local() {
+                println!("☁️  Uploading to {}...", output);
+            }
+            storage::store(&local_output, &output).or_fail(Failure::Write)
+        }
     }
+}
 
-    if cli.formant < -12 || cli.formant > 12 {
-        return Err("Formant shift must be between -12 and +12 semitones".into());
+// What a render of `input` to `output` is made of, None when it writes side files an
+// identical earlier render may not have left, or a file it reads can't be read
+fn render_fingerprint(
+    input: &Path,
+    output: &Path,
+    params: &ProcessParams,
+    side_files: &SideFiles,
+) -> Option<Fingerprint> {
+    let SideFiles { export, markers, stems, float_master, .. } = side_files;
+    if export.is_some() || markers.is_some() || *stems || *float_master {
+        return None;
+    }
+    let mut fingerprinter = Fingerprinter::new();
+    // The speed limit doesn't change the audio and neither does how many threads share the
+    // chunks, but rendering in chunks does
+    fingerprinter.settings(&ProcessParams { threads: 0, max_speed: None, ..*params });
+    fingerprinter.settings(&(
+        render_threads(params) > 1,
+        output.extension(),
+        side_files.start,
+        side_files.end,
+        side_files.split,
+        &side_files.harmony,
+        side_files.harmony_level,
+        &side_files.routing,
+    ));
+    let SideFiles { regions, sections, tint, midi, .. } = side_files;
+    for file in
+        [Some(input), regions.as_deref(), sections.as_deref(), tint.as_deref(), midi.as_deref()]
+    {
+        match file {
+            Some(path) => fingerprinter.file(path).ok()?,
+            None => fingerprinter.bytes(&[]),
+        }
     }
+    Some(fingerprinter.finish())
+}
 
-    if cli.octave < 0 || cli.octave > 4 {
-        return Err("Octave must be between 0 and 4".into());
+/// Split `input_path` at its silences and autotune each phrase into its own numbered file
+/// next to `output_path`, like take_001.wav for take.wav. Returns the files written.
+pub fn process_phrases(
+    input_path: &Path,
+    output_path: &Path,
+    params: &ProcessParams,
+    side_files: &SideFiles,
+    verbose: bool,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    if !Location::from_path(input_path)?.is_local() || !Location::from_path(output_path)?.is_local()
+    {
+        let message = "Phrase files can only be split from and into local files";
+        return Err(Failed::new(Failure::Arguments, message).into());
+    }
+    decoder::probe(input_path)
+        .or_fail(Failure::Decode)?
+        .check_supported()
+        .or_fail(Failure::UnsupportedFormat)?;
+    let (phrases, sample_rate) = find_phrases(input_path, side_files.split.unwrap_or_default())?;
+    if verbose {
+        println!("🔇 Split at silences into {} phrase(s)", phrases.len());
     }
 
-    if cli.verbose {
-        println!("🎵 Autotune CLI Processor");
-        println!("========================");
-        println!("Input: {}", cli.input.as_ref().unwrap().display());
-        println!("Output: {}", cli.output.as_ref().unwrap().display());
-        println!("Key: {} ({})", cli.key, KEY_NAMES[cli.key as usize]);
+    // Each phrase is copied to a scratch file in turn and processed from there, so the
+    // input is only decoded once
+    let staging = JobDir::create("phrases").or_fail(Failure::Write)?;
+    let scratch = staging.path().join("phrase.wav");
+    let side_files = SideFiles { split: None, ..side_files.clone() };
+    write_phrases(input_path, &scratch, &phrases, |index, phrase| {
+        let path = phrase_path(output_path, index, phrases.len());
         println!(
-            "Note Mode: {}",
-            if cli.note == 0 {
-                "Auto".to_string()
+            "Phrase {}/{}: {:.2}s to {:.2}s -> {}",
+            index + 1,
+            phrases.len(),
+            phrase.start_secs(sample_rate),
+            phrase.end_secs(sample_rate),
+            path.display()
+        );
+        process_file(&scratch, &path, params, &side_files, verbose)?;
+        Ok(path)
+    })
+}
+
+// The phrases of `input_path` and the rate they are at, an error when it has none
+fn find_phrases(input_path: &Path, split: SilenceSplit) -> Result<(Vec<Phrase>, u32), String> {
+    let (phrases, sample_rate) = phrases::find(input_path, split)?;
+    if phrases.is_empty() {
+        return Err(format!(
+            "Nothing louder than {} dBFS to split into phrases in {}",
+            split.threshold_db,
+            input_path.display()
+        ));
+    }
+    Ok((phrases, sample_rate))
+}
+
+// Copy each of `phrases` out of `input_path` to `scratch` and hand it to `process`, in
+// one pass over the input
+fn write_phrases(
+    input_path: &Path,
+    scratch: &Path,
+    phrases: &[Phrase],
+    mut process: impl FnMut(usize, &Phrase) -> Result<PathBuf, Box<dyn std::error::Error>>,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let mut reader = decoder::MonoReader::open(input_path).or_fail(Failure::Decode)?;
+    let spec = reader.info().output_spec();
+    let write_error = |e: hound::Error| format!("Failed to write {}: {}", scratch.display(), e);
+    let mut block = Vec::with_capacity(PHRASE_BLOCK);
+    let mut position = 0;
+    let mut written = Vec::new();
+    for (index, phrase) in phrases.iter().enumerate() {
+        let mut writer =
+            paths::create_wav(scratch, spec).map_err(write_error).or_fail(Failure::Write)?;
+        while position < phrase.end {
+            // Up to the phrase to skip what's before it, then through it
+            let until = if position < phrase.start {
+                phrase.start
             } else {
-                format!("Note {}", cli.note)
+                phrase.end
+            };
+            block.clear();
+            let read = reader
+                .read(&mut block, (until - position).min(PHRASE_BLOCK))
+                .or_fail(Failure::Decode)?;
+            if read == 0 {
+                break;
             }
-        );
-        println!("Octave: {}", cli.octave);
-        println!("Formant Shift: {} semitones", cli.formant);
-        println!("Pitch Correction: {:.1}%", cli.strength * 100.0);
-        println!("Transition Speed: {:.2}", cli.transition);
-        println!();
+            if position >= phrase.start {
+                pcm::write_mono(&mut writer, &block, None)
+                    .map_err(write_error)
+                    .or_fail(Failure::Write)?;
+            }
+            position += read;
+        }
+        writer.finalize().map_err(write_error).or_fail(Failure::Write)?;
+        written.push(process(index, phrase)?);
     }
+    Ok(written)
+}
 
-    // Validate required arguments when not listing keys
-    let input_path = cli.input.as_ref().ok_or("Input file is required")?;
-    let output_path = cli.output.as_ref().ok_or("Output file is required")?;
+// Numbered file for phrase `index` of `count` next to `output`, at least 3 digits
+fn phrase_path(output: &Path, index: usize, count: usize) -> PathBuf {
+    let name = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().map_or("wav".into(), |ext| ext.to_string_lossy());
+    let width = count.to_string().len().max(3);
+    output.with_file_name(format!("{}_{:0width$}.{}", name, index + 1, extension, width = width))
+}
 
+/// Autotune `input_path` into `output_path`.
+pub fn process_file(
+    input_path: &Path,
+    output_path: &Path,
+    params: &ProcessParams,
+    side_files: &SideFiles,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Open input file
-    let mut reader = WavReader::open(input_path)?;
-    let spec = reader.spec();
+    let info = decoder::probe(input_path).or_fail(Failure::Decode)?;
+    let spec = info.output_spec();
 
-    if cli.verbose {
+    if report::json() {
+        report::emit(&Event::Input {
+            path: input_path.to_path_buf(),
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            bits_per_sample: spec.bits_per_sample,
+            compressed: info.compressed,
+            duration_secs: info.duration_secs(),
+        });
+    }
+    if verbose {
         println!("📁 Input File Info:");
         println!("   Sample Rate: {}Hz", spec.sample_rate);
         println!("   Channels: {}", spec.channels);
-        println!("   Bit Depth: {}", spec.bits_per_sample);
-        println!("   Duration: {:.2}s", reader.duration() as f32 / spec.sample_rate as f32);
+        if info.compressed {
+            println!("   Format: compressed (decoded to {}-bit)", spec.bits_per_sample);
+        } else {
+            println!("   Bit Depth: {}", spec.bits_per_sample);
+        }
+        match info.duration_secs() {
+            Some(duration) => println!("   Duration: {}", format::duration(duration)),
+            None => println!("   Duration: unknown"),
+        }
         println!();
     }
 
-    // Validate audio format
-    if spec.channels != 1 && spec.channels != 2 {
-        return Err(format!(
-            "Unsupported channel count: {}. Only mono and stereo are supported.",
-            spec.channels
-        )
-        .into());
-    }
+    info.check_supported().or_fail(Failure::UnsupportedFormat)?;
 
-    if spec.bits_per_sample != 16 && spec.bits_per_sample != 24 && spec.bits_per_sample != 32 {
-        return Err(format!(
-            "Unsupported bit depth: {}. Only 16, 24, and 32-bit are supported.",
-            spec.bits_per_sample
-        )
-        .into());
+    let mut regions: Vec<Label> = match &side_files.regions {
+        Some(path) => labels::read_file(path)?,
+        None => Vec::new(),
+    };
+    if let Some(split) = side_files.split {
+        let (phrases, sample_rate) = find_phrases(input_path, split)?;
+        if verbose {
+            println!("🔇 Split at silences into {} phrase(s)", phrases.len());
+        }
+        regions = phrases
+            .iter()
+            .enumerate()
+            .map(|(index, phrase)| Label {
+                start_secs: phrase.start_secs(sample_rate),
+                end_secs: phrase.end_secs(sample_rate),
+                text: format!("Phrase {}", index + 1),
+            })
+            .collect();
     }
-
-    // Read samples
-    if cli.verbose {
-        println!("📖 Reading audio data...");
+    if side_files.start.is_some() || side_files.end.is_some() {
+        let start = side_files.start.map_or(0.0, |start| start.secs(spec.sample_rate));
+        let end = side_files.end.map_or(f32::INFINITY, |end| end.secs(spec.sample_rate));
+        if end <= start {
+            let message = format!("The end ({:.2}s) must be after the start ({:.2}s)", end, start);
+            return Err(Failed::new(Failure::Arguments, message).into());
+        }
+        if verbose {
+            match side_files.end {
+                Some(_) => println!("✂️  Correcting only {:.2}s to {:.2}s", start, end),
+                None => println!("✂️  Correcting only from {:.2}s on", start),
+            }
+        }
+        regions = labels::within(&regions, start, end);
+    }
+    let key_changes = keys::key_changes(&regions);
+    let sections = match &side_files.sections {
+        Some(path) => sections::from_labels(&labels::read_file(path)?, &params.musical())?,
+        None => Vec::new(),
+    };
+    if verbose {
+        for section in &sections {
+            println!(
+                "🎼 Section {:.2}s to {:.2}s: {}, {}, formant {:+}",
+                section.start_secs,
+                section.end_secs,
+                KEY_NAMES[section.key as usize],
+                match section.note {
+                    0 => "auto".to_string(),
+                    note => format!("locked to {}", labels::NOTE_NAMES[note as usize - 1]),
+                },
+                section.formant
+            );
+        }
+    }
+    let melody = side_files.midi.as_deref().map(Melody::read_file).transpose()?;
+    // Measured up front, so a bad reference fails before the render
+    let tint = side_files.tint.as_deref().map(Spectrum::measure_file).transpose()?;
+    if let Some(melody) = &melody {
+        if verbose {
+            println!(
+                "🎹 Following a melody of {} notes over {:.1}s",
+                melody.notes().len(),
+                melody.duration_secs()
+            );
+        }
+    }
+    if verbose && !regions.is_empty() {
+        let regions = regions.iter().filter(|label| !label.is_point()).count();
+        if regions > 0 && side_files.regions.is_some() {
+            println!("🏷️  Correcting only {} labelled region(s)", regions);
+        }
+        for change in &key_changes {
+            println!("🎼 Key change at {:.2}s to {}", change.secs, KEY_NAMES[change.key]);
+        }
+        println!();
     }
 
-    let samples: Result<Vec<i32>, _> = reader.samples().collect();
-    let samples = samples?;
-    let total_samples = samples.len();
-
-    if cli.verbose {
-        println!("   Read {} samples", total_samples);
+    // Classify content and warn when autotune is unlikely to help
+    let (excerpt, _) =
+        decoder::read_mono(input_path, 30 * spec.sample_rate as usize).or_fail(Failure::Decode)?;
+    if let Some(classification) = classifier::classify(&excerpt, spec.sample_rate as f32) {
+        if verbose {
+            let features = classification.features;
+            println!("🔍 Content: {}", classification.class.label());
+            println!(
+                "   Harmonicity: {:.2} | Centroid: {:.0}Hz | Silence: {:.0}% | Dynamics: {:.1}dB",
+                features.harmonicity,
+                features.spectral_centroid,
+                features.silence_ratio * 100.0,
+                features.dynamic_range_db
+            );
+            if let (Some(floor), Some(snr)) = (features.noise_floor_db, features.snr_db) {
+                println!("   Noise floor: {:.0} dBFS | SNR: {:.0}dB", floor, snr);
+            }
+            println!(
+                "   Suggested: strength {:.1}, transition {:.2}",
+                classification.class.suggested_strength(),
+                classification.class.suggested_transition()
+            );
+            println!();
+        }
+        if let Some(warning) = classification.class.warning() {
+            report::warning(warning);
+        }
+        if let Some(warning) = classification.features.noise_warning() {
+            report::warning(warning);
+        }
     }
 
-    // Convert to f32
-    let scale_factor = match spec.bits_per_sample {
-        16 => 1.0 / 32768.0,
-        24 => 1.0 / 8388608.0,
-        32 => 1.0 / 2147483648.0,
-        _ => return Err("Unsupported bit depth".into()),
+    // Process outside the preferred rates at an analysis rate, and resample back after
+    let analysis_rate = if params.native_rate {
+        None
+    } else {
+        resample::analysis_rate(spec.sample_rate)
     };
-
-    let audio_data: Vec<f32> = samples.iter().map(|&x| x as f32 * scale_factor).collect();
-
-    // Convert stereo to mono if needed
-    let mono_data = if spec.channels == 2 {
-        if cli.verbose {
-            println!("🔄 Converting stereo to mono...");
+    if let Some(rate) = analysis_rate {
+        if verbose {
+            println!("🔁 Resampling {}Hz -> {}Hz for processing", spec.sample_rate, rate);
         }
-        let mut mono = Vec::with_capacity(audio_data.len() / 2);
-        for i in (0..audio_data.len()).step_by(2) {
-            let left = audio_data[i];
-            let right = audio_data.get(i + 1).copied().unwrap_or(0.0);
-            mono.push((left + right) * 0.5);
+    }
+    if let Some(rate) = params.output_rate.filter(|&rate| rate != spec.sample_rate) {
+        if verbose {
+            println!("🔁 Writing the output at {}Hz", rate);
         }
-        mono
+    }
+    if verbose && (params.output_depth.is_some() || params.output_channels.is_some()) {
+        let output = params.output_spec(spec);
+        let depth =
+            params.output_depth.map_or("input's".to_string(), |depth| depth.name().to_string());
+        println!("💾 Writing {} output with {} channel(s)", depth, output.channels);
+    }
+
+    // A mono output has nowhere to route to
+    let routing = if params.output_spec(spec).channels == 2 {
+        side_files.routing.clone()
     } else {
-        audio_data
+        if !side_files.routing.is_empty() {
+            report::warning("--route and --width only apply to stereo output, the output is mono");
+        }
+        Vec::new()
     };
 
     // Configure autotune
-    let config = AutotuneConfig {
-        fft_size: cli.fft_size,
-        hop_size: cli.hop_size,
-        sample_rate: spec.sample_rate as f32,
-        pitch_correction_strength: cli.strength,
-        transition_speed: cli.transition,
-        ..Default::default()
+    let processing_rate = analysis_rate.unwrap_or(spec.sample_rate);
+    let settings = RenderSettings {
+        sections,
+        stems: side_files.stems,
+        harmony: side_files.harmony.clone(),
+        harmony_level: side_files.harmony_level,
+        routing,
+        ..render_settings(params, processing_rate, key_changes, melody, regions)
     };
+    let config = settings.config;
 
-    let musical_settings =
-        MusicalSettings { key: cli.key, note: cli.note, octave: cli.octave, formant: cli.formant };
-
-    if cli.verbose {
+    if verbose {
         println!("🎛️  Processing Configuration:");
         println!("   FFT Size: {}", config.fft_size);
         println!("   Hop Size: {}", config.hop_size);
+        println!("   Window: {}", settings.window);
+        println!("   Padding: {}", settings.padding);
+        println!("   Pitch detection: {}", settings.pitch_algorithm.label());
+        if let Some(range) = settings.f0_range {
+            println!("   Pitch range: {:.0}-{:.0} Hz", range.min, range.max);
+        }
+        if let OutputLength::Frames(frames) = settings.length {
+            println!("   Length: {} frames", frames);
+        }
         println!("   Sample Rate: {}Hz", config.sample_rate);
+        if !settings.harmony.is_empty() {
+            println!("   Harmony voices: {}, each a pass after the lead", settings.harmony.len());
+        }
         println!();
         println!("🎵 Starting autotune processing...");
     }
 
-    // Process audio
+    // Decode, process, and spool the result a block at a time
     let start_time = Instant::now();
-    let mut autotune_state = AutotuneState::new(config);
-    let mut processed_audio = Vec::new();
-
-    let fft_size = config.fft_size;
-    let hop_size = config.hop_size;
-    let total_chunks = (mono_data.len() + hop_size - 1) / hop_size;
-
-    let mut input_buffer = vec![0.0f32; fft_size];
-    let mut output_buffer = vec![0.0f32; fft_size];
-    let mut sample_pos = 0;
-    let mut chunk_count = 0;
-
-    while sample_pos + fft_size <= mono_data.len() {
-        // Fill input buffer
-        input_buffer.copy_from_slice(&mono_data[sample_pos..sample_pos + fft_size]);
-
-        // Process with autotune
-        match process_autotune(
-            &input_buffer,
-            &mut output_buffer,
-            &mut autotune_state,
-            &musical_settings,
-        ) {
-            Ok(_) => {
-                // Overlap-add
-                if processed_audio.len() < sample_pos + fft_size {
-                    processed_audio.resize(sample_pos + fft_size, 0.0);
-                }
-
-                for (i, &sample) in output_buffer.iter().enumerate() {
-                    processed_audio[sample_pos + i] += sample;
-                }
-            }
-            Err(e) => {
-                eprintln!("Warning: Processing error at sample {}: {:?}", sample_pos, e);
-                // Fallback to original
-                if processed_audio.len() < sample_pos + fft_size {
-                    processed_audio.resize(sample_pos + fft_size, 0.0);
-                }
-                for (i, &sample) in input_buffer.iter().enumerate() {
-                    processed_audio[sample_pos + i] += sample;
-                }
-            }
+    let mut throttle = params.max_speed.map(|speed| Throttle::new(speed, config.sample_rate));
+    let mut bar = RenderProgress::start(config.sample_rate);
+    let mut percent_reported = 0;
+    // Decoding the rest of the input streams through the render, so what fails in it counts
+    // as processing
+    let rendered = pipeline::render(input_path, output_path, &settings, |progress| {
+        if failure::interrupted() {
+            return ControlFlow::Break(());
         }
-
-        sample_pos += hop_size;
-        chunk_count += 1;
-
-        if cli.verbose && chunk_count % 100 == 0 {
-            let progress = (chunk_count as f32 / total_chunks as f32) * 100.0;
-            println!("   Progress: {:.1}%", progress);
+        if let Some(throttle) = &mut throttle {
+            throttle.advance(progress.finished.len());
+        }
+        if let Some(bar) = &mut bar {
+            bar.update(progress.done, progress.total);
+        }
+        // Every whole percent, which is plenty for a wrapper to show
+        let percent = progress.done * 100 / progress.total.max(1);
+        if report::json() && percent > percent_reported {
+            percent_reported = percent;
+            report::emit(&Event::Progress {
+                done: progress.done,
+                total: progress.total,
+                percent: progress.done as f32 / progress.total as f32 * 100.0,
+            });
+        }
+        ControlFlow::Continue(())
+    })
+    .or_fail(Failure::Processing)?;
+    // Cleared before anything else is printed
+    drop(bar);
+    // Stopped early, the partial render is dropped with its scratch file
+    failure::check_interrupted()?;
+    if verbose {
+        println!("   Read {} samples", rendered.frames * spec.channels as usize);
+        if spec.channels == 2 {
+            println!("🔄 Converted stereo to mono");
         }
     }
+    // The render is at the output rate and written in the output format from here on
+    let spec = params.output_spec(spec);
+    for (sample_pos, e) in &rendered.failed_frames {
+        report::warning(format!("Processing error at sample {}: {}", sample_pos, e));
+    }
 
-    // Handle remaining samples
-    if sample_pos < mono_data.len() {
-        let remaining = mono_data.len() - sample_pos;
-        input_buffer.fill(0.0);
-        input_buffer[..remaining].copy_from_slice(&mono_data[sample_pos..]);
-
-        if let Ok(_) = process_autotune(
-            &input_buffer,
-            &mut output_buffer,
-            &mut autotune_state,
-            &musical_settings,
-        ) {
-            if processed_audio.len() < sample_pos + remaining {
-                processed_audio.resize(sample_pos + remaining, 0.0);
-            }
-            for i in 0..remaining {
-                processed_audio[sample_pos + i] += output_buffer[i];
+    let rendered = match &tint {
+        Some(reference) => {
+            if verbose {
+                println!("🎨 Matching tone to the reference ({:.0}%)", params.tint_amount * 100.0);
             }
+            rendered
+                .match_tint(output_path, reference, params.tint_amount)
+                .or_fail(Failure::Processing)?
         }
-    }
-
-    // Normalize to prevent clipping
-    if cli.verbose {
-        println!("🔧 Normalizing audio...");
-    }
+        None => rendered,
+    };
 
-    let max_amplitude = processed_audio.iter().map(|&x| x.abs()).fold(0.0, f32::max);
-    if max_amplitude > 1.0 {
-        let scale = 0.95 / max_amplitude;
-        for sample in &mut processed_audio {
-            *sample *= scale;
+    // Normalize while writing the output file, duplicating mono to every channel on the fly
+    let normalization_gain =
+        rendered.normalization_gain(params.normalization, spec.channels, params.limit_db.is_some());
+    if verbose {
+        if let Some(gain) = normalization_gain {
+            println!("🔧 Applied normalization ({}): {:.2}x", params.normalization, gain);
         }
-        if cli.verbose {
-            println!("   Applied normalization: {:.2}x", scale);
+        if let Some(db) = params.limit_db {
+            println!("🧱 Limiting peaks to {} dBFS", db);
         }
+        println!("💾 Writing output file...");
     }
 
-    // Convert back to stereo if needed
-    let output_samples = if spec.channels == 2 {
-        if cli.verbose {
-            println!("🔄 Converting back to stereo...");
+    // Re-read what was written before reporting success
+    let verification = rendered
+        .write(output_path, spec, params.normalization, params.limit().as_ref())
+        .or_fail(Failure::Write)?;
+    if verbose {
+        println!("🔎 Output check: {}", verification.summary());
+        if let Some(pitch) = &rendered.pitch {
+            println!("🎯 Pitch: {}", pitch.stats().summary());
+        }
+    }
+    for warning in verification.warnings() {
+        report::warning(warning);
+    }
+    let errors = verification.errors();
+    if !errors.is_empty() {
+        let message = format!("Output verification failed: {}", errors.join("; "));
+        return Err(Failed::new(Failure::Processing, message).into());
+    }
+    let stems =
+        rendered.write_stems(output_path, spec, params.normalization).or_fail(Failure::Write)?;
+    if verbose {
+        for path in &stems {
+            println!("🎚️  Wrote stem {}", path.display());
         }
-        let mut stereo = Vec::with_capacity(processed_audio.len() * 2);
-        for sample in processed_audio {
-            stereo.push(sample); // Left
-            stereo.push(sample); // Right
+    }
+    let master = if side_files.float_master {
+        let path = rendered.write_master(output_path, spec).or_fail(Failure::Write)?;
+        if verbose {
+            println!("🎛️  Wrote float master {}", path.display());
         }
-        stereo
+        Some(path)
     } else {
-        processed_audio
+        None
     };
 
-    // Convert back to integer format
-    if cli.verbose {
-        println!("💾 Writing output file...");
+    let duration_secs = rendered.frames as f32 / spec.sample_rate as f32;
+    if let (Some(path), Some(pitch)) = (&side_files.export, &rendered.pitch) {
+        let segments = labels::note_segments(&pitch.detected);
+        labels::write_file(path, &segments, duration_secs).or_fail(Failure::Write)?;
+        if verbose {
+            println!("🏷️  Wrote {} label(s) to {}", segments.len(), path.display());
+        }
     }
-
-    let output_scale = match spec.bits_per_sample {
-        16 => 32767.0,
-        24 => 8388607.0,
-        32 => 2147483647.0,
-        _ => return Err("Unsupported bit depth".into()),
-    };
-
-    let integer_samples: Vec<i32> =
-        output_samples.iter().map(|&x| (x * output_scale).round() as i32).collect();
-
-    // Write output file
-    let mut writer = WavWriter::create(output_path, spec)?;
-    for &sample in &integer_samples {
-        writer.write_sample(sample)?;
+    if let Some(path) = &side_files.markers {
+        let markers =
+            pipeline::problem_markers(&rendered.failed_frames, &settings, &verification.warnings());
+        labels::write_file(path, &markers, duration_secs).or_fail(Failure::Write)?;
+        if verbose {
+            println!("📍 Wrote {} problem marker(s) to {}", markers.len(), path.display());
+        }
     }
-    writer.finalize()?;
 
     let duration = start_time.elapsed();
+    let input_loudness =
+        rendered.input_loudness.map(|lufs| loudness::with_channels(lufs, info.channels));
+    let realtime_factor = duration_secs / duration.as_secs_f32();
+    if let Some(mono) = rendered.mono.filter(|mono| !mono.is_safe()) {
+        report::warning(format!(
+            "the output loses {:.1} dB played back in mono (correlation {:.2}); try a \
+             narrower --width",
+            -mono.fold_down_db, mono.correlation
+        ));
+    }
 
-    if cli.verbose {
+    if verbose {
         println!();
         println!("✅ Processing complete!");
         println!(
-            "   Processed {} samples in {:.2}s",
-            integer_samples.len(),
-            duration.as_secs_f32()
+            "   Processed {} samples in {}",
+            rendered.frames * spec.channels as usize,
+            format::duration(duration.as_secs_f32())
         );
-        println!("   Output saved to: {}", output_path.display());
+        let size = std::fs::metadata(output_path)
+            .map_or(String::new(), |file| format!(" ({})", format::file_size(file.len())));
+        println!("   Output saved to: {}{}", output_path.display(), size);
+        let lufs = |loudness: Option<f32>| {
+            loudness.map_or("too quiet to measure".to_string(), |lufs| format!("{:.1} LUFS", lufs))
+        };
+        println!("   Loudness: {} in, {} out", lufs(input_loudness), lufs(verification.loudness));
+        if let Some(mono) = rendered.mono {
+            println!(
+                "   Mono check: {:+.1} dB folded down, correlation {:.2}",
+                mono.fold_down_db, mono.correlation
+            );
+        }
 
-        let original_duration =
-            total_samples as f32 / (spec.sample_rate * spec.channels as u32) as f32;
-        let processing_ratio = original_duration / duration.as_secs_f32();
-        println!("   Processing speed: {:.1}x real-time", processing_ratio);
+        println!("   Processing speed: {}x real-time", format::number(realtime_factor as f64, 1));
+    }
+    if report::json() {
+        report::emit(&Event::Summary(Summary {
+            input: input_path.to_path_buf(),
+            output: output_path.to_path_buf(),
+            samples_processed: rendered.frames * spec.channels as usize,
+            frames: rendered.frames,
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            peak_dbfs: verification.peak_db(),
+            true_peak_dbtp: verification.true_peak_db(),
+            loudness_lufs: verification.loudness,
+            input_loudness_lufs: input_loudness,
+            normalization: params.normalization.to_string(),
+            normalization_gain,
+            limit_db: params.limit_db,
+            processing_secs: duration.as_secs_f32(),
+            realtime_factor,
+            stems,
+            master,
+        }));
+    }
+
+    Ok(())
+}
+
+/// Tighten the double `input_path` against `lead_path` in timing and pitch, and write it to
+/// `output_path` in the double's format.
+pub fn align_file(
+    lead_path: &Path,
+    input_path: &Path,
+    output_path: &Path,
+    limits: &AlignLimits,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let info = decoder::probe(input_path).or_fail(Failure::Decode)?;
+    info.check_supported().or_fail(Failure::UnsupportedFormat)?;
+    let spec = info.output_spec();
+
+    // Both takes are analysed whole, at the double's rate
+    let (double, _) = decoder::read_mono(input_path, usize::MAX).or_fail(Failure::Decode)?;
+    let (lead, lead_info) = decoder::read_mono(lead_path, usize::MAX).or_fail(Failure::Decode)?;
+    let lead = if lead_info.sample_rate == spec.sample_rate {
+        lead
     } else {
+        let len = resample::resampled_len(lead.len(), lead_info.sample_rate, spec.sample_rate);
+        resample::resample(&lead, lead_info.sample_rate, spec.sample_rate, len)
+    };
+
+    if verbose {
+        println!("🎯 Aligning {} to {}", input_path.display(), lead_path.display());
         println!(
-            "Autotune processing complete: {} -> {}",
-            input_path.display(),
-            output_path.display()
+            "   Limits: {:.0} ms, {:.0} cents",
+            limits.max_shift_secs * 1000.0,
+            limits.max_pitch_cents
+        );
+    }
+    let (aligned, report) = align::align_double(&lead, &double, spec.sample_rate, limits);
+    if verbose {
+        println!(
+            "   Timing: {:+.1} ms on average, {:.1} ms at most",
+            report.mean_shift_secs * 1000.0,
+            report.max_shift_secs * 1000.0
+        );
+        println!(
+            "   Pitch: {:.1}s matched, by {:.0} cents on average",
+            report.pitch_matched_secs, report.mean_cents
+        );
+        println!("💾 Writing output file...");
+    }
+    write_whole(&aligned, output_path, spec, Normalization::default(), None, None, verbose)
+}
+
+// How `params` correct a file processed at `processing_rate`
+fn render_settings(
+    params: &ProcessParams,
+    processing_rate: u32,
+    key_changes: Vec<KeyChange>,
+    melody: Option<Melody>,
+    regions: Vec<Label>,
+) -> RenderSettings {
+    RenderSettings {
+        config: AutotuneConfig {
+            fft_size: params.fft_size,
+            hop_size: params.hop_size,
+            sample_rate: processing_rate as f32,
+            pitch_correction_strength: params.strength,
+            transition_speed: params.transition,
+            ..Default::default()
+        },
+        window: params.window,
+        padding: params.padding,
+        pitch_algorithm: params.pitch_algorithm,
+        f0_range: params.f0_range,
+        musical: params.musical(),
+        scale: params.scale,
+        key_changes,
+        sections: Vec::new(),
+        melody,
+        processing_rate,
+        output_rate: params.output_rate,
+        length: params.length,
+        threads: render_threads(params),
+        regions,
+        correct_fry: params.correct_fry,
+        confidence_strength: params.confidence_strength,
+        unvoiced_sensitivity: params.unvoiced_sensitivity,
+        a4: params.a4,
+        energy: params.energy,
+        chain: params.chain,
+        high_pass_hz: params.high_pass_hz,
+        gate: params.gate,
+        eq: params.eq,
+        compressor: params.compressor,
+        meters: None,
+        stems: false,
+        harmony: Vec::new(),
+        harmony_level: 0.0,
+        routing: Vec::new(),
+    }
+}
+
+// The worker threads `params` render with, 0 being one per core
+fn render_threads(params: &ProcessParams) -> usize {
+    match params.threads {
+        0 => engine::available_threads(),
+        threads => threads,
+    }
+}
+
+/// Retune `input_path` to the nearest of `targets` and write it to `output_path`.
+pub fn tune_file(
+    targets: &FrequencyList,
+    input_path: &Path,
+    output_path: &Path,
+    params: &ProcessParams,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let info = decoder::probe(input_path).or_fail(Failure::Decode)?;
+    info.check_supported().or_fail(Failure::UnsupportedFormat)?;
+    let spec = info.output_spec();
+    // Analysed whole
+    let (samples, _) = decoder::read_mono(input_path, usize::MAX).or_fail(Failure::Decode)?;
+
+    if verbose {
+        println!("🎯 Tuning {} to {} frequencies", input_path.display(), targets.len());
+    }
+    let (tuned, report) = frequencies::tune_to_frequencies(
+        &samples,
+        spec.sample_rate,
+        targets,
+        params.strength,
+        params.transition,
+    );
+    if verbose {
+        println!(
+            "   Pitch: {:.1}s corrected, {:.0} cents off on average",
+            report.corrected_secs, report.mean_cents
         );
+        println!("💾 Writing output file...");
     }
+    let output_spec = params.output_spec(spec);
+    let tuned = if output_spec.sample_rate == spec.sample_rate {
+        tuned
+    } else {
+        let len = resample::resampled_len(tuned.len(), spec.sample_rate, output_spec.sample_rate);
+        resample::resample(&tuned, spec.sample_rate, output_spec.sample_rate, len)
+    };
+    let (normalization, limit) = (params.normalization, params.limit());
+    write_whole(&tuned, output_path, output_spec, normalization, limit.as_ref(), None, verbose)
+}
 
+// Spool a whole processed signal to `output_path`, matching its tone to a reference by an
+// amount when `tint` has one and limiting it when there is a `limit`, and check the written
+// file
+fn write_whole(
+    samples: &[f32],
+    output_path: &Path,
+    spec: WavSpec,
+    normalization: Normalization,
+    limit: Option<&Limit>,
+    tint: Option<(&Spectrum, f32)>,
+    verbose: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut scratch =
+        ScratchWriter::create(output_path, spec.sample_rate, 1).or_fail(Failure::Write)?;
+    scratch.push(samples).or_fail(Failure::Write)?;
+    let mut rendered = scratch.finish(Vec::new()).or_fail(Failure::Write)?;
+    if let Some((reference, amount)) = tint {
+        rendered =
+            rendered.match_tint(output_path, reference, amount).or_fail(Failure::Processing)?;
+    }
+    let verification =
+        rendered.write(output_path, spec, normalization, limit).or_fail(Failure::Write)?;
+    if verbose {
+        println!("🔎 Output check: {}", verification.summary());
+    }
+    for warning in verification.warnings() {
+        report::warning(warning);
+    }
+    let errors = verification.errors();
+    if !errors.is_empty() {
+        let message = format!("Output verification failed: {}", errors.join("; "));
+        return Err(Failed::new(Failure::Processing, message).into());
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::effects::Stage;
 
     #[test]
     fn test_key_names_length() {
         assert_eq!(KEY_NAMES.len(), 24);
     }
 
+    // Parsed like `run_cli` does, where no subcommand means process
+    fn parse<T: Into<OsString>>(args: impl IntoIterator<Item = T>) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(with_default_subcommand(args.into_iter().map(Into::into)))
+    }
+
+    fn parse_process<T: Into<OsString>>(
+        args: impl IntoIterator<Item = T>,
+    ) -> Result<ProcessArgs, clap::Error> {
+        parse(args).map(|cli| match cli.command {
+            Command::Process(args) => args,
+            _ => panic!("not a process command line"),
+        })
+    }
+
+    fn process_matches<T: Into<OsString>>(
+        args: impl IntoIterator<Item = T>,
+    ) -> (ProcessArgs, ArgMatches) {
+        let args = with_default_subcommand(args.into_iter().map(Into::into));
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let Command::Process(process) = Cli::from_arg_matches(&matches).unwrap().command else {
+            panic!("not a process command line");
+        };
+        (process, matches.subcommand_matches("process").unwrap().clone())
+    }
+
     #[test]
     fn test_cli_parsing() {
-        use clap::Parser;
+        assert!(parse_process(["autotune-cli", "-i", "input.wav", "-o", "output.wav"]).is_ok());
+        // Spelled out, as before there were other subcommands, or with global options first
+        let explicit = ["autotune-cli", "process", "-i", "a.wav", "-o", "b.wav"];
+        assert!(parse_process(explicit).is_ok());
+        let cli = parse(["autotune-cli", "-v", "-i", "a.wav", "-o", "b.wav"]).unwrap();
+        assert!(cli.verbose && matches!(cli.command, Command::Process(_)));
+        let cli = parse(["autotune-cli", "-v", "--scratch-cap", "64", "analyze", "take.wav"]);
+        let cli = cli.unwrap();
+        assert!(cli.verbose && matches!(cli.command, Command::Analyze(_)));
+        let cli = parse(["autotune-cli", "--trace-file=t.json", "keys"]).unwrap();
+        assert!(matches!(cli.command, Command::Keys));
+        let cli = parse(["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--scratch-dir", "/ssd"]);
+        let cli = cli.unwrap();
+        assert_eq!(cli.scratch_dir, Some(PathBuf::from("/ssd")));
+        assert_eq!(cli.scratch_cap, None);
+        assert_eq!(
+            parse(["autotune-cli", "keys", "--scratch-cap", "512"]).unwrap().scratch_cap,
+            Some(512)
+        );
+        let piped = ["autotune-cli", "-i", "-", "-o", "-", "--raw-format", "f32le"];
+        let piped = parse_process(piped.into_iter().chain(["--raw-rate", "48000"])).unwrap();
+        assert_eq!(piped.raw_format, Some(RawFormat(BitDepth::Float32)));
+        assert_eq!((piped.raw_rate, piped.raw_channels), (Some(48000), 1));
+        // Piped audio has no header to say how it is laid out
+        assert!(parse_process(["autotune-cli", "-i", "-", "-o", "b.wav"]).is_err());
+        assert!(parse_process(["autotune-cli", "-i", "a.wav", "-o", "-"]).is_err());
+        let no_rate = ["autotune-cli", "-i", "-", "-o", "b.wav", "--raw-format", "s16le"];
+        assert!(parse_process(no_rate).is_err());
+        let out_only = ["autotune-cli", "-i", "a.wav", "-o", "-", "--raw-format", "s16le"];
+        assert!(parse_process(out_only).is_ok());
+
+        assert!(matches!(parse(["autotune-cli", "keys"]).unwrap().command, Command::Keys));
+        let cli = parse(["autotune-cli", "analyze", "take.wav", "--export-labels", "n.txt"]);
+        assert!(
+            matches!(cli.unwrap().command, Command::Analyze(args) if args.input().ends_with("take.wav"))
+        );
+        let Command::Batch(batch) =
+            parse(["autotune-cli", "batch", "takes", "tuned", "-k", "12"]).unwrap().command
+        else {
+            panic!("not a batch command line");
+        };
+        assert_eq!(batch.tune.params().key, 12);
+        let named = parse_process(["autotune-cli", "-i", "a.wav", "-o", "b.wav", "-k", "Am"]);
+        assert_eq!(named.unwrap().tune.key, 12);
+        assert!(parse_process(["autotune-cli", "-i", "a.wav", "-o", "b.wav", "-k", "24"]).is_err());
+        assert_eq!(
+            batch_output(Path::new("takes/lead.mp3"), &batch.output_dir),
+            Path::new("tuned/lead_tuned.wav")
+        );
+        assert!(!batch.watch);
+        let watch = ["autotune-cli", "batch", "in", "out", "--watch", "--settle", "5"];
+        let Command::Batch(watch) = parse(watch).unwrap().command else {
+            panic!("not a batch command line");
+        };
+        assert!(watch.watch);
+        assert_eq!((watch.settle, watch.poll_interval), (5, 2));
+        let Command::Generate(generate) = parse(["autotune-cli", "generate"]).unwrap().command
+        else {
+            panic!("not a generate command line");
+        };
+        assert_eq!(generate.dir, PathBuf::from("."));
+        let compare = ["autotune-cli", "compare", "take.wav", "grid", "--strengths", "0.5,0.9"];
+        let Command::Compare(compare) = parse(compare).unwrap().command else {
+            panic!("not a compare command line");
+        };
+        assert_eq!((compare.strengths, compare.transitions), (vec![0.5, 0.9], Vec::new()));
+        let params = ProcessParams { strength: 0.5, transition: 0.05, ..Default::default() };
+        assert_eq!(
+            compare_output(&compare.input, &compare.output_dir, &params),
+            Path::new("grid/take_s0.50_t0.05.wav")
+        );
+        #[cfg(feature = "scripting")]
+        {
+            let script = ["autotune-cli", "run-script", "job.rhai", "takes", "--dry-run"];
+            let Command::RunScript(script) = parse(script).unwrap().command else {
+                panic!("not a run-script command line");
+            };
+            assert_eq!(script.script, PathBuf::from("job.rhai"));
+            assert_eq!(script.args, ["takes", "--dry-run"]);
+        }
+        // The compared settings replace the single ones
+        let both = ["autotune-cli", "compare", "a.wav", "grid", "--strengths", "1", "-s", "1"];
+        assert!(parse(both).is_err());
+
+        // Processing options belong to process and batch
+        assert!(parse(["autotune-cli", "analyze", "take.wav", "-s", "0.5"]).is_err());
+        let report = ["autotune-cli", "analyze", "-i", "take.wav", "--report", "r.csv", "-k", "Am"];
+        let Command::Analyze(report) = parse(report).unwrap().command else {
+            panic!("not an analyze command line");
+        };
+        assert_eq!(report.input(), Path::new("take.wav"));
+        assert_eq!((report.key, report.report), (Some(12), Some(PathBuf::from("r.csv"))));
+        assert!(parse(["autotune-cli", "analyze", "take.wav", "-k", "24"]).is_err());
+        assert!(parse(["autotune-cli", "analyze", "a.wav", "-i", "b.wav"]).is_err());
+        assert!(parse(["autotune-cli", "analyze"]).is_err());
+        assert!(parse(["autotune-cli", "--list-keys"]).is_err());
+    }
+
+    #[test]
+    fn test_note_parsing() {
+        let note = |value: &str| {
+            parse_process(["autotune-cli", "-i", "a.wav", "-o", "b.wav", "-n", value])
+                .map(|cli| cli.tune.note)
+        };
+        assert_eq!(note("auto").unwrap(), 0);
+        assert_eq!(note("A").unwrap(), 10);
+        assert_eq!(note("Db").unwrap(), 2);
+        assert_eq!(note("12").unwrap(), 12);
+        assert!(note("H").is_err());
+        assert!(ProcessParams { note: 13, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_frequencies_parsing() {
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--frequencies", "220, 330"];
+        let cli = parse_process(args).unwrap();
+        assert_eq!(cli.frequencies.unwrap().len(), 2);
+
+        // Neither a list nor a file
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--frequencies", "gamelan.txt"];
+        assert!(parse_process(args).is_err());
+        let args =
+            ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--frequencies", "220", "-n", "A"];
+        assert!(parse_process(args).is_err());
+    }
+
+    #[test]
+    fn test_spool_parsing() {
+        let cli = parse_process(["autotune-cli", "--spool", "jobs", "--watch"]).unwrap();
+        assert_eq!(cli.spool, Some(PathBuf::from("jobs")));
+        assert!(cli.watch);
+
+        // Watch mode only makes sense with a spool
+        assert!(parse_process(["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--watch"]).is_err());
+    }
+
+    #[test]
+    fn test_align_parsing() {
+        let args = ["autotune-cli", "-i", "double.wav", "-o", "out.wav", "--align-to", "lead.wav"];
+        let cli = parse_process(args).unwrap();
+        assert_eq!(cli.align_to, Some(PathBuf::from("lead.wav")));
+        assert_eq!(cli.align_limits().unwrap().max_shift_secs, 0.06);
+
+        // The limits only apply to alignment
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--max-shift-ms", "20"];
+        assert!(parse_process(args).is_err());
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--align-to", "c.wav"];
+        let mut cli = parse_process(args).unwrap();
+        cli.max_pitch_cents = -1.0;
+        assert!(cli.align_limits().is_err());
+    }
+
+    #[test]
+    fn test_detect_key_parsing() {
+        let cli = parse_process(["autotune-cli", "-i", "take.wav", "--detect-key"]).unwrap();
+        assert!(cli.detect_key && cli.output.is_none());
+
+        // The detected key replaces --key, so both can't be given
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--detect-key", "-k", "3"];
+        assert!(parse_process(args).is_err());
+        assert!(parse_process(["autotune-cli", "-i", "a.wav"]).is_err());
+    }
+
+    #[test]
+    fn test_label_file_parsing() {
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--regions", "chorus.TextGrid"];
+        let cli = parse_process(args).unwrap();
+        assert_eq!(cli.regions, Some(PathBuf::from("chorus.TextGrid")));
+        assert!(cli.export_labels.is_none());
+        assert!(!cli.stems);
+
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--start", "12.5s", "--end"];
+        let cli = parse_process(args.iter().chain(&["661500smp"])).unwrap();
+        assert_eq!(cli.start, Some(Position::Secs(12.5)));
+        assert_eq!(cli.end.unwrap().secs(44100), 15.0);
+        assert!(parse_process(args.iter().chain(&["-3"])).is_err());
+        assert!(parse_process(args.iter().chain(&["15 samples"])).is_err());
+
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--split-silence"];
+        let cli = parse_process(args.iter().chain(&["--silence-threshold", "-50"])).unwrap();
+        assert_eq!((cli.silence_threshold, cli.min_silence), (-50.0, 0.5));
+        assert!(!cli.split_files);
+        assert!(parse_process(args.iter().chain(&["--regions", "c.txt"])).is_err());
+        assert!(parse_process(args.iter().chain(&["--split-files", "--midi", "c.mid"])).is_err());
+        assert!(
+            parse_process(["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--split-files"]).is_err()
+        );
+        let phrase = phrase_path(Path::new("takes/hour.wav"), 0, 40);
+        assert_eq!(phrase, PathBuf::from("takes/hour_001.wav"));
+        assert_eq!(phrase_path(Path::new("hour.wav"), 1233, 1500), PathBuf::from("hour_1234.wav"));
+
+        let harmony = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--harmony", "-8,3"];
+        let cli = parse_process(harmony).unwrap();
+        assert_eq!(cli.harmony, ["-8".parse().unwrap(), Interval::THIRD]);
+        assert_eq!(cli.harmony_level, 0.5);
+        let routed = harmony.iter().chain(&["--route", "3=1,0", "--route", "-8=0,1"]);
+        let cli = parse_process(routed).unwrap();
+        assert_eq!(
+            cli.routes[1],
+            Route { voice: Voice::Harmony(cli.harmony[0]), left: 0.0, right: 1.0 }
+        );
+        assert!(parse_process(harmony.iter().chain(&["--route", "3=1"])).is_err());
+        let wide = parse_process(harmony.iter().chain(&["--width", "0.7"])).unwrap();
+        assert_eq!(wide.width, Some(0.7));
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--width", "0.7"];
+        assert!(parse_process(args).is_err());
+
+        // Only a plain render reports as JSON
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--json"];
+        assert!(parse_process(args).unwrap().json);
+        assert!(parse_process(args.iter().chain(&["--align-to", "c.wav"])).is_err());
+
+        // Aligning a double has no regions
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--align-to", "c.wav"];
+        assert!(parse_process(args.iter().chain(&["--export-labels", "d.txt"])).is_err());
+        assert!(parse_process(args.iter().chain(&["--export-markers", "d.csv"])).is_err());
+        assert!(parse_process(args.iter().chain(&["--midi", "melody.mid"])).is_err());
+        assert!(parse_process(args.iter().chain(&["--sections", "song.txt"])).is_err());
+        assert!(parse_process(args.iter().chain(&["--stems"])).is_err());
+        assert!(parse_process(args.iter().chain(&["--harmony", "3"])).is_err());
+    }
+
+    #[test]
+    fn test_scale_parsing() {
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--scale", "0,2,3,5,7,8,11"];
+        let params = parse_process(args).unwrap().tune.params();
+        assert_eq!(params.scale.unwrap().to_string(), "0,2,3,5,7,8,11");
+
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--scale", "4,13"];
+        assert!(parse_process(args).is_err());
+        // A scale picks the note itself
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--scale", "0", "-n", "3"];
+        assert!(parse_process(args).is_err());
+
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--energy-strength"];
+        assert_eq!(parse_process(args).unwrap().tune.params().energy, Some(EnergyCurve::default()));
+        let curve = parse_process(args.iter().chain(&["--energy-curve", "-50,-20,0.5,2"]));
+        assert_eq!(curve.unwrap().tune.params().energy.unwrap().shape, 2.0);
+        assert!(parse_process(args.iter().chain(&["--energy-curve", "-20,-50,0.5"])).is_err());
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--energy-curve", "-50,-20,0.5"];
+        assert!(parse_process(args).is_err());
+        assert_eq!(parse_process(&args[..5]).unwrap().tune.params().energy, None);
+    }
+
+    #[test]
+    fn test_failures_exit_by_class() {
+        let run = |input: &str, params: &ProcessParams| {
+            let output = Path::new("b.wav");
+            let e =
+                process_location(Path::new(input), output, params, &SideFiles::default(), false)
+                    .unwrap_err();
+            failure::class_of(&*e)
+        };
+        let params = ProcessParams::default();
+        assert_eq!(run("notes.txt", &params), Some(Failure::UnsupportedFormat));
+        assert_eq!(run("missing_take.wav", &params), Some(Failure::Decode));
+        let e = ProcessParams { strength: 1.5, ..params }.validate().or_fail(Failure::Arguments);
+        assert_eq!(failure::exit_code(&*e.unwrap_err()), 2);
+    }
+
+    #[test]
+    fn test_pre_processing_flags() {
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--high-pass", "100", "--gate"];
+        let params = parse_process(args.iter().chain(&["--gate-threshold", "-45"])).unwrap();
+        let params = params.tune.params();
+        assert_eq!(params.high_pass_hz, Some(100.0));
+        assert_eq!(params.gate, Some(Gate { threshold_db: -45.0, ..Gate::default() }));
+        assert!(params.validate().is_ok());
+        assert_eq!(parse_process(&args[..5]).unwrap().tune.params().gate, None);
+        assert!(parse_process(args[..5].iter().chain(&["--gate-release", "50"])).is_err());
+
+        let low = ProcessParams { high_pass_hz: Some(5.0), ..Default::default() };
+        assert!(low.validate().is_err());
+
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--chain"];
+        let order = ["autotune,gate,high-pass,compressor,eq"];
+        let chain = parse_process(args.iter().chain(&order)).unwrap().tune.params().chain;
+        assert_eq!(
+            chain.stages(),
+            [Stage::Autotune, Stage::Gate, Stage::HighPass, Stage::Compressor, Stage::Eq]
+        );
+        assert_eq!(parse_process(&args[..5]).unwrap().tune.chain, ChainOrder::default());
+        assert!(parse_process(args.iter().chain(&["gate,gate,autotune,eq,compressor"])).is_err());
+
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--eq", "250:-3:1.2,4000:3:0.8"];
+        let eq = parse_process(args).unwrap().tune.params().eq.unwrap();
+        assert_eq!(eq.active().map(|band| band.gain_db).collect::<Vec<_>>(), [-3.0, 3.0]);
+        assert!(parse_process(args[..6].iter().chain(&["250:-3"])).is_err());
+
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--compress", "--limit", "-2"];
+        let params = parse_process(args.iter().chain(&["--compress-ratio", "4"])).unwrap();
+        let params = params.tune.params();
+        assert_eq!(params.compressor, Some(Compressor { ratio: 4.0, ..Compressor::default() }));
+        assert_eq!(params.limit().map(|limit| limit.ceiling_db), Some(-2.0));
+        assert!(params.validate().is_ok());
+        assert!(parse_process(args[..5].iter().chain(&["--compress-attack", "5"])).is_err());
+        let loud = ProcessParams { limit_db: Some(3.0), ..Default::default() };
+        assert!(loud.validate().is_err());
+    }
+
+    #[test]
+    fn test_project_under_command_line() {
+        let project = ProjectFile {
+            input: Some(PathBuf::from("take.wav")),
+            output: Some(PathBuf::from("take_tuned.wav")),
+            key: 5,
+            scale: Some("0,3,5,7,10".parse().unwrap()),
+            strength: 0.5,
+            a4: 442.0,
+            ..Default::default()
+        };
+        let parse = |args: &[&str]| {
+            let (mut cli, matches) =
+                process_matches(["autotune-cli", "--project", "a.atproj"].iter().chain(args));
+            cli.apply_project(&project, &matches);
+            cli
+        };
+
+        let cli = parse(&["-s", "0.9", "-o", "other.wav"]);
+        assert_eq!(cli.input, project.input);
+        assert_eq!(cli.output, Some(PathBuf::from("other.wav")));
+        assert_eq!((cli.tune.key, cli.tune.strength, cli.tune.scale), (5, 0.9, project.scale));
+        // Saving the run again gives the same project, apart from what was overridden
+        let saved = cli.project_file(&cli.tune.params());
+        assert_eq!(
+            saved,
+            ProjectFile { strength: 0.9, output: cli.output.clone(), ..project.clone() }
+        );
+
+        // A note replaces the project's scale
+        let cli = parse(&["-n", "3"]);
+        assert_eq!((cli.tune.note, cli.tune.scale), (3, None));
+        assert_eq!(parse(&["--a4", "415"]).tune.a4, 415.0);
+        assert!(ProcessParams { a4: 220.0, ..Default::default() }.validate().is_err());
+
+        // A project without an output needs one from the command line, even to print it
+        let path =
+            std::env::temp_dir().join(format!("autotune_no_output_{}.atproj", std::process::id()));
+        ProjectFile { output: None, ..project }.save(&path).unwrap();
+        let (cli, matches) =
+            process_matches(["autotune-cli", "--project", path.to_str().unwrap(), "-v"]);
+        let e = run_process(cli, &matches, true, false).unwrap_err();
+        assert_eq!(failure::class_of(&*e), Some(Failure::Arguments));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_preset_under_command_line() {
+        let preset = presets::find(&[], "subtle").unwrap();
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--preset", "Subtle", "-f", "3"];
+        let (mut cli, matches) = process_matches(args);
+        cli.apply_preset(&preset, &matches);
+        assert_eq!((cli.tune.strength, cli.tune.transition, cli.tune.formant), (0.4, 0.1, 3));
+
+        // Saving or listing presets doesn't need any files
+        assert!(parse_process(["autotune-cli", "--save-preset", "Mine", "-s", "0.6"]).is_ok());
+        assert!(parse_process(["autotune-cli", "--list-presets", "--portable"]).is_ok());
+    }
+
+    #[test]
+    fn test_params_validation() {
+        assert!(ProcessParams::default().validate().is_ok());
+        assert!(ProcessParams { key: 24, ..Default::default() }.validate().is_err());
+        assert!(ProcessParams { strength: 1.5, ..Default::default() }.validate().is_err());
+        assert!(ProcessParams { max_speed: Some(0.0), ..Default::default() }.validate().is_err());
+        assert!(
+            ProcessParams { output_rate: Some(96_000), ..Default::default() }.validate().is_ok()
+        );
+        assert!(
+            ProcessParams { output_rate: Some(1_000), ..Default::default() }.validate().is_err()
+        );
+        assert!(ProcessParams { tint_amount: 2.0, ..Default::default() }.validate().is_err());
+        let unvoiced = ProcessParams { unvoiced_sensitivity: -0.1, ..Default::default() };
+        assert!(unvoiced.validate().is_err());
+        assert!(
+            ProcessParams { output_channels: Some(6), ..Default::default() }.validate().is_err()
+        );
+        let large = ProcessParams { fft_size: 4096, hop_size: 1024, ..Default::default() };
+        assert!(large.validate().is_ok());
+        let uneven = ProcessParams { fft_size: 2048, hop_size: 300, ..Default::default() };
+        assert!(uneven.validate().is_err());
+        assert!(ProcessParams { fft_size: 1000, ..Default::default() }.validate().is_err());
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--window", "blackman"];
+        assert_eq!(parse_process(args).unwrap().tune.params().window, Window::Blackman);
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--padding", "reflect"];
+        assert_eq!(parse_process(args).unwrap().tune.params().padding, Padding::Reflect);
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--pitch-algo", "pyin"];
+        let params = parse_process(args).unwrap().tune.params();
+        assert_eq!(params.pitch_algorithm, PitchAlgorithm::Pyin);
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--f0-min", "38"];
+        let params = parse_process(args).unwrap().tune.params();
+        assert_eq!(params.f0_range, Some(F0Range { min: 38.0, max: 1000.0 }));
+        assert!(params.validate().is_ok());
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav"];
+        assert_eq!(parse_process(args).unwrap().tune.params().f0_range, None);
+        let args =
+            ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--f0-min", "600", "--f0-max", "300"];
+        assert!(parse_process(args).unwrap().tune.params().validate().is_err());
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--output-length", "44100"];
+        assert_eq!(parse_process(args).unwrap().tune.params().length, OutputLength::Frames(44100));
+
+        let input = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 24,
+            sample_format: hound::SampleFormat::Int,
+        };
+        assert_eq!(ProcessParams::default().output_spec(input), input);
+        let params = ProcessParams {
+            output_rate: Some(48_000),
+            output_depth: Some(BitDepth::Float32),
+            output_channels: Some(1),
+            ..Default::default()
+        };
+        let spec = params.output_spec(input);
+        assert_eq!((spec.sample_rate, spec.channels, spec.bits_per_sample), (48_000, 1, 32));
+        assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--output-depth", "16"];
+        assert_eq!(parse_process(args).unwrap().tune.output_depth, Some(BitDepth::Int16));
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--output-channels", "3"];
+        assert!(parse_process(args).is_err());
+
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--normalize"];
+        let cli = parse_process(args.iter().chain(&["lufs:-16"])).unwrap();
+        assert_eq!(cli.tune.params().normalization, Normalization::Loudness(-16.0));
+        assert_eq!(parse_process(&args[..5]).unwrap().tune.normalize, Normalization::Clip);
+        assert!(parse_process(args.iter().chain(&["lufs:-80"])).is_err());
+    }
+
+    #[test]
+    fn test_render_fingerprint_ignores_speed_but_not_chunking() {
+        let input = std::env::temp_dir().join("cli_fingerprint_test.wav");
+        std::fs::write(&input, [0u8; 16]).unwrap();
+        let output = Path::new("tuned.wav");
+        let params = ProcessParams::default();
+        let side_files = SideFiles::default();
+        let identity = render_fingerprint(&input, output, &params, &side_files).unwrap();
+
+        let capped = ProcessParams { max_speed: Some(2.0), ..params };
+        assert_eq!(render_fingerprint(&input, output, &capped, &side_files), Some(identity));
+        let chunked = ProcessParams { threads: 4, ..params };
+        let chunks = render_fingerprint(&input, output, &chunked, &side_files);
+        assert_ne!(chunks, Some(identity));
+        let more_threads = ProcessParams { threads: 8, ..params };
+        assert_eq!(render_fingerprint(&input, output, &more_threads, &side_files), chunks);
+        let stronger = ProcessParams { strength: 1.0, ..params };
+        assert_ne!(render_fingerprint(&input, output, &stronger, &side_files), Some(identity));
+        let harmony = SideFiles { harmony: vec![Interval::THIRD], ..Default::default() };
+        assert_ne!(render_fingerprint(&input, output, &params, &harmony), Some(identity));
+        // An earlier render wouldn't have left the stems
+        let stems = SideFiles { stems: true, ..Default::default() };
+        assert_eq!(render_fingerprint(&input, output, &params, &stems), None);
+        let missing = SideFiles { tint: Some(PathBuf::from("missing.wav")), ..Default::default() };
+        assert_eq!(render_fingerprint(&input, output, &params, &missing), None);
+        let _ = std::fs::remove_file(&input);
 
-        // Test basic parsing
-        let args = vec!["autotune-cli", "-i", "input.wav", "-o", "output.wav"];
-        let cli = Cli::try_parse_from(args);
-        assert!(cli.is_ok());
+        let args = ["autotune-cli", "-i", "a.wav", "-o", "b.wav", "--reuse"];
+        assert!(parse_process(args).unwrap().reuse);
+        assert!(parse_process(args.iter().chain(&["--align-to", "c.wav"])).is_err());
     }
 }
@@ -1,10 +1,38 @@
+use autotune_core::{
+    align, bookmarks, classifier, clock, decoder, demo_audio, dynamics, effects, energy, engine,
+    equalizer, fingerprint, format, frequencies, harmony, intonation, keys, labels, loudness, midi,
+    paths, pcm, phrases, pipeline, pitch, prefilter, profiling, resample, routing, scale, scratch,
+    sections, tint,
+};
+
+mod app_config;
 mod cli;
+mod failure;
+mod jobs;
+mod pipe;
+mod presets;
+mod progress;
+mod project_file;
+mod report;
+#[cfg(feature = "object-storage")]
+mod s3;
+#[cfg(feature = "scripting")]
+mod script;
+mod storage;
+mod throttle;
+mod watch;
 
 fn main() {
     env_logger::init();
+    failure::catch_interrupt();
 
     if let Err(e) = cli::run_cli() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+        let code = failure::exit_code(&*e);
+        if report::json() {
+            report::emit(&report::Event::Error { message: e.to_string(), code });
+        } else {
+            eprintln!("Error: {}", e);
+        }
+        std::process::exit(code);
     }
 }
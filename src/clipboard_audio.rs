@@ -0,0 +1,148 @@
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::decoder;
+
+// MIME types used by apps that put WAV/PCM audio on the clipboard
+const AUDIO_MIME_TYPES: [&str; 4] = ["audio/wav", "audio/x-wav", "audio/wave", "audio/vnd.wave"];
+
+/// Directory that pasted clips are written to before processing.
+pub fn paste_dir() -> PathBuf {
+    std::env::temp_dir().join("autotune-clipboard")
+}
+
+/// Fetch WAV bytes from the system clipboard, if it currently holds audio.
+///
+/// Only Linux exposes arbitrary clipboard formats through standard tools (`wl-paste` on
+/// Wayland, `xclip` on X11); on other platforms this fails, as only paths can be pasted.
+pub fn read_clipboard_wav() -> Result<Option<Vec<u8>>, String> {
+    if !cfg!(target_os = "linux") {
+        return Err(
+            "Pasting audio isn't supported on this platform, paste an audio file path instead"
+                .to_string(),
+        );
+    }
+
+    let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+    let types = if wayland {
+        run_clipboard_tool("wl-paste", &["--list-types"])?
+    } else {
+        run_clipboard_tool("xclip", &["-selection", "clipboard", "-t", "TARGETS", "-o"])?
+    };
+
+    let types = String::from_utf8_lossy(&types);
+    let Some(&mime) =
+        AUDIO_MIME_TYPES.iter().find(|mime| types.lines().any(|t| t.trim() == **mime))
+    else {
+        return Ok(None);
+    };
+
+    let bytes = if wayland {
+        run_clipboard_tool("wl-paste", &["--no-newline", "--type", mime])?
+    } else {
+        run_clipboard_tool("xclip", &["-selection", "clipboard", "-t", mime, "-o"])?
+    };
+
+    Ok(Some(bytes))
+}
+
+fn run_clipboard_tool(program: &str, args: &[&str]) -> Result<Vec<u8>, String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Clipboard access requires `{}`: {}", program, e))?;
+
+    // Both tools exit non-zero when the clipboard is empty
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Validate clipboard bytes as WAV and store them as a file in `dir`.
+pub fn save_pasted_wav(bytes: &[u8], dir: &Path) -> Result<PathBuf, String> {
+    hound::WavReader::new(Cursor::new(bytes))
+        .map_err(|e| format!("Clipboard audio is not a readable WAV: {}", e))?;
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let path = dir.join(format!("pasted_{}.wav", stamp));
+
+    std::fs::write(&path, bytes)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+/// Interpret pasted text as an audio file reference (plain path or `file://` URI).
+pub fn path_from_text(text: &str) -> Option<PathBuf> {
+    let line =
+        text.lines().map(str::trim).find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    let line = line.trim_matches(|c| c == '"' || c == '\'');
+
+    let path = match line.strip_prefix("file://") {
+        // Drop an optional host part ("file://localhost/...")
+        Some(rest) => PathBuf::from(percent_decode(&rest[rest.find('/')?..])?),
+        None => PathBuf::from(line),
+    };
+
+    decoder::is_supported_input(&path).then_some(path)
+}
+
+fn percent_decode(input: &str) -> Option<String> {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = input.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_from_plain_text() {
+        assert_eq!(path_from_text("/tmp/take 1.wav\n"), Some(PathBuf::from("/tmp/take 1.wav")));
+        assert_eq!(path_from_text("\"/tmp/Take.WAV\""), Some(PathBuf::from("/tmp/Take.WAV")));
+        assert_eq!(path_from_text("/tmp/take.mp3"), Some(PathBuf::from("/tmp/take.mp3")));
+        assert_eq!(path_from_text("/tmp/notes.txt"), None);
+        assert_eq!(path_from_text(""), None);
+    }
+
+    #[test]
+    fn test_path_from_file_uri() {
+        assert_eq!(
+            path_from_text("file:///home/me/take%201.wav"),
+            Some(PathBuf::from("/home/me/take 1.wav"))
+        );
+        assert_eq!(
+            path_from_text("# uri list\nfile://localhost/srv/vox.wav"),
+            Some(PathBuf::from("/srv/vox.wav"))
+        );
+        assert_eq!(path_from_text("file:///bad%zz.wav"), None);
+    }
+
+    #[test]
+    fn test_rejects_non_wav_bytes() {
+        let dir = std::env::temp_dir().join("autotune-clipboard-test");
+        assert!(save_pasted_wav(b"not audio", &dir).is_err());
+    }
+}
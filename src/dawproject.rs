@@ -0,0 +1,645 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::ops::{ControlFlow, Range};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use synthphone_vocals::{AutotuneConfig, MusicalSettings};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::audio_processor::RenderOptions;
+use crate::dynamics::Limit;
+use crate::loudness::Normalization;
+use crate::pipeline::{self, OutputLength, RenderSettings};
+use crate::scale::Scale;
+use crate::scratch::JobDir;
+use crate::{decoder, paths};
+
+pub const EXTENSION: &str = "dawproject";
+const PROJECT_XML: &str = "project.xml";
+// Added to the stem of every corrected file
+const SUFFIX: &str = "_tuned";
+// Words in a track or clip name that suggest a voice
+const VOCAL_WORDS: [&str; 5] = ["vox", "vocal", "voice", "choir", "sing"];
+
+/// One audio clip of the arrangement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectClip {
+    pub track: String,
+    pub name: String,
+    // As written in the project: inside the zip, or on disk when `external`
+    pub file: String,
+    pub external: bool,
+    // Index of the clip's `File` element among all of them, to point it somewhere else
+    file_element: usize,
+}
+
+impl ProjectClip {
+    pub fn looks_like_vocals(&self) -> bool {
+        let names = format!("{} {}", self.track, self.name).to_lowercase();
+        VOCAL_WORDS.iter().any(|word| names.contains(word))
+    }
+}
+
+/// A DAWproject file and the audio clips it arranges. The file is a zip of `project.xml`
+/// and the audio it embeds. AAF sessions aren't read; most DAWs can export the same session
+/// as DAWproject.
+#[derive(Debug, Clone)]
+pub struct Project {
+    pub path: PathBuf,
+    pub clips: Vec<ProjectClip>,
+    xml: String,
+}
+
+impl Project {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let extension = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        if extension.as_deref() == Some("aaf") {
+            return Err("AAF sessions aren't supported, export the session as DAWproject instead"
+                .to_string());
+        }
+
+        let mut archive = open_archive(path)?;
+        let mut xml = String::new();
+        archive
+            .by_name(PROJECT_XML)
+            .map_err(|e| format!("{} has no {}: {}", paths::display_name(path), PROJECT_XML, e))?
+            .read_to_string(&mut xml)
+            .map_err(|e| format!("Failed to read {}: {}", PROJECT_XML, e))?;
+        let clips = parse_project(&xml)?;
+        Ok(Self { path: path.to_path_buf(), clips, xml })
+    }
+}
+
+/// How the picked clips are corrected, the same for all of them.
+pub struct ClipCorrection {
+    pub config: AutotuneConfig,
+    pub musical: MusicalSettings,
+    pub scale: Option<Scale>,
+    pub a4: f32,
+    pub options: RenderOptions,
+}
+
+pub enum ProjectEvent {
+    Progress(f32), // 0.0 to 1.0
+    Done(ProjectRender),
+    Failed(String),
+}
+
+/// Outcome of correcting clips into a project copy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectRender {
+    // Audio files corrected, fewer than the clips when several share a file
+    pub files: usize,
+    pub duration_secs: f32,
+    pub failed_frames: usize,
+}
+
+/// Correct the audio of the clips at `selected` and write a copy of the project to `output`
+/// that plays the corrected files in their place. Embedded audio is added to the copy;
+/// external audio is written next to it. The original project is left as it is.
+pub fn render(
+    project: &Project,
+    selected: &[usize],
+    output: &Path,
+    correction: &ClipCorrection,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(f32),
+) -> Result<ProjectRender, String> {
+    if paths::normalize_for_io(output) == paths::normalize_for_io(&project.path) {
+        return Err("Save the corrected project under a new name".to_string());
+    }
+    let mut archive = open_archive(&project.path)?;
+    let scratch = JobDir::create("dawproject")?;
+    let output_dir = output.parent().unwrap_or(Path::new("."));
+
+    // Clips sharing a file get the same corrected file
+    let mut files: Vec<&ProjectClip> = Vec::new();
+    for &index in selected {
+        let clip = project.clips.get(index).ok_or("No such clip")?;
+        if !files.iter().any(|file| file.file == clip.file && file.external == clip.external) {
+            files.push(clip);
+        }
+    }
+
+    let mut taken: HashSet<String> = archive.file_names().map(str::to_string).collect();
+    // Corrected path by original (path, external)
+    let mut corrected: HashMap<(&str, bool), String> = HashMap::new();
+    let mut embedded = Vec::new();
+    let mut result = ProjectRender { files: 0, duration_secs: 0.0, failed_frames: 0 };
+
+    for (number, clip) in files.iter().enumerate() {
+        let source = Path::new(&clip.file);
+        let input = if clip.external {
+            project.path.parent().unwrap_or(Path::new(".")).join(source)
+        } else {
+            let input = scratch.path().join(format!("{}-{}", number, paths::display_name(source)));
+            extract(&mut archive, &clip.file, &input)?;
+            input
+        };
+        let stem = source.file_stem().map_or("clip".into(), |stem| stem.to_string_lossy());
+        let name = unique_name(&taken, &format!("{}{}", stem, SUFFIX));
+        let (file, destination) = if clip.external {
+            (name.clone(), output_dir.join(&name))
+        } else {
+            let dir = clip.file.rsplit_once('/').map_or("", |(dir, _)| dir);
+            let file = if dir.is_empty() {
+                name.clone()
+            } else {
+                format!("{}/{}", dir, name)
+            };
+            let destination = scratch.path().join(&name);
+            embedded.push((file.clone(), destination.clone()));
+            (file, destination)
+        };
+        taken.insert(name.clone());
+        taken.insert(file.clone());
+
+        let info = decoder::probe(&input)?;
+        let spec = info.output_spec();
+        let settings = RenderSettings {
+            config: correction.config,
+            window: correction.options.window,
+            padding: correction.options.padding,
+            pitch_algorithm: correction.options.pitch_algorithm,
+            f0_range: correction.options.f0_range,
+            musical: MusicalSettings {
+                key: correction.musical.key,
+                note: correction.musical.note,
+                octave: correction.musical.octave,
+                formant: correction.musical.formant,
+            },
+            scale: correction.scale,
+            key_changes: Vec::new(),
+            sections: Vec::new(),
+            melody: None,
+            processing_rate: correction.options.processing_rate(spec.sample_rate),
+            // Corrected clips replace the session's files, so they keep their rate
+            output_rate: None,
+            length: OutputLength::Input,
+            threads: correction.options.threads,
+            regions: Vec::new(),
+            correct_fry: correction.options.correct_fry,
+            confidence_strength: correction.options.confidence_strength,
+            unvoiced_sensitivity: correction.options.unvoiced_sensitivity,
+            a4: correction.a4,
+            energy: None,
+            chain: correction.options.chain,
+            high_pass_hz: correction.options.high_pass_hz,
+            gate: correction.options.gate,
+            eq: correction.options.eq,
+            compressor: correction.options.compressor,
+            meters: None,
+            stems: false,
+            harmony: Vec::new(),
+            harmony_level: 0.0,
+            routing: Vec::new(),
+        };
+        let rendered = pipeline::render(&input, &destination, &settings, |progress| {
+            let fraction = progress.done as f32 / progress.total.max(1) as f32;
+            on_progress((number as f32 + fraction) / files.len() as f32);
+            if cancel.load(Ordering::Relaxed) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?;
+        if cancel.load(Ordering::Relaxed) {
+            return Err("Stopped before every clip was corrected".to_string());
+        }
+        // Only kept from clipping, so the clips stay at their level in the session's mix
+        let limit =
+            correction.options.limit_db.map(|ceiling_db| Limit { ceiling_db, meters: None });
+        let verification =
+            rendered.write(&destination, spec, Normalization::Clip, limit.as_ref())?;
+        let errors = verification.errors();
+        if !errors.is_empty() {
+            return Err(format!("{}: {}", clip.file, errors.join("; ")));
+        }
+
+        result.files += 1;
+        result.duration_secs += rendered.frames as f32 / spec.sample_rate as f32;
+        result.failed_frames += rendered.failed_frames.len();
+        corrected.insert((clip.file.as_str(), clip.external), file);
+    }
+
+    let replacements: HashMap<usize, String> = selected
+        .iter()
+        .map(|&index| &project.clips[index])
+        .map(|clip| (clip.file_element, corrected[&(clip.file.as_str(), clip.external)].clone()))
+        .collect();
+    let xml = rewrite_files(&project.xml, &replacements)?;
+    write_copy(&mut archive, &xml, &embedded, output)?;
+    Ok(result)
+}
+
+fn open_archive(path: &Path) -> Result<ZipArchive<BufReader<File>>, String> {
+    let file = File::open(paths::normalize_for_io(path))
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    ZipArchive::new(BufReader::new(file))
+        .map_err(|e| format!("{} isn't a DAWproject file: {}", paths::display_name(path), e))
+}
+
+fn extract(
+    archive: &mut ZipArchive<BufReader<File>>,
+    name: &str,
+    destination: &Path,
+) -> Result<(), String> {
+    let mut entry =
+        archive.by_name(name).map_err(|e| format!("The project has no {}: {}", name, e))?;
+    let mut file = File::create(paths::normalize_for_io(destination))
+        .map_err(|e| format!("Failed to create {}: {}", destination.display(), e))?;
+    io::copy(&mut entry, &mut file).map_err(|e| format!("Failed to extract {}: {}", name, e))?;
+    Ok(())
+}
+
+// The source archive with `xml` as its project and the `embedded` (name, file) pairs added,
+// every other entry copied as it is
+fn write_copy(
+    archive: &mut ZipArchive<BufReader<File>>,
+    xml: &str,
+    embedded: &[(String, PathBuf)],
+    output: &Path,
+) -> Result<(), String> {
+    let zip_error = |e: zip::result::ZipError| format!("Failed to write the project: {}", e);
+    let io_error = |e: io::Error| format!("Failed to write the project: {}", e);
+    let file = File::create(paths::normalize_for_io(output))
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let mut writer = ZipWriter::new(BufWriter::new(file));
+
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index).map_err(zip_error)?;
+        if entry.name() == PROJECT_XML {
+            writer.start_file(PROJECT_XML, FileOptions::default()).map_err(zip_error)?;
+            writer.write_all(xml.as_bytes()).map_err(io_error)?;
+        } else {
+            writer.raw_copy_file(entry).map_err(zip_error)?;
+        }
+    }
+    // Audio hardly compresses, so it is stored
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, path) in embedded {
+        writer.start_file(name.as_str(), stored).map_err(zip_error)?;
+        let mut audio = File::open(paths::normalize_for_io(path)).map_err(io_error)?;
+        io::copy(&mut audio, &mut writer).map_err(io_error)?;
+    }
+    writer.finish().map_err(zip_error)?.flush().map_err(io_error)
+}
+
+// `stem`.wav, numbered if a file of that name is already taken
+fn unique_name(taken: &HashSet<String>, stem: &str) -> String {
+    let mut name = format!("{}.wav", stem);
+    let mut number = 1;
+    while taken.iter().any(|file| file.rsplit('/').next() == Some(name.as_str())) {
+        number += 1;
+        name = format!("{}_{}.wav", stem, number);
+    }
+    name
+}
+
+/// The audio clips of a `project.xml`, in document order. Each is named after its track,
+/// found through the lane it sits in.
+pub fn parse_project(xml: &str) -> Result<Vec<ProjectClip>, String> {
+    // (element name, its track reference, its clip name) of every open element
+    let mut open: Vec<(&str, Option<String>, Option<String>)> = Vec::new();
+    let mut track_names = HashMap::new();
+    let mut clips = Vec::new();
+    let mut track_ids = Vec::new();
+    let mut file_element = 0;
+
+    for tag in tags(xml)? {
+        if tag.kind == TagKind::Close {
+            if let Some(depth) = open.iter().rposition(|(name, ..)| *name == tag.name) {
+                open.truncate(depth);
+            }
+            continue;
+        }
+        match tag.name {
+            "Track" => {
+                if let (Some(id), Some(name)) = (tag.value("id"), tag.value("name")) {
+                    track_names.insert(id, name);
+                }
+            }
+            "File" => {
+                let clip_name = open.iter().rev().find_map(|(_, _, clip)| clip.clone());
+                let in_audio = open.iter().any(|(name, ..)| *name == "Audio");
+                if let (Some(clip_name), Some(file), true) =
+                    (clip_name, tag.value("path"), in_audio)
+                {
+                    let name = if clip_name.is_empty() {
+                        paths::display_name(Path::new(&file))
+                    } else {
+                        clip_name
+                    };
+                    track_ids.push(open.iter().rev().find_map(|(_, track, _)| track.clone()));
+                    clips.push(ProjectClip {
+                        track: String::new(),
+                        name,
+                        file,
+                        external: tag.value("external").as_deref() == Some("true"),
+                        file_element,
+                    });
+                }
+                file_element += 1;
+            }
+            _ => {}
+        }
+        if tag.kind == TagKind::Open {
+            let clip = (tag.name == "Clip").then(|| tag.value("name").unwrap_or_default());
+            open.push((tag.name, tag.value("track"), clip));
+        }
+    }
+
+    // Tracks are usually declared before the arrangement, but don't have to be
+    for (clip, id) in clips.iter_mut().zip(track_ids) {
+        clip.track = id.and_then(|id| track_names.get(&id).cloned()).unwrap_or_default();
+    }
+    Ok(clips)
+}
+
+// `xml` with the path of each `File` element numbered in `replacements` changed
+fn rewrite_files(xml: &str, replacements: &HashMap<usize, String>) -> Result<String, String> {
+    let mut edits: Vec<(Range<usize>, &str)> = Vec::new();
+    let files =
+        tags(xml)?.into_iter().filter(|tag| tag.name == "File" && tag.kind != TagKind::Close);
+    for (index, tag) in files.enumerate() {
+        let path = tag.attributes.iter().find(|attribute| attribute.name == "path");
+        if let (Some(path), Some(replacement)) = (path, replacements.get(&index)) {
+            edits.push((path.span.clone(), replacement));
+        }
+    }
+
+    let mut rewritten = String::with_capacity(xml.len());
+    let mut end = 0;
+    for (span, replacement) in edits {
+        rewritten.push_str(&xml[end..span.start]);
+        rewritten.push_str(&escape(replacement));
+        end = span.end;
+    }
+    rewritten.push_str(&xml[end..]);
+    Ok(rewritten)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TagKind {
+    Open,
+    Close,
+    // `<Name/>`
+    Empty,
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    kind: TagKind,
+    attributes: Vec<Attribute<'a>>,
+}
+
+impl Tag<'_> {
+    fn value(&self, name: &str) -> Option<String> {
+        self.attributes.iter().find(|attribute| attribute.name == name).map(|a| unescape(a.raw))
+    }
+}
+
+struct Attribute<'a> {
+    name: &'a str,
+    raw: &'a str,
+    // Where `raw` sits in the document, between the quotes
+    span: Range<usize>,
+}
+
+// The element tags of an XML document, skipping the declaration, comments, CDATA, and text.
+// Only as much XML as project files use, not a validating parser.
+fn tags(xml: &str) -> Result<Vec<Tag<'_>>, String> {
+    let bytes = xml.as_bytes();
+    let mut tags = Vec::new();
+    let mut pos = 0;
+    let skip_to = |from: usize, end: &str| {
+        xml[from..]
+            .find(end)
+            .map(|at| from + at + end.len())
+            .ok_or_else(|| format!("Unterminated markup at byte {}", from))
+    };
+
+    while let Some(at) = xml[pos..].find('<') {
+        let start = pos + at;
+        let rest = &xml[start..];
+        if rest.starts_with("<!--") {
+            pos = skip_to(start, "-->")?;
+            continue;
+        } else if rest.starts_with("<![CDATA[") {
+            pos = skip_to(start, "]]>")?;
+            continue;
+        } else if rest.starts_with("<?") {
+            pos = skip_to(start, "?>")?;
+            continue;
+        } else if rest.starts_with("<!") {
+            pos = skip_to(start, ">")?;
+            continue;
+        }
+
+        let closing = rest.starts_with("</");
+        let mut i = start + if closing { 2 } else { 1 };
+        let name_start = i;
+        while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"/>".contains(&bytes[i]) {
+            i += 1;
+        }
+        let name = &xml[name_start..i];
+        if name.is_empty() {
+            return Err(format!("Tag without a name at byte {}", start));
+        }
+
+        let mut attributes = Vec::new();
+        let kind = loop {
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            match bytes.get(i) {
+                None => return Err(format!("Unterminated tag <{}> at byte {}", name, start)),
+                Some(b'>') => {
+                    break if closing {
+                        TagKind::Close
+                    } else {
+                        TagKind::Open
+                    };
+                }
+                Some(b'/') if bytes.get(i + 1) == Some(&b'>') && !closing => {
+                    i += 1;
+                    break TagKind::Empty;
+                }
+                _ if closing => return Err(format!("Malformed closing tag at byte {}", start)),
+                _ => {}
+            }
+
+            let attribute_start = i;
+            while i < bytes.len() && !bytes[i].is_ascii_whitespace() && !b"=/>".contains(&bytes[i])
+            {
+                i += 1;
+            }
+            let attribute = &xml[attribute_start..i];
+            while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                i += 1;
+            }
+            let quote = match (bytes.get(i), bytes.get(i + 1)) {
+                (Some(b'='), _) => {
+                    i += 1;
+                    while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+                        i += 1;
+                    }
+                    bytes.get(i).copied().filter(|&c| c == b'"' || c == b'\'')
+                }
+                _ => None,
+            };
+            let Some(quote) = quote.filter(|_| !attribute.is_empty()) else {
+                return Err(format!("Malformed attribute in <{}> at byte {}", name, start));
+            };
+            let value_start = i + 1;
+            let value_end = xml[value_start..]
+                .find(quote as char)
+                .map(|at| value_start + at)
+                .ok_or_else(|| format!("Unterminated attribute in <{}> at byte {}", name, start))?;
+            attributes.push(Attribute {
+                name: attribute,
+                raw: &xml[value_start..value_end],
+                span: value_start..value_end,
+            });
+            i = value_end + 1;
+        };
+        tags.push(Tag { name, kind, attributes });
+        pos = i + 1;
+    }
+    Ok(tags)
+}
+
+fn unescape(raw: &str) -> String {
+    let mut text = String::with_capacity(raw.len());
+    let mut rest = raw;
+    while let Some(at) = rest.find('&') {
+        text.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let entity = rest.find(';').map(|end| &rest[1..end]);
+        let decoded = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        });
+        match (decoded, entity) {
+            (Some(c), Some(entity)) => {
+                text.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            // A stray ampersand is kept as it is
+            _ => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Project version="1.0">
+  <Application name="Bitwig Studio" version="5.0"/>
+  <Structure>
+    <Track contentType="audio" loaded="true" id="id1" name="Lead Vox">
+      <Channel role="regular" id="id2"/>
+    </Track>
+    <Track contentType="audio" id="id3" name='Drums &amp; Perc'/>
+  </Structure>
+  <Arrangement id="id4">
+    <Lanes timeUnit="beats" id="id5">
+      <Lanes track="id1" id="id6">
+        <Clips id="id7">
+          <Clip time="0.0" duration="8.0" name="Verse">
+            <Warps contentTimeUnit="seconds" timeUnit="beats">
+              <Audio channels="2" sampleRate="44100" id="id8">
+                <File path="audio/vox take 1.wav"/>
+              </Audio>
+            </Warps>
+          </Clip>
+          <!-- <File path="audio/commented.wav"/> -->
+          <Clip time="16.0" duration="8.0">
+            <Audio channels="1" sampleRate="48000" id="id9">
+              <File path="/sessions/chorus.wav" external="true"/>
+            </Audio>
+          </Clip>
+        </Clips>
+      </Lanes>
+      <Lanes track="id3" id="id10">
+        <Clips id="id11">
+          <Clip time="0.0" duration="32.0" name="Loop">
+            <Audio channels="2" sampleRate="44100" id="id12">
+              <File path="audio/drums.wav"/>
+            </Audio>
+          </Clip>
+        </Clips>
+      </Lanes>
+    </Lanes>
+  </Arrangement>
+</Project>
+"#;
+
+    #[test]
+    fn test_project_clips() {
+        let clips = parse_project(PROJECT).unwrap();
+        let summary: Vec<(&str, &str, &str, bool)> = clips
+            .iter()
+            .map(|clip| (&clip.track[..], &clip.name[..], &clip.file[..], clip.external))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("Lead Vox", "Verse", "audio/vox take 1.wav", false),
+                ("Lead Vox", "chorus.wav", "/sessions/chorus.wav", true),
+                ("Drums & Perc", "Loop", "audio/drums.wav", false),
+            ]
+        );
+        assert!(clips[0].looks_like_vocals());
+        assert!(!clips[2].looks_like_vocals());
+
+        assert!(parse_project("<Project><Track name=\"x\"").is_err());
+        assert!(parse_project("<Project><Track name=x/></Project>").is_err());
+        assert!(parse_project("<Project><!-- open").is_err());
+    }
+
+    #[test]
+    fn test_rewritten_project_points_at_new_files() {
+        let clips = parse_project(PROJECT).unwrap();
+        let replacements = HashMap::from([
+            (clips[0].file_element, "audio/vox & co_tuned.wav".to_string()),
+            (clips[1].file_element, "chorus_tuned.wav".to_string()),
+        ]);
+        let rewritten = rewrite_files(PROJECT, &replacements).unwrap();
+        assert!(rewritten.contains(r#"<File path="audio/vox &amp; co_tuned.wav"/>"#));
+        assert!(rewritten.contains(r#"<File path="chorus_tuned.wav" external="true"/>"#));
+        // The commented out element doesn't count, so the drums stay where they were
+        assert!(rewritten.contains(r#"<File path="audio/drums.wav"/>"#));
+
+        let files: Vec<String> =
+            parse_project(&rewritten).unwrap().into_iter().map(|clip| clip.file).collect();
+        assert_eq!(files, ["audio/vox & co_tuned.wav", "chorus_tuned.wav", "audio/drums.wav"]);
+
+        assert_eq!(unescape("a &lt;b&gt; &#233;&#x41; & c"), "a <b> éA & c");
+        let taken = HashSet::from(["audio/drums_tuned.wav".to_string()]);
+        assert_eq!(unique_name(&taken, "drums_tuned"), "drums_tuned_2.wav");
+        assert_eq!(unique_name(&taken, "bass_tuned"), "bass_tuned.wav");
+    }
+}
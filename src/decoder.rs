@@ -0,0 +1,317 @@
+use hound::{SampleFormat, WavIntoSamples, WavSpec};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::profiling::profile_span;
+use crate::{paths, pcm};
+
+/// File extensions accepted as input. WAV is read with hound, the rest with symphonia.
+pub const INPUT_EXTENSIONS: [&str; 4] = ["wav", "mp3", "m4a", "aac"];
+
+// Compressed inputs have no native bit depth, write their output as 16-bit
+const DECODED_BITS_PER_SAMPLE: u16 = 16;
+
+pub fn is_supported_input(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext| {
+        INPUT_EXTENSIONS.iter().any(|supported| ext.eq_ignore_ascii_case(supported))
+    })
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("wav"))
+}
+
+/// Format of an input file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputInfo {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub bits_per_sample: u16,
+    // Unknown for some compressed streams until they are fully decoded
+    pub frames: Option<u64>,
+    pub compressed: bool,
+}
+
+impl InputInfo {
+    pub fn duration_secs(&self) -> Option<f32> {
+        self.frames.map(|frames| frames as f32 / self.sample_rate as f32)
+    }
+
+    /// Whether the processed output of this input can be written in its own format.
+    pub fn check_supported(&self) -> Result<(), String> {
+        if self.channels != 1 && self.channels != 2 {
+            return Err(format!(
+                "Unsupported channel count: {}. Only mono and stereo are supported.",
+                self.channels
+            ));
+        }
+
+        if self.bits_per_sample != 16 && self.bits_per_sample != 24 && self.bits_per_sample != 32 {
+            return Err(format!(
+                "Unsupported bit depth: {}. Only 16, 24, and 32-bit are supported.",
+                self.bits_per_sample
+            ));
+        }
+        Ok(())
+    }
+
+    /// WAV format for the processed output of this input.
+    pub fn output_spec(&self) -> WavSpec {
+        WavSpec {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: self.bits_per_sample,
+            sample_format: SampleFormat::Int,
+        }
+    }
+}
+
+/// Read the format of `path` without decoding it.
+pub fn probe(path: &Path) -> Result<InputInfo, String> {
+    if is_wav(path) {
+        let reader =
+            paths::open_wav(path).map_err(|e| format!("Failed to open input file: {}", e))?;
+        let spec = reader.spec();
+        return Ok(InputInfo {
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+            bits_per_sample: spec.bits_per_sample,
+            frames: Some(reader.duration() as u64),
+            compressed: false,
+        });
+    }
+
+    let (_, _, info) = open_compressed(path)?;
+    Ok(info)
+}
+
+/// Decode up to `max_frames` frames of `path` as mono f32 (channels averaged).
+pub fn read_mono(path: &Path, max_frames: usize) -> Result<(Vec<f32>, InputInfo), String> {
+    profile_span!("decode", max_frames);
+    if is_wav(path) {
+        let info = probe(path)?;
+        let mut reader =
+            paths::open_wav(path).map_err(|e| format!("Failed to open input file: {}", e))?;
+        let mono = pcm::read_mono(&mut reader, max_frames)
+            .map_err(|e| format!("Failed to read samples: {}", e))?;
+        return Ok((mono, info));
+    }
+
+    let mut reader = MonoReader::open(path)?;
+    let capacity = reader.info.frames.map_or(0, |frames| frames as usize).min(max_frames);
+    let mut mono = Vec::with_capacity(capacity);
+    while mono.len() < max_frames {
+        let wanted = max_frames - mono.len();
+        if reader.read(&mut mono, wanted)? == 0 {
+            break;
+        }
+    }
+
+    let mut info = reader.info;
+    info.frames = Some(mono.len() as u64);
+    Ok((mono, info))
+}
+
+/// Decodes a file as mono f32 (channels averaged) a block at a time, so only the block
+/// being worked on has to be in memory.
+pub struct MonoReader {
+    info: InputInfo,
+    source: Source,
+}
+
+enum Source {
+    Wav { samples: WavIntoSamples<BufReader<File>, i32>, spec: WavSpec },
+    Compressed(CompressedStream),
+}
+
+impl MonoReader {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if is_wav(path) {
+            let info = probe(path)?;
+            let reader =
+                paths::open_wav(path).map_err(|e| format!("Failed to open input file: {}", e))?;
+            let spec = reader.spec();
+            let samples = reader.into_samples();
+            return Ok(Self { info, source: Source::Wav { samples, spec } });
+        }
+
+        let (format, decoder, info) = open_compressed(path)?;
+        let track_id = format.default_track().map(|track| track.id).unwrap_or_default();
+        let stream = CompressedStream {
+            path: path.to_path_buf(),
+            format,
+            decoder,
+            track_id,
+            buffer: None,
+            pending: Vec::new(),
+        };
+        Ok(Self { info, source: Source::Compressed(stream) })
+    }
+
+    /// Format of the file. The frame count may be unknown for compressed streams.
+    pub fn info(&self) -> &InputInfo {
+        &self.info
+    }
+
+    /// Append up to `max_frames` frames to `mono`, returning how many were added. 0 means
+    /// the end of the file.
+    pub fn read(&mut self, mono: &mut Vec<f32>, max_frames: usize) -> Result<usize, String> {
+        profile_span!("decode", max_frames);
+        match &mut self.source {
+            Source::Wav { samples, spec } => pcm::read_mono_block(samples, *spec, mono, max_frames)
+                .map_err(|e| format!("Failed to read samples: {}", e)),
+            Source::Compressed(stream) => stream.read(mono, max_frames),
+        }
+    }
+}
+
+// Symphonia decoding state, with the frames of the last packet that didn't fit yet
+struct CompressedStream {
+    path: PathBuf,
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    buffer: Option<SampleBuffer<f32>>,
+    pending: Vec<f32>,
+}
+
+impl CompressedStream {
+    fn read(&mut self, mono: &mut Vec<f32>, max_frames: usize) -> Result<usize, String> {
+        let start = mono.len();
+        while mono.len() - start < max_frames {
+            if self.pending.is_empty() && !self.decode_packet()? {
+                break;
+            }
+            let take = (max_frames - (mono.len() - start)).min(self.pending.len());
+            mono.extend(self.pending.drain(..take));
+        }
+        Ok(mono.len() - start)
+    }
+
+    // Decode the next packet of the track into `pending`. False at the end of the stream.
+    fn decode_packet(&mut self) -> Result<bool, String> {
+        let name = || paths::display_name(&self.path);
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                // Symphonia reports the end of the stream as an unexpected EOF
+                Err(SymphoniaError::IoError(e))
+                    if e.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(false);
+                }
+                Err(e) => return Err(format!("Failed to read {}: {}", name(), e)),
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                // A corrupt packet only loses a few milliseconds, keep going
+                Err(SymphoniaError::DecodeError(e)) => {
+                    log::warn!("Skipping undecodable packet in {}: {}", self.path.display(), e);
+                    continue;
+                }
+                Err(e) => return Err(format!("Failed to decode {}: {}", name(), e)),
+            };
+
+            let spec = *decoded.spec();
+            let channels = spec.channels.count().max(1);
+            let needed = decoded.capacity() * channels;
+            if self.buffer.as_ref().is_none_or(|buffer| buffer.capacity() < needed) {
+                self.buffer = Some(SampleBuffer::new(decoded.capacity() as u64, spec));
+            }
+            let buffer = self.buffer.as_mut().expect("buffer was just created");
+            buffer.copy_interleaved_ref(decoded);
+
+            self.pending.extend(
+                buffer
+                    .samples()
+                    .chunks_exact(channels)
+                    .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+            );
+            return Ok(true);
+        }
+    }
+}
+
+// A probed compressed file: its demuxer, the decoder for its first audio track, and its info
+type OpenedFile = (Box<dyn FormatReader>, Box<dyn Decoder>, InputInfo);
+
+fn open_compressed(path: &Path) -> Result<OpenedFile, String> {
+    let file = File::open(paths::normalize_for_io(path))
+        .map_err(|e| format!("Failed to open input file: {}", e))?;
+    // Symphonia wants a power-of-two buffer of more than 32 KiB
+    let options = MediaSourceStreamOptions {
+        buffer_len: paths::io_block_size().next_power_of_two().max(64 << 10),
+    };
+    let stream = MediaSourceStream::new(Box::new(file), options);
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(&hint, stream, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| format!("Unsupported or corrupt audio file: {}", e))?;
+    let format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("No audio track found")?;
+    let params = &track.codec_params;
+
+    let info = InputInfo {
+        sample_rate: params.sample_rate.ok_or("Audio track has no sample rate")?,
+        channels: params.channels.map_or(1, |channels| channels.count() as u16),
+        bits_per_sample: DECODED_BITS_PER_SAMPLE,
+        frames: params.n_frames,
+        compressed: true,
+    };
+
+    let decoder = symphonia::default::get_codecs()
+        .make(params, &DecoderOptions::default())
+        .map_err(|e| format!("Unsupported codec: {}", e))?;
+
+    Ok((format, decoder, info))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_inputs() {
+        assert!(is_supported_input(Path::new("take.wav")));
+        assert!(is_supported_input(Path::new("Take.MP3")));
+        assert!(is_supported_input(Path::new("/music/take.m4a")));
+        assert!(!is_supported_input(Path::new("take.flac")));
+        assert!(!is_supported_input(Path::new("take")));
+    }
+
+    #[test]
+    fn test_output_spec_for_compressed_input() {
+        let info = InputInfo {
+            sample_rate: 48000,
+            channels: 2,
+            bits_per_sample: DECODED_BITS_PER_SAMPLE,
+            frames: Some(96000),
+            compressed: true,
+        };
+        assert_eq!(info.duration_secs(), Some(2.0));
+        assert_eq!(info.output_spec().bits_per_sample, 16);
+        assert_eq!(info.output_spec().sample_format, SampleFormat::Int);
+    }
+}
@@ -2,42 +2,33 @@ use hound::{WavSpec, WavWriter};
 use std::f32::consts::PI;
 use std::path::Path;
 
-/// Generate a test WAV file with multiple tones for testing autotune
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let spec = WavSpec {
+pub const SAMPLE_RATE: u32 = 44100;
+
+// C4 to C5 major scale
+pub const BASE_NOTES: [f32; 8] = [261.63, 293.66, 329.63, 349.23, 392.00, 440.00, 493.88, 523.25];
+
+// Alternating sharp/flat deviation applied to the off-pitch demo
+pub const OFF_PITCH_CENTS: f32 = 25.0;
+
+fn demo_spec() -> WavSpec {
+    WavSpec {
         channels: 1,
-        sample_rate: 44100,
+        sample_rate: SAMPLE_RATE,
         bits_per_sample: 16,
         sample_format: hound::SampleFormat::Int,
-    };
+    }
+}
 
-    let mut writer = WavWriter::create("test_audio.wav", spec)?;
+/// Write a 10 second in-tune C major scale with a few harmonics.
+pub fn write_test_tones(path: &Path) -> Result<(), hound::Error> {
+    let mut writer = WavWriter::create(path, demo_spec())?;
 
-    let sample_rate = spec.sample_rate as f32;
+    let sample_rate = SAMPLE_RATE as f32;
     let duration = 10.0; // seconds
     let total_samples = (duration * sample_rate) as usize;
+    let samples_per_note = (duration / BASE_NOTES.len() as f32 * sample_rate) as usize;
 
-    // Generate a sequence of musical notes
-    let notes = [
-        261.63, // C4
-        293.66, // D4
-        329.63, // E4
-        349.23, // F4
-        392.00, // G4
-        440.00, // A4
-        493.88, // B4
-        523.25, // C5
-    ];
-
-    let note_duration = duration / notes.len() as f32;
-    let samples_per_note = (note_duration * sample_rate) as usize;
-
-    println!("Generating test WAV file with {} notes...", notes.len());
-    println!("Duration: {:.1}s, Sample rate: {}Hz", duration, sample_rate);
-
-    for (note_idx, &frequency) in notes.iter().enumerate() {
-        println!("Generating note {}: {:.2}Hz", note_idx + 1, frequency);
-
+    for (note_idx, &frequency) in BASE_NOTES.iter().enumerate() {
         let start_sample = note_idx * samples_per_note;
         let end_sample = ((note_idx + 1) * samples_per_note).min(total_samples);
 
@@ -62,65 +53,40 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             };
 
             let final_sample = sample * envelope * 0.7; // Scale to prevent clipping
-            let amplitude = (final_sample * 32767.0) as i16;
-            writer.write_sample(amplitude)?;
+            writer.write_sample((final_sample * 32767.0) as i16)?;
         }
     }
 
-    writer.finalize()?;
-    println!("Test WAV file 'test_audio.wav' generated successfully!");
-
-    // Generate a second test file with off-pitch notes for dramatic autotune effect
-    generate_off_pitch_test()?;
-
-    Ok(())
+    writer.finalize()
 }
 
-fn generate_off_pitch_test() -> Result<(), Box<dyn std::error::Error>> {
-    let spec = WavSpec {
-        channels: 1,
-        sample_rate: 44100,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-
-    let mut writer = WavWriter::create("test_audio_off_pitch.wav", spec)?;
-
-    let sample_rate = spec.sample_rate as f32;
-    let duration = 8.0; // seconds
-    let total_samples = (duration * sample_rate) as usize;
-
-    // Generate notes that are slightly off-pitch for dramatic autotune effect
-    let base_notes = [
-        261.63, // C4
-        293.66, // D4
-        329.63, // E4
-        349.23, // F4
-        392.00, // G4
-        440.00, // A4
-        493.88, // B4
-        523.25, // C5
-    ];
-
-    // Add random pitch deviation (±30 cents)
-    let off_pitch_notes: Vec<f32> = base_notes
+/// Frequencies of the off-pitch demo notes, alternating sharp and flat.
+pub fn off_pitch_notes() -> Vec<f32> {
+    BASE_NOTES
         .iter()
         .enumerate()
         .map(|(i, &freq)| {
-            let cents_deviation = if i % 2 == 0 { 25.0 } else { -25.0 }; // Alternate sharp/flat
+            let cents_deviation = if i % 2 == 0 {
+                OFF_PITCH_CENTS
+            } else {
+                -OFF_PITCH_CENTS
+            };
             freq * (2.0_f32).powf(cents_deviation / 1200.0)
         })
-        .collect();
-
-    let note_duration = duration / off_pitch_notes.len() as f32;
-    let samples_per_note = (note_duration * sample_rate) as usize;
+        .collect()
+}
 
-    println!("Generating off-pitch test WAV file...");
+/// Write an 8 second vocal-like scale that is audibly out of tune, for demonstrating correction.
+pub fn write_off_pitch_demo(path: &Path) -> Result<(), hound::Error> {
+    let mut writer = WavWriter::create(path, demo_spec())?;
 
-    for (note_idx, &frequency) in off_pitch_notes.iter().enumerate() {
-        let deviation = ((frequency / base_notes[note_idx] - 1.0) * 1200.0).round();
-        println!("Note {}: {:.2}Hz ({:+.0} cents)", note_idx + 1, frequency, deviation);
+    let sample_rate = SAMPLE_RATE as f32;
+    let duration = 8.0; // seconds
+    let total_samples = (duration * sample_rate) as usize;
+    let notes = off_pitch_notes();
+    let samples_per_note = (duration / notes.len() as f32 * sample_rate) as usize;
 
+    for (note_idx, &frequency) in notes.iter().enumerate() {
         let start_sample = note_idx * samples_per_note;
         let end_sample = ((note_idx + 1) * samples_per_note).min(total_samples);
 
@@ -152,14 +118,9 @@ fn generate_off_pitch_test() -> Result<(), Box<dyn std::error::Error>> {
             };
 
             let final_sample = sample * envelope * 0.6;
-            let amplitude = (final_sample * 32767.0) as i16;
-            writer.write_sample(amplitude)?;
+            writer.write_sample((final_sample * 32767.0) as i16)?;
         }
     }
 
-    writer.finalize()?;
-    println!("Off-pitch test WAV file 'test_audio_off_pitch.wav' generated successfully!");
-    println!("This file will demonstrate the autotune effect more dramatically.");
-
-    Ok(())
+    writer.finalize()
 }
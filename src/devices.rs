@@ -0,0 +1,252 @@
+use cpal::traits::{DeviceTrait, HostTrait};
+use cpal::{SampleFormat, SupportedStreamConfig, SupportedStreamConfigRange};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+/// Sample rates offered for the devices.
+pub const SAMPLE_RATES: [u32; 6] = [22050, 32000, 44100, 48000, 88200, 96000];
+/// Rate the test tone is made at, the output resamples it to the device's.
+pub const TONE_RATE: u32 = 48000;
+// The test tone: an A4 for a second, faded in and out so it doesn't click
+const TONE_HZ: f32 = 440.0;
+const TONE_SECS: f32 = 1.0;
+const TONE_FADE_SECS: f32 = 0.01;
+const TONE_LEVEL: f32 = 0.25;
+
+static CURRENT: RwLock<DeviceSettings> =
+    RwLock::new(DeviceSettings { input: None, output: None, sample_rate: None });
+
+/// Which devices live mode, recording, and playback open, and the rate they run at.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceSettings {
+    // Device names, the system's default device when None
+    pub input: Option<String>,
+    pub output: Option<String>,
+    // Each device's own default rate when None
+    pub sample_rate: Option<u32>,
+}
+
+/// Open `settings` for every device stream started from now on.
+pub fn set(settings: DeviceSettings) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = settings;
+}
+
+pub fn current() -> DeviceSettings {
+    CURRENT.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// The devices connected, and the rates of `SAMPLE_RATES` the chosen ones both run at.
+/// Scanning takes a moment on some systems, so this is done on request rather than every
+/// frame.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DeviceList {
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub rates: Vec<u32>,
+}
+
+impl DeviceList {
+    pub fn scan(settings: &DeviceSettings) -> Self {
+        let host = cpal::default_host();
+        let inputs: Vec<_> = host.input_devices().map(Iterator::collect).unwrap_or_default();
+        let outputs: Vec<_> = host.output_devices().map(Iterator::collect).unwrap_or_default();
+        let names = |devices: &[cpal::Device]| -> Vec<String> {
+            devices.iter().filter_map(|device| device.name().ok()).collect()
+        };
+        let input = settings
+            .input
+            .as_deref()
+            .and_then(|name| find(inputs.iter().cloned(), name))
+            .or_else(|| host.default_input_device());
+        let output = settings
+            .output
+            .as_deref()
+            .and_then(|name| find(outputs.iter().cloned(), name))
+            .or_else(|| host.default_output_device());
+        let input_ranges: Vec<_> = input
+            .and_then(|device| device.supported_input_configs().ok())
+            .map(Iterator::collect)
+            .unwrap_or_default();
+        let output_ranges: Vec<_> = output
+            .and_then(|device| device.supported_output_configs().ok())
+            .map(Iterator::collect)
+            .unwrap_or_default();
+        let supports = |ranges: &[SupportedStreamConfigRange], rate: u32| {
+            ranges.is_empty() || usable(ranges.iter().cloned(), rate).next().is_some()
+        };
+
+        Self {
+            inputs: names(&inputs),
+            outputs: names(&outputs),
+            rates: SAMPLE_RATES
+                .into_iter()
+                .filter(|&rate| supports(&input_ranges, rate) && supports(&output_ranges, rate))
+                .collect(),
+        }
+    }
+}
+
+fn find(mut devices: impl Iterator<Item = cpal::Device>, name: &str) -> Option<cpal::Device> {
+    devices.find(|device| device.name().is_ok_and(|found| found == name))
+}
+
+/// The chosen input device, or the system's default.
+pub fn input_device() -> Result<cpal::Device, String> {
+    let host = cpal::default_host();
+    match current().input {
+        Some(name) => host
+            .input_devices()
+            .ok()
+            .and_then(|devices| find(devices, &name))
+            .ok_or_else(|| missing(&name)),
+        None => {
+            host.default_input_device().ok_or_else(|| "No audio input device available".to_string())
+        }
+    }
+}
+
+/// The chosen output device, or the system's default.
+pub fn output_device() -> Result<cpal::Device, String> {
+    let host = cpal::default_host();
+    match current().output {
+        Some(name) => host
+            .output_devices()
+            .ok()
+            .and_then(|devices| find(devices, &name))
+            .ok_or_else(|| missing(&name)),
+        None => host
+            .default_output_device()
+            .ok_or_else(|| "No audio output device available".to_string()),
+    }
+}
+
+fn missing(name: &str) -> String {
+    format!("Audio device \"{}\" isn't connected, pick another under 🔈 Devices", name)
+}
+
+/// How to record from `device`: at the chosen rate, otherwise as the device would by default.
+pub fn input_config(device: &cpal::Device) -> Result<SupportedStreamConfig, String> {
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to query input device: {}", e);
+    let default = device.default_input_config().map_err(|e| failed(&e))?;
+    let ranges = device.supported_input_configs().map_err(|e| failed(&e))?;
+    let rate = current().sample_rate;
+    config_at(default, ranges, rate).ok_or_else(|| unsupported(device, rate))
+}
+
+/// How to play on `device`: at the chosen rate, otherwise as the device would by default.
+pub fn output_config(device: &cpal::Device) -> Result<SupportedStreamConfig, String> {
+    let failed = |e: &dyn std::fmt::Display| format!("Failed to query output device: {}", e);
+    let default = device.default_output_config().map_err(|e| failed(&e))?;
+    let ranges = device.supported_output_configs().map_err(|e| failed(&e))?;
+    let rate = current().sample_rate;
+    config_at(default, ranges, rate).ok_or_else(|| unsupported(device, rate))
+}
+
+fn unsupported(device: &cpal::Device, rate: Option<u32>) -> String {
+    format!(
+        "{} can't run at {}Hz, pick another rate under 🔈 Devices",
+        device.name().unwrap_or_else(|_| "The audio device".to_string()),
+        rate.unwrap_or_default()
+    )
+}
+
+// The layouts of `ranges` that run at `rate`, in sample formats the streams can convert
+fn usable(
+    ranges: impl Iterator<Item = SupportedStreamConfigRange>,
+    rate: u32,
+) -> impl Iterator<Item = SupportedStreamConfigRange> {
+    let rate = cpal::SampleRate(rate);
+    ranges.filter(move |range| {
+        matches!(range.sample_format(), SampleFormat::F32 | SampleFormat::I16 | SampleFormat::U16)
+            && range.min_sample_rate() <= rate
+            && rate <= range.max_sample_rate()
+    })
+}
+
+// The default layout at `rate` when the device has it there, or else the first it has
+fn config_at(
+    default: SupportedStreamConfig,
+    ranges: impl Iterator<Item = SupportedStreamConfigRange>,
+    rate: Option<u32>,
+) -> Option<SupportedStreamConfig> {
+    let Some(rate) = rate else {
+        return Some(default);
+    };
+    let ranges: Vec<_> = usable(ranges, rate).collect();
+    let same_layout = ranges.iter().find(|range| {
+        range.channels() == default.channels() && range.sample_format() == default.sample_format()
+    });
+    same_layout
+        .or(ranges.first())
+        .map(|range| (*range).with_sample_rate(cpal::SampleRate(rate)))
+}
+
+/// A second of A4 at `sample_rate`, to check the output device with.
+pub fn test_tone(sample_rate: u32) -> Vec<f32> {
+    let rate = sample_rate as f32;
+    let len = (TONE_SECS * rate) as usize;
+    let fade = TONE_FADE_SECS * rate;
+    (0..len)
+        .map(|i| {
+            let envelope = (i as f32 / fade).min((len - i) as f32 / fade).min(1.0);
+            let phase = std::f32::consts::TAU * TONE_HZ * i as f32 / rate;
+            phase.sin() * TONE_LEVEL * envelope
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cpal::SupportedBufferSize;
+
+    #[test]
+    fn test_config_at_keeps_the_default_layout_when_it_can() {
+        let range = |channels, min, max, format| {
+            SupportedStreamConfigRange::new(
+                channels,
+                cpal::SampleRate(min),
+                cpal::SampleRate(max),
+                SupportedBufferSize::Unknown,
+                format,
+            )
+        };
+        let default = SupportedStreamConfig::new(
+            2,
+            cpal::SampleRate(44100),
+            SupportedBufferSize::Unknown,
+            SampleFormat::F32,
+        );
+        let ranges = || {
+            [
+                range(1, 8000, 96000, SampleFormat::I16),
+                range(2, 8000, 48000, SampleFormat::F32),
+                range(2, 8000, 192000, SampleFormat::I32),
+            ]
+            .into_iter()
+        };
+
+        assert_eq!(config_at(default.clone(), ranges(), None), Some(default.clone()));
+        let at_48k = config_at(default.clone(), ranges(), Some(48000)).unwrap();
+        assert_eq!(
+            (at_48k.channels(), at_48k.sample_rate().0, at_48k.sample_format()),
+            (2, 48000, SampleFormat::F32)
+        );
+        // Only the mono layout goes that high in a format the streams take
+        let at_96k = config_at(default.clone(), ranges(), Some(96000)).unwrap();
+        assert_eq!((at_96k.channels(), at_96k.sample_format()), (1, SampleFormat::I16));
+        assert_eq!(config_at(default, ranges(), Some(192000)), None);
+    }
+
+    #[test]
+    fn test_tone_fades_in_and_out() {
+        let tone = test_tone(48000);
+        assert_eq!(tone.len(), 48000);
+        assert_eq!(tone[0], 0.0);
+        assert!(tone.iter().all(|sample| sample.abs() <= TONE_LEVEL));
+        let peak = tone[20000..21000].iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((peak - TONE_LEVEL).abs() < 0.01);
+        assert!(tone[47999].abs() < 0.001);
+    }
+}
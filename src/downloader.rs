@@ -0,0 +1,123 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+use crate::decoder::INPUT_EXTENSIONS;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone)]
+pub enum DownloadEvent {
+    Progress(f32), // 0.0 to 1.0, only sent when the server reports a length
+    Done(PathBuf),
+    Failed(String),
+}
+
+/// Directory that downloaded inputs are stored in.
+pub fn download_dir() -> PathBuf {
+    std::env::temp_dir().join("autotune-downloads")
+}
+
+/// Check that `url` is an http(s) link to a supported audio file and derive a local file name.
+pub fn file_name_from_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    let rest = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+        .ok_or_else(|| "Only http:// and https:// URLs are supported".to_string())?;
+
+    // Ignore query strings and fragments when looking at the file name
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let name = path.split('/').skip(1).filter(|segment| !segment.is_empty()).last();
+    let name = name.ok_or_else(|| "URL does not point to a file".to_string())?;
+
+    let sanitized: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    let extension =
+        Path::new(&sanitized).extension().map(|ext| ext.to_string_lossy().to_lowercase());
+    match extension {
+        Some(ext) if INPUT_EXTENSIONS.contains(&ext.as_str()) => Ok(sanitized),
+        _ => {
+            Err(format!("Unsupported file type in URL (expected {})", INPUT_EXTENSIONS.join(", ")))
+        }
+    }
+}
+
+/// Download `url` into `dest_dir`, reporting progress and the final path on `events`.
+pub fn download(url: &str, dest_dir: &Path, events: Sender<DownloadEvent>) {
+    let event = match download_to(url, dest_dir, &events) {
+        Ok(path) => DownloadEvent::Done(path),
+        Err(e) => DownloadEvent::Failed(e),
+    };
+    let _ = events.send(event);
+}
+
+fn download_to(
+    url: &str,
+    dest_dir: &Path,
+    events: &Sender<DownloadEvent>,
+) -> Result<PathBuf, String> {
+    let file_name = file_name_from_url(url)?;
+
+    let response = ureq::get(url.trim()).call().map_err(|e| format!("Download failed: {}", e))?;
+    let total_bytes = response.header("Content-Length").and_then(|len| len.parse::<u64>().ok());
+
+    std::fs::create_dir_all(dest_dir)
+        .map_err(|e| format!("Failed to create {}: {}", dest_dir.display(), e))?;
+    let path = dest_dir.join(file_name);
+    let mut file =
+        File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+    let mut reader = response.into_reader();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut received: u64 = 0;
+
+    loop {
+        let read = reader.read(&mut buffer).map_err(|e| format!("Download interrupted: {}", e))?;
+        if read == 0 {
+            break;
+        }
+
+        file.write_all(&buffer[..read]).map_err(|e| format!("Failed to write download: {}", e))?;
+        received += read as u64;
+
+        if let Some(total) = total_bytes.filter(|&total| total > 0) {
+            let _ = events.send(DownloadEvent::Progress(received as f32 / total as f32));
+        }
+    }
+
+    file.flush().map_err(|e| format!("Failed to write download: {}", e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name_from_url() {
+        assert_eq!(file_name_from_url("https://example.com/takes/vox.wav").unwrap(), "vox.wav");
+        assert_eq!(
+            file_name_from_url(" http://example.com/a/Lead Take.WAV?dl=1#x ").unwrap(),
+            "Lead_Take.WAV"
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_urls() {
+        assert!(file_name_from_url("ftp://example.com/vox.wav").is_err());
+        assert!(file_name_from_url("https://example.com/").is_err());
+        assert!(file_name_from_url("https://example.com/page.html").is_err());
+        assert!(file_name_from_url("/local/file.wav").is_err());
+    }
+}
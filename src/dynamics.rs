@@ -0,0 +1,267 @@
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::effects::Effect;
+
+/// Ceilings the limiter can hold the output under, in dBFS.
+pub const CEILING_RANGE: RangeInclusive<f32> = -12.0..=0.0;
+pub const DEFAULT_CEILING_DB: f32 = -1.0;
+
+// How quickly the compressor's level detector falls after a peak, slow enough to carry it
+// over the cycles of a low note
+const DETECTOR_RELEASE_MS: f32 = 20.0;
+// How quickly the limiter lets go once a peak has passed
+const LIMITER_RELEASE_MS: f32 = 50.0;
+
+/// A compressor: the level above `threshold_db` is divided by `ratio`, so loud lines sit
+/// closer to quiet ones. It clamps down over `attack_ms` once the voice rises above the
+/// threshold and lets go over `release_ms` after it falls back.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Compressor {
+    pub threshold_db: f32,
+    pub ratio: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self { threshold_db: -18.0, ratio: 3.0, attack_ms: 10.0, release_ms: 120.0 }
+    }
+}
+
+impl Compressor {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-60.0..=0.0).contains(&self.threshold_db) {
+            return Err("Compressor threshold must be between -60 and 0 dBFS".to_string());
+        }
+        if !(1.0..=20.0).contains(&self.ratio) {
+            return Err("Compressor ratio must be between 1 and 20".to_string());
+        }
+        if !(0.1..=200.0).contains(&self.attack_ms) {
+            return Err("Compressor attack must be between 0.1 and 200 ms".to_string());
+        }
+        if !(5.0..=2000.0).contains(&self.release_ms) {
+            return Err("Compressor release must be between 5 and 2000 ms".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub fn validate_ceiling(db: f32) -> Result<(), String> {
+    if !CEILING_RANGE.contains(&db) {
+        return Err(format!(
+            "Limiter ceiling must be between {} and {} dBFS",
+            CEILING_RANGE.start(),
+            CEILING_RANGE.end()
+        ));
+    }
+    Ok(())
+}
+
+/// How far a stage is turning the audio down, in dB, read from another thread while it
+/// runs.
+#[derive(Debug, Default)]
+pub struct GainMeter {
+    // Bits of the reductions, which as positive floats order like the numbers
+    now: AtomicU32,
+    most: AtomicU32,
+}
+
+impl GainMeter {
+    fn record(&self, reduction_db: f32) {
+        let bits = reduction_db.max(0.0).to_bits();
+        self.now.store(bits, Ordering::Relaxed);
+        self.most.fetch_max(bits, Ordering::Relaxed);
+    }
+
+    /// The reduction over the last block.
+    pub fn reduction_db(&self) -> f32 {
+        f32::from_bits(self.now.load(Ordering::Relaxed))
+    }
+
+    /// The most of it since the meter was reset.
+    pub fn most_db(&self) -> f32 {
+        f32::from_bits(self.most.load(Ordering::Relaxed))
+    }
+
+    pub fn reset(&self) {
+        self.now.store(0, Ordering::Relaxed);
+        self.most.store(0, Ordering::Relaxed);
+    }
+}
+
+/// The gain reduction of the compressor and the limiter of a render.
+#[derive(Debug, Default)]
+pub struct Meters {
+    pub compressor: GainMeter,
+    pub limiter: GainMeter,
+}
+
+impl Meters {
+    pub fn reset(&self) {
+        self.compressor.reset();
+        self.limiter.reset();
+    }
+}
+
+/// A [`Compressor`] running over audio at one sample rate.
+#[derive(Debug, Clone)]
+pub struct LevelCompressor {
+    threshold_db: f32,
+    // Share of the level above the threshold taken off
+    slope: f32,
+    // Share of the way the reduction moves toward its target each sample
+    attack: f32,
+    release: f32,
+    // Factor the detected level falls by each sample
+    detector_release: f32,
+    level: f32,
+    reduction_db: f32,
+    meters: Option<Arc<Meters>>,
+}
+
+impl LevelCompressor {
+    pub fn new(compressor: Compressor, sample_rate: f32, meters: Option<Arc<Meters>>) -> Self {
+        let step = |ms: f32| 1.0 - (-1000.0 / (ms * sample_rate)).exp();
+        Self {
+            threshold_db: compressor.threshold_db,
+            slope: 1.0 - 1.0 / compressor.ratio,
+            attack: step(compressor.attack_ms),
+            release: step(compressor.release_ms),
+            detector_release: 1.0 - step(DETECTOR_RELEASE_MS),
+            level: 0.0,
+            reduction_db: 0.0,
+            meters,
+        }
+    }
+}
+
+impl Effect for LevelCompressor {
+    fn process(&mut self, samples: &mut [f32]) {
+        let mut most = 0f32;
+        for sample in samples {
+            self.level = sample.abs().max(self.level * self.detector_release);
+            let target = (to_db(self.level) - self.threshold_db).max(0.0) * self.slope;
+            let step = if target > self.reduction_db {
+                self.attack
+            } else {
+                self.release
+            };
+            self.reduction_db += (target - self.reduction_db) * step;
+            *sample *= from_db(-self.reduction_db);
+            most = most.max(self.reduction_db);
+        }
+        if let Some(meters) = &self.meters {
+            meters.compressor.record(most);
+        }
+    }
+}
+
+/// The limiter at the very end of the chain, run as the output is written, once its level
+/// is set.
+#[derive(Debug, Clone)]
+pub struct Limit {
+    pub ceiling_db: f32,
+    pub meters: Option<Arc<Meters>>,
+}
+
+/// A brick-wall limiter: no sample leaves it above the ceiling. It clamps down on a peak
+/// at once, with no look-ahead, and lets go over `LIMITER_RELEASE_MS`. The channels of a
+/// frame are turned down together, so a stereo image doesn't shift.
+#[derive(Debug, Clone)]
+pub struct Limiter {
+    ceiling: f32,
+    release: f32,
+    gain: f32,
+    meters: Option<Arc<Meters>>,
+}
+
+impl Limiter {
+    pub fn new(limit: &Limit, sample_rate: f32) -> Self {
+        Self {
+            ceiling: from_db(limit.ceiling_db),
+            release: 1.0 - (-1000.0 / (LIMITER_RELEASE_MS * sample_rate)).exp(),
+            gain: 1.0,
+            meters: limit.meters.clone(),
+        }
+    }
+
+    /// Limit interleaved frames of `channels` channels, in place.
+    pub fn process_frames(&mut self, samples: &mut [f32], channels: usize) {
+        let mut least = 1f32;
+        for frame in samples.chunks_mut(channels) {
+            let peak = frame.iter().fold(0f32, |peak, sample| peak.max(sample.abs()));
+            self.gain += (1.0 - self.gain) * self.release;
+            if peak.is_finite() && peak * self.gain > self.ceiling {
+                self.gain = self.ceiling / peak;
+            }
+            frame.iter_mut().for_each(|sample| *sample *= self.gain);
+            least = least.min(self.gain);
+        }
+        if let Some(meters) = &self.meters {
+            meters.limiter.record(-to_db(least));
+        }
+    }
+}
+
+fn from_db(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-9).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_compressor_and_limiter_control_the_level() {
+        let rate = 48000.0;
+        let peak = |samples: &[f32]| samples.iter().fold(0f32, |peak, s| peak.max(s.abs()));
+        let tone = |level: f32| -> Vec<f32> {
+            (0..48000).map(|i| level * (2.0 * PI * 220.0 * i as f32 / rate).sin()).collect()
+        };
+
+        // A note peaking at -6 dBFS, 12 dB over the threshold, comes out 8 dB down at 3:1.
+        // One under the threshold is left alone
+        let meters = Arc::new(Meters::default());
+        let compress = |samples: &mut Vec<f32>| {
+            let mut compressor =
+                LevelCompressor::new(Compressor::default(), rate, Some(meters.clone()));
+            for block in samples.chunks_mut(1000) {
+                compressor.process(block);
+            }
+        };
+        let mut loud = tone(from_db(-6.0));
+        compress(&mut loud);
+        assert!((to_db(peak(&loud[24000..])) - -14.0).abs() < 1.0);
+        assert!((meters.compressor.reduction_db() - 8.0).abs() < 1.0);
+        let mut quiet = tone(from_db(-24.0));
+        compress(&mut quiet);
+        assert_eq!(quiet, tone(from_db(-24.0)));
+        assert_eq!(meters.compressor.reduction_db(), 0.0);
+        assert!(meters.compressor.most_db() > 7.5);
+
+        // Stereo frames over 0 dBFS stay under the ceiling, both channels turned down alike
+        let limit = Limit { ceiling_db: DEFAULT_CEILING_DB, meters: Some(meters.clone()) };
+        let mut limiter = Limiter::new(&limit, rate);
+        let mut frames: Vec<f32> = tone(1.5).into_iter().flat_map(|s| [s, s * 0.5]).collect();
+        for block in frames.chunks_mut(2000) {
+            limiter.process_frames(block, 2);
+        }
+        assert!(peak(&frames) <= from_db(DEFAULT_CEILING_DB) + 1e-6);
+        assert!(frames.chunks(2).all(|frame| (frame[1] - frame[0] * 0.5).abs() < 1e-6));
+        assert!(meters.limiter.most_db() > 4.0);
+        meters.reset();
+        assert_eq!(meters.limiter.most_db(), 0.0);
+
+        assert!(Compressor { ratio: 0.5, ..Compressor::default() }.validate().is_err());
+        assert!(validate_ceiling(3.0).is_err());
+    }
+}
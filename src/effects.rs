@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Processing the audio of a render goes through one block of the stream at a time, in
+/// place, carrying its state from block to block.
+pub trait Effect {
+    fn process(&mut self, samples: &mut [f32]);
+}
+
+/// A stage of the effects chain, written as its name: high-pass, gate, autotune, eq, or
+/// compressor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    HighPass,
+    Gate,
+    // The correction itself. The stages before it clean up its input, the ones after it
+    // shape its output
+    Autotune,
+    Eq,
+    Compressor,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] =
+        [Stage::HighPass, Stage::Gate, Stage::Autotune, Stage::Eq, Stage::Compressor];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::HighPass => "high-pass",
+            Stage::Gate => "gate",
+            Stage::Autotune => "autotune",
+            Stage::Eq => "eq",
+            Stage::Compressor => "compressor",
+        }
+    }
+}
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        let name = name.trim();
+        Stage::ALL.into_iter().find(|stage| stage.name().eq_ignore_ascii_case(name)).ok_or_else(
+            || {
+                let names: Vec<&str> = Stage::ALL.iter().map(|stage| stage.name()).collect();
+                format!("Unknown effect '{}', expected one of {}", name, names.join(", "))
+            },
+        )
+    }
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// The order the stages of the effects chain run in, each of them once; stages are turned
+/// off with their own settings, not by leaving them out. Written as their names joined by
+/// commas, like "high-pass,gate,autotune,eq,compressor", the default: the input is cleaned
+/// up, corrected, then shaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ChainOrder([Stage; Stage::ALL.len()]);
+
+impl Default for ChainOrder {
+    fn default() -> Self {
+        Self(Stage::ALL)
+    }
+}
+
+impl ChainOrder {
+    /// The order of `stages`, as long as each stage is among them once.
+    pub fn new(stages: &[Stage]) -> Result<Self, String> {
+        let once = |stage: &Stage| stages.iter().filter(|&other| other == stage).count() == 1;
+        match stages.try_into() {
+            Ok(stages) if Stage::ALL.iter().all(once) => Ok(Self(stages)),
+            _ => {
+                let names: Vec<&str> = Stage::ALL.iter().map(|stage| stage.name()).collect();
+                Err(format!("The effects chain must list each of {} once", names.join(", ")))
+            }
+        }
+    }
+
+    pub fn stages(&self) -> &[Stage] {
+        &self.0
+    }
+
+    /// Swap the stages at `a` and `b`, to move one a step along the chain.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.0.swap(a, b);
+    }
+}
+
+impl FromStr for ChainOrder {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let stages = text.split(',').map(str::parse).collect::<Result<Vec<Stage>, _>>()?;
+        Self::new(&stages)
+    }
+}
+
+impl TryFrom<String> for ChainOrder {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        text.parse()
+    }
+}
+
+impl From<ChainOrder> for String {
+    fn from(order: ChainOrder) -> Self {
+        order.to_string()
+    }
+}
+
+impl fmt::Display for ChainOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let names: Vec<&str> = self.0.iter().map(|stage| stage.name()).collect();
+        write!(f, "{}", names.join(","))
+    }
+}
+
+/// Effects run one after another over the same blocks, itself an effect.
+#[derive(Default)]
+pub struct Chain {
+    effects: Vec<Box<dyn Effect>>,
+}
+
+impl Chain {
+    pub fn push(&mut self, effect: impl Effect + 'static) {
+        self.effects.push(Box::new(effect));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+}
+
+impl Effect for Chain {
+    fn process(&mut self, samples: &mut [f32]) {
+        for effect in &mut self.effects {
+            effect.process(samples);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Gain(f32);
+
+    impl Effect for Gain {
+        fn process(&mut self, samples: &mut [f32]) {
+            samples.iter_mut().for_each(|sample| *sample *= self.0);
+        }
+    }
+
+    struct Offset(f32);
+
+    impl Effect for Offset {
+        fn process(&mut self, samples: &mut [f32]) {
+            samples.iter_mut().for_each(|sample| *sample += self.0);
+        }
+    }
+
+    #[test]
+    fn test_chain_runs_in_order() {
+        let mut chain = Chain::default();
+        assert!(chain.is_empty());
+        chain.push(Gain(2.0));
+        chain.push(Offset(1.0));
+        let mut samples = [0.5, -1.0];
+        chain.process(&mut samples);
+        assert_eq!(samples, [2.0, -1.0]);
+
+        let order: ChainOrder = "eq,compressor,autotune, gate,HIGH-PASS".parse().unwrap();
+        assert_eq!(
+            order.stages(),
+            [Stage::Eq, Stage::Compressor, Stage::Autotune, Stage::Gate, Stage::HighPass]
+        );
+        assert_eq!(order.to_string(), "eq,compressor,autotune,gate,high-pass");
+        assert_eq!(ChainOrder::default().to_string().parse(), Ok(ChainOrder::default()));
+        let twice = [Stage::Gate, Stage::Gate, Stage::Autotune, Stage::Eq, Stage::Compressor];
+        assert!(ChainOrder::new(&twice).is_err());
+        assert!(ChainOrder::new(&[Stage::Autotune]).is_err());
+        assert!("gate,reverb,autotune,eq,compressor".parse::<ChainOrder>().is_err());
+    }
+}
@@ -0,0 +1,165 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+// How quickly the level follows the voice: slow enough to follow phrases and their tails
+// rather than each syllable
+const SMOOTHING_SECS: f32 = 0.15;
+// Level of digital silence, so an empty frame doesn't drag the level to -inf
+const FLOOR_DB: f32 = -120.0;
+
+/// How correction strength follows the level of the voice: from `quiet_db` down only
+/// `quiet_strength` of it is applied, from `loud_db` up all of it, and in between the share
+/// rises along a curve, straight for a `shape` of 1, slower at first above it. Written as
+/// "quiet_db,loud_db,quiet_strength" with an optional ",shape", like "-40,-12,0.3".
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct EnergyCurve {
+    pub quiet_db: f32,
+    pub loud_db: f32,
+    pub quiet_strength: f32,
+    pub shape: f32,
+}
+
+impl Default for EnergyCurve {
+    fn default() -> Self {
+        Self { quiet_db: -40.0, loud_db: -12.0, quiet_strength: 0.3, shape: 1.0 }
+    }
+}
+
+impl EnergyCurve {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-96.0..=0.0).contains(&self.quiet_db) || !(-96.0..=0.0).contains(&self.loud_db) {
+            return Err("Energy curve levels must be between -96 and 0 dBFS".to_string());
+        }
+        if self.quiet_db >= self.loud_db {
+            return Err(
+                "The quiet level of the energy curve must be below the loud one".to_string()
+            );
+        }
+        if !(0.0..=1.0).contains(&self.quiet_strength) {
+            return Err(
+                "The quiet strength of the energy curve must be between 0.0 and 1.0".to_string()
+            );
+        }
+        if !(0.25..=4.0).contains(&self.shape) {
+            return Err("The shape of the energy curve must be between 0.25 and 4".to_string());
+        }
+        Ok(())
+    }
+
+    /// Share of the correction strength applied at `level_db`, 0.0 to 1.0.
+    pub fn amount(&self, level_db: f32) -> f32 {
+        let position =
+            ((level_db - self.quiet_db) / (self.loud_db - self.quiet_db)).clamp(0.0, 1.0);
+        self.quiet_strength + (1.0 - self.quiet_strength) * position.powf(self.shape)
+    }
+}
+
+impl FromStr for EnergyCurve {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid energy curve '{}': expected quiet dB, loud dB, and quiet strength, \
+                 optionally with a shape, like -40,-12,0.3 or -40,-12,0.3,2",
+                text
+            )
+        };
+        let values = text
+            .split(',')
+            .map(|value| value.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()
+            .map_err(|_| invalid())?;
+        let curve = match values[..] {
+            [quiet_db, loud_db, quiet_strength] => {
+                Self { quiet_db, loud_db, quiet_strength, ..Default::default() }
+            }
+            [quiet_db, loud_db, quiet_strength, shape] => {
+                Self { quiet_db, loud_db, quiet_strength, shape }
+            }
+            _ => return Err(invalid()),
+        };
+        curve.validate()?;
+        Ok(curve)
+    }
+}
+
+impl TryFrom<String> for EnergyCurve {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        text.parse()
+    }
+}
+
+impl From<EnergyCurve> for String {
+    fn from(curve: EnergyCurve) -> Self {
+        curve.to_string()
+    }
+}
+
+impl fmt::Display for EnergyCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{},{},{}", self.quiet_db, self.loud_db, self.quiet_strength, self.shape)
+    }
+}
+
+/// The level of a voice frame by frame, in dBFS, smoothed so it follows phrases.
+#[derive(Debug, Clone)]
+pub struct EnergyFollower {
+    // Share of the way the level moves to each new frame's
+    smoothing: f32,
+    level_db: Option<f32>,
+}
+
+impl EnergyFollower {
+    /// A follower for frames `hop_secs` apart.
+    pub fn new(hop_secs: f32) -> Self {
+        Self { smoothing: 1.0 - (-hop_secs / SMOOTHING_SECS).exp(), level_db: None }
+    }
+
+    /// The smoothed level with `frame` taken in.
+    pub fn push(&mut self, frame: &[f32]) -> f32 {
+        let power =
+            frame.iter().map(|sample| sample * sample).sum::<f32>() / frame.len().max(1) as f32;
+        let frame_db = (10.0 * power.log10()).max(FLOOR_DB);
+        let level_db = match self.level_db {
+            Some(level_db) => level_db + (frame_db - level_db) * self.smoothing,
+            None => frame_db,
+        };
+        self.level_db = Some(level_db);
+        level_db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strength_follows_the_level() {
+        let curve = EnergyCurve::default();
+        assert_eq!(curve.amount(-60.0), 0.3);
+        assert_eq!(curve.amount(-6.0), 1.0);
+        assert!((curve.amount(-26.0) - 0.65).abs() < 1e-6);
+        let eased = EnergyCurve { shape: 2.0, ..curve };
+        assert!(eased.amount(-26.0) < curve.amount(-26.0));
+
+        let parsed: EnergyCurve = "-50, -20, 0.5".parse().unwrap();
+        assert_eq!(
+            parsed,
+            EnergyCurve { quiet_db: -50.0, loud_db: -20.0, quiet_strength: 0.5, shape: 1.0 }
+        );
+        assert_eq!(parsed.to_string().parse::<EnergyCurve>(), Ok(parsed));
+        assert!("-12,-40,0.3".parse::<EnergyCurve>().is_err());
+        assert!("-40,-12".parse::<EnergyCurve>().is_err());
+
+        // A loud phrase, then a quiet tail the level falls to over a few hundred ms
+        let mut follower = EnergyFollower::new(0.01);
+        assert!((follower.push(&[0.5; 64]) - -6.02).abs() < 0.01);
+        let tail: Vec<f32> = (0..100).map(|_| follower.push(&[0.005; 64])).collect();
+        assert!(tail[4] > -20.0 && tail[99] < -45.0);
+    }
+}
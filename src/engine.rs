@@ -0,0 +1,1352 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::f32::consts::PI;
+use std::fmt::{self, Debug};
+use std::ops::{ControlFlow, Range};
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::profiling::profile_span;
+
+// Samples asked from `read` at a time by the serial stream
+const READ_BLOCK: usize = 1 << 14;
+
+/// Frame sizes the correction runs at. Larger frames resolve low voices better, smaller
+/// ones follow fast runs more closely.
+pub const FFT_SIZES: [usize; 4] = [512, 1024, 2048, 4096];
+pub const DEFAULT_FFT_SIZE: usize = 1024;
+pub const DEFAULT_HOP_SIZE: usize = 256;
+
+/// Check a frame layout before processing with it: a supported FFT size, and a hop that
+/// divides it, so every sample is covered by the same number of frames.
+pub fn validate_frames(fft_size: usize, hop_size: usize) -> Result<(), String> {
+    if !FFT_SIZES.contains(&fft_size) {
+        let sizes: Vec<String> = FFT_SIZES.iter().map(usize::to_string).collect();
+        return Err(format!("FFT size must be one of {}, got {}", sizes.join(", "), fft_size));
+    }
+    if hop_size == 0 || hop_size > fft_size || !fft_size.is_multiple_of(hop_size) {
+        return Err(format!(
+            "Hop size must divide the FFT size of {} (like {} or {}), got {}",
+            fft_size,
+            fft_size / 4,
+            fft_size / 2,
+            hop_size
+        ));
+    }
+    Ok(())
+}
+
+/// The window processed frames are crossfaded with. Hann is the smoothest all-round;
+/// Hamming keeps more of each frame's edges, and Blackman less, for the fewest seams on
+/// sustained notes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Window {
+    #[default]
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    pub const ALL: [Window; 3] = [Window::Hann, Window::Hamming, Window::Blackman];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Window::Hann => "hann",
+            Window::Hamming => "hamming",
+            Window::Blackman => "blackman",
+        }
+    }
+
+    // The window over `len` samples, sampled half a sample in so even a frame's first
+    // sample has some weight
+    fn weights(self, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|i| {
+                let phase = 2.0 * PI * (i as f32 + 0.5) / len as f32;
+                match self {
+                    Window::Hann => 0.5 - 0.5 * phase.cos(),
+                    Window::Hamming => 0.54 - 0.46 * phase.cos(),
+                    Window::Blackman => 0.42 - 0.5 * phase.cos() + 0.08 * (2.0 * phase).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// What the last frames, which reach past the end of the signal, are filled out with.
+/// Silence can make the detector lose the final note as it fades; a mirror of the signal
+/// keeps it going to the last sample.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Padding {
+    #[default]
+    Zero,
+    Reflect,
+}
+
+impl Padding {
+    pub const ALL: [Padding; 2] = [Padding::Zero, Padding::Reflect];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Padding::Zero => "zero",
+            Padding::Reflect => "reflect",
+        }
+    }
+
+    // Fill `frame` from `len` on, after the last sample of the signal
+    fn pad(self, frame: &mut [f32], len: usize) {
+        match self {
+            Padding::Reflect if len > 1 => {
+                // Mirrored about the last sample, back and forth if the gap is longer
+                let period = 2 * (len - 1);
+                for i in len..frame.len() {
+                    let k = i % period;
+                    frame[i] = frame[if k < len { k } else { period - k }];
+                }
+            }
+            Padding::Reflect if len == 1 => {
+                let last = frame[0];
+                frame[1..].fill(last);
+            }
+            _ => frame[len..].fill(0.0),
+        }
+    }
+}
+
+impl FromStr for Padding {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        Padding::ALL
+            .into_iter()
+            .find(|padding| padding.name() == text.trim())
+            .ok_or_else(|| format!("Unknown padding '{}': expected zero or reflect", text.trim()))
+    }
+}
+
+impl fmt::Display for Padding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// How a signal is cut into frames: `fft_size` samples every `hop_size`, crossfaded with
+/// `window`, with the frames past the end filled out by `padding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Framing {
+    pub fft_size: usize,
+    pub hop_size: usize,
+    pub window: Window,
+    pub padding: Padding,
+}
+
+impl Framing {
+    /// Hann-windowed, zero-padded frames.
+    pub fn new(fft_size: usize, hop_size: usize) -> Self {
+        Self { fft_size, hop_size, window: Window::default(), padding: Padding::default() }
+    }
+
+    // At least one sample per frame and per hop, and no gaps between frames
+    fn clamped(self) -> Self {
+        let fft_size = self.fft_size.max(1);
+        Self { fft_size, hop_size: self.hop_size.clamp(1, fft_size), ..self }
+    }
+}
+
+impl FromStr for Window {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        Window::ALL.into_iter().find(|window| window.name() == text.trim()).ok_or_else(|| {
+            format!("Unknown window '{}': expected hann, hamming, or blackman", text.trim())
+        })
+    }
+}
+
+impl fmt::Display for Window {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Summary of a processed stream. The samples themselves went out through `on_progress`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StreamOutput {
+    // Samples handed to `on_progress`, all of the input unless it returned `Break`
+    pub len: usize,
+    // (sample position, error) in signal order
+    pub failed_frames: Vec<(usize, String)>,
+}
+
+/// What `on_progress` sees after each frame (serial) or merged chunk (parallel).
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    pub done: usize,
+    // Estimated from the expected length until the end of the input is reached
+    pub total: usize,
+    // Output samples that no later frame overlaps any more, new since the previous report
+    pub finished: &'a [f32],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParallelOptions {
+    pub threads: usize,
+    // Frames run before each chunk (output discarded) to rebuild the state's context
+    pub warmup_frames: usize,
+    pub chunk_frames: usize,
+    // Frames at each join rendered by both chunks and crossfaded, at most `chunk_frames`
+    pub crossfade_frames: usize,
+}
+
+impl Default for ParallelOptions {
+    fn default() -> Self {
+        Self {
+            threads: available_threads(),
+            warmup_frames: 16,
+            chunk_frames: 256,
+            crossfade_frames: 8,
+        }
+    }
+}
+
+pub fn available_threads() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Number of frames needed to cover `len` samples, including the padded partial frames at
+/// the end.
+pub fn frame_count(len: usize, fft_size: usize, hop_size: usize) -> usize {
+    let full = if len >= fft_size {
+        (len - fft_size) / hop_size + 1
+    } else {
+        0
+    };
+    let tail = full * hop_size < len;
+    full + tail as usize
+}
+
+/// Replace NaN/inf samples with silence and flush denormals to zero. Returns how many
+/// samples were NaN/inf.
+pub fn sanitize(samples: &mut [f32]) -> usize {
+    let mut non_finite = 0;
+    for sample in samples {
+        if !sample.is_finite() {
+            *sample = 0.0;
+            non_finite += 1;
+        } else if sample.is_subnormal() {
+            *sample = 0.0;
+        }
+    }
+    non_finite
+}
+
+// Why a frame fell back to the dry input
+enum FrameError<E> {
+    Failed(E),
+    NonFinite(usize),
+}
+
+impl<E: Debug> Debug for FrameError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Failed(e) => e.fmt(f),
+            FrameError::NonFinite(count) => write!(f, "{} NaN/inf samples in frame output", count),
+        }
+    }
+}
+
+/// Run `process` on one frame and check its output, so a single NaN can't spread through
+/// overlap-add and normalization.
+fn run_frame<S, E>(
+    frame_in: &[f32],
+    frame_out: &mut [f32],
+    state: &mut S,
+    process: &mut impl FnMut(&[f32], &mut [f32], &mut S) -> Result<(), E>,
+) -> Result<(), FrameError<E>> {
+    process(frame_in, frame_out, state).map_err(FrameError::Failed)?;
+    match sanitize(frame_out) {
+        0 => Ok(()),
+        non_finite => Err(FrameError::NonFinite(non_finite)),
+    }
+}
+
+/// Synthesis window frames are crossfaded with, and the gain that undoes it. Each output
+/// sample is divided by the sum of the window over the frames that actually cover it, so
+/// it's a weighted average of those frames' outputs: whatever a frame puts out comes out
+/// at the same level, at the edges of the signal and for any hop size, with no overlap
+/// boost for normalization to mask.
+struct Synthesis {
+    window: Vec<f32>,
+    hop_size: usize,
+}
+
+impl Synthesis {
+    fn new(fft_size: usize, hop_size: usize, window: Window) -> Self {
+        // Without overlap there's nothing to crossfade
+        let window = if hop_size >= fft_size {
+            vec![1.0; fft_size]
+        } else {
+            window.weights(fft_size)
+        };
+        Self { window, hop_size }
+    }
+
+    /// Overlap-add the first `dst.len()` samples of a processed `frame`, scaled by `gain`.
+    fn add(&self, dst: &mut [f32], frame: &[f32], gain: f32) {
+        for ((dst, &sample), &weight) in dst.iter_mut().zip(frame).zip(&self.window) {
+            *dst += sample * weight * gain;
+        }
+    }
+
+    /// Undo the window on overlap-added `samples` starting at sample `start`, once the
+    /// first `frames` frames (all that cover them) have been added.
+    fn normalize(&self, samples: &mut [f32], start: usize, frames: usize) {
+        let fft_size = self.window.len();
+        for (n, sample) in (start..).zip(samples) {
+            let first = (n + 1).saturating_sub(fft_size).div_ceil(self.hop_size);
+            let last = (n / self.hop_size + 1).min(frames);
+            let weight: f32 = (first..last).map(|k| self.window[n - k * self.hop_size]).sum();
+            if weight > 0.0 {
+                *sample /= weight;
+            }
+        }
+    }
+}
+
+/// Streaming overlap-add: samples go in as they arrive and each hop of finished output
+/// comes out once its frame is complete, one frame (`fft_size` samples) later. Frames that
+/// fail, or produce NaN/inf samples, pass the dry input through. Nothing is allocated after
+/// construction.
+pub struct FrameStream {
+    hop_size: usize,
+    padding: Padding,
+    synthesis: Synthesis,
+    window: Vec<f32>,
+    filled: usize,
+    frame_out: Vec<f32>,
+    overlap: Vec<f32>,
+    received: usize,
+    frames_done: usize,
+}
+
+impl FrameStream {
+    pub fn new(framing: Framing) -> Self {
+        let Framing { fft_size, hop_size, window, padding } = framing.clamped();
+        Self {
+            hop_size,
+            padding,
+            synthesis: Synthesis::new(fft_size, hop_size, window),
+            window: vec![0.0; fft_size],
+            filled: 0,
+            frame_out: vec![0.0; fft_size],
+            overlap: vec![0.0; fft_size],
+            received: 0,
+            frames_done: 0,
+        }
+    }
+
+    /// Feed `samples`, calling `emit` once per completed frame with the output it finished,
+    /// and `on_failed` with the position of each frame that fell back to the dry input.
+    pub fn push<S, E: Debug>(
+        &mut self,
+        mut samples: &[f32],
+        state: &mut S,
+        mut process: impl FnMut(&[f32], &mut [f32], &mut S) -> Result<(), E>,
+        mut emit: impl FnMut(&[f32]),
+        mut on_failed: impl FnMut(usize, &dyn Debug),
+    ) {
+        let fft_size = self.window.len();
+        while !samples.is_empty() {
+            let take = (fft_size - self.filled).min(samples.len());
+            let window = &mut self.window[self.filled..self.filled + take];
+            window.copy_from_slice(&samples[..take]);
+            sanitize(window);
+            self.filled += take;
+            self.received += take;
+            samples = &samples[take..];
+
+            if self.filled == fft_size {
+                self.next_frame(state, &mut process, &mut emit, &mut on_failed, false);
+            }
+        }
+    }
+
+    /// End of the input: run the padded frames still missing, so everything pushed has come
+    /// out through `emit`, down to the tail only the last frames overlap.
+    pub fn finish<S, E: Debug>(
+        &mut self,
+        state: &mut S,
+        mut process: impl FnMut(&[f32], &mut [f32], &mut S) -> Result<(), E>,
+        mut emit: impl FnMut(&[f32]),
+        mut on_failed: impl FnMut(usize, &dyn Debug),
+    ) {
+        let total = frame_count(self.received, self.window.len(), self.hop_size);
+        while self.frames_done < total {
+            self.padding.pad(&mut self.window, self.filled);
+            let last = self.frames_done + 1 == total;
+            self.next_frame(state, &mut process, &mut emit, &mut on_failed, last);
+        }
+    }
+
+    fn next_frame<S, E: Debug>(
+        &mut self,
+        state: &mut S,
+        process: &mut impl FnMut(&[f32], &mut [f32], &mut S) -> Result<(), E>,
+        emit: &mut impl FnMut(&[f32]),
+        on_failed: &mut impl FnMut(usize, &dyn Debug),
+        last: bool,
+    ) {
+        let pos = self.frames_done * self.hop_size;
+        let result = {
+            profile_span!("frame_dsp", frame = self.frames_done);
+            run_frame(&self.window, &mut self.frame_out, state, process)
+        };
+
+        profile_span!("overlap_add");
+        let processed = match &result {
+            Ok(()) => &self.frame_out,
+            Err(e) => {
+                on_failed(pos, e);
+                &self.window
+            }
+        };
+        self.synthesis.add(&mut self.overlap, processed, 1.0);
+        self.frames_done += 1;
+
+        // Everything before the next frame is final, and at the end everything received
+        let end = if last {
+            self.received
+        } else {
+            (pos + self.hop_size).min(self.received)
+        };
+        self.synthesis.normalize(&mut self.overlap[..end - pos], pos, self.frames_done);
+        emit(&self.overlap[..end - pos]);
+
+        let fft_size = self.window.len();
+        self.overlap.copy_within(self.hop_size.., 0);
+        self.overlap[fft_size - self.hop_size..].fill(0.0);
+        self.window.copy_within(self.hop_size.., 0);
+        self.filled = self.filled.saturating_sub(self.hop_size);
+    }
+}
+
+// Reports finished output to `on_progress` until it asks to stop
+struct Reporter<F> {
+    on_progress: F,
+    expected_total: usize,
+    done: usize,
+    len: usize,
+    stopped: bool,
+}
+
+impl<F: FnMut(Progress) -> ControlFlow<()>> Reporter<F> {
+    fn new(on_progress: F, expected_len: usize, fft_size: usize, hop_size: usize) -> Self {
+        let expected_total = frame_count(expected_len, fft_size, hop_size);
+        Self { on_progress, expected_total, done: 0, len: 0, stopped: false }
+    }
+
+    fn report(&mut self, done: usize, total: Option<usize>, finished: &[f32]) {
+        self.done = done;
+        if self.stopped {
+            return;
+        }
+        self.len += finished.len();
+        let total = total.unwrap_or(self.expected_total.max(done));
+        self.stopped = (self.on_progress)(Progress { done, total, finished }).is_break();
+    }
+}
+
+/// Run `process` over overlapping frames of the input and overlap-add the results, holding
+/// only one frame of audio. `read` appends up to the given number of input samples to the
+/// buffer and returns how many it added, 0 at the end of the input; `expected_len` is only
+/// used to estimate progress. The output goes to `on_progress` as soon as it is final, and
+/// returning `Break` from it stops early.
+pub fn process_stream<S, E: Debug>(
+    mut read: impl FnMut(&mut Vec<f32>, usize) -> Result<usize, String>,
+    expected_len: usize,
+    framing: Framing,
+    state: &mut S,
+    mut process: impl FnMut(&[f32], &mut [f32], &mut S) -> Result<(), E>,
+    on_progress: impl FnMut(Progress) -> ControlFlow<()>,
+) -> Result<StreamOutput, String> {
+    let mut frames = FrameStream::new(framing);
+    let mut reporter =
+        Reporter::new(on_progress, expected_len, frames.window.len(), frames.hop_size);
+    let mut failed_frames = Vec::new();
+    let mut block = Vec::with_capacity(READ_BLOCK);
+
+    loop {
+        block.clear();
+        if read(&mut block, READ_BLOCK)? == 0 {
+            break;
+        }
+        frames.push(
+            &block,
+            state,
+            &mut process,
+            |finished| reporter.report(reporter.done + 1, None, finished),
+            |pos, e| failed_frames.push((pos, format!("{:?}", e))),
+        );
+        if reporter.stopped {
+            return Ok(StreamOutput { len: reporter.len, failed_frames });
+        }
+    }
+
+    let total = frame_count(frames.received, frames.window.len(), frames.hop_size);
+    frames.finish(
+        state,
+        &mut process,
+        |finished| reporter.report(reporter.done + 1, Some(total), finished),
+        |pos, e| failed_frames.push((pos, format!("{:?}", e))),
+    );
+    Ok(StreamOutput { len: reporter.len, failed_frames })
+}
+
+// Processed chunk of frames, starting at the first frame's position
+struct ChunkOutput {
+    samples: Vec<f32>,
+    failed_frames: Vec<(usize, String)>,
+}
+
+// A chunk handed to a worker, with the input it needs from sample `base` on
+struct ChunkJob {
+    chunk: usize,
+    warmup: Range<usize>,
+    frames: Range<usize>,
+    crossfade: Crossfade,
+    base: usize,
+    input: Vec<f32>,
+}
+
+/// The frames a chunk shares with its neighbours. Both chunks at a join render its frames,
+/// the earlier one fading out from `fade_out` on while the later one fades in from
+/// `fade_in`, with gains that sum to one so the overlap-add stays a weighted average.
+#[derive(Debug, Clone, Copy)]
+struct Crossfade {
+    frames: usize,
+    // Not for the first chunk
+    fade_in: Option<usize>,
+    fade_out: usize,
+}
+
+impl Crossfade {
+    fn gain(&self, frame: usize) -> f32 {
+        let ramp = |i: usize| {
+            let x = (i as f32 + 0.5) / self.frames as f32;
+            (0.5 * PI * x).sin().powi(2)
+        };
+        if frame >= self.fade_out {
+            return 1.0 - ramp(frame - self.fade_out);
+        }
+        match self.fade_in {
+            Some(start) if frame < start + self.frames => ramp(frame - start),
+            _ => 1.0,
+        }
+    }
+}
+
+/// Like [`process_stream`], but chunks of frames run on several threads. Each chunk gets a
+/// fresh state from `make_state`, warmed up on the frames just before it, so the state
+/// carries the same recent context it would have had in a single serial pass; `make_state`
+/// is given the index of the first frame the state will see. Each chunk also renders the
+/// next chunk's first `crossfade_frames` frames, and at a join the two renderings of those
+/// frames are crossfaded before the windowed overlap-add, so whatever context the warm-up
+/// didn't rebuild comes in gradually instead of at one frame. Chunks are picked up by
+/// whichever worker is free and merged strictly in order, so the result is deterministic
+/// regardless of thread timing. Only a few chunks per thread are held in memory at a time.
+#[allow(clippy::too_many_arguments)]
+pub fn process_stream_parallel<S, E: Debug>(
+    mut read: impl FnMut(&mut Vec<f32>, usize) -> Result<usize, String>,
+    expected_len: usize,
+    framing: Framing,
+    options: &ParallelOptions,
+    make_state: impl Fn(usize) -> S + Sync,
+    process: impl Fn(&[f32], &mut [f32], &mut S) -> Result<(), E> + Sync,
+    on_progress: impl FnMut(Progress) -> ControlFlow<()>,
+) -> Result<StreamOutput, String> {
+    let framing = framing.clamped();
+    let Framing { fft_size, hop_size, window, padding } = framing;
+    if options.threads <= 1 {
+        let mut state = make_state(0);
+        return process_stream(
+            read,
+            expected_len,
+            framing,
+            &mut state,
+            |frame_in, frame_out, state| process(frame_in, frame_out, state),
+            on_progress,
+        );
+    }
+
+    let chunk_frames = options.chunk_frames.max(1);
+    let crossfade_frames = options.crossfade_frames.min(chunk_frames);
+    let threads = options.threads;
+    // Input from the first sample any later chunk (or its warm-up) needs
+    let mut input = Vec::new();
+    let mut input_base = 0;
+    let mut signal_len: Option<usize> = None;
+    // Output still receiving overlap from later chunks
+    let mut output = Vec::new();
+    let mut output_base = 0;
+
+    let synthesis = Synthesis::new(fft_size, hop_size, window);
+    let mut reporter = Reporter::new(on_progress, expected_len, fft_size, hop_size);
+    let mut failed_frames = Vec::new();
+    let stop = AtomicBool::new(false);
+
+    let (job_tx, job_rx) = mpsc::sync_channel::<ChunkJob>(threads);
+    let job_rx = Mutex::new(job_rx);
+    let (result_tx, result_rx) = mpsc::channel::<(usize, ChunkOutput)>();
+
+    thread::scope(|scope| -> Result<(), String> {
+        for _ in 0..threads {
+            let result_tx = result_tx.clone();
+            let (job_rx, stop, synthesis) = (&job_rx, &stop, &synthesis);
+            let (make_state, process) = (&make_state, &process);
+
+            scope.spawn(move || {
+                let mut frames = FrameBuffers::new(fft_size, padding);
+                loop {
+                    let job = match job_rx.lock().map(|jobs| jobs.recv()) {
+                        Ok(Ok(job)) => job,
+                        _ => break,
+                    };
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+
+                    let mut state = make_state(job.warmup.start);
+                    let chunk_output = frames.run_chunk(
+                        &job.input,
+                        job.base,
+                        synthesis,
+                        job.warmup,
+                        job.frames,
+                        job.crossfade,
+                        &mut state,
+                        &mut |frame_in: &[f32], frame_out: &mut [f32], state: &mut S| {
+                            process(frame_in, frame_out, state)
+                        },
+                    );
+                    if result_tx.send((job.chunk, chunk_output)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(result_tx);
+        let stopped_unexpectedly = || "Processing workers stopped unexpectedly".to_string();
+
+        // Closed once every chunk is dispatched; workers exit when the queue runs dry
+        let mut job_tx = Some(job_tx);
+        let mut dispatched = 0;
+        let mut next_to_merge = 0;
+        let mut pending = BTreeMap::new();
+
+        loop {
+            // Keep the workers busy, but don't read arbitrarily far ahead of the merge
+            if job_tx.is_some() && dispatched - next_to_merge < 2 * threads {
+                let first_frame = dispatched * chunk_frames;
+                let warmup_start = first_frame.saturating_sub(options.warmup_frames);
+                let start = warmup_start * hop_size;
+                let fade_out = first_frame + chunk_frames;
+                let end = (fade_out + crossfade_frames - 1) * hop_size + fft_size;
+                while signal_len.is_none() && input_base + input.len() < end {
+                    let wanted = end - input_base - input.len();
+                    if read(&mut input, wanted)? == 0 {
+                        signal_len = Some(input_base + input.len());
+                    }
+                }
+
+                let total = signal_len.map(|len| frame_count(len, fft_size, hop_size));
+                if total.is_some_and(|total| first_frame >= total) {
+                    job_tx = None;
+                    continue;
+                }
+                let last_frame = total.map_or(fade_out + crossfade_frames, |total| {
+                    total.min(fade_out + crossfade_frames)
+                });
+                let available = (input_base + input.len()).min(end);
+                let job = ChunkJob {
+                    chunk: dispatched,
+                    warmup: warmup_start..first_frame,
+                    frames: first_frame..last_frame,
+                    crossfade: Crossfade {
+                        frames: crossfade_frames,
+                        fade_in: (dispatched > 0).then_some(first_frame),
+                        fade_out,
+                    },
+                    base: start,
+                    input: input[start - input_base..available - input_base].to_vec(),
+                };
+                if job_tx.as_ref().is_none_or(|jobs| jobs.send(job).is_err()) {
+                    return Err(stopped_unexpectedly());
+                }
+                dispatched += 1;
+
+                // Drop input no later chunk (or its warm-up) needs
+                let next_start =
+                    (dispatched * chunk_frames).saturating_sub(options.warmup_frames) * hop_size;
+                let consumed = next_start.saturating_sub(input_base).min(input.len());
+                input.drain(..consumed);
+                input_base += consumed;
+                continue;
+            }
+
+            if job_tx.is_none() && next_to_merge == dispatched {
+                return Ok(());
+            }
+            let (chunk, chunk_output) = result_rx.recv().map_err(|_| stopped_unexpectedly())?;
+            pending.insert(chunk, chunk_output);
+
+            // Reorder buffer: merge chunk results strictly in order as they arrive
+            while let Some(chunk_output) = pending.remove(&next_to_merge) {
+                profile_span!("merge_chunk", chunk = next_to_merge);
+                let offset = next_to_merge * chunk_frames * hop_size - output_base;
+                if output.len() < offset + chunk_output.samples.len() {
+                    output.resize(offset + chunk_output.samples.len(), 0.0);
+                }
+                for (dst, src) in output[offset..].iter_mut().zip(&chunk_output.samples) {
+                    *dst += src;
+                }
+                // Both chunks at a join may have failed on the frames they share
+                failed_frames.extend(chunk_output.failed_frames);
+                failed_frames.sort_by_key(|&(pos, _)| pos);
+                failed_frames.dedup_by_key(|&mut (pos, _)| pos);
+                next_to_merge += 1;
+
+                // Output before the next chunk's first frame is final, at the end all of it
+                let total = signal_len.map(|len| frame_count(len, fft_size, hop_size));
+                let done = (next_to_merge * chunk_frames).min(total.unwrap_or(usize::MAX));
+                let end = match signal_len {
+                    Some(len) if total == Some(done) => len,
+                    _ => done * hop_size,
+                };
+                let finished = (end - output_base).min(output.len());
+                synthesis.normalize(&mut output[..finished], output_base, done);
+                reporter.report(done, total, &output[..finished]);
+                output.drain(..finished);
+                output_base += finished;
+
+                if reporter.stopped {
+                    // Busy workers finish their chunk, queued chunks are skipped
+                    stop.store(true, Ordering::Relaxed);
+                    return Ok(());
+                }
+            }
+        }
+    })?;
+
+    Ok(StreamOutput { len: reporter.len, failed_frames })
+}
+
+// Reused per-thread frame buffers
+struct FrameBuffers {
+    input: Vec<f32>,
+    output: Vec<f32>,
+    padding: Padding,
+}
+
+impl FrameBuffers {
+    fn new(fft_size: usize, padding: Padding) -> Self {
+        Self { input: vec![0.0; fft_size], output: vec![0.0; fft_size], padding }
+    }
+
+    /// Copy the frame starting at `pos` of `input` into the input buffer, padding a partial
+    /// frame and sanitizing it so a bad input sample can't poison the state.
+    /// Returns how many samples of the frame lie inside `input`.
+    fn fill(&mut self, input: &[f32], pos: usize) -> usize {
+        let len = self.input.len().min(input.len().saturating_sub(pos));
+        self.input[..len].copy_from_slice(&input[pos..pos + len]);
+        sanitize(&mut self.input[..len]);
+        self.padding.pad(&mut self.input, len);
+        len
+    }
+
+    /// Run the `warmup` frames (output discarded), then overlap-add the windowed `frames`,
+    /// faded by `crossfade`, into a buffer starting at the first frame's position, for the
+    /// merge to normalize.
+    /// `input` holds the signal from sample `base` on, through the end of the last frame or
+    /// of the signal.
+    #[allow(clippy::too_many_arguments)]
+    fn run_chunk<S, E: Debug>(
+        &mut self,
+        input: &[f32],
+        base: usize,
+        synthesis: &Synthesis,
+        warmup: Range<usize>,
+        frames: Range<usize>,
+        crossfade: Crossfade,
+        state: &mut S,
+        process: &mut impl FnMut(&[f32], &mut [f32], &mut S) -> Result<(), E>,
+    ) -> ChunkOutput {
+        let hop_size = synthesis.hop_size;
+        for frame in warmup {
+            profile_span!("warmup_frame", frame);
+            self.fill(input, frame * hop_size - base);
+            let _ = process(&self.input, &mut self.output, state);
+        }
+
+        let offset = frames.start * hop_size;
+        let end = ((frames.end - 1) * hop_size + self.input.len()).min(base + input.len());
+        let mut output =
+            ChunkOutput { samples: vec![0.0; end - offset], failed_frames: Vec::new() };
+        for frame in frames {
+            let pos = frame * hop_size;
+            let len = self.fill(input, pos - base);
+            let result = {
+                profile_span!("frame_dsp", frame);
+                run_frame(&self.input, &mut self.output, state, process)
+            };
+
+            profile_span!("overlap_add");
+            let processed = match result {
+                Ok(()) => &self.output,
+                Err(e) => {
+                    output.failed_frames.push((pos, format!("{:?}", e)));
+                    &self.input
+                }
+            };
+            let dst = &mut output.samples[pos - offset..pos - offset + len];
+            synthesis.add(dst, processed, crossfade.gain(frame));
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
+
+    // Counts allocations per thread, so tests running in parallel don't disturb each other
+    struct CountingAllocator;
+
+    thread_local! {
+        static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            ALLOCATIONS.with(|count| count.set(count.get() + 1));
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            unsafe { System.dealloc(ptr, layout) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    fn allocations_during(f: impl FnOnce()) -> usize {
+        let before = ALLOCATIONS.with(Cell::get);
+        f();
+        ALLOCATIONS.with(Cell::get) - before
+    }
+
+    // Stateful toy processor: each frame is offset by the previous frame's first sample,
+    // so its output depends on exactly one frame of context
+    fn toy(frame_in: &[f32], frame_out: &mut [f32], previous: &mut f32) -> Result<(), String> {
+        if frame_in[0] < 0.0 {
+            return Err("negative".to_string());
+        }
+        for (out, &x) in frame_out.iter_mut().zip(frame_in) {
+            *out = x + *previous;
+        }
+        *previous = frame_in[0];
+        Ok(())
+    }
+
+    fn signal(len: usize) -> Vec<f32> {
+        (0..len).map(|i| (i % 37) as f32 * 0.01).collect()
+    }
+
+    // Input source handing out `input` in blocks of at most `block` samples
+    fn reader(
+        input: &[f32],
+        block: usize,
+    ) -> impl FnMut(&mut Vec<f32>, usize) -> Result<usize, String> + '_ {
+        let mut pos = 0;
+        move |buffer, max| {
+            let n = max.min(block).min(input.len() - pos);
+            buffer.extend_from_slice(&input[pos..pos + n]);
+            pos += n;
+            Ok(n)
+        }
+    }
+
+    // Straightforward frame-by-frame windowed overlap-add of the whole signal, divided by
+    // the window sum, for comparison
+    fn reference(input: &[f32], fft_size: usize, hop_size: usize) -> Vec<f32> {
+        let window = Synthesis::new(fft_size, hop_size, Window::Hann).window;
+        let mut output = vec![0.0; input.len()];
+        let mut weights = vec![0.0; input.len()];
+        let mut previous = 0.0;
+        for frame in 0..frame_count(input.len(), fft_size, hop_size) {
+            let pos = frame * hop_size;
+            let len = fft_size.min(input.len() - pos);
+            let mut frame_in = vec![0.0; fft_size];
+            frame_in[..len].copy_from_slice(&input[pos..pos + len]);
+            let mut frame_out = vec![0.0; fft_size];
+            toy(&frame_in, &mut frame_out, &mut previous).unwrap();
+            for (i, (&sample, &weight)) in frame_out[..len].iter().zip(&window).enumerate() {
+                output[pos + i] += sample * weight;
+                weights[pos + i] += weight;
+            }
+        }
+        output.iter().zip(&weights).map(|(sample, weight)| sample / weight).collect()
+    }
+
+    fn collect_serial(input: &[f32], fft_size: usize, hop_size: usize) -> (Vec<f32>, StreamOutput) {
+        let mut samples = Vec::new();
+        let output = process_stream(
+            reader(input, 1000),
+            input.len(),
+            Framing::new(fft_size, hop_size),
+            &mut 0.0,
+            toy,
+            |progress| {
+                samples.extend_from_slice(progress.finished);
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+        (samples, output)
+    }
+
+    #[test]
+    fn test_frame_count() {
+        assert_eq!(frame_count(0, 8, 2), 0);
+        assert_eq!(frame_count(5, 8, 2), 1);
+        // With overlap the samples after the last full frame's hop get a zero-padded frame
+        assert_eq!(frame_count(8, 8, 2), 2);
+        assert_eq!(frame_count(12, 8, 2), 4);
+        assert_eq!(frame_count(16, 8, 8), 2);
+        assert_eq!(frame_count(17, 8, 8), 3);
+    }
+
+    #[test]
+    fn test_stream_matches_whole_signal_processing() {
+        for len in [0, 5, 64, 100, 10_007] {
+            let input = signal(len);
+            let (streamed, output) = collect_serial(&input, 64, 16);
+            assert_eq!(output.len, input.len());
+            assert_eq!(streamed, reference(&input, 64, 16));
+        }
+
+        // Arbitrary push sizes, like an audio device would deliver
+        let input = signal(4_000);
+        let mut stream = FrameStream::new(Framing::new(64, 16));
+        let mut state = 0.0;
+        let mut streamed = Vec::new();
+        for block in input.chunks(37) {
+            stream.push(block, &mut state, toy, |hop| streamed.extend_from_slice(hop), |_, _| {});
+        }
+        // The last fft - hop samples wait for later frames until the stream is finished
+        assert_eq!(streamed.len(), input.len() - (64 - 16));
+        stream.finish(&mut state, toy, |hop| streamed.extend_from_slice(hop), |_, _| {});
+        assert_eq!(streamed, reference(&input, 64, 16));
+    }
+
+    #[test]
+    fn test_parallel_matches_serial() {
+        let input = signal(10_007);
+        let (serial, _) = collect_serial(&input, 64, 16);
+        assert_eq!(serial.len(), input.len());
+
+        let options = ParallelOptions {
+            threads: 4,
+            warmup_frames: 1,
+            chunk_frames: 10,
+            crossfade_frames: 3,
+        };
+        let mut last_progress = 0;
+        let mut streamed = Vec::new();
+        let parallel = process_stream_parallel(
+            reader(&input, 333),
+            input.len(),
+            Framing::new(64, 16),
+            &options,
+            |_| 0.0,
+            toy,
+            |progress| {
+                assert!(progress.done > last_progress && progress.done <= progress.total);
+                last_progress = progress.done;
+                streamed.extend_from_slice(progress.finished);
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(last_progress, frame_count(input.len(), 64, 16));
+        assert_eq!(parallel.len, serial.len());
+        assert_eq!(streamed.len(), serial.len());
+        for (a, b) in streamed.iter().zip(&serial) {
+            assert!((a - b).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn test_parallel_states_know_their_frame() {
+        // Each frame writes the index its state counted to at its first sample
+        let count = |_: &[f32], frame_out: &mut [f32], frame: &mut usize| -> Result<(), ()> {
+            frame_out.fill(0.0);
+            frame_out[0] = *frame as f32;
+            *frame += 1;
+            Ok(())
+        };
+        let options = ParallelOptions {
+            threads: 3,
+            warmup_frames: 2,
+            chunk_frames: 5,
+            crossfade_frames: 2,
+        };
+        let mut streamed = Vec::new();
+        let input = [0.0; 400];
+        process_stream_parallel(
+            reader(&input, 64),
+            input.len(),
+            Framing::new(8, 8),
+            &options,
+            |first| first,
+            count,
+            |progress| {
+                streamed.extend_from_slice(progress.finished);
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+        // Both chunks at a join render its frames, so those come out mixed, but from
+        // states that agree on the index
+        let firsts: Vec<f32> = streamed.iter().step_by(8).copied().collect();
+        assert_eq!(firsts.len(), 50);
+        for (frame, first) in firsts.iter().enumerate() {
+            assert!((first - frame as f32).abs() < 1e-4, "{} {}", frame, first);
+        }
+    }
+
+    #[test]
+    fn test_steady_state_does_not_allocate() {
+        let short = signal(20_000);
+        let long = signal(2_000_000);
+        let run = |input: &[f32]| {
+            allocations_during(|| {
+                let output = process_stream(
+                    reader(input, usize::MAX),
+                    input.len(),
+                    Framing::new(1024, 256),
+                    &mut 0.0,
+                    toy,
+                    |_| ControlFlow::Continue(()),
+                )
+                .unwrap();
+                assert_eq!(output.len, input.len());
+            })
+        };
+
+        // One-time setup (e.g. registering profiling spans) doesn't count as steady state
+        run(&short);
+
+        // Read block, the three frame buffers and the window, no matter how many frames are processed
+        assert_eq!(run(&short), 5);
+        assert_eq!(run(&long), 5);
+    }
+
+    #[test]
+    fn test_passthrough_keeps_the_input_level() {
+        let input: Vec<f32> = (0..5_000).map(|i| 0.8 * (i as f32 * 0.05).sin()).collect();
+        let passthrough = |frame_in: &[f32], frame_out: &mut [f32], _: &mut ()| -> Result<(), ()> {
+            frame_out.copy_from_slice(frame_in);
+            Ok(())
+        };
+        // Including the edges, and hops the window doesn't add up evenly at, for each window
+        let layouts = [(1024, 256), (64, 16), (64, 24), (64, 64)];
+        for ((fft_size, hop_size), window) in
+            layouts.into_iter().flat_map(|layout| Window::ALL.map(|window| (layout, window)))
+        {
+            let mut samples = Vec::new();
+            process_stream(
+                reader(&input, 1000),
+                input.len(),
+                Framing { window, ..Framing::new(fft_size, hop_size) },
+                &mut (),
+                passthrough,
+                |progress| {
+                    samples.extend_from_slice(progress.finished);
+                    ControlFlow::Continue(())
+                },
+            )
+            .unwrap();
+            assert_eq!(samples.len(), input.len());
+            for (a, b) in samples.iter().zip(&input) {
+                assert!((a - b).abs() < 1e-5, "{} vs {} at {}/{}", a, b, fft_size, hop_size);
+            }
+        }
+    }
+
+    #[test]
+    fn test_frame_layouts_are_checked() {
+        for fft_size in FFT_SIZES {
+            assert!(validate_frames(fft_size, fft_size / 4).is_ok());
+        }
+        assert!(validate_frames(1024, 1024).is_ok());
+        assert!(validate_frames(1000, 250).is_err());
+        assert!(validate_frames(1024, 300).is_err());
+        assert!(validate_frames(1024, 0).is_err());
+        assert!(validate_frames(512, 1024).is_err());
+        assert_eq!("blackman".parse(), Ok(Window::Blackman));
+        assert!("kaiser".parse::<Window>().is_err());
+    }
+
+    #[test]
+    fn test_tail_is_padded_to_the_last_sample() {
+        let mut frame = [1.0, 2.0, 3.0, 9.0, 9.0, 9.0, 9.0, 9.0];
+        Padding::Reflect.pad(&mut frame, 3);
+        assert_eq!(frame, [1.0, 2.0, 3.0, 2.0, 1.0, 2.0, 3.0, 2.0]);
+        Padding::Reflect.pad(&mut frame, 1);
+        assert_eq!(frame, [1.0; 8]);
+        Padding::Zero.pad(&mut frame, 2);
+        assert_eq!(frame, [1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!("reflect".parse(), Ok(Padding::Reflect));
+
+        // A note held to the very end comes out whole with either padding, serial or parallel
+        let input: Vec<f32> = (0..4_100).map(|i| 0.8 * (i as f32 * 0.05).sin()).collect();
+        let passthrough = |frame_in: &[f32], frame_out: &mut [f32], _: &mut ()| -> Result<(), ()> {
+            frame_out.copy_from_slice(frame_in);
+            Ok(())
+        };
+        let options = ParallelOptions {
+            threads: 3,
+            warmup_frames: 1,
+            chunk_frames: 4,
+            crossfade_frames: 4,
+        };
+        for padding in Padding::ALL {
+            let framing = Framing { padding, ..Framing::new(1024, 256) };
+            let mut serial = Vec::new();
+            let output = process_stream(
+                reader(&input, 1000),
+                input.len(),
+                framing,
+                &mut (),
+                passthrough,
+                |progress| {
+                    serial.extend_from_slice(progress.finished);
+                    ControlFlow::Continue(())
+                },
+            )
+            .unwrap();
+            let mut parallel = Vec::new();
+            process_stream_parallel(
+                reader(&input, 700),
+                input.len(),
+                framing,
+                &options,
+                |_| (),
+                passthrough,
+                |progress| {
+                    parallel.extend_from_slice(progress.finished);
+                    ControlFlow::Continue(())
+                },
+            )
+            .unwrap();
+            assert_eq!(output.len, input.len());
+            assert_eq!(serial.len(), input.len());
+            assert_eq!(parallel.len(), input.len());
+            for ((a, b), c) in serial.iter().zip(&parallel).zip(&input) {
+                assert!((a - c).abs() < 1e-5 && (b - c).abs() < 1e-5, "{}", padding);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cancel_keeps_finished_samples() {
+        let input = signal(100_000);
+        let mut streamed = Vec::new();
+        let output = process_stream(
+            reader(&input, 1000),
+            input.len(),
+            Framing::new(64, 16),
+            &mut 0.0,
+            toy,
+            |progress| {
+                streamed.extend_from_slice(progress.finished);
+                if progress.done == 10 {
+                    ControlFlow::Break(())
+                } else {
+                    ControlFlow::Continue(())
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!(output.len, 10 * 16);
+        assert_eq!(streamed.len(), output.len);
+
+        let options = ParallelOptions {
+            threads: 2,
+            warmup_frames: 1,
+            chunk_frames: 5,
+            crossfade_frames: 2,
+        };
+        let mut streamed = Vec::new();
+        let parallel = process_stream_parallel(
+            reader(&input, 1000),
+            input.len(),
+            Framing::new(64, 16),
+            &options,
+            |_| 0.0,
+            toy,
+            |progress| {
+                streamed.extend_from_slice(progress.finished);
+                ControlFlow::Break(())
+            },
+        )
+        .unwrap();
+        assert_eq!(parallel.len, 5 * 16);
+        assert_eq!(streamed.len(), parallel.len);
+    }
+
+    #[test]
+    fn test_sanitize() {
+        let mut samples = [0.5, f32::NAN, f32::INFINITY, f32::MIN_POSITIVE / 4.0, -0.25];
+        assert_eq!(sanitize(&mut samples), 2);
+        assert_eq!(samples, [0.5, 0.0, 0.0, 0.0, -0.25]);
+    }
+
+    #[test]
+    fn test_non_finite_frames_fall_back_to_input() {
+        let input = signal(256);
+        let blow_up = |frame_in: &[f32], frame_out: &mut [f32], _: &mut ()| -> Result<(), String> {
+            frame_out.copy_from_slice(frame_in);
+            if frame_in[0] == input[96] {
+                frame_out[3] = f32::NAN;
+            }
+            Ok(())
+        };
+
+        let mut samples = Vec::new();
+        let output = process_stream(
+            reader(&input, 100),
+            input.len(),
+            Framing::new(32, 32),
+            &mut (),
+            blow_up,
+            |progress| {
+                samples.extend_from_slice(progress.finished);
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            output.failed_frames,
+            vec![(96, "1 NaN/inf samples in frame output".to_string())]
+        );
+        assert_eq!(samples, input);
+    }
+
+    #[test]
+    fn test_failed_frames_fall_back_to_input() {
+        let mut input = signal(256);
+        input[64] = -1.0;
+
+        let mut samples = Vec::new();
+        let output = process_stream(
+            reader(&input, 100),
+            input.len(),
+            Framing::new(32, 32),
+            &mut 0.0,
+            toy,
+            |progress| {
+                samples.extend_from_slice(progress.finished);
+                ControlFlow::Continue(())
+            },
+        )
+        .unwrap();
+        assert_eq!(output.failed_frames, vec![(64, "\"negative\"".to_string())]);
+        assert_eq!(&samples[64..96], &input[64..96]);
+
+        let options = ParallelOptions {
+            threads: 3,
+            warmup_frames: 1,
+            chunk_frames: 2,
+            crossfade_frames: 1,
+        };
+        let parallel = process_stream_parallel(
+            reader(&input, 50),
+            input.len(),
+            Framing::new(32, 32),
+            &options,
+            |_| 0.0,
+            toy,
+            |_| ControlFlow::Continue(()),
+        )
+        .unwrap();
+        assert_eq!(parallel.failed_frames, output.failed_frames);
+    }
+
+    #[test]
+    fn test_crossfade_smooths_chunk_joins() {
+        // A correction that settles over many frames, like a slow glide, so a fresh state
+        // is still off after its warm-up
+        let glide = |frame_in: &[f32], frame_out: &mut [f32], gain: &mut f32| -> Result<(), ()> {
+            *gain += 0.2 * (1.0 - *gain);
+            for (out, &x) in frame_out.iter_mut().zip(frame_in) {
+                *out = x * *gain;
+            }
+            Ok(())
+        };
+        let len = 6 * 32 * 16;
+        let sweep: Vec<f32> = (0..len)
+            .map(|n| {
+                let t = n as f32 / len as f32;
+                0.5 * (n as f32 * (0.01 + 0.15 * t)).sin()
+            })
+            .collect();
+
+        // (largest step between neighbouring samples, lowest value) of the gain the sweep
+        // came out with after the first chunk
+        let gain_after_first_chunk = |crossfade_frames| {
+            let options = ParallelOptions {
+                threads: 3,
+                warmup_frames: 2,
+                chunk_frames: 32,
+                crossfade_frames,
+            };
+            let mut output = Vec::new();
+            process_stream_parallel(
+                reader(&sweep, 500),
+                len,
+                Framing::new(64, 16),
+                &options,
+                |_| 0.0,
+                glide,
+                |progress| {
+                    output.extend_from_slice(progress.finished);
+                    ControlFlow::Continue(())
+                },
+            )
+            .unwrap();
+            let gains: Vec<f32> = (32 * 16..len - 64)
+                .filter(|&n| sweep[n].abs() > 0.1)
+                .map(|n| output[n] / sweep[n])
+                .collect();
+            let step = gains.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max);
+            (step, gains.iter().copied().fold(1.0, f32::min))
+        };
+
+        // Switching states at one frame drops the level in a few samples
+        let (step, lowest) = gain_after_first_chunk(0);
+        assert!(step > 0.05 && lowest < 0.6, "{} {}", step, lowest);
+        let (step, lowest) = gain_after_first_chunk(8);
+        assert!(step < 0.02 && lowest > 0.85, "{} {}", step, lowest);
+    }
+}
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::effects::Effect;
+
+pub const BANDS: usize = 5;
+pub const FREQ_RANGE: RangeInclusive<f32> = 20.0..=20000.0;
+pub const GAIN_RANGE: RangeInclusive<f32> = -18.0..=18.0;
+pub const Q_RANGE: RangeInclusive<f32> = 0.1..=10.0;
+
+// Where the bands sit until they are moved: lows, mud, body, presence, and air
+const DEFAULT_FREQS: [f32; BANDS] = [100.0, 250.0, 1000.0, 4000.0, 10000.0];
+
+/// One band of the EQ: a peak that boosts or cuts `gain_db` around `freq_hz`, narrower the
+/// higher `q`. Written as "freq:gain:q", like "4000:3:0.8".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Band {
+    pub freq_hz: f32,
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+impl Band {
+    fn flat(freq_hz: f32) -> Self {
+        Self { freq_hz, gain_db: 0.0, q: 1.0 }
+    }
+
+    /// Whether the band leaves the audio as it is.
+    pub fn is_flat(&self) -> bool {
+        self.gain_db == 0.0
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !FREQ_RANGE.contains(&self.freq_hz) {
+            return Err(format!(
+                "EQ frequencies must be between {} and {} Hz",
+                FREQ_RANGE.start(),
+                FREQ_RANGE.end()
+            ));
+        }
+        if !GAIN_RANGE.contains(&self.gain_db) {
+            return Err(format!(
+                "EQ gains must be between {} and +{} dB",
+                GAIN_RANGE.start(),
+                GAIN_RANGE.end()
+            ));
+        }
+        if !Q_RANGE.contains(&self.q) {
+            return Err(format!("EQ Q must be between {} and {}", Q_RANGE.start(), Q_RANGE.end()));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Band {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let values = text
+            .split(':')
+            .map(|value| value.trim().parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>();
+        match values.as_deref() {
+            Ok(&[freq_hz, gain_db, q]) => Ok(Self { freq_hz, gain_db, q }),
+            _ => Err(format!(
+                "Invalid EQ band '{}': expected frequency, gain, and Q, like 4000:3:0.8",
+                text.trim()
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Band {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.freq_hz, self.gain_db, self.q)
+    }
+}
+
+/// A parametric EQ of `BANDS` bands, each flat until it is given a gain. Written as up to
+/// `BANDS` bands joined by commas, like "250:-3:1.2,4000:3:0.8" to cut mud and lift
+/// presence; bands left out stay flat.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Equalizer {
+    pub bands: [Band; BANDS],
+}
+
+impl Default for Equalizer {
+    fn default() -> Self {
+        Self { bands: DEFAULT_FREQS.map(Band::flat) }
+    }
+}
+
+impl Equalizer {
+    pub fn validate(&self) -> Result<(), String> {
+        self.bands.iter().try_for_each(Band::validate)
+    }
+
+    /// The bands that change the audio.
+    pub fn active(&self) -> impl Iterator<Item = &Band> {
+        self.bands.iter().filter(|band| !band.is_flat())
+    }
+}
+
+impl FromStr for Equalizer {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let bands = text.split(',').map(str::parse).collect::<Result<Vec<Band>, _>>()?;
+        if bands.len() > BANDS {
+            return Err(format!("The EQ has at most {} bands, got {}", BANDS, bands.len()));
+        }
+        let mut eq = Self::default();
+        eq.bands[..bands.len()].copy_from_slice(&bands);
+        Ok(eq)
+    }
+}
+
+impl TryFrom<String> for Equalizer {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        text.parse()
+    }
+}
+
+impl From<Equalizer> for String {
+    fn from(eq: Equalizer) -> Self {
+        eq.to_string()
+    }
+}
+
+impl fmt::Display for Equalizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bands: Vec<String> = self.bands.iter().map(Band::to_string).collect();
+        write!(f, "{}", bands.join(","))
+    }
+}
+
+/// The peaking filter of one [`Band`], running over audio at one sample rate.
+#[derive(Debug, Clone)]
+pub struct Peak {
+    b: [f32; 3],
+    a: [f32; 2],
+    state: [f32; 2],
+}
+
+impl Peak {
+    pub fn new(band: Band, sample_rate: f32) -> Self {
+        let w0 = 2.0 * PI * band.freq_hz.min(sample_rate * 0.45) / sample_rate;
+        let gain = 10f32.powf(band.gain_db / 40.0);
+        let alpha = w0.sin() / (2.0 * band.q);
+        let a0 = 1.0 + alpha / gain;
+        Self {
+            b: [(1.0 + alpha * gain) / a0, -2.0 * w0.cos() / a0, (1.0 - alpha * gain) / a0],
+            a: [-2.0 * w0.cos() / a0, (1.0 - alpha / gain) / a0],
+            state: [0.0; 2],
+        }
+    }
+}
+
+impl Effect for Peak {
+    fn process(&mut self, samples: &mut [f32]) {
+        for x in samples {
+            let y = self.b[0] * *x + self.state[0];
+            self.state[0] = self.b[1] * *x - self.a[0] * y + self.state[1];
+            self.state[1] = self.b[2] * *x - self.a[1] * y;
+            *x = y;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bands_boost_and_cut_around_their_frequency() {
+        let rate = 48000.0;
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+        // Change in level, in dB, of a sine at `hz` through `band`
+        let change_db = |band: Band, hz: f32| {
+            let mut samples: Vec<f32> =
+                (0..48000).map(|i| 0.25 * (2.0 * PI * hz * i as f32 / rate).sin()).collect();
+            let mut peak = Peak::new(band, rate);
+            for block in samples.chunks_mut(1000) {
+                peak.process(block);
+            }
+            20.0 * (rms(&samples[4800..]) / (0.25 / 2f32.sqrt())).log10()
+        };
+        let presence = Band { freq_hz: 4000.0, gain_db: 6.0, q: 1.0 };
+        assert!((change_db(presence, 4000.0) - 6.0).abs() < 0.1);
+        assert!(change_db(presence, 200.0).abs() < 0.1);
+        let mud = Band { freq_hz: 250.0, gain_db: -4.0, q: 2.0 };
+        assert!((change_db(mud, 250.0) + 4.0).abs() < 0.1);
+
+        let eq: Equalizer = "250:-4:2, 4000:6:1".parse().unwrap();
+        assert_eq!(eq.bands[..2], [mud, presence]);
+        assert_eq!(eq.active().count(), 2);
+        assert_eq!(eq.to_string().parse(), Ok(eq));
+        assert!(eq.validate().is_ok());
+        assert_eq!(Equalizer::default().active().count(), 0);
+        assert!("250:-4".parse::<Equalizer>().is_err());
+        assert!(["100:1:1"; 6].join(",").parse::<Equalizer>().is_err());
+        assert!(Band { q: 50.0, ..presence }.validate().is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::fmt;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// Exit code of a failure that isn't of one of the classes, like an unreadable settings
+/// directory or side file.
+pub const GENERAL_CODE: i32 = 1;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// A class of failure, each with an exit code of its own so scripts wrapping the CLI can
+/// branch on what went wrong instead of reading the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    // Arguments that can't be used, like an out of range setting or a missing input. Also
+    // what clap exits with for arguments it can't parse
+    Arguments,
+    // An input in a format or layout that can't be processed
+    UnsupportedFormat,
+    // An input that can't be opened or decoded
+    Decode,
+    // Correcting the audio failed, or produced output that didn't pass the check
+    Processing,
+    // The output or its side files couldn't be written
+    Write,
+    // Stopped with Ctrl-C before finishing
+    Cancelled,
+}
+
+impl Failure {
+    pub fn code(self) -> i32 {
+        match self {
+            Failure::Arguments => 2,
+            Failure::UnsupportedFormat => 3,
+            Failure::Decode => 4,
+            Failure::Processing => 5,
+            Failure::Write => 6,
+            // Like a shell reports a process stopped by SIGINT
+            Failure::Cancelled => 130,
+        }
+    }
+}
+
+/// An error of a known class of failure.
+#[derive(Debug)]
+pub struct Failed {
+    pub failure: Failure,
+    message: String,
+}
+
+impl Failed {
+    pub fn new(failure: Failure, message: impl Into<String>) -> Self {
+        Self { failure, message: message.into() }
+    }
+}
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for Failed {}
+
+/// The class of failure `error` was marked as, if any.
+pub fn class_of(error: &(dyn Error + 'static)) -> Option<Failure> {
+    error.downcast_ref::<Failed>().map(|failed| failed.failure)
+}
+
+/// Exit code for a run that ended in `error`.
+pub fn exit_code(error: &(dyn Error + 'static)) -> i32 {
+    class_of(error).map_or(GENERAL_CODE, Failure::code)
+}
+
+/// Marks the error of a result as a class of failure.
+pub trait Classify<T> {
+    /// The error as a `failure`, unless it was classified already closer to where it
+    /// happened.
+    fn or_fail(self, failure: Failure) -> Result<T, Box<dyn Error>>;
+}
+
+impl<T, E: Into<Box<dyn Error>>> Classify<T> for Result<T, E> {
+    fn or_fail(self, failure: Failure) -> Result<T, Box<dyn Error>> {
+        self.map_err(|e| {
+            let e = e.into();
+            if e.is::<Failed>() {
+                e
+            } else {
+                Box::new(Failed::new(failure, e.to_string()))
+            }
+        })
+    }
+}
+
+/// Catch Ctrl-C, so a render can stop at the next block and clean up after itself. A second
+/// Ctrl-C quits straight away.
+pub fn catch_interrupt() {
+    thread::spawn(|| {
+        let Ok(runtime) = tokio::runtime::Builder::new_current_thread().enable_io().build() else {
+            return;
+        };
+        while runtime.block_on(tokio::signal::ctrl_c()).is_ok() {
+            if INTERRUPTED.swap(true, Ordering::Relaxed) {
+                process::exit(Failure::Cancelled.code());
+            }
+            eprintln!("Stopping, press Ctrl-C again to quit straight away");
+        }
+    });
+}
+
+/// Whether Ctrl-C was pressed.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+/// An error if Ctrl-C was pressed, to stop before the next step.
+pub fn check_interrupted() -> Result<(), Failed> {
+    if interrupted() {
+        return Err(Failed::new(Failure::Cancelled, "Cancelled"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_keep_their_class() {
+        let decode: Result<(), String> = Err("Failed to decode take.mp3".to_string());
+        let e = decode.or_fail(Failure::Decode).unwrap_err();
+        assert_eq!(exit_code(&*e), Failure::Decode.code());
+        assert_eq!(e.to_string(), "Failed to decode take.mp3");
+        // Classified where it happened, a later, broader class leaves it as it is
+        let e = Err::<(), _>(e).or_fail(Failure::Processing).unwrap_err();
+        assert_eq!(exit_code(&*e), Failure::Decode.code());
+        let other: Box<dyn Error> = "Failed to read presets.json".into();
+        assert_eq!(class_of(&*other), None);
+        assert_eq!(exit_code(&*other), GENERAL_CODE);
+        assert!(check_interrupted().is_ok());
+    }
+}
@@ -0,0 +1,235 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::app_config::PORTABLE_FLAG;
+use crate::decoder;
+
+const APP_NAME: &str = "Autotune";
+const DESKTOP_FILE: &str = "desktop-autotune-app.desktop";
+// MIME types of the input formats, for the Linux desktop entry
+const AUDIO_MIME_TYPES: [&str; 8] = [
+    "audio/wav",
+    "audio/x-wav",
+    "audio/wave",
+    "audio/vnd.wave",
+    "audio/mpeg",
+    "audio/mp4",
+    "audio/x-m4a",
+    "audio/aac",
+];
+// Context-menu verb added to each input extension on Windows
+const WINDOWS_VERB: &str = "AutotuneOpen";
+
+/// Whether this platform can be registered from the app. On Linux a desktop entry is
+/// written, on Windows per-user registry keys; on macOS Finder's "Open With" comes from
+/// the document types an app bundle declares, so there is nothing to register at runtime.
+pub fn is_supported() -> bool {
+    cfg!(target_os = "linux") || cfg!(windows)
+}
+
+/// Add "Open with Autotune" for the supported input formats to the file manager, launching
+/// `exe` with the file as its argument (with --portable when `portable`). Only for the
+/// current user; existing default apps are left alone.
+pub fn register(exe: &Path, portable: bool) -> Result<String, String> {
+    if cfg!(windows) {
+        register_windows(exe, portable)?;
+        Ok(format!("Added \"Open with {}\" to Explorer", APP_NAME))
+    } else if cfg!(target_os = "linux") {
+        let dir = applications_dir().ok_or("No home directory to add the desktop entry to")?;
+        let path = write_desktop_entry(&dir, exe, portable)?;
+        refresh_desktop_database(&dir);
+        Ok(format!("Added \"Open with {}\" with {}", APP_NAME, path.display()))
+    } else {
+        Err("On macOS, Open With comes from the app bundle".to_string())
+    }
+}
+
+/// Remove what `register` added.
+pub fn unregister() -> Result<String, String> {
+    if cfg!(windows) {
+        let exe_name = current_exe_name()?;
+        for key in windows_keys(&exe_name) {
+            // Missing keys are fine, one may have been removed by hand
+            let _ = run_reg(&["delete", &key, "/f"]);
+        }
+        Ok(format!("Removed \"Open with {}\" from Explorer", APP_NAME))
+    } else if cfg!(target_os = "linux") {
+        let dir = applications_dir().ok_or("No home directory")?;
+        let path = dir.join(DESKTOP_FILE);
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+            refresh_desktop_database(&dir);
+        }
+        Ok(format!("Removed \"Open with {}\"", APP_NAME))
+    } else {
+        Err("On macOS, Open With comes from the app bundle".to_string())
+    }
+}
+
+pub fn is_registered() -> bool {
+    if cfg!(windows) {
+        current_exe_name().is_ok_and(|exe_name| {
+            run_reg(&["query", &format!("{}\\shell\\open\\command", application_key(&exe_name))])
+                .is_ok()
+        })
+    } else if cfg!(target_os = "linux") {
+        applications_dir().is_some_and(|dir| dir.join(DESKTOP_FILE).exists())
+    } else {
+        false
+    }
+}
+
+// Where per-user desktop entries live (XDG base directory spec)
+fn applications_dir() -> Option<PathBuf> {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local").join("share"))
+        })?;
+    Some(data_home.join("applications"))
+}
+
+fn write_desktop_entry(dir: &Path, exe: &Path, portable: bool) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    let path = dir.join(DESKTOP_FILE);
+    std::fs::write(&path, desktop_entry(exe, portable))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+    Ok(path)
+}
+
+fn desktop_entry(exe: &Path, portable: bool) -> String {
+    let mut exec = quote_exec_arg(&exe.to_string_lossy());
+    if portable {
+        exec.push(' ');
+        exec.push_str(PORTABLE_FLAG);
+    }
+    let mime_types: String = AUDIO_MIME_TYPES.iter().map(|mime| format!("{};", mime)).collect();
+    format!(
+        "[Desktop Entry]\nType=Application\nName={}\nComment=Pitch correction for vocals\n\
+         Exec={} %f\nTerminal=false\nCategories=AudioVideo;Audio;\nMimeType={}\n",
+        APP_NAME, exec, mime_types
+    )
+}
+
+// Quoted for the Exec key: the spec's argument quoting, then string-value escaping of the
+// backslashes that adds
+fn quote_exec_arg(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in arg.chars() {
+        match c {
+            '"' | '`' | '$' => {
+                quoted.push_str("\\\\");
+                quoted.push(c);
+            }
+            '\\' => quoted.push_str("\\\\\\\\"),
+            '%' => quoted.push_str("%%"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Lets the file manager pick up the new entry without logging out. Not every desktop
+// ships the tool, and those that don't watch the directory themselves
+fn refresh_desktop_database(dir: &Path) {
+    let _ = Command::new("update-desktop-database").arg(dir).output();
+}
+
+fn current_exe_name() -> Result<String, String> {
+    let exe = std::env::current_exe().map_err(|e| format!("Can't locate the app: {}", e))?;
+    exe.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| "Can't locate the app".to_string())
+}
+
+fn application_key(exe_name: &str) -> String {
+    format!("HKCU\\Software\\Classes\\Applications\\{}", exe_name)
+}
+
+// Every key `register_windows` creates, parents of others last
+fn windows_keys(exe_name: &str) -> Vec<String> {
+    let mut keys: Vec<String> = decoder::INPUT_EXTENSIONS
+        .iter()
+        .map(|ext| {
+            format!(
+                "HKCU\\Software\\Classes\\SystemFileAssociations\\.{}\\shell\\{}",
+                ext, WINDOWS_VERB
+            )
+        })
+        .collect();
+    keys.push(application_key(exe_name));
+    keys
+}
+
+fn windows_command(exe: &Path, portable: bool) -> String {
+    let portable = if portable {
+        format!(" {}", PORTABLE_FLAG)
+    } else {
+        String::new()
+    };
+    format!("\"{}\"{} \"%1\"", exe.display(), portable)
+}
+
+fn register_windows(exe: &Path, portable: bool) -> Result<(), String> {
+    let exe_name = current_exe_name()?;
+    let command = windows_command(exe, portable);
+    let app_key = application_key(&exe_name);
+    let open_with = format!("Open with {}", APP_NAME);
+
+    // Listed under "Open with" for the supported types
+    run_reg(&["add", &app_key, "/v", "FriendlyAppName", "/d", APP_NAME, "/f"])?;
+    run_reg(&["add", &format!("{}\\shell\\open\\command", app_key), "/ve", "/d", &command, "/f"])?;
+    for ext in decoder::INPUT_EXTENSIONS {
+        let ext = format!(".{}", ext);
+        run_reg(&["add", &format!("{}\\SupportedTypes", app_key), "/v", &ext, "/d", "", "/f"])?;
+    }
+
+    // And as its own context-menu entry, without changing the default app
+    for key in windows_keys(&exe_name).iter().filter(|key| **key != app_key) {
+        run_reg(&["add", key, "/ve", "/d", &open_with, "/f"])?;
+        run_reg(&["add", &format!("{}\\command", key), "/ve", "/d", &command, "/f"])?;
+    }
+    Ok(())
+}
+
+fn run_reg(args: &[&str]) -> Result<(), String> {
+    let output =
+        Command::new("reg").args(args).output().map_err(|e| format!("Failed to run reg: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_launch_commands_pass_the_file() {
+        let entry = desktop_entry(Path::new("/opt/Auto Tune/autotune-gui"), true);
+        assert!(entry.starts_with("[Desktop Entry]\n"));
+        assert!(entry.contains("\nExec=\"/opt/Auto Tune/autotune-gui\" --portable %f\n"));
+        assert!(entry.contains("MimeType=audio/wav;audio/x-wav;"));
+
+        // Escaped once for the command line and once more for the desktop file
+        assert_eq!(quote_exec_arg("a\"b\\c%d"), "\"a\\\\\"b\\\\\\\\c%%d\"");
+
+        let command = windows_command(Path::new(r"C:\Apps\autotune-gui.exe"), false);
+        assert_eq!(command, r#""C:\Apps\autotune-gui.exe" "%1""#);
+
+        let dir = std::env::temp_dir().join("autotune-file-association-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = write_desktop_entry(&dir, Path::new("/usr/bin/autotune-gui"), false).unwrap();
+        assert_eq!(path, dir.join(DESKTOP_FILE));
+        assert!(
+            std::fs::read_to_string(&path).unwrap().contains("Exec=\"/usr/bin/autotune-gui\" %f")
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,242 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::UNIX_EPOCH;
+
+use crate::paths;
+
+// Renders the index remembers, the oldest dropped first
+const MAX_RENDERS: usize = 1000;
+
+static INDEX: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// What a render is made of: the audio of its input and every setting that changes the
+/// output, but not the names of either file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Fingerprint(u64);
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// Builds a [`Fingerprint`] from the parts of a render, in the order they are added.
+/// FNV-1a, which is the same from one run and platform to the next.
+#[derive(Debug, Clone)]
+pub struct Fingerprinter(u64);
+
+impl Default for Fingerprinter {
+    fn default() -> Self {
+        // Different versions may render the same settings differently
+        let mut fingerprinter = Self(0xcbf2_9ce4_8422_2325);
+        fingerprinter.bytes(env!("CARGO_PKG_VERSION").as_bytes());
+        fingerprinter
+    }
+}
+
+impl Fingerprinter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `bytes`, with their length so neighbouring parts can't run into each other.
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.raw(&(bytes.len() as u64).to_le_bytes());
+        self.raw(bytes);
+    }
+
+    /// Add settings by how they print with `{:?}`, which covers every field of them.
+    pub fn settings(&mut self, settings: &impl fmt::Debug) {
+        self.bytes(format!("{:?}", settings).as_bytes());
+    }
+
+    /// Add the contents of the file at `path`, read a block at a time.
+    pub fn file(&mut self, path: &Path) -> Result<(), String> {
+        let mut file = File::open(paths::normalize_for_io(path))
+            .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+        let mut block = vec![0u8; paths::io_block_size()];
+        let mut len = 0u64;
+        loop {
+            let read = file
+                .read(&mut block)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            if read == 0 {
+                break;
+            }
+            self.raw(&block[..read]);
+            len += read as u64;
+        }
+        self.raw(&len.to_le_bytes());
+        Ok(())
+    }
+
+    pub fn finish(&self) -> Fingerprint {
+        Fingerprint(self.0)
+    }
+
+    fn raw(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+        }
+    }
+}
+
+/// Keep the index of finished renders in the file at `path`, or none with None.
+pub fn set_index(path: Option<PathBuf>) {
+    *INDEX.write().unwrap_or_else(|e| e.into_inner()) = path;
+}
+
+fn index() -> Option<PathBuf> {
+    INDEX.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// An earlier render made of the same as `fingerprint`, if one is still where it was
+/// written and hasn't been changed since, whatever it was named.
+pub fn find(fingerprint: Fingerprint) -> Option<PathBuf> {
+    find_in(&index()?, fingerprint)
+}
+
+/// Remember that `output` was just rendered from `fingerprint`.
+pub fn record(fingerprint: Fingerprint, output: &Path) -> Result<(), String> {
+    match index() {
+        Some(index) => record_in(&index, fingerprint, output),
+        None => Ok(()),
+    }
+}
+
+/// Copy the earlier `render` of `fingerprint` to `output` instead of rendering it again,
+/// and remember the copy as one too. Nothing is copied when `output` is that render.
+pub fn reuse(render: &Path, output: &Path, fingerprint: Fingerprint) -> Result<(), String> {
+    if !is_same_file(render, output) {
+        fs::copy(render, output).map_err(|e| {
+            format!("Failed to copy {} to {}: {}", render.display(), output.display(), e)
+        })?;
+    }
+    record(fingerprint, output)
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    matches!((fs::canonicalize(a), fs::canonicalize(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+// A finished render, and how its file was when it was written
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct IndexedRender {
+    fingerprint: Fingerprint,
+    path: PathBuf,
+    len: u64,
+    modified_ms: u64,
+}
+
+impl IndexedRender {
+    fn of(fingerprint: Fingerprint, path: &Path) -> Result<Self, String> {
+        let path = fs::canonicalize(path)
+            .map_err(|e| format!("Failed to find {}: {}", path.display(), e))?;
+        let (len, modified_ms) = file_state(&path)
+            .ok_or_else(|| format!("Failed to read the metadata of {}", path.display()))?;
+        Ok(Self { fingerprint, path, len, modified_ms })
+    }
+
+    // Still there, and not written to since
+    fn is_current(&self) -> bool {
+        file_state(&self.path) == Some((self.len, self.modified_ms))
+    }
+}
+
+fn file_state(path: &Path) -> Option<(u64, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((metadata.len(), modified.as_millis() as u64))
+}
+
+fn find_in(index: &Path, fingerprint: Fingerprint) -> Option<PathBuf> {
+    load(index)
+        .into_iter()
+        .rev()
+        .find(|render| render.fingerprint == fingerprint && render.is_current())
+        .map(|render| render.path)
+}
+
+fn record_in(index: &Path, fingerprint: Fingerprint, output: &Path) -> Result<(), String> {
+    let render = IndexedRender::of(fingerprint, output)?;
+    let mut renders = load(index);
+    // Renders that were overwritten, this one included, or moved away are of no use
+    renders.retain(|earlier| earlier.path != render.path && earlier.is_current());
+    renders.push(render);
+    let excess = renders.len().saturating_sub(MAX_RENDERS);
+    renders.drain(..excess);
+    save(index, &renders)
+}
+
+fn load(index: &Path) -> Vec<IndexedRender> {
+    let Ok(contents) = fs::read_to_string(index) else {
+        return Vec::new();
+    };
+    serde_json::from_str(&contents).unwrap_or_else(|e| {
+        log::warn!("Ignoring unreadable render index {}: {}", index.display(), e);
+        Vec::new()
+    })
+}
+
+// Written then renamed, so a crash never leaves half an index behind
+fn save(index: &Path, renders: &[IndexedRender]) -> Result<(), String> {
+    let failed = |e: &dyn fmt::Display| format!("Failed to save {}: {}", index.display(), e);
+    if let Some(dir) = index.parent() {
+        fs::create_dir_all(dir).map_err(|e| failed(&e))?;
+    }
+    let json = serde_json::to_string(renders).map_err(|e| failed(&e))?;
+    let temp_path = index.with_extension("tmp");
+    fs::write(&temp_path, json).and_then(|()| fs::rename(&temp_path, index)).map_err(|e| failed(&e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_renders_are_found_under_any_name() {
+        let dir = std::env::temp_dir().join("autotune-fingerprint-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("take.wav");
+        fs::write(&input, [1u8, 2, 3]).unwrap();
+        let copy = dir.join("take copy.wav");
+        fs::copy(&input, &copy).unwrap();
+
+        let fingerprint = |path: &Path, strength: f32| {
+            let mut fingerprinter = Fingerprinter::new();
+            fingerprinter.file(path).unwrap();
+            fingerprinter.settings(&strength);
+            fingerprinter.finish()
+        };
+        let tuned = fingerprint(&input, 0.8);
+        assert_eq!(fingerprint(&copy, 0.8), tuned);
+        assert_ne!(fingerprint(&input, 0.5), tuned);
+        // Parts don't run into each other
+        let mut a = Fingerprinter::new();
+        a.bytes(b"ab");
+        a.bytes(b"c");
+        let mut b = Fingerprinter::new();
+        b.bytes(b"a");
+        b.bytes(b"bc");
+        assert_ne!(a.finish(), b.finish());
+
+        let index = dir.join("renders.json");
+        let output = dir.join("tuned.wav");
+        assert_eq!(find_in(&index, tuned), None);
+        fs::write(&output, [4u8, 5]).unwrap();
+        record_in(&index, tuned, &output).unwrap();
+        let found = find_in(&index, tuned).unwrap();
+        assert!(is_same_file(&found, &output));
+        assert_eq!(find_in(&index, fingerprint(&input, 0.5)), None);
+
+        // A render that was written over is no longer one
+        fs::write(&output, [4u8, 5, 6]).unwrap();
+        assert_eq!(find_in(&index, tuned), None);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,205 @@
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+
+// Languages that write 1,5 where English writes 1.5
+const COMMA_LANGUAGES: [&str; 31] = [
+    "bg", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr", "hu", "id", "it",
+    "lt", "lv", "nb", "nl", "nn", "pl", "pt", "ro", "ru", "sk", "sl", "sr", "sv", "tr", "uk",
+];
+
+static CURRENT: RwLock<Format> = RwLock::new(Format::PLAIN);
+
+/// What separates the whole and fractional parts of numbers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Separator {
+    // The one of the language the system is set to
+    #[default]
+    Locale,
+    Point,
+    Comma,
+}
+
+/// Whether file sizes count in steps of 1024 (KiB, MiB) or of 1000 (kB, MB).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SizeUnits {
+    #[default]
+    Binary,
+    Si,
+}
+
+/// How numbers shown to people are written, as chosen in the settings.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatSettings {
+    pub decimal: Separator,
+    pub size_units: SizeUnits,
+}
+
+/// How durations, file sizes, sample rates and other numbers are written in the GUI, the
+/// CLI's output, and reports. Numbers in files other programs read, like label tracks and
+/// projects, are always written with a point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    decimal: char,
+    size_units: SizeUnits,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self::PLAIN
+    }
+}
+
+impl Format {
+    /// Points and binary units, what's used until [`set`] is called.
+    pub const PLAIN: Self = Self { decimal: '.', size_units: SizeUnits::Binary };
+
+    /// The format of `settings`, looking up the separator of the system's language if it
+    /// is to follow it.
+    pub fn new(settings: FormatSettings) -> Self {
+        let decimal = match settings.decimal {
+            Separator::Locale => locale_decimal(system_locale().as_deref()),
+            Separator::Point => '.',
+            Separator::Comma => ',',
+        };
+        Self { decimal, size_units: settings.size_units }
+    }
+
+    /// `value` to `decimals` places.
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let text = format!("{:.*}", decimals, value);
+        if self.decimal == '.' {
+            text
+        } else {
+            text.replace('.', &self.decimal.to_string())
+        }
+    }
+
+    /// A duration to a tenth of a second, like "5.7s", "2m 5.7s", or "1h 2m 5.7s".
+    pub fn duration(&self, seconds: f32) -> String {
+        // Rounded before splitting, so 59.97s is 1m 0.0s rather than 60.0s
+        let tenths = (seconds.max(0.0) as f64 * 10.0).round() as u64;
+        let (hours, minutes) = (tenths / 36000, tenths / 600 % 60);
+        let seconds = self.number((tenths % 600) as f64 / 10.0, 1);
+        if hours > 0 {
+            format!("{}h {}m {}s", hours, minutes, seconds)
+        } else if minutes > 0 {
+            format!("{}m {}s", minutes, seconds)
+        } else {
+            format!("{}s", seconds)
+        }
+    }
+
+    pub fn file_size(&self, bytes: u64) -> String {
+        let (step, units) = match self.size_units {
+            SizeUnits::Binary => (1024.0, ["B", "KiB", "MiB", "GiB", "TiB"]),
+            SizeUnits::Si => (1000.0, ["B", "kB", "MB", "GB", "TB"]),
+        };
+        let mut size = bytes as f64;
+        let mut unit_index = 0;
+        while size >= step && unit_index < units.len() - 1 {
+            size /= step;
+            unit_index += 1;
+        }
+
+        if unit_index == 0 {
+            format!("{} {}", bytes, units[unit_index])
+        } else {
+            format!("{} {}", self.number(size, 1), units[unit_index])
+        }
+    }
+
+    pub fn sample_rate(&self, sample_rate: f32) -> String {
+        if sample_rate >= 1000.0 {
+            format!("{}kHz", self.number(sample_rate as f64 / 1000.0, 1))
+        } else {
+            format!("{}Hz", sample_rate as i32)
+        }
+    }
+}
+
+/// Use `format` for everything written from now on.
+pub fn set(format: Format) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = format;
+}
+
+pub fn current() -> Format {
+    *CURRENT.read().unwrap_or_else(|e| e.into_inner())
+}
+
+/// `value` to `decimals` places, in the current format.
+pub fn number(value: f64, decimals: usize) -> String {
+    current().number(value, decimals)
+}
+
+pub fn duration(seconds: f32) -> String {
+    current().duration(seconds)
+}
+
+pub fn file_size(bytes: u64) -> String {
+    current().file_size(bytes)
+}
+
+pub fn sample_rate(sample_rate: f32) -> String {
+    current().sample_rate(sample_rate)
+}
+
+// The locale numbers are written in, like "de_DE.UTF-8", by the variables POSIX looks it up in
+fn system_locale() -> Option<String> {
+    ["LC_ALL", "LC_NUMERIC", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+}
+
+fn locale_decimal(locale: Option<&str>) -> char {
+    let Some(locale) = locale else {
+        return '.';
+    };
+    // language_TERRITORY.codeset@modifier
+    let name = locale.split(['.', '@']).next().unwrap_or_default();
+    let mut parts = name.split(['_', '-']);
+    let language = parts.next().unwrap_or_default().to_ascii_lowercase();
+    let territory = parts.next().unwrap_or_default().to_ascii_uppercase();
+    // Switzerland writes German and Italian numbers with a point
+    if COMMA_LANGUAGES.contains(&language.as_str()) && territory != "CH" {
+        ','
+    } else {
+        '.'
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers_follow_the_format() {
+        let plain = Format::PLAIN;
+        assert_eq!(plain.duration(30.5), "30.5s");
+        assert_eq!(plain.duration(90.0), "1m 30.0s");
+        assert_eq!(plain.duration(125.7), "2m 5.7s");
+        assert_eq!(plain.duration(59.97), "1m 0.0s");
+        assert_eq!(plain.duration(3725.5), "1h 2m 5.5s");
+        assert_eq!(plain.file_size(512), "512 B");
+        assert_eq!(plain.file_size(1536), "1.5 KiB");
+        assert_eq!(plain.file_size(1048576), "1.0 MiB");
+        assert_eq!(plain.sample_rate(44100.0), "44.1kHz");
+        assert_eq!(plain.sample_rate(48000.0), "48.0kHz");
+        assert_eq!(plain.sample_rate(800.0), "800Hz");
+
+        let settings = FormatSettings { decimal: Separator::Comma, size_units: SizeUnits::Si };
+        let german = Format::new(settings);
+        assert_eq!(german.duration(125.7), "2m 5,7s");
+        assert_eq!(german.file_size(1500), "1,5 kB");
+        assert_eq!(german.sample_rate(44100.0), "44,1kHz");
+        assert_eq!(german.number(-0.25, 2), "-0,25");
+
+        assert_eq!(locale_decimal(Some("de_DE.UTF-8")), ',');
+        assert_eq!(locale_decimal(Some("fr_FR@euro")), ',');
+        assert_eq!(locale_decimal(Some("de_CH.UTF-8")), '.');
+        assert_eq!(locale_decimal(Some("en_US.UTF-8")), '.');
+        assert_eq!(locale_decimal(Some("C")), '.');
+        assert_eq!(locale_decimal(None), '.');
+    }
+}
@@ -0,0 +1,173 @@
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::align;
+use crate::classifier;
+
+// Grains the correction curve is averaged over on each side at the slowest transition speed
+const MAX_SMOOTHING: usize = 8;
+
+/// Target pitches given as plain frequencies in Hz, for tunings that no scale describes,
+/// like gamelan or a detuned synth stack. Nothing is repeated in other octaves, so every
+/// pitch to correct to has to be listed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrequencyList {
+    // Ascending
+    frequencies: Vec<f32>,
+}
+
+impl FromStr for FrequencyList {
+    type Err = String;
+
+    /// Frequencies separated by commas, spaces, or lines, with `#` starting a comment that
+    /// runs to the end of the line.
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut frequencies = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("");
+            for value in line.split([',', ' ', '\t']).filter(|value| !value.is_empty()) {
+                let frequency: f32 =
+                    value.parse().map_err(|_| format!("'{}' isn't a frequency in Hz", value))?;
+                if !frequency.is_finite() || frequency <= 0.0 {
+                    return Err(format!("{} Hz isn't a pitch", value));
+                }
+                frequencies.push(frequency);
+            }
+        }
+        if frequencies.is_empty() {
+            return Err("No frequencies given".to_string());
+        }
+        frequencies.sort_by(|a, b| a.total_cmp(b));
+        frequencies.dedup();
+        Ok(Self { frequencies })
+    }
+}
+
+impl FrequencyList {
+    /// `argument` as a list of frequencies, or else as the text file it names.
+    pub fn from_arg(argument: &str) -> Result<Self, String> {
+        if let Ok(list) = argument.parse() {
+            return Ok(list);
+        }
+        let path = Path::new(argument);
+        let text = fs::read_to_string(path).map_err(|e| {
+            format!("'{}' is neither a list of frequencies nor a readable file: {}", argument, e)
+        })?;
+        text.parse().map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frequencies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frequencies.is_empty()
+    }
+
+    /// The listed frequency closest to `frequency` in pitch.
+    pub fn nearest(&self, frequency: f32) -> f32 {
+        let distance = |target: f32| (target / frequency).log2().abs();
+        self.frequencies
+            .iter()
+            .copied()
+            .min_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or(frequency)
+    }
+}
+
+/// What was done to the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TuningReport {
+    pub corrected_secs: f32,
+    // Mean size of the corrections that were made, before `strength` was applied
+    pub mean_cents: f32,
+}
+
+/// Pull the pitch of mono `samples` at `sample_rate` onto the nearest of `targets`, by
+/// `strength` (0.0 to 1.0) of the way. A `transition` speed of 1.0 follows every change of
+/// the sung pitch at once, slower ones smooth the correction over up to about 100ms. The
+/// result is as long as `samples`.
+pub fn tune_to_frequencies(
+    samples: &[f32],
+    sample_rate: u32,
+    targets: &FrequencyList,
+    strength: f32,
+    transition: f32,
+) -> (Vec<f32>, TuningReport) {
+    let rate = sample_rate as f32;
+    let mut report = TuningReport::default();
+    if samples.is_empty() {
+        return (Vec::new(), report);
+    }
+
+    let track = classifier::pitch_track(samples, rate);
+    let hop = align::grain_hop(rate);
+    let cents: Vec<f32> = (0..align::grain_count(samples.len(), rate))
+        .map(|k| {
+            let sung = track.at((k * hop) as f32 / rate).and_then(|frame| frame.f0);
+            sung.map_or(0.0, |sung| 1200.0 * (targets.nearest(sung) / sung).log2())
+        })
+        .collect();
+    let corrected: Vec<f32> = cents.iter().copied().filter(|&c| c != 0.0).collect();
+    report.corrected_secs = (corrected.len() * hop) as f32 / rate;
+    if !corrected.is_empty() {
+        report.mean_cents = corrected.iter().map(|c| c.abs()).sum::<f32>() / corrected.len() as f32;
+    }
+
+    let smoothing = ((1.0 - transition.clamp(0.0, 1.0)) * MAX_SMOOTHING as f32).round() as usize;
+    let cents: Vec<f32> =
+        align::average(&cents, smoothing).iter().map(|c| c * strength.clamp(0.0, 1.0)).collect();
+    (align::resynthesize(samples, rate, &cents, |_| 0.0), report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+
+    const RATE: u32 = 44_100;
+
+    fn tone(frequency: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * RATE as f32) as usize)
+            .map(|i| 0.5 * (2.0 * PI * frequency * i as f32 / RATE as f32).sin())
+            .collect()
+    }
+
+    fn frequency(samples: &[f32]) -> f32 {
+        let crossings = samples.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count();
+        crossings as f32 / 2.0 / (samples.len() as f32 / RATE as f32)
+    }
+
+    #[test]
+    fn test_frequency_list_parsing() {
+        let list: FrequencyList =
+            "# slendro\n 282.5, 325.1\n373.8 430.0 # high\n\n325.1".parse().unwrap();
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.nearest(300.0), 282.5);
+        assert_eq!(list.nearest(310.0), 325.1);
+        assert_eq!(list.nearest(2000.0), 430.0);
+
+        assert!("".parse::<FrequencyList>().is_err());
+        assert!("# nothing".parse::<FrequencyList>().is_err());
+        assert!("220, -1".parse::<FrequencyList>().is_err());
+        assert!("220, A".parse::<FrequencyList>().is_err());
+        assert!(FrequencyList::from_arg("no-such-tuning.txt").is_err());
+    }
+
+    #[test]
+    fn test_tone_is_pulled_onto_the_nearest_frequency() {
+        // 227Hz is 50 cents off any equal-tempered note
+        let targets: FrequencyList = "180, 227".parse().unwrap();
+        let sung = tone(220.0, 1.0);
+        let middle = (0.3 * RATE as f32) as usize..(0.7 * RATE as f32) as usize;
+
+        let (tuned, report) = tune_to_frequencies(&sung, RATE, &targets, 1.0, 1.0);
+        assert_eq!(tuned.len(), sung.len());
+        assert!((frequency(&tuned[middle.clone()]) - 227.0).abs() < 1.5);
+        assert!((report.mean_cents - 54.0).abs() < 5.0, "{:?}", report);
+
+        let (half, _) = tune_to_frequencies(&sung, RATE, &targets, 0.5, 1.0);
+        assert!((frequency(&half[middle]) - 223.5).abs() < 1.5);
+    }
+}
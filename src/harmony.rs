@@ -0,0 +1,156 @@
+use std::fmt;
+use std::str::FromStr;
+use synthphone_vocals::MusicalSettings;
+
+use crate::scale::Scale;
+
+// Degrees a voice can be given, up to two octaves from the lead
+const MAX_DEGREE: i32 = 15;
+// Steps of a major or minor scale that make up an octave
+const DIATONIC_OCTAVE: i32 = 7;
+// Lowest and highest octave reference of the library
+const OCTAVES: (i32, i32) = (0, 4);
+
+/// A diatonic interval a harmony voice sings from the lead, as a degree counted in the
+/// scale the way musicians do: 3 is a third above, 5 a fifth, 8 an octave, and negative
+/// degrees are below. In a major or minor key a third is three or four semitones,
+/// depending on the note.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interval(i32);
+
+impl Interval {
+    pub const THIRD: Self = Self(3);
+    pub const FIFTH: Self = Self(5);
+    pub const OCTAVE: Self = Self(8);
+
+    /// The degree as it is written, like 3 or -8.
+    pub fn degree(self) -> i32 {
+        self.0
+    }
+
+    /// The note `self` away from MIDI note `lead` in `scale`. Whole octaves are always
+    /// twelve semitones, whatever the number of notes in the scale.
+    pub fn note_from(self, lead: i32, scale: &Scale) -> i32 {
+        if scale.is_empty() {
+            return lead;
+        }
+        let direction = self.0.signum();
+        let steps = self.0.abs() - 1;
+        let mut note = lead;
+        for _ in 0..steps % DIATONIC_OCTAVE {
+            note += direction;
+            while !scale.notes[note.rem_euclid(12) as usize] {
+                note += direction;
+            }
+        }
+        note + direction * 12 * (steps / DIATONIC_OCTAVE)
+    }
+
+    /// The lead's `musical` settings changed to sing this interval from `lead_note`, the
+    /// MIDI note the lead is corrected to. The note is forced the way a scale target
+    /// forces it, with the octave reference moved by the octaves the interval crosses.
+    pub fn settings(
+        self,
+        lead_note: Option<i32>,
+        scale: &Scale,
+        musical: &MusicalSettings,
+    ) -> MusicalSettings {
+        // A locked note is the lead's target throughout
+        let lead_note = lead_note.or((musical.note > 0).then(|| 59 + musical.note));
+        let (note, octave) = match lead_note {
+            Some(lead_note) => {
+                let note = self.note_from(lead_note, scale);
+                let octaves = note.div_euclid(12) - lead_note.div_euclid(12);
+                (note.rem_euclid(12) + 1, musical.octave + octaves)
+            }
+            // Nothing to harmonize until the lead has a note
+            None => (musical.note, musical.octave),
+        };
+        MusicalSettings {
+            key: musical.key,
+            note,
+            octave: octave.clamp(OCTAVES.0, OCTAVES.1),
+            formant: musical.formant,
+        }
+    }
+
+    /// Short name for files, like "3rd" or "octave_below".
+    pub fn file_name(self) -> String {
+        let name = self.degree_name();
+        if self.0 < 0 {
+            format!("{}_below", name)
+        } else {
+            name
+        }
+    }
+
+    fn degree_name(self) -> String {
+        match self.0.abs() {
+            8 => "octave".to_string(),
+            15 => "2_octaves".to_string(),
+            degree => {
+                let suffix = match degree % 10 {
+                    2 if degree != 12 => "nd",
+                    3 if degree != 13 => "rd",
+                    _ => "th",
+                };
+                format!("{}{}", degree, suffix)
+            }
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(degree: &str) -> Result<Self, String> {
+        match degree.trim().parse::<i32>() {
+            Ok(degree) if (2..=MAX_DEGREE).contains(&degree.abs()) => Ok(Self(degree)),
+            _ => Err(format!(
+                "Invalid interval '{}': expected a degree from 2 to {}, like 3 for a third \
+                 above or -8 for an octave below",
+                degree, MAX_DEGREE
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let direction = if self.0 < 0 { "below" } else { "above" };
+        write!(f, "{} {}", self.degree_name().replace('_', " "), direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_voices_follow_the_scale() {
+        let c_major = Scale::from_key(0);
+        // Thirds are major or minor depending on the note
+        assert_eq!(Interval::THIRD.note_from(60, &c_major), 64);
+        assert_eq!(Interval::THIRD.note_from(62, &c_major), 65);
+        assert_eq!(Interval::FIFTH.note_from(71, &c_major), 77); // B up to F
+        assert_eq!(Interval::OCTAVE.note_from(60, &c_major), 72);
+        assert_eq!("-3".parse::<Interval>().unwrap().note_from(60, &c_major), 57);
+        assert_eq!("10".parse::<Interval>().unwrap().note_from(60, &c_major), 76);
+        // A note outside the scale counts from where it is
+        assert_eq!(Interval::THIRD.note_from(61, &c_major), 64);
+
+        let musical = MusicalSettings { key: 0, note: 0, octave: 2, formant: 0 };
+        let settings = Interval::FIFTH.settings(Some(67), &c_major, &musical);
+        assert_eq!((settings.note, settings.octave), (3, 3)); // G up to D, over the octave
+        let below = "-8".parse::<Interval>().unwrap().settings(Some(60), &c_major, &musical);
+        assert_eq!((below.note, below.octave), (1, 1));
+        assert_eq!(Interval::THIRD.settings(None, &c_major, &musical).note, 0);
+        let locked = MusicalSettings { note: 10, ..musical }; // A
+        assert_eq!(Interval::THIRD.settings(None, &c_major, &locked).note, 1);
+
+        assert!("1".parse::<Interval>().is_err());
+        assert!("third".parse::<Interval>().is_err());
+        assert_eq!(Interval::THIRD.to_string(), "3rd above");
+        assert_eq!("-8".parse::<Interval>().unwrap().file_name(), "octave_below");
+    }
+}
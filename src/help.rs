@@ -0,0 +1,183 @@
+use std::ops::ControlFlow;
+use std::path::Path;
+
+use crate::playback::Tracks;
+use crate::timeline::ClipSettings;
+use crate::{decoder, pipeline};
+
+// Length of the excerpt "hear the difference" renders
+const EXCERPT_SECS: f32 = 6.0;
+// How far into the input the excerpt may start, looking for the voice
+const SEARCH_SECS: f32 = 60.0;
+// Peak level the voice is taken to start at, so the excerpt doesn't open on a silent intro
+const START_LEVEL: f32 = 0.05;
+// Kept before that peak so the excerpt doesn't cut into the first note
+const LEAD_IN_SECS: f32 = 0.2;
+
+/// A setting with an explanation in the app.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Topic {
+    Key,
+    NoteMode,
+    Octave,
+    A4,
+    Formant,
+    Strength,
+    Transition,
+    CorrectFry,
+    Resample,
+}
+
+/// Two values of a setting far enough apart to hear what it does.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contrast {
+    pub low: f32,
+    pub high: f32,
+    pub low_label: &'static str,
+    pub high_label: &'static str,
+}
+
+impl Topic {
+    pub fn explanation(self) -> &'static str {
+        match self {
+            Topic::Key => {
+                "The notes the take is corrected to. Each moment is pulled to the nearest note \
+                 of the key, so a wrong key pulls notes the singer meant onto a neighbouring \
+                 one. Detect Key suggests the keys that fit the take best."
+            }
+            Topic::NoteMode => {
+                "Auto follows the melody, correcting each moment to the nearest note of the \
+                 key. A manual note ignores the melody and locks everything to that one note, \
+                 in the octave below, for drones and monotone effects."
+            }
+            Topic::Octave => {
+                "The octave a manual note is locked in, 0 the lowest. 2 suits most voices. \
+                 Only used when Note Mode is manual."
+            }
+            Topic::A4 => {
+                "The pitch of the A above middle C, which every note is measured from. 440 Hz \
+                 is the modern standard. A take recorded with a band or orchestra tuned \
+                 elsewhere, like 442 Hz or 415 Hz, is pulled a little off its accompaniment \
+                 unless this matches it."
+            }
+            Topic::Formant => {
+                "Moves the resonances that tell a large voice from a small one, without \
+                 changing the notes. Negative sounds deeper and bigger, positive brighter and \
+                 smaller; 0 keeps the singer's own character."
+            }
+            Topic::Strength => {
+                "How far each note is pulled toward its target: 0 leaves the pitch alone, 1 \
+                 lands exactly on the note. Natural correction sits around 0.4 to 0.8; 1.0 \
+                 with an instant transition is the hard-tuned effect."
+            }
+            Topic::Transition => {
+                "How quickly the correction follows when the singer moves to a new note. Low \
+                 values glide, keeping slides and vibrato; 1.0 snaps to each note at once, the \
+                 robotic stepping of the classic effect."
+            }
+            Topic::CorrectFry => {
+                "Vocal fry is the creak at the ends of phrases. Its pitch is irregular, so it \
+                 normally passes through untouched; correcting it pulls it up into a croak."
+            }
+            Topic::Resample => {
+                "Pitch detection is tuned for 44.1 and 48 kHz. Voice memos at 8 or 16 kHz and \
+                 96 kHz sessions are processed at the nearest of those and converted back, \
+                 which finds low notes more reliably."
+            }
+        }
+    }
+
+    /// The low and high value "hear the difference" renders, None for settings that have
+    /// nothing to hear on the loaded take by themselves.
+    pub fn contrast(self) -> Option<Contrast> {
+        let contrast =
+            |low, high, low_label, high_label| Some(Contrast { low, high, low_label, high_label });
+        match self {
+            Topic::A4 => contrast(415.0, 466.0, "415 Hz", "466 Hz"),
+            Topic::Formant => contrast(-6.0, 6.0, "-6 (deeper)", "+6 (smaller)"),
+            Topic::Strength => contrast(0.3, 1.0, "0.3 (gentle)", "1.0 (full)"),
+            Topic::Transition => contrast(0.05, 1.0, "0.05 (gliding)", "1.0 (instant)"),
+            _ => None,
+        }
+    }
+
+    /// `settings` with this setting at `value`.
+    pub fn apply(self, settings: ClipSettings, value: f32) -> ClipSettings {
+        match self {
+            Topic::A4 => ClipSettings { a4: value, ..settings },
+            Topic::Formant => ClipSettings { formant: value.round() as i32, ..settings },
+            Topic::Strength => ClipSettings { strength: value, ..settings },
+            Topic::Transition => ClipSettings { transition: value, ..settings },
+            _ => settings,
+        }
+    }
+}
+
+/// Render a few seconds of `input` where the voice starts with `topic` at both ends of its
+/// contrast and everything else as `settings`. The low render is the original track of the
+/// result and the high one the processed, for the A/B player.
+pub fn render_contrast(
+    input: &Path,
+    topic: Topic,
+    settings: ClipSettings,
+) -> Result<Tracks, String> {
+    let contrast = topic.contrast().ok_or("Nothing to hear for this setting")?;
+    let sample_rate = decoder::probe(input)?.sample_rate;
+    let (samples, _) =
+        decoder::read_mono(input, ((SEARCH_SECS + EXCERPT_SECS) * sample_rate as f32) as usize)?;
+    let excerpt = excerpt(&samples, sample_rate);
+    if excerpt.is_empty() {
+        return Err("The file has no audio".to_string());
+    }
+
+    let render = |value| {
+        let settings = topic.apply(settings, value).render_settings(sample_rate);
+        pipeline::process_samples(excerpt, &settings, |_| ControlFlow::Continue(()))
+            .map(|processed| processed.samples)
+    };
+    Ok(Tracks { original: render(contrast.low)?, processed: render(contrast.high)?, sample_rate })
+}
+
+// The excerpt of `samples` to render, from just before the first loud sample
+fn excerpt(samples: &[f32], sample_rate: u32) -> &[f32] {
+    let to_samples = |secs: f32| (secs * sample_rate as f32) as usize;
+    let first_loud = samples.iter().position(|sample| sample.abs() >= START_LEVEL).unwrap_or(0);
+    let start = first_loud.saturating_sub(to_samples(LEAD_IN_SECS));
+    let end = (start + to_samples(EXCERPT_SECS)).min(samples.len());
+    &samples[start..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contrasts_change_only_their_setting() {
+        let settings = ClipSettings {
+            key: 0,
+            note: 0,
+            octave: 2,
+            formant: 0,
+            strength: 0.8,
+            transition: 0.1,
+            a4: pipeline::STANDARD_A4,
+        };
+        for topic in [Topic::A4, Topic::Formant, Topic::Strength, Topic::Transition] {
+            let contrast = topic.contrast().unwrap();
+            let (low, high) =
+                (topic.apply(settings, contrast.low), topic.apply(settings, contrast.high));
+            assert_ne!(low, high, "{:?}", topic);
+            assert!(pipeline::A4_RANGE.contains(&low.a4) && pipeline::A4_RANGE.contains(&high.a4));
+        }
+        assert_eq!(Topic::Key.contrast(), None);
+        assert_eq!(Topic::Key.apply(settings, 5.0), settings);
+
+        // The excerpt starts just before the voice rather than on the silent intro
+        let mut samples = vec![0.0; 1000];
+        samples.extend(vec![0.5; 1000]);
+        let excerpt = excerpt(&samples, 100);
+        assert_eq!(excerpt.len(), 600);
+        assert_eq!(excerpt[..20], [0.0; 20]);
+        assert_eq!(excerpt[20], 0.5);
+    }
+}
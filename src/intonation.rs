@@ -0,0 +1,142 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::classifier::{self, PitchTrack};
+use crate::labels;
+use crate::scale::Scale;
+
+/// Furthest a pitched frame may be from the nearest note of the scale and still count as
+/// in tune, in cents.
+pub const IN_TUNE_CENTS: f32 = 20.0;
+
+/// How the pitch of one analysis frame sits against the scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrameIntonation {
+    // Center of the frame
+    pub secs: f32,
+    // None for silent or unvoiced frames, and then so are the rest
+    pub f0: Option<f32>,
+    // MIDI note of the scale closest to the pitch
+    pub note: Option<i32>,
+    // Above the note when positive, below it when negative
+    pub cents: Option<f32>,
+}
+
+/// How far a take is from the notes of a scale, frame by frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntonationReport {
+    pub frames: Vec<FrameIntonation>,
+}
+
+impl IntonationReport {
+    /// Measure every frame of `track` against the nearest note of `scale`.
+    pub fn measure(track: &PitchTrack, scale: &Scale) -> Self {
+        let frames = track
+            .frames
+            .iter()
+            .enumerate()
+            .map(|(index, frame)| {
+                let secs = track.offset_secs + index as f32 * track.hop_secs;
+                let pitch = frame.f0.and_then(|f0| {
+                    let note = classifier::note_number(f0);
+                    let nearest = scale.nearest(note)?;
+                    Some((f0, nearest, (note - nearest as f32) * 100.0))
+                });
+                FrameIntonation {
+                    secs,
+                    f0: pitch.map(|(f0, _, _)| f0),
+                    note: pitch.map(|(_, note, _)| note),
+                    cents: pitch.map(|(_, _, cents)| cents),
+                }
+            })
+            .collect();
+        Self { frames }
+    }
+
+    // Deviations of the pitched frames in cents, without their sign
+    fn deviations(&self) -> Vec<f32> {
+        self.frames.iter().filter_map(|frame| frame.cents).map(f32::abs).collect()
+    }
+
+    /// Median distance of the pitched frames from their notes in cents, None without any.
+    pub fn median_deviation(&self) -> Option<f32> {
+        let mut deviations = self.deviations();
+        if deviations.is_empty() {
+            return None;
+        }
+        deviations.sort_by(f32::total_cmp);
+        // The middle one, or halfway between the middle two
+        let len = deviations.len();
+        Some((deviations[(len - 1) / 2] + deviations[len / 2]) / 2.0)
+    }
+
+    /// Share of the pitched frames within `IN_TUNE_CENTS` of their notes, None without any.
+    pub fn in_tune_share(&self) -> Option<f32> {
+        let deviations = self.deviations();
+        let in_tune = deviations.iter().filter(|&&cents| cents <= IN_TUNE_CENTS).count();
+        (!deviations.is_empty()).then(|| in_tune as f32 / deviations.len() as f32)
+    }
+
+    /// The frames as CSV, one line each with its time, pitch, nearest note, and deviation,
+    /// the last three empty for unpitched frames.
+    pub fn to_csv(&self) -> String {
+        let mut text = "time_secs,f0_hz,note,cents\n".to_string();
+        for frame in &self.frames {
+            let _ = write!(text, "{:.3},", frame.secs);
+            if let (Some(f0), Some(note), Some(cents)) = (frame.f0, frame.note, frame.cents) {
+                let _ = write!(text, "{:.2},{},{:.1}", f0, labels::note_name(note), cents);
+            } else {
+                text.push_str(",,");
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn write_csv(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_csv())
+            .map_err(|e| format!("Failed to write report {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classifier::PitchFrame;
+
+    #[test]
+    fn test_frames_are_measured_against_the_scale() {
+        let frame = |f0: Option<f32>| PitchFrame { rms: 0.1, harmonicity: 0.9, f0 };
+        let cents_above_a4 = |cents: f32| 440.0 * 2f32.powf(cents / 1200.0);
+        let track = PitchTrack {
+            frames: vec![
+                frame(Some(440.0)),
+                frame(Some(cents_above_a4(30.0))),
+                frame(None),
+                // Just below A#4, which C major hasn't got, so nearest to A4
+                frame(Some(cents_above_a4(90.0))),
+            ],
+            hop_secs: 0.01,
+            offset_secs: 0.005,
+        };
+        let report = IntonationReport::measure(&track, &Scale::from_key(0));
+        assert_eq!(report.frames[0].note, Some(69));
+        assert!(report.frames[0].cents.unwrap().abs() < 0.1);
+        assert!((report.frames[1].cents.unwrap() - 30.0).abs() < 0.1);
+        assert_eq!(report.frames[2].cents, None);
+        assert_eq!(report.frames[3].note, Some(69));
+        assert!((report.frames[3].cents.unwrap() - 90.0).abs() < 0.1);
+
+        assert!((report.median_deviation().unwrap() - 30.0).abs() < 0.1);
+        assert_eq!(report.in_tune_share(), Some(1.0 / 3.0));
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "time_secs,f0_hz,note,cents");
+        assert_eq!(lines[1], "0.005,440.00,A4,0.0");
+        assert_eq!(lines[3], "0.025,,,");
+
+        let silent = PitchTrack { frames: vec![frame(None)], hop_secs: 0.01, offset_secs: 0.0 };
+        let report = IntonationReport::measure(&silent, &Scale::from_key(0));
+        assert_eq!((report.median_deviation(), report.in_tune_share()), (None, None));
+    }
+}
@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::cli::ProcessParams;
+use crate::clock::unix_now;
+
+const TICKET_EXTENSION: &str = "json";
+
+/// Lifecycle of a job ticket. Each state has its own subdirectory in the spool.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    #[default]
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+impl JobState {
+    pub const ALL: [JobState; 4] =
+        [JobState::Pending, JobState::Running, JobState::Done, JobState::Failed];
+
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            JobState::Pending => "pending",
+            JobState::Running => "running",
+            JobState::Done => "done",
+            JobState::Failed => "failed",
+        }
+    }
+}
+
+/// A unit of work. External systems enqueue jobs by writing a ticket into `pending/`;
+/// only `input` and `output` are required, everything else has a default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobTicket {
+    // Always the ticket's file name, whatever the file itself says
+    #[serde(default)]
+    pub id: String,
+    pub input: PathBuf,
+    pub output: PathBuf,
+    #[serde(default)]
+    pub params: ProcessParams,
+    #[serde(default)]
+    pub state: JobState,
+    #[serde(default)]
+    pub attempts: u32,
+    // Seconds since the Unix epoch
+    #[serde(default)]
+    pub created_at: Option<u64>,
+    #[serde(default)]
+    pub started_at: Option<u64>,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl JobTicket {
+    pub fn new(id: impl Into<String>, input: PathBuf, output: PathBuf) -> Self {
+        Self {
+            id: id.into(),
+            input,
+            output,
+            params: ProcessParams::default(),
+            state: JobState::Pending,
+            attempts: 0,
+            created_at: Some(unix_now()),
+            started_at: None,
+            finished_at: None,
+            error: None,
+        }
+    }
+}
+
+/// Spool directory with one subdirectory per job state. Tickets move between them with
+/// renames, so a ticket is only ever claimed by one worker.
+#[derive(Debug, Clone)]
+pub struct Spool {
+    root: PathBuf,
+}
+
+impl Spool {
+    pub fn open(root: &Path) -> io::Result<Self> {
+        let spool = Self { root: root.to_path_buf() };
+        for state in JobState::ALL {
+            std::fs::create_dir_all(spool.state_dir(state))?;
+        }
+        Ok(spool)
+    }
+
+    pub fn state_dir(&self, state: JobState) -> PathBuf {
+        self.root.join(state.dir_name())
+    }
+
+    fn ticket_path(&self, state: JobState, id: &str) -> PathBuf {
+        self.state_dir(state).join(format!("{}.{}", id, TICKET_EXTENSION))
+    }
+
+    /// Write a new ticket into `pending/`.
+    pub fn enqueue(&self, ticket: &JobTicket) -> io::Result<PathBuf> {
+        let path = self.ticket_path(JobState::Pending, &ticket.id);
+        write_ticket(&path, &JobTicket { state: JobState::Pending, ..ticket.clone() })?;
+        Ok(path)
+    }
+
+    /// Pending tickets, oldest file name first.
+    pub fn pending(&self) -> io::Result<Vec<PathBuf>> {
+        let mut tickets: Vec<PathBuf> = std::fs::read_dir(self.state_dir(JobState::Pending))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == TICKET_EXTENSION))
+            .collect();
+        tickets.sort();
+        Ok(tickets)
+    }
+
+    /// Move a pending ticket to `running/` and mark it started. Returns `None` when the ticket
+    /// is gone (claimed by someone else). Unreadable tickets are moved to `failed/`.
+    pub fn claim(&self, pending_path: &Path) -> io::Result<Option<JobTicket>> {
+        let Some(id) = pending_path.file_stem().map(|stem| stem.to_string_lossy().into_owned())
+        else {
+            return Ok(None);
+        };
+
+        let running_path = self.ticket_path(JobState::Running, &id);
+        match std::fs::rename(pending_path, &running_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let contents = std::fs::read_to_string(&running_path)?;
+        let mut ticket: JobTicket = match serde_json::from_str(&contents) {
+            Ok(ticket) => ticket,
+            Err(e) => {
+                let failed_path = self.ticket_path(JobState::Failed, &id);
+                std::fs::rename(&running_path, &failed_path)?;
+                log::warn!("Invalid job ticket {}: {}", failed_path.display(), e);
+                return Ok(None);
+            }
+        };
+
+        ticket.id = id;
+        ticket.state = JobState::Running;
+        ticket.attempts += 1;
+        ticket.started_at = Some(unix_now());
+        ticket.finished_at = None;
+        ticket.error = None;
+        write_ticket(&running_path, &ticket)?;
+        Ok(Some(ticket))
+    }
+
+    /// Record the outcome of a running ticket and move it to `done/` or `failed/`.
+    pub fn finish(&self, mut ticket: JobTicket, result: Result<(), String>) -> io::Result<()> {
+        ticket.finished_at = Some(unix_now());
+        (ticket.state, ticket.error) = match result {
+            Ok(()) => (JobState::Done, None),
+            Err(e) => (JobState::Failed, Some(e)),
+        };
+
+        write_ticket(&self.ticket_path(ticket.state, &ticket.id), &ticket)?;
+        match std::fs::remove_file(self.ticket_path(JobState::Running, &ticket.id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// Move tickets left in `running/` by a crashed run back to `pending/`. Only call this
+    /// when no other worker is using the spool.
+    pub fn recover_interrupted(&self) -> io::Result<usize> {
+        let mut recovered = 0;
+        for entry in std::fs::read_dir(self.state_dir(JobState::Running))? {
+            let path = entry?.path();
+            if let Some(name) = path.file_name() {
+                std::fs::rename(&path, self.state_dir(JobState::Pending).join(name))?;
+                recovered += 1;
+            }
+        }
+        Ok(recovered)
+    }
+}
+
+// Write then rename so watchers never see a half-written ticket
+fn write_ticket(path: &Path, ticket: &JobTicket) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(ticket).map_err(io::Error::other)?;
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, json)?;
+    std::fs::rename(&temp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(spool: &Spool, state: JobState, id: &str) -> Option<JobTicket> {
+        let contents = std::fs::read_to_string(spool.ticket_path(state, id)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    fn temp_spool(name: &str) -> (PathBuf, Spool) {
+        let dir = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_dir_all(&dir);
+        let spool = Spool::open(&dir).unwrap();
+        (dir, spool)
+    }
+
+    #[test]
+    fn test_job_lifecycle() {
+        let (dir, spool) = temp_spool("autotune-spool-lifecycle");
+        let ticket = JobTicket::new("take1", "in.wav".into(), "out.wav".into());
+        spool.enqueue(&ticket).unwrap();
+
+        let pending = spool.pending().unwrap();
+        assert_eq!(pending.len(), 1);
+
+        let claimed = spool.claim(&pending[0]).unwrap().unwrap();
+        assert_eq!(claimed.state, JobState::Running);
+        assert_eq!(claimed.attempts, 1);
+        assert!(spool.claim(&pending[0]).unwrap().is_none());
+
+        spool.finish(claimed, Err("boom".to_string())).unwrap();
+        let failed = load(&spool, JobState::Failed, "take1").unwrap();
+        assert_eq!(failed.state, JobState::Failed);
+        assert_eq!(failed.error.as_deref(), Some("boom"));
+        assert!(load(&spool, JobState::Running, "take1").is_none());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_minimal_ticket_from_external_system() {
+        let (dir, spool) = temp_spool("autotune-spool-minimal");
+        let path = spool.state_dir(JobState::Pending).join("vocal-42.json");
+        std::fs::write(&path, r#"{"input": "a.wav", "output": "b.wav", "params": {"key": 5}}"#)
+            .unwrap();
+
+        let ticket = spool.claim(&path).unwrap().unwrap();
+        assert_eq!(ticket.id, "vocal-42");
+        assert_eq!(ticket.params.key, 5);
+        assert_eq!(ticket.params.strength, ProcessParams::default().strength);
+
+        std::fs::write(spool.state_dir(JobState::Pending).join("bad.json"), "not json").unwrap();
+        let bad = spool.pending().unwrap().remove(0);
+        assert!(spool.claim(&bad).unwrap().is_none());
+        assert!(spool.state_dir(JobState::Failed).join("bad.json").exists());
+
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
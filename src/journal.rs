@@ -0,0 +1,162 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::clock::UtcTime;
+use crate::paths;
+
+pub const EXTENSION: &str = "txt";
+// Changes to one setting closer together than this are one edit, like a slider drag
+const EDIT_GAP: Duration = Duration::from_secs(2);
+
+/// Everything done in this session (loads, settings changes, renders) with the time it
+/// was done, for studios that need an audit trail of what happened to a client's vocal and
+/// when. Exported as plain text.
+pub struct Journal {
+    started: UtcTime,
+    entries: Vec<Entry>,
+    // Settings as last recorded, by name
+    settings: Option<Map<String, Value>>,
+}
+
+struct Entry {
+    time: UtcTime,
+    message: String,
+    // The setting this entry changed, while later changes can still be folded into it
+    edit: Option<Edit>,
+}
+
+struct Edit {
+    setting: String,
+    from: String,
+    at: Instant,
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self { started: UtcTime::now(), entries: Vec::new(), settings: None }
+    }
+}
+
+impl Journal {
+    pub fn record(&mut self, message: impl Into<String>) {
+        self.entries.push(Entry { time: UtcTime::now(), message: message.into(), edit: None });
+    }
+
+    /// Compare `settings` with the last ones recorded and journal each field that changed,
+    /// except the `ignored` ones. The first call records them all as the starting point.
+    pub fn record_settings(&mut self, settings: &impl Serialize, ignored: &[&str]) {
+        // Through JSON text rather than a `Value` so f32s print as written, not widened
+        let Some(mut current) = serde_json::to_string(settings)
+            .ok()
+            .and_then(|json| serde_json::from_str::<Map<String, Value>>(&json).ok())
+        else {
+            return;
+        };
+        current.retain(|name, _| !ignored.contains(&name.as_str()));
+
+        let Some(previous) = self.settings.replace(current.clone()) else {
+            let all: Vec<String> = current
+                .iter()
+                .map(|(name, value)| format!("{} {}", setting_name(name), show(value)))
+                .collect();
+            self.record(format!("Settings: {}", all.join(", ")));
+            return;
+        };
+        let now = Instant::now();
+        for (name, value) in &current {
+            let old = previous.get(name).unwrap_or(&Value::Null);
+            if old != value {
+                self.record_edit(name, show(old), show(value), now);
+            }
+        }
+    }
+
+    fn record_edit(&mut self, setting: &str, from: String, to: String, now: Instant) {
+        let message = |from: &str| format!("{}: {} → {}", setting_name(setting), from, to);
+        if let Some(entry) = self.entries.last_mut() {
+            if let Some(edit) = &mut entry.edit {
+                if edit.setting == setting && now.duration_since(edit.at) < EDIT_GAP {
+                    entry.message = message(&edit.from);
+                    edit.at = now;
+                    return;
+                }
+            }
+        }
+        self.entries.push(Entry {
+            time: UtcTime::now(),
+            message: message(&from),
+            edit: Some(Edit { setting: setting.to_string(), from, at: now }),
+        });
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("Autotune session journal\nSession started {}\n\n", self.started);
+        for entry in &self.entries {
+            text.push_str(&format!("{}  {}\n", entry.time, entry.message));
+        }
+        text
+    }
+
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        fs::write(paths::normalize_for_io(path), self.to_text())
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+fn setting_name(name: &str) -> String {
+    name.replace('_', " ")
+}
+
+fn show(value: &Value) -> String {
+    match value {
+        Value::Null => "none".to_string(),
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Settings {
+        version: u32,
+        strength: f32,
+        tint: Option<String>,
+    }
+
+    #[test]
+    fn test_settings_changes_are_journaled() {
+        let mut journal = Journal::default();
+        let mut settings = Settings { version: 1, strength: 0.8, tint: None };
+        journal.record_settings(&settings, &["version"]);
+        journal.record("Selected input file: take1.wav");
+
+        // A slider drag is one change, from where it started to where it ended
+        for strength in [0.75, 0.7, 0.65] {
+            settings.strength = strength;
+            journal.record_settings(&settings, &["version"]);
+        }
+        settings.tint = Some("ref.wav".to_string());
+        settings.version = 2;
+        journal.record_settings(&settings, &["version"]);
+        journal.record_settings(&settings, &["version"]);
+
+        let text = journal.to_text();
+        let lines: Vec<&str> = text.lines().skip(3).map(|line| &line[25..]).collect();
+        assert_eq!(
+            lines,
+            [
+                "Settings: strength 0.8, tint none",
+                "Selected input file: take1.wav",
+                "strength: 0.8 → 0.65",
+                "tint: none → ref.wav",
+            ]
+        );
+        assert!(text.starts_with("Autotune session journal\nSession started "));
+    }
+}
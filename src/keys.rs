@@ -0,0 +1,251 @@
+use std::path::Path;
+
+use crate::classifier::{self, PitchTrack, PitchTracker};
+use crate::decoder::MonoReader;
+use crate::labels::Label;
+use crate::scale::TONICS;
+
+// Frames decoded at a time while tracking a file
+const READ_BLOCK: usize = 1 << 16;
+// Pitched frames needed before a key is guessed, about a second at the analysis hop
+const MIN_PITCHED_FRAMES: usize = 40;
+
+// Krumhansl-Kessler key profiles, from the tonic up
+const MAJOR_PROFILE: [f32; 12] =
+    [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] =
+    [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+
+/// How well one entry of `KEY_NAMES` fits the pitches of a take.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyMatch {
+    pub key: usize,
+    // Correlation of the take's pitch classes with the key's profile, -1.0 to 1.0
+    pub score: f32,
+}
+
+/// Every key, best match first. Empty when there is too little pitched audio to tell.
+pub fn rank_keys(track: &PitchTrack) -> Vec<KeyMatch> {
+    let Some(profile) = pitch_class_profile(track) else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<KeyMatch> = TONICS
+        .iter()
+        .enumerate()
+        .map(|(key, &tonic)| {
+            let reference = if key < 12 {
+                &MAJOR_PROFILE
+            } else {
+                &MINOR_PROFILE
+            };
+            // The take's profile seen from this key's tonic
+            let rotated: Vec<f32> = (0..12).map(|step| profile[(tonic + step) % 12]).collect();
+            KeyMatch { key, score: correlation(&rotated, reference) }
+        })
+        .collect();
+    matches.sort_by(|a, b| b.score.total_cmp(&a.score));
+    matches
+}
+
+/// A change of key partway through a take, from a marker named after the new key.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyChange {
+    pub secs: f32,
+    // Index into `KEY_NAMES`
+    pub key: usize,
+}
+
+/// The key a marker names, like "Am", "F# major", or "Key: Bb minor", as an index into
+/// `KEY_NAMES`. Flat and sharp spellings of the same key are the same entry.
+pub fn parse_key_name(text: &str) -> Option<usize> {
+    let text = text.trim().to_lowercase();
+    let text = text.strip_prefix("key").map_or(text.as_str(), |rest| {
+        rest.trim_start().trim_start_matches([':', '=']).trim_start()
+    });
+    let (tonic, quality) = split_note_name(text)?;
+    let major = match quality.trim() {
+        "" | "maj" | "major" => true,
+        "m" | "min" | "minor" => false,
+        _ => return None,
+    };
+    (0..TONICS.len()).find(|&key| TONICS[key] == tonic && (key < 12) == major)
+}
+
+/// The pitch class (0 = C to 11 = B) of a note name like "A", "F#", or "Bb".
+pub fn parse_note_name(text: &str) -> Option<usize> {
+    match split_note_name(&text.trim().to_lowercase())? {
+        (pitch_class, "") => Some(pitch_class),
+        _ => None,
+    }
+}
+
+// The pitch class a lowercase `text` starts with, and the rest of it
+fn split_note_name(text: &str) -> Option<(usize, &str)> {
+    let mut chars = text.chars();
+    let natural = match chars.next()? {
+        'c' => 0,
+        'd' => 2,
+        'e' => 4,
+        'f' => 5,
+        'g' => 7,
+        'a' => 9,
+        'b' => 11,
+        _ => return None,
+    };
+    let rest = chars.as_str();
+    Some(if let Some(rest) = rest.strip_prefix(['#', '♯']) {
+        ((natural + 1) % 12, rest)
+    } else if let Some(rest) = rest.strip_prefix(['b', '♭']) {
+        ((natural + 11) % 12, rest)
+    } else {
+        (natural, rest)
+    })
+}
+
+/// The point labels named after a key, in time order.
+pub fn key_changes(labels: &[Label]) -> Vec<KeyChange> {
+    let mut changes: Vec<KeyChange> = labels
+        .iter()
+        .filter(|label| label.is_point())
+        .filter_map(|label| {
+            parse_key_name(&label.text).map(|key| KeyChange { secs: label.start_secs, key })
+        })
+        .collect();
+    changes.sort_by(|a, b| a.secs.total_cmp(&b.secs));
+    changes
+}
+
+/// The key in effect at `secs`: that of the last change at or before it, if any.
+pub fn key_at(changes: &[KeyChange], secs: f32) -> Option<usize> {
+    changes.iter().take_while(|change| change.secs <= secs).last().map(|change| change.key)
+}
+
+/// Decode `path` a block at a time, track its pitch, and rank the keys it fits.
+pub fn detect_file(path: &Path) -> Result<Vec<KeyMatch>, String> {
+    Ok(rank_keys(&track_file(path)?))
+}
+
+/// Decode `path` a block at a time and track its pitch.
+pub fn track_file(path: &Path) -> Result<PitchTrack, String> {
+    let mut reader = MonoReader::open(path)?;
+    let mut tracker = PitchTracker::new(reader.info().sample_rate as f32);
+    let mut block = Vec::with_capacity(READ_BLOCK);
+    loop {
+        block.clear();
+        if reader.read(&mut block, READ_BLOCK)? == 0 {
+            break;
+        }
+        tracker.push(&block);
+    }
+    Ok(tracker.finish())
+}
+
+// Time spent on each pitch class, weighted by level so breaths and bleed count for little
+fn pitch_class_profile(track: &PitchTrack) -> Option<[f32; 12]> {
+    let mut profile = [0.0; 12];
+    let mut pitched = 0;
+    for frame in &track.frames {
+        if let Some(f0) = frame.f0 {
+            let note = classifier::note_number(f0).round() as i32;
+            profile[note.rem_euclid(12) as usize] += frame.rms;
+            pitched += 1;
+        }
+    }
+    (pitched >= MIN_PITCHED_FRAMES).then_some(profile)
+}
+
+// Pearson correlation of two equally long series
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    let mean = |values: &[f32]| values.iter().sum::<f32>() / values.len() as f32;
+    let (mean_a, mean_b) = (mean(a), mean(b));
+    let (mut cross, mut energy_a, mut energy_b) = (0.0, 0.0, 0.0);
+    for (&a, &b) in a.iter().zip(b) {
+        cross += (a - mean_a) * (b - mean_b);
+        energy_a += (a - mean_a) * (a - mean_a);
+        energy_b += (b - mean_b) * (b - mean_b);
+    }
+    let norm = (energy_a * energy_b).sqrt();
+    if norm > 0.0 { cross / norm } else { 0.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classifier::PitchFrame;
+
+    // A track holding each MIDI note for `frames` frames
+    fn melody(notes: &[(i32, usize)]) -> PitchTrack {
+        let frames = notes
+            .iter()
+            .flat_map(|&(note, frames)| {
+                let f0 = 440.0 * 2f32.powf((note - 69) as f32 / 12.0);
+                vec![PitchFrame { rms: 0.2, harmonicity: 0.9, f0: Some(f0) }; frames]
+            })
+            .collect();
+        PitchTrack { frames, hop_secs: 0.023, offset_secs: 0.0 }
+    }
+
+    #[test]
+    fn test_scale_finds_its_key() {
+        // A G major scale, lingering on the tonic and the fifth
+        let track = melody(&[
+            (67, 30),
+            (69, 10),
+            (71, 15),
+            (72, 10),
+            (74, 25),
+            (76, 10),
+            (78, 10),
+            (79, 30),
+        ]);
+        let matches = rank_keys(&track);
+        assert_eq!(matches.len(), 24);
+        assert_eq!(matches[0].key, 1, "{:?}", &matches[..3]);
+        assert!(matches.windows(2).all(|pair| pair[0].score >= pair[1].score));
+
+        // An A minor arpeggio and scale, centered on A
+        let track = melody(&[(69, 40), (72, 15), (76, 20), (74, 10), (71, 10), (69, 30)]);
+        assert_eq!(rank_keys(&track)[0].key, 12);
+    }
+
+    #[test]
+    fn test_key_names_from_markers() {
+        assert_eq!(parse_key_name("C"), Some(0));
+        assert_eq!(parse_key_name("Key: Am"), Some(12));
+        assert_eq!(parse_key_name("key=F# major"), Some(6));
+        assert_eq!(parse_key_name("Gb"), Some(6));
+        assert_eq!(parse_key_name("bb minor"), Some(19)); // A# minor
+        assert_eq!(parse_key_name("B minor"), Some(14));
+        assert_eq!(parse_key_name("Chorus"), None);
+        assert_eq!(parse_key_name("Ab dorian"), None);
+        assert_eq!(parse_note_name("Cb"), Some(11));
+        assert_eq!(parse_note_name(" f# "), Some(6));
+        assert_eq!(parse_note_name("Am"), None);
+
+        let point =
+            |secs, text: &str| Label { start_secs: secs, end_secs: secs, text: text.into() };
+        let labels = [
+            point(30.0, "Key: Dm"),
+            Label { start_secs: 0.0, end_secs: 10.0, text: "E".to_string() },
+            point(5.0, "Verse"),
+            point(12.5, "G"),
+        ];
+        let changes = key_changes(&labels);
+        assert_eq!(
+            changes,
+            vec![KeyChange { secs: 12.5, key: 1 }, KeyChange { secs: 30.0, key: 20 }]
+        );
+        assert_eq!(key_at(&changes, 3.0), None);
+        assert_eq!(key_at(&changes, 12.5), Some(1));
+        assert_eq!(key_at(&changes, 90.0), Some(20));
+    }
+
+    #[test]
+    fn test_too_little_pitch_gives_no_match() {
+        assert!(rank_keys(&melody(&[(60, 10)])).is_empty());
+        let silent = PitchFrame { rms: 0.0, harmonicity: 0.0, f0: None };
+        let track = PitchTrack { frames: vec![silent; 500], hop_secs: 0.023, offset_secs: 0.0 };
+        assert!(rank_keys(&track).is_empty());
+    }
+}
@@ -0,0 +1,591 @@
+use std::path::Path;
+
+use crate::classifier::{self, PitchTrack};
+
+pub const NOTE_NAMES: [&str; 12] =
+    ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+// Shorter notes are left out of exported segments, they're mostly glides between notes
+const MIN_SEGMENT_SECS: f32 = 0.1;
+// A note segment ends once the pitch is this many semitones from the note, so vibrato
+// around a note boundary doesn't chop it up
+const NOTE_HYSTERESIS: f32 = 0.75;
+const UNPITCHED_TEXT: &str = "no pitch";
+
+/// A labelled stretch of a file, or a point in it when both ends are the same.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub text: String,
+}
+
+impl Label {
+    pub fn is_point(&self) -> bool {
+        self.end_secs <= self.start_secs
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LabelFormat {
+    // Tab-separated start, end, and text per line, as exported by "Export Labels"
+    Audacity,
+    // Praat TextGrid, long or short text format
+    TextGrid,
+    // REAPER's Region/Marker Manager CSV, with regions as R1, R2... and markers as M1, M2...
+    ReaperCsv,
+}
+
+impl LabelFormat {
+    fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("textgrid") => LabelFormat::TextGrid,
+            Some(extension) if extension.eq_ignore_ascii_case("csv") => LabelFormat::ReaperCsv,
+            _ => LabelFormat::Audacity,
+        }
+    }
+}
+
+/// Read an Audacity label file, or a Praat TextGrid or REAPER marker CSV when the extension
+/// says so. All tiers of a TextGrid are read; its empty intervals are gaps, not labels.
+/// REAPER markers become points.
+pub fn read_file(path: &Path) -> Result<Vec<Label>, String> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| format!("Failed to read labels {}: {}", path.display(), e))?;
+    let text = decode_text(&bytes);
+    let labels = match LabelFormat::from_path(path) {
+        LabelFormat::Audacity => parse_audacity(&text),
+        LabelFormat::TextGrid => parse_textgrid(&text),
+        LabelFormat::ReaperCsv => parse_reaper_csv(&text),
+    };
+    labels.map_err(|e| format!("Invalid labels in {}: {}", path.display(), e))
+}
+
+/// Write `labels` in the format `path`'s extension asks for. A TextGrid spans
+/// `duration_secs`, at least.
+pub fn write_file(path: &Path, labels: &[Label], duration_secs: f32) -> Result<(), String> {
+    let text = match LabelFormat::from_path(path) {
+        LabelFormat::Audacity => write_audacity(labels),
+        LabelFormat::TextGrid => write_textgrid(labels, duration_secs),
+        LabelFormat::ReaperCsv => write_reaper_csv(labels),
+    };
+    std::fs::write(path, text)
+        .map_err(|e| format!("Failed to write labels {}: {}", path.display(), e))
+}
+
+/// Split a pitch track into labelled segments: its notes, named like "A4", and audible
+/// stretches where no pitch was found. Silence is left out.
+pub fn note_segments(track: &PitchTrack) -> Vec<Label> {
+    // (first frame, frames, note or None when unpitched)
+    let mut runs: Vec<(usize, usize, Option<f32>)> = Vec::new();
+    for (index, frame) in track.frames.iter().enumerate() {
+        let note = frame.f0.map(classifier::note_number);
+        match (runs.last_mut(), note) {
+            _ if note.is_none() && frame.is_silent() => continue,
+            (Some((first, frames, Some(current))), Some(note))
+                if *first + *frames == index && (note - *current).abs() < NOTE_HYSTERESIS =>
+            {
+                *frames += 1
+            }
+            (Some((first, frames, None)), None) if *first + *frames == index => *frames += 1,
+            _ => runs.push((index, 1, note.map(f32::round))),
+        }
+    }
+
+    let frame_start =
+        |index: usize| (track.offset_secs + (index as f32 - 0.5) * track.hop_secs).max(0.0);
+    runs.into_iter()
+        .map(|(first, frames, note)| Label {
+            start_secs: frame_start(first),
+            end_secs: frame_start(first + frames),
+            text: note.map_or(UNPITCHED_TEXT.to_string(), |note| note_name(note as i32)),
+        })
+        .filter(|label| label.end_secs - label.start_secs >= MIN_SEGMENT_SECS)
+        .collect()
+}
+
+/// `labels` cut down to the stretch from `start_secs` to `end_secs`, for correcting only
+/// part of a file: regions are clipped to it and those outside of it dropped, points kept.
+/// Without any regions the stretch itself is the one region.
+pub fn within(labels: &[Label], start_secs: f32, end_secs: f32) -> Vec<Label> {
+    let mut within: Vec<Label> = labels
+        .iter()
+        .filter_map(|label| {
+            if label.is_point() {
+                return Some(label.clone());
+            }
+            let clipped = Label {
+                start_secs: label.start_secs.max(start_secs),
+                end_secs: label.end_secs.min(end_secs),
+                text: label.text.clone(),
+            };
+            (!clipped.is_point()).then_some(clipped)
+        })
+        .collect();
+    if labels.iter().all(Label::is_point) {
+        within.push(Label { start_secs, end_secs, text: String::new() });
+    }
+    within
+}
+
+/// Name and octave of a MIDI note number, like "A4".
+pub fn note_name(note: i32) -> String {
+    format!("{}{}", NOTE_NAMES[note.rem_euclid(12) as usize], note.div_euclid(12) - 1)
+}
+
+// Praat writes UTF-16 with a byte order mark when a TextGrid has non-ASCII text
+fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| from([pair[0], pair[1]])).collect();
+        String::from_utf16_lossy(&units)
+    };
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn parse_audacity(text: &str) -> Result<Vec<Label>, String> {
+    let mut labels = Vec::new();
+    for (number, line) in text.lines().enumerate() {
+        // Spectral selections add a line of frequencies starting with a backslash
+        if line.trim().is_empty() || line.starts_with('\\') {
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let mut time = || {
+            fields
+                .next()
+                .and_then(|field| field.trim().parse::<f32>().ok())
+                .ok_or_else(|| format!("line {}: expected start and end times", number + 1))
+        };
+        let (start_secs, end_secs) = (time()?, time()?);
+        let text = fields.next().unwrap_or_default().trim_end_matches('\r').to_string();
+        labels.push(Label { start_secs, end_secs, text });
+    }
+    Ok(labels)
+}
+
+fn write_audacity(labels: &[Label]) -> String {
+    labels
+        .iter()
+        .map(|label| format!("{:.6}\t{:.6}\t{}\n", label.start_secs, label.end_secs, label.text))
+        .collect()
+}
+
+// Fields of one CSV line, with quotes around fields and doubled quotes inside them removed
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        let field = fields.last_mut().unwrap();
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => field.push(c),
+        }
+    }
+    fields
+}
+
+// REAPER writes times in the ruler's format: seconds, or minutes:seconds and
+// hours:minutes:seconds. Measures.beats can't be converted without the project's tempo map
+fn parse_reaper_time(field: &str) -> Result<f32, String> {
+    let field = field.trim();
+    if field.matches('.').count() > 1 {
+        return Err(format!(
+            "time {} is in measures and beats; set REAPER's ruler to Minutes:Seconds before \
+             exporting",
+            field
+        ));
+    }
+    let parts: Vec<&str> = field.split(':').collect();
+    if parts.len() > 3 {
+        return Err(format!("time {} has frames; export it as Minutes:Seconds", field));
+    }
+    parts.iter().try_fold(0.0, |secs, part| {
+        let value = part.parse::<f32>().map_err(|_| format!("invalid time {}", field))?;
+        Ok(secs * 60.0 + value)
+    })
+}
+
+fn parse_reaper_csv(text: &str) -> Result<Vec<Label>, String> {
+    let mut lines = text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+    let header = lines.next().map(|(_, line)| split_csv_line(line)).unwrap_or_default();
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|field| field.trim().eq_ignore_ascii_case(name))
+            .ok_or_else(|| format!("no {} column in the header", name))
+    };
+    let (id_column, name_column) = (column("#")?, column("Name")?);
+    let (start_column, end_column) = (column("Start")?, column("End")?);
+
+    let mut labels = Vec::new();
+    for (number, line) in lines {
+        let fields = split_csv_line(line);
+        let field = |column: usize| fields.get(column).map_or("", |field| field.trim());
+        let at_line = |e: String| format!("line {}: {}", number + 1, e);
+        let start_secs = parse_reaper_time(field(start_column)).map_err(at_line)?;
+        let end_secs = match field(id_column).chars().next() {
+            Some('R' | 'r') => parse_reaper_time(field(end_column)).map_err(at_line)?,
+            Some('M' | 'm') => start_secs,
+            _ => {
+                return Err(at_line(format!(
+                    "{} is neither a region nor a marker",
+                    field(id_column)
+                )));
+            }
+        };
+        labels.push(Label { start_secs, end_secs, text: field(name_column).to_string() });
+    }
+    labels.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+    Ok(labels)
+}
+
+// In the Minutes:Seconds format REAPER's own export uses, which its import reads back
+fn write_reaper_csv(labels: &[Label]) -> String {
+    let time = |secs: f32| {
+        let millis = (secs.max(0.0) * 1000.0).round() as u64;
+        format!("{}:{:02}.{:03}", millis / 60_000, millis / 1000 % 60, millis % 1000)
+    };
+    let (mut regions, mut markers) = (0, 0);
+    let mut text = "#,Name,Start,End,Length\n".to_string();
+    for label in labels {
+        let name = if label.text.contains([',', '"']) {
+            format!("\"{}\"", label.text.replace('"', "\"\""))
+        } else {
+            label.text.clone()
+        };
+        if label.is_point() {
+            markers += 1;
+            text.push_str(&format!("M{},{},{},,\n", markers, name, time(label.start_secs)));
+        } else {
+            regions += 1;
+            text.push_str(&format!(
+                "R{},{},{},{},{}\n",
+                regions,
+                name,
+                time(label.start_secs),
+                time(label.end_secs),
+                time(label.end_secs - label.start_secs)
+            ));
+        }
+    }
+    text
+}
+
+#[derive(Debug)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Flag(String),
+}
+
+// A TextGrid is a stream of numbers, quoted strings, and <flags>; the long format only adds
+// `key =` names and `[index]:` headers around them, which are skipped
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                let mut string = String::new();
+                loop {
+                    match chars.next() {
+                        // A doubled quote is a literal quote
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            string.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => string.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                tokens.push(Token::Text(string));
+            }
+            '<' => tokens.push(Token::Flag(chars.by_ref().take_while(|&c| c != '>').collect())),
+            '[' => while chars.next().is_some_and(|c| c != ']') {},
+            // Comments run to the end of the line
+            '!' => while chars.next().is_some_and(|c| c != '\n') {},
+            c if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => {
+                let mut word = c.to_string();
+                while let Some(&c) = chars.peek().filter(|c| !c.is_whitespace()) {
+                    word.push(c);
+                    chars.next();
+                }
+                let number = word.parse().map_err(|_| format!("invalid number {}", word))?;
+                tokens.push(Token::Number(number));
+            }
+            c if c.is_alphabetic() => {
+                while chars.peek().is_some_and(|c| c.is_alphanumeric() || *c == '_') {
+                    chars.next();
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_textgrid(text: &str) -> Result<Vec<Label>, String> {
+    let mut tokens = tokenize(text)?.into_iter();
+    let next_text = |tokens: &mut std::vec::IntoIter<Token>, what: &str| match tokens.next() {
+        Some(Token::Text(text)) => Ok(text),
+        _ => Err(format!("expected {}", what)),
+    };
+    let next_number = |tokens: &mut std::vec::IntoIter<Token>, what: &str| match tokens.next() {
+        Some(Token::Number(number)) => Ok(number),
+        _ => Err(format!("expected {}", what)),
+    };
+
+    if next_text(&mut tokens, "file type")? != "ooTextFile" {
+        return Err("not a Praat text file".to_string());
+    }
+    if next_text(&mut tokens, "object class")? != "TextGrid" {
+        return Err("not a TextGrid".to_string());
+    }
+    next_number(&mut tokens, "xmin")?;
+    next_number(&mut tokens, "xmax")?;
+    match tokens.next() {
+        Some(Token::Flag(flag)) if flag == "exists" => {}
+        // A TextGrid without tiers
+        _ => return Ok(Vec::new()),
+    }
+
+    let mut labels = Vec::new();
+    let tiers = next_number(&mut tokens, "tier count")? as usize;
+    for _ in 0..tiers {
+        let class = next_text(&mut tokens, "tier class")?;
+        next_text(&mut tokens, "tier name")?;
+        next_number(&mut tokens, "tier xmin")?;
+        next_number(&mut tokens, "tier xmax")?;
+        let count = next_number(&mut tokens, "interval count")? as usize;
+        for _ in 0..count {
+            let label = match class.as_str() {
+                "IntervalTier" => {
+                    let start_secs = next_number(&mut tokens, "interval xmin")? as f32;
+                    let end_secs = next_number(&mut tokens, "interval xmax")? as f32;
+                    Label { start_secs, end_secs, text: next_text(&mut tokens, "interval text")? }
+                }
+                "TextTier" => {
+                    let secs = next_number(&mut tokens, "point time")? as f32;
+                    Label {
+                        start_secs: secs,
+                        end_secs: secs,
+                        text: next_text(&mut tokens, "mark")?,
+                    }
+                }
+                class => return Err(format!("unknown tier class {}", class)),
+            };
+            if !label.text.trim().is_empty() {
+                labels.push(label);
+            }
+        }
+    }
+    labels.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+    Ok(labels)
+}
+
+// One interval tier covering the whole file, with empty intervals in the gaps. Overlapping
+// labels are cut where the next one starts, and points are left out.
+fn write_textgrid(labels: &[Label], duration_secs: f32) -> String {
+    let mut sorted: Vec<&Label> = labels.iter().filter(|label| !label.is_point()).collect();
+    sorted.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+    let end = sorted.iter().map(|label| label.end_secs).fold(duration_secs, f32::max);
+
+    let mut intervals: Vec<(f32, f32, &str)> = Vec::new();
+    let mut position = 0.0;
+    for (index, label) in sorted.iter().enumerate() {
+        let start = label.start_secs.max(position);
+        let next_start = sorted.get(index + 1).map_or(end, |next| next.start_secs);
+        let stop = label.end_secs.min(next_start.max(start));
+        if stop <= start {
+            continue;
+        }
+        if start > position {
+            intervals.push((position, start, ""));
+        }
+        intervals.push((start, stop, &label.text));
+        position = stop;
+    }
+    if position < end || intervals.is_empty() {
+        intervals.push((position, end, ""));
+    }
+
+    let mut text = format!(
+        "File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n\nxmin = 0 \nxmax = {} \n\
+         tiers? <exists> \nsize = 1 \nitem []: \n    item [1]:\n        \
+         class = \"IntervalTier\" \n        name = \"autotune\" \n        xmin = 0 \n        \
+         xmax = {} \n        intervals: size = {} \n",
+        end,
+        end,
+        intervals.len()
+    );
+    for (index, (start, stop, label)) in intervals.iter().enumerate() {
+        text.push_str(&format!(
+            "        intervals [{}]:\n            xmin = {} \n            xmax = {} \n            \
+             text = \"{}\" \n",
+            index + 1,
+            start,
+            stop,
+            label.replace('"', "\"\"")
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classifier::PitchFrame;
+
+    fn label(start_secs: f32, end_secs: f32, text: &str) -> Label {
+        Label { start_secs, end_secs, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_audacity_round_trip() {
+        let text = "1.500000\t3.250000\tChorus 1\n\\\t100.0\t2000.0\n4.0\t4.0\tbreath\r\n";
+        let labels = parse_audacity(text).unwrap();
+        assert_eq!(labels, vec![label(1.5, 3.25, "Chorus 1"), label(4.0, 4.0, "breath")]);
+        assert!(labels[1].is_point());
+        assert_eq!(parse_audacity(&write_audacity(&labels)).unwrap(), labels);
+        assert!(parse_audacity("1.0\tlate\n").unwrap_err().contains("line 1"));
+    }
+
+    #[test]
+    fn test_textgrid_formats() {
+        let long = r#"File type = "ooTextFile"
+Object class = "TextGrid"
+
+xmin = 0
+xmax = 3
+tiers? <exists>
+size = 2
+item []:
+    item [1]:
+        class = "IntervalTier"
+        name = "words"
+        xmin = 0
+        xmax = 3
+        intervals: size = 3
+        intervals [1]:
+            xmin = 0
+            xmax = 1
+            text = ""
+        intervals [2]:
+            xmin = 1
+            xmax = 2.5
+            text = "say ""hi"""
+        intervals [3]:
+            xmin = 2.5
+            xmax = 3
+            text = ""
+    item [2]:
+        class = "TextTier"
+        name = "events"
+        xmin = 0
+        xmax = 3
+        points: size = 1
+        points [1]:
+            number = 0.5
+            mark = "click"
+"#;
+        let labels = parse_textgrid(long).unwrap();
+        assert_eq!(labels, vec![label(0.5, 0.5, "click"), label(1.0, 2.5, "say \"hi\"")]);
+
+        // The short format is the same values without the names
+        let short = "File type = \"ooTextFile\"\nObject class = \"TextGrid\"\n\n0\n3\n<exists>\n1\n\
+                     \"IntervalTier\"\n\"words\"\n0\n3\n1\n0.25\n3\n\"all\"\n";
+        assert_eq!(parse_textgrid(short).unwrap(), vec![label(0.25, 3.0, "all")]);
+        assert!(parse_textgrid("File type = \"ooTextFile\"\nObject class = \"Pitch 1\"").is_err());
+    }
+
+    #[test]
+    fn test_textgrid_export_fills_gaps() {
+        let labels = [label(0.5, 1.0, "A4"), label(0.8, 1.5, "B4"), label(2.0, 2.0, "point")];
+        let text = write_textgrid(&labels, 2.0);
+        // The overlap is cut and the gaps are empty intervals
+        assert_eq!(
+            parse_textgrid(&text).unwrap(),
+            vec![label(0.5, 0.8, "A4"), label(0.8, 1.5, "B4")]
+        );
+        assert!(text.contains("intervals: size = 4"));
+        assert!(text.contains("xmax = 2 \n        intervals"));
+    }
+
+    #[test]
+    fn test_reaper_csv_round_trip() {
+        let text = "#,Name,Start,End,Length,Color\n\
+                    R1,Verse,0:04.500,0:21.000,0:16.500,\n\
+                    M1,\"Key: Am, from here\",1:02.250,,,\n\
+                    R2,\"Chorus \"\"big\"\"\",30,45.5,15.5,1A2B3C\n";
+        let labels = parse_reaper_csv(text).unwrap();
+        assert_eq!(
+            labels,
+            vec![
+                label(4.5, 21.0, "Verse"),
+                label(30.0, 45.5, "Chorus \"big\""),
+                label(62.25, 62.25, "Key: Am, from here"),
+            ]
+        );
+        let written = write_reaper_csv(&labels);
+        assert!(written.contains("R1,Verse,0:04.500,0:21.000,0:16.500\n"), "{}", written);
+        assert_eq!(parse_reaper_csv(&written).unwrap(), labels);
+
+        let bars = "#,Name,Start,End,Length\nR1,Verse,1.1.00,9.1.00,8.0.00\n";
+        assert!(parse_reaper_csv(bars).unwrap_err().contains("measures and beats"));
+        assert!(parse_reaper_csv("Start,End\n1,2\n").is_err());
+        assert_eq!(parse_reaper_time("1:00:01.5"), Ok(3601.5));
+    }
+
+    #[test]
+    fn test_utf16_textgrid_is_decoded() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("\"é\"".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(decode_text(&bytes), "\"é\"");
+    }
+
+    #[test]
+    fn test_labels_within_a_stretch() {
+        let labels =
+            [label(1.0, 3.0, "Verse"), label(5.0, 6.0, "Chorus"), label(2.0, 2.0, "Key: G")];
+        let within = within(&labels, 2.5, 5.5);
+        assert_eq!(
+            within,
+            [label(2.5, 3.0, "Verse"), label(5.0, 5.5, "Chorus"), labels[2].clone()]
+        );
+        // Nothing left of a region outside the stretch
+        assert_eq!(super::within(&labels, 3.5, 4.5), [labels[2].clone()]);
+        // Without regions the stretch is corrected
+        assert_eq!(super::within(&labels[2..], 10.0, 12.0)[1], label(10.0, 12.0, ""));
+    }
+
+    #[test]
+    fn test_note_segments() {
+        let frame = |rms, f0| PitchFrame { rms, harmonicity: 0.9, f0 };
+        let mut frames = vec![frame(0.0, None); 10];
+        // A4 with vibrato crossing into A#4's half, then unpitched, then a blip of C5
+        frames.extend((0..20).map(|i| frame(0.3, Some(if i % 2 == 0 { 440.0 } else { 457.0 }))));
+        frames.extend(vec![frame(0.3, None); 10]);
+        frames.push(frame(0.3, Some(523.25)));
+        let track = PitchTrack { frames, hop_secs: 0.02, offset_secs: 0.01 };
+
+        let segments = note_segments(&track);
+        assert_eq!(segments.len(), 2, "{:?}", segments);
+        assert_eq!(segments[0].text, "A4");
+        assert!((segments[0].start_secs - 0.2).abs() < 1e-4);
+        assert!((segments[0].end_secs - 0.6).abs() < 1e-4);
+        assert_eq!(segments[1].text, UNPITCHED_TEXT);
+        assert_eq!(note_name(60), "C4");
+        assert_eq!(note_name(-1), "B-2");
+    }
+}
@@ -0,0 +1,120 @@
+use eframe::egui;
+use egui_dock::{DockState, NodeIndex, TabViewer};
+use serde::{Deserialize, Serialize};
+
+use crate::AutotuneApp;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Tab {
+    Files,
+    Settings,
+    Devices,
+    Processing,
+    Analysis,
+    Log,
+    Live,
+    Record,
+    Timeline,
+    Session,
+}
+
+impl Tab {
+    pub const ALL: [Tab; 10] = [
+        Tab::Files,
+        Tab::Settings,
+        Tab::Devices,
+        Tab::Processing,
+        Tab::Analysis,
+        Tab::Log,
+        Tab::Live,
+        Tab::Record,
+        Tab::Timeline,
+        Tab::Session,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Tab::Files => "📁 Files",
+            Tab::Settings => "🎼 Settings",
+            Tab::Devices => "🔈 Devices",
+            Tab::Processing => "🔄 Processing",
+            Tab::Analysis => "📈 Analysis",
+            Tab::Log => "📜 Log",
+            Tab::Live => "🎤 Live",
+            Tab::Record => "⏺ Record",
+            Tab::Timeline => "🎞 Timeline",
+            Tab::Session => "🎚 Session",
+        }
+    }
+}
+
+/// Window size the full layout opens at, and the smallest it can be made.
+pub const FULL_SIZE: [f32; 2] = [800.0, 600.0];
+pub const FULL_MIN_SIZE: [f32; 2] = [600.0, 400.0];
+// Room for the compact layout's rows and no more, to fit in a corner of the screen
+const COMPACT_SIZE: [f32; 2] = [360.0, 250.0];
+
+/// Resize the window for the compact layout, or back to the full layout's size.
+pub fn resize_for(ctx: &egui::Context, compact: bool) {
+    let (size, min_size) = if compact {
+        (COMPACT_SIZE, COMPACT_SIZE)
+    } else {
+        (FULL_SIZE, FULL_MIN_SIZE)
+    };
+    // The minimum first, so it doesn't hold the window at its old size
+    ctx.send_viewport_cmd(egui::ViewportCommand::MinInnerSize(min_size.into()));
+    ctx.send_viewport_cmd(egui::ViewportCommand::InnerSize(size.into()));
+}
+
+/// Controls on the left, analysis and log stacked on the right.
+pub fn default_dock_state() -> DockState<Tab> {
+    let mut dock_state = DockState::new(vec![Tab::Files, Tab::Settings, Tab::Devices]);
+    let surface = dock_state.main_surface_mut();
+
+    let [controls, analysis] = surface.split_right(NodeIndex::root(), 0.5, vec![Tab::Analysis]);
+    surface.split_below(
+        controls,
+        0.6,
+        vec![Tab::Processing, Tab::Live, Tab::Record, Tab::Timeline, Tab::Session],
+    );
+    surface.split_below(analysis, 0.6, vec![Tab::Log]);
+
+    dock_state
+}
+
+/// Add tabs that a layout saved by an older version doesn't know about yet.
+pub fn add_missing_tabs(dock_state: &mut DockState<Tab>) {
+    for tab in Tab::ALL {
+        if dock_state.find_tab(&tab).is_none() {
+            dock_state.push_to_first_leaf(tab);
+        }
+    }
+}
+
+impl TabViewer for AutotuneApp {
+    type Tab = Tab;
+
+    fn title(&mut self, tab: &mut Self::Tab) -> egui::WidgetText {
+        tab.title().into()
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, tab: &mut Self::Tab) {
+        match tab {
+            Tab::Files => self.files_ui(ui),
+            Tab::Settings => self.settings_ui(ui),
+            Tab::Devices => self.devices_ui(ui),
+            Tab::Processing => self.processing_ui(ui),
+            Tab::Analysis => self.analysis_ui(ui),
+            Tab::Log => self.log_ui(ui),
+            Tab::Live => self.live_ui(ui),
+            Tab::Record => self.record_ui(ui),
+            Tab::Timeline => self.timeline_ui(ui),
+            Tab::Session => self.session_ui(ui),
+        }
+    }
+
+    // Tabs can be rearranged but not closed, otherwise controls could be lost for good
+    fn closeable(&mut self, _tab: &mut Self::Tab) -> bool {
+        false
+    }
+}
@@ -0,0 +1,37 @@
+// The decoding, pitch correction, and encoding shared by the GUI and CLI, usable on its
+// own through `pipeline::process_samples` and `pipeline::render`.
+pub mod align;
+pub mod bookmarks;
+pub mod classifier;
+pub mod clock;
+pub mod decoder;
+pub mod demo_audio;
+pub mod dynamics;
+pub mod effects;
+pub mod energy;
+pub mod engine;
+pub mod equalizer;
+pub mod fingerprint;
+pub mod format;
+pub mod frequencies;
+pub mod harmony;
+pub mod intonation;
+pub mod keys;
+pub mod labels;
+pub mod loudness;
+pub mod midi;
+pub mod paths;
+pub mod pcm;
+pub mod phrases;
+pub mod pitch;
+pub mod pipeline;
+pub mod prefilter;
+pub mod profiling;
+pub mod resample;
+pub mod routing;
+pub mod scale;
+pub mod scratch;
+pub mod sections;
+pub mod stretch;
+pub mod tint;
+pub mod verify;
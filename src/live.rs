@@ -0,0 +1,392 @@
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{FromSample, Sample, SampleFormat, SizedSample};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI32, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use synthphone_vocals::{AutotuneConfig, AutotuneState, MusicalSettings, process_autotune};
+
+use crate::devices;
+use crate::engine::{FrameStream, Framing, Window};
+use crate::monitor::{Monitor, MonitorFeed};
+use crate::pipeline;
+
+/// Device buffer sizes offered in the UI, in frames.
+pub const BUFFER_SIZES: [u32; 5] = [64, 128, 256, 512, 1024];
+
+// Output queued beyond this many device buffers is dropped to keep latency bounded
+const MAX_QUEUED_BUFFERS: f32 = 4.0;
+// Load readings are averaged over this long, so the display doesn't flicker
+const LOAD_WINDOW: Duration = Duration::from_millis(500);
+// Average load from which on overloads are blamed on the CPU rather than on bursts
+const CPU_BOUND_LOAD: f32 = 0.8;
+// This many xruns within XRUN_WINDOW make a buffer size unstable. Those while the devices
+// start up don't count
+const XRUN_LIMIT: usize = 3;
+const XRUN_WINDOW: Duration = Duration::from_secs(10);
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// The next larger buffer size offered, if there is one.
+pub fn larger_buffer_size(buffer_size: u32) -> Option<u32> {
+    BUFFER_SIZES.into_iter().find(|&size| size > buffer_size)
+}
+
+/// Musical settings the live callback picks up without locking, so they can be changed
+/// while it runs.
+#[derive(Debug, Default)]
+pub struct LiveSettings {
+    key: AtomicI32,
+    note: AtomicI32,
+    octave: AtomicI32,
+    formant: AtomicI32,
+}
+
+impl LiveSettings {
+    pub fn set(&self, settings: &MusicalSettings) {
+        self.key.store(settings.key, Ordering::Relaxed);
+        self.note.store(settings.note, Ordering::Relaxed);
+        self.octave.store(settings.octave, Ordering::Relaxed);
+        self.formant.store(settings.formant, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> MusicalSettings {
+        MusicalSettings {
+            key: self.key.load(Ordering::Relaxed),
+            note: self.note.load(Ordering::Relaxed),
+            octave: self.octave.load(Ordering::Relaxed),
+            formant: self.formant.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Time the input callback spends processing against the time its block lasts, updated
+/// by the callback and read by the UI.
+#[derive(Debug, Default)]
+pub struct LiveLoad {
+    busy_nanos: AtomicU64,
+    budget_nanos: AtomicU64,
+    // Highest single-callback load since the last reading, in thousandths
+    peak: AtomicU32,
+    // Callbacks that took longer than their block lasts
+    overloads: AtomicUsize,
+}
+
+impl LiveLoad {
+    fn record(&self, busy: Duration, budget: Duration) {
+        self.busy_nanos.fetch_add(busy.as_nanos() as u64, Ordering::Relaxed);
+        self.budget_nanos.fetch_add(budget.as_nanos() as u64, Ordering::Relaxed);
+        let load = busy.as_secs_f64() / budget.as_secs_f64().max(1e-9);
+        self.peak.fetch_max((load * 1000.0).min(u32::MAX as f64) as u32, Ordering::Relaxed);
+        if busy > budget {
+            self.overloads.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Realtime headroom of a live session over the last reading window, and its glitches
+/// since it started.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LoadReading {
+    // Share of the block duration spent processing, on average and at most
+    pub load: f32,
+    pub peak: f32,
+    // Callbacks that overran their block, a CPU problem
+    pub overloads: usize,
+    // Output buffers that ran dry although processing kept up
+    pub underruns: usize,
+    // Audio dropped because the devices' clocks drift apart
+    pub dropped_secs: f32,
+}
+
+impl LoadReading {
+    pub fn headroom(&self) -> f32 {
+        (1.0 - self.load).max(0.0)
+    }
+
+    /// The likely cause of glitches, or None while there are none.
+    pub fn diagnosis(&self) -> Option<&'static str> {
+        if self.overloads > 0 && self.load >= CPU_BOUND_LOAD {
+            Some("CPU: processing can't keep up. Close other apps that keep the CPU busy")
+        } else if self.overloads > 0 {
+            Some(
+                "Buffer: analysis frames come in bursts that overrun small blocks. Try a larger \
+                 buffer",
+            )
+        } else if self.underruns > 0 {
+            Some(
+                "Buffer or device: the output ran dry although processing kept up. Try a larger \
+                 buffer",
+            )
+        } else if self.dropped_secs > 0.0 {
+            Some("Device: the input and output clocks drift apart. Use one interface for both")
+        } else {
+            None
+        }
+    }
+}
+
+// Tells apart a stray xrun from a buffer size that keeps glitching
+struct XrunWatch {
+    started: Instant,
+    counted: usize,
+    recent: VecDeque<Instant>,
+}
+
+impl XrunWatch {
+    fn new(now: Instant) -> Self {
+        Self { started: now, counted: 0, recent: VecDeque::new() }
+    }
+
+    // Whether `xruns` so far amount to too many lately
+    fn update(&mut self, xruns: usize, now: Instant) -> bool {
+        let new = xruns.saturating_sub(self.counted);
+        self.counted = xruns;
+        if now.duration_since(self.started) < SETTLE_TIME {
+            return false;
+        }
+        self.recent.extend(std::iter::repeat_n(now, new.min(XRUN_LIMIT)));
+        while self.recent.front().is_some_and(|&xrun| now.duration_since(xrun) > XRUN_WINDOW) {
+            self.recent.pop_front();
+        }
+        self.recent.len() >= XRUN_LIMIT
+    }
+}
+
+/// Input device -> autotune -> output device, running until dropped.
+pub struct LiveSession {
+    _input: cpal::Stream,
+    output: Monitor,
+    pub settings: Arc<LiveSettings>,
+    pub sample_rate: u32,
+    pub buffer_size: u32,
+    fft_size: usize,
+    failed_frames: Arc<AtomicUsize>,
+    load: Arc<LiveLoad>,
+    // Totals at the start of the current window, and the last window's reading
+    window_start: Instant,
+    window_busy: u64,
+    window_budget: u64,
+    reading: LoadReading,
+    xruns: XrunWatch,
+}
+
+impl LiveSession {
+    /// Open the chosen input and output devices with `buffer_size` frames per callback.
+    /// `config` supplies the frame layout and correction parameters; its sample rate is
+    /// replaced by the input device's. Frames are crossfaded with `window`, and notes are
+    /// tuned to `a4`.
+    pub fn start(
+        config: AutotuneConfig,
+        window: Window,
+        settings: &MusicalSettings,
+        a4: f32,
+        buffer_size: u32,
+    ) -> Result<Self, String> {
+        let device = devices::input_device()?;
+        let supported = devices::input_config(&device)?;
+        let sample_format = supported.sample_format();
+        let mut stream_config: cpal::StreamConfig = supported.into();
+        stream_config.buffer_size = cpal::BufferSize::Fixed(buffer_size);
+        let sample_rate = stream_config.sample_rate.0;
+
+        let output = Monitor::start(sample_rate, Some(buffer_size))?;
+        let max_latency = MAX_QUEUED_BUFFERS * buffer_size as f32 / sample_rate as f32;
+
+        let shared_settings = Arc::new(LiveSettings::default());
+        shared_settings.set(settings);
+        let failed_frames = Arc::new(AtomicUsize::new(0));
+        let load = Arc::new(LiveLoad::default());
+
+        let config =
+            AutotuneConfig { sample_rate: pipeline::tuned_rate(sample_rate as f32, a4), ..config };
+        let processor = LiveProcessor {
+            frames: FrameStream::new(Framing {
+                window,
+                ..Framing::new(config.fft_size, config.hop_size)
+            }),
+            state: AutotuneState::new(config),
+            settings: shared_settings.clone(),
+            output: output.live_feed(max_latency),
+            failed_frames: failed_frames.clone(),
+            load: load.clone(),
+            sample_rate,
+            mono: Vec::with_capacity(8192),
+        };
+
+        let input = match sample_format {
+            SampleFormat::F32 => build_input::<f32>(&device, &stream_config, processor),
+            SampleFormat::I16 => build_input::<i16>(&device, &stream_config, processor),
+            SampleFormat::U16 => build_input::<u16>(&device, &stream_config, processor),
+            other => Err(format!("Unsupported input sample format: {}", other)),
+        }?;
+        input.play().map_err(|e| format!("Failed to start recording: {}", e))?;
+
+        Ok(Self {
+            _input: input,
+            output,
+            settings: shared_settings,
+            sample_rate,
+            buffer_size,
+            fft_size: config.fft_size,
+            failed_frames,
+            load,
+            window_start: Instant::now(),
+            window_busy: 0,
+            window_budget: 0,
+            reading: LoadReading::default(),
+            xruns: XrunWatch::new(Instant::now()),
+        })
+    }
+
+    /// Estimated input-to-output delay: one analysis frame plus an input and output buffer.
+    pub fn latency_ms(&self) -> f32 {
+        let frames = self.fft_size + 2 * self.buffer_size as usize;
+        frames as f32 * 1000.0 / self.sample_rate as f32
+    }
+
+    pub fn failed_frames(&self) -> usize {
+        self.failed_frames.load(Ordering::Relaxed)
+    }
+
+    /// Load over the last complete window; call it every UI frame to keep it current.
+    pub fn load(&mut self) -> LoadReading {
+        if self.window_start.elapsed() >= LOAD_WINDOW {
+            let busy = self.load.busy_nanos.load(Ordering::Relaxed);
+            let budget = self.load.budget_nanos.load(Ordering::Relaxed);
+            let window_budget = budget - self.window_budget;
+            self.reading.load = if window_budget > 0 {
+                (busy - self.window_busy) as f32 / window_budget as f32
+            } else {
+                0.0
+            };
+            self.reading.peak = self.load.peak.swap(0, Ordering::Relaxed) as f32 / 1000.0;
+            (self.window_start, self.window_busy, self.window_budget) =
+                (Instant::now(), busy, budget);
+        }
+        LoadReading {
+            overloads: self.load.overloads.load(Ordering::Relaxed),
+            underruns: self.output.underruns(),
+            dropped_secs: self.output.dropped_secs(),
+            ..self.reading
+        }
+    }
+
+    /// Whether the session glitched a few times in the last seconds, beyond the odd xrun.
+    /// Clock drift between the devices doesn't count, a larger buffer can't fix it.
+    pub fn unstable(&mut self) -> bool {
+        let reading = self.load();
+        self.xruns.update(reading.overloads + reading.underruns, Instant::now())
+    }
+}
+
+// Everything the input callback owns
+struct LiveProcessor {
+    frames: FrameStream,
+    state: AutotuneState,
+    settings: Arc<LiveSettings>,
+    output: MonitorFeed,
+    failed_frames: Arc<AtomicUsize>,
+    load: Arc<LiveLoad>,
+    sample_rate: u32,
+    // Downmix buffer, reused between callbacks
+    mono: Vec<f32>,
+}
+
+fn build_input<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut processor: LiveProcessor,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let channels = config.channels.max(1) as usize;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                let started = Instant::now();
+                let LiveProcessor {
+                    frames,
+                    state,
+                    settings,
+                    output,
+                    failed_frames,
+                    load,
+                    sample_rate,
+                    mono,
+                } = &mut processor;
+                mono.clear();
+                mono.extend(data.chunks(channels).map(|frame| {
+                    frame.iter().map(|&sample| f32::from_sample(sample)).sum::<f32>()
+                        / frame.len() as f32
+                }));
+
+                let musical_settings = settings.get();
+                let mut failed = 0;
+                frames.push(
+                    mono,
+                    state,
+                    |frame_in, frame_out, state| {
+                        process_autotune(frame_in, frame_out, state, &musical_settings).map(|_| ())
+                    },
+                    |finished| output.push(finished),
+                    |_, _| failed += 1,
+                );
+                if failed > 0 {
+                    failed_frames.fetch_add(failed, Ordering::Relaxed);
+                }
+                let budget = Duration::from_secs_f64(mono.len() as f64 / *sample_rate as f64);
+                load.record(started.elapsed(), budget);
+            },
+            |e| log::warn!("Live input error: {}", e),
+            None,
+        )
+        .map_err(|e| format!("Failed to open input stream: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_and_diagnosis() {
+        let load = LiveLoad::default();
+        let block = Duration::from_millis(10);
+        load.record(Duration::from_millis(2), block);
+        load.record(Duration::from_millis(12), block);
+        assert_eq!(load.busy_nanos.load(Ordering::Relaxed), 14_000_000);
+        assert_eq!(load.peak.load(Ordering::Relaxed), 1200);
+        assert_eq!(load.overloads.load(Ordering::Relaxed), 1);
+
+        let clean = LoadReading { load: 0.3, ..Default::default() };
+        assert!((clean.headroom() - 0.7).abs() < 1e-6);
+        assert!(clean.diagnosis().is_none());
+        let bursty = LoadReading { load: 0.3, peak: 1.2, overloads: 3, ..Default::default() };
+        assert!(bursty.diagnosis().unwrap().starts_with("Buffer:"));
+        let busy = LoadReading { load: 0.95, overloads: 3, ..Default::default() };
+        assert!(busy.diagnosis().unwrap().starts_with("CPU:"));
+        let drifting = LoadReading { dropped_secs: 0.2, ..Default::default() };
+        assert!(drifting.diagnosis().unwrap().starts_with("Device:"));
+    }
+
+    #[test]
+    fn test_repeated_xruns_make_a_buffer_unstable() {
+        let start = Instant::now();
+        let at = |secs: f32| start + Duration::from_secs_f32(secs);
+        let mut watch = XrunWatch::new(start);
+        // Startup glitches are forgiven
+        assert!(!watch.update(5, at(0.5)));
+        assert!(!watch.update(6, at(2.0)));
+        assert!(!watch.update(7, at(5.0)));
+        // A third one long after the first two is just another stray one
+        assert!(!watch.update(8, at(20.0)));
+        assert!(!watch.update(9, at(21.0)));
+        assert!(watch.update(10, at(22.0)));
+
+        assert_eq!(larger_buffer_size(64), Some(128));
+        assert_eq!(larger_buffer_size(200), Some(256));
+        assert_eq!(larger_buffer_size(1024), None);
+    }
+}
@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+
+use crate::timeline::ClipSettings;
+
+/// A correction setting that can be locked, so presets and projects leave it as it is, or
+/// linked across the clips of the timeline, so changing it on one changes it on all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Param {
+    Key,
+    Note,
+    Octave,
+    Formant,
+    Strength,
+    Transition,
+    A4,
+}
+
+impl Param {
+    pub fn name(self) -> &'static str {
+        match self {
+            Param::Key => "key",
+            Param::Note => "note",
+            Param::Octave => "octave",
+            Param::Formant => "formant shift",
+            Param::Strength => "strength",
+            Param::Transition => "transition speed",
+            Param::A4 => "A4 reference",
+        }
+    }
+
+    /// Give `to` the value `from` has for this setting.
+    pub fn copy(self, from: &ClipSettings, to: &mut ClipSettings) {
+        match self {
+            Param::Key => to.key = from.key,
+            Param::Note => to.note = from.note,
+            Param::Octave => to.octave = from.octave,
+            Param::Formant => to.formant = from.formant,
+            Param::Strength => to.strength = from.strength,
+            Param::Transition => to.transition = from.transition,
+            Param::A4 => to.a4 = from.a4,
+        }
+    }
+
+    pub fn differs(self, a: &ClipSettings, b: &ClipSettings) -> bool {
+        let mut copied = *b;
+        self.copy(a, &mut copied);
+        copied != *b
+    }
+}
+
+/// A set of settings, like the locked or the linked ones.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParamSet(Vec<Param>);
+
+impl ParamSet {
+    pub fn contains(&self, param: Param) -> bool {
+        self.0.contains(&param)
+    }
+
+    pub fn toggle(&mut self, param: Param) {
+        match self.0.iter().position(|&other| other == param) {
+            Some(index) => {
+                self.0.remove(index);
+            }
+            None => self.0.push(param),
+        }
+    }
+
+    /// `incoming` with the settings in this set kept as `current` has them.
+    pub fn keep(&self, current: &ClipSettings, mut incoming: ClipSettings) -> ClipSettings {
+        for &param in &self.0 {
+            param.copy(current, &mut incoming);
+        }
+        incoming
+    }
+
+    /// The settings in this set that `incoming` would change on `current`.
+    pub fn overridden(&self, current: &ClipSettings, incoming: &ClipSettings) -> Vec<Param> {
+        self.0.iter().copied().filter(|param| param.differs(incoming, current)).collect()
+    }
+
+    /// Give each of `clips` the settings in this set that one clip changed from `before` to
+    /// `after`.
+    pub fn spread<'a>(
+        &self,
+        before: &ClipSettings,
+        after: &ClipSettings,
+        clips: impl IntoIterator<Item = &'a mut ClipSettings>,
+    ) {
+        let changed = self.overridden(before, after);
+        for clip in clips {
+            for param in &changed {
+                param.copy(after, clip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_locked_and_linked_settings() {
+        let settings = |key, strength| ClipSettings {
+            key,
+            note: 0,
+            octave: 2,
+            formant: 0,
+            strength,
+            transition: 0.1,
+            a4: 440.0,
+        };
+        let mut locked = ParamSet::default();
+        locked.toggle(Param::Key);
+        locked.toggle(Param::Strength);
+        locked.toggle(Param::Strength);
+        assert!(locked.contains(Param::Key) && !locked.contains(Param::Strength));
+        // A preset or project brings a new key and strength, only the strength is taken
+        let (current, incoming) = (settings(3, 0.8), settings(7, 0.4));
+        let kept = locked.keep(&current, incoming);
+        assert_eq!((kept.key, kept.strength), (3, 0.4));
+        assert_eq!(locked.overridden(&current, &incoming), [Param::Key]);
+
+        let mut linked = ParamSet::default();
+        linked.toggle(Param::Strength);
+        let mut clips = [settings(0, 0.8), settings(5, 0.8), settings(9, 0.6)];
+        let before = clips[1];
+        let after = ClipSettings { key: 6, strength: 0.5, ..before };
+        clips[1] = after;
+        linked.spread(&before, &after, &mut clips);
+        assert!(clips.iter().all(|clip| clip.strength == 0.5));
+        assert_eq!(clips.map(|clip| clip.key), [0, 6, 9]);
+    }
+}
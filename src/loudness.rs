@@ -0,0 +1,331 @@
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::fmt;
+use std::str::FromStr;
+
+// Highest peak a normalized render is brought to, leaving a little headroom for encoding
+pub const PEAK_CEILING: f32 = 0.95;
+// Blocks quieter than this never count towards the loudness, in LUFS
+const ABSOLUTE_GATE: f64 = -70.0;
+// Nor do blocks this far below the loudness of the rest, in LU
+const RELATIVE_GATE: f64 = -10.0;
+// Loudness is measured over 400 ms blocks overlapping by 75%, so a new one every 100 ms
+const STEP_SECS: f64 = 0.1;
+const STEPS_PER_BLOCK: usize = 4;
+
+/// How a render's level is set when it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Normalization {
+    /// Left as rendered, even if it clips
+    None,
+    /// Only brought down to the ceiling if it would otherwise clip
+    #[default]
+    Clip,
+    /// Brought up or down so its peak is at this level, in dBFS
+    Peak(f32),
+    /// Brought up or down to this integrated loudness, in LUFS, as far as the peak ceiling
+    /// allows
+    Loudness(f32),
+}
+
+impl Normalization {
+    pub const DEFAULT_PEAK_DB: f32 = -1.0;
+    pub const DEFAULT_LUFS: f32 = -14.0;
+
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Self::Peak(db) if !(-60.0..=0.0).contains(&db) => {
+                Err("Peak normalization target must be between -60 and 0 dBFS".to_string())
+            }
+            Self::Loudness(lufs) if !(-60.0..=0.0).contains(&lufs) => {
+                Err("Loudness normalization target must be between -60 and 0 LUFS".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Gain that brings a render with sample peak `peak` and integrated loudness `loudness`
+    /// (of its mono signal) to this target, written with `channels` channels. None leaves
+    /// it as it is. When the render is `limited`, the limiter holds its peaks down instead,
+    /// so it isn't turned down to keep them under the ceiling.
+    pub fn gain(
+        &self,
+        peak: f32,
+        loudness: Option<f32>,
+        channels: u16,
+        limited: bool,
+    ) -> Option<f32> {
+        let ceiling = (peak > 0.0 && !limited).then(|| PEAK_CEILING / peak);
+        match *self {
+            Self::None => None,
+            Self::Clip => (peak > 1.0 && !limited).then(|| PEAK_CEILING / peak),
+            Self::Peak(db) => (peak > 0.0).then(|| from_db(db) / peak),
+            Self::Loudness(lufs) => {
+                let gain = from_db(lufs - with_channels(loudness?, channels));
+                Some(ceiling.map_or(gain, |ceiling| gain.min(ceiling)))
+            }
+        }
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let text = text.trim().to_lowercase();
+        let (mode, target) = match text.split_once(':') {
+            Some((mode, target)) => (mode, Some(target)),
+            None => (text.as_str(), None),
+        };
+        let value = |default: f32| match target {
+            Some(target) => target.trim().parse().map_err(|_| {
+                format!("Invalid normalization target '{}': expected a number", target)
+            }),
+            None => Ok(default),
+        };
+        let normalization = match mode {
+            "none" => Self::None,
+            "clip" => Self::Clip,
+            "peak" => Self::Peak(value(Self::DEFAULT_PEAK_DB)?),
+            "lufs" => Self::Loudness(value(Self::DEFAULT_LUFS)?),
+            _ => {
+                return Err(format!(
+                    "Invalid normalization '{}': expected none, clip, peak[:DBFS], or lufs[:LUFS]",
+                    text
+                ));
+            }
+        };
+        if target.is_some() && matches!(normalization, Self::None | Self::Clip) {
+            return Err(format!("Normalization '{}' takes no target", mode));
+        }
+        normalization.validate()?;
+        Ok(normalization)
+    }
+}
+
+impl TryFrom<String> for Normalization {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        text.parse()
+    }
+}
+
+impl From<Normalization> for String {
+    fn from(normalization: Normalization) -> Self {
+        normalization.to_string()
+    }
+}
+
+impl fmt::Display for Normalization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Clip => write!(f, "clip"),
+            Self::Peak(db) => write!(f, "peak:{}", db),
+            Self::Loudness(lufs) => write!(f, "lufs:{}", lufs),
+        }
+    }
+}
+
+/// Loudness of a file with `channels` copies of a mono signal of `loudness`: each channel
+/// adds its power.
+pub fn with_channels(loudness: f32, channels: u16) -> f32 {
+    loudness + 10.0 * (channels.max(1) as f32).log10()
+}
+
+/// Integrated loudness (ITU-R BS.1770) of interleaved audio pushed a block at a time:
+/// K-weighted, measured over gated 400 ms blocks, with mono and stereo channels weighted
+/// alike.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+    filters: Vec<KWeighting>,
+    // Channel the next pushed sample belongs to
+    channel: usize,
+    step_len: usize,
+    // Sum of squares and frames of the 100 ms step being measured
+    step: (f64, usize),
+    // Mean power of the last steps, enough for a block
+    recent: Vec<f64>,
+    // Mean power of each block
+    blocks: Vec<f64>,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let rate = sample_rate as f64;
+        Self {
+            filters: (0..channels.max(1)).map(|_| KWeighting::new(rate)).collect(),
+            channel: 0,
+            step_len: ((STEP_SECS * rate) as usize).max(1),
+            step: (0.0, 0),
+            recent: Vec::with_capacity(STEPS_PER_BLOCK),
+            blocks: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            let weighted = self.filters[self.channel].process(sample as f64);
+            self.step.0 += weighted * weighted;
+            self.channel += 1;
+            if self.channel < self.filters.len() {
+                continue;
+            }
+            self.channel = 0;
+            self.step.1 += 1;
+            if self.step.1 == self.step_len {
+                self.end_step();
+            }
+        }
+    }
+
+    /// The integrated loudness in LUFS, None when nothing was loud enough to measure.
+    pub fn finish(self) -> Option<f32> {
+        let gated = |threshold: f64| {
+            let loud: Vec<f64> =
+                self.blocks.iter().copied().filter(|&power| to_lufs(power) > threshold).collect();
+            (!loud.is_empty()).then(|| loud.iter().sum::<f64>() / loud.len() as f64)
+        };
+        let absolute = gated(ABSOLUTE_GATE)?;
+        let relative = gated((to_lufs(absolute) + RELATIVE_GATE).max(ABSOLUTE_GATE))?;
+        Some(to_lufs(relative) as f32)
+    }
+
+    fn end_step(&mut self) {
+        let (sum, len) = self.step;
+        self.step = (0.0, 0);
+        if self.recent.len() == STEPS_PER_BLOCK {
+            self.recent.remove(0);
+        }
+        self.recent.push(sum / len as f64);
+        if self.recent.len() == STEPS_PER_BLOCK {
+            self.blocks.push(self.recent.iter().sum::<f64>() / STEPS_PER_BLOCK as f64);
+        }
+    }
+}
+
+// The two filters of the K-weighting, a high shelf for the head's acoustics and a high
+// pass for how little low frequencies add to loudness, designed for any sample rate
+#[derive(Debug, Clone)]
+struct KWeighting {
+    stages: [Biquad; 2],
+}
+
+impl KWeighting {
+    fn new(rate: f64) -> Self {
+        // High shelf: +4 dB above about 1.7 kHz
+        let k = (PI * 1681.974450955533 / rate).tan();
+        let q = 0.7071752369554196;
+        let vh = 10f64.powf(3.999843853973347 / 20.0);
+        let vb = vh.powf(0.4996667741545416);
+        let a0 = 1.0 + k / q + k * k;
+        let shelf = Biquad::new(
+            [
+                (vh + vb * k / q + k * k) / a0,
+                2.0 * (k * k - vh) / a0,
+                (vh - vb * k / q + k * k) / a0,
+            ],
+            [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+        );
+
+        // High pass at about 38 Hz
+        let k = (PI * 38.13547087602444 / rate).tan();
+        let q = 0.5003270373238773;
+        let a0 = 1.0 + k / q + k * k;
+        let high_pass =
+            Biquad::new([1.0, -2.0, 1.0], [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0]);
+        Self { stages: [shelf, high_pass] }
+    }
+
+    fn process(&mut self, sample: f64) -> f64 {
+        self.stages.iter_mut().fold(sample, |sample, stage| stage.process(sample))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 2],
+    state: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 2]) -> Self {
+        Self { b, a, state: [0.0; 2] }
+    }
+
+    fn process(&mut self, x: f64) -> f64 {
+        let y = self.b[0] * x + self.state[0];
+        self.state[0] = self.b[1] * x - self.a[0] * y + self.state[1];
+        self.state[1] = self.b[2] * x - self.a[1] * y;
+        y
+    }
+}
+
+fn to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.max(1e-20).log10()
+}
+
+fn from_db(db: f32) -> f32 {
+    10f32.powf(db / 20.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loudness_of_a_sine() {
+        // A full scale 997 Hz sine in one channel is -3.01 LUFS by definition
+        let rate = 48000;
+        let sine = |amplitude: f32, secs: usize| -> Vec<f32> {
+            let step = 2.0 * std::f32::consts::PI * 997.0 / rate as f32;
+            (0..rate as usize * secs).map(|i| amplitude * (step * i as f32).sin()).collect()
+        };
+        let mut meter = LoudnessMeter::new(rate, 1);
+        meter.push(&sine(1.0, 3));
+        assert!((meter.finish().unwrap() - -3.01).abs() < 0.05);
+
+        // Stereo counts both channels
+        let quiet = sine(0.1, 2);
+        let mut meter = LoudnessMeter::new(rate, 2);
+        meter.push(&quiet.iter().flat_map(|&sample| [sample, sample]).collect::<Vec<f32>>());
+        assert!((meter.finish().unwrap() - with_channels(-23.01, 2)).abs() < 0.05);
+
+        // Long silences are gated out rather than averaged in
+        let mut meter = LoudnessMeter::new(rate, 1);
+        meter.push(&quiet);
+        meter.push(&vec![0.0; rate as usize * 10]);
+        assert!((meter.finish().unwrap() - -23.01).abs() < 0.5);
+        assert_eq!(LoudnessMeter::new(rate, 1).finish(), None);
+    }
+
+    #[test]
+    fn test_normalization_gain() {
+        assert_eq!(Normalization::default().gain(2.0, Some(-10.0), 2, false), Some(0.475));
+        assert_eq!(Normalization::Clip.gain(0.5, None, 2, false), None);
+        assert_eq!(Normalization::None.gain(2.0, Some(-10.0), 2, false), None);
+        let peak = Normalization::Peak(-6.0);
+        assert_eq!(peak.gain(0.25, None, 1, false), Some(from_db(-6.0) / 0.25));
+        // Up to -14 LUFS, 6 dB, stereo already adding 3 of it
+        let gain = Normalization::Loudness(-14.0).gain(0.1, Some(-23.01), 2, false).unwrap();
+        assert!((gain - from_db(6.0)).abs() < 1e-3);
+        // As far as the ceiling allows
+        assert_eq!(Normalization::Loudness(-14.0).gain(0.5, Some(-23.0), 1, false), Some(1.9));
+        assert_eq!(Normalization::Loudness(-14.0).gain(0.0, None, 1, false), None);
+        // Or all the way, with a limiter to take the peaks
+        let gain = Normalization::Loudness(-14.0).gain(0.5, Some(-23.0), 1, true).unwrap();
+        assert!((gain - from_db(9.0)).abs() < 1e-3);
+        assert_eq!(Normalization::default().gain(2.0, Some(-10.0), 2, true), None);
+
+        assert_eq!("lufs".parse(), Ok(Normalization::Loudness(-14.0)));
+        assert_eq!(" Peak:-0.5".parse(), Ok(Normalization::Peak(-0.5)));
+        let lufs = Normalization::Loudness(-23.0);
+        assert_eq!(lufs.to_string().parse(), Ok(lufs));
+        assert!("lufs:+3".parse::<Normalization>().is_err());
+        assert!("clip:-1".parse::<Normalization>().is_err());
+        assert!("loud".parse::<Normalization>().is_err());
+    }
+}
@@ -1,21 +1,163 @@
 use eframe::egui;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc;
 use std::thread;
+use std::time::Instant;
 
+use serde::{Deserialize, Serialize};
+
+use autotune_core::{
+    align, bookmarks, classifier, clock, decoder, demo_audio, dynamics, effects, energy, engine,
+    equalizer, fingerprint, format, harmony, keys, labels, loudness, midi, paths, pcm, pipeline,
+    pitch, prefilter, profiling, resample, routing, scale, scratch, sections, stretch, tint,
+    verify,
+};
+
+mod advisor;
+mod app_config;
 mod audio_processor;
+mod clipboard_audio;
+mod dawproject;
+mod devices;
+mod downloader;
+mod file_association;
+mod help;
+mod journal;
+mod layout;
+mod live;
+mod locks;
+mod monitor;
+mod onboarding;
+mod pitch_view;
+mod playback;
+mod presets;
+mod project_file;
+mod recorder;
+mod sandbox;
+mod single_instance;
+mod theme;
+mod timeline;
 mod ui;
+mod updater;
+mod waveform;
+
+use advisor::{Fix, Measurements, Setup};
+use app_config::ConfigLocation;
+use audio_processor::{
+    AudioProcessor, PartialRender, ProcessingProgress, ProcessingResult, RenderControl,
+    RenderOptions,
+};
+use bookmarks::Bookmark;
+use classifier::Classification;
+use dawproject::{ClipCorrection, Project, ProjectEvent};
+use devices::{DeviceList, DeviceSettings};
+use downloader::DownloadEvent;
+use dynamics::{Compressor, Meters};
+use effects::{ChainOrder, Stage};
+use energy::EnergyCurve;
+use engine::{Padding, Window};
+use equalizer::Equalizer;
+use fingerprint::Fingerprint;
+use format::{Format, FormatSettings, Separator, SizeUnits};
+use harmony::Interval;
+use help::Topic;
+use journal::Journal;
+use keys::KeyMatch;
+use labels::Label;
+use live::LiveSession;
+use locks::{Param, ParamSet};
+use loudness::Normalization;
+use midi::Melody;
+use monitor::Monitor;
+use onboarding::OnboardingStep;
+use pcm::BitDepth;
+use pipeline::OutputLength;
+use pitch::{F0Range, PitchAlgorithm};
+use playback::{Player, Source, Tracks};
+use prefilter::Gate;
+use presets::Preset;
+use project_file::ProjectFile;
+use recorder::{LevelMeter, Recorder};
+use routing::{Route, Voice};
+use sandbox::Sandbox;
+use scale::Scale;
+use scratch::ScratchSettings;
+use sections::Section;
+use single_instance::{Handoff, Instance};
+use theme::{Palette, ThemeSettings};
+use timeline::{ClipSettings, Timeline, TimelineEvent};
+use updater::{Release, UpdateEvent};
+use waveform::Envelope;
+
+// How much of the input is inspected when classifying content
+const CLASSIFY_SECONDS: f32 = 30.0;
+// Best matches offered after key detection
+const KEY_SUGGESTIONS: usize = 3;
+// Common concert pitches offered next to the A4 reference
+const A4_CHOICES: [f32; 5] = [415.0, 432.0, 440.0, 442.0, 443.0];
+// Harmony voices offered in the settings
+const HARMONY_CHOICES: [(Interval, &str); 3] =
+    [(Interval::THIRD, "3rd"), (Interval::FIFTH, "5th"), (Interval::OCTAVE, "Octave")];
+const DEFAULT_HARMONY_LEVEL: f32 = 0.5;
 
-use audio_processor::{AudioProcessor, ProcessingProgress, ProcessingResult};
+const GUI_SETTINGS_FILE: &str = "gui_settings.json";
+
+// Everything the GUI remembers between sessions
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct GuiSettings {
+    dock_state: Option<egui_dock::DockState<layout::Tab>>,
+    // The compact window instead of the dockable layout
+    compact: bool,
+    theme: ThemeSettings,
+    format: FormatSettings,
+    scratch: ScratchSettings,
+    devices: DeviceSettings,
+    onboarding_done: bool,
+    check_for_updates: bool,
+    monitor_while_processing: bool,
+    live_buffer_size: Option<u32>,
+    fixed_live_buffer_size: bool,
+    tune_after_recording: Option<bool>,
+    a4: Option<f32>,
+    fft_size: Option<usize>,
+    hop_size: Option<usize>,
+    window: Window,
+    padding: Padding,
+    native_sample_rate: bool,
+    output_rate: Option<u32>,
+    output_depth: Option<BitDepth>,
+    output_channels: Option<u16>,
+    normalization: Normalization,
+    limit_db: Option<f32>,
+    pitch_algorithm: PitchAlgorithm,
+    f0_range: Option<F0Range>,
+    correct_fry: bool,
+    confidence_strength: bool,
+    unvoiced_sensitivity: Option<f32>,
+    export_stems: bool,
+    float_master: bool,
+    waveform_view: waveform::View,
+    auto_select_key: bool,
+    locked: ParamSet,
+}
+
+const DEFAULT_LIVE_BUFFER_SIZE: u32 = 256;
 
 #[derive(Default)]
 pub struct AutotuneApp {
     // File paths
     input_file: Option<PathBuf>,
     output_file: Option<PathBuf>,
+    // Flatpak or Snap the app runs in, where files outside it come through the portals
+    sandbox: Option<Sandbox>,
 
-    // Audio processing
-    processor: AudioProcessor,
+    // URL input
+    url_input: String,
+    download_progress: Option<f32>,
+    download_receiver: Option<mpsc::Receiver<DownloadEvent>>,
 
     // UI state
     is_processing: bool,
@@ -26,47 +168,534 @@ pub struct AutotuneApp {
     // Communication with processing thread
     progress_receiver: Option<mpsc::Receiver<ProcessingProgress>>,
     result_receiver: Option<mpsc::Receiver<ProcessingResult>>,
+    cancel_processing: Arc<AtomicBool>,
+
+    // Listen to the render while it runs
+    monitor_while_processing: bool,
+    monitor: Option<Monitor>,
+
+    // Preview of the last render
+    player: Option<Player>,
+    player_receiver: Option<mpsc::Receiver<Result<Tracks, String>>>,
+
+    // Setting whose explanation is open, and its "hear the difference" renders as low (A)
+    // and high (B)
+    help_topic: Option<Topic>,
+    help_player: Option<Player>,
+    help_receiver: Option<mpsc::Receiver<Result<Tracks, String>>>,
+
+    // Peak envelopes of the input and of the last render
+    input_waveform: Option<Envelope>,
+    input_waveform_receiver: Option<mpsc::Receiver<Result<Envelope, String>>>,
+    output_waveform: Option<Envelope>,
+    output_waveform_receiver: Option<mpsc::Receiver<Result<Envelope, String>>>,
+    waveform_view: waveform::View,
+
+    // Labels imported for the input; only their regions are corrected when there are any
+    regions: Vec<Label>,
+    regions_source: Option<String>,
+    // In and out points dragged on the waveform: only that stretch is corrected
+    selection: Option<(f32, f32)>,
+    // Stretches of the input corrected with settings of their own
+    sections: Vec<Section>,
+    // Spots in the input to jump back to, and where the waveform was last clicked or
+    // jumped to, where bookmarks go without playback
+    bookmarks: Vec<Bookmark>,
+    cursor_secs: Option<f32>,
+    go_to_text: String,
+
+    // Live microphone mode
+    live_session: Option<LiveSession>,
+    live_buffer_size: u32,
+    // Keep the buffer size even when live mode glitches, instead of raising it
+    fixed_live_buffer_size: bool,
+    live_error: Option<String>,
+    // Why the buffer size was raised, or what else to try
+    live_notice: Option<String>,
+    // Glitching at the largest buffer size was already reported
+    live_buffer_exhausted: bool,
+
+    // Audio devices, with those connected scanned when the tab is shown
+    devices: DeviceSettings,
+    device_list: Option<DeviceList>,
+    // Playing until it has been heard
+    test_tone: Option<Monitor>,
+    device_error: Option<String>,
+
+    // Takes recorded from the microphone
+    recorder: Option<Recorder>,
+    record_meters: Vec<LevelMeter>,
+    record_metered_at: Option<Instant>,
+    // Start processing a take as soon as recording stops
+    tune_after_recording: bool,
+    record_error: Option<String>,
+
+    // Multi-clip timeline
+    timeline: Timeline,
+    selected_clip: Option<usize>,
+    timeline_receiver: Option<mpsc::Receiver<TimelineEvent>>,
+    timeline_progress: f32,
+    timeline_cancel: Arc<AtomicBool>,
+    timeline_status: Option<String>,
+
+    // DAWproject session whose clips are picked for correction
+    daw_project: Option<Project>,
+    // Per clip of `daw_project`
+    daw_selected: Vec<bool>,
+    daw_receiver: Option<mpsc::Receiver<ProjectEvent>>,
+    daw_progress: f32,
+    daw_cancel: Arc<AtomicBool>,
+    daw_status: Option<String>,
 
     // Musical settings
     selected_key: usize,
+    // Keys the input fits best, from the last key detection
+    key_matches: Vec<KeyMatch>,
+    key_receiver: Option<mpsc::Receiver<Result<Vec<KeyMatch>, String>>>,
+    // Select the best match as soon as detection finishes
+    auto_select_key: bool,
+    // Settings presets and projects leave as they are
+    locked: ParamSet,
+    // Settings shared by every timeline clip, changing one clip's changes them all
+    linked: ParamSet,
+    // Notes to correct to instead of the key's, when enabled
+    use_custom_scale: bool,
+    custom_scale: Scale,
+    // MIDI melody the notes are corrected to, and the file it was read from
+    melody: Option<(PathBuf, Melody)>,
+    // Root and preset the custom scale was last built from
+    scale_root: usize,
+    scale_preset: usize,
     selected_note: i32,
     octave: i32,
     formant_shift: i32,
+    // Concert pitch the notes are tuned to, in Hz
+    a4: f32,
 
     // Autotune parameters
     pitch_correction_strength: f32,
+    // Scale the strength with the level of the voice along the curve
+    energy_strength: bool,
+    energy_curve: EnergyCurve,
     transition_speed: f32,
+    // Frame layout of the correction, and the window its frames are crossfaded with
+    fft_size: usize,
+    hop_size: usize,
+    window: Window,
+    padding: Padding,
+    // Order of the effects around the correction, and the settings of each
+    chain: ChainOrder,
+    high_pass: bool,
+    high_pass_hz: f32,
+    noise_gate: bool,
+    gate: Gate,
+    eq: bool,
+    equalizer: Equalizer,
+    compress: bool,
+    compressor: Compressor,
+    // How far the compressor and the limiter turn the render down, while it runs
+    meters: Arc<Meters>,
+    // Saved by the user, on top of the factory presets
+    user_presets: Vec<Preset>,
+    // Name typed in for saving the current settings as a preset
+    preset_name: String,
+    use_all_cores: bool,
+    // Process at the file's own rate even outside the preferred range
+    native_sample_rate: bool,
+    // Rate renders are written at, the input's own when None
+    output_rate: Option<u32>,
+    // Frames renders are written with
+    length: OutputLength,
+    // Sample format and channel count renders are written in, the input's when None
+    output_depth: Option<BitDepth>,
+    output_channels: Option<u16>,
+    // How the level of renders is set, and the ceiling the limiter holds their peaks under
+    normalization: Normalization,
+    limiter: bool,
+    limit_db: f32,
+    // How the pitch the note is chosen from is found, and the frequencies it is looked for in
+    pitch_algorithm: PitchAlgorithm,
+    f0_range: Option<F0Range>,
+    // Correct vocal fry instead of passing it through
+    correct_fry: bool,
+    // Keep less of the correction where the pitch is found less surely
+    confidence_strength: bool,
+    // How readily consonants and breaths pass through uncorrected, 0.0 for never
+    unvoiced_sensitivity: f32,
+    // Write the dry and corrected layers next to renders
+    export_stems: bool,
+    // Write the unnormalized float render next to renders
+    float_master: bool,
+    // Voices sung with the lead, and the level they are mixed in at
+    harmony: Vec<Interval>,
+    harmony_level: f32,
+    // Levels the lead and each voice go into the channels of stereo renders at, over the
+    // spread of the voices `width` of the way out
+    routing: Vec<Route>,
+    width: f32,
+    // Reference vocal whose tone renders are matched to, and how far
+    tint_reference: Option<PathBuf>,
+    tint_amount: f32,
 
     // Audio info
     sample_rate: Option<f32>,
     duration: Option<f32>,
     channels: Option<u16>,
+    classification: Option<Classification>,
+    classification_receiver: Option<mpsc::Receiver<Result<Option<Classification>, String>>>,
+    // What the input measured, to check the settings against before a render
+    measurements: Option<Measurements>,
+    measurement_receiver: Option<mpsc::Receiver<Result<Measurements, String>>>,
+
+    // Layout
+    dock_state: Option<egui_dock::DockState<layout::Tab>>,
+    // Showing the compact window instead of the dock
+    compact: bool,
+    analysis_detached: bool,
+    theme: ThemeSettings,
+    // How durations, sizes, and rates are written
+    format: FormatSettings,
+    // Where renders spool and jobs stage their files
+    scratch: ScratchSettings,
+
+    // First-run walkthrough
+    onboarding_step: Option<OnboardingStep>,
+    onboarding_done: bool,
+    onboarding_reference: Option<PathBuf>,
+
+    // Settings storage (user profile or next to the executable)
+    config: ConfigLocation,
+    // Later launches hand their files to this window
+    instance: Option<Instance>,
+    // "Open with Autotune" is in the file manager
+    open_with_registered: bool,
+
+    // Activity log
+    session_start: Option<Instant>,
+    activity_log: Vec<String>,
+    journal: Journal,
+
+    // Update checks (opt-in)
+    check_for_updates: bool,
+    update_receiver: Option<mpsc::Receiver<UpdateEvent>>,
+    available_update: Option<Release>,
+    update_status: Option<String>,
 }
 
 impl AutotuneApp {
-    pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Self {
+    pub fn new(
+        cc: &eframe::CreationContext<'_>,
+        config: ConfigLocation,
+        file: Option<PathBuf>,
+    ) -> Self {
+        let ctx = cc.egui_ctx.clone();
+        let instance = Instance::listen(&config, move || ctx.request_repaint())
+            .map_err(|e| log::warn!("Other launches can't hand files to this window: {}", e))
+            .ok();
+        let mut settings: GuiSettings = config.load(GUI_SETTINGS_FILE).unwrap_or_default();
+        if let Some(dock_state) = &mut settings.dock_state {
+            layout::add_missing_tabs(dock_state);
+        }
+        settings.theme.apply(&cc.egui_ctx);
+        format::set(Format::new(settings.format));
+        scratch::set(settings.scratch.clone());
+        devices::set(settings.devices.clone());
+        fingerprint::set_index(Some(config.file(app_config::RENDER_INDEX_FILE)));
+        let user_presets = presets::load_user_presets(&config);
+        let frames = settings
+            .fft_size
+            .zip(settings.hop_size)
+            .filter(|&(fft_size, hop_size)| engine::validate_frames(fft_size, hop_size).is_ok())
+            .unwrap_or((engine::DEFAULT_FFT_SIZE, engine::DEFAULT_HOP_SIZE));
+
+        let mut app = Self {
             selected_key: 0,  // C Major
             selected_note: 0, // Auto mode
             octave: 2,
             formant_shift: 0,
             pitch_correction_strength: 0.8,
+            energy_strength: false,
+            energy_curve: EnergyCurve::default(),
             transition_speed: 0.1,
+            chain: ChainOrder::default(),
+            high_pass: false,
+            high_pass_hz: prefilter::DEFAULT_HIGH_PASS_HZ,
+            noise_gate: false,
+            gate: Gate::default(),
+            eq: false,
+            equalizer: Equalizer::default(),
+            compress: false,
+            compressor: Compressor::default(),
             processing_status: "Ready".to_string(),
+            dock_state: settings.dock_state,
+            compact: settings.compact,
+            theme: settings.theme,
+            format: settings.format,
+            scratch: settings.scratch,
+            devices: settings.devices,
+            onboarding_step: (!settings.onboarding_done).then_some(OnboardingStep::Welcome),
+            onboarding_done: settings.onboarding_done,
+            config,
+            instance,
+            sandbox: Sandbox::detect(),
+            open_with_registered: file_association::is_registered(),
+            session_start: Some(Instant::now()),
+            check_for_updates: settings.check_for_updates,
+            monitor_while_processing: settings.monitor_while_processing,
+            live_buffer_size: settings.live_buffer_size.unwrap_or(DEFAULT_LIVE_BUFFER_SIZE),
+            fixed_live_buffer_size: settings.fixed_live_buffer_size,
+            tune_after_recording: settings.tune_after_recording.unwrap_or(true),
+            a4: settings
+                .a4
+                .filter(|a4| pipeline::A4_RANGE.contains(a4))
+                .unwrap_or(pipeline::STANDARD_A4),
+            fft_size: frames.0,
+            hop_size: frames.1,
+            window: settings.window,
+            padding: settings.padding,
+            native_sample_rate: settings.native_sample_rate,
+            output_rate: settings.output_rate,
+            length: OutputLength::Input,
+            output_depth: settings.output_depth,
+            output_channels: settings.output_channels.filter(|channels| (1..=2).contains(channels)),
+            normalization: settings.normalization,
+            limiter: settings.limit_db.is_some(),
+            limit_db: settings
+                .limit_db
+                .unwrap_or(dynamics::DEFAULT_CEILING_DB)
+                .clamp(*dynamics::CEILING_RANGE.start(), *dynamics::CEILING_RANGE.end()),
+            pitch_algorithm: settings.pitch_algorithm,
+            f0_range: settings.f0_range.filter(|range| range.validate().is_ok()),
+            correct_fry: settings.correct_fry,
+            confidence_strength: settings.confidence_strength,
+            unvoiced_sensitivity: settings
+                .unvoiced_sensitivity
+                .unwrap_or(pipeline::DEFAULT_UNVOICED_SENSITIVITY)
+                .clamp(0.0, 1.0),
+            export_stems: settings.export_stems,
+            float_master: settings.float_master,
+            tint_amount: 1.0,
+            harmony_level: DEFAULT_HARMONY_LEVEL,
+            waveform_view: settings.waveform_view,
+            auto_select_key: settings.auto_select_key,
+            locked: settings.locked,
+            linked: ParamSet::default(),
+            user_presets,
             ..Default::default()
+        };
+
+        if app.compact {
+            layout::resize_for(&cc.egui_ctx, true);
+        }
+        if app.check_for_updates {
+            app.start_update_check();
+        }
+        if let Some(file) = file {
+            app.open_path(file);
+        }
+        app
+    }
+
+    /// Open a file given from outside the app, like "Open with": a project, or else an
+    /// input to tune.
+    fn open_path(&mut self, path: PathBuf) {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case(project_file::EXTENSION)) {
+            self.load_project_file(&path);
+        } else {
+            self.default_output_next_to(&path);
+            self.set_input_file(path);
+        }
+    }
+
+    fn update_handoffs(&mut self, ctx: &egui::Context) {
+        let Some(instance) = &self.instance else {
+            return;
+        };
+        let handoffs: Vec<Handoff> = std::iter::from_fn(|| instance.try_recv()).collect();
+        for handoff in handoffs {
+            match handoff {
+                Handoff::Open(path) if self.is_processing => self.log_event(format!(
+                    "Can't open {} while processing, try again once it's done",
+                    path.display()
+                )),
+                Handoff::Open(path) => self.open_path(path),
+                Handoff::Focus => {}
+            }
+            ctx.send_viewport_cmd(egui::ViewportCommand::Minimized(false));
+            ctx.send_viewport_cmd(egui::ViewportCommand::Focus);
         }
     }
 
+    // In a Flatpak or Snap, the dialogs are the desktop portal's, which grant the app the
+    // files picked in them
     fn select_input_file(&mut self) {
         if let Some(path) = rfd::FileDialog::new()
-            .add_filter("WAV Audio", &["wav"])
-            .set_title("Select Input WAV File")
+            .add_filter("Audio", &decoder::INPUT_EXTENSIONS)
+            .set_title("Select Input Audio File")
             .pick_file()
         {
-            self.input_file = Some(path.clone());
-            self.load_audio_info(&path);
-            self.processing_result = None;
-            log::info!("Selected input file: {:?}", path);
+            self.set_input_file(path);
+        }
+    }
+
+    fn set_input_file(&mut self, path: PathBuf) {
+        self.input_file = Some(path.clone());
+        self.load_audio_info(&path);
+        self.processing_result = None;
+        self.regions.clear();
+        self.regions_source = None;
+        self.selection = None;
+        self.sections.clear();
+        self.bookmarks.clear();
+        self.cursor_secs = None;
+        self.help_player = None;
+        self.log_event(format!("Selected input file: {}", path.display()));
+    }
+
+    fn default_output_next_to(&mut self, input: &std::path::Path) {
+        // A file the portal handed in is the only one of its folder the app can reach
+        if self.output_file.is_none() && sandbox::is_portal_document(input) {
+            self.log_event("Pick an output with Browse, the sandbox can't write next to the input");
+            return;
+        }
+        if self.output_file.is_none() {
+            let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+            self.output_file = Some(input.with_file_name(format!("{}_tuned.wav", stem)));
+        }
+    }
+
+    fn paste_audio(&mut self) {
+        let pasted = clipboard_audio::read_clipboard_wav().and_then(|bytes| match bytes {
+            Some(bytes) => {
+                clipboard_audio::save_pasted_wav(&bytes, &clipboard_audio::paste_dir()).map(Some)
+            }
+            None => Ok(None),
+        });
+
+        match pasted {
+            Ok(Some(path)) => {
+                // Quick fixes of pasted snippets shouldn't require picking an output first
+                self.default_output_next_to(&path);
+                self.set_input_file(path);
+            }
+            Ok(None) => {
+                self.processing_status = "Clipboard doesn't contain WAV audio".to_string();
+            }
+            Err(e) => {
+                self.processing_status = format!("Paste failed: {}", e);
+                self.log_event(format!("Paste failed: {}", e));
+            }
+        }
+    }
+
+    fn start_download(&mut self) {
+        let url = self.url_input.trim().to_string();
+        if let Err(e) = downloader::file_name_from_url(&url) {
+            self.processing_status = e;
+            return;
+        }
+
+        let (event_tx, event_rx) = mpsc::channel();
+        self.download_receiver = Some(event_rx);
+        self.download_progress = Some(0.0);
+        self.log_event(format!("Downloading {}", url));
+
+        thread::spawn(move || downloader::download(&url, &downloader::download_dir(), event_tx));
+    }
+
+    fn update_download_status(&mut self) {
+        let Some(ref receiver) = self.download_receiver else {
+            return;
+        };
+
+        let mut finished = None;
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                DownloadEvent::Progress(progress) => self.download_progress = Some(progress),
+                DownloadEvent::Done(path) => finished = Some(Ok(path)),
+                DownloadEvent::Failed(err) => finished = Some(Err(err)),
+            }
+        }
+
+        match finished {
+            Some(Ok(path)) => {
+                self.download_receiver = None;
+                self.download_progress = None;
+                self.default_output_next_to(&path);
+                self.set_input_file(path);
+            }
+            Some(Err(err)) => {
+                self.download_receiver = None;
+                self.download_progress = None;
+                self.processing_status = err.clone();
+                self.log_event(err);
+            }
+            None => {}
+        }
+    }
+
+    fn start_update_check(&mut self) {
+        let (event_tx, event_rx) = mpsc::channel();
+        self.update_receiver = Some(event_rx);
+        self.update_status = Some("Checking for updates...".to_string());
+
+        thread::spawn(move || {
+            let _ = event_tx.send(updater::check_for_update());
+        });
+    }
+
+    fn start_update_download(&mut self) {
+        let Some(asset) = self.available_update.as_ref().and_then(Release::asset_for_platform)
+        else {
+            return;
+        };
+
+        let asset = asset.clone();
+        let (event_tx, event_rx) = mpsc::channel();
+        self.update_receiver = Some(event_rx);
+        self.update_status = Some(format!("Downloading {}...", asset.name));
+        self.log_event(format!("Downloading update {}", asset.name));
+
+        thread::spawn(move || {
+            let dest_dir = downloader::download_dir().join("updates");
+            let _ = event_tx.send(updater::download_asset(&asset, &dest_dir));
+        });
+    }
+
+    fn update_update_status(&mut self) {
+        let Some(event) = self.update_receiver.as_ref().and_then(|rx| rx.try_recv().ok()) else {
+            return;
+        };
+        self.update_receiver = None;
+
+        match event {
+            UpdateEvent::Available(release) => {
+                self.update_status = None;
+                self.log_event(format!("Update available: {}", release.title()));
+                self.available_update = Some(release);
+            }
+            UpdateEvent::UpToDate => {
+                self.update_status = Some(format!("Up to date (v{})", updater::CURRENT_VERSION));
+            }
+            UpdateEvent::Downloaded(path) => {
+                let message = format!("Update saved to {}", path.display());
+                self.log_event(message.clone());
+                self.update_status = Some(message);
+            }
+            UpdateEvent::Failed(err) => {
+                self.log_event(err.clone());
+                self.update_status = Some(err);
+            }
+        }
+    }
+
+    fn handle_pasted_text(&mut self, text: &str) {
+        match clipboard_audio::path_from_text(text) {
+            Some(path) if path.is_file() => self.set_input_file(path),
+            Some(path) => {
+                self.processing_status = format!("Pasted file not found: {}", path.display());
+            }
+            None => self.paste_audio(),
         }
     }
 
@@ -77,42 +706,228 @@ impl AutotuneApp {
             .set_file_name("autotuned.wav")
             .save_file()
         {
+            self.log_event(format!("Selected output file: {}", path.display()));
             self.output_file = Some(path);
-            log::info!("Selected output file: {:?}", self.output_file);
+        }
+    }
+
+    // Copy the identical earlier `render` to the output instead of rendering it again
+    fn reuse_render(&mut self, render: &std::path::Path, fingerprint: Fingerprint) {
+        let Some(output) = self.output_file.clone() else {
+            return;
+        };
+        match fingerprint::reuse(render, &output, fingerprint) {
+            Ok(()) => {
+                self.processing_status = "Reused an identical render".to_string();
+                self.log_event(format!("Copied {} to {}", render.display(), output.display()));
+                self.processing_result = None;
+                self.load_playback();
+                self.output_waveform_receiver = Some(Envelope::load_in_background(output));
+            }
+            Err(e) => {
+                self.processing_status = format!("Error: {}", e);
+                self.log_event(format!("Reusing the render failed: {}", e));
+            }
+        }
+    }
+
+    fn save_partial_render(&mut self, partial: &PartialRender) {
+        let stem = self
+            .output_file
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map_or("autotuned".into(), |stem| stem.to_string_lossy());
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("WAV Audio", &["wav"])
+            .set_title("Save Partial Result")
+            .set_file_name(format!("{}_partial.wav", stem))
+            .save_file()
+        else {
+            return;
+        };
+
+        match partial.save(&path) {
+            Ok(()) => {
+                self.processing_status = format!("Saved partial result to {}", path.display());
+                self.log_event(format!(
+                    "Saved {} partial result to {}",
+                    format::duration(partial.duration_secs()),
+                    path.display()
+                ));
+            }
+            Err(e) => {
+                self.processing_status = format!("Error: {}", e);
+                self.log_event(format!("Saving partial result failed: {}", e));
+            }
         }
     }
 
     fn load_audio_info(&mut self, path: &PathBuf) {
-        match hound::WavReader::open(path) {
-            Ok(reader) => {
-                let spec = reader.spec();
-                self.sample_rate = Some(spec.sample_rate as f32);
-                self.channels = Some(spec.channels);
-                self.duration = Some(reader.duration() as f32 / spec.sample_rate as f32);
+        match decoder::probe(path) {
+            Ok(info) => {
+                self.sample_rate = Some(info.sample_rate as f32);
+                self.channels = Some(info.channels);
+                self.duration = info.duration_secs();
+                let length = self.duration.map_or("unknown length".to_string(), format::duration);
+                let size = std::fs::metadata(path)
+                    .map_or(String::new(), |file| format!(", {}", format::file_size(file.len())));
                 self.processing_status = format!(
-                    "Loaded: {:.1}s, {}Hz, {} ch",
-                    self.duration.unwrap(),
-                    spec.sample_rate,
-                    spec.channels
+                    "Loaded: {}, {}, {} ch{}",
+                    length,
+                    format::sample_rate(info.sample_rate as f32),
+                    info.channels,
+                    size
                 );
+                self.input_waveform = None;
+                self.output_waveform = None;
+                self.input_waveform_receiver = Some(Envelope::load_in_background(path.clone()));
+                self.key_matches.clear();
+                self.key_receiver = None;
+                self.start_measuring(path.clone());
             }
             Err(e) => {
+                let hint = self.sandbox.and_then(|sandbox| sandbox.access_hint(path));
+                let e = match hint {
+                    Some(hint) => format!("{} ({})", e, hint),
+                    None => e.to_string(),
+                };
                 self.processing_status = format!("Error loading file: {}", e);
+                self.log_event(format!("Error loading file: {}", e));
                 self.sample_rate = None;
                 self.duration = None;
                 self.channels = None;
+                self.classification = None;
+                self.classification_receiver = None;
+                self.measurements = None;
+                self.measurement_receiver = None;
+                return;
+            }
+        }
+
+        self.start_classifying(path.clone());
+    }
+
+    // Decoding up to CLASSIFY_SECONDS of the input takes a moment, so it's done off the UI thread
+    fn start_classifying(&mut self, path: PathBuf) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let classified = AudioProcessor::read_mono_excerpt(&path, CLASSIFY_SECONDS)
+                .map(|(samples, sample_rate)| classifier::classify(&samples, sample_rate as f32));
+            let _ = sender.send(classified);
+        });
+        self.classification_receiver = Some(receiver);
+        self.classification = None;
+    }
+
+    fn update_classification(&mut self) {
+        let Some(classified) =
+            self.classification_receiver.as_ref().and_then(|r| r.try_recv().ok())
+        else {
+            return;
+        };
+        self.classification_receiver = None;
+        self.classification = match classified {
+            Ok(classification) => classification,
+            Err(e) => {
+                log::warn!("Content classification skipped: {}", e);
+                None
+            }
+        };
+
+        // Pre-select defaults that suit the detected material, except for locked settings
+        if let Some(classification) = self.classification {
+            log::info!(
+                "Classified input as {:?}: {:?}",
+                classification.class,
+                classification.features
+            );
+            self.take_settings(self.suggested_settings(classification));
+        }
+    }
+
+    fn start_measuring(&mut self, path: PathBuf) {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(Measurements::of_file(&path));
+        });
+        self.measurement_receiver = Some(receiver);
+        self.measurements = None;
+    }
+
+    fn update_measurements(&mut self) {
+        let Some(measured) = self.measurement_receiver.as_ref().and_then(|r| r.try_recv().ok())
+        else {
+            return;
+        };
+        self.measurement_receiver = None;
+        match measured {
+            Ok(measurements) => self.measurements = Some(measurements),
+            Err(e) => log::warn!("Settings checks skipped: {}", e),
+        }
+    }
+
+    fn start_key_detection(&mut self) {
+        let Some(path) = self.input_file.clone() else {
+            return;
+        };
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(keys::detect_file(&path));
+        });
+        self.key_receiver = Some(receiver);
+        self.key_matches.clear();
+    }
+
+    fn update_key_detection(&mut self) {
+        let Some(detected) = self.key_receiver.as_ref().and_then(|r| r.try_recv().ok()) else {
+            return;
+        };
+        self.key_receiver = None;
+        match detected {
+            Ok(matches) => match matches.first() {
+                Some(best) => {
+                    self.log_event(format!(
+                        "Detected key: {} (match {:.2})",
+                        ui::get_key_name(best.key),
+                        best.score
+                    ));
+                    if self.auto_select_key {
+                        self.selected_key = best.key;
+                    }
+                    self.key_matches = matches;
+                }
+                None => {
+                    self.processing_status = "Not enough pitched audio to detect a key".to_string();
+                }
+            },
+            Err(e) => {
+                self.processing_status = format!("Key detection failed: {}", e);
+                self.log_event(format!("Key detection failed: {}", e));
             }
         }
     }
 
-    fn start_processing(&mut self) {
+    /// Render the input to the output in the background. With `check_duplicates`, an
+    /// identical earlier render is offered instead of rendering when there is one.
+    fn start_processing(&mut self, check_duplicates: bool) {
         if self.input_file.is_none() || self.output_file.is_none() {
             self.processing_status = "Please select input and output files".to_string();
             return;
         }
+        if let Err(e) = engine::validate_frames(self.fft_size, self.hop_size) {
+            self.processing_status = format!("Error: {}", e);
+            return;
+        }
 
         let input_path = self.input_file.as_ref().unwrap().clone();
         let output_path = self.output_file.as_ref().unwrap().clone();
+        if (self.export_stems || self.float_master) && !self.can_write_beside_output() {
+            self.log_event(
+                "Skipping the stems and float master, the sandbox can't write next to the output",
+            );
+        }
+        let started =
+            format!("Processing started: {} → {}", input_path.display(), output_path.display());
 
         // Create channels for progress updates
         let (progress_tx, progress_rx) = mpsc::channel();
@@ -122,31 +937,74 @@ impl AutotuneApp {
         self.result_receiver = Some(result_rx);
 
         // Configure autotune settings
-        let autotune_config = synthphone_vocals::AutotuneConfig {
-            fft_size: 1024,
-            hop_size: 256,
-            sample_rate: self.sample_rate.unwrap_or(44100.0),
-            pitch_correction_strength: self.pitch_correction_strength,
-            transition_speed: self.transition_speed,
-            ..Default::default()
-        };
+        let autotune_config = self.autotune_config();
+        let musical_settings = self.musical_settings();
+        let scale = self.scale();
+        let melody = self.melody.as_ref().map(|(_, melody)| melody.clone());
+        let a4 = self.a4;
+        let options = self.render_options();
 
-        let musical_settings = synthphone_vocals::MusicalSettings {
-            key: self.selected_key as i32,
-            note: self.selected_note,
-            octave: self.octave,
-            formant: self.formant_shift,
+        let regions = match self.selection {
+            Some((start, end)) => labels::within(&self.regions, start, end),
+            None => self.regions.clone(),
+        };
+        self.player = None;
+        self.output_waveform = None;
+        self.cancel_processing = Arc::new(AtomicBool::new(false));
+        let mut control = RenderControl {
+            cancel: self.cancel_processing.clone(),
+            meters: self.meters.clone(),
+            ..Default::default()
         };
+        self.monitor = None;
+        if self.monitor_while_processing {
+            let rate = options.processing_rate(autotune_config.sample_rate as u32);
+            match Monitor::start(rate, None) {
+                Ok(monitor) => {
+                    control.monitor = Some(monitor.feed());
+                    self.monitor = Some(monitor);
+                }
+                Err(e) => self.log_event(format!("Monitoring unavailable: {}", e)),
+            }
+        }
 
         // Start processing in background thread
         thread::spawn(move || {
+            let fingerprint = AudioProcessor::fingerprint(
+                &input_path,
+                &output_path,
+                &autotune_config,
+                &musical_settings,
+                scale,
+                melody.as_ref(),
+                a4,
+                &regions,
+                &options,
+            );
+            if let Some(fingerprint) = fingerprint.filter(|_| check_duplicates) {
+                if let Some(render) = fingerprint::find(fingerprint) {
+                    let _ = result_tx.send(ProcessingResult::Duplicate { render, fingerprint });
+                    return;
+                }
+            }
             let result = AudioProcessor::process_file(
                 &input_path,
                 &output_path,
                 autotune_config,
                 musical_settings,
+                scale,
+                melody,
+                a4,
+                regions,
+                options,
+                control,
                 progress_tx,
             );
+            if let (Some(fingerprint), ProcessingResult::Success { .. }) = (fingerprint, &result) {
+                if let Err(e) = fingerprint::record(fingerprint, &output_path) {
+                    log::warn!("The render won't be offered again: {}", e);
+                }
+            }
 
             let _ = result_tx.send(result);
         });
@@ -154,240 +1012,3836 @@ impl AutotuneApp {
         self.is_processing = true;
         self.processing_progress = 0.0;
         self.processing_status = "Processing...".to_string();
+        self.log_event(started);
         self.processing_result = None;
     }
 
-    fn update_processing_status(&mut self) {
-        // Check for progress updates
-        if let Some(ref receiver) = self.progress_receiver {
-            while let Ok(progress) = receiver.try_recv() {
-                match progress {
-                    ProcessingProgress::Progress(percent) => {
-                        self.processing_progress = percent;
-                        self.processing_status = format!("Processing... {:.1}%", percent * 100.0);
-                    }
-                    ProcessingProgress::Status(status) => {
-                        self.processing_status = status;
-                    }
-                }
-            }
-        }
+    // Whether side files, like the stems, can be written next to the output. An output on
+    // its own in the document portal is the only file of its folder the sandbox can write
+    fn can_write_beside_output(&self) -> bool {
+        !self.output_file.as_deref().is_some_and(sandbox::is_portal_document)
+    }
 
-        // Check for completion
-        if let Some(ref receiver) = self.result_receiver {
-            if let Ok(result) = receiver.try_recv() {
-                self.is_processing = false;
-                self.processing_result = Some(result.clone());
+    fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            threads: if self.use_all_cores {
+                engine::available_threads()
+            } else {
+                1
+            },
+            window: self.window,
+            padding: self.padding,
+            pitch_algorithm: self.pitch_algorithm,
+            f0_range: self.f0_range,
+            adapt_sample_rate: !self.native_sample_rate,
+            correct_fry: self.correct_fry,
+            confidence_strength: self.confidence_strength,
+            unvoiced_sensitivity: self.unvoiced_sensitivity,
+            tint_reference: self.tint_reference.clone(),
+            tint_amount: self.tint_amount,
+            output_rate: self.output_rate,
+            length: self.length,
+            output_depth: self.output_depth,
+            output_channels: self.output_channels,
+            normalization: self.normalization,
+            stems: self.export_stems && self.can_write_beside_output(),
+            float_master: self.float_master && self.can_write_beside_output(),
+            harmony: self.harmony.clone(),
+            harmony_level: self.harmony_level,
+            routing: self.routes(),
+            sections: self.sections.clone(),
+            energy: self.energy_strength.then_some(self.energy_curve),
+            chain: self.chain,
+            high_pass_hz: self.high_pass.then_some(self.high_pass_hz),
+            gate: self.noise_gate.then_some(self.gate),
+            eq: self.eq.then_some(self.equalizer),
+            compressor: self.compress.then_some(self.compressor),
+            limit_db: self.limiter.then_some(self.limit_db),
+        }
+    }
 
-                match result {
-                    ProcessingResult::Success { duration_ms, .. } => {
-                        self.processing_status =
-                            format!("Completed in {:.2}s!", duration_ms / 1000.0);
-                    }
-                    ProcessingResult::Error(err) => {
-                        self.processing_status = format!("Error: {}", err);
-                    }
-                }
+    fn autotune_config(&self) -> synthphone_vocals::AutotuneConfig {
+        synthphone_vocals::AutotuneConfig {
+            fft_size: self.fft_size,
+            hop_size: self.hop_size,
+            sample_rate: self.sample_rate.unwrap_or(44100.0),
+            pitch_correction_strength: self.pitch_correction_strength,
+            transition_speed: self.transition_speed,
+            ..Default::default()
+        }
+    }
 
-                // Clean up channels
-                self.progress_receiver = None;
-                self.result_receiver = None;
-            }
+    fn musical_settings(&self) -> synthphone_vocals::MusicalSettings {
+        synthphone_vocals::MusicalSettings {
+            key: self.selected_key as i32,
+            note: self.selected_note,
+            octave: self.octave,
+            formant: self.formant_shift,
         }
     }
-}
 
-impl eframe::App for AutotuneApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        self.update_processing_status();
+    /// The custom scale, if one is enabled and has any notes.
+    fn scale(&self) -> Option<Scale> {
+        (self.use_custom_scale && !self.custom_scale.is_empty()).then_some(self.custom_scale)
+    }
 
-        // Request repaint if processing
-        if self.is_processing {
-            ctx.request_repaint();
+    /// The current main settings, as a starting point for a timeline clip.
+    fn clip_settings(&self) -> ClipSettings {
+        ClipSettings {
+            key: self.selected_key as i32,
+            note: self.selected_note,
+            octave: self.octave,
+            formant: self.formant_shift,
+            strength: self.pitch_correction_strength,
+            transition: self.transition_speed,
+            a4: self.a4,
         }
+    }
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("🎵 Desktop Autotune Application");
-            ui.separator();
-
-            // File selection section
-            ui.group(|ui| {
-                ui.label("📁 File Selection");
+    /// Take the settings of a preset or project, except for the locked ones.
+    fn take_settings(&mut self, settings: ClipSettings) {
+        let current = self.clip_settings();
+        let kept: Vec<&str> =
+            self.locked.overridden(&current, &settings).iter().map(|param| param.name()).collect();
+        if !kept.is_empty() {
+            self.log_event(format!("Kept locked {}", kept.join(", ")));
+        }
+        let settings = self.locked.keep(&current, settings);
+        self.selected_key = settings.key as usize;
+        self.selected_note = settings.note;
+        self.octave = settings.octave;
+        self.formant_shift = settings.formant;
+        self.pitch_correction_strength = settings.strength;
+        self.transition_speed = settings.transition;
+        self.a4 = settings.a4;
+    }
 
-                ui.horizontal(|ui| {
-                    if ui.button("Select Input WAV").clicked() {
-                        self.select_input_file();
-                    }
+    fn add_timeline_clips(&mut self) {
+        let Some(paths) = rfd::FileDialog::new()
+            .add_filter("Audio", &decoder::INPUT_EXTENSIONS)
+            .set_title("Add Clips to the Timeline")
+            .pick_files()
+        else {
+            return;
+        };
 
-                    if let Some(ref path) = self.input_file {
-                        ui.label(format!("📄 {}", path.file_name().unwrap().to_string_lossy()));
-                    } else {
-                        ui.label("No file selected");
-                    }
-                });
+        let mut settings = self.clip_settings();
+        // New clips share the linked settings of the ones already there
+        if let Some(clip) = self.timeline.clips.first() {
+            settings = self.linked.keep(&clip.settings, settings);
+        }
+        for path in paths {
+            match self.timeline.add(&path, settings) {
+                Ok(()) => {
+                    self.selected_clip = Some(self.timeline.clips.len() - 1);
+                    self.log_event(format!("Added {} to the timeline", path.display()));
+                }
+                Err(e) => self.log_event(format!("Can't add {}: {}", path.display(), e)),
+            }
+        }
+    }
 
+    fn start_timeline_render(&mut self) {
+        let Some(output) = rfd::FileDialog::new()
+            .add_filter("WAV files", &["wav"])
+            .set_title("Export Timeline")
+            .set_file_name("timeline.wav")
+            .save_file()
+        else {
+            return;
+        };
+
+        let (event_tx, event_rx) = mpsc::channel();
+        let timeline = self.timeline.clone();
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.timeline_cancel = cancel.clone();
+        self.timeline_receiver = Some(event_rx);
+        self.timeline_progress = 0.0;
+        self.timeline_status = None;
+        self.log_event(format!("Rendering the timeline to {}", output.display()));
+
+        thread::spawn(move || {
+            let result = timeline::render(&timeline, &output, &cancel, |progress| {
+                let _ = event_tx.send(TimelineEvent::Progress(progress));
+            });
+            let _ = event_tx.send(match result {
+                Ok(render) => TimelineEvent::Done(render),
+                Err(e) => TimelineEvent::Failed(e),
+            });
+        });
+    }
+
+    fn open_daw_project(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("DAWproject", &[dawproject::EXTENSION])
+            .set_title("Open DAW Project")
+            .pick_file()
+        else {
+            return;
+        };
+
+        match Project::open(&path) {
+            Ok(project) => {
+                self.log_event(format!(
+                    "Opened {} with {} audio clip(s)",
+                    path.display(),
+                    project.clips.len()
+                ));
+                self.daw_selected =
+                    project.clips.iter().map(|clip| clip.looks_like_vocals()).collect();
+                self.daw_project = Some(project);
+                self.daw_status = None;
+            }
+            Err(e) => {
+                self.log_event(format!("Can't open {}: {}", path.display(), e));
+                self.daw_status = Some(format!("❌ {}", e));
+            }
+        }
+    }
+
+    fn start_project_render(&mut self) {
+        let Some(project) = self.daw_project.clone() else {
+            return;
+        };
+        let stem = project.path.file_stem().map(|stem| stem.to_string_lossy().into_owned());
+        let Some(output) = rfd::FileDialog::new()
+            .add_filter("DAWproject", &[dawproject::EXTENSION])
+            .set_title("Save Corrected Project")
+            .set_file_name(format!("{}_tuned.{}", stem.unwrap_or_default(), dawproject::EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+
+        let selected: Vec<usize> =
+            (0..project.clips.len()).filter(|&i| self.daw_selected[i]).collect();
+        let correction = ClipCorrection {
+            config: self.autotune_config(),
+            musical: self.musical_settings(),
+            scale: self.scale(),
+            a4: self.a4,
+            options: self.render_options(),
+        };
+        let (event_tx, event_rx) = mpsc::channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.daw_cancel = cancel.clone();
+        self.daw_receiver = Some(event_rx);
+        self.daw_progress = 0.0;
+        self.daw_status = None;
+        self.log_event(format!(
+            "Correcting {} clip(s) of {} into {}",
+            selected.len(),
+            paths::display_name(&project.path),
+            output.display()
+        ));
+
+        thread::spawn(move || {
+            let result = dawproject::render(
+                &project,
+                &selected,
+                &output,
+                &correction,
+                &cancel,
+                |progress| {
+                    let _ = event_tx.send(ProjectEvent::Progress(progress));
+                },
+            );
+            let _ = event_tx.send(match result {
+                Ok(render) => ProjectEvent::Done(render),
+                Err(e) => ProjectEvent::Failed(e),
+            });
+        });
+    }
+
+    fn update_project_status(&mut self) {
+        let Some(receiver) = &self.daw_receiver else {
+            return;
+        };
+
+        let mut finished = None;
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                ProjectEvent::Progress(progress) => self.daw_progress = progress,
+                ProjectEvent::Done(render) => finished = Some(Ok(render)),
+                ProjectEvent::Failed(err) => finished = Some(Err(err)),
+            }
+        }
+
+        let status = match finished {
+            Some(Ok(render)) => {
+                let mut status = format!(
+                    "✅ Corrected {} file(s), {}",
+                    render.files,
+                    format::duration(render.duration_secs)
+                );
+                if render.failed_frames > 0 {
+                    status += &format!(" ({} frames left uncorrected)", render.failed_frames);
+                }
+                status
+            }
+            Some(Err(err)) => format!("❌ {}", err),
+            None => return,
+        };
+        self.daw_receiver = None;
+        self.log_event(format!("DAW project: {}", status));
+        self.daw_status = Some(status);
+    }
+
+    fn update_timeline_status(&mut self) {
+        let Some(receiver) = &self.timeline_receiver else {
+            return;
+        };
+
+        let mut finished = None;
+        while let Ok(event) = receiver.try_recv() {
+            match event {
+                TimelineEvent::Progress(progress) => self.timeline_progress = progress,
+                TimelineEvent::Done(render) => finished = Some(Ok(render)),
+                TimelineEvent::Failed(err) => finished = Some(Err(err)),
+            }
+        }
+
+        let status = match finished {
+            Some(Ok(render)) if render.failed_frames > 0 => format!(
+                "✅ Exported {} ({} frames left uncorrected)",
+                format::duration(render.duration_secs),
+                render.failed_frames
+            ),
+            Some(Ok(render)) => {
+                format!("✅ Exported {}", format::duration(render.duration_secs))
+            }
+            Some(Err(err)) => format!("❌ {}", err),
+            None => return,
+        };
+        self.timeline_receiver = None;
+        self.log_event(format!("Timeline render: {}", status));
+        self.timeline_status = Some(status);
+    }
+
+    fn start_live(&mut self) {
+        self.live_session = None;
+        if let Err(e) = engine::validate_frames(self.fft_size, self.hop_size) {
+            self.live_error = Some(e);
+            return;
+        }
+        match LiveSession::start(
+            self.autotune_config(),
+            self.window,
+            &self.musical_settings(),
+            self.a4,
+            self.live_buffer_size,
+        ) {
+            Ok(session) => {
+                self.log_event(format!(
+                    "Live mode started at {}Hz, {} frame buffer (~{:.0}ms latency)",
+                    session.sample_rate,
+                    session.buffer_size,
+                    session.latency_ms()
+                ));
+                self.live_session = Some(session);
+                self.live_error = None;
+            }
+            Err(e) => {
+                self.log_event(format!("Live mode failed to start: {}", e));
+                self.live_error = Some(e);
+            }
+        }
+    }
+
+    fn stop_live(&mut self) {
+        self.live_notice = None;
+        self.live_buffer_exhausted = false;
+        if self.live_session.take().is_some() {
+            self.log_event("Live mode stopped");
+        }
+    }
+
+    fn start_recording(&mut self) {
+        // Live mode would play the take back into the microphone
+        self.stop_live();
+        match Recorder::start() {
+            Ok(recorder) => {
+                self.log_event(format!(
+                    "Recording started at {}Hz, {} channel(s)",
+                    recorder.sample_rate, recorder.channels
+                ));
+                self.record_meters = vec![LevelMeter::default(); recorder.channels as usize];
+                self.record_metered_at = None;
+                self.recorder = Some(recorder);
+                self.record_error = None;
+            }
+            Err(e) => {
+                self.log_event(format!("Recording failed to start: {}", e));
+                self.record_error = Some(e);
+            }
+        }
+    }
+
+    // Load the take as the input, and tune it right away unless that's turned off
+    fn stop_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+        let duration = recorder.duration_secs();
+        match recorder.finish(&recorder::take_dir()) {
+            Ok(path) => {
+                self.log_event(format!(
+                    "Recorded a {} take: {}",
+                    format::duration(duration),
+                    path.display()
+                ));
+                self.default_output_next_to(&path);
+                self.set_input_file(path);
+                if self.tune_after_recording && !self.is_processing {
+                    self.start_processing(true);
+                }
+            }
+            Err(e) => {
+                self.log_event(format!("Recording failed: {}", e));
+                self.record_error = Some(e);
+            }
+        }
+    }
+
+    // Step up to the next buffer size while live mode keeps glitching, or say what else
+    // can be done once the largest is reached
+    fn check_live_stability(&mut self) {
+        let Some(session) = &mut self.live_session else {
+            return;
+        };
+        if self.fixed_live_buffer_size || !session.unstable() {
+            return;
+        }
+        let cause = session.load().diagnosis().unwrap_or("glitches");
+        match live::larger_buffer_size(self.live_buffer_size) {
+            Some(size) => {
+                let notice = format!(
+                    "Raised the buffer size from {} to {} frames to stop glitches",
+                    self.live_buffer_size, size
+                );
+                self.live_buffer_size = size;
+                self.start_live();
+                self.log_event(format!("{} ({})", notice, cause));
+                self.live_notice = Some(notice);
+            }
+            None if !self.live_buffer_exhausted => {
+                let notice = if self.is_processing && self.use_all_cores {
+                    "Still glitching at the largest buffer. Stop the render running on all \
+                     cores, or turn off \"Use all CPU cores\""
+                } else if self.is_processing || self.timeline_receiver.is_some() {
+                    "Still glitching at the largest buffer. Stop the render running alongside"
+                } else {
+                    "Still glitching at the largest buffer. Close other busy apps, or try \
+                     another audio device"
+                };
+                self.log_event(format!("{} ({})", notice, cause));
+                self.live_notice = Some(notice.to_string());
+                self.live_buffer_exhausted = true;
+            }
+            None => {}
+        }
+    }
+
+    /// Load the input and the finished render for A/B preview in the background.
+    fn load_playback(&mut self) {
+        let (Some(input), Some(output)) = (self.input_file.clone(), self.output_file.clone())
+        else {
+            return;
+        };
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(Tracks::load(&input, &output));
+        });
+        self.player_receiver = Some(receiver);
+    }
+
+    fn update_playback_status(&mut self) {
+        let Some(receiver) = &self.player_receiver else {
+            return;
+        };
+        let Ok(loaded) = receiver.try_recv() else {
+            return;
+        };
+        self.player_receiver = None;
+
+        match loaded.and_then(Player::open) {
+            Ok(player) => self.player = Some(player),
+            Err(e) => self.log_event(format!("Preview unavailable: {}", e)),
+        }
+    }
+
+    fn start_help_render(&mut self, topic: Topic) {
+        let Some(input) = self.input_file.clone() else {
+            return;
+        };
+        let settings = self.clip_settings();
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(help::render_contrast(&input, topic, settings));
+        });
+        self.help_receiver = Some(receiver);
+    }
+
+    fn update_help_status(&mut self) {
+        let Some(rendered) = self.help_receiver.as_ref().and_then(|r| r.try_recv().ok()) else {
+            return;
+        };
+        self.help_receiver = None;
+
+        match rendered.and_then(Player::open) {
+            Ok(player) => {
+                player.set_source(Source::Original);
+                player.toggle_playing();
+                self.help_player = Some(player);
+            }
+            Err(e) => self.log_event(format!("Couldn't render the difference: {}", e)),
+        }
+    }
+
+    // Toggles whether presets and projects leave `param` as it is
+    fn lock_button(&mut self, ui: &mut egui::Ui, param: Param) {
+        let locked = self.locked.contains(param);
+        if ui
+            .selectable_label(locked, if locked { "🔒" } else { "🔓" })
+            .on_hover_text("Locked settings are left as they are by presets and projects")
+            .clicked()
+        {
+            self.locked.toggle(param);
+        }
+    }
+
+    // Toggles the explanation of `topic`, next to its setting
+    fn help_button(&mut self, ui: &mut egui::Ui, topic: Topic) {
+        let open = self.help_topic == Some(topic);
+        if ui.selectable_label(open, "❓").on_hover_text("What does this do?").clicked() {
+            self.help_topic = (!open).then_some(topic);
+            self.help_player = None;
+            self.help_receiver = None;
+        }
+    }
+
+    // The explanation of `topic` while it is open, with its low and high renders to play
+    fn help_ui(&mut self, ui: &mut egui::Ui, topic: Topic) {
+        if self.help_topic != Some(topic) {
+            return;
+        }
+        ui.group(|ui| {
+            ui.label(topic.explanation());
+            let Some(contrast) = topic.contrast() else {
+                return;
+            };
+            match &self.help_player {
+                Some(player) => {
+                    ui.horizontal(|ui| {
+                        let sides = [
+                            (Source::Original, contrast.low_label),
+                            (Source::Processed, contrast.high_label),
+                        ];
+                        for (source, label) in sides {
+                            let playing = player.is_playing() && player.source() == source;
+                            let icon = if playing { "⏸" } else { "▶" };
+                            if ui.button(format!("{} {}", icon, label)).clicked() {
+                                // Switching sides continues at the same point in the excerpt
+                                if player.source() == source {
+                                    player.toggle_playing();
+                                } else {
+                                    player.set_source(source);
+                                    if !player.is_playing() {
+                                        player.toggle_playing();
+                                    }
+                                }
+                            }
+                        }
+                    });
+                }
+                None if self.help_receiver.is_some() => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Rendering...");
+                    });
+                }
+                None => {
+                    let hear = egui::Button::new("🎧 Hear the difference");
+                    if ui
+                        .add_enabled(self.input_file.is_some(), hear)
+                        .on_hover_text(format!(
+                            "Render a few seconds of the input at {} and at {}, with the                              other settings as they are",
+                            contrast.low_label, contrast.high_label
+                        ))
+                        .on_disabled_hover_text("Load an input file first")
+                        .clicked()
+                    {
+                        self.start_help_render(topic);
+                    }
+                }
+            }
+        });
+    }
+
+    fn update_waveform_status(&mut self) {
+        for (receiver, waveform) in [
+            (&mut self.input_waveform_receiver, &mut self.input_waveform),
+            (&mut self.output_waveform_receiver, &mut self.output_waveform),
+        ] {
+            let Some(loaded) = receiver.as_ref().and_then(|receiver| receiver.try_recv().ok())
+            else {
+                continue;
+            };
+            *receiver = None;
+            match loaded {
+                Ok(envelope) => *waveform = Some(envelope),
+                Err(e) => log::warn!("Waveform unavailable: {}", e),
+            }
+        }
+    }
+
+    /// Input waveform, with the render's overlaid or below it once there is one.
+    fn waveform_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(input) = &self.input_waveform else {
+            if self.input_waveform_receiver.is_some() {
+                ui.label("Loading waveform...");
+            }
+            return;
+        };
+        let duration = input.duration_secs();
+
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label("〰 Waveform");
+                if self.output_waveform.is_some() {
+                    ui.selectable_value(
+                        &mut self.waveform_view,
+                        waveform::View::Overlay,
+                        "Overlay",
+                    );
+                    ui.selectable_value(&mut self.waveform_view, waveform::View::Split, "Split");
+                }
+            });
+            let colors = self.theme.palette.colors(ui.visuals());
+            let marks = waveform::Marks {
+                regions: &self.regions,
+                bookmarks: &self.bookmarks,
+                cursor: self.player.as_ref().map(Player::position_secs).or(self.cursor_secs),
+            };
+            let clicked = waveform::show(
+                ui,
+                Some(input),
+                self.output_waveform.as_ref(),
+                self.waveform_view,
+                &marks,
+                &mut self.selection,
+                &colors,
+            );
+            if let Some(secs) = clicked {
+                self.cursor_secs = Some(secs);
+                if let Some(player) = &self.player {
+                    player.seek(secs);
+                }
+            }
+            ui.horizontal(|ui| match self.selection {
+                Some((start, end)) => {
+                    ui.label(format!(
+                        "✂ In {} – Out {}: only this is corrected, crossfaded into the rest",
+                        format::duration(start),
+                        format::duration(end)
+                    ));
+                    if ui.button("✖ Clear").clicked() {
+                        self.selection = None;
+                    }
+                }
+                None => {
+                    ui.weak("Drag across the waveform to correct only part of the file");
+                }
+            });
+
+            ui.label(format!("Input: {}", input.summary()));
+            if let Some(output) = &self.output_waveform {
+                let summary = format!("Output: {}", output.summary());
+                if output.clipped > 0 || output.is_silent() {
+                    ui.colored_label(ui.visuals().warn_fg_color, summary);
+                } else {
+                    ui.label(summary);
+                }
+            } else if self.output_waveform_receiver.is_some() {
+                ui.label("Loading output waveform...");
+            }
+        });
+        ui.group(|ui| self.bookmarks_ui(ui, duration));
+    }
+
+    // A box to go to a typed time, and the bookmarks dropped with M to jump back to
+    fn bookmarks_ui(&mut self, ui: &mut egui::Ui, duration: f32) {
+        let mut jump = None;
+        ui.horizontal(|ui| {
+            ui.label("Go to:");
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.go_to_text)
+                    .desired_width(90.0)
+                    .hint_text("1:02:30"),
+            );
+            let entered =
+                response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if ui.button("Go").clicked() || entered {
+                match bookmarks::parse_time(&self.go_to_text) {
+                    Ok(secs) => jump = Some(secs.min(duration)),
+                    Err(e) => self.processing_status = e,
+                }
+            }
+            if ui
+                .button("🔖 Bookmark")
+                .on_hover_text(
+                    "Mark the playback position, or where the waveform was last clicked (M)",
+                )
+                .clicked()
+            {
+                self.add_bookmark();
+            }
+        });
+
+        let mut removed = None;
+        if !self.bookmarks.is_empty() {
+            egui::CollapsingHeader::new(format!("🔖 Bookmarks ({})", self.bookmarks.len()))
+                .id_source("bookmarks")
+                .default_open(true)
+                .show(ui, |ui| {
+                    egui::ScrollArea::vertical().id_source("bookmarks").max_height(150.0).show(
+                        ui,
+                        |ui| {
+                            for (index, bookmark) in self.bookmarks.iter_mut().enumerate() {
+                                ui.horizontal(|ui| {
+                                    if ui.button(format::duration(bookmark.secs)).clicked() {
+                                        jump = Some(bookmark.secs);
+                                    }
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut bookmark.name)
+                                            .desired_width(160.0),
+                                    );
+                                    if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                                        removed = Some(index);
+                                    }
+                                });
+                            }
+                        },
+                    );
+                });
+        }
+        if let Some(index) = removed {
+            self.bookmarks.remove(index);
+        }
+        if let Some(secs) = jump {
+            self.cursor_secs = Some(secs);
+            if let Some(player) = &self.player {
+                player.seek(secs);
+            }
+        }
+    }
+
+    // At the playback position when there is one, otherwise where the waveform was last
+    // clicked or jumped to
+    fn add_bookmark(&mut self) {
+        let secs = match &self.player {
+            Some(player) => player.position_secs(),
+            None => self.cursor_secs.unwrap_or(0.0),
+        };
+        let index = bookmarks::insert(&mut self.bookmarks, secs);
+        let name = self.bookmarks[index].name.clone();
+        self.log_event(format!("Bookmarked {} as {}", format::duration(secs), name));
+    }
+
+    fn pitch_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(ProcessingResult::Success { pitch: Some(curves), .. }) = &self.processing_result
+        else {
+            return;
+        };
+
+        let mut export = false;
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label("🎯 Pitch");
+                export = ui
+                    .button("🏷 Export Labels...")
+                    .on_hover_text(
+                        "Save the input's notes, and where no pitch was found, as Audacity \
+                         labels or a Praat TextGrid",
+                    )
+                    .clicked();
+            });
+            let colors = self.theme.palette.colors(ui.visuals());
+            let clicked = pitch_view::show(ui, curves, &colors);
+            if let Some(secs) = clicked {
+                self.cursor_secs = Some(secs);
+                if let Some(player) = &self.player {
+                    player.seek(secs);
+                }
+            }
+            ui.label(curves.stats().summary());
+        });
+
+        if export {
+            let segments = labels::note_segments(&curves.detected);
+            self.export_labels(&segments, "labels.txt");
+        }
+    }
+
+    fn export_labels(&mut self, segments: &[Label], file_name: &str) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Audacity labels", &["txt"])
+            .add_filter("Praat TextGrid", &["TextGrid"])
+            .add_filter("REAPER markers", &["csv"])
+            .set_file_name(file_name)
+            .set_title("Export Labels")
+            .save_file()
+        else {
+            return;
+        };
+
+        match labels::write_file(&path, segments, self.duration.unwrap_or(0.0)) {
+            Ok(()) => {
+                self.log_event(format!("Exported {} labels to {}", segments.len(), path.display()))
+            }
+            Err(e) => {
+                self.processing_status = e.clone();
+                self.log_event(e);
+            }
+        }
+    }
+
+    fn import_regions(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Labels", &["txt", "TextGrid", "csv"])
+            .set_title("Import Regions from Labels")
+            .pick_file()
+        else {
+            return;
+        };
+
+        match labels::read_file(&path) {
+            Ok(labels) => {
+                self.log_event(format!("Imported {} labels from {}", labels.len(), path.display()));
+                self.regions = labels;
+                self.regions_source = Some(paths::display_name(&path));
+            }
+            Err(e) => {
+                self.processing_status = e.clone();
+                self.log_event(e);
+            }
+        }
+    }
+
+    fn regions_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("🏷 Regions:");
+            let count = self.regions.iter().filter(|label| !label.is_point()).count();
+            match &self.regions_source {
+                Some(source) if count > 0 => {
+                    ui.label(format!("{} from {}, the rest is left as it is", count, source));
+                }
+                Some(source) => {
+                    ui.label(format!("none in {}, the whole file is corrected", source));
+                }
+                None if self.selection.is_some() => {
+                    ui.label("the waveform selection");
+                }
+                None => {
+                    ui.label("whole file");
+                }
+            }
+            let key_changes = keys::key_changes(&self.regions);
+            if !key_changes.is_empty() {
+                let keys: Vec<String> = key_changes
+                    .iter()
+                    .map(|change| {
+                        format!(
+                            "{} at {}",
+                            ui::get_key_name(change.key),
+                            format::duration(change.secs)
+                        )
+                    })
+                    .collect();
+                ui.label(format!("🎼 {} key change(s)", key_changes.len()))
+                    .on_hover_text(keys.join("\n"));
+            }
+
+            ui.add_enabled_ui(!self.is_processing, |ui| {
+                if ui
+                    .button("Import Labels...")
+                    .on_hover_text(
+                        "Only correct the labelled regions of an Audacity label file, a Praat \
+                         TextGrid, or a REAPER marker CSV. Markers named after a key, like \
+                         \"Key: Am\", change the key from there on",
+                    )
+                    .clicked()
+                {
+                    self.import_regions();
+                }
+                if self.regions_source.is_some() && ui.button("✖ Clear").clicked() {
+                    self.regions.clear();
+                    self.regions_source = None;
+                }
+            });
+        });
+    }
+
+    fn frames_ui(&mut self, ui: &mut egui::Ui) {
+        let overlap = (self.fft_size / self.hop_size.max(1)).clamp(1, 8);
+        ui.horizontal(|ui| {
+            ui.label("FFT Size:");
+            egui::ComboBox::from_id_source("fft_size_selector")
+                .selected_text(self.fft_size.to_string())
+                .show_ui(ui, |ui| {
+                    for size in engine::FFT_SIZES {
+                        if ui.selectable_value(&mut self.fft_size, size, size.to_string()).clicked()
+                        {
+                            // Keep the overlap the hop had
+                            self.hop_size = size / overlap;
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Larger frames resolve low voices better, smaller ones follow fast runs \
+                     more closely",
+                );
+            ui.label("Hop:");
+            egui::ComboBox::from_id_source("hop_size_selector")
+                .selected_text(self.hop_size.to_string())
+                .show_ui(ui, |ui| {
+                    for frames in [2, 4, 8] {
+                        let hop = self.fft_size / frames;
+                        let label = format!("{} ({}x overlap)", hop, frames);
+                        ui.selectable_value(&mut self.hop_size, hop, label);
+                    }
+                });
+            ui.label("Window:");
+            egui::ComboBox::from_id_source("window_selector")
+                .selected_text(self.window.name())
+                .show_ui(ui, |ui| {
+                    for window in Window::ALL {
+                        ui.selectable_value(&mut self.window, window, window.name());
+                    }
+                });
+            ui.label("Padding:");
+            egui::ComboBox::from_id_source("padding_selector")
+                .selected_text(self.padding.name())
+                .show_ui(ui, |ui| {
+                    for padding in Padding::ALL {
+                        ui.selectable_value(&mut self.padding, padding, padding.name());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "What the last frames are filled out with past the end of the input: \
+                     reflect keeps a note held to the very end tracked",
+                );
+        });
+        if let Err(e) = engine::validate_frames(self.fft_size, self.hop_size) {
+            ui.colored_label(ui.visuals().error_fg_color, e);
+        }
+    }
+
+    /// Sections of the input with a key, note, and formant of their own, added from the
+    /// waveform selection.
+    fn normalization_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Normalize:");
+            let name = |normalization: &Normalization| match normalization {
+                Normalization::None => "Off",
+                Normalization::Clip => "Prevent clipping",
+                Normalization::Peak(_) => "Peak",
+                Normalization::Loudness(_) => "Loudness (LUFS)",
+            };
+            let choices = [
+                Normalization::None,
+                Normalization::Clip,
+                Normalization::Peak(Normalization::DEFAULT_PEAK_DB),
+                Normalization::Loudness(Normalization::DEFAULT_LUFS),
+            ];
+            egui::ComboBox::from_id_source("normalization_selector")
+                .selected_text(name(&self.normalization))
+                .show_ui(ui, |ui| {
+                    for choice in choices {
+                        let selected = std::mem::discriminant(&self.normalization)
+                            == std::mem::discriminant(&choice);
+                        if ui.selectable_label(selected, name(&choice)).clicked() && !selected {
+                            self.normalization = choice;
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "How the level of the render is set: left as it is, only brought down if it \
+                     would clip, to a peak level, or to an integrated loudness like the -14 LUFS \
+                     streaming services play at",
+                );
+            match &mut self.normalization {
+                Normalization::Peak(db) => {
+                    ui.add(
+                        egui::DragValue::new(db)
+                            .clamp_range(-60.0..=0.0)
+                            .speed(0.1)
+                            .suffix(" dBFS"),
+                    );
+                }
+                Normalization::Loudness(lufs) => {
+                    ui.add(
+                        egui::DragValue::new(lufs)
+                            .clamp_range(-60.0..=0.0)
+                            .speed(0.1)
+                            .suffix(" LUFS"),
+                    )
+                    .on_hover_text("Brought as close as the peak allows without clipping");
+                }
+                Normalization::None | Normalization::Clip => {}
+            }
+        });
+    }
+
+    fn limiter_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.limiter, "Limit peaks").on_hover_text(
+                "Hold the peaks of the render under the ceiling with a brick-wall limiter at the \
+                 very end, instead of turning the whole render down for them. Loudness \
+                 normalization then goes all the way to its target",
+            );
+            ui.add_enabled(
+                self.limiter,
+                egui::Slider::new(&mut self.limit_db, dynamics::CEILING_RANGE).text("dBFS"),
+            );
+        });
+    }
+
+    fn f0_range_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Voice range:");
+            let selected = match &self.f0_range {
+                None => "Any",
+                Some(range) => range.preset().unwrap_or("Custom"),
+            };
+            egui::ComboBox::from_id_source("f0_range_selector")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.f0_range, None, "Any");
+                    for (name, range) in F0Range::PRESETS {
+                        ui.selectable_value(&mut self.f0_range, Some(range), name);
+                    }
+                    if ui.selectable_label(selected == "Custom", "Custom").clicked() {
+                        self.f0_range = Some(self.f0_range.unwrap_or_default());
+                    }
+                })
+                .response
+                .on_hover_text(
+                    "Only look for the sung pitch between these frequencies, so a low or high \
+                     source can't be taken an octave off",
+                );
+            if let Some(range) = &mut self.f0_range {
+                let (lowest, highest) = (*pitch::F0_LIMITS.start(), *pitch::F0_LIMITS.end());
+                ui.add(
+                    egui::DragValue::new(&mut range.min)
+                        .clamp_range(lowest..=range.max - 1.0)
+                        .speed(1.0)
+                        .suffix(" Hz"),
+                );
+                ui.label("to");
+                ui.add(
+                    egui::DragValue::new(&mut range.max)
+                        .clamp_range(range.min + 1.0..=highest)
+                        .speed(1.0)
+                        .suffix(" Hz"),
+                );
+            }
+        });
+    }
+
+    fn energy_ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.energy_strength, "Follow the voice's level").on_hover_text(
+            "Correct loud lead lines with the full strength and quiet tails and breaths more \
+             gently, so they keep their natural drift",
+        );
+        if !self.energy_strength {
+            return;
+        }
+        let curve = &mut self.energy_curve;
+        ui.horizontal(|ui| {
+            ui.label("Quiet below:");
+            ui.add(egui::Slider::new(&mut curve.quiet_db, -96.0..=-1.0).text("dBFS"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Loud from:");
+            ui.add(egui::Slider::new(&mut curve.loud_db, curve.quiet_db + 1.0..=0.0).text("dBFS"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Quiet strength:");
+            ui.add(egui::Slider::new(&mut curve.quiet_strength, 0.0..=1.0).text("of strength"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Curve:");
+            ui.add(egui::Slider::new(&mut curve.shape, 0.25..=4.0).logarithmic(true))
+                .on_hover_text("1 rises evenly with the level, higher stays gentle for longer");
+        });
+        curve.loud_db = curve.loud_db.max(curve.quiet_db + 1.0);
+    }
+
+    fn effects_chain_ui(&mut self, ui: &mut egui::Ui) {
+        // Stage swapped with the one after it
+        let mut moved = None;
+        let stages = self.chain.stages().to_vec();
+        for (index, &stage) in stages.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui.add_enabled(index > 0, egui::Button::new("⬆")).clicked() {
+                    moved = Some(index - 1);
+                }
+                if ui.add_enabled(index + 1 < stages.len(), egui::Button::new("⬇")).clicked() {
+                    moved = Some(index);
+                }
+                match stage {
+                    Stage::HighPass => {
+                        ui.checkbox(&mut self.high_pass, "High-pass").on_hover_text(
+                            "Filter out rumble below the cutoff, so stands, traffic, and air \
+                             conditioning aren't taken for pitch",
+                        );
+                        ui.add_enabled(
+                            self.high_pass,
+                            egui::Slider::new(&mut self.high_pass_hz, prefilter::HIGH_PASS_RANGE)
+                                .logarithmic(true)
+                                .text("Hz"),
+                        );
+                    }
+                    Stage::Gate => {
+                        ui.checkbox(&mut self.noise_gate, "Noise gate").on_hover_text(
+                            "Mute the audio while it stays below the threshold, so hiss and room \
+                             noise between phrases aren't taken for pitch",
+                        );
+                    }
+                    Stage::Autotune => {
+                        ui.label("🎤 Autotune").on_hover_text(
+                            "The correction, always on. Effects above it clean up its input, \
+                             effects below it shape its output",
+                        );
+                    }
+                    Stage::Eq => {
+                        ui.checkbox(&mut self.eq, "EQ").on_hover_text(
+                            "Shape the voice with up to five bands, like a presence boost or a \
+                             cut in the mud, without a second pass in another tool",
+                        );
+                    }
+                    Stage::Compressor => {
+                        ui.checkbox(&mut self.compress, "Compressor").on_hover_text(
+                            "Turn the voice down above the threshold, so loud lines sit closer \
+                             to quiet ones",
+                        );
+                    }
+                }
+            });
+            if stage == Stage::Gate && self.noise_gate {
+                ui.indent("gate_settings", |ui| self.gate_ui(ui));
+            }
+            if stage == Stage::Eq && self.eq {
+                ui.indent("eq_settings", |ui| self.eq_ui(ui));
+            }
+            if stage == Stage::Compressor && self.compress {
+                ui.indent("compressor_settings", |ui| self.compressor_ui(ui));
+            }
+        }
+        if let Some(index) = moved {
+            self.chain.swap(index, index + 1);
+        }
+    }
+
+    fn gate_ui(&mut self, ui: &mut egui::Ui) {
+        let gate = &mut self.gate;
+        ui.horizontal(|ui| {
+            ui.label("Threshold:");
+            ui.add(egui::Slider::new(&mut gate.threshold_db, -96.0..=0.0).text("dBFS"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Attack:");
+            ui.add(
+                egui::Slider::new(&mut gate.attack_ms, 0.1..=100.0).logarithmic(true).text("ms"),
+            )
+            .on_hover_text("How quickly the gate opens once the voice comes in");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Release:");
+            ui.add(
+                egui::Slider::new(&mut gate.release_ms, 5.0..=2000.0).logarithmic(true).text("ms"),
+            )
+            .on_hover_text("How quickly the gate closes after the voice stops, keeping tails");
+        });
+    }
+
+    fn compressor_ui(&mut self, ui: &mut egui::Ui) {
+        let compressor = &mut self.compressor;
+        ui.horizontal(|ui| {
+            ui.label("Threshold:");
+            ui.add(egui::Slider::new(&mut compressor.threshold_db, -60.0..=0.0).text("dBFS"));
+        });
+        ui.horizontal(|ui| {
+            ui.label("Ratio:");
+            ui.add(
+                egui::Slider::new(&mut compressor.ratio, 1.0..=20.0).logarithmic(true).suffix(":1"),
+            )
+            .on_hover_text("How much the level above the threshold is divided by");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Attack:");
+            ui.add(
+                egui::Slider::new(&mut compressor.attack_ms, 0.1..=200.0)
+                    .logarithmic(true)
+                    .text("ms"),
+            )
+            .on_hover_text("How quickly it clamps down once the voice gets loud");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Release:");
+            ui.add(
+                egui::Slider::new(&mut compressor.release_ms, 5.0..=2000.0)
+                    .logarithmic(true)
+                    .text("ms"),
+            )
+            .on_hover_text("How quickly it lets go once the voice gets quieter");
+        });
+    }
+
+    // How far the compressor and the limiter turned the render down, now and at most
+    fn gain_reduction_ui(&self, ui: &mut egui::Ui) {
+        // Reduction that fills a meter
+        const FULL_DB: f32 = 24.0;
+        let meters = [
+            ("Compressor", self.compress, &self.meters.compressor),
+            ("Limiter", self.limiter, &self.meters.limiter),
+        ];
+        for (name, on, meter) in meters {
+            if !on {
+                continue;
+            }
+            ui.horizontal(|ui| {
+                ui.label(format!("{}:", name));
+                ui.add(
+                    egui::ProgressBar::new((meter.reduction_db() / FULL_DB).min(1.0))
+                        .desired_width(160.0)
+                        .text(format!("-{:.1} dB", meter.reduction_db())),
+                )
+                .on_hover_text("Gain reduction over the last block");
+                ui.label(format!("most -{:.1} dB", meter.most_db()));
+            });
+        }
+    }
+
+    fn eq_ui(&mut self, ui: &mut egui::Ui) {
+        for (i, band) in self.equalizer.bands.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(format!("Band {}:", i + 1));
+                ui.add(
+                    egui::Slider::new(&mut band.freq_hz, equalizer::FREQ_RANGE)
+                        .logarithmic(true)
+                        .text("Hz"),
+                );
+                ui.add(egui::Slider::new(&mut band.gain_db, equalizer::GAIN_RANGE).text("dB"))
+                    .on_hover_text("Boost or cut around the frequency, 0 dB leaves the band off");
+                ui.add(
+                    egui::DragValue::new(&mut band.q)
+                        .speed(0.01)
+                        .clamp_range(equalizer::Q_RANGE)
+                        .prefix("Q "),
+                )
+                .on_hover_text("Width of the band, narrower the higher");
+            });
+        }
+        if ui.button("Flatten").clicked() {
+            self.equalizer = Equalizer::default();
+        }
+    }
+
+    fn sections_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("🎼 Sections:");
+            if self.sections.is_empty() {
+                ui.label("none, the main settings apply throughout");
+            }
+            let selection = self.selection.filter(|_| !self.is_processing);
+            let add = ui
+                .add_enabled(selection.is_some(), egui::Button::new("➕ From Selection"))
+                .on_hover_text(
+                    "Give the stretch selected on the waveform its own key, note, and formant, \
+                     for a song that modulates. It starts with the current settings",
+                );
+            if let Some((start, end)) = selection.filter(|_| add.clicked()) {
+                let musical = self.musical_settings();
+                self.sections.push(Section::new(start, end, &musical));
+                self.sections.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+            }
+        });
+
+        let mut removed = None;
+        ui.add_enabled_ui(!self.is_processing, |ui| {
+            for (index, section) in self.sections.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} – {}",
+                        format::duration(section.start_secs),
+                        format::duration(section.end_secs)
+                    ));
+                    let mut key = section.key as usize;
+                    egui::ComboBox::from_id_source(("section_key", index))
+                        .selected_text(ui::get_key_name(key))
+                        .show_ui(ui, |ui| {
+                            for (i, name) in ui::KEY_NAMES.iter().enumerate() {
+                                ui.selectable_value(&mut key, i, *name);
+                            }
+                        });
+                    section.key = key as i32;
+                    let note = match section.note {
+                        0 => "Auto",
+                        note => ui::get_note_name(note - 1),
+                    };
+                    egui::ComboBox::from_id_source(("section_note", index))
+                        .selected_text(note)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut section.note, 0, "Auto");
+                            for (i, name) in ui::NOTE_NAMES.iter().enumerate() {
+                                ui.selectable_value(&mut section.note, i as i32 + 1, *name);
+                            }
+                        });
+                    ui.add(
+                        egui::DragValue::new(&mut section.formant)
+                            .clamp_range(-12..=12)
+                            .prefix("Formant "),
+                    );
+                    if ui.button("🗑").on_hover_text("Remove this section").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+        });
+        if let Some(index) = removed {
+            self.sections.remove(index);
+        }
+    }
+
+    fn playback_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(player) = &self.player else {
+            if self.player_receiver.is_some() {
+                ui.label("Loading preview...");
+            }
+            return;
+        };
+
+        ui.group(|ui| {
+            ui.label("🔊 Preview");
+            ui.horizontal(|ui| {
+                let label = if player.is_playing() {
+                    "⏸ Pause"
+                } else {
+                    "▶ Play"
+                };
+                if ui.button(label).clicked() {
+                    player.toggle_playing();
+                }
+
+                // A/B switch, playback continues at the same position
+                let mut source = player.source();
+                ui.selectable_value(&mut source, Source::Original, "A: Original");
+                ui.selectable_value(&mut source, Source::Processed, "B: Autotuned");
+                if source != player.source() {
+                    player.set_source(source);
+                }
+            });
+
+            let duration = player.duration_secs();
+            let mut position = player.position_secs();
+            ui.horizontal(|ui| {
+                let seek = egui::Slider::new(&mut position, 0.0..=duration).show_value(false);
+                if ui.add(seek).changed() {
+                    player.seek(position);
+                }
+                ui.label(format!(
+                    "{} / {}",
+                    format::duration(position),
+                    format::duration(duration)
+                ));
+            });
+        });
+    }
+
+    fn update_processing_status(&mut self) {
+        // Check for progress updates
+        if let Some(ref receiver) = self.progress_receiver {
+            while let Ok(progress) = receiver.try_recv() {
+                match progress {
+                    ProcessingProgress::Progress(percent) => {
+                        self.processing_progress = percent;
+                        self.processing_status = format!("Processing... {:.1}%", percent * 100.0);
+                    }
+                    ProcessingProgress::Status(status) => {
+                        self.processing_status = status;
+                    }
+                }
+            }
+        }
+
+        // Check for completion
+        if let Some(ref receiver) = self.result_receiver {
+            if let Ok(result) = receiver.try_recv() {
+                self.is_processing = false;
+                self.processing_result = Some(result.clone());
+
+                match result {
+                    ProcessingResult::Success { duration_ms, stems, master, .. } => {
+                        let took = format::duration((duration_ms / 1000.0) as f32);
+                        self.processing_status = format!("Completed in {}!", took);
+                        self.log_event(format!("Processing completed in {}", took));
+                        for stem in stems {
+                            self.log_event(format!("Wrote stem {}", stem.display()));
+                        }
+                        if let Some(master) = master {
+                            self.log_event(format!("Wrote float master {}", master.display()));
+                        }
+                        self.load_playback();
+                        self.output_waveform_receiver =
+                            self.output_file.clone().map(Envelope::load_in_background);
+                    }
+                    ProcessingResult::Error(err) => {
+                        self.processing_status = format!("Error: {}", err);
+                        self.log_event(format!("Processing failed: {}", err));
+                    }
+                    ProcessingResult::Cancelled(_) => {
+                        self.processing_status = "Processing stopped".to_string();
+                        self.log_event("Processing stopped before completion");
+                        self.monitor = None;
+                    }
+                    ProcessingResult::Duplicate { render, .. } => {
+                        self.processing_status = "Already rendered".to_string();
+                        self.log_event(format!("Found an identical render: {}", render.display()));
+                        self.monitor = None;
+                    }
+                }
+
+                // Clean up channels
+                self.progress_receiver = None;
+                self.result_receiver = None;
+            }
+        }
+    }
+
+    fn files_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            if ui.button("Select Input Audio").clicked() {
+                self.select_input_file();
+            }
+
+            if ui
+                .add_enabled(!self.is_processing, egui::Button::new("📋 Paste Audio"))
+                .on_hover_text(if cfg!(target_os = "linux") {
+                    "Use WAV audio or an audio file path from the clipboard (Ctrl+V)"
+                } else {
+                    "Use an audio file path from the clipboard (Ctrl+V)"
+                })
+                .clicked()
+            {
+                self.paste_audio();
+            }
+
+            if let Some(ref path) = self.input_file {
+                ui.label(format!("📄 {}", paths::display_name(path)));
+            } else {
+                ui.label("No file selected");
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.url_input)
+                    .hint_text("https://example.com/take.wav")
+                    .desired_width(260.0),
+            );
+
+            let downloading = self.download_progress.is_some();
+            let can_download =
+                !downloading && !self.is_processing && !self.url_input.trim().is_empty();
+            if ui.add_enabled(can_download, egui::Button::new("⬇ Download")).clicked() {
+                self.start_download();
+            }
+        });
+
+        if let Some(progress) = self.download_progress {
+            ui.add(egui::ProgressBar::new(progress).text("Downloading..."));
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Select Output Path").clicked() {
+                self.select_output_file();
+            }
+
+            if let Some(ref path) = self.output_file {
+                ui.label(format!("💾 {}", paths::display_name(path)));
+            } else {
+                ui.label("No output path selected");
+            }
+        });
+
+        // Audio file info
+        if let (Some(sample_rate), Some(channels)) = (self.sample_rate, self.channels) {
+            // Some MP3s don't declare their length up front
+            let duration = self.duration.map_or("unknown".to_string(), format::duration);
+            ui.label(format!(
+                "📊 Duration: {} | Sample Rate: {} | Channels: {}",
+                duration,
+                format::sample_rate(sample_rate),
+                channels
+            ));
+        }
+
+        if let Some(classification) = self.classification {
+            ui.label(format!("🔍 Content: {}", classification.class.label()));
+            self.suggestion_ui(ui, classification);
+            if let Some(warning) = classification.class.warning() {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+            if let Some(warning) = classification.features.noise_warning() {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", warning));
+            }
+        }
+    }
+
+    // The current settings with the strength and transition speed that suit the content
+    fn suggested_settings(&self, classification: Classification) -> ClipSettings {
+        ClipSettings {
+            strength: classification.class.suggested_strength(),
+            transition: classification.class.suggested_transition(),
+            ..self.clip_settings()
+        }
+    }
+
+    // Pre-selected on load, the suggestion is offered again once the user has moved off it
+    fn suggestion_ui(&mut self, ui: &mut egui::Ui, classification: Classification) {
+        let current = self.clip_settings();
+        let suggested = self.suggested_settings(classification);
+        // Nothing to offer when it's what is set, or all it would change is locked
+        if self.locked.keep(&current, suggested) == current {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label(format!(
+                "💡 Suggested: {:.0}% strength, {:.2} transition speed",
+                suggested.strength * 100.0,
+                suggested.transition
+            ));
+            if ui.button("Apply").on_hover_text("Locked settings stay as they are").clicked() {
+                self.take_settings(suggested);
+            }
+        });
+    }
+
+    fn scale_editor_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let previous_root = self.scale_root;
+            ui.label("Root:");
+            egui::ComboBox::from_id_source("scale_root")
+                .selected_text(ui::get_note_name(self.scale_root as i32))
+                .show_ui(ui, |ui| {
+                    for (i, name) in ui::NOTE_NAMES.iter().enumerate() {
+                        ui.selectable_value(&mut self.scale_root, i, *name);
+                    }
+                });
+            // Transpose, so notes toggled by hand move along with the root
+            self.custom_scale.notes.rotate_right((self.scale_root + 12 - previous_root) % 12);
+
+            let mut rebuild = false;
+
+            let (preset_name, pattern) = scale::PRESETS[self.scale_preset];
+            let edited = self.custom_scale != Scale::from_pattern(self.scale_root, pattern);
+            egui::ComboBox::from_id_source("scale_preset")
+                .selected_text(if edited { "Custom" } else { preset_name })
+                .show_ui(ui, |ui| {
+                    for (i, (name, _)) in scale::PRESETS.iter().enumerate() {
+                        rebuild |= ui.selectable_value(&mut self.scale_preset, i, *name).clicked();
+                    }
+                });
+            if rebuild {
+                self.custom_scale =
+                    Scale::from_pattern(self.scale_root, scale::PRESETS[self.scale_preset].1);
+            }
+
+            if ui.button("From Key").on_hover_text("Start from the selected key's notes").clicked()
+            {
+                self.custom_scale = Scale::from_key(self.selected_key);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            for (pitch_class, name) in ui::NOTE_NAMES.iter().enumerate() {
+                ui.toggle_value(&mut self.custom_scale.notes[pitch_class], *name);
+            }
+        });
+        if self.custom_scale.is_empty() {
+            ui.colored_label(
+                ui.visuals().warn_fg_color,
+                "⚠ No notes selected, the key is used instead",
+            );
+        }
+    }
+
+    fn settings_ui(&mut self, ui: &mut egui::Ui) {
+        // Musical settings section
+        ui.group(|ui| {
+            ui.label("🎼 Musical Settings");
+
+            ui.horizontal(|ui| {
+                ui.label("Key:");
+                egui::ComboBox::from_id_source("key_selector")
+                    .selected_text(ui::get_key_name(self.selected_key))
+                    .show_ui(ui, |ui| {
+                        for (i, name) in ui::KEY_NAMES.iter().enumerate() {
+                            ui.selectable_value(&mut self.selected_key, i, *name);
+                        }
+                    });
+
+                let detecting = self.key_receiver.is_some();
+                let label = if detecting {
+                    "Detecting..."
+                } else {
+                    "🔍 Detect Key"
+                };
+                if ui
+                    .add_enabled(self.input_file.is_some() && !detecting, egui::Button::new(label))
+                    .on_disabled_hover_text("Load an input file first")
+                    .clicked()
+                {
+                    self.start_key_detection();
+                }
+                ui.checkbox(&mut self.auto_select_key, "Apply best match")
+                    .on_hover_text("Select the best matching key as soon as detection finishes");
+                self.lock_button(ui, Param::Key);
+                self.help_button(ui, Topic::Key);
+            });
+            self.help_ui(ui, Topic::Key);
+
+            if !self.key_matches.is_empty() {
+                ui.horizontal(|ui| {
+                    ui.label("Suggested:");
+                    for key_match in self.key_matches.iter().take(KEY_SUGGESTIONS) {
+                        let label = format!(
+                            "{} ({:.0}%)",
+                            ui::get_key_name(key_match.key),
+                            key_match.score.max(0.0) * 100.0
+                        );
+                        ui.selectable_value(&mut self.selected_key, key_match.key, label);
+                    }
+                });
+            }
+
+            ui.checkbox(&mut self.use_custom_scale, "Custom scale").on_hover_text(
+                "Correct processed files to the notes picked here instead of the key's, for \
+                 scales like harmonic minor, dorian, pentatonic, or blues",
+            );
+            if self.use_custom_scale {
+                self.scale_editor_ui(ui);
+            }
+            self.melody_ui(ui);
+            self.harmony_ui(ui);
+
+            ui.add_enabled_ui(!self.use_custom_scale, |ui| {
                 ui.horizontal(|ui| {
-                    if ui.button("Select Output Path").clicked() {
-                        self.select_output_file();
+                    ui.label("Note Mode:");
+                    let selected = match self.selected_note {
+                        0 => "Auto".to_string(),
+                        note => format!("Manual: {}", ui::get_note_name(note - 1)),
+                    };
+                    egui::ComboBox::from_id_source("note_selector")
+                        .selected_text(selected)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut self.selected_note, 0, "Auto (snap to key)");
+                            ui.separator();
+                            // The library numbers forced notes 1 = C up to 12 = B
+                            for (i, name) in ui::NOTE_NAMES.iter().enumerate() {
+                                ui.selectable_value(&mut self.selected_note, i as i32 + 1, *name);
+                            }
+                        })
+                        .response
+                        .on_hover_text(
+                            "Auto corrects to the nearest note of the key. A manual note locks \
+                             everything to that one note, in the octave set below",
+                        );
+                    self.lock_button(ui, Param::Note);
+                    self.help_button(ui, Topic::NoteMode);
+                });
+                self.help_ui(ui, Topic::NoteMode);
+            })
+            .response
+            .on_disabled_hover_text("The custom scale picks the note");
+
+            ui.horizontal(|ui| {
+                ui.label("Octave:");
+                ui.add(egui::Slider::new(&mut self.octave, 0..=4).text("octave"));
+                self.lock_button(ui, Param::Octave);
+                self.help_button(ui, Topic::Octave);
+            });
+            self.help_ui(ui, Topic::Octave);
+
+            ui.horizontal(|ui| {
+                ui.label("A4 Reference:");
+                ui.add(
+                    egui::DragValue::new(&mut self.a4)
+                        .clamp_range(pipeline::A4_RANGE)
+                        .speed(0.1)
+                        .suffix(" Hz"),
+                )
+                .on_hover_text(
+                    "Concert pitch the notes are tuned to. Orchestras often tune to 442 Hz, \
+                     period instruments to 415 Hz",
+                );
+                for pitch in A4_CHOICES {
+                    ui.selectable_value(&mut self.a4, pitch, format!("{}", pitch));
+                }
+                self.lock_button(ui, Param::A4);
+                self.help_button(ui, Topic::A4);
+            });
+            self.help_ui(ui, Topic::A4);
+
+            ui.horizontal(|ui| {
+                ui.label("Formant Shift:");
+                ui.add(egui::Slider::new(&mut self.formant_shift, -12..=12).text("semitones"));
+                self.lock_button(ui, Param::Formant);
+                self.help_button(ui, Topic::Formant);
+            });
+            self.help_ui(ui, Topic::Formant);
+        });
+
+        ui.group(|ui| {
+            ui.label("🔗 Effects Chain");
+            self.effects_chain_ui(ui);
+        });
+
+        // Autotune parameters section
+        ui.group(|ui| {
+            ui.label("⚙️ Autotune Parameters");
+            self.preset_ui(ui);
+
+            ui.horizontal(|ui| {
+                ui.label("Pitch Correction:");
+                ui.add(
+                    egui::Slider::new(&mut self.pitch_correction_strength, 0.0..=1.0)
+                        .text("strength"),
+                );
+                self.lock_button(ui, Param::Strength);
+                self.help_button(ui, Topic::Strength);
+            });
+            self.help_ui(ui, Topic::Strength);
+            self.energy_ui(ui);
+
+            ui.horizontal(|ui| {
+                ui.label("Transition Speed:");
+                ui.add(egui::Slider::new(&mut self.transition_speed, 0.01..=1.0).text("speed"));
+                self.lock_button(ui, Param::Transition);
+                self.help_button(ui, Topic::Transition);
+            });
+            self.help_ui(ui, Topic::Transition);
+
+            self.frames_ui(ui);
+
+            ui.checkbox(&mut self.use_all_cores, "Use all CPU cores").on_hover_text(
+                "Process in parallel chunks. Faster on long files, but pitch tracking restarts \
+                 (with a short warm-up) at each chunk boundary.",
+            );
+
+            let mut adapt_sample_rate = !self.native_sample_rate;
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut adapt_sample_rate, "Resample unusual sample rates").on_hover_text(
+                    "Process files outside 44.1-48kHz (voice memos, 96kHz sessions) at the \
+                         rate pitch detection is tuned for, then convert back to the file's rate",
+                );
+                self.help_button(ui, Topic::Resample);
+            });
+            self.help_ui(ui, Topic::Resample);
+            self.native_sample_rate = !adapt_sample_rate;
+
+            ui.horizontal(|ui| {
+                ui.label("Output Rate:");
+                let name = |rate: Option<u32>| {
+                    rate.map_or("Same as input".to_string(), |rate| {
+                        format!("{} kHz", rate as f32 / 1000.0)
+                    })
+                };
+                egui::ComboBox::from_id_source("output_rate_selector")
+                    .selected_text(name(self.output_rate))
+                    .show_ui(ui, |ui| {
+                        for rate in [None, Some(44_100), Some(48_000), Some(88_200), Some(96_000)] {
+                            ui.selectable_value(&mut self.output_rate, rate, name(rate));
+                        }
+                    })
+                    .response
+                    .on_hover_text("Convert the render to this sample rate after processing");
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Output Length:");
+                let mut fixed = matches!(self.length, OutputLength::Frames(_));
+                ui.radio_value(&mut fixed, false, "Same as input");
+                ui.radio_value(&mut fixed, true, "Frames").on_hover_text(
+                    "Pad the render with silence or cut it off at this many frames, at the \
+                     output rate, to line it up with the other tracks of a session",
+                );
+                self.length = match (fixed, self.length) {
+                    (false, _) => OutputLength::Input,
+                    (true, OutputLength::Frames(frames)) => OutputLength::Frames(frames),
+                    // Starting from the input's own length
+                    (true, OutputLength::Input) => {
+                        let rate = self.output_rate.map(|rate| rate as f32).or(self.sample_rate);
+                        let frames = self.duration.zip(rate).map(|(secs, rate)| secs * rate);
+                        OutputLength::Frames(frames.unwrap_or(0.0).round() as usize)
+                    }
+                };
+                if let OutputLength::Frames(frames) = &mut self.length {
+                    ui.add(egui::DragValue::new(frames).speed(100.0));
+                }
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Output Format:");
+                let name = |depth: Option<BitDepth>| depth.map_or("Same as input", BitDepth::name);
+                egui::ComboBox::from_id_source("output_depth_selector")
+                    .selected_text(name(self.output_depth))
+                    .show_ui(ui, |ui| {
+                        for depth in [None].into_iter().chain(BitDepth::ALL.map(Some)) {
+                            ui.selectable_value(&mut self.output_depth, depth, name(depth));
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "Write renders in this sample format. 16 and 24-bit renders are \
+                         dithered, so quiet tails fade out smoothly instead of breaking up",
+                    );
+                let name = |channels: Option<u16>| match channels {
+                    None => "Same channels",
+                    Some(1) => "Mono",
+                    Some(_) => "Stereo",
+                };
+                egui::ComboBox::from_id_source("output_channels_selector")
+                    .selected_text(name(self.output_channels))
+                    .show_ui(ui, |ui| {
+                        for channels in [None, Some(1), Some(2)] {
+                            ui.selectable_value(
+                                &mut self.output_channels,
+                                channels,
+                                name(channels),
+                            );
+                        }
+                    });
+            });
+            self.normalization_ui(ui);
+            self.limiter_ui(ui);
+
+            ui.horizontal(|ui| {
+                ui.label("Pitch detection:");
+                egui::ComboBox::from_id_source("pitch_algorithm_selector")
+                    .selected_text(self.pitch_algorithm.label())
+                    .show_ui(ui, |ui| {
+                        for algorithm in PitchAlgorithm::ALL {
+                            ui.selectable_value(
+                                &mut self.pitch_algorithm,
+                                algorithm,
+                                algorithm.label(),
+                            );
+                        }
+                    })
+                    .response
+                    .on_hover_text(
+                        "How the sung pitch is found to pick the note from. YIN and pYIN make \
+                         fewer octave mistakes on low voices; pYIN also follows the note from \
+                         frame to frame",
+                    );
+            });
+            self.f0_range_ui(ui);
+
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.correct_fry, "Correct vocal fry").on_hover_text(
+                    "Creaky stretches, often at the ends of phrases, normally pass through \
+                     untouched because correcting them pulls them up into a croak",
+                );
+                self.help_button(ui, Topic::CorrectFry);
+            });
+            self.help_ui(ui, Topic::CorrectFry);
+
+            ui.checkbox(&mut self.confidence_strength, "Follow detection confidence")
+                .on_hover_text(
+                    "Keep less of the correction the less surely a pitch is found in a stretch, \
+                     so breathy and noisy notes are corrected gently instead of all or nothing",
+                );
+
+            ui.add(
+                egui::Slider::new(&mut self.unvoiced_sensitivity, 0.0..=1.0)
+                    .text("consonant bypass"),
+            )
+            .on_hover_text(
+                "How readily sibilants, breaths, and other unvoiced sounds pass through \
+                 uncorrected, since hard correction makes them sound metallic. Higher catches \
+                 more borderline sounds; 0 corrects them like the rest",
+            );
+
+            ui.checkbox(&mut self.export_stems, "Export stems").on_hover_text(
+                "Also write the dry input, the correction, and each harmony voice on its own \
+                 next to the render, as <name>_dry.wav, <name>_corrected.wav, and \
+                 <name>_harmony_3rd.wav and so on, to blend in a DAW",
+            );
+            ui.checkbox(&mut self.float_master, "Export float master").on_hover_text(
+                "Also write the render unnormalized as 32-bit float, as <name>_master.wav, \
+                 keeping all of its headroom for mixing while the normalized file is for \
+                 listening",
+            );
+
+            self.tint_ui(ui);
+        });
+    }
+
+    fn melody_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Melody:");
+            let name = self.melody.as_ref().map(|(path, melody)| {
+                format!("{} ({} notes)", paths::display_name(path), melody.notes().len())
+            });
+            if ui
+                .button(name.as_deref().unwrap_or("MIDI File..."))
+                .on_hover_text(
+                    "Correct each moment to the note a MIDI file plays at that time instead of \
+                     the nearest note of the key. The key or custom scale still applies where \
+                     the MIDI rests",
+                )
+                .clicked()
+            {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("MIDI", &midi::EXTENSIONS)
+                    .set_title("Pick MIDI Melody")
+                    .pick_file()
+                {
+                    self.load_melody(path);
+                }
+            }
+            if self.melody.is_some()
+                && ui.small_button("✖").on_hover_text("Correct to the key again").clicked()
+            {
+                self.melody = None;
+            }
+        });
+    }
+
+    fn harmony_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Harmony:").on_hover_text(
+                "Add voices above the corrected lead, moving with it in the key or custom \
+                 scale. Each is another pass over the file, and with Export stems it is \
+                 written on its own too",
+            );
+            for (interval, name) in HARMONY_CHOICES {
+                let mut on = self.harmony.contains(&interval);
+                if ui.checkbox(&mut on, name).changed() {
+                    self.harmony.retain(|voice| *voice != interval);
+                    if on {
+                        self.harmony.push(interval);
+                    }
+                }
+            }
+        });
+        if !self.harmony.is_empty() {
+            ui.add(egui::Slider::new(&mut self.harmony_level, 0.0..=1.0).text("harmony level"));
+            ui.collapsing("Stereo routing", |ui| self.routing_ui(ui))
+                .header_response
+                .on_hover_text(
+                    "The level each voice goes into the left and right channel at when the \
+                     output is stereo. The harmony voices' levels scale the harmony level",
+                );
+        }
+    }
+
+    // The routes of renders: the voices spread `width` out, and those set by hand on top
+    fn routes(&self) -> Vec<Route> {
+        let spread = if self.width > 0.0 {
+            routing::spread(&self.harmony, self.width)
+        } else {
+            Vec::new()
+        };
+        spread.into_iter().chain(self.routing.iter().copied()).collect()
+    }
+
+    fn routing_ui(&mut self, ui: &mut egui::Ui) {
+        ui.add(egui::Slider::new(&mut self.width, routing::WIDTH_RANGE).text("width"))
+            .on_hover_text(
+                "Spread the harmony voices around the lead, alternately left and right. Check \
+                 the mono fold-down after the render if it will be played back in mono",
+            );
+        let routes = self.routes();
+        let voices: Vec<Voice> = std::iter::once(Voice::Lead)
+            .chain(self.harmony.iter().map(|&interval| Voice::Harmony(interval)))
+            .collect();
+        egui::Grid::new("routing_grid").num_columns(3).show(ui, |ui| {
+            for voice in voices {
+                let [mut left, mut right] = routing::levels(&routes, voice);
+                ui.label(match voice {
+                    Voice::Lead => "Lead".to_string(),
+                    Voice::Harmony(interval) => interval.to_string(),
+                });
+                let mut changed = false;
+                for (level, channel) in [(&mut left, "L "), (&mut right, "R ")] {
+                    let drag = egui::DragValue::new(level).clamp_range(0.0..=1.0).speed(0.01);
+                    changed |= ui.add(drag.prefix(channel)).changed();
+                }
+                if changed {
+                    self.routing.retain(|route| route.voice != voice);
+                    self.routing.push(Route { voice, left, right });
+                }
+                ui.end_row();
+            }
+        });
+        if !routes.is_empty() && ui.small_button("Center all").clicked() {
+            self.routing.clear();
+            self.width = 0.0;
+        }
+    }
+
+    fn load_melody(&mut self, path: PathBuf) {
+        match Melody::read_file(&path) {
+            Ok(melody) => {
+                self.log_event(format!(
+                    "Following the melody of {} ({} notes)",
+                    path.display(),
+                    melody.notes().len()
+                ));
+                self.melody = Some((path, melody));
+            }
+            Err(e) => self.log_event(format!("Couldn't load the melody: {}", e)),
+        }
+    }
+
+    fn tint_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Match Tone:");
+            let name = self.tint_reference.as_deref().map(paths::display_name);
+            if ui
+                .button(name.as_deref().unwrap_or("Reference..."))
+                .on_hover_text(
+                    "EQ the render toward the overall tone of a reference vocal, so a tuned \
+                     demo sits like the reference production",
+                )
+                .clicked()
+            {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Audio", &decoder::INPUT_EXTENSIONS)
+                    .set_title("Pick Tone Reference")
+                    .pick_file()
+                {
+                    self.log_event(format!("Matching tone to {}", path.display()));
+                    self.tint_reference = Some(path);
+                }
+            }
+            if self.tint_reference.is_some() {
+                ui.add(egui::Slider::new(&mut self.tint_amount, 0.0..=1.0).text("amount"));
+                if ui.small_button("✖").on_hover_text("Stop matching tone").clicked() {
+                    self.tint_reference = None;
+                }
+            }
+        });
+    }
+
+    fn preset_ui(&mut self, ui: &mut egui::Ui) {
+        self.preset_selector_ui(ui);
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.preset_name).hint_text("Preset name"));
+            let name = self.preset_name.trim().to_string();
+            if ui
+                .add_enabled(!name.is_empty(), egui::Button::new("💾 Save Preset"))
+                .on_hover_text("Save the strength, transition speed, and formant shift")
+                .clicked()
+            {
+                let preset = Preset::new(
+                    &name,
+                    self.pitch_correction_strength,
+                    self.transition_speed,
+                    self.formant_shift,
+                );
+                presets::upsert(&mut self.user_presets, preset);
+                self.save_user_presets(format!("Saved preset {}", name));
+                self.preset_name.clear();
+            }
+        });
+    }
+
+    fn preset_selector_ui(&mut self, ui: &mut egui::Ui) {
+        // Factory presets the user saved over are replaced by theirs
+        let choices: Vec<Preset> = presets::factory_presets()
+            .into_iter()
+            .filter(|factory| !self.user_presets.iter().any(|user| user.is_named(&factory.name)))
+            .chain(self.user_presets.iter().cloned())
+            .collect();
+        let current = choices.iter().position(|preset| {
+            preset.strength == self.pitch_correction_strength
+                && preset.transition == self.transition_speed
+                && preset.formant == self.formant_shift
+        });
+
+        let mut chosen = None;
+        let mut delete = None;
+        ui.horizontal(|ui| {
+            ui.label("Preset:");
+            egui::ComboBox::from_id_source("preset_selector")
+                .selected_text(current.map_or("Custom", |i| choices[i].name.as_str()))
+                .show_ui(ui, |ui| {
+                    for (i, preset) in choices.iter().enumerate() {
+                        if ui.selectable_label(current == Some(i), &preset.name).clicked() {
+                            chosen = Some(preset.clone());
+                        }
+                    }
+                });
+
+            let user_preset = current
+                .map(|i| &choices[i].name)
+                .filter(|name| self.user_presets.iter().any(|user| user.is_named(name)));
+            if ui
+                .add_enabled(user_preset.is_some(), egui::Button::new("🗑"))
+                .on_hover_text("Delete this preset")
+                .on_disabled_hover_text("Only your own presets can be deleted")
+                .clicked()
+            {
+                delete = user_preset.cloned();
+            }
+        });
+
+        if let Some(preset) = chosen {
+            self.take_settings(ClipSettings {
+                strength: preset.strength,
+                transition: preset.transition,
+                formant: preset.formant,
+                ..self.clip_settings()
+            });
+            self.log_event(format!("Applied preset {}", preset.name));
+        }
+        if let Some(name) = delete {
+            self.user_presets.retain(|preset| !preset.is_named(&name));
+            self.save_user_presets(format!("Deleted preset {}", name));
+        }
+    }
+
+    fn save_user_presets(&mut self, done: String) {
+        match presets::save_user_presets(&self.config, &self.user_presets) {
+            Ok(()) => self.log_event(done),
+            Err(e) => self.log_event(format!("Failed to save presets: {}", e)),
+        }
+    }
+
+    // Warnings about settings that look wrong for the input, with a button to fix each
+    fn advice_ui(&mut self, ui: &mut egui::Ui) {
+        let (Some(measured), Some(sample_rate)) = (&self.measurements, self.sample_rate) else {
+            return;
+        };
+        let setup = Setup {
+            key: (self.selected_note == 0 && self.scale().is_none()).then_some(self.selected_key),
+            fft_size: self.autotune_config().fft_size,
+            processing_rate: self.render_options().processing_rate(sample_rate as u32),
+        };
+        let mut apply = None;
+        for advice in advisor::check(&setup, measured) {
+            ui.horizontal(|ui| {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", advice.message));
+                let Some(fix) = advice.fix else {
+                    return;
+                };
+                let label = match fix {
+                    Fix::SelectKey(key) => format!("Switch to {}", ui::get_key_name(key)),
+                    Fix::ProcessAt(rate) => format!("Process at {} Hz", rate),
+                };
+                if ui.add_enabled(!self.is_processing, egui::Button::new(label)).clicked() {
+                    apply = Some(fix);
+                }
+            });
+        }
+
+        match apply {
+            Some(Fix::SelectKey(key)) => {
+                self.selected_key = key;
+                self.log_event(format!("Switched to {} as suggested", ui::get_key_name(key)));
+            }
+            Some(Fix::ProcessAt(rate)) => {
+                self.native_sample_rate = false;
+                self.log_event(format!("Processing at {} Hz as suggested", rate));
+            }
+            None => {}
+        }
+    }
+
+    fn processing_ui(&mut self, ui: &mut egui::Ui) {
+        self.advice_ui(ui);
+        ui.horizontal(|ui| {
+            let process_button = ui.add_enabled(
+                !self.is_processing && self.input_file.is_some() && self.output_file.is_some(),
+                egui::Button::new("🚀 Start Processing"),
+            );
+
+            if process_button.clicked() {
+                self.start_processing(true);
+            }
+
+            if self.is_processing {
+                if ui
+                    .button("⏹ Stop")
+                    .on_hover_text("Stop the render. What's rendered so far can still be kept")
+                    .clicked()
+                {
+                    self.cancel_processing.store(true, Ordering::Relaxed);
+                }
+                if self.theme.show_spinner() {
+                    ui.spinner();
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.add_enabled(
+                !self.is_processing,
+                egui::Checkbox::new(
+                    &mut self.monitor_while_processing,
+                    "🎧 Listen while rendering",
+                ),
+            )
+            .on_hover_text(
+                "Play the processed audio on the default output device as it is rendered, \
+                 so a bad-sounding render can be stopped early",
+            );
+
+            if let Some(monitor) = &self.monitor {
+                ui.label(format!("{:.1}s buffered", monitor.queued_secs()));
+                if ui.button("🔇 Stop Listening").clicked() {
+                    self.monitor = None;
+                }
+            }
+        });
+
+        self.regions_ui(ui);
+        self.sections_ui(ui);
+
+        // Progress bar
+        if self.is_processing {
+            ui.add(
+                egui::ProgressBar::new(self.processing_progress)
+                    .text(format!("{:.1}%", self.processing_progress * 100.0)),
+            );
+        }
+        if self.is_processing || self.processing_result.is_some() {
+            self.gain_reduction_ui(ui);
+        }
+
+        // Status
+        ui.label(format!("Status: {}", self.processing_status));
+
+        // Results
+        let mut save_partial = None;
+        let mut export_markers = None;
+        // Whether to use an identical earlier render or to render again, if asked
+        let mut duplicate = None;
+        if let Some(ref result) = self.processing_result {
+            match result {
+                ProcessingResult::Success {
+                    samples_processed,
+                    duration_ms,
+                    verification,
+                    input_loudness,
+                    mono,
+                    markers,
+                    ..
+                } => {
+                    ui.label(format!(
+                        "✅ Success: Processed {} samples in {}",
+                        samples_processed,
+                        format::duration((duration_ms / 1000.0) as f32)
+                    ));
+                    let lufs = |loudness: Option<f32>| {
+                        loudness.map_or("-".to_string(), |lufs| format!("{:.1} LUFS", lufs))
+                    };
+                    ui.label(format!(
+                        "🔊 Loudness: {} in, {} out",
+                        lufs(*input_loudness),
+                        lufs(verification.loudness)
+                    ));
+                    if let Some(mono) = mono {
+                        let check = format!(
+                            "🎧 Mono check: {:+.1} dB folded down, correlation {:.2}",
+                            mono.fold_down_db, mono.correlation
+                        );
+                        if mono.is_safe() {
+                            ui.label(check);
+                        } else {
+                            ui.colored_label(ui.visuals().warn_fg_color, check)
+                                .on_hover_text("Try a narrower stereo width");
+                        }
+                    }
+                    if verification.warnings().is_empty() {
+                        ui.label(format!("🔎 Output {}", verification.summary()));
+                    } else {
+                        ui.colored_label(
+                            ui.visuals().warn_fg_color,
+                            format!("⚠ Output check: {}", verification.summary()),
+                        );
+                    }
+                    if !markers.is_empty() {
+                        ui.horizontal(|ui| {
+                            ui.label(format!("📍 {} spot(s) to check", markers.len()));
+                            if ui
+                                .button("Export Markers...")
+                                .on_hover_text(
+                                    "Save the frames that fell back to the dry input, and any \
+                                     output warnings, as REAPER markers or labels to jump to \
+                                     in your DAW",
+                                )
+                                .clicked()
+                            {
+                                export_markers = Some(markers.clone());
+                            }
+                        });
+                    }
+                }
+                ProcessingResult::Error(err) => {
+                    ui.colored_label(ui.visuals().error_fg_color, format!("❌ Error: {}", err));
+                }
+                ProcessingResult::Cancelled(None) => {
+                    ui.label("⏹ Stopped before anything was rendered");
+                }
+                ProcessingResult::Cancelled(Some(partial)) => {
+                    ui.horizontal(|ui| {
+                        ui.label(format!(
+                            "⏹ Stopped after {} of audio",
+                            format::duration(partial.duration_secs())
+                        ));
+                        if ui.button("💾 Keep Partial Result").clicked() {
+                            save_partial = Some(partial.clone());
+                        }
+                    });
+                }
+                ProcessingResult::Duplicate { render, fingerprint } => {
+                    ui.label(format!(
+                        "♻️ This input was rendered with these settings before: {}",
+                        render.display()
+                    ));
+                    duplicate = duplicate_ui(ui, render, *fingerprint);
+                }
+            }
+        }
+        self.answer_duplicate(duplicate);
+        if let Some(partial) = save_partial {
+            self.save_partial_render(&partial);
+        }
+        if let Some(markers) = export_markers {
+            self.export_labels(&markers, "markers.csv");
+        }
+
+        self.waveform_ui(ui);
+        // Popped out, the pitch view goes along with the analysis
+        if !self.analysis_detached {
+            self.pitch_ui(ui);
+        }
+        self.playback_ui(ui);
+
+        ui.separator();
+
+        // Info section
+        ui.collapsing("ℹ️ About", |ui| {
+            ui.label("Desktop Autotune Application");
+            ui.label("Built with the synthphone_vocals real-time autotune library");
+            ui.label("Supports WAV, MP3, and M4A/AAC input at various sample rates");
+            ui.label("Uses FFT-based pitch detection and correction");
+        });
+    }
+
+    // Use the identical earlier render with Some, or render again with None
+    fn answer_duplicate(&mut self, answer: Option<Option<(PathBuf, Fingerprint)>>) {
+        match answer {
+            Some(Some((render, fingerprint))) => self.reuse_render(&render, fingerprint),
+            Some(None) => self.start_processing(false),
+            None => {}
+        }
+    }
+
+    /// File, preset, key, strength, and go, small enough for a corner of the screen. The
+    /// settings are the full layout's, so switching back keeps them.
+    fn compact_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.strong("🎵 Autotune");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.button("⛶ Full Layout").clicked() {
+                    self.set_compact(ui.ctx(), false);
+                }
+            });
+        });
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if ui.button("📂 Input").clicked() {
+                self.select_input_file();
+                // Quick jobs go next to their input unless told otherwise
+                if let Some(input) = self.input_file.clone() {
+                    self.default_output_next_to(&input);
+                }
+            }
+            match &self.input_file {
+                Some(path) => ui.label(paths::display_name(path)),
+                None => ui.weak("No file selected"),
+            };
+        });
+        ui.horizontal(|ui| {
+            if ui.button("💾 Output").clicked() {
+                self.select_output_file();
+            }
+            match &self.output_file {
+                Some(path) => ui.label(paths::display_name(path)),
+                None => ui.weak("No output path selected"),
+            };
+        });
+
+        self.preset_selector_ui(ui);
+        ui.horizontal(|ui| {
+            ui.label("Key:");
+            egui::ComboBox::from_id_source("compact_key_selector")
+                .selected_text(ui::get_key_name(self.selected_key))
+                .show_ui(ui, |ui| {
+                    for (i, name) in ui::KEY_NAMES.iter().enumerate() {
+                        ui.selectable_value(&mut self.selected_key, i, *name);
+                    }
+                });
+        });
+        ui.horizontal(|ui| {
+            ui.label("Strength:");
+            ui.add(egui::Slider::new(&mut self.pitch_correction_strength, 0.0..=1.0));
+        });
+
+        ui.horizontal(|ui| {
+            let ready =
+                !self.is_processing && self.input_file.is_some() && self.output_file.is_some();
+            if ui.add_enabled(ready, egui::Button::new("🚀 Go")).clicked() {
+                self.start_processing(true);
+            }
+            if self.is_processing {
+                if ui.button("⏹ Stop").clicked() {
+                    self.cancel_processing.store(true, Ordering::Relaxed);
+                }
+                ui.add(egui::ProgressBar::new(self.processing_progress).show_percentage());
+            }
+        });
+        ui.label(self.processing_status.as_str());
+        let duplicate = match &self.processing_result {
+            Some(ProcessingResult::Duplicate { render, fingerprint }) => {
+                duplicate_ui(ui, render, *fingerprint)
+            }
+            _ => None,
+        };
+        self.answer_duplicate(duplicate);
+    }
+
+    fn set_compact(&mut self, ctx: &egui::Context, compact: bool) {
+        self.compact = compact;
+        layout::resize_for(ctx, compact);
+    }
+
+    fn buffer_size_ui(&mut self, ui: &mut egui::Ui) {
+        let previous = self.live_buffer_size;
+        egui::ComboBox::from_id_source("live_buffer_size")
+            .selected_text(format!("{} frames", self.live_buffer_size))
+            .show_ui(ui, |ui| {
+                for size in live::BUFFER_SIZES {
+                    ui.selectable_value(
+                        &mut self.live_buffer_size,
+                        size,
+                        format!("{} frames", size),
+                    );
+                }
+            })
+            .response
+            .on_hover_text("Smaller buffers lower the delay but may crackle on slower machines");
+
+        // Devices only take a new buffer size when reopened
+        if self.live_buffer_size != previous && self.live_session.is_some() {
+            self.live_notice = None;
+            self.live_buffer_exhausted = false;
+            self.start_live();
+        }
+    }
+
+    fn devices_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("The devices live mode, recording, and playback use.");
+        ui.separator();
+
+        let list = self.device_list.get_or_insert_with(|| DeviceList::scan(&self.devices)).clone();
+        let previous = self.devices.clone();
+        egui::Grid::new("devices").num_columns(2).show(ui, |ui| {
+            ui.label("Input:");
+            device_combo(ui, "input_device", &mut self.devices.input, &list.inputs);
+            ui.end_row();
+
+            ui.label("Output:");
+            device_combo(ui, "output_device", &mut self.devices.output, &list.outputs);
+            ui.end_row();
+
+            ui.label("Sample rate:");
+            let rate_text = |rate: Option<u32>| {
+                rate.map_or("Device default".to_string(), |rate| format::sample_rate(rate as f32))
+            };
+            egui::ComboBox::from_id_source("device_sample_rate")
+                .selected_text(rate_text(self.devices.sample_rate))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.devices.sample_rate, None, rate_text(None));
+                    for &rate in &list.rates {
+                        let text = rate_text(Some(rate));
+                        ui.selectable_value(&mut self.devices.sample_rate, Some(rate), text);
                     }
+                })
+                .response
+                .on_hover_text("Rates both devices can run at. Files are resampled to play");
+            ui.end_row();
+
+            ui.label("Buffer size:");
+            self.buffer_size_ui(ui);
+            ui.end_row();
+        });
+
+        ui.horizontal(|ui| {
+            if ui.button("⟳ Refresh").on_hover_text("Look for devices plugged in since").clicked()
+            {
+                self.device_list = None;
+            }
+            if ui
+                .add_enabled(self.test_tone.is_none(), egui::Button::new("🔊 Test Tone"))
+                .on_hover_text("Play a second of A4 on the output")
+                .clicked()
+            {
+                self.play_test_tone();
+            }
+        });
+        if let Some(err) = &self.device_error {
+            ui.colored_label(ui.visuals().error_fg_color, format!("❌ {}", err));
+        }
+
+        if self.devices != previous {
+            devices::set(self.devices.clone());
+            // The rates on offer depend on the devices
+            self.device_list = None;
+            self.device_error = None;
+            if self.live_session.is_some() {
+                self.start_live();
+            }
+        }
+    }
+
+    fn play_test_tone(&mut self) {
+        match Monitor::start(devices::TONE_RATE, None) {
+            Ok(monitor) => {
+                monitor.feed().push(&devices::test_tone(devices::TONE_RATE));
+                self.test_tone = Some(monitor);
+                self.device_error = None;
+            }
+            Err(e) => {
+                self.log_event(format!("Test tone failed: {}", e));
+                self.device_error = Some(e);
+            }
+        }
+    }
+
+    fn record_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Record a take from the microphone, then tune it like any opened file.");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            if self.recorder.is_some() {
+                if ui.button("⏹ Stop").clicked() {
+                    self.stop_recording();
+                }
+            } else if ui
+                .add_enabled(!self.is_processing, egui::Button::new("⏺ Record"))
+                .on_disabled_hover_text("Wait for the current render to finish")
+                .clicked()
+            {
+                self.start_recording();
+            }
+            ui.checkbox(&mut self.tune_after_recording, "Tune when stopped").on_hover_text(
+                "Process the take with the current settings as soon as recording stops",
+            );
+        });
+
+        let Some(recorder) = &self.recorder else {
+            if let Some(err) = &self.record_error {
+                ui.colored_label(ui.visuals().error_fg_color, format!("❌ {}", err));
+            }
+            return;
+        };
+        ui.label(format!(
+            "🔴 Recording at {}Hz: {}",
+            recorder.sample_rate,
+            format::duration(recorder.duration_secs())
+        ));
+
+        let now = Instant::now();
+        let elapsed = self.record_metered_at.map_or(0.0, |at| (now - at).as_secs_f32());
+        self.record_metered_at = Some(now);
+        let colors = self.theme.palette.colors(ui.visuals());
+        let channels = self.record_meters.len();
+        for (channel, (meter, peak)) in
+            self.record_meters.iter_mut().zip(recorder.peaks()).enumerate()
+        {
+            meter.update(peak, elapsed);
+            let color = if meter.clipped {
+                colors.error
+            } else if meter.db() > recorder::HOT_DB {
+                colors.warning
+            } else {
+                colors.good
+            };
+            ui.horizontal(|ui| {
+                ui.label(match (channels, channel) {
+                    (1, _) => "Level:",
+                    (_, 0) => "L:",
+                    _ => "R:",
+                });
+                ui.add(
+                    egui::ProgressBar::new(meter.fraction())
+                        .desired_width(200.0)
+                        .fill(color)
+                        .text(format!("{:.0} dB", meter.db().max(recorder::METER_FLOOR_DB))),
+                );
+                if meter.clipped {
+                    ui.colored_label(colors.error, "Clipped").on_hover_text(
+                        "Turn the input gain down and record again, clipping can't be undone",
+                    );
+                }
+            });
+        }
+    }
+
+    fn live_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Sing into your microphone and hear the corrected voice as you go.");
+        ui.label("Use headphones, otherwise the speakers feed back into the microphone.");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Buffer size:");
+            self.buffer_size_ui(ui);
+        });
+
+        let mut auto_buffer_size = !self.fixed_live_buffer_size;
+        ui.checkbox(&mut auto_buffer_size, "Raise buffer size on glitches").on_hover_text(
+            "When live mode crackles repeatedly, restart it with the next larger buffer until \
+             it plays cleanly",
+        );
+        self.fixed_live_buffer_size = !auto_buffer_size;
+
+        ui.horizontal(|ui| {
+            if self.live_session.is_some() {
+                if ui.button("⏹ Stop Live").clicked() {
+                    self.stop_live();
+                }
+            } else if ui.button("🎤 Start Live").clicked() {
+                self.start_live();
+            }
+        });
+
+        if let Some(session) = &mut self.live_session {
+            ui.label(format!(
+                "🟢 Live at {}Hz, ~{:.0}ms latency",
+                session.sample_rate,
+                session.latency_ms()
+            ));
+
+            let load = session.load();
+            let block_ms = session.buffer_size as f32 * 1000.0 / session.sample_rate as f32;
+            ui.horizontal(|ui| {
+                ui.label("CPU:");
+                let colors = self.theme.palette.colors(ui.visuals());
+                let color = if load.peak > 1.0 {
+                    colors.error
+                } else if load.peak > 0.7 {
+                    colors.warning
+                } else {
+                    colors.good
+                };
+                ui.add(
+                    egui::ProgressBar::new(load.load.min(1.0))
+                        .desired_width(120.0)
+                        .fill(color)
+                        .text(format!("{:.0}%", load.load * 100.0)),
+                )
+                .on_hover_text(format!(
+                    "Processing time per {:.1}ms block, on average. Peak {:.0}%",
+                    block_ms,
+                    load.peak * 100.0
+                ));
+                ui.label(format!("{:.0}% headroom", load.headroom() * 100.0));
+            });
+            ui.label(format!(
+                "Xruns: {} overloads, {} dropouts, {:.2}s dropped",
+                load.overloads, load.underruns, load.dropped_secs
+            ))
+            .on_hover_text(
+                "Overloads: blocks that took longer to process than they last. Dropouts: the \
+                 output ran out of audio. Dropped: audio skipped because the devices run at \
+                 slightly different speeds",
+            );
+            if let Some(diagnosis) = load.diagnosis() {
+                ui.colored_label(ui.visuals().warn_fg_color, format!("⚠ {}", diagnosis));
+            }
+            if let Some(notice) = &self.live_notice {
+                ui.label(format!("ℹ {}", notice));
+            }
+
+            if session.failed_frames() > 0 {
+                ui.label(format!(
+                    "⚠ {} frames passed through uncorrected",
+                    session.failed_frames()
+                ));
+            }
+            ui.label(
+                "Key, note, octave, and formant changes apply immediately; restart live mode \
+                      to apply strength and transition speed.",
+            );
+        } else if let Some(err) = &self.live_error {
+            ui.colored_label(ui.visuals().error_fg_color, format!("❌ {}", err));
+        }
+    }
+
+    fn timeline_ui(&mut self, ui: &mut egui::Ui) {
+        let rendering = self.timeline_receiver.is_some();
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!rendering, egui::Button::new("➕ Add Clips")).clicked() {
+                self.add_timeline_clips();
+            }
+            let can_render = !rendering && !self.timeline.clips.is_empty();
+            if ui.add_enabled(can_render, egui::Button::new("💾 Export Timeline")).clicked() {
+                self.start_timeline_render();
+            }
+            if rendering && ui.button("⏹ Stop").clicked() {
+                self.timeline_cancel.store(true, Ordering::Relaxed);
+            }
+        });
+
+        if rendering {
+            ui.add(
+                egui::ProgressBar::new(self.timeline_progress)
+                    .text(format!("{:.1}%", self.timeline_progress * 100.0)),
+            );
+        } else if let Some(status) = &self.timeline_status {
+            ui.label(status);
+        }
+
+        if self.timeline.clips.is_empty() {
+            ui.label("Add clips, place them where they belong, and export them as one file.");
+            return;
+        }
+
+        ui.separator();
+        self.timeline_lanes_ui(ui, !rendering);
+
+        let Some(index) = self.selected_clip.filter(|&i| i < self.timeline.clips.len()) else {
+            ui.label("Click a clip to edit it. Drag clips to move them.");
+            return;
+        };
+        let main_settings = self.clip_settings();
+        let mut remove = false;
+        // Doubles can follow any clip that isn't a double itself
+        let leads: Vec<(usize, String)> = self
+            .timeline
+            .clips
+            .iter()
+            .enumerate()
+            .filter(|&(i, clip)| i != index && clip.align_to.is_none())
+            .map(|(i, clip)| (i, format!("{}: {}", i + 1, clip.name)))
+            .collect();
+        let is_lead = self.timeline.is_lead(index);
+        let mut linked = self.linked.clone();
+        let clip = &mut self.timeline.clips[index];
+        let before = clip.settings;
+
+        ui.separator();
+        ui.add_enabled_ui(!rendering, |ui| {
+            ui.horizontal(|ui| {
+                ui.strong(&clip.name);
+                ui.label(format!("({})", format::duration(clip.duration_secs)));
+                remove = ui.button("🗑 Remove").clicked();
+            });
+            ui.horizontal(|ui| {
+                ui.label("Start:");
+                ui.add(
+                    egui::DragValue::new(&mut clip.offset_secs)
+                        .speed(0.01)
+                        .clamp_range(0.0..=f32::MAX)
+                        .suffix(" s"),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Stretch:");
+                let mut percent = clip.stretch * 100.0;
+                let range = stretch::MIN_STRETCH * 100.0..=stretch::MAX_STRETCH * 100.0;
+                ui.add(
+                    egui::DragValue::new(&mut percent).speed(0.1).clamp_range(range).suffix("%"),
+                )
+                .on_hover_text("Length of the clip on the timeline, with its pitch kept");
+                clip.stretch = percent / 100.0;
+                ui.label(format!("-> {}", format::duration(clip.length_secs())));
+            });
+            ui.add_enabled_ui(!is_lead, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Align to:");
+                    let selected = leads
+                        .iter()
+                        .find(|(i, _)| Some(*i) == clip.align_to)
+                        .map_or("Nothing", |(_, name)| name.as_str());
+                    egui::ComboBox::from_id_source("clip_align").selected_text(selected).show_ui(
+                        ui,
+                        |ui| {
+                            ui.selectable_value(&mut clip.align_to, None, "Nothing");
+                            for (i, name) in &leads {
+                                ui.selectable_value(&mut clip.align_to, Some(*i), name);
+                            }
+                        },
+                    );
+                })
+                .response
+                .on_disabled_hover_text("Other clips are aligned to this one");
+            });
+            if clip.align_to.is_some() {
+                let limits = &mut clip.align_limits;
+                ui.horizontal(|ui| {
+                    ui.label("Max shift:");
+                    let mut ms = limits.max_shift_secs * 1000.0;
+                    ui.add(egui::Slider::new(&mut ms, 0.0..=200.0).suffix(" ms"));
+                    limits.max_shift_secs = ms / 1000.0;
+                    ui.label("Max pitch:");
+                    ui.add(
+                        egui::Slider::new(&mut limits.max_pitch_cents, 0.0..=100.0).suffix(" ct"),
+                    )
+                    .on_hover_text(
+                        "Larger differences to the lead are left alone; 0 aligns timing only",
+                    );
+                });
+            }
+
+            let settings = &mut clip.settings;
+            ui.horizontal(|ui| {
+                ui.label("Key:");
+                let mut key = settings.key as usize;
+                egui::ComboBox::from_id_source("clip_key")
+                    .selected_text(ui::get_key_name(key))
+                    .show_ui(ui, |ui| {
+                        for (i, name) in ui::KEY_NAMES.iter().enumerate() {
+                            ui.selectable_value(&mut key, i, *name);
+                        }
+                    });
+                settings.key = key as i32;
+                link_button(ui, &mut linked, Param::Key);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Pitch Correction:");
+                ui.add(egui::Slider::new(&mut settings.strength, 0.0..=1.0).text("strength"));
+                link_button(ui, &mut linked, Param::Strength);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Transition Speed:");
+                ui.add(egui::Slider::new(&mut settings.transition, 0.01..=1.0).text("speed"));
+                link_button(ui, &mut linked, Param::Transition);
+            });
+            ui.horizontal(|ui| {
+                ui.label("Formant Shift:");
+                ui.add(egui::Slider::new(&mut settings.formant, -12..=12).text("semitones"));
+                link_button(ui, &mut linked, Param::Formant);
+            });
+            if ui.button("Use Main Settings").clicked() {
+                *settings = main_settings;
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Gain:");
+                ui.add(egui::Slider::new(&mut clip.gain_db, -24.0..=12.0).suffix(" dB"));
+            });
+            ui.horizontal(|ui| {
+                ui.label("Fade in:");
+                ui.add(egui::DragValue::new(&mut clip.fade_in_secs).speed(0.01).suffix(" s"));
+                ui.label("Fade out:");
+                ui.add(egui::DragValue::new(&mut clip.fade_out_secs).speed(0.01).suffix(" s"));
+            });
+            clip.clamp_fades();
+        });
+
+        let after = self.timeline.clips[index].settings;
+        if after != before {
+            linked.spread(
+                &before,
+                &after,
+                self.timeline.clips.iter_mut().map(|clip| &mut clip.settings),
+            );
+        }
+        self.linked = linked;
+
+        if remove {
+            self.timeline.remove(index);
+            self.selected_clip = None;
+        }
+    }
+
+    /// One lane per clip, positioned by offset. Clips can be dragged along the time axis,
+    /// with handles for the fades (top corners), gain (drag up or down), and time-stretch
+    /// (right edge).
+    fn session_ui(&mut self, ui: &mut egui::Ui) {
+        let rendering = self.daw_receiver.is_some();
+        let any_selected = self.daw_selected.contains(&true);
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!rendering, egui::Button::new("📂 Open Project...")).clicked() {
+                self.open_daw_project();
+            }
+            let can_render = !rendering && any_selected;
+            if ui
+                .add_enabled(can_render, egui::Button::new("💾 Correct Selected"))
+                .on_hover_text("Correct the picked clips and save a project copy that uses them")
+                .clicked()
+            {
+                self.start_project_render();
+            }
+            if rendering && ui.button("⏹ Stop").clicked() {
+                self.daw_cancel.store(true, Ordering::Relaxed);
+            }
+        });
+
+        if rendering {
+            ui.add(
+                egui::ProgressBar::new(self.daw_progress)
+                    .text(format!("{:.1}%", self.daw_progress * 100.0)),
+            );
+        } else if let Some(status) = &self.daw_status {
+            ui.label(status);
+        }
+
+        let Some(project) = &self.daw_project else {
+            ui.label("Open a DAWproject session to correct its vocal clips.");
+            ui.label("Most DAWs can export AAF sessions as DAWproject.");
+            return;
+        };
+        ui.separator();
+        ui.strong(paths::display_name(&project.path));
+        if project.clips.is_empty() {
+            ui.label("The project has no audio clips.");
+            return;
+        }
 
-                    if let Some(ref path) = self.output_file {
-                        ui.label(format!("💾 {}", path.file_name().unwrap().to_string_lossy()));
-                    } else {
-                        ui.label("No output path selected");
+        ui.add_enabled_ui(!rendering, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Select Vocals").clicked() {
+                    for (selected, clip) in self.daw_selected.iter_mut().zip(&project.clips) {
+                        *selected = clip.looks_like_vocals();
+                    }
+                }
+                if ui.button("Select None").clicked() {
+                    self.daw_selected.fill(false);
+                }
+            });
+            egui::ScrollArea::vertical().id_source("daw_clips").show(ui, |ui| {
+                egui::Grid::new("daw_clip_grid").striped(true).show(ui, |ui| {
+                    ui.strong("");
+                    ui.strong("Track");
+                    ui.strong("Clip");
+                    ui.strong("File");
+                    ui.end_row();
+                    for (selected, clip) in self.daw_selected.iter_mut().zip(&project.clips) {
+                        ui.checkbox(selected, "");
+                        ui.label(&clip.track);
+                        ui.label(&clip.name);
+                        let file = ui.label(paths::display_name(std::path::Path::new(&clip.file)));
+                        if clip.external {
+                            file.on_hover_text(format!("{} (outside the project)", clip.file));
+                        } else {
+                            file.on_hover_text(&clip.file);
+                        }
+                        ui.end_row();
                     }
                 });
+            });
+        });
+    }
+
+    fn timeline_lanes_ui(&mut self, ui: &mut egui::Ui, editable: bool) {
+        const LANE_HEIGHT: f32 = 36.0;
+        const HANDLE_SIZE: f32 = 8.0;
+        const DB_PER_PIXEL: f32 = 0.1;
+        let width = ui.available_width();
+        // Leave room to drag the last clip further out
+        let visible_secs = (self.timeline.duration_secs() * 1.25).max(1.0);
+        let pixels_per_sec = width / visible_secs;
+
+        let height = LANE_HEIGHT * self.timeline.clips.len() as f32;
+        let (area, _) = ui.allocate_exact_size(egui::vec2(width, height), egui::Sense::hover());
+        let painter = ui.painter_at(area);
+        painter.rect_filled(area, 2.0, ui.visuals().extreme_bg_color);
+
+        for (i, clip) in self.timeline.clips.iter_mut().enumerate() {
+            let rect = egui::Rect::from_min_size(
+                area.min + egui::vec2(clip.offset_secs * pixels_per_sec, i as f32 * LANE_HEIGHT),
+                egui::vec2(clip.length_secs() * pixels_per_sec, LANE_HEIGHT - 2.0),
+            );
+            let response =
+                ui.interact(rect, ui.id().with(("clip", i)), egui::Sense::click_and_drag());
+            if response.clicked() || response.drag_started() {
+                self.selected_clip = Some(i);
+            }
+            if editable && response.dragged() {
+                clip.offset_secs =
+                    (clip.offset_secs + response.drag_delta().x / pixels_per_sec).max(0.0);
+            }
+
+            let selected = self.selected_clip == Some(i);
+            let fill = if selected {
+                ui.visuals().selection.bg_fill
+            } else {
+                ui.visuals().widgets.inactive.bg_fill
+            };
+            painter.rect_filled(rect, 3.0, fill);
+            painter.text(
+                rect.left_center() + egui::vec2(4.0, 0.0),
+                egui::Align2::LEFT_CENTER,
+                &clip.name,
+                egui::FontId::proportional(12.0),
+                ui.visuals().text_color(),
+            );
+            response.on_hover_text(format!(
+                "{} at {}",
+                clip.name,
+                format::duration(clip.offset_secs)
+            ));
+
+            // Handles go on top of the clip body, so they win when both are under the pointer
+            let handle = |center: egui::Pos2, id: &str| {
+                let handle_rect =
+                    egui::Rect::from_center_size(center, egui::Vec2::splat(HANDLE_SIZE));
+                let response = ui.interact(handle_rect, ui.id().with((id, i)), egui::Sense::drag());
+                (handle_rect, response)
+            };
+            let fade_in_x = rect.left() + clip.fade_in_secs * pixels_per_sec;
+            let fade_out_x = rect.right() - clip.fade_out_secs * pixels_per_sec;
+            let (fade_in_rect, fade_in) = handle(egui::pos2(fade_in_x, rect.top()), "fade_in");
+            let (fade_out_rect, fade_out) = handle(egui::pos2(fade_out_x, rect.top()), "fade_out");
+            let (gain_rect, gain) =
+                handle(rect.center_top() + egui::vec2(0.0, HANDLE_SIZE), "gain");
+            let (stretch_rect, stretch) = handle(rect.right_center(), "stretch");
+            if editable {
+                clip.fade_in_secs += fade_in.drag_delta().x / pixels_per_sec;
+                clip.fade_out_secs -= fade_out.drag_delta().x / pixels_per_sec;
+                clip.gain_db =
+                    (clip.gain_db - gain.drag_delta().y * DB_PER_PIXEL).clamp(-24.0, 12.0);
+                // The clip end follows the pointer
+                let length = clip.length_secs() + stretch.drag_delta().x / pixels_per_sec;
+                clip.stretch =
+                    (length / clip.duration_secs).clamp(stretch::MIN_STRETCH, stretch::MAX_STRETCH);
+                clip.clamp_fades();
+            }
+
+            let stroke = ui.visuals().widgets.active.fg_stroke;
+            painter.line_segment([rect.left_bottom(), egui::pos2(fade_in_x, rect.top())], stroke);
+            painter.line_segment([egui::pos2(fade_out_x, rect.top()), rect.right_bottom()], stroke);
+            painter.rect_filled(fade_in_rect, 1.0, stroke.color);
+            painter.rect_filled(fade_out_rect, 1.0, stroke.color);
+            painter.circle_filled(gain_rect.center(), HANDLE_SIZE / 2.0, stroke.color);
+            painter.rect_filled(stretch_rect, 1.0, stroke.color);
+            if clip.gain_db != 0.0 {
+                painter.text(
+                    gain_rect.right_center() + egui::vec2(4.0, 0.0),
+                    egui::Align2::LEFT_CENTER,
+                    format!("{:+.1} dB", clip.gain_db),
+                    egui::FontId::proportional(11.0),
+                    ui.visuals().text_color(),
+                );
+            }
+            fade_in.on_hover_text("Drag to set the fade-in");
+            fade_out.on_hover_text("Drag to set the fade-out");
+            gain.on_hover_text("Drag up or down to set the clip gain");
+            stretch.on_hover_text(format!(
+                "Drag to stretch the clip without changing its pitch ({:.0}%)",
+                clip.stretch * 100.0
+            ));
+        }
+    }
+
+    fn analysis_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let toggle_label = if self.analysis_detached {
+                "Dock"
+            } else {
+                "Pop Out"
+            };
+            if ui.button(toggle_label).clicked() {
+                self.analysis_detached = !self.analysis_detached;
+            }
+        });
+
+        if self.analysis_detached {
+            ui.label("Shown in a separate window");
+        } else {
+            self.show_analysis(ui);
+        }
+    }
+
+    fn file_menu_ui(&mut self, ui: &mut egui::Ui) {
+        if ui.button("📂 Open Project...").clicked() {
+            ui.close_menu();
+            self.open_project_file();
+        }
+        if ui.button("💾 Save Project...").clicked() {
+            ui.close_menu();
+            self.save_project_file();
+        }
+        ui.separator();
+        if ui.button("📜 Export Session Journal...").clicked() {
+            ui.close_menu();
+            self.export_journal();
+        }
+    }
+
+    fn export_journal(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Text", &[journal::EXTENSION])
+            .set_title("Export Session Journal")
+            .set_file_name(format!("session-journal.{}", journal::EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+
+        match self.journal.export(&path) {
+            Ok(()) => self.log_event(format!("Exported the session journal to {}", path.display())),
+            Err(e) => {
+                self.processing_status = e.clone();
+                self.log_event(e);
+            }
+        }
+    }
+
+    fn project_file(&self) -> ProjectFile {
+        let config = self.autotune_config();
+        ProjectFile {
+            input: self.input_file.clone(),
+            output: self.output_file.clone(),
+            key: self.selected_key as i32,
+            note: self.selected_note,
+            octave: self.octave,
+            formant: self.formant_shift,
+            scale: self.scale(),
+            strength: self.pitch_correction_strength,
+            transition: self.transition_speed,
+            fft_size: config.fft_size,
+            hop_size: config.hop_size,
+            window: self.window,
+            padding: self.padding,
+            pitch_algorithm: self.pitch_algorithm,
+            f0_range: self.f0_range,
+            a4: self.a4,
+            correct_fry: self.correct_fry,
+            confidence_strength: self.confidence_strength,
+            unvoiced_sensitivity: self.unvoiced_sensitivity,
+            tint: self.tint_reference.clone(),
+            tint_amount: self.tint_amount,
+            output_rate: self.output_rate,
+            length: self.length,
+            output_depth: self.output_depth,
+            output_channels: self.output_channels,
+            normalization: self.normalization,
+            limit_db: self.limiter.then_some(self.limit_db),
+            energy: self.energy_strength.then_some(self.energy_curve),
+            chain: self.chain,
+            high_pass_hz: self.high_pass.then_some(self.high_pass_hz),
+            gate: self.noise_gate.then_some(self.gate),
+            eq: self.eq.then_some(self.equalizer),
+            compressor: self.compress.then_some(self.compressor),
+            midi: self.melody.as_ref().map(|(path, _)| path.clone()),
+            bookmarks: self.bookmarks.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn save_project_file(&mut self) {
+        let stem = self.input_file.as_ref().and_then(|path| path.file_stem());
+        let stem = stem.map_or("project".into(), |stem| stem.to_string_lossy());
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Autotune Project", &[project_file::EXTENSION])
+            .set_title("Save Project")
+            .set_file_name(format!("{}.{}", stem, project_file::EXTENSION))
+            .save_file()
+        else {
+            return;
+        };
+
+        match self.project_file().save(&path) {
+            Ok(()) => self.log_event(format!("Saved project {}", path.display())),
+            Err(e) => {
+                self.processing_status = e.clone();
+                self.log_event(e);
+            }
+        }
+    }
+
+    fn open_project_file(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("Autotune Project", &[project_file::EXTENSION])
+            .set_title("Open Project")
+            .pick_file()
+        else {
+            return;
+        };
+
+        self.load_project_file(&path);
+    }
+
+    fn load_project_file(&mut self, path: &std::path::Path) {
+        match ProjectFile::load(path) {
+            Ok(project) => {
+                self.log_event(format!("Opened project {}", path.display()));
+                self.apply_project_file(project);
+            }
+            Err(e) => {
+                self.processing_status = e.clone();
+                self.log_event(e);
+            }
+        }
+    }
+
+    fn apply_project_file(&mut self, project: ProjectFile) {
+        // Hand-edited projects are kept within what the controls offer
+        self.take_settings(ClipSettings {
+            key: project.key.clamp(0, 23),
+            note: project.note.clamp(0, 12),
+            octave: project.octave.clamp(0, 4),
+            formant: project.formant.clamp(-12, 12),
+            strength: project.strength.clamp(0.0, 1.0),
+            transition: project.transition.clamp(0.01, 1.0),
+            a4: project.a4.clamp(*pipeline::A4_RANGE.start(), *pipeline::A4_RANGE.end()),
+        });
+        self.pitch_algorithm = project.pitch_algorithm;
+        self.f0_range = project.f0_range.filter(|range| range.validate().is_ok());
+        self.correct_fry = project.correct_fry;
+        self.confidence_strength = project.confidence_strength;
+        self.unvoiced_sensitivity = project.unvoiced_sensitivity.clamp(0.0, 1.0);
+        self.tint_amount = project.tint_amount.clamp(0.0, 1.0);
+        self.output_rate = project.output_rate.filter(|rate| resample::OUTPUT_RATES.contains(rate));
+        self.length = project.length;
+        self.output_depth = project.output_depth;
+        self.output_channels =
+            project.output_channels.filter(|channels| (1..=2).contains(channels));
+        self.normalization = project.normalization;
+        self.energy_strength = project.energy.is_some();
+        if let Some(curve) = project.energy {
+            self.energy_curve = curve;
+        }
+        self.chain = project.chain;
+        self.high_pass = project.high_pass_hz.is_some();
+        if let Some(hz) = project.high_pass_hz {
+            self.high_pass_hz =
+                hz.clamp(*prefilter::HIGH_PASS_RANGE.start(), *prefilter::HIGH_PASS_RANGE.end());
+        }
+        self.noise_gate = project.gate.is_some();
+        if let Some(gate) = project.gate {
+            self.gate = gate;
+        }
+        self.eq = project.eq.is_some();
+        if let Some(eq) = project.eq.filter(|eq| eq.validate().is_ok()) {
+            self.equalizer = eq;
+        }
+        self.compress = project.compressor.is_some();
+        if let Some(compressor) =
+            project.compressor.filter(|compressor| compressor.validate().is_ok())
+        {
+            self.compressor = compressor;
+        }
+        self.limiter = project.limit_db.is_some();
+        if let Some(db) = project.limit_db.filter(|&db| dynamics::validate_ceiling(db).is_ok()) {
+            self.limit_db = db;
+        }
+        self.tint_reference = match project.tint {
+            Some(tint) if !tint.exists() => {
+                self.log_event(format!("Project tone reference not found: {}", tint.display()));
+                None
+            }
+            tint => tint,
+        };
+        self.use_custom_scale = project.scale.is_some();
+        if let Some(scale) = project.scale {
+            self.custom_scale = scale;
+        }
+        self.melody = None;
+        if let Some(midi) = project.midi {
+            self.load_melody(midi);
+        }
+
+        match engine::validate_frames(project.fft_size, project.hop_size) {
+            Ok(()) => (self.fft_size, self.hop_size) = (project.fft_size, project.hop_size),
+            Err(e) => self.log_event(format!(
+                "Keeping the {}/{} FFT and hop sizes, the project's can't be used: {}",
+                self.fft_size, self.hop_size, e
+            )),
+        }
+        self.window = project.window;
+        self.padding = project.padding;
+        match project.input {
+            Some(input) if input.exists() => self.set_input_file(input),
+            Some(input) => self.log_event(format!("Project input not found: {}", input.display())),
+            None => {}
+        }
+        if project.output.is_some() {
+            self.output_file = project.output;
+        }
+        self.bookmarks = project.bookmarks;
+    }
 
-                // Audio file info
-                if let (Some(duration), Some(sample_rate), Some(channels)) =
-                    (self.duration, self.sample_rate, self.channels)
+    fn view_menu_ui(&mut self, ui: &mut egui::Ui) {
+        let previous = self.theme;
+        ui.checkbox(&mut self.theme.high_contrast, "High contrast");
+        ui.checkbox(&mut self.theme.reduced_motion, "Reduce motion");
+        egui::ComboBox::from_label("Colors")
+            .selected_text(self.theme.palette.label())
+            .show_ui(ui, |ui| {
+                for palette in Palette::ALL {
+                    ui.selectable_value(&mut self.theme.palette, palette, palette.label());
+                }
+            })
+            .response
+            .on_hover_text(
+                "Colors for the waveform, the pitch curves, and warnings. The color-blind \
+                 safe palettes stay distinct with red-green color blindness",
+            );
+
+        if self.theme != previous {
+            self.theme.apply(ui.ctx());
+        }
+        self.format_ui(ui);
+
+        ui.separator();
+        let storage_label = if self.config.portable {
+            "Portable mode"
+        } else {
+            "Settings"
+        };
+        ui.label(format!("{}: {}", storage_label, self.config.dir.display()));
+        self.open_with_ui(ui);
+
+        ui.separator();
+        self.scratch_ui(ui);
+
+        ui.separator();
+        ui.checkbox(&mut self.check_for_updates, "Check for updates at startup");
+        let checking = self.update_receiver.is_some();
+        if ui.add_enabled(!checking, egui::Button::new("Check for Updates Now")).clicked() {
+            self.start_update_check();
+        }
+        if let Some(status) = &self.update_status {
+            ui.label(status);
+        }
+
+        ui.separator();
+        if ui.button("Show Welcome Tour").clicked() {
+            self.onboarding_step = Some(OnboardingStep::Welcome);
+            ui.close_menu();
+        }
+    }
+
+    fn format_ui(&mut self, ui: &mut egui::Ui) {
+        let previous = self.format;
+        ui.horizontal(|ui| {
+            ui.label("Decimals:");
+            let system =
+                format!("System ({})", Format::new(FormatSettings::default()).number(1.5, 1));
+            ui.radio_value(&mut self.format.decimal, Separator::Locale, system);
+            ui.radio_value(&mut self.format.decimal, Separator::Point, "1.5");
+            ui.radio_value(&mut self.format.decimal, Separator::Comma, "1,5");
+        });
+        ui.horizontal(|ui| {
+            ui.label("File sizes:");
+            ui.radio_value(&mut self.format.size_units, SizeUnits::Binary, "MiB (1024)")
+                .on_hover_text("Steps of 1024 bytes, like Windows shows them");
+            ui.radio_value(&mut self.format.size_units, SizeUnits::Si, "MB (1000)")
+                .on_hover_text("Steps of 1000 bytes, like macOS shows them");
+        });
+
+        if self.format != previous {
+            format::set(Format::new(self.format));
+        }
+    }
+
+    fn scratch_ui(&mut self, ui: &mut egui::Ui) {
+        let previous = self.scratch.clone();
+        let dir = self.scratch.dir();
+        ui.label(format!("Scratch space: {}", dir.display()))
+            .on_hover_text("Where renders spool their audio and jobs stage their files");
+        ui.horizontal(|ui| {
+            if ui.button("Choose Folder...").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .set_title("Choose Scratch Folder")
+                    .set_directory(&dir)
+                    .pick_folder()
                 {
-                    ui.label(format!(
-                        "📊 Duration: {:.2}s | Sample Rate: {}Hz | Channels: {}",
-                        duration, sample_rate as i32, channels
-                    ));
+                    self.scratch.dir = Some(path);
                 }
-            });
+            }
+            if ui.add_enabled(self.scratch.dir.is_some(), egui::Button::new("Default")).clicked() {
+                self.scratch.dir = None;
+            }
+        });
+        ui.horizontal(|ui| {
+            let mut capped = self.scratch.cap_mib.is_some();
+            ui.checkbox(&mut capped, "Cap at")
+                .on_hover_text("Fail a render rather than let scratch files take up more");
+            let mut cap_mib = self.scratch.cap_mib.unwrap_or(4096);
+            ui.add_enabled(
+                capped,
+                egui::DragValue::new(&mut cap_mib).clamp_range(1..=1 << 20).suffix(" MiB"),
+            );
+            self.scratch.cap_mib = capped.then_some(cap_mib);
+        });
+        ui.horizontal(|ui| {
+            ui.label(format!("In use: {}", format::file_size(scratch::usage(&dir))));
+            if ui
+                .button("Clean Up Leftovers")
+                .on_hover_text("Remove what crashed runs left behind more than a day ago")
+                .clicked()
+            {
+                let freed = scratch::clean_stale(&dir);
+                self.processing_status =
+                    format!("Freed {} of scratch space", format::file_size(freed));
+            }
+        });
 
-            ui.separator();
+        if self.scratch != previous {
+            scratch::set(self.scratch.clone());
+        }
+    }
 
-            // Musical settings section
-            ui.group(|ui| {
-                ui.label("🎼 Musical Settings");
+    fn open_with_ui(&mut self, ui: &mut egui::Ui) {
+        let (label, hover) = if self.open_with_registered {
+            ("Remove \"Open with Autotune\"", "Take the app out of the file manager's Open With")
+        } else {
+            (
+                "Add \"Open with Autotune\"",
+                "List the app under Open With for audio files in the file manager, without \
+                 changing their default app",
+            )
+        };
+        let clicked = ui
+            .add_enabled(file_association::is_supported(), egui::Button::new(label))
+            .on_hover_text(hover)
+            .on_disabled_hover_text("On macOS, Open With comes from the app bundle")
+            .clicked();
+        if !clicked {
+            return;
+        }
 
-                ui.horizontal(|ui| {
-                    ui.label("Key:");
-                    egui::ComboBox::from_id_source("key_selector")
-                        .selected_text(ui::get_key_name(self.selected_key))
-                        .show_ui(ui, |ui| {
-                            for (i, name) in ui::KEY_NAMES.iter().enumerate() {
-                                ui.selectable_value(&mut self.selected_key, i, *name);
-                            }
-                        });
-                });
+        let result = if self.open_with_registered {
+            file_association::unregister()
+        } else {
+            std::env::current_exe()
+                .map_err(|e| format!("Can't locate the app: {}", e))
+                .and_then(|exe| file_association::register(&exe, self.config.portable))
+        };
+        match result {
+            Ok(message) => {
+                self.open_with_registered = !self.open_with_registered;
+                self.log_event(message);
+            }
+            Err(e) => self.log_event(format!("Couldn't change Open With: {}", e)),
+        }
+        ui.close_menu();
+    }
 
-                ui.horizontal(|ui| {
-                    ui.label("Note Mode:");
-                    egui::ComboBox::from_id_source("note_selector")
-                        .selected_text(if self.selected_note == 0 {
-                            "Auto"
-                        } else {
-                            "Manual"
-                        })
-                        .show_ui(ui, |ui| {
-                            ui.selectable_value(&mut self.selected_note, 0, "Auto (snap to key)");
-                            // Could add manual note selection here
-                        });
-                });
+    fn log_ui(&mut self, ui: &mut egui::Ui) {
+        if self.activity_log.is_empty() {
+            ui.label("No activity yet");
+            return;
+        }
 
-                ui.horizontal(|ui| {
-                    ui.label("Octave:");
-                    ui.add(egui::Slider::new(&mut self.octave, 0..=4).text("octave"));
-                });
+        egui::ScrollArea::vertical().stick_to_bottom(true).show(ui, |ui| {
+            for entry in &self.activity_log {
+                ui.monospace(entry);
+            }
+        });
+    }
 
-                ui.horizontal(|ui| {
-                    ui.label("Formant Shift:");
-                    ui.add(egui::Slider::new(&mut self.formant_shift, -12..=12).text("semitones"));
-                });
-            });
+    fn log_event(&mut self, message: impl Into<String>) {
+        let message = message.into();
+        log::info!("{}", message);
+        self.journal.record(message.clone());
 
-            ui.separator();
+        let elapsed = self.session_start.map(|start| start.elapsed().as_secs()).unwrap_or(0);
+        self.activity_log.push(format!("[{:02}:{:02}] {}", elapsed / 60, elapsed % 60, message));
+    }
 
-            // Autotune parameters section
-            ui.group(|ui| {
-                ui.label("⚙️ Autotune Parameters");
+    fn show_analysis(&self, ui: &mut egui::Ui) {
+        let Some(classification) = self.classification else {
+            ui.label("Load an input file to see its analysis");
+            return;
+        };
 
-                ui.horizontal(|ui| {
-                    ui.label("Pitch Correction:");
-                    ui.add(
-                        egui::Slider::new(&mut self.pitch_correction_strength, 0.0..=1.0)
-                            .text("strength"),
-                    );
-                });
+        let features = classification.features;
+        egui::Grid::new("analysis_grid").num_columns(2).striped(true).show(ui, |ui| {
+            ui.label("Content:");
+            ui.label(classification.class.label());
+            ui.end_row();
 
-                ui.horizontal(|ui| {
-                    ui.label("Transition Speed:");
-                    ui.add(egui::Slider::new(&mut self.transition_speed, 0.01..=1.0).text("speed"));
+            ui.label("Harmonicity:");
+            ui.add(egui::ProgressBar::new(features.harmonicity.clamp(0.0, 1.0)));
+            ui.end_row();
+
+            ui.label("Spectral centroid:");
+            ui.label(format::sample_rate(features.spectral_centroid));
+            ui.end_row();
+
+            ui.label("Silence:");
+            ui.label(format!("{:.0}%", features.silence_ratio * 100.0));
+            ui.end_row();
+
+            ui.label("Dynamic range:");
+            ui.label(format!("{:.1} dB", features.dynamic_range_db));
+            ui.end_row();
+
+            ui.label("Noise floor:");
+            ui.label(
+                features.noise_floor_db.map_or("no pauses to measure".to_string(), |floor| {
+                    format!("{:.0} dBFS", floor)
+                }),
+            );
+            ui.end_row();
+
+            ui.label("SNR:");
+            match features.snr_db {
+                Some(snr) if snr < classifier::LOW_SNR_DB => {
+                    ui.colored_label(ui.visuals().warn_fg_color, format!("{:.0} dB (low)", snr))
+                }
+                Some(snr) => ui.label(format!("{:.0} dB", snr)),
+                None => ui.label("-"),
+            };
+            ui.end_row();
+        });
+    }
+
+    fn show_update_window(&mut self, ctx: &egui::Context) {
+        let Some(release) = &self.available_update else {
+            return;
+        };
+
+        let mut open = true;
+        let mut download = false;
+        egui::Window::new("⬆ Update Available").open(&mut open).collapsible(false).show(
+            ctx,
+            |ui| {
+                ui.label(format!(
+                    "{} is available (you have v{}).",
+                    release.title(),
+                    updater::CURRENT_VERSION
+                ));
+                ui.hyperlink_to("View release page", &release.html_url);
+
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(250.0).show(ui, |ui| {
+                    ui.label(release.changelog());
                 });
-            });
+                ui.separator();
+
+                match release.asset_for_platform() {
+                    Some(asset) => {
+                        let size = format!("{:.1} MB", asset.size as f64 / 1_000_000.0);
+                        let idle = self.update_receiver.is_none();
+                        if ui
+                            .add_enabled(idle, egui::Button::new(format!("Download ({})", size)))
+                            .clicked()
+                        {
+                            download = true;
+                        }
+                    }
+                    None => {
+                        ui.label("No download for this platform, use the release page instead.");
+                    }
+                }
+
+                if let Some(status) = &self.update_status {
+                    ui.label(status);
+                }
+            },
+        );
 
+        if download {
+            self.start_update_download();
+        }
+        if !open {
+            self.available_update = None;
+        }
+    }
+
+    // The analysis with the pitch view, which leaves the processing tab while popped out
+    fn detached_analysis_ui(&mut self, ui: &mut egui::Ui) {
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            self.show_analysis(ui);
             ui.separator();
+            self.pitch_ui(ui);
+        });
+    }
 
-            // Processing section
-            ui.group(|ui| {
-                ui.label("🔄 Processing");
+    fn show_detached_analysis(&mut self, ctx: &egui::Context) {
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("analysis_viewport"),
+            egui::ViewportBuilder::default()
+                .with_title("Autotune Analysis")
+                .with_inner_size([700.0, 600.0]),
+            |ctx, class| {
+                if class == egui::ViewportClass::Embedded {
+                    // Backend can't open extra OS windows, fall back to a floating window
+                    egui::Window::new("📈 Analysis").show(ctx, |ui| self.detached_analysis_ui(ui));
+                } else {
+                    egui::CentralPanel::default().show(ctx, |ui| self.detached_analysis_ui(ui));
+                }
 
-                ui.horizontal(|ui| {
-                    let process_button = ui.add_enabled(
-                        !self.is_processing
-                            && self.input_file.is_some()
-                            && self.output_file.is_some(),
-                        egui::Button::new("🚀 Start Processing"),
-                    );
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.analysis_detached = false;
+                }
+            },
+        );
+    }
+}
 
-                    if process_button.clicked() {
-                        self.start_processing();
-                    }
+// Buttons to use the identical earlier `render` or render again, and which was clicked
+fn duplicate_ui(
+    ui: &mut egui::Ui,
+    render: &std::path::Path,
+    fingerprint: Fingerprint,
+) -> Option<Option<(PathBuf, Fingerprint)>> {
+    let mut answer = None;
+    ui.horizontal(|ui| {
+        if ui.button("Use It").on_hover_text("Copy the earlier render to the output").clicked() {
+            answer = Some(Some((render.to_path_buf(), fingerprint)));
+        }
+        if ui.button("Render Again").clicked() {
+            answer = Some(None);
+        }
+    });
+    answer
+}
 
-                    if self.is_processing {
-                        ui.spinner();
-                    }
+// Pick a device out of `names`, the system's default when None
+fn device_combo(ui: &mut egui::Ui, id: &str, choice: &mut Option<String>, names: &[String]) {
+    let selected = match choice.as_deref() {
+        Some(name) if names.iter().any(|found| found == name) => name.to_string(),
+        Some(name) => format!("{} (not connected)", name),
+        None => "System default".to_string(),
+    };
+    egui::ComboBox::from_id_source(id).selected_text(selected).show_ui(ui, |ui| {
+        ui.selectable_value(choice, None, "System default");
+        for name in names {
+            ui.selectable_value(choice, Some(name.clone()), name.as_str());
+        }
+    });
+}
+
+// Toggles whether changing `param` on one timeline clip changes it on every clip
+fn link_button(ui: &mut egui::Ui, linked: &mut ParamSet, param: Param) {
+    let on = linked.contains(param);
+    if ui
+        .selectable_label(on, "🔗")
+        .on_hover_text("Linked settings are shared by every clip of the timeline")
+        .clicked()
+    {
+        linked.toggle(param);
+    }
+}
+
+impl eframe::App for AutotuneApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.update_processing_status();
+        self.update_download_status();
+        self.update_update_status();
+        self.update_playback_status();
+        self.update_help_status();
+        self.update_waveform_status();
+        self.update_key_detection();
+        self.update_measurements();
+        self.update_classification();
+        self.update_timeline_status();
+        self.update_project_status();
+        self.update_handoffs(ctx);
+
+        // Ctrl+V outside of text fields pastes audio or an audio file path as the input. Only
+        // text comes as a paste event, a clipboard holding just audio gives the key alone
+        if !self.is_processing && !ctx.wants_keyboard_input() {
+            let (pasted, ctrl_v) = ctx.input_mut(|i| {
+                let text = i.events.iter().find_map(|event| match event {
+                    egui::Event::Paste(text) => Some(text.clone()),
+                    _ => None,
                 });
+                (text, i.consume_key(egui::Modifiers::COMMAND, egui::Key::V))
+            });
+            match pasted {
+                Some(text) => self.handle_pasted_text(&text),
+                None if ctrl_v => self.paste_audio(),
+                None => {}
+            }
+        }
+        // M drops a bookmark, like the marker key of most DAWs, unless a field is being typed in
+        if self.input_waveform.is_some()
+            && !ctx.wants_keyboard_input()
+            && ctx.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::M))
+        {
+            self.add_bookmark();
+        }
 
-                // Progress bar
-                if self.is_processing {
-                    ui.add(
-                        egui::ProgressBar::new(self.processing_progress)
-                            .text(format!("{:.1}%", self.processing_progress * 100.0)),
-                    );
-                }
+        // Request repaint if processing
+        if let Some(session) = &self.live_session {
+            session.settings.set(&self.musical_settings());
+        }
+        self.check_live_stability();
+        // Paths are journaled as they're picked, along with why, and bookmarks aren't settings
+        self.journal
+            .record_settings(&self.project_file(), &["version", "input", "output", "bookmarks"]);
+
+        // Playback of a finished render stops once everything rendered has been heard
+        if !self.is_processing && self.monitor.as_ref().is_some_and(|m| m.queued_secs() == 0.0) {
+            self.monitor = None;
+        }
+        if self.test_tone.as_ref().is_some_and(|tone| tone.queued_secs() == 0.0) {
+            self.test_tone = None;
+        }
 
-                // Status
-                ui.label(format!("Status: {}", self.processing_status));
+        let playing = self.player.as_ref().is_some_and(Player::is_playing)
+            || self.help_player.as_ref().is_some_and(Player::is_playing);
+        if self.is_processing
+            || self.download_progress.is_some()
+            || self.monitor.is_some()
+            || self.timeline_receiver.is_some()
+            || self.daw_receiver.is_some()
+            || self.input_waveform_receiver.is_some()
+            || self.output_waveform_receiver.is_some()
+            || self.key_receiver.is_some()
+            || self.help_receiver.is_some()
+            || self.live_session.is_some()
+            || self.recorder.is_some()
+            || self.test_tone.is_some()
+            || playing
+        {
+            self.theme.request_progress_repaint(ctx);
+        }
 
-                // Results
-                if let Some(ref result) = self.processing_result {
-                    match result {
-                        ProcessingResult::Success { samples_processed, duration_ms } => {
-                            ui.label(format!(
-                                "✅ Success: Processed {} samples in {:.2}s",
-                                samples_processed,
-                                duration_ms / 1000.0
-                            ));
+        if self.compact {
+            egui::CentralPanel::default().show(ctx, |ui| self.compact_ui(ui));
+        } else {
+            egui::TopBottomPanel::top("title_panel").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.heading("🎵 Desktop Autotune Application");
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("Reset Layout").clicked() {
+                            self.dock_state = Some(layout::default_dock_state());
                         }
-                        ProcessingResult::Error(err) => {
-                            ui.colored_label(egui::Color32::RED, format!("❌ Error: {}", err));
+                        if ui
+                            .button("🗕 Compact")
+                            .on_hover_text(
+                                "A small window with just the file, preset, key, and strength",
+                            )
+                            .clicked()
+                        {
+                            self.set_compact(ctx, true);
                         }
-                    }
-                }
+                        ui.menu_button("👁 View", |ui| self.view_menu_ui(ui));
+                        ui.menu_button("📄 File", |ui| self.file_menu_ui(ui));
+                    });
+                });
             });
 
-            ui.separator();
+            // Take the dock state out so the app itself can act as the tab viewer
+            let mut dock_state = self.dock_state.take().unwrap_or_else(layout::default_dock_state);
+            egui_dock::DockArea::new(&mut dock_state)
+                .style(egui_dock::Style::from_egui(ctx.style().as_ref()))
+                .show(ctx, self);
+            self.dock_state = Some(dock_state);
+        }
 
-            // Info section
-            ui.collapsing("ℹ️ About", |ui| {
-                ui.label("Desktop Autotune Application");
-                ui.label("Built with the synthphone_vocals real-time autotune library");
-                ui.label("Supports WAV files with various sample rates");
-                ui.label("Uses FFT-based pitch detection and correction");
-            });
-        });
+        if self.analysis_detached {
+            self.show_detached_analysis(ctx);
+        }
+
+        self.show_onboarding(ctx);
+        self.show_update_window(ctx);
+    }
+
+    // eframe calls this periodically and on shutdown; settings go to our own config
+    // location rather than eframe's storage so portable mode can keep them local
+    fn save(&mut self, _storage: &mut dyn eframe::Storage) {
+        let settings = GuiSettings {
+            dock_state: self.dock_state.clone(),
+            compact: self.compact,
+            theme: self.theme,
+            format: self.format,
+            scratch: self.scratch.clone(),
+            devices: self.devices.clone(),
+            onboarding_done: self.onboarding_done,
+            check_for_updates: self.check_for_updates,
+            monitor_while_processing: self.monitor_while_processing,
+            live_buffer_size: Some(self.live_buffer_size),
+            fixed_live_buffer_size: self.fixed_live_buffer_size,
+            tune_after_recording: Some(self.tune_after_recording),
+            a4: Some(self.a4),
+            fft_size: Some(self.fft_size),
+            hop_size: Some(self.hop_size),
+            window: self.window,
+            padding: self.padding,
+            native_sample_rate: self.native_sample_rate,
+            output_rate: self.output_rate,
+            output_depth: self.output_depth,
+            output_channels: self.output_channels,
+            normalization: self.normalization,
+            limit_db: self.limiter.then_some(self.limit_db),
+            pitch_algorithm: self.pitch_algorithm,
+            f0_range: self.f0_range,
+            correct_fry: self.correct_fry,
+            confidence_strength: self.confidence_strength,
+            unvoiced_sensitivity: Some(self.unvoiced_sensitivity),
+            export_stems: self.export_stems,
+            float_master: self.float_master,
+            waveform_view: self.waveform_view,
+            auto_select_key: self.auto_select_key,
+            locked: self.locked.clone(),
+        };
+
+        if let Err(e) = self.config.save(GUI_SETTINGS_FILE, &settings) {
+            log::warn!("Failed to save settings to {}: {}", self.config.dir.display(), e);
+        }
     }
 }
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
 
+    // Profiling without a custom build: AUTOTUNE_TRACE_FILE=trace.json autotune-gui
+    let _trace = std::env::var_os("AUTOTUNE_TRACE_FILE").and_then(|path| {
+        profiling::start_trace(std::path::Path::new(&path))
+            .map_err(|e| log::warn!("Trace recording disabled: {}", e))
+            .ok()
+    });
+
+    // Larger read/write blocks for slow storage, like the CLI's --io-block-size
+    if let Some(kib) = std::env::var("AUTOTUNE_IO_BLOCK_KIB").ok().and_then(|v| v.parse::<usize>().ok()) {
+        paths::set_io_block_size(kib * 1024);
+    }
+
+    let portable = std::env::args().any(|arg| arg == app_config::PORTABLE_FLAG);
+    let config = ConfigLocation::locate(portable);
+    // A file to open, as "Open with" passes it; everything else on the command line is a flag
+    let file = std::env::args_os()
+        .skip(1)
+        .find(|arg| !arg.to_string_lossy().starts_with("--"))
+        .map(PathBuf::from);
+    // A second launch hands its file to the window already open instead of opening another
+    if single_instance::hand_off(&config, file.as_deref()) {
+        return Ok(());
+    }
+
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([800.0, 600.0])
-            .with_min_inner_size([600.0, 400.0]),
+            .with_inner_size(layout::FULL_SIZE)
+            .with_min_inner_size(layout::FULL_MIN_SIZE),
         ..Default::default()
     };
 
     eframe::run_native(
         "Desktop Autotune Application",
         options,
-        Box::new(|cc| Box::new(AutotuneApp::new(cc))),
+        Box::new(move |cc| Box::new(AutotuneApp::new(cc, config, file))),
     )
 }
@@ -0,0 +1,349 @@
+use std::fs;
+use std::path::Path;
+
+use crate::paths;
+
+pub const EXTENSIONS: [&str; 2] = ["mid", "midi"];
+// Tempo until a file sets one: 120 BPM
+const DEFAULT_TEMPO_US: u32 = 500_000;
+// Percussion channel in General MIDI (channel 10), which has no melody to follow
+const DRUM_CHANNEL: u8 = 9;
+
+/// One note of a melody, as a MIDI note number (60 = middle C).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MelodyNote {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub note: u8,
+}
+
+/// The notes of a MIDI file as one line, to correct each moment of a take to the note
+/// playing at that time. Where notes overlap, as in chords, the highest one is the melody.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Melody {
+    // In time order, not overlapping
+    notes: Vec<MelodyNote>,
+}
+
+impl Melody {
+    pub fn read_file(path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(paths::normalize_for_io(path))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let melody = Self::parse(&bytes)
+            .map_err(|e| format!("{} isn't a MIDI file: {}", path.display(), e))?;
+        if melody.notes.is_empty() {
+            return Err(format!("{} has no notes", path.display()));
+        }
+        Ok(melody)
+    }
+
+    /// Read a Standard MIDI File, of any format and with any number of tracks, following
+    /// its tempo changes. Drums are left out.
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let (header, mut header_data) = reader.chunk()?;
+        if header != *b"MThd" {
+            return Err("no MThd header".to_string());
+        }
+        let _format = header_data.u16()?;
+        let tracks = header_data.u16()?;
+        let division = header_data.u16()?;
+
+        let mut events = Vec::new();
+        for _ in 0..tracks {
+            let (kind, track) = reader.chunk()?;
+            // Other chunk types are allowed and skipped
+            if kind == *b"MTrk" {
+                read_track(track, &mut events)?;
+            }
+        }
+        // Stable, so an off and an on at the same tick stay in the order they were written
+        events.sort_by_key(|event| event.tick);
+
+        let to_secs = TickClock::new(division, &events)?;
+        let mut sounding = [0u16; 128];
+        let mut notes: Vec<MelodyNote> = Vec::new();
+        let mut index = 0;
+        while index < events.len() {
+            // Everything at one tick changes at once
+            let tick = events[index].tick;
+            while index < events.len() && events[index].tick == tick {
+                match events[index].kind {
+                    EventKind::NoteOn(note) => sounding[note as usize] += 1,
+                    EventKind::NoteOff(note) => {
+                        let count = &mut sounding[note as usize];
+                        *count = count.saturating_sub(1);
+                    }
+                    EventKind::Tempo(_) => {}
+                }
+                index += 1;
+            }
+
+            let secs = to_secs.secs(tick);
+            let top = (0..128u8).rev().find(|&note| sounding[note as usize] > 0);
+            if let Some(last) = notes.last_mut().filter(|last| last.end_secs > secs) {
+                if Some(last.note) == top {
+                    continue;
+                }
+                last.end_secs = secs;
+            }
+            if let Some(note) = top {
+                notes.push(MelodyNote { start_secs: secs, end_secs: f32::INFINITY, note });
+            }
+        }
+        // Notes still sounding at the end of the file stop with it
+        let end = events.last().map_or(0.0, |event| to_secs.secs(event.tick));
+        if let Some(last) = notes.last_mut() {
+            last.end_secs = last.end_secs.min(end);
+        }
+        notes.retain(|note| note.end_secs > note.start_secs);
+        Ok(Self { notes })
+    }
+
+    pub fn notes(&self) -> &[MelodyNote] {
+        &self.notes
+    }
+
+    /// The note playing at `secs`, None in rests.
+    pub fn note_at(&self, secs: f32) -> Option<u8> {
+        let index = self.notes.partition_point(|note| note.start_secs <= secs);
+        let note = self.notes.get(index.checked_sub(1)?)?;
+        (secs < note.end_secs).then_some(note.note)
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.notes.last().map_or(0.0, |note| note.end_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Event {
+    tick: u64,
+    kind: EventKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum EventKind {
+    NoteOn(u8),
+    NoteOff(u8),
+    // Microseconds per quarter note from here on
+    Tempo(u32),
+}
+
+// Collect the notes and tempo changes of one track, at ticks from the start of the file
+fn read_track(mut track: Reader, events: &mut Vec<Event>) -> Result<(), String> {
+    let mut tick = 0u64;
+    let mut running_status = None;
+    while !track.is_empty() {
+        tick += track.varint()? as u64;
+        let mut status = track.u8()?;
+        let first_data = if status < 0x80 {
+            // Running status: the byte read is the first data byte of a repeated message
+            let data = status;
+            status = running_status.ok_or("data byte without a status")?;
+            Some(data)
+        } else {
+            None
+        };
+
+        match status {
+            0xFF => {
+                let kind = track.u8()?;
+                let len = track.varint()? as usize;
+                let data = track.take(len)?;
+                match kind {
+                    0x2F => break,
+                    0x51 if len == 3 => {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        events.push(Event { tick, kind: EventKind::Tempo(tempo) });
+                    }
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                let len = track.varint()? as usize;
+                track.take(len)?;
+            }
+            0x80..=0xEF => {
+                running_status = Some(status);
+                let data_len = match status & 0xF0 {
+                    0xC0 | 0xD0 => 1,
+                    _ => 2,
+                };
+                let mut data = [0u8; 2];
+                for (i, byte) in data.iter_mut().take(data_len).enumerate() {
+                    *byte = match (i, first_data) {
+                        (0, Some(first)) => first,
+                        _ => track.u8()?,
+                    };
+                }
+                let [note, velocity] = data;
+                if status & 0x0F == DRUM_CHANNEL || note >= 128 {
+                    continue;
+                }
+                // A note on with no velocity is how most files end a note
+                let kind = match status & 0xF0 {
+                    0x90 if velocity > 0 => EventKind::NoteOn(note),
+                    0x80 | 0x90 => EventKind::NoteOff(note),
+                    _ => continue,
+                };
+                events.push(Event { tick, kind });
+            }
+            _ => return Err(format!("unknown status byte {:#04x}", status)),
+        }
+    }
+    Ok(())
+}
+
+// Converts ticks to seconds through the tempo changes of a file
+struct TickClock {
+    // Tick, seconds at that tick, and seconds per tick from there on
+    segments: Vec<(u64, f64, f64)>,
+}
+
+impl TickClock {
+    fn new(division: u16, events: &[Event]) -> Result<Self, String> {
+        if division & 0x8000 != 0 {
+            // SMPTE: frames per second (as a negative byte) and ticks per frame, no tempo.
+            // Widened first, as a malformed 0x80 would overflow negated as a byte
+            let fps = match -(((division >> 8) as u8 as i8) as i32) {
+                24 => 24.0,
+                25 => 25.0,
+                // Drop-frame 30, which runs at 29.97
+                29 => 30_000.0 / 1001.0,
+                30 => 30.0,
+                other => return Err(format!("unknown SMPTE frame rate {}", other)),
+            };
+            let ticks_per_frame = (division & 0xFF).max(1) as f64;
+            return Ok(Self { segments: vec![(0, 0.0, 1.0 / (fps * ticks_per_frame))] });
+        }
+        let ticks_per_quarter = division.max(1) as f64;
+        let per_tick = |tempo: u32| tempo as f64 / 1_000_000.0 / ticks_per_quarter;
+        let mut segments = vec![(0, 0.0, per_tick(DEFAULT_TEMPO_US))];
+        for event in events {
+            if let EventKind::Tempo(tempo) = event.kind {
+                let &(tick, secs, rate) = segments.last().unwrap();
+                // The events come sorted by tick, this only keeps a bad caller from panicking
+                let at = secs + event.tick.saturating_sub(tick) as f64 * rate;
+                segments.push((event.tick.max(tick), at, per_tick(tempo)));
+            }
+        }
+        Ok(Self { segments })
+    }
+
+    fn secs(&self, tick: u64) -> f32 {
+        let index = self.segments.partition_point(|&(start, _, _)| start <= tick);
+        let (start, secs, rate) = self.segments[index.saturating_sub(1)];
+        (secs + (tick - start) as f64 * rate) as f32
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.bytes.len());
+        let data = &self.bytes[self.pos..end.ok_or("ends early")?];
+        self.pos += len;
+        Ok(data)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let data = self.take(2)?;
+        Ok(u16::from_be_bytes([data[0], data[1]]))
+    }
+
+    // A chunk's four-byte type and its contents
+    fn chunk(&mut self) -> Result<([u8; 4], Reader<'a>), String> {
+        let kind = self.take(4)?;
+        let len = self.take(4)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let data = self.take(len)?;
+        Ok(([kind[0], kind[1], kind[2], kind[3]], Reader { bytes: data, pos: 0 }))
+    }
+
+    // Variable-length quantity: 7 bits per byte, high bit set on all but the last
+    fn varint(&mut self) -> Result<u32, String> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("variable-length number too long".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(kind: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend((data.len() as u32).to_be_bytes());
+        chunk.extend(data);
+        chunk
+    }
+
+    #[test]
+    fn test_melody_follows_the_top_note() {
+        // 96 ticks per quarter note. Tempo track at 120 BPM, then 60 BPM from beat 2
+        let mut file = chunk(b"MThd", &[0, 1, 0, 2, 0, 96]);
+        #[rustfmt::skip]
+        let tempo = [
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20,
+            0x60, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        file.extend(chunk(b"MTrk", &tempo));
+        #[rustfmt::skip]
+        let notes = [
+            // C4 for a beat, then an E4 under a G4 with running status and velocity 0 offs
+            0x00, 0x90, 60, 100,
+            0x60, 0x80, 60, 0,
+            0x00, 0x90, 64, 100,
+            0x00, 67, 100,
+            0x60, 67, 0,
+            0x60, 64, 0,
+            // Drums don't count
+            0x00, 0x99, 36, 100,
+            0x60, 0x89, 36, 0,
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        file.extend(chunk(b"MTrk", &notes));
+
+        let melody = Melody::parse(&file).unwrap();
+        assert_eq!(melody.note_at(0.25), Some(60));
+        // The second beat starts at 0.5s and lasts a second at the slower tempo
+        assert_eq!(melody.note_at(0.75), Some(67));
+        assert_eq!(melody.note_at(1.75), Some(64));
+        assert_eq!(melody.note_at(2.75), None);
+        assert_eq!(melody.notes().len(), 3);
+        assert!((melody.duration_secs() - 2.5).abs() < 1e-4);
+
+        assert!(Melody::parse(b"RIFF").is_err());
+        assert!(Melody::parse(&file[..file.len() - 3]).is_err());
+
+        // SMPTE timing at 25 fps and 40 ticks a frame, a millisecond each
+        let mut smpte = chunk(b"MThd", &[0, 0, 0, 1, -25i8 as u8, 40]);
+        smpte.extend(chunk(b"MTrk", &[0x00, 0x90, 60, 100, 0x83, 0x60, 0x80, 60, 0]));
+        let melody = Melody::parse(&smpte).unwrap();
+        assert!((melody.duration_secs() - 0.48).abs() < 1e-4);
+        // A frame rate byte of 0x80 is no frame rate at all
+        smpte[12] = 0x80;
+        assert!(Melody::parse(&smpte).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{FromSample, SampleFormat, SizedSample};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::devices;
+
+/// Plays audio on the chosen output device as it is rendered, so a render can be judged
+/// (and stopped) long before it finishes. Lives on the UI thread; the render thread pushes
+/// audio through a [`MonitorFeed`].
+pub struct Monitor {
+    _stream: cpal::Stream,
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    source_rate: u32,
+    device_rate: u32,
+    // Device buffers the queue ran dry in, after playback started
+    underruns: Arc<AtomicUsize>,
+    // Device samples dropped to keep a live feed's latency bounded
+    dropped: Arc<AtomicUsize>,
+}
+
+impl Monitor {
+    /// Open the chosen output device for mono audio at `source_rate`, optionally with a
+    /// fixed device buffer size in frames.
+    pub fn start(source_rate: u32, buffer_size: Option<u32>) -> Result<Self, String> {
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let underruns = Arc::new(AtomicUsize::new(0));
+        let playback_queue = queue.clone();
+        let playback_underruns = underruns.clone();
+        let mut started = false;
+        let (stream, device_rate) = open_output(buffer_size, move |buffer| {
+            let mut queue = playback_queue.lock().ok();
+            let mut ran_dry = false;
+            for sample in buffer {
+                match queue.as_mut().and_then(|queue| queue.pop_front()) {
+                    Some(value) => {
+                        *sample = value;
+                        started = true;
+                    }
+                    None => {
+                        *sample = 0.0;
+                        ran_dry = true;
+                    }
+                }
+            }
+            if started && ran_dry {
+                playback_underruns.fetch_add(1, Ordering::Relaxed);
+            }
+        })?;
+
+        Ok(Self {
+            _stream: stream,
+            queue,
+            source_rate,
+            device_rate,
+            underruns,
+            dropped: Arc::default(),
+        })
+    }
+
+    /// Handle for the render thread to push finished audio into.
+    pub fn feed(&self) -> MonitorFeed {
+        MonitorFeed {
+            queue: self.queue.clone(),
+            step: self.source_rate as f64 / self.device_rate as f64,
+            position: 0.0,
+            previous: 0.0,
+            max_queued: usize::MAX,
+            dropped: self.dropped.clone(),
+        }
+    }
+
+    /// Like [`feed`](Self::feed), but drops the oldest queued audio beyond `max_latency`
+    /// seconds, so playback can't fall behind a live source when device clocks drift apart.
+    pub fn live_feed(&self, max_latency: f32) -> MonitorFeed {
+        MonitorFeed { max_queued: (max_latency * self.device_rate as f32) as usize, ..self.feed() }
+    }
+
+    /// Seconds of rendered audio still waiting to be played.
+    pub fn queued_secs(&self) -> f32 {
+        let queued = self.queue.lock().map_or(0, |queue| queue.len());
+        queued as f32 / self.device_rate as f32
+    }
+
+    /// Device buffers that ran out of audio partway since playback started, so part of
+    /// them played as silence.
+    pub fn underruns(&self) -> usize {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    /// Seconds of audio a live feed dropped because it arrived faster than it was played.
+    pub fn dropped_secs(&self) -> f32 {
+        self.dropped.load(Ordering::Relaxed) as f32 / self.device_rate as f32
+    }
+}
+
+/// Sending side of a [`Monitor`], resampling to the device rate as audio is pushed.
+pub struct MonitorFeed {
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    // Source samples per device sample
+    step: f64,
+    // Read position within the next pushed block, relative to `previous`
+    position: f64,
+    previous: f32,
+    max_queued: usize,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl MonitorFeed {
+    pub fn push(&mut self, samples: &[f32]) {
+        let Ok(mut queue) = self.queue.lock() else {
+            return;
+        };
+
+        // Linear interpolation between `previous` (index -1) and the new samples
+        let sample_at = |index: isize| {
+            if index < 0 {
+                self.previous
+            } else {
+                samples[index as usize]
+            }
+        };
+        while self.position < samples.len() as f64 - 1.0 {
+            let index = self.position.floor() as isize;
+            let fraction = (self.position - index as f64) as f32;
+            let value = sample_at(index) + (sample_at(index + 1) - sample_at(index)) * fraction;
+            // Monitored audio is not normalized yet, so keep it from clipping harshly
+            queue.push_back(value.clamp(-1.0, 1.0));
+            self.position += self.step;
+        }
+
+        if let Some(&last) = samples.last() {
+            self.position -= samples.len() as f64;
+            self.previous = last;
+        }
+
+        let excess = queue.len().saturating_sub(self.max_queued);
+        if excess > 0 {
+            queue.drain(..excess);
+            self.dropped.fetch_add(excess, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Start a stream on the chosen output device that asks `render` for mono audio and plays
+/// it on every channel. Returns the stream (playing until dropped) and the device rate.
+pub fn open_output(
+    buffer_size: Option<u32>,
+    render: impl FnMut(&mut [f32]) + Send + 'static,
+) -> Result<(cpal::Stream, u32), String> {
+    let device = devices::output_device()?;
+    let supported = devices::output_config(&device)?;
+    let sample_format = supported.sample_format();
+    let mut config: cpal::StreamConfig = supported.into();
+    if let Some(frames) = buffer_size {
+        config.buffer_size = cpal::BufferSize::Fixed(frames);
+    }
+
+    let stream = match sample_format {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, render),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, render),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, render),
+        other => Err(format!("Unsupported output sample format: {}", other)),
+    }?;
+    stream.play().map_err(|e| format!("Failed to start playback: {}", e))?;
+
+    Ok((stream, config.sample_rate.0))
+}
+
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    mut render: impl FnMut(&mut [f32]) + Send + 'static,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels.max(1) as usize;
+    // Grows to the largest callback size once, then is reused
+    let mut mono = Vec::new();
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                mono.resize(data.len() / channels, 0.0);
+                render(&mut mono);
+                for (frame, &sample) in data.chunks_mut(channels).zip(&mono) {
+                    frame.fill(T::from_sample(sample));
+                }
+            },
+            |e| log::warn!("Audio output error: {}", e),
+            None,
+        )
+        .map_err(|e| format!("Failed to open output stream: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed(step: f64) -> MonitorFeed {
+        MonitorFeed {
+            queue: Arc::default(),
+            step,
+            position: 0.0,
+            previous: 0.0,
+            max_queued: usize::MAX,
+            dropped: Arc::default(),
+        }
+    }
+
+    #[test]
+    fn test_feed_resamples_across_blocks() {
+        // Half the source rate: every other sample, continuous across push calls
+        let mut feed = feed(2.0);
+        let signal: Vec<f32> = (0..20).map(|i| i as f32 * 0.01).collect();
+        feed.push(&signal[..7]);
+        feed.push(&signal[7..]);
+
+        let queued: Vec<f32> = feed.queue.lock().unwrap().iter().copied().collect();
+        assert_eq!(queued.len(), 10);
+        for (i, sample) in queued.iter().enumerate() {
+            assert!((sample - i as f32 * 0.02).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_feed_drops_oldest_beyond_max_queued() {
+        let mut feed = MonitorFeed { max_queued: 4, ..feed(1.0) };
+        feed.push(&[0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7]);
+
+        let queued: Vec<f32> = feed.queue.lock().unwrap().iter().copied().collect();
+        // The last sample waits for the next block to interpolate against
+        assert_eq!(queued, vec![0.3, 0.4, 0.5, 0.6]);
+        assert_eq!(feed.dropped.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,141 @@
+use eframe::egui;
+use std::path::PathBuf;
+
+use crate::audio_processor::ProcessingResult;
+use crate::{AutotuneApp, demo_audio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnboardingStep {
+    Welcome,
+    Process,
+    Compare,
+}
+
+fn demo_dir() -> PathBuf {
+    std::env::temp_dir().join("autotune-demo")
+}
+
+impl AutotuneApp {
+    pub(crate) fn show_onboarding(&mut self, ctx: &egui::Context) {
+        let Some(step) = self.onboarding_step else {
+            return;
+        };
+
+        egui::Window::new("👋 Welcome")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| match step {
+                OnboardingStep::Welcome => self.onboarding_welcome_ui(ui),
+                OnboardingStep::Process => self.onboarding_process_ui(ui),
+                OnboardingStep::Compare => self.onboarding_compare_ui(ui),
+            });
+    }
+
+    fn onboarding_welcome_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("New here? Let's tune a short demo clip in three steps.");
+        ui.label("We'll generate an out-of-tune scale, correct it, and compare the two.");
+        ui.add_space(8.0);
+
+        ui.horizontal(|ui| {
+            if ui.button("🎼 Generate Demo").clicked() {
+                self.load_onboarding_demo();
+            }
+            if ui.button("Skip").clicked() {
+                self.finish_onboarding();
+            }
+        });
+    }
+
+    fn onboarding_process_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label(format!(
+            "Loaded a C major scale with every note {:.0} cents sharp or flat.",
+            demo_audio::OFF_PITCH_CENTS
+        ));
+        ui.label("Settings: C Major, full strength, fast transitions (hard tune).");
+        ui.add_space(8.0);
+
+        if self.is_processing {
+            ui.add(egui::ProgressBar::new(self.processing_progress).show_percentage());
+        } else if let Some(ProcessingResult::Success { .. }) = self.processing_result {
+            self.onboarding_step = Some(OnboardingStep::Compare);
+        } else {
+            if let Some(ProcessingResult::Error(ref err)) = self.processing_result {
+                ui.colored_label(ui.visuals().error_fg_color, format!("❌ {}", err));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("🚀 Process Demo").clicked() {
+                    self.start_processing(true);
+                }
+                if ui.button("Skip").clicked() {
+                    self.finish_onboarding();
+                }
+            });
+        }
+    }
+
+    fn onboarding_compare_ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Done! Compare the files in your audio player:");
+
+        let files = [
+            ("Before", &self.input_file),
+            ("After", &self.output_file),
+            ("In-tune reference", &self.onboarding_reference),
+        ];
+        for (label, path) in files {
+            if let Some(path) = path {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}:", label));
+                    ui.monospace(path.display().to_string());
+                });
+            }
+        }
+
+        ui.label("Listen for each note snapping onto the C major scale.");
+        ui.label("Load your own file from the Files tab whenever you're ready.");
+        ui.add_space(8.0);
+
+        if ui.button("Finish").clicked() {
+            self.finish_onboarding();
+        }
+    }
+
+    fn load_onboarding_demo(&mut self) {
+        let dir = demo_dir();
+        let input_path = dir.join("off_pitch_demo.wav");
+        let reference_path = dir.join("in_tune_reference.wav");
+
+        let written = std::fs::create_dir_all(&dir).map_err(|e| e.to_string()).and_then(|_| {
+            demo_audio::write_off_pitch_demo(&input_path)
+                .and_then(|_| demo_audio::write_test_tones(&reference_path))
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = written {
+            self.processing_status = format!("Failed to generate demo: {}", e);
+            self.log_event(format!("Failed to generate demo: {}", e));
+            return;
+        }
+
+        self.input_file = Some(input_path.clone());
+        self.onboarding_reference = Some(reference_path);
+        self.output_file = Some(dir.join("off_pitch_demo_tuned.wav"));
+        self.processing_result = None;
+        self.load_audio_info(&input_path);
+
+        // Hard-tune settings make the correction obvious on the demo
+        self.selected_key = 0;
+        self.selected_note = 0;
+        self.octave = 2;
+        self.formant_shift = 0;
+        self.pitch_correction_strength = 1.0;
+        self.transition_speed = 0.05;
+
+        self.log_event(format!("Generated demo file: {}", input_path.display()));
+        self.onboarding_step = Some(OnboardingStep::Process);
+    }
+
+    fn finish_onboarding(&mut self) {
+        self.onboarding_step = None;
+        self.onboarding_done = true;
+    }
+}
@@ -0,0 +1,215 @@
+use hound::{WavReader, WavSpec, WavWriter};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// Default read/write block size. Large blocks keep the number of round trips down on
+/// network shares.
+pub const DEFAULT_IO_BLOCK_SIZE: usize = 1 << 20;
+const MIN_IO_BLOCK_SIZE: usize = 4 << 10;
+
+static IO_BLOCK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_IO_BLOCK_SIZE);
+
+const RETRY_ATTEMPTS: u32 = 4;
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+// Windows APIs reject longer paths unless they use the extended-length form
+const WINDOWS_MAX_PATH: usize = 260;
+
+/// Set the block size of all audio file reads and writes, independent of the DSP framing.
+/// It only affects throughput, never the audio.
+pub fn set_io_block_size(bytes: usize) {
+    IO_BLOCK_SIZE.store(bytes.max(MIN_IO_BLOCK_SIZE), Ordering::Relaxed);
+}
+
+pub fn io_block_size() -> usize {
+    IO_BLOCK_SIZE.load(Ordering::Relaxed)
+}
+
+/// Name to show for a path in the UI. Never panics, even for `..` or non-UTF8 names.
+pub fn display_name(path: &Path) -> String {
+    match path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.display().to_string(),
+    }
+}
+
+/// Adjust a path so the OS accepts it for IO (Windows long and UNC paths).
+pub fn normalize_for_io(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        if let Some(extended) = path.to_str().and_then(extended_length_form) {
+            return PathBuf::from(extended);
+        }
+    }
+    path.to_path_buf()
+}
+
+fn extended_length_form(path: &str) -> Option<String> {
+    if path.len() < WINDOWS_MAX_PATH || path.starts_with(r"\\?\") {
+        return None;
+    }
+
+    // The extended form doesn't normalize separators for us
+    let path = path.replace('/', r"\");
+    if let Some(unc) = path.strip_prefix(r"\\") {
+        Some(format!(r"\\?\UNC\{}", unc))
+    } else if path.as_bytes().get(1) == Some(&b':') {
+        Some(format!(r"\\?\{}", path))
+    } else {
+        // Relative paths can't use the extended form
+        None
+    }
+}
+
+/// Errors worth retrying on flaky network storage.
+pub fn is_transient_io_error(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+/// Run `op`, retrying with exponential backoff while it fails with a transient error.
+pub fn retry_io<T, E>(
+    mut op: impl FnMut() -> Result<T, E>,
+    is_transient: impl Fn(&E) -> bool,
+) -> Result<T, E> {
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e) if attempt < RETRY_ATTEMPTS && is_transient(&e) => {
+                thread::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt - 1));
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient_hound_error(err: &hound::Error) -> bool {
+    matches!(err, hound::Error::IoError(e) if is_transient_io_error(e))
+}
+
+/// Open a WAV file with retries, reading in blocks of [`io_block_size`].
+pub fn open_wav(path: &Path) -> Result<WavReader<BufReader<File>>, hound::Error> {
+    let path = normalize_for_io(path);
+    retry_io(
+        || {
+            let file = File::open(&path)?;
+            WavReader::new(BufReader::with_capacity(io_block_size(), file))
+        },
+        is_transient_hound_error,
+    )
+}
+
+/// Create a WAV file with retries, writing in blocks of [`io_block_size`].
+pub fn create_wav(path: &Path, spec: WavSpec) -> Result<WavWriter<BufWriter<File>>, hound::Error> {
+    let path = normalize_for_io(path);
+    retry_io(
+        || {
+            let file = File::create(&path)?;
+            WavWriter::new(BufWriter::with_capacity(io_block_size(), file), spec)
+        },
+        is_transient_hound_error,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_display_name() {
+        assert_eq!(display_name(Path::new("/music/take.wav")), "take.wav");
+        assert_eq!(display_name(Path::new("/music/..")), "/music/..");
+        assert_eq!(display_name(Path::new("/")), "/");
+    }
+
+    #[test]
+    fn test_extended_length_form() {
+        let long_dir = "d".repeat(WINDOWS_MAX_PATH);
+        assert_eq!(extended_length_form(r"C:\short.wav"), None);
+        assert_eq!(
+            extended_length_form(&format!(r"C:\{}\take.wav", long_dir)),
+            Some(format!(r"\\?\C:\{}\take.wav", long_dir))
+        );
+        assert_eq!(
+            extended_length_form(&format!(r"\\server\share/{}/take.wav", long_dir)),
+            Some(format!(r"\\?\UNC\server\share\{}\take.wav", long_dir))
+        );
+        assert_eq!(extended_length_form(&format!(r"{}\take.wav", long_dir)), None);
+    }
+
+    #[test]
+    fn test_retry_io_recovers_from_transient_errors() {
+        let calls = Cell::new(0);
+        let result = retry_io(
+            || {
+                calls.set(calls.get() + 1);
+                if calls.get() < 3 {
+                    Err(io::Error::from(io::ErrorKind::TimedOut))
+                } else {
+                    Ok(calls.get())
+                }
+            },
+            is_transient_io_error,
+        );
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_io_block_size_does_not_change_audio() {
+        let dir = std::env::temp_dir().join("autotune-io-block-test");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("take.wav");
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        let mut writer = create_wav(&path, spec).unwrap();
+        for i in 0..20_000 {
+            writer.write_sample((i % 3000) as i16).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let read = || {
+            let mut reader = open_wav(&path).unwrap();
+            reader.samples::<i16>().collect::<Result<Vec<_>, _>>().unwrap()
+        };
+        let default_blocks = read();
+        set_io_block_size(0);
+        assert_eq!(io_block_size(), MIN_IO_BLOCK_SIZE);
+        let small_blocks = read();
+        set_io_block_size(DEFAULT_IO_BLOCK_SIZE);
+
+        assert_eq!(default_blocks.len(), 20_000);
+        assert_eq!(small_blocks, default_blocks);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_retry_io_gives_up_on_permanent_errors() {
+        let calls = Cell::new(0);
+        let result: Result<(), io::Error> = retry_io(
+            || {
+                calls.set(calls.get() + 1);
+                Err(io::Error::from(io::ErrorKind::NotFound))
+            },
+            is_transient_io_error,
+        );
+        assert!(result.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+}
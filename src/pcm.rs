@@ -0,0 +1,348 @@
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{Read, Seek, Write};
+use std::str::FromStr;
+
+use crate::profiling::profile_span;
+
+/// Sample format an output can be written in instead of the input's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum BitDepth {
+    Int16,
+    Int24,
+    Int32,
+    Float32,
+}
+
+impl BitDepth {
+    pub const ALL: [BitDepth; 4] = [Self::Int16, Self::Int24, Self::Int32, Self::Float32];
+
+    /// `spec` with this sample format.
+    pub fn apply(self, spec: WavSpec) -> WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            Self::Int16 => (16, SampleFormat::Int),
+            Self::Int24 => (24, SampleFormat::Int),
+            Self::Int32 => (32, SampleFormat::Int),
+            Self::Float32 => (32, SampleFormat::Float),
+        };
+        WavSpec { bits_per_sample, sample_format, ..spec }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Int16 => "16-bit",
+            Self::Int24 => "24-bit",
+            Self::Int32 => "32-bit",
+            Self::Float32 => "32-bit float",
+        }
+    }
+}
+
+impl FromStr for BitDepth {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "16" => Ok(Self::Int16),
+            "24" => Ok(Self::Int24),
+            "32" => Ok(Self::Int32),
+            "float" | "32f" | "32float" => Ok(Self::Float32),
+            _ => Err(format!("Invalid bit depth '{}': expected 16, 24, 32, or float", text)),
+        }
+    }
+}
+
+impl TryFrom<String> for BitDepth {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        text.parse()
+    }
+}
+
+impl From<BitDepth> for String {
+    fn from(depth: BitDepth) -> Self {
+        depth.to_string()
+    }
+}
+
+impl fmt::Display for BitDepth {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Int16 => "16",
+            Self::Int24 => "24",
+            Self::Int32 => "32",
+            Self::Float32 => "float",
+        })
+    }
+}
+
+/// TPDF dither noise, the sum of two uniform values, up to one step of the output either
+/// way. Added before rounding float audio to 16 or 24-bit integers, it turns the distortion
+/// rounding puts on quiet fades and reverb tails into a steady, inaudible hiss.
+#[derive(Debug, Clone)]
+pub struct Dither {
+    // xorshift32, seeded the same for every file so renders stay reproducible
+    state: u32,
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Self { state: 0x9E37_79B9 }
+    }
+}
+
+impl Dither {
+    /// The next noise value, in output steps (-1.0 to 1.0).
+    pub fn noise(&mut self) -> f32 {
+        self.uniform() + self.uniform() - 1.0
+    }
+
+    fn uniform(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1 << 24) as f32
+    }
+}
+
+/// Multiplier from integer PCM of `bits_per_sample` to f32 in -1.0..1.0.
+pub fn input_scale(bits_per_sample: u16) -> Option<f32> {
+    match bits_per_sample {
+        16 => Some(1.0 / 32768.0),
+        24 => Some(1.0 / 8388608.0),
+        32 => Some(1.0 / 2147483648.0),
+        _ => None,
+    }
+}
+
+/// Multiplier from f32 back to integer PCM of `bits_per_sample`.
+pub fn output_scale(bits_per_sample: u16) -> Option<f32> {
+    match bits_per_sample {
+        16 => Some(32767.0),
+        24 => Some(8388607.0),
+        32 => Some(2147483647.0),
+        _ => None,
+    }
+}
+
+/// Read up to `max_frames` frames as mono f32 (channels averaged) straight into one
+/// preallocated buffer, without an intermediate interleaved copy.
+pub fn read_mono<R: Read>(
+    reader: &mut WavReader<R>,
+    max_frames: usize,
+) -> Result<Vec<f32>, hound::Error> {
+    let spec = reader.spec();
+    let frames = (reader.duration() as usize).min(max_frames);
+    let mut mono = Vec::with_capacity(frames);
+    read_mono_block(&mut reader.samples::<i32>(), spec, &mut mono, frames)?;
+    Ok(mono)
+}
+
+/// Append up to `max_frames` frames of interleaved integer `samples` in format `spec` to
+/// `mono` (channels averaged), returning how many were added. 0 means the end of the data.
+pub fn read_mono_block(
+    samples: &mut impl Iterator<Item = Result<i32, hound::Error>>,
+    spec: WavSpec,
+    mono: &mut Vec<f32>,
+    max_frames: usize,
+) -> Result<usize, hound::Error> {
+    let scale = input_scale(spec.bits_per_sample).ok_or(hound::Error::Unsupported)?;
+    let channels = spec.channels.max(1) as usize;
+    let start = mono.len();
+    let mut sum = 0.0f32;
+    let mut channel = 0;
+
+    for sample in samples.take(max_frames.saturating_mul(channels)) {
+        sum += sample? as f32 * scale;
+        channel += 1;
+        if channel == channels {
+            mono.push(sum / channels as f32);
+            sum = 0.0;
+            channel = 0;
+        }
+    }
+    Ok(mono.len() - start)
+}
+
+/// Write mono f32 samples to every channel of `writer`, as float or as integer PCM. With
+/// `dither`, 16 and 24-bit samples are dithered rather than just rounded; 32-bit integers
+/// hold more than a float's precision and never need it.
+pub fn write_mono<W: Write + Seek>(
+    writer: &mut WavWriter<W>,
+    samples: &[f32],
+    dither: Option<&mut Dither>,
+) -> Result<(), hound::Error> {
+    profile_span!("encode", samples = samples.len());
+    let copies = writer.spec().channels;
+    write_copies(writer, samples, copies, dither)
+}
+
+/// Write interleaved f32 samples, already as many channels as `writer` has, like
+/// [`write_mono`] writes mono ones.
+pub fn write_interleaved<W: Write + Seek>(
+    writer: &mut WavWriter<W>,
+    samples: &[f32],
+    dither: Option<&mut Dither>,
+) -> Result<(), hound::Error> {
+    profile_span!("encode", samples = samples.len());
+    write_copies(writer, samples, 1, dither)
+}
+
+// Write each sample `copies` times over
+fn write_copies<W: Write + Seek>(
+    writer: &mut WavWriter<W>,
+    samples: &[f32],
+    copies: u16,
+    mut dither: Option<&mut Dither>,
+) -> Result<(), hound::Error> {
+    let spec = writer.spec();
+    if spec.sample_format == SampleFormat::Float {
+        for &sample in samples {
+            for _ in 0..copies {
+                writer.write_sample(sample)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let scale = output_scale(spec.bits_per_sample).ok_or(hound::Error::Unsupported)?;
+    let noise = |dither: &mut Option<&mut Dither>| match dither {
+        Some(dither) if spec.bits_per_sample < 32 => dither.noise(),
+        _ => 0.0,
+    };
+    for &sample in samples {
+        let value = (sample * scale + noise(&mut dither)).round().clamp(-scale - 1.0, scale);
+        for _ in 0..copies {
+            writer.write_sample(value as i32)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the audio with `write` (e.g. [`write_mono`]) and finalize the file. If writing
+/// fails partway, the header is still finalized so whatever was written remains a valid,
+/// shorter WAV.
+pub fn write_mono_file<W: Write + Seek>(
+    mut writer: WavWriter<W>,
+    write: impl FnOnce(&mut WavWriter<W>) -> Result<(), hound::Error>,
+) -> Result<(), hound::Error> {
+    let written = write(&mut writer);
+    let finalized = writer.finalize();
+    written.and(finalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_stereo_round_trip() {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 44100,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        let mut bytes = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(&mut bytes, spec).unwrap();
+        write_mono(&mut writer, &[0.5, -0.25, 0.0], None).unwrap();
+        writer.finalize().unwrap();
+
+        bytes.set_position(0);
+        let mut reader = WavReader::new(bytes).unwrap();
+        assert_eq!(reader.len(), 6);
+
+        let mono = read_mono(&mut reader, usize::MAX).unwrap();
+        assert_eq!(mono.len(), 3);
+        assert!((mono[0] - 0.5).abs() < 1e-4);
+        assert!((mono[1] + 0.25).abs() < 1e-4);
+    }
+
+    // Fails any write past `limit` bytes, like a full disk
+    struct LimitedCursor {
+        inner: Cursor<Vec<u8>>,
+        limit: u64,
+    }
+
+    impl Write for LimitedCursor {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.inner.position() + buf.len() as u64 > self.limit {
+                return Err(std::io::Error::other("disk full"));
+            }
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Seek for LimitedCursor {
+        fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+            self.inner.seek(pos)
+        }
+    }
+
+    #[test]
+    fn test_failed_write_leaves_valid_shorter_file() {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: 8000,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+        // Room for the header and a few hundred of the thousand samples
+        let mut output = LimitedCursor { inner: Cursor::new(Vec::new()), limit: 1000 };
+        let writer = WavWriter::new(&mut output, spec).unwrap();
+        assert!(write_mono_file(writer, |writer| write_mono(writer, &[0.25; 1000], None)).is_err());
+
+        output.inner.set_position(0);
+        let mut reader = WavReader::new(output.inner).unwrap();
+        let mono = read_mono(&mut reader, usize::MAX).unwrap();
+        assert!(!mono.is_empty() && mono.len() < 500);
+        assert!(mono.iter().all(|&sample| (sample - 0.25).abs() < 1e-4));
+    }
+
+    #[test]
+    fn test_bit_depths_and_dither() {
+        let input = WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 24,
+            sample_format: SampleFormat::Int,
+        };
+        let write = |depth: BitDepth, samples: &[f32], dither: Option<&mut Dither>| {
+            let mut bytes = Cursor::new(Vec::new());
+            let mut writer = WavWriter::new(&mut bytes, depth.apply(input)).unwrap();
+            write_mono(&mut writer, samples, dither).unwrap();
+            writer.finalize().unwrap();
+            bytes.set_position(0);
+            WavReader::new(bytes).unwrap()
+        };
+
+        let mut float = write(BitDepth::Float32, &[0.3, -1.5], None);
+        assert_eq!(float.spec().channels, 2);
+        let samples: Vec<f32> = float.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(samples, [0.3, 0.3, -1.5, -1.5]);
+
+        // A fade below one 16-bit step: rounded it is silence, dithered it stays audible
+        // on average, each sample within a step of its exact value
+        let quiet = vec![0.4 / 32767.0; 20000];
+        let mut rounded = write(BitDepth::Int16, &quiet, None);
+        assert!(rounded.samples::<i32>().all(|sample| sample.unwrap() == 0));
+        let mut dithered = write(BitDepth::Int16, &quiet, Some(&mut Dither::default()));
+        let samples: Vec<i32> = dithered.samples::<i32>().map(Result::unwrap).collect();
+        assert!(samples.iter().all(|sample| (-1..=1).contains(sample)));
+        let mean = samples.iter().sum::<i32>() as f32 / samples.len() as f32;
+        assert!((mean - 0.4).abs() < 0.05);
+
+        assert_eq!("FLOAT".parse(), Ok(BitDepth::Float32));
+        assert_eq!(BitDepth::Int24.to_string().parse(), Ok(BitDepth::Int24));
+        assert!("20".parse::<BitDepth>().is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::Path;
+
+use crate::decoder::MonoReader;
+
+// Length of the windows the level is measured over
+const WINDOW_SECS: f32 = 0.01;
+// Silence kept before and after each phrase, so breaths and decays aren't cut off
+const PADDING_SECS: f32 = 0.1;
+// Anything shorter is a click or a bump of the stand rather than a phrase
+const MIN_PHRASE_SECS: f32 = 0.1;
+// Frames decoded at a time while looking for phrases
+const READ_BLOCK: usize = 1 << 16;
+
+/// How a recording is split into phrases at its silences.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SilenceSplit {
+    // Level below which a window counts as silent, in dBFS
+    pub threshold_db: f32,
+    // Shortest silence that separates two phrases
+    pub min_silence_secs: f32,
+}
+
+impl Default for SilenceSplit {
+    fn default() -> Self {
+        Self { threshold_db: -40.0, min_silence_secs: 0.5 }
+    }
+}
+
+impl SilenceSplit {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-96.0..=0.0).contains(&self.threshold_db) {
+            return Err("Silence threshold must be between -96 and 0 dBFS".to_string());
+        }
+        if !(0.05..=60.0).contains(&self.min_silence_secs) {
+            return Err("Minimum silence must be between 0.05 and 60 seconds".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A phrase of a recording in frames, with a little of the silence around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Phrase {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Phrase {
+    pub fn start_secs(&self, sample_rate: u32) -> f32 {
+        self.start as f32 / sample_rate as f32
+    }
+
+    pub fn end_secs(&self, sample_rate: u32) -> f32 {
+        self.end as f32 / sample_rate as f32
+    }
+}
+
+/// Finds the phrases of a mono signal pushed to it a block at a time.
+#[derive(Debug)]
+pub struct PhraseFinder {
+    split: SilenceSplit,
+    sample_rate: u32,
+    threshold: f32,
+    window_len: usize,
+    // Sum of squares and length of the window being measured
+    window: (f32, usize),
+    frames: usize,
+    // Loud stretches found so far, the last one possibly still going on
+    phrases: Vec<Phrase>,
+}
+
+impl PhraseFinder {
+    pub fn new(split: SilenceSplit, sample_rate: u32) -> Self {
+        Self {
+            split,
+            sample_rate,
+            threshold: 10f32.powf(split.threshold_db / 20.0),
+            window_len: ((WINDOW_SECS * sample_rate as f32) as usize).max(1),
+            window: (0.0, 0),
+            frames: 0,
+            phrases: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.window.0 += sample * sample;
+            self.window.1 += 1;
+            self.frames += 1;
+            if self.window.1 == self.window_len {
+                self.end_window();
+            }
+        }
+    }
+
+    /// The phrases of everything pushed, in order.
+    pub fn finish(mut self) -> Vec<Phrase> {
+        if self.window.1 > 0 {
+            self.end_window();
+        }
+        let to_frames = |secs: f32| (secs * self.sample_rate as f32) as usize;
+        let min_len = to_frames(MIN_PHRASE_SECS);
+        let mut phrases: Vec<Phrase> = self
+            .phrases
+            .into_iter()
+            .filter(|phrase| phrase.end - phrase.start >= min_len)
+            .collect();
+
+        // Padded into the silence around them, half of it at most so neighbours don't overlap
+        let padding = to_frames(PADDING_SECS);
+        let bounds: Vec<(usize, usize)> = (0..phrases.len())
+            .map(|index| {
+                let before = index.checked_sub(1).map_or(0, |before| phrases[before].end);
+                let after = phrases.get(index + 1).map_or(self.frames, |after| after.start);
+                (before, after)
+            })
+            .collect();
+        for (phrase, (before, after)) in phrases.iter_mut().zip(bounds) {
+            let start = phrase.start.saturating_sub(padding.min((phrase.start - before) / 2));
+            let end = phrase.end + padding.min((after - phrase.end) / 2);
+            *phrase = Phrase { start, end };
+        }
+        phrases
+    }
+
+    fn end_window(&mut self) {
+        let (sum, len) = self.window;
+        self.window = (0.0, 0);
+        if (sum / len as f32).sqrt() < self.threshold {
+            return;
+        }
+        let (start, end) = (self.frames - len, self.frames);
+        let min_gap = (self.split.min_silence_secs * self.sample_rate as f32) as usize;
+        match self.phrases.last_mut() {
+            Some(last) if start - last.end < min_gap => last.end = end,
+            _ => self.phrases.push(Phrase { start, end }),
+        }
+    }
+}
+
+/// Decode `path` a block at a time and find its phrases, with the sample rate they are at.
+pub fn find(path: &Path, split: SilenceSplit) -> Result<(Vec<Phrase>, u32), String> {
+    let mut reader = MonoReader::open(path)?;
+    let sample_rate = reader.info().sample_rate;
+    let mut finder = PhraseFinder::new(split, sample_rate);
+    let mut block = Vec::with_capacity(READ_BLOCK);
+    loop {
+        block.clear();
+        if reader.read(&mut block, READ_BLOCK)? == 0 {
+            return Ok((finder.finish(), sample_rate));
+        }
+        finder.push(&block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phrases_are_split_at_long_silences() {
+        // 1 kHz: 1s of voice, a 0.2s breath, 1s of voice, 1s of silence, a click, 1s of voice
+        let rate = 1000;
+        let voice = vec![0.5; 1000];
+        let mut samples = voice.clone();
+        samples.extend([0.0; 200]);
+        samples.extend(&voice);
+        samples.extend([0.0; 1000]);
+        samples.extend([0.9; 20]);
+        samples.extend([0.0; 1000]);
+        samples.extend(&voice);
+        samples.extend([0.0; 30]);
+
+        let mut finder = PhraseFinder::new(SilenceSplit::default(), rate);
+        for block in samples.chunks(333) {
+            finder.push(block);
+        }
+        let phrases = finder.finish();
+        // The breath is part of the first phrase and the click no phrase, each padded by
+        // 0.1s and the last by half of the 30ms left
+        assert_eq!(phrases, [Phrase { start: 0, end: 2300 }, Phrase { start: 4120, end: 5235 }]);
+        assert_eq!(phrases[1].start_secs(rate), 4.12);
+
+        assert!(SilenceSplit { threshold_db: 6.0, ..Default::default() }.validate().is_err());
+        assert!(SilenceSplit::default().validate().is_ok());
+    }
+}
@@ -0,0 +1,230 @@
+use hound::SampleFormat;
+use std::fmt;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::paths;
+use crate::pcm::{self, BitDepth};
+
+/// The input or output path that stands for stdin or stdout.
+pub const STDIO: &str = "-";
+
+/// Whether `path` stands for stdin or stdout rather than a file.
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO
+}
+
+/// Sample format of headerless PCM, interleaved and little-endian, named like ffmpeg's
+/// `-f` formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawFormat(pub BitDepth);
+
+impl RawFormat {
+    pub fn name(self) -> &'static str {
+        match self.0 {
+            BitDepth::Int16 => "s16le",
+            BitDepth::Int24 => "s24le",
+            BitDepth::Int32 => "s32le",
+            BitDepth::Float32 => "f32le",
+        }
+    }
+
+    fn bytes_per_sample(self) -> usize {
+        match self.0 {
+            BitDepth::Int16 => 2,
+            BitDepth::Int24 => 3,
+            BitDepth::Int32 | BitDepth::Float32 => 4,
+        }
+    }
+}
+
+impl FromStr for RawFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        BitDepth::ALL
+            .into_iter()
+            .map(RawFormat)
+            .find(|format| format.name() == text.trim().to_lowercase())
+            .ok_or_else(|| {
+                format!("Invalid raw format '{}': expected s16le, s24le, s32le, or f32le", text)
+            })
+    }
+}
+
+impl fmt::Display for RawFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How audio piped through stdin and stdout is laid out. Piped output has the rate and
+/// channels of the render, so only its format is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RawPcm {
+    pub format: RawFormat,
+    // Rate and channel count of piped input
+    pub sample_rate: Option<u32>,
+    pub channels: u16,
+}
+
+/// Read raw PCM from stdin until it closes, into a WAV at `dest` to be processed like any
+/// other input.
+pub fn receive(raw: &RawPcm, dest: &Path) -> Result<(), String> {
+    receive_from(io::stdin().lock(), raw, dest)
+}
+
+/// Write the WAV at `source` to stdout as raw PCM in `format`.
+pub fn send(source: &Path, format: RawFormat) -> Result<(), String> {
+    send_to(source, format, io::stdout().lock())
+}
+
+fn receive_from(mut reader: impl Read, raw: &RawPcm, dest: &Path) -> Result<(), String> {
+    let sample_rate = raw.sample_rate.ok_or("Piped input needs its rate, with --raw-rate")?;
+    let spec = raw.format.0.apply(hound::WavSpec {
+        channels: raw.channels,
+        sample_rate,
+        bits_per_sample: 16,
+        sample_format: SampleFormat::Int,
+    });
+    let mut writer = paths::create_wav(dest, spec)
+        .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+
+    // Whole samples of each read are written, and the bytes of one cut off carried over
+    let width = raw.format.bytes_per_sample();
+    let mut block = vec![0u8; paths::io_block_size().max(width)];
+    let mut filled = 0;
+    let mut samples = 0usize;
+    loop {
+        let read = match reader.read(&mut block[filled..]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("Failed to read stdin: {}", e)),
+        };
+        filled += read;
+        let whole = filled - filled % width;
+        for bytes in block[..whole].chunks_exact(width) {
+            write_raw_sample(&mut writer, raw.format, bytes)
+                .map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+        }
+        samples += whole / width;
+        block.copy_within(whole..filled, 0);
+        filled -= whole;
+    }
+    writer.finalize().map_err(|e| format!("Failed to write {}: {}", dest.display(), e))?;
+    if samples == 0 {
+        return Err("No audio came in on stdin".to_string());
+    }
+    Ok(())
+}
+
+// Stored as it came, so staging the input loses nothing
+fn write_raw_sample<W: Write + io::Seek>(
+    writer: &mut hound::WavWriter<W>,
+    format: RawFormat,
+    bytes: &[u8],
+) -> Result<(), hound::Error> {
+    match format.0 {
+        BitDepth::Int16 => writer.write_sample(i16::from_le_bytes([bytes[0], bytes[1]]) as i32),
+        BitDepth::Int24 => {
+            writer.write_sample(i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8)
+        }
+        BitDepth::Int32 => {
+            writer.write_sample(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+        BitDepth::Float32 => {
+            writer.write_sample(f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        }
+    }
+}
+
+fn send_to(source: &Path, format: RawFormat, writer: impl Write) -> Result<(), String> {
+    let mut reader = paths::open_wav(source)
+        .map_err(|e| format!("Failed to open {}: {}", source.display(), e))?;
+    let spec = reader.spec();
+    let failed = |e: &dyn fmt::Display| format!("Failed to write to stdout: {}", e);
+    let mut out = BufWriter::with_capacity(paths::io_block_size(), writer);
+
+    if spec.sample_format == SampleFormat::Float {
+        for sample in reader.samples::<f32>() {
+            let sample = sample.map_err(|e| failed(&e))?;
+            write_float(&mut out, format, sample).map_err(|e| failed(&e))?;
+        }
+    } else {
+        // Integers already in the format go out as they are, the rest through a float
+        let same = format.0.apply(spec) == spec;
+        let scale = pcm::input_scale(spec.bits_per_sample)
+            .ok_or_else(|| format!("Unsupported bit depth: {}", spec.bits_per_sample))?;
+        for sample in reader.samples::<i32>() {
+            let sample = sample.map_err(|e| failed(&e))?;
+            let written = if same {
+                write_int(&mut out, format, sample)
+            } else {
+                write_float(&mut out, format, sample as f32 * scale)
+            };
+            written.map_err(|e| failed(&e))?;
+        }
+    }
+    out.flush().map_err(|e| failed(&e))
+}
+
+fn write_float(out: &mut impl Write, format: RawFormat, sample: f32) -> io::Result<()> {
+    let bits = match format.0 {
+        BitDepth::Float32 => return out.write_all(&sample.to_le_bytes()),
+        BitDepth::Int16 => 16,
+        BitDepth::Int24 => 24,
+        BitDepth::Int32 => 32,
+    };
+    let scale = pcm::output_scale(bits).unwrap_or(1.0);
+    write_int(out, format, (sample * scale).round().clamp(-scale - 1.0, scale) as i32)
+}
+
+fn write_int(out: &mut impl Write, format: RawFormat, sample: i32) -> io::Result<()> {
+    let bytes = sample.to_le_bytes();
+    out.write_all(&bytes[..format.bytes_per_sample()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_pcm_round_trips() {
+        assert_eq!("F32LE".parse::<RawFormat>().unwrap(), RawFormat(BitDepth::Float32));
+        assert_eq!(RawFormat(BitDepth::Int24).to_string(), "s24le");
+        assert!("u8".parse::<RawFormat>().is_err());
+        assert!(is_stdio(Path::new("-")) && !is_stdio(Path::new("-.wav")));
+
+        let dest = std::env::temp_dir().join("pipe_round_trip_test.wav");
+        let raw =
+            RawPcm { format: RawFormat(BitDepth::Int24), sample_rate: Some(48000), channels: 2 };
+        let samples: Vec<u8> = [-8388608i32, -1, 0, 4194304]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes()[..3].to_vec())
+            .collect();
+        // Split partway through a sample, like a pipe can
+        let reader =
+            io::Cursor::new(samples[..4].to_vec()).chain(io::Cursor::new(samples[4..].to_vec()));
+        receive_from(reader, &raw, &dest).unwrap();
+        let spec = paths::open_wav(&dest).unwrap().spec();
+        assert_eq!((spec.sample_rate, spec.channels, spec.bits_per_sample), (48000, 2, 24));
+
+        let mut same = Vec::new();
+        send_to(&dest, raw.format, &mut same).unwrap();
+        assert_eq!(same, samples);
+        let mut floats = Vec::new();
+        send_to(&dest, RawFormat(BitDepth::Float32), &mut floats).unwrap();
+        let floats: Vec<f32> = floats
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            .collect();
+        assert_eq!(floats[0], -1.0);
+        assert_eq!(floats[3], 0.5);
+
+        let empty = receive_from(io::empty(), &raw, &dest);
+        assert!(empty.unwrap_err().contains("No audio"));
+        let _ = std::fs::remove_file(&dest);
+    }
+}
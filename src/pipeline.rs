@@ -0,0 +1,1800 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::ops::{ControlFlow, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use synthphone_vocals::{AutotuneConfig, AutotuneState, MusicalSettings, process_autotune};
+
+use crate::classifier::{self, PitchTrack, PitchTracker};
+use crate::decoder::MonoReader;
+use crate::dynamics::{Compressor, LevelCompressor, Limit, Limiter, Meters};
+use crate::effects::{Chain, ChainOrder, Effect, Stage};
+use crate::energy::{EnergyCurve, EnergyFollower};
+use crate::engine::{self, Framing, Padding, ParallelOptions, Window};
+use crate::equalizer::{Equalizer, Peak};
+use crate::harmony::Interval;
+use crate::keys::{self, KeyChange};
+use crate::labels::Label;
+use crate::loudness::{LoudnessMeter, Normalization};
+use crate::midi::Melody;
+use crate::pcm::BitDepth;
+use crate::pitch::{F0Range, PitchAlgorithm};
+use crate::prefilter::{Gate, HighPass, NoiseGate};
+use crate::resample::{self, Resampler};
+use crate::routing::{self, MonoCheck, MonoMeter, Route, Voice};
+use crate::scale::{Scale, ScaleTarget};
+use crate::sections::{self, Section};
+use crate::tint::{Spectrum, SpectrumMeter, TintFilter};
+use crate::verify::{self, Verification};
+use crate::{format, paths, pcm, scratch};
+
+/// Concert pitch the library's notes are tuned to, the default A4 reference.
+pub const STANDARD_A4: f32 = 440.0;
+/// A4 references notes can be tuned to, from below Baroque pitch to above orchestral.
+pub const A4_RANGE: RangeInclusive<f32> = 400.0..=480.0;
+/// How readily frames are taken for consonants and breaths and passed through uncorrected.
+pub const DEFAULT_UNVOICED_SENSITIVITY: f32 = 0.5;
+
+// Frames converted per block when writing the final file
+const WRITE_BLOCK: usize = 1 << 16;
+// Output this far from the input pitch counts as corrected
+const CORRECTED_CENTS: f32 = 10.0;
+// Crossfade between the input and the correction at the edges of a region
+const REGION_FADE_SECS: f32 = 0.01;
+// Crossfade from one section's correction to the next at their edge
+const SECTION_FADE_SECS: f32 = 0.05;
+// Change in the share of input per frame going into or out of vocal fry, a crossfade over
+// a few hops
+const FRY_FADE_STEP: f32 = 0.34;
+// The same for unvoiced frames, quicker since consonants are short
+const UNVOICED_FADE_STEP: f32 = 0.5;
+// Detection confidence below which a frame is left uncorrected, and from which on it gets
+// the full correction. In between the share of it kept, in cents, rises linearly
+const CONFIDENCE_NONE: f32 = 0.3;
+const CONFIDENCE_FULL: f32 = 0.85;
+// Share of the way the correction amount moves to a new frame's confidence, so it doesn't
+// flutter with the detector
+const CONFIDENCE_SMOOTHING: f32 = 0.5;
+
+/// What to do to one file.
+pub struct RenderSettings {
+    pub config: AutotuneConfig,
+    // Window the corrected frames are crossfaded with
+    pub window: Window,
+    // What the frames past the end of the input are filled out with
+    pub padding: Padding,
+    // How the pitch the note is chosen from is found. With anything but the library's own,
+    // the note is chosen here from the key or scale and forced like a scale note
+    pub pitch_algorithm: PitchAlgorithm,
+    // Frequencies the pitch is looked for in, which also has the note chosen here
+    pub f0_range: Option<F0Range>,
+    pub musical: MusicalSettings,
+    // Correct to the nearest note of this scale instead of snapping to `musical`'s key
+    pub scale: Option<Scale>,
+    // Keys that take over from `musical`'s partway through, in time order
+    pub key_changes: Vec<KeyChange>,
+    // Stretches with settings of their own instead of `musical` and the key changes
+    pub sections: Vec<Section>,
+    // Notes to correct to instead, each while it plays. The key or scale applies in its rests
+    pub melody: Option<Melody>,
+    // The file's own rate, or an analysis rate it is resampled to for processing
+    pub processing_rate: u32,
+    // Rate `render` writes at, the file's own when None
+    pub output_rate: Option<u32>,
+    // How many frames `render` writes at the output rate
+    pub length: OutputLength,
+    pub threads: usize,
+    // Only these stretches are corrected and the rest passes through unchanged. Empty, or
+    // only points, corrects everything
+    pub regions: Vec<Label>,
+    // Correct vocal fry like everything else. Otherwise it passes through, since the pitch
+    // found in creak is a subharmonic or noise and correcting it adds a croak
+    pub correct_fry: bool,
+    // Keep less of the lead's correction, in cents, the less confidently a pitch is found in
+    // a frame, so breathy and noisy frames are corrected gently instead of all or nothing
+    pub confidence_strength: bool,
+    // How readily (0.0 to 1.0) frames that sound unvoiced, like sibilants and breaths, pass
+    // through uncorrected, since correcting them hard sounds metallic. 0.0 corrects them too
+    pub unvoiced_sensitivity: f32,
+    // Frequency of A4 that the notes are tuned to, `STANDARD_A4` unless the recording is
+    // tuned to another concert pitch
+    pub a4: f32,
+    // Scale the lead's correction with its level along this curve, so loud lines are
+    // corrected harder than quiet tails
+    pub energy: Option<EnergyCurve>,
+    // The order the stages run in, every stage once. Those before the autotune clean up
+    // its input, what passes through uncorrected included, and those after it shape the
+    // output; neither reaches the dry and corrected stems
+    pub chain: ChainOrder,
+    // Rumble below this cutoff is filtered out, with the high-pass on
+    pub high_pass_hz: Option<f32>,
+    // What stays below the threshold is muted, with the gate on
+    pub gate: Option<Gate>,
+    // The voice is shaped by the bands of this EQ, with the EQ on
+    pub eq: Option<Equalizer>,
+    // Loud lines are brought closer to quiet ones, with the compressor on
+    pub compressor: Option<Compressor>,
+    // Where the compressor reports how far it is turning the lead down, while it runs
+    pub meters: Option<Arc<Meters>>,
+    // Spool the layers of the render as stems too, for `render`
+    pub stems: bool,
+    // Voices `render` adds at these intervals from the corrected lead, in the scale or key
+    pub harmony: Vec<Interval>,
+    // Level the harmony voices are mixed into the render at, 0.0 only writes their stems
+    pub harmony_level: f32,
+    // Levels the lead and harmony voices go into each channel at. Empty renders mono,
+    // otherwise the render is stereo
+    pub routing: Vec<Route>,
+}
+
+/// How long a render comes out at the output rate. Written as "input", or as a number of
+/// frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum OutputLength {
+    /// Exactly as long as the input, so it lines up with it in a DAW
+    #[default]
+    Input,
+    /// Padded with silence or cut off to this many frames, like the other tracks of a
+    /// session
+    Frames(usize),
+}
+
+impl OutputLength {
+    /// The frames a complete render of `input_frames` frames comes out as.
+    pub fn frames(self, input_frames: usize) -> usize {
+        match self {
+            Self::Input => input_frames,
+            Self::Frames(frames) => frames,
+        }
+    }
+}
+
+impl FromStr for OutputLength {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        match text.trim().to_lowercase().as_str() {
+            "input" => Ok(Self::Input),
+            frames => frames.parse().map(Self::Frames).map_err(|_| {
+                format!("Invalid length '{}': expected input or a number of frames", text.trim())
+            }),
+        }
+    }
+}
+
+impl TryFrom<String> for OutputLength {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, String> {
+        text.parse()
+    }
+}
+
+impl From<OutputLength> for String {
+    fn from(length: OutputLength) -> Self {
+        length.to_string()
+    }
+}
+
+impl fmt::Display for OutputLength {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Input => write!(f, "input"),
+            Self::Frames(frames) => write!(f, "{}", frames),
+        }
+    }
+}
+
+/// A layer of a render, written as its own file for blending in a DAW.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stem {
+    // The input as it went in
+    Dry,
+    // The correction everywhere, before the input is mixed back in outside of the regions
+    Corrected,
+    // A harmony voice on its own, silent outside of the regions
+    Harmony(Interval),
+}
+
+impl Stem {
+    pub fn name(self) -> String {
+        match self {
+            Stem::Dry => "dry".to_string(),
+            Stem::Corrected => "corrected".to_string(),
+            Stem::Harmony(interval) => format!("harmony_{}", interval.file_name()),
+        }
+    }
+}
+
+/// Where the `stem` of a render to `output` is written: next to it, named after it.
+pub fn stem_path(output: &Path, stem: Stem) -> PathBuf {
+    let name = output.file_stem().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{}_{}.wav", name, stem.name()))
+}
+
+/// Where the float processing master of a render to `output` is written, like a stem.
+pub fn master_path(output: &Path) -> PathBuf {
+    let name = output.file_stem().unwrap_or_default().to_string_lossy();
+    output.with_file_name(format!("{}_master.wav", name))
+}
+
+/// Decode `input`, correct it, and spool the result at the input's own rate (or
+/// `settings.output_rate`) to a scratch file next to `output`, one block at a time, so
+/// memory use doesn't grow with the length of the file, along with its stems when
+/// `settings.stems` asks for them. The pitch going in and coming out is tracked on the way.
+/// Each harmony voice is another pass over the input once the lead is done, mixed in at
+/// the end, into two channels as `settings.routing` asks when it isn't empty. `on_progress`
+/// sees the engine's progress at the processing rate, over all of the passes, with only the
+/// lead's audio; returning `Break` stops early and keeps the mono lead finished so far.
+pub fn render(
+    input: &Path,
+    output: &Path,
+    settings: &RenderSettings,
+    mut on_progress: impl FnMut(engine::Progress) -> ControlFlow<()>,
+) -> Result<Rendered, String> {
+    let rate = settings.processing_rate;
+    let mut source = Source::open(input, rate)?;
+    let file_rate = source.file_rate;
+    let output_rate = settings.output_rate.unwrap_or(file_rate);
+    let expected_len = source.expected_len();
+    let voices = if settings.harmony_level > 0.0 || settings.stems {
+        settings.harmony.len()
+    } else {
+        0
+    };
+    let passes = 1 + voices;
+
+    let limit = match settings.length {
+        OutputLength::Input => None,
+        OutputLength::Frames(frames) => Some(frames),
+    };
+    let new_sink = || Sink::create(output, rate, output_rate, limit);
+    let mut sink = new_sink()?;
+    // Through the same resampling as the render, so they line up with it
+    let mut dry_stem = settings.stems.then(new_sink).transpose()?;
+    let mut corrected_stem = settings.stems.then(new_sink).transpose()?;
+
+    // Both at the processing rate, so their frames line up
+    let mut detected = PitchTracker::new(rate as f32);
+    let mut corrected = PitchTracker::new(rate as f32);
+    let mut input_loudness = LoudnessMeter::new(rate, 1);
+    let read = |buffer: &mut Vec<f32>, max: usize| {
+        let start = buffer.len();
+        let read = source.read(buffer, max)?;
+        detected.push(&buffer[start..]);
+        input_loudness.push(&buffer[start..]);
+        if let Some(stem) = &mut dry_stem {
+            stem.push(&buffer[start..])?;
+        }
+        Ok(read)
+    };
+    let mut write_error = None;
+    let mut stopped = false;
+    let on_finished = |progress: engine::Progress, unmixed: &[f32]| {
+        corrected.push(progress.finished);
+        let pushed = sink.push(progress.finished).and_then(|()| match &mut corrected_stem {
+            Some(stem) => stem.push(unmixed),
+            None => Ok(()),
+        });
+        if let Err(e) = pushed {
+            write_error = Some(e);
+            return ControlFlow::Break(());
+        }
+        let flow = on_progress(engine::Progress { total: progress.total * passes, ..progress });
+        stopped = flow.is_break();
+        flow
+    };
+    let stream = correct(settings, None, read, expected_len, on_finished)?;
+    if let Some(e) = write_error {
+        return Err(e);
+    }
+
+    // A complete render lasts exactly as long as the input, or as asked for
+    let len = if source.finished && stream.len == source.delivered {
+        let input_len = resample::resampled_len(source.decoded, file_rate, output_rate);
+        settings.length.frames(input_len)
+    } else {
+        let len = resample::resampled_len(stream.len, rate, output_rate);
+        limit.map_or(len, |limit| len.min(limit))
+    };
+    let mut rendered = sink.finish(len, stream.failed_frames)?;
+    rendered.input_loudness = input_loudness.finish();
+    rendered.pitch =
+        Some(PitchCurves { detected: detected.finish(), corrected: corrected.finish() });
+    for (stem, sink) in [(Stem::Dry, dry_stem), (Stem::Corrected, corrected_stem)] {
+        if let Some(sink) = sink {
+            rendered.stems.push((stem, sink.finish(len, Vec::new())?));
+        }
+    }
+    if stopped || (voices == 0 && settings.routing.is_empty()) {
+        return Ok(rendered);
+    }
+
+    let mut rendered_voices = Vec::new();
+    for (pass, &interval) in (1..).zip(&settings.harmony) {
+        let voice = render_voice(input, output, settings, interval, len, |progress| {
+            on_progress(engine::Progress {
+                done: progress.total * pass + progress.done,
+                total: progress.total * passes,
+                finished: &[],
+            })
+        })?;
+        match voice {
+            Some(voice) => rendered_voices.push((interval, voice)),
+            None => return Ok(rendered),
+        }
+    }
+    if !settings.routing.is_empty() || settings.harmony_level > 0.0 {
+        // Voices only rendered for their stems aren't mixed in
+        let mixed = if settings.harmony_level > 0.0 {
+            &rendered_voices[..]
+        } else {
+            &[]
+        };
+        rendered = rendered.mix_voices(output, mixed, settings.harmony_level, &settings.routing)?;
+    }
+    if settings.stems {
+        for (interval, voice) in rendered_voices {
+            rendered.stems.push((Stem::Harmony(interval), voice));
+        }
+    }
+    Ok(rendered)
+}
+
+// Render the harmony voice `interval` from the lead over `input`, `len` frames at the output
+// rate like the lead's render. None when `on_progress` stopped it
+fn render_voice(
+    input: &Path,
+    output: &Path,
+    settings: &RenderSettings,
+    interval: Interval,
+    len: usize,
+    mut on_progress: impl FnMut(engine::Progress) -> ControlFlow<()>,
+) -> Result<Option<Rendered>, String> {
+    let rate = settings.processing_rate;
+    let mut source = Source::open(input, rate)?;
+    let expected_len = source.expected_len();
+    let output_rate = settings.output_rate.unwrap_or(source.file_rate);
+    let mut sink = Sink::create(output, rate, output_rate, Some(len))?;
+
+    let mut write_error = None;
+    let mut stopped = false;
+    let on_finished = |progress: engine::Progress, _: &[f32]| {
+        if let Err(e) = sink.push(progress.finished) {
+            write_error = Some(e);
+            return ControlFlow::Break(());
+        }
+        let flow = on_progress(progress);
+        stopped = flow.is_break();
+        flow
+    };
+    let read = |buffer: &mut Vec<f32>, max: usize| source.read(buffer, max);
+    correct(settings, Some(interval), read, expected_len, on_finished)?;
+    if let Some(e) = write_error {
+        return Err(e);
+    }
+    if stopped {
+        return Ok(None);
+    }
+    sink.finish(len, Vec::new()).map(Some)
+}
+
+/// Mono audio corrected in memory by [`process_samples`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Processed {
+    pub samples: Vec<f32>,
+    // (sample position, error) of the frames that fell back to the dry input
+    pub failed_frames: Vec<(usize, String)>,
+}
+
+/// Correct mono `samples` at `settings.processing_rate` in memory, the way `render`
+/// corrects a file, for callers that bring their own audio. Nothing is resampled or
+/// normalized. `on_progress` gets 0.0 to 1.0; returning `Break` stops early and keeps the
+/// audio finished so far.
+pub fn process_samples(
+    samples: &[f32],
+    settings: &RenderSettings,
+    mut on_progress: impl FnMut(f32) -> ControlFlow<()>,
+) -> Result<Processed, String> {
+    let mut remaining = samples;
+    let read = |buffer: &mut Vec<f32>, max: usize| {
+        let (block, rest) = remaining.split_at(max.min(remaining.len()));
+        buffer.extend_from_slice(block);
+        remaining = rest;
+        Ok(block.len())
+    };
+    let mut processed = Vec::with_capacity(samples.len());
+    let output = correct(settings, None, read, samples.len(), |progress, _| {
+        processed.extend_from_slice(progress.finished);
+        on_progress(progress.done as f32 / progress.total.max(1) as f32)
+    })?;
+    Ok(Processed { samples: processed, failed_frames: output.failed_frames })
+}
+
+// Correct the samples `read` hands out at the processing rate as `settings` say, mixing the
+// input back in outside of its regions before `on_finished` sees the result. It gets the
+// correction from before the mix too. With a `voice`, the harmony voice at that interval
+// from the lead is sung instead, silent outside of the regions
+fn correct(
+    settings: &RenderSettings,
+    voice: Option<Interval>,
+    mut read: impl FnMut(&mut Vec<f32>, usize) -> Result<usize, String>,
+    expected_len: usize,
+    mut on_finished: impl FnMut(engine::Progress, &[f32]) -> ControlFlow<()>,
+) -> Result<engine::StreamOutput, String> {
+    let rate = settings.processing_rate;
+    let tuned_rate = tuned_rate(rate as f32, settings.a4);
+    let config = AutotuneConfig { sample_rate: tuned_rate, ..settings.config };
+    let musical = &settings.musical;
+    let scale = settings.scale;
+    // The notes harmony voices move in, the key's unless there is a scale
+    let voice_scale = |key: i32| scale.unwrap_or_else(|| Scale::from_key(key as usize));
+    // The settings at `secs` before any note is chosen: its section's, or the main ones in
+    // the key in effect
+    let musical_at = |secs: f32| match sections::section_at(&settings.sections, secs) {
+        Some(section) => section.musical(),
+        None => MusicalSettings {
+            key: keys::key_at(&settings.key_changes, secs).map_or(musical.key, |key| key as i32),
+            note: musical.note,
+            octave: musical.octave,
+            formant: musical.formant,
+        },
+    };
+    // A harmony voice needs the lead's note even where the library would choose it, and
+    // so does a detector of our own or one kept to a range
+    let picks_note = settings.pitch_algorithm.picks_note() || settings.f0_range.is_some();
+    let follows_scale = scale.is_some() || voice.is_some() || picks_note;
+    let make_state = |frame| FrameState {
+        autotune: AutotuneState::new(config),
+        incoming: None,
+        energy: EnergyFollower::new(config.hop_size as f32 / rate as f32),
+        target: follows_scale.then(|| {
+            let detector = settings.pitch_algorithm.detector(tuned_rate, settings.f0_range);
+            ScaleTarget::new(voice_scale(musical.key), detector)
+        }),
+        frame,
+        confidence: 1.0,
+        retuned: vec![0.0; config.fft_size],
+        fry: 0.0,
+        unvoiced: 0.0,
+    };
+    let process = |frame_in: &[f32], frame_out: &mut [f32], state: &mut FrameState| {
+        let center = state.frame * config.hop_size + config.fft_size / 2;
+        state.frame += 1;
+        let secs = center as f32 / rate as f32;
+        let musical = musical_at(secs);
+        let melody_note = settings.melody.as_ref().and_then(|melody| melody.note_at(secs));
+        if (voice.is_some() || picks_note) && scale.is_none() {
+            if let Some(target) = &mut state.target {
+                target.set_scale(voice_scale(musical.key));
+            }
+        }
+        if let Some(target) = &mut state.target {
+            // Tracks the pitch even where the note is locked, for a section that isn't
+            target.settings(frame_in, &musical);
+        }
+        // The target's note, Some(None) until it found a first pitch
+        let target_note = state.target.as_ref().map(ScaleTarget::note);
+        // What the library is told for `musical`: the note the lead is forced to, then the
+        // voice's from it
+        let resolve = |musical: MusicalSettings| {
+            // A scale always picks the note, for a voice or a detector of our own only where
+            // the settings don't lock one
+            let follows = target_note.filter(|_| scale.is_some() || musical.note == 0);
+            let lead = match (melody_note, follows) {
+                // Forced through the note setting like a scale note, 1 = C up to 12 = B
+                (Some(note), _) => MusicalSettings { note: (note % 12) as i32 + 1, ..musical },
+                (None, Some(note)) => MusicalSettings {
+                    // Auto until the first pitch is found
+                    note: note.map_or(0, |note| note.rem_euclid(12) + 1),
+                    ..musical
+                },
+                (None, None) => musical,
+            };
+            match voice {
+                Some(interval) => {
+                    let lead_note = melody_note.map(i32::from).or(follows.flatten());
+                    interval.settings(lead_note, &voice_scale(lead.key), &lead)
+                }
+                None => lead,
+            }
+        };
+
+        // Run through fry too, so the library's state carries on from the audio around it
+        let edge = sections::next_edge(&settings.sections, secs, SECTION_FADE_SECS);
+        let processed = match edge {
+            // Approaching the edge of a section, the settings after it are run alongside from
+            // a state of their own and faded in
+            Some(edge) => {
+                let (incoming, faded) = state.incoming.get_or_insert_with(|| {
+                    (AutotuneState::new(config), vec![0.0; frame_out.len()])
+                });
+                process_autotune(frame_in, frame_out, &mut state.autotune, &resolve(musical))
+                    .and_then(|_| {
+                        process_autotune(frame_in, faded, incoming, &resolve(musical_at(edge)))
+                    })
+                    .map(|_| {
+                        let amount = 1.0 - (edge - secs) / SECTION_FADE_SECS;
+                        for (out, &faded) in frame_out.iter_mut().zip(faded.iter()) {
+                            *out += (faded - *out) * amount;
+                        }
+                    })
+            }
+            None => {
+                // Past the edge the incoming state carries on
+                if let Some((incoming, _)) = state.incoming.take() {
+                    state.autotune = incoming;
+                }
+                process_autotune(frame_in, frame_out, &mut state.autotune, &resolve(musical))
+                    .map(|_| ())
+            }
+        };
+        processed.map(|()| {
+            // A voice stays on its interval however uncertain or quiet the lead gets
+            if voice.is_none() {
+                let mut amount = 1.0;
+                if settings.confidence_strength {
+                    amount *= confidence_amount(frame_in, &mut state.confidence, rate as f32);
+                }
+                if let Some(curve) = &settings.energy {
+                    amount *= curve.amount(state.energy.push(frame_in));
+                }
+                let ratio = state.autotune.previous_pitch_shift_ratio;
+                scale_pitch_correction(frame_in, frame_out, &mut state.retuned, ratio, amount);
+            }
+            if !settings.correct_fry {
+                pass_fry(frame_in, frame_out, &mut state.fry, rate as f32);
+            }
+            if settings.unvoiced_sensitivity > 0.0 {
+                let unvoiced =
+                    classifier::is_unvoiced(frame_in, rate as f32, settings.unvoiced_sensitivity);
+                // A voice has no consonants of its own to pass, it drops out for the lead's
+                let dry = if voice.is_some() {
+                    None
+                } else {
+                    Some(frame_in)
+                };
+                fade_to_dry(dry, frame_out, &mut state.unvoiced, unvoiced, UNVOICED_FADE_STEP);
+            }
+        })
+    };
+    // With regions, input read but not finished yet, to mix back in outside of them
+    let mut regions = Regions::new(&settings.regions, rate);
+    let keep_dry = regions.is_some();
+    let dry = RefCell::new(VecDeque::new());
+    // The harmony voices are compressed too, but only the lead is metered
+    let meters = settings.meters.clone().filter(|_| voice.is_none());
+    let (mut before, mut after) = effect_chains(settings, rate as f32, meters);
+    let read_keeping_dry = |buffer: &mut Vec<f32>, max: usize| {
+        let start = buffer.len();
+        let read = read(buffer, max)?;
+        before.process(&mut buffer[start..]);
+        if keep_dry {
+            let silent = voice.is_some();
+            let samples = buffer[start..].iter().map(|&sample| if silent { 0.0 } else { sample });
+            dry.borrow_mut().extend(samples);
+        }
+        Ok(read)
+    };
+    let mut mixed = Vec::new();
+    let mut shaped = Vec::new();
+    let finish_mixed = |progress: engine::Progress| {
+        let finished = match &mut regions {
+            Some(regions) => {
+                regions.mix(progress.finished, &mut dry.borrow_mut(), &mut mixed);
+                &mixed[..]
+            }
+            None => progress.finished,
+        };
+        let finished = if after.is_empty() {
+            finished
+        } else {
+            shaped.clear();
+            shaped.extend_from_slice(finished);
+            after.process(&mut shaped);
+            &shaped[..]
+        };
+        on_finished(engine::Progress { finished, ..progress }, progress.finished)
+    };
+
+    let framing = Framing {
+        window: settings.window,
+        padding: settings.padding,
+        ..Framing::new(config.fft_size, config.hop_size)
+    };
+    if settings.threads > 1 {
+        let options = ParallelOptions { threads: settings.threads, ..Default::default() };
+        engine::process_stream_parallel(
+            read_keeping_dry,
+            expected_len,
+            framing,
+            &options,
+            make_state,
+            process,
+            finish_mixed,
+        )
+    } else {
+        engine::process_stream(
+            read_keeping_dry,
+            expected_len,
+            framing,
+            &mut make_state(0),
+            process,
+            finish_mixed,
+        )
+    }
+}
+
+// The effects of `settings.chain` that are on, for audio at `rate`: the chain the input
+// runs through before the correction, and the one its output runs through after it. The
+// compressor reports to `meters`
+fn effect_chains(
+    settings: &RenderSettings,
+    rate: f32,
+    meters: Option<Arc<Meters>>,
+) -> (Chain, Chain) {
+    let (mut before, mut after) = (Chain::default(), Chain::default());
+    let mut chain = &mut before;
+    for &stage in settings.chain.stages() {
+        match stage {
+            Stage::HighPass => {
+                if let Some(hz) = settings.high_pass_hz {
+                    chain.push(HighPass::new(hz, rate));
+                }
+            }
+            Stage::Gate => {
+                if let Some(gate) = settings.gate {
+                    chain.push(NoiseGate::new(gate, rate));
+                }
+            }
+            Stage::Autotune => chain = &mut after,
+            Stage::Eq => {
+                for &band in settings.eq.iter().flat_map(Equalizer::active) {
+                    chain.push(Peak::new(band, rate));
+                }
+            }
+            Stage::Compressor => {
+                if let Some(compressor) = settings.compressor {
+                    chain.push(LevelCompressor::new(compressor, rate, meters.clone()));
+                }
+            }
+        }
+    }
+    (before, after)
+}
+
+/// Sample rate to tell the library audio at `rate` runs at, so it corrects to notes tuned to
+/// `a4`. It only knows A4 = 440 Hz, but hears every pitch scaled by the rate it is told over
+/// the real one: at 440/442 of the real rate a 442 Hz A is heard as 440 Hz and left alone.
+pub fn tuned_rate(rate: f32, a4: f32) -> f32 {
+    rate * STANDARD_A4 / a4
+}
+
+// Everything one processing thread carries from frame to frame
+struct FrameState {
+    autotune: AutotuneState,
+    // State of the next section's settings while crossfading into them, and its output
+    incoming: Option<(AutotuneState, Vec<f32>)>,
+    // Level of the input, for the energy curve
+    energy: EnergyFollower,
+    target: Option<ScaleTarget>,
+    // Index of the next frame, to find the key in effect
+    frame: usize,
+    // Smoothed share of the correction kept, 0.0 to 1.0
+    confidence: f32,
+    // Copy of the corrected frame while it is re-pitched to a share of its correction
+    retuned: Vec<f32>,
+    // Share of the input passed through while in vocal fry, 0.0 to 1.0
+    fry: f32,
+    // The same while unvoiced
+    unvoiced: f32,
+}
+
+// Move the smoothed share of the correction kept, `amount`, towards how confidently a
+// pitch can be found in `frame_in`, and return it
+fn confidence_amount(frame_in: &[f32], amount: &mut f32, rate: f32) -> f32 {
+    let confidence = classifier::frame_confidence(frame_in, rate);
+    let target =
+        ((confidence - CONFIDENCE_NONE) / (CONFIDENCE_FULL - CONFIDENCE_NONE)).clamp(0.0, 1.0);
+    *amount += (target - *amount) * CONFIDENCE_SMOOTHING;
+    *amount
+}
+
+// Re-pitch `frame_out`, which the library shifted by `ratio`, so only `amount` of that shift
+// in cents is left. Mixing the input back in instead would leave two pitches that comb
+// against each other. Frames the library passed through as they are, with no pitch found in
+// range, had no shift to scale
+fn scale_pitch_correction(
+    frame_in: &[f32],
+    frame_out: &mut [f32],
+    scratch: &mut [f32],
+    ratio: f32,
+    amount: f32,
+) {
+    if amount >= 1.0 || !(ratio > 0.0 && ratio.is_finite()) || ratio == 1.0 || frame_out == frame_in
+    {
+        return;
+    }
+    repitch(frame_out, scratch, ratio.powf(amount - 1.0));
+}
+
+// Resample `frame` about its centre so its pitch is multiplied by `shift`, using `scratch`,
+// as long as the frame, for a copy. Samples that run off its ends are silent, which the
+// synthesis window mostly hides
+fn repitch(frame: &mut [f32], scratch: &mut [f32], shift: f32) {
+    scratch.copy_from_slice(frame);
+    let at = |index: isize| usize::try_from(index).ok().and_then(|i| scratch.get(i));
+    let sample = |index: isize| at(index).copied().unwrap_or(0.0);
+    let centre = (frame.len() - 1) as f32 / 2.0;
+    for (index, out) in frame.iter_mut().enumerate() {
+        let position = centre + (index as f32 - centre) * shift;
+        let before = position.floor();
+        let left = sample(before as isize);
+        *out = left + (sample(before as isize + 1) - left) * (position - before);
+    }
+}
+
+// Crossfade `frame_out` towards `frame_in` while it sounds like vocal fry, and back after
+fn pass_fry(frame_in: &[f32], frame_out: &mut [f32], fry: &mut f32, sample_rate: f32) {
+    let is_fry = classifier::is_fry(frame_in, sample_rate);
+    fade_to_dry(Some(frame_in), frame_out, fry, is_fry, FRY_FADE_STEP);
+}
+
+// Move `share` by up to `step` towards 1.0 while `pass` holds and back to 0.0 after, and
+// crossfade `frame_out` that far towards `dry`, or towards silence without it
+fn fade_to_dry(dry: Option<&[f32]>, frame_out: &mut [f32], share: &mut f32, pass: bool, step: f32) {
+    let target = if pass { 1.0 } else { 0.0 };
+    *share = f32::clamp(target, *share - step, *share + step);
+    if *share > 0.0 {
+        for (index, out) in frame_out.iter_mut().enumerate() {
+            let dry = dry.map_or(0.0, |dry| dry[index]);
+            *out += (dry - *out) * *share;
+        }
+    }
+}
+
+/// Spots in a render worth checking, as labels to jump to in a DAW: a region over each run
+/// of frames that fell back to the dry input, and a marker at the start per warning.
+pub fn problem_markers(
+    failed_frames: &[(usize, String)],
+    settings: &RenderSettings,
+    warnings: &[String],
+) -> Vec<Label> {
+    let rate = settings.processing_rate as f32;
+    let mut markers: Vec<Label> = warnings
+        .iter()
+        .map(|warning| Label {
+            start_secs: 0.0,
+            end_secs: 0.0,
+            text: format!("Warning: {}", warning),
+        })
+        .collect();
+    // (first sample, end sample, frames, first error)
+    let mut runs: Vec<(usize, usize, usize, &str)> = Vec::new();
+    for (pos, e) in failed_frames {
+        let end = pos + settings.config.fft_size;
+        match runs.last_mut() {
+            Some((_, run_end, frames, _)) if *pos <= *run_end => {
+                *run_end = end;
+                *frames += 1;
+            }
+            _ => runs.push((*pos, end, 1, e)),
+        }
+    }
+    markers.extend(runs.into_iter().map(|(start, end, frames, e)| Label {
+        start_secs: start as f32 / rate,
+        end_secs: end as f32 / rate,
+        text: match frames {
+            1 => format!("Fallback: {}", e),
+            frames => format!("Fallback, {} frames: {}", frames, e),
+        },
+    }));
+    markers
+}
+
+/// Pitch of the input and of the corrected output over a render, frame by frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PitchCurves {
+    pub detected: PitchTrack,
+    pub corrected: PitchTrack,
+}
+
+impl PitchCurves {
+    pub fn stats(&self) -> PitchStats {
+        let (mut voiced, mut corrected, mut undetected) = (0, 0, 0);
+        let mut total_cents = 0.0;
+        for (before, after) in self.detected.frames.iter().zip(&self.corrected.frames) {
+            match (before.f0, after.f0) {
+                (Some(before), Some(after)) => {
+                    let cents = (1200.0 * (after / before).log2()).abs();
+                    voiced += 1;
+                    total_cents += cents;
+                    if cents > CORRECTED_CENTS {
+                        corrected += 1;
+                    }
+                }
+                (None, _) if !before.is_silent() => undetected += 1,
+                _ => {}
+            }
+        }
+        let share = |count: usize| {
+            if voiced > 0 {
+                count as f32 / voiced as f32
+            } else {
+                0.0
+            }
+        };
+        PitchStats {
+            voiced_secs: voiced as f32 * self.detected.hop_secs,
+            corrected_ratio: share(corrected),
+            mean_shift_cents: if voiced > 0 {
+                total_cents / voiced as f32
+            } else {
+                0.0
+            },
+            undetected_secs: undetected as f32 * self.detected.hop_secs,
+        }
+    }
+}
+
+/// How far a render moved the pitch, from its [`PitchCurves`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PitchStats {
+    // Time with a pitch both going in and coming out
+    pub voiced_secs: f32,
+    // Share of that time moved by more than `CORRECTED_CENTS`
+    pub corrected_ratio: f32,
+    pub mean_shift_cents: f32,
+    // Audible input without a detected pitch, which the correction can't follow
+    pub undetected_secs: f32,
+}
+
+impl PitchStats {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{:.0}% of {} voiced corrected, {:.0} cents on average",
+            self.corrected_ratio * 100.0,
+            format::duration(self.voiced_secs),
+            self.mean_shift_cents
+        );
+        if self.undetected_secs > 0.0 {
+            let undetected = format::duration(self.undetected_secs);
+            summary.push_str(&format!(", no pitch found for {}", undetected));
+        }
+        summary
+    }
+}
+
+// Processing regions in samples at the processing rate, and how far the output has got
+struct Regions {
+    ranges: Vec<(usize, usize)>,
+    fade: usize,
+    position: usize,
+}
+
+impl Regions {
+    fn new(labels: &[Label], rate: u32) -> Option<Self> {
+        let to_samples = |secs: f32| (secs.max(0.0) * rate as f32).round() as usize;
+        let ranges: Vec<(usize, usize)> = labels
+            .iter()
+            .filter(|label| !label.is_point())
+            .map(|label| (to_samples(label.start_secs), to_samples(label.end_secs)))
+            .collect();
+        let fade = to_samples(REGION_FADE_SECS).max(1);
+        (!ranges.is_empty()).then_some(Self { ranges, fade, position: 0 })
+    }
+
+    // Share of the correction at `position`, ramping up and down inside each region
+    fn weight(&self, position: usize) -> f32 {
+        self.ranges
+            .iter()
+            .filter(|&&(start, end)| (start..end).contains(&position))
+            .map(|&(start, end)| {
+                (position - start).min(end - 1 - position) as f32 / self.fade as f32
+            })
+            .fold(0.0, f32::max)
+            .min(1.0)
+    }
+
+    // Mix the next `wet` output samples with the input they were made from
+    fn mix(&mut self, wet: &[f32], dry: &mut VecDeque<f32>, mixed: &mut Vec<f32>) {
+        mixed.clear();
+        for &wet in wet {
+            let dry = dry.pop_front().unwrap_or(0.0);
+            mixed.push(dry + (wet - dry) * self.weight(self.position));
+            self.position += 1;
+        }
+    }
+}
+
+// Decoded input, resampled to the processing rate on the way in
+struct Source {
+    reader: MonoReader,
+    resampler: Option<Resampler>,
+    file_rate: u32,
+    rate: u32,
+    block: Vec<f32>,
+    // Resampled input not handed to the engine yet
+    pending: Vec<f32>,
+    decoded: usize,
+    delivered: usize,
+    finished: bool,
+}
+
+impl Source {
+    fn open(input: &Path, rate: u32) -> Result<Self, String> {
+        let reader = MonoReader::open(input)?;
+        let file_rate = reader.info().sample_rate;
+        Ok(Self {
+            reader,
+            resampler: (rate != file_rate).then(|| Resampler::new(file_rate, rate)),
+            file_rate,
+            rate,
+            block: Vec::new(),
+            pending: Vec::new(),
+            decoded: 0,
+            delivered: 0,
+            finished: false,
+        })
+    }
+
+    // Length at the processing rate, 0 when the file doesn't say
+    fn expected_len(&self) -> usize {
+        self.reader
+            .info()
+            .frames
+            .map_or(0, |frames| resample::resampled_len(frames as usize, self.file_rate, self.rate))
+    }
+
+    fn read(&mut self, buffer: &mut Vec<f32>, max: usize) -> Result<usize, String> {
+        let Some(resampler) = &mut self.resampler else {
+            let read = self.reader.read(buffer, max)?;
+            self.decoded += read;
+            self.delivered += read;
+            self.finished |= read == 0;
+            return Ok(read);
+        };
+
+        while self.pending.is_empty() && !self.finished {
+            self.block.clear();
+            let read = self.reader.read(&mut self.block, max)?;
+            self.decoded += read;
+            if read == 0 {
+                let len = resample::resampled_len(self.decoded, self.file_rate, self.rate);
+                resampler.finish(&mut self.pending, len);
+                self.finished = true;
+            } else {
+                resampler.process(&self.block, &mut self.pending);
+            }
+        }
+        let take = max.min(self.pending.len());
+        buffer.extend(self.pending.drain(..take));
+        self.delivered += take;
+        Ok(take)
+    }
+}
+
+// Finished output, resampled to the output rate and spooled to the scratch file, cut off
+// after `limit` frames when there is one
+struct Sink {
+    resampler: Option<Resampler>,
+    block: Vec<f32>,
+    scratch: ScratchWriter,
+    limit: Option<usize>,
+}
+
+impl Sink {
+    fn create(
+        output: &Path,
+        rate: u32,
+        output_rate: u32,
+        limit: Option<usize>,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            resampler: (rate != output_rate).then(|| Resampler::new(rate, output_rate)),
+            block: Vec::new(),
+            scratch: ScratchWriter::create(output, output_rate, 1)?,
+            limit,
+        })
+    }
+
+    fn push(&mut self, samples: &[f32]) -> Result<(), String> {
+        let samples = match &mut self.resampler {
+            Some(resampler) => {
+                self.block.clear();
+                resampler.process(samples, &mut self.block);
+                &self.block[..]
+            }
+            None => samples,
+        };
+        let room =
+            self.limit.map_or(samples.len(), |limit| limit.saturating_sub(self.scratch.frames()));
+        self.scratch.push(&samples[..samples.len().min(room)])
+    }
+
+    // Write what is still missing of `len` frames: the rest of the resampled output, then
+    // silence for frames the processing didn't cover
+    fn finish(
+        mut self,
+        len: usize,
+        failed_frames: Vec<(usize, String)>,
+    ) -> Result<Rendered, String> {
+        let len = self.limit.map_or(len, |limit| len.min(limit));
+        if let Some(resampler) = &mut self.resampler {
+            self.block.clear();
+            resampler.finish(&mut self.block, len);
+            let room = len.saturating_sub(self.scratch.frames());
+            self.scratch.push(&self.block[..self.block.len().min(room)])?;
+        }
+        let missing = len.saturating_sub(self.scratch.frames());
+        if missing > 0 {
+            self.scratch.push(&vec![0.0; missing])?;
+        }
+        self.scratch.finish(failed_frames)
+    }
+}
+
+/// Spools rendered mono or interleaved stereo audio to a 32-bit float WAV, measuring it on
+/// the way, so the final file can be normalized and converted without holding the render in
+/// memory.
+pub struct ScratchWriter {
+    // Declared first so the file is closed before `rendered` deletes it on an error
+    writer: WavWriter<BufWriter<File>>,
+    loudness: LoudnessMeter,
+    // Bytes of audio the file may take before the scratch space is full, None without a cap
+    room: Option<u64>,
+    rendered: Rendered,
+}
+
+impl ScratchWriter {
+    /// Create the scratch file for a render to `output` in the scratch directory. Each
+    /// render gets its own, so a kept partial render survives the next render to the same
+    /// output.
+    pub fn create(output: &Path, sample_rate: u32, channels: u16) -> Result<Self, String> {
+        let name = output.file_name().unwrap_or_default().to_string_lossy();
+        let path = scratch::file(&format!("{}.render.tmp", name))?;
+
+        let spec = WavSpec {
+            channels,
+            sample_rate,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let writer = paths::create_wav(&path, spec)
+            .map_err(|e| format!("Failed to create scratch file {}: {}", path.display(), e))?;
+        let rendered = Rendered {
+            path,
+            channels,
+            frames: 0,
+            peak: 0.0,
+            non_finite: 0,
+            loudness: None,
+            input_loudness: None,
+            failed_frames: Vec::new(),
+            pitch: None,
+            mono: None,
+            stems: Vec::new(),
+        };
+        let loudness = LoudnessMeter::new(sample_rate, channels);
+        Ok(Self { writer, loudness, room: scratch::room(), rendered })
+    }
+
+    /// Frames written so far.
+    pub fn frames(&self) -> usize {
+        self.rendered.frames
+    }
+
+    pub fn push(&mut self, samples: &[f32]) -> Result<(), String> {
+        let rendered = &mut self.rendered;
+        if let Some(room) = self.room {
+            let bytes = (rendered.frames * rendered.channels as usize + samples.len()) * 4;
+            if bytes as u64 > room {
+                let settings = scratch::current();
+                return Err(scratch::full(&settings.dir(), settings.cap_mib.unwrap_or(0) << 20));
+            }
+        }
+        rendered.non_finite += verify::count_non_finite(samples);
+        rendered.peak = samples.iter().fold(rendered.peak, |peak, s| peak.max(s.abs()));
+        self.loudness.push(samples);
+        for &sample in samples {
+            self.writer
+                .write_sample(sample)
+                .map_err(|e| format!("Failed to write scratch file: {}", e))?;
+        }
+        rendered.frames += samples.len() / rendered.channels as usize;
+        Ok(())
+    }
+
+    pub fn finish(self, failed_frames: Vec<(usize, String)>) -> Result<Rendered, String> {
+        let Self { writer, loudness, mut rendered, .. } = self;
+        writer.finalize().map_err(|e| format!("Failed to write scratch file: {}", e))?;
+        rendered.loudness = loudness.finish();
+        rendered.failed_frames = failed_frames;
+        Ok(rendered)
+    }
+}
+
+/// A render waiting in its scratch file, which is deleted when this is dropped.
+#[derive(Debug)]
+pub struct Rendered {
+    path: PathBuf,
+    // 1, or 2 for a routed stereo render
+    channels: u16,
+    // Frames at the output rate
+    pub frames: usize,
+    peak: f32,
+    non_finite: usize,
+    // Integrated loudness in LUFS, None for a render too short or quiet to measure
+    pub loudness: Option<f32>,
+    // The same of the input's mono mix, set by `render`
+    pub input_loudness: Option<f32>,
+    // (sample position at the processing rate, error) in signal order
+    pub failed_frames: Vec<(usize, String)>,
+    // Set by `render`
+    pub pitch: Option<PitchCurves>,
+    // How the render holds up in mono, set by `render` for a stereo one
+    pub mono: Option<MonoCheck>,
+    // Layers spooled alongside, when asked for
+    pub stems: Vec<(Stem, Rendered)>,
+}
+
+impl Rendered {
+    /// Gain [`write`](Self::write) applies for `normalization` to a file with `channels`
+    /// channels, `limited` when its limiter holds the peaks. Its stems count towards the
+    /// peak too and get the same gain as without a limiter, so they keep their balance.
+    pub fn normalization_gain(
+        &self,
+        normalization: Normalization,
+        channels: u16,
+        limited: bool,
+    ) -> Option<f32> {
+        let peak = self.stems.iter().fold(self.peak, |peak, (_, stem)| peak.max(stem.peak));
+        // A stereo render is measured with both of its channels already
+        let copies = if self.channels > 1 { 1 } else { channels };
+        normalization.gain(peak, self.loudness, copies, limited)
+    }
+
+    /// Write the render to `output` in format `spec`, normalized, through `limit` when
+    /// there is one, dithered down to 16 or 24-bit, and with mono copied to every channel (a
+    /// stereo render to a mono file is downmixed), then re-read the file to verify it.
+    pub fn write(
+        &self,
+        output: &Path,
+        spec: WavSpec,
+        normalization: Normalization,
+        limit: Option<&Limit>,
+    ) -> Result<Verification, String> {
+        let gain = self.normalization_gain(normalization, spec.channels, limit.is_some());
+        self.write_with_gain(output, spec, gain.unwrap_or(1.0), limit)
+    }
+
+    /// Write the stems next to `output` like [`write`](Self::write) writes the render, and
+    /// return where they went.
+    pub fn write_stems(
+        &self,
+        output: &Path,
+        spec: WavSpec,
+        normalization: Normalization,
+    ) -> Result<Vec<PathBuf>, String> {
+        let gain = self.normalization_gain(normalization, spec.channels, false).unwrap_or(1.0);
+        let mut written = Vec::new();
+        for (stem, rendered) in &self.stems {
+            let path = stem_path(output, *stem);
+            let errors = rendered.write_with_gain(&path, spec, gain, None)?.errors();
+            if !errors.is_empty() {
+                return Err(format!("{}: {}", path.display(), errors.join("; ")));
+            }
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /// Write the render to [`master_path`] as 32-bit float at `spec`'s rate and channel
+    /// count, neither normalized nor dithered, so everything above full scale is kept for
+    /// mixing, and return where it went.
+    pub fn write_master(&self, output: &Path, spec: WavSpec) -> Result<PathBuf, String> {
+        let path = master_path(output);
+        let errors =
+            self.write_with_gain(&path, BitDepth::Float32.apply(spec), 1.0, None)?.errors();
+        if !errors.is_empty() {
+            return Err(format!("{}: {}", path.display(), errors.join("; ")));
+        }
+        Ok(path)
+    }
+
+    fn write_with_gain(
+        &self,
+        output: &Path,
+        spec: WavSpec,
+        gain: f32,
+        limit: Option<&Limit>,
+    ) -> Result<Verification, String> {
+        let mut reader = paths::open_wav(&self.path)
+            .map_err(|e| format!("Failed to re-open scratch file: {}", e))?;
+        let writer = paths::create_wav(output, spec)
+            .map_err(|e| format!("Failed to create output file: {}", e))?;
+
+        pcm::write_mono_file(writer, |writer| {
+            let mut samples = reader.samples::<f32>();
+            let mut block = Vec::with_capacity(WRITE_BLOCK);
+            let mut dither = pcm::Dither::default();
+            let mut limiter = limit.map(|limit| Limiter::new(limit, spec.sample_rate as f32));
+            loop {
+                block.clear();
+                for sample in samples.by_ref().take(WRITE_BLOCK) {
+                    block.push(sample? * gain);
+                }
+                if block.is_empty() {
+                    return Ok(());
+                }
+                if let Some(limiter) = &mut limiter {
+                    limiter.process_frames(&mut block, self.channels as usize);
+                }
+                if self.channels == 1 {
+                    pcm::write_mono(writer, &block, Some(&mut dither))?;
+                } else if self.channels == spec.channels {
+                    pcm::write_interleaved(writer, &block, Some(&mut dither))?;
+                } else {
+                    let channels = self.channels as usize;
+                    let mono: Vec<f32> = block
+                        .chunks(channels)
+                        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                        .collect();
+                    pcm::write_mono(writer, &mono, Some(&mut dither))?;
+                }
+            }
+        })
+        .map_err(|e| {
+            format!("Failed to write output file (audio written before the error was kept): {}", e)
+        })?;
+
+        verify::verify_output(output, self.frames, self.non_finite)
+    }
+
+    /// The render with the long-term tone of `reference`, `amount` (0.0 to 1.0) of the way,
+    /// run through the matching filter into a new scratch file for `output`. A silent
+    /// render is returned as it is. Both channels of a stereo render are measured together
+    /// and filtered alike.
+    pub fn match_tint(
+        mut self,
+        output: &Path,
+        reference: &Spectrum,
+        amount: f32,
+    ) -> Result<Self, String> {
+        let sample_rate = self.sample_rate()?;
+        let channels = self.channels as usize;
+        let mut meter = SpectrumMeter::new(sample_rate);
+        let mut mono = Vec::with_capacity(WRITE_BLOCK);
+        self.for_each_block(|block| {
+            mono.clear();
+            mono.extend(
+                block.chunks(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32),
+            );
+            meter.push(&mono);
+            Ok(())
+        })?;
+        let Some(spectrum) = meter.finish() else {
+            return Ok(self);
+        };
+
+        let gains = spectrum.matching_gains(reference, amount);
+        let mut filters: Vec<_> =
+            (0..channels).map(|_| TintFilter::new(&gains, sample_rate)).collect();
+        let mut scratch = ScratchWriter::create(output, sample_rate, self.channels)?;
+        let mut channel = Vec::with_capacity(WRITE_BLOCK);
+        let mut filtered = vec![Vec::with_capacity(WRITE_BLOCK); channels];
+        let mut interleaved = Vec::with_capacity(WRITE_BLOCK);
+        let mut filter_block = |block: Option<&[f32]>| {
+            for (index, (filter, filtered)) in filters.iter_mut().zip(&mut filtered).enumerate() {
+                filtered.clear();
+                match block {
+                    Some(block) => {
+                        channel.clear();
+                        channel.extend(block.iter().skip(index).step_by(channels));
+                        filter.process(&channel, filtered);
+                    }
+                    None => filter.finish(filtered),
+                }
+            }
+            interleaved.clear();
+            for frame in 0..filtered[0].len() {
+                interleaved.extend(filtered.iter().map(|filtered| filtered[frame]));
+            }
+            scratch.push(&interleaved)
+        };
+        self.for_each_block(|block| filter_block(Some(block)))?;
+        filter_block(None)?;
+
+        let mut tinted = scratch.finish(std::mem::take(&mut self.failed_frames))?;
+        tinted.pitch = self.pitch.take();
+        tinted.input_loudness = self.input_loudness;
+        tinted.mono = self.mono;
+        // Left as they are, to blend with the matched render as the engineer sees fit
+        tinted.stems = std::mem::take(&mut self.stems);
+        Ok(tinted)
+    }
+
+    // The render with the harmony `voices`, as long as it, added at `level` into a new
+    // scratch file for `output`, which is stereo with each voice at its levels in `routing`
+    // unless that is empty
+    fn mix_voices(
+        mut self,
+        output: &Path,
+        voices: &[(Interval, Rendered)],
+        level: f32,
+        routing: &[Route],
+    ) -> Result<Self, String> {
+        let mut readers = Vec::new();
+        for (_, voice) in voices {
+            readers.push(
+                paths::open_wav(&voice.path)
+                    .map_err(|e| format!("Failed to re-open scratch file: {}", e))?,
+            );
+        }
+        let mut samples: Vec<_> =
+            readers.iter_mut().map(|reader| reader.samples::<f32>()).collect();
+        let channels = if routing.is_empty() { 1 } else { 2 };
+        let lead = routing::levels(routing, Voice::Lead);
+        let levels: Vec<[f32; 2]> = voices
+            .iter()
+            .map(|(interval, _)| routing::levels(routing, Voice::Harmony(*interval)))
+            .collect();
+
+        let mut scratch = ScratchWriter::create(output, self.sample_rate()?, channels)?;
+        let mut mono = MonoMeter::default();
+        let mut frame = vec![0.0; voices.len()];
+        let mut mixed = Vec::with_capacity(WRITE_BLOCK * channels as usize);
+        self.for_each_block(|block| {
+            mixed.clear();
+            for &sample in block {
+                for (voice, samples) in frame.iter_mut().zip(&mut samples) {
+                    *voice = match samples.next() {
+                        Some(voice) => {
+                            voice.map_err(|e| format!("Failed to read scratch file: {}", e))?
+                        }
+                        None => 0.0,
+                    };
+                }
+                for channel in 0..channels as usize {
+                    let voices: f32 = frame
+                        .iter()
+                        .zip(&levels)
+                        .map(|(voice, levels)| voice * levels[channel])
+                        .sum();
+                    mixed.push(lead[channel] * sample + level * voices);
+                }
+                if channels == 2 {
+                    mono.push([mixed[mixed.len() - 2], mixed[mixed.len() - 1]]);
+                }
+            }
+            scratch.push(&mixed)
+        })?;
+
+        let mut with_voices = scratch.finish(std::mem::take(&mut self.failed_frames))?;
+        with_voices.pitch = self.pitch.take();
+        with_voices.input_loudness = self.input_loudness;
+        with_voices.mono = mono.finish();
+        with_voices.stems = std::mem::take(&mut self.stems);
+        Ok(with_voices)
+    }
+
+    fn sample_rate(&self) -> Result<u32, String> {
+        let reader = paths::open_wav(&self.path)
+            .map_err(|e| format!("Failed to re-open scratch file: {}", e))?;
+        Ok(reader.spec().sample_rate)
+    }
+
+    // Read the scratch file back in blocks of up to WRITE_BLOCK samples
+    fn for_each_block(
+        &self,
+        mut f: impl FnMut(&[f32]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        let mut reader = paths::open_wav(&self.path)
+            .map_err(|e| format!("Failed to re-open scratch file: {}", e))?;
+        let mut samples = reader.samples::<f32>();
+        let mut block = Vec::with_capacity(WRITE_BLOCK);
+        loop {
+            block.clear();
+            for sample in samples.by_ref().take(WRITE_BLOCK) {
+                block.push(sample.map_err(|e| format!("Failed to read scratch file: {}", e))?);
+            }
+            if block.is_empty() {
+                return Ok(());
+            }
+            f(&block)?;
+        }
+    }
+}
+
+impl Drop for Rendered {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(paths::normalize_for_io(&self.path));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classifier::PitchFrame;
+
+    #[test]
+    fn test_scratch_render_is_normalized_and_cleaned_up() {
+        let output = std::env::temp_dir().join("pipeline_scratch_test.wav");
+        let mut scratch = ScratchWriter::create(&output, 8000, 1).unwrap();
+        scratch.push(&[0.5, 2.0]).unwrap();
+        scratch.push(&[-1.0, 0.0]).unwrap();
+        let mut rendered = scratch.finish(Vec::new()).unwrap();
+        let scratch_path = rendered.path.clone();
+        assert!(scratch_path.exists());
+        assert!(scratch_path.starts_with(scratch::current().dir()));
+        assert_eq!(rendered.frames, 4);
+        assert_eq!(rendered.normalization_gain(Normalization::Clip, 2, false), Some(0.475));
+        assert_eq!(rendered.normalization_gain(Normalization::None, 2, false), None);
+        // The limiter takes the peak down instead of the whole render
+        assert_eq!(rendered.normalization_gain(Normalization::Clip, 2, true), None);
+
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 8000,
+            bits_per_sample: 24,
+            sample_format: SampleFormat::Int,
+        };
+        let verification = rendered.write(&output, spec, Normalization::Clip, None).unwrap();
+        assert!(verification.errors().is_empty());
+        assert_eq!(verification.frames, 4);
+        assert!((verification.sample_peak - 0.95).abs() < 1e-4);
+        let limit = Limit { ceiling_db: -6.0, meters: None };
+        let verification =
+            rendered.write(&output, spec, Normalization::Clip, Some(&limit)).unwrap();
+        assert!(verification.errors().is_empty());
+        assert!(verification.sample_peak <= 10f32.powf(-6.0 / 20.0) + 1e-4);
+
+        // Stems get the same gain as the render, so they keep their balance
+        let mut stem = ScratchWriter::create(&output, 8000, 1).unwrap();
+        stem.push(&[0.0, 0.0, 3.8, 0.0]).unwrap();
+        rendered.stems.push((Stem::Dry, stem.finish(Vec::new()).unwrap()));
+        assert_eq!(rendered.normalization_gain(Normalization::Clip, 2, false), Some(0.25));
+        let written = rendered.write_stems(&output, spec, Normalization::Clip).unwrap();
+        assert_eq!(written, [output.with_file_name("pipeline_scratch_test_dry.wav")]);
+        let _ = fs::remove_file(&written[0]);
+
+        drop(rendered);
+        assert!(!scratch_path.exists());
+        let _ = fs::remove_file(&output);
+    }
+
+    #[test]
+    fn test_regions_mix_in_the_input() {
+        let region = |start_secs, end_secs| Label { start_secs, end_secs, text: String::new() };
+        assert!(Regions::new(&[], 1000).is_none());
+        assert!(Regions::new(&[region(0.5, 0.5)], 1000).is_none());
+
+        // One region from 20 to 60 ms at 1kHz, so 10-sample fades
+        let mut regions = Regions::new(&[region(0.02, 0.06)], 1000).unwrap();
+        let mut dry: VecDeque<f32> = vec![0.0; 100].into();
+        let mut mixed = Vec::new();
+        // Mixed in two blocks, the position carries over
+        regions.mix(&[1.0; 30], &mut dry, &mut mixed);
+        let mut output = mixed.clone();
+        regions.mix(&[1.0; 70], &mut dry, &mut mixed);
+        output.extend(&mixed);
+
+        assert!(output[..=20].iter().all(|&s| s == 0.0));
+        assert_eq!(output[25], 0.5);
+        assert!(output[30..=49].iter().all(|&s| s == 1.0));
+        assert_eq!(output[55], 0.4);
+        assert!(output[60..].iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_samples_processed_in_memory() {
+        let samples: Vec<f32> = (0..8000).map(|i| 0.5 * (i as f32 * 0.1).sin()).collect();
+        let region = Label { start_secs: 0.5, end_secs: 0.8, text: String::new() };
+        let mut settings = RenderSettings {
+            config: AutotuneConfig { fft_size: 1024, hop_size: 256, ..Default::default() },
+            window: Window::Hann,
+            padding: Padding::Zero,
+            pitch_algorithm: PitchAlgorithm::Fft,
+            f0_range: None,
+            musical: MusicalSettings { key: 0, note: 0, octave: 2, formant: 0 },
+            scale: None,
+            key_changes: Vec::new(),
+            sections: Vec::new(),
+            melody: None,
+            processing_rate: 8000,
+            output_rate: None,
+            length: OutputLength::Input,
+            threads: 1,
+            regions: vec![region],
+            correct_fry: false,
+            confidence_strength: false,
+            unvoiced_sensitivity: DEFAULT_UNVOICED_SENSITIVITY,
+            a4: STANDARD_A4,
+            energy: None,
+            chain: ChainOrder::default(),
+            high_pass_hz: None,
+            gate: None,
+            eq: None,
+            compressor: None,
+            meters: None,
+            stems: false,
+            harmony: Vec::new(),
+            harmony_level: 0.0,
+            routing: Vec::new(),
+        };
+
+        let processed =
+            process_samples(&samples, &settings, |_| ControlFlow::Continue(())).unwrap();
+        assert_eq!(processed.samples.len(), samples.len());
+        // Outside the region the input passes through
+        assert_eq!(processed.samples[..3900], samples[..3900]);
+
+        let mut reported = 0.0;
+        let stopped = process_samples(&samples, &settings, |done| {
+            reported = done;
+            ControlFlow::Break(())
+        })
+        .unwrap();
+        assert!(stopped.samples.len() < samples.len());
+        assert!(reported > 0.0 && reported < 1.0);
+
+        // Choosing the note from another detector still only corrects the region
+        for pitch_algorithm in [PitchAlgorithm::Yin, PitchAlgorithm::Pyin] {
+            settings.pitch_algorithm = pitch_algorithm;
+            let processed =
+                process_samples(&samples, &settings, |_| ControlFlow::Continue(())).unwrap();
+            assert_eq!(processed.samples.len(), samples.len());
+            assert_eq!(processed.samples[..3900], samples[..3900]);
+        }
+        settings.pitch_algorithm = PitchAlgorithm::Fft;
+        settings.f0_range = Some(F0Range::PRESETS[1].1);
+        let processed =
+            process_samples(&samples, &settings, |_| ControlFlow::Continue(())).unwrap();
+        assert_eq!(processed.samples[..3900], samples[..3900]);
+        settings.f0_range = None;
+
+        // Effects before the correction clean up what passes through too, effects after it
+        // see the passed through input: here a gate no sample gets above, muting all of it
+        let gate = Gate { threshold_db: -3.0, ..Gate::default() };
+        let gated = RenderSettings { gate: Some(gate), ..settings };
+        let processed = process_samples(&samples, &gated, |_| ControlFlow::Continue(())).unwrap();
+        assert!(processed.samples[..3900].iter().all(|&sample| sample == 0.0));
+        let chain = ChainOrder::new(&[
+            Stage::HighPass,
+            Stage::Autotune,
+            Stage::Gate,
+            Stage::Eq,
+            Stage::Compressor,
+        ])
+        .unwrap();
+        let gated_after = RenderSettings { chain, ..gated };
+        let processed =
+            process_samples(&samples, &gated_after, |_| ControlFlow::Continue(())).unwrap();
+        assert!(processed.samples.iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn test_fry_fades_to_the_input() {
+        // Separate clicks with no pitch between them
+        let mut frame_in = vec![0.0; 1024];
+        frame_in[100] = 0.9;
+        frame_in[950] = -0.8;
+        let mut fry = 0.0;
+        let mut mixes = Vec::new();
+        for _ in 0..4 {
+            let mut frame_out = vec![0.0; 1024];
+            pass_fry(&frame_in, &mut frame_out, &mut fry, 44100.0);
+            mixes.push(frame_out[100] / frame_in[100]);
+        }
+        for (mix, expected) in mixes.iter().zip([0.34, 0.68, 1.0, 1.0]) {
+            assert!((mix - expected).abs() < 1e-6, "{:?}", mixes);
+        }
+
+        // And back once it's sung again
+        let sung: Vec<f32> = (0..1024).map(|i| 0.5 * (i as f32 * 0.05).sin()).collect();
+        let mut frame_out = vec![0.0; 1024];
+        pass_fry(&sung, &mut frame_out, &mut fry, 44100.0);
+        assert!((fry - 0.66).abs() < 1e-6);
+
+        // Without an input to pass, as for a harmony voice, it fades to silence
+        let mut unvoiced = 0.0;
+        let mut frame_out = vec![1.0; 4];
+        fade_to_dry(None, &mut frame_out, &mut unvoiced, true, UNVOICED_FADE_STEP);
+        assert_eq!(frame_out, [0.5; 4]);
+    }
+
+    #[test]
+    fn test_correction_follows_confidence() {
+        let rate = 44100.0;
+        let sung: Vec<f32> = (0..1024).map(|i| 0.5 * (i as f32 * 0.05).sin()).collect();
+        // Uniform noise from a fixed seed
+        let mut seed = 1u32;
+        let noise: Vec<f32> = (0..1024)
+            .map(|_| {
+                seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+                0.3 * (seed >> 8) as f32 / (1 << 24) as f32 - 0.15
+            })
+            .collect();
+
+        let mut amount = 1.0;
+        assert!((confidence_amount(&sung, &mut amount, rate) - 1.0).abs() < 1e-4);
+        // Noise fades the correction out over a few frames, and a note brings it back
+        let fading: Vec<f32> =
+            (0..4).map(|_| confidence_amount(&noise, &mut amount, rate)).collect();
+        assert!(fading.windows(2).all(|pair| pair[1] < pair[0]), "{:?}", fading);
+        assert!(fading[3] < 0.1);
+        confidence_amount(&sung, &mut amount, rate);
+        assert!(confidence_amount(&sung, &mut amount, rate) > 0.7);
+
+        // What is kept is a share of the shift in pitch: a note the library pulled up 60
+        // cents onto A4 comes out 30 cents flat at half the correction
+        let sine = |hz: f32| -> Vec<f32> {
+            let step = std::f32::consts::TAU * hz / rate;
+            (0..2048).map(|i| 0.5 * (step * i as f32).sin()).collect()
+        };
+        let cents_off_a4 = |frame: &[f32]| {
+            100.0 * (classifier::note_number(classifier::frame_pitch(frame, rate).unwrap()) - 69.0)
+        };
+        let ratio = 2f32.powf(60.0 / 1200.0);
+        let flat = sine(440.0 / ratio);
+        let mut scratch = vec![0.0; 2048];
+        let mut frame_out = sine(440.0);
+        scale_pitch_correction(&flat, &mut frame_out, &mut scratch, ratio, 0.5);
+        assert!((cents_off_a4(&frame_out) + 30.0).abs() < 3.0, "{}", cents_off_a4(&frame_out));
+        // Full correction, and frames the library passed through, are left alone
+        let mut frame_out = sine(440.0);
+        scale_pitch_correction(&flat, &mut frame_out, &mut scratch, ratio, 1.0);
+        assert_eq!(frame_out, sine(440.0));
+        let mut passed = flat.clone();
+        scale_pitch_correction(&flat, &mut passed, &mut scratch, ratio, 0.5);
+        assert_eq!(passed, flat);
+    }
+
+    #[test]
+    fn test_energy_scales_the_correction_in_pitch() {
+        let rate = 44100.0;
+        let curve = EnergyCurve::default();
+        // A note the library pulled up 60 cents onto A4, sung out and as a quiet tail
+        let ratio = 2f32.powf(60.0 / 1200.0);
+        let sine = |hz: f32, level: f32| -> Vec<f32> {
+            let step = std::f32::consts::TAU * hz / rate;
+            (0..2048).map(|i| level * (step * i as f32).sin()).collect()
+        };
+        let cents_off_a4 = |level: f32| {
+            let frame_in = sine(440.0 / ratio, level);
+            let mut frame_out = sine(440.0, level);
+            let amount = curve.amount(EnergyFollower::new(0.01).push(&frame_in));
+            scale_pitch_correction(&frame_in, &mut frame_out, &mut vec![0.0; 2048], ratio, amount);
+            // Brought up to where the detector hears it
+            let heard: Vec<f32> = frame_out.iter().map(|sample| sample / level).collect();
+            let pitch = classifier::frame_pitch(&heard, rate).unwrap();
+            100.0 * (classifier::note_number(pitch) - 69.0)
+        };
+        // Loud lines get all of it, quiet ones only the curve's 0.3 of the 60 cents
+        assert!(cents_off_a4(0.5).abs() < 2.0, "{}", cents_off_a4(0.5));
+        assert!((cents_off_a4(0.01) + 42.0).abs() < 3.0, "{}", cents_off_a4(0.01));
+    }
+
+    #[test]
+    fn test_tuned_rate_hears_the_reference_as_standard() {
+        let rate = 44100.0;
+        let orchestra_a: Vec<f32> = (0..2048)
+            .map(|i| 0.5 * (std::f32::consts::TAU * 442.0 * i as f32 / rate).sin())
+            .collect();
+        // 8 cents sharp of A4 as is, on it at the rate the library is told
+        let heard =
+            |rate| classifier::note_number(classifier::frame_pitch(&orchestra_a, rate).unwrap());
+        assert!((heard(rate) - 69.08).abs() < 0.03);
+        assert!((heard(tuned_rate(rate, 442.0)) - 69.0).abs() < 0.03);
+        assert_eq!(tuned_rate(rate, STANDARD_A4), rate);
+    }
+
+    #[test]
+    fn test_problem_markers() {
+        let settings = RenderSettings {
+            config: AutotuneConfig { fft_size: 100, hop_size: 25, ..Default::default() },
+            window: Window::Hann,
+            padding: Padding::Zero,
+            pitch_algorithm: PitchAlgorithm::Fft,
+            f0_range: None,
+            musical: MusicalSettings { key: 0, note: 0, octave: 2, formant: 0 },
+            scale: None,
+            key_changes: Vec::new(),
+            sections: Vec::new(),
+            melody: None,
+            processing_rate: 1000,
+            output_rate: None,
+            length: OutputLength::Input,
+            threads: 1,
+            regions: Vec::new(),
+            correct_fry: false,
+            confidence_strength: false,
+            unvoiced_sensitivity: DEFAULT_UNVOICED_SENSITIVITY,
+            a4: STANDARD_A4,
+            energy: None,
+            chain: ChainOrder::default(),
+            high_pass_hz: None,
+            gate: None,
+            eq: None,
+            compressor: None,
+            meters: None,
+            stems: false,
+            harmony: Vec::new(),
+            harmony_level: 0.0,
+            routing: Vec::new(),
+        };
+        let failed = |pos: usize| (pos, "NaN".to_string());
+        let markers =
+            problem_markers(&[failed(0), failed(25), failed(500)], &settings, &["loud".into()]);
+        let spans: Vec<(f32, f32, &str)> = markers
+            .iter()
+            .map(|marker| (marker.start_secs, marker.end_secs, marker.text.as_str()))
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0.0, 0.0, "Warning: loud"),
+                (0.0, 0.125, "Fallback, 2 frames: NaN"),
+                (0.5, 0.6, "Fallback: NaN"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pitch_stats() {
+        let frame = |rms, f0| PitchFrame { rms, harmonicity: 0.9, f0 };
+        let track = |frames| PitchTrack { frames, hop_secs: 0.5, offset_secs: 0.0 };
+        let curves = PitchCurves {
+            detected: track(vec![
+                frame(0.5, Some(440.0)),
+                frame(0.5, Some(440.0)),
+                frame(0.5, None),
+                frame(0.0, None),
+            ]),
+            // The second frame pulled up a semitone
+            corrected: track(vec![
+                frame(0.5, Some(440.0)),
+                frame(0.5, Some(440.0 * 2f32.powf(1.0 / 12.0))),
+                frame(0.5, None),
+                frame(0.0, None),
+            ]),
+        };
+        let stats = curves.stats();
+        assert_eq!(stats.voiced_secs, 1.0);
+        assert_eq!(stats.corrected_ratio, 0.5);
+        assert!((stats.mean_shift_cents - 50.0).abs() < 0.1);
+        // Only the audible frame without a pitch
+        assert_eq!(stats.undetected_secs, 0.5);
+        assert!(stats.summary().contains("no pitch found for 0.5s"));
+    }
+
+    #[test]
+    fn test_renders_come_out_at_their_length() {
+        assert_eq!("input".parse(), Ok(OutputLength::Input));
+        assert_eq!(" 48000".parse(), Ok(OutputLength::Frames(48000)));
+        assert!("-1".parse::<OutputLength>().is_err());
+        assert_eq!(OutputLength::Frames(10).to_string(), "10");
+        assert_eq!(OutputLength::Input.frames(123), 123);
+        assert_eq!(OutputLength::Frames(10).frames(123), 10);
+
+        let output = std::env::temp_dir().join("pipeline_length_test.wav");
+        // Output short of the input's length is padded with silence
+        let mut sink = Sink::create(&output, 8000, 8000, None).unwrap();
+        sink.push(&[0.5; 90]).unwrap();
+        assert_eq!(sink.finish(100, Vec::new()).unwrap().frames, 100);
+        // And cut off at an asked for length, resampled or not
+        for output_rate in [8000, 16000] {
+            let mut sink = Sink::create(&output, 8000, output_rate, Some(50)).unwrap();
+            sink.push(&[0.5; 90]).unwrap();
+            sink.push(&[0.5; 90]).unwrap();
+            assert_eq!(sink.finish(360, Vec::new()).unwrap().frames, 50);
+        }
+    }
+}
@@ -0,0 +1,385 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::classifier;
+
+// Frames are decimated to about 11kHz before YIN runs on them, like the classifier's
+const ANALYSIS_RATE: f32 = 11025.0;
+// Lower than the classifier's, for the low male voices YIN is picked for
+const MIN_F0: f32 = 50.0;
+const MAX_F0: f32 = 1000.0;
+/// Frequencies a detection range can reach, in Hz.
+pub const F0_LIMITS: RangeInclusive<f32> = 20.0..=2000.0;
+const SILENCE_THRESHOLD: f32 = 0.01; // ~-40 dBFS RMS
+// Dip of the normalized difference YIN takes for the period
+const YIN_THRESHOLD: f32 = 0.15;
+// pYIN tries this many thresholds, from 0.01 to 1.0, weighted by a beta distribution with
+// these parameters (mean 0.1)
+const PYIN_THRESHOLDS: usize = 100;
+const PYIN_BETA: (i32, i32) = (2, 18);
+// Probability of a frame being voiced, out of its candidates, below which it has no pitch
+const PYIN_VOICED: f32 = 0.3;
+// Jump in semitones from the last pitch that halves a candidate's chances, so the track
+// doesn't flip between octaves
+const PYIN_JUMP_SEMITONES: f32 = 3.0;
+
+/// The frequencies a pitch is looked for in, in Hz, so a source that never goes below
+/// or above them can't be taken an octave off.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct F0Range {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl F0Range {
+    /// Ranges of common sources, with some room around the notes they reach.
+    pub const PRESETS: [(&'static str, F0Range); 5] = [
+        ("Bass", F0Range { min: 70.0, max: 350.0 }),
+        ("Tenor", F0Range { min: 110.0, max: 550.0 }),
+        ("Alto", F0Range { min: 160.0, max: 750.0 }),
+        ("Soprano", F0Range { min: 240.0, max: 1100.0 }),
+        ("Bass Guitar", F0Range { min: 38.0, max: 420.0 }),
+    ];
+
+    /// The range from `min` to `max`, with the detectors' own bounds for those not given.
+    pub fn new(min: Option<f32>, max: Option<f32>) -> Self {
+        Self { min: min.unwrap_or(MIN_F0), max: max.unwrap_or(MAX_F0) }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !F0_LIMITS.contains(&self.min) || !F0_LIMITS.contains(&self.max) {
+            Err(format!(
+                "Pitch range must be between {} and {} Hz",
+                F0_LIMITS.start(),
+                F0_LIMITS.end()
+            ))
+        } else if self.min >= self.max {
+            Err("Lowest pitch must be below the highest".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The name of the preset this is, if it is one.
+    pub fn preset(&self) -> Option<&'static str> {
+        Self::PRESETS.iter().find(|(_, range)| range == self).map(|(name, _)| *name)
+    }
+}
+
+/// How the pitch of each frame is found, which the correction picks the note from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PitchAlgorithm {
+    // The library's own FFT peak picking, which chooses the note in the key itself
+    #[default]
+    Fft,
+    // The YIN difference function, far less prone to octave errors on low voices
+    Yin,
+    // Probabilistic YIN: candidates from many thresholds, followed from frame to frame
+    Pyin,
+}
+
+impl PitchAlgorithm {
+    pub const ALL: [PitchAlgorithm; 3] =
+        [PitchAlgorithm::Fft, PitchAlgorithm::Yin, PitchAlgorithm::Pyin];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PitchAlgorithm::Fft => "fft",
+            PitchAlgorithm::Yin => "yin",
+            PitchAlgorithm::Pyin => "pyin",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PitchAlgorithm::Fft => "FFT peak",
+            PitchAlgorithm::Yin => "YIN",
+            PitchAlgorithm::Pyin => "pYIN",
+        }
+    }
+
+    /// Whether the note is chosen from this detector's pitch rather than by the library.
+    pub fn picks_note(self) -> bool {
+        self != PitchAlgorithm::Fft
+    }
+
+    /// A detector for frames at `sample_rate`, looking in `range` when there is one. The
+    /// library finds the pitch itself with `Fft`, so where a note is chosen outside of it
+    /// anyway, for a scale, a harmony voice, or a range, the classifier's autocorrelation
+    /// does it as before.
+    pub fn detector(self, sample_rate: f32, range: Option<F0Range>) -> Box<dyn PitchDetector> {
+        match self {
+            PitchAlgorithm::Fft => Box::new(Autocorrelation { sample_rate, range }),
+            PitchAlgorithm::Yin => Box::new(Yin { sample_rate, range: range.unwrap_or_default() }),
+            PitchAlgorithm::Pyin => Box::new(Pyin::new(sample_rate, range.unwrap_or_default())),
+        }
+    }
+}
+
+impl Default for F0Range {
+    fn default() -> Self {
+        Self::new(None, None)
+    }
+}
+
+impl FromStr for PitchAlgorithm {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        PitchAlgorithm::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == text.trim().to_ascii_lowercase())
+            .ok_or_else(|| {
+                format!("Unknown pitch algorithm '{}': expected fft, yin, or pyin", text.trim())
+            })
+    }
+}
+
+impl fmt::Display for PitchAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Finds the pitch of one frame after another, carrying what it learned from the frames
+/// before. None for silent and unpitched frames.
+pub trait PitchDetector {
+    fn detect(&mut self, frame: &[f32]) -> Option<f32>;
+}
+
+/// The classifier's normalized autocorrelation.
+pub struct Autocorrelation {
+    sample_rate: f32,
+    // The classifier's own range when None
+    range: Option<F0Range>,
+}
+
+impl PitchDetector for Autocorrelation {
+    fn detect(&mut self, frame: &[f32]) -> Option<f32> {
+        match self.range {
+            Some(range) => {
+                classifier::frame_pitch_in(frame, self.sample_rate, range.min, range.max)
+            }
+            None => classifier::frame_pitch(frame, self.sample_rate),
+        }
+    }
+}
+
+/// YIN (de Cheveigné and Kawahara, 2002): the first period whose normalized difference
+/// dips below a fixed threshold.
+pub struct Yin {
+    sample_rate: f32,
+    range: F0Range,
+}
+
+impl PitchDetector for Yin {
+    fn detect(&mut self, frame: &[f32]) -> Option<f32> {
+        let (difference, rate, min_lag) =
+            normalized_difference(frame, self.sample_rate, self.range)?;
+        let lag = first_dip(&difference, min_lag, YIN_THRESHOLD)?;
+        Some(rate / interpolated(&difference, lag))
+    }
+}
+
+/// pYIN (Mauch and Dixon, 2014): every threshold of a distribution votes for the dip it
+/// finds, and the candidates are weighed against the last pitch, so a frame's octave error
+/// loses to the note held around it. Followed greedily frame by frame instead of decoded
+/// over the whole file, so it can stream.
+pub struct Pyin {
+    sample_rate: f32,
+    range: F0Range,
+    // (threshold, its share of the votes)
+    thresholds: Vec<(f32, f32)>,
+    last: Option<f32>,
+}
+
+impl Pyin {
+    pub fn new(sample_rate: f32, range: F0Range) -> Self {
+        let (a, b) = PYIN_BETA;
+        // The beta density up to its normalization, which the sum below takes care of
+        let density = |x: f32| x.powi(a - 1) * (1.0 - x).powi(b - 1);
+        let thresholds: Vec<f32> =
+            (1..=PYIN_THRESHOLDS).map(|i| i as f32 / PYIN_THRESHOLDS as f32).collect();
+        let total: f32 = thresholds.iter().map(|&x| density(x)).sum();
+        let thresholds = thresholds.into_iter().map(|x| (x, density(x) / total)).collect();
+        Self { sample_rate, range, thresholds, last: None }
+    }
+}
+
+impl PitchDetector for Pyin {
+    fn detect(&mut self, frame: &[f32]) -> Option<f32> {
+        let (difference, rate, min_lag) =
+            normalized_difference(frame, self.sample_rate, self.range)?;
+        // (lag, probability) of each dip some threshold found
+        let mut candidates: Vec<(usize, f32)> = Vec::new();
+        for &(threshold, weight) in &self.thresholds {
+            let Some(lag) = first_dip(&difference, min_lag, threshold) else {
+                continue;
+            };
+            match candidates.iter_mut().find(|(candidate, _)| *candidate == lag) {
+                Some((_, probability)) => *probability += weight,
+                None => candidates.push((lag, weight)),
+            }
+        }
+        let voiced: f32 = candidates.iter().map(|(_, probability)| probability).sum();
+        if voiced < PYIN_VOICED {
+            return None;
+        }
+
+        let last = self.last;
+        let score = |&(lag, probability): &(usize, f32)| {
+            let f0 = rate / interpolated(&difference, lag);
+            let jump = last.map_or(0.0, |last| (12.0 * (f0 / last).log2()).abs());
+            (f0, probability * 0.5f32.powf(jump / PYIN_JUMP_SEMITONES))
+        };
+        let (f0, _) = candidates.iter().map(score).max_by(|a, b| a.1.total_cmp(&b.1))?;
+        self.last = Some(f0);
+        Some(f0)
+    }
+}
+
+// The cumulative mean normalized difference of `frame` at `sample_rate` decimated to the
+// analysis rate, by lag up to the period of the lowest pitch of `range`, with the rate and
+// the shortest lag a pitch is looked for at. None for silent frames and frames too short
+// for any pitch in the range
+fn normalized_difference(
+    frame: &[f32],
+    sample_rate: f32,
+    range: F0Range,
+) -> Option<(Vec<f32>, f32, usize)> {
+    if sample_rate <= 0.0 || range.min <= 0.0 {
+        return None;
+    }
+    let decimation = ((sample_rate / ANALYSIS_RATE).round() as usize).max(1);
+    let rate = sample_rate / decimation as f32;
+    let decimated: Vec<f32> = frame
+        .chunks(decimation)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect();
+    let rms =
+        (decimated.iter().map(|&x| x * x).sum::<f32>() / decimated.len().max(1) as f32).sqrt();
+    // Half the frame is compared against the half a lag further on
+    let window = decimated.len() / 2;
+    let min_lag = (rate / range.max).floor().max(2.0) as usize;
+    let max_lag = ((rate / range.min).ceil() as usize).min(window);
+    if rms < SILENCE_THRESHOLD || max_lag <= min_lag {
+        return None;
+    }
+
+    let mut difference = vec![1.0; max_lag + 1];
+    let mut running = 0.0;
+    for lag in 1..=max_lag {
+        let squared: f32 = (0..window)
+            .map(|i| {
+                let delta = decimated[i] - decimated[i + lag];
+                delta * delta
+            })
+            .sum();
+        running += squared;
+        difference[lag] = if running > 0.0 {
+            squared * lag as f32 / running
+        } else {
+            1.0
+        };
+    }
+    Some((difference, rate, min_lag))
+}
+
+// The first lag from `min_lag` on whose difference dips below `threshold`, at the bottom of
+// that dip
+fn first_dip(difference: &[f32], min_lag: usize, threshold: f32) -> Option<usize> {
+    let mut lag = (min_lag..difference.len()).find(|&lag| difference[lag] < threshold)?;
+    while lag + 1 < difference.len() && difference[lag + 1] < difference[lag] {
+        lag += 1;
+    }
+    Some(lag)
+}
+
+// `lag` moved to the bottom of the parabola through it and its neighbours
+fn interpolated(difference: &[f32], lag: usize) -> f32 {
+    if lag == 0 || lag + 1 >= difference.len() {
+        return lag as f32;
+    }
+    let (a, b, c) = (difference[lag - 1], difference[lag], difference[lag + 1]);
+    let denom = a - 2.0 * b + c;
+    if denom.abs() > f32::EPSILON {
+        lag as f32 + (0.5 * (a - c) / denom).clamp(-0.5, 0.5)
+    } else {
+        lag as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detectors_find_a_low_voice() {
+        let rate = 44100.0;
+        // A low E with a louder second harmonic than its fundamental, an octave trap
+        let tone = |frequency: f32, len: usize| -> Vec<f32> {
+            (0..len)
+                .map(|i| {
+                    let t = i as f32 / rate;
+                    0.3 * (std::f32::consts::TAU * frequency * t).sin()
+                        + 0.5 * (std::f32::consts::TAU * 2.0 * frequency * t).sin()
+                })
+                .collect()
+        };
+        let low_e = tone(82.4, 2048);
+        for algorithm in [PitchAlgorithm::Yin, PitchAlgorithm::Pyin] {
+            let mut detector = algorithm.detector(rate, None);
+            let f0 = detector.detect(&low_e).unwrap();
+            assert!((f0 - 82.4).abs() < 1.0, "{} found {}", algorithm, f0);
+            assert_eq!(detector.detect(&[0.0; 2048]), None);
+            // Half of the frame has to hold a whole period
+            assert_eq!(detector.detect(&tone(82.4, 256)), None);
+        }
+
+        // pYIN keeps following a held note from the pitch before
+        let mut pyin = Pyin::new(rate, F0Range::default());
+        let a3 = tone(220.0, 2048);
+        for _ in 0..3 {
+            assert!((pyin.detect(&a3).unwrap() - 220.0).abs() < 2.0);
+        }
+
+        assert_eq!("pYIN".parse(), Ok(PitchAlgorithm::Pyin));
+        assert!("crepe".parse::<PitchAlgorithm>().is_err());
+        assert!(!PitchAlgorithm::Fft.picks_note());
+    }
+
+    #[test]
+    fn test_ranges_bound_the_pitch_found() {
+        let rate = 44100.0;
+        let tone = |frequency: f32| -> Vec<f32> {
+            (0..4096).map(|i| (std::f32::consts::TAU * frequency * i as f32 / rate).sin()).collect()
+        };
+        // A low G on a bass, under the classifier's own range
+        let low_g = tone(49.0);
+        let bass_guitar = F0Range::PRESETS[4].1;
+        assert_ne!(
+            PitchAlgorithm::Fft.detector(rate, None).detect(&low_g).map(f32::round),
+            Some(49.0)
+        );
+        for algorithm in PitchAlgorithm::ALL {
+            let f0 = algorithm.detector(rate, Some(bass_guitar)).detect(&low_g).unwrap();
+            assert!((f0 - 49.0).abs() < 1.0, "{} found {}", algorithm, f0);
+        }
+
+        // A bass voice can't be taken for an A4, only for one under it
+        let a4 = tone(440.0);
+        let bass = F0Range::PRESETS[0].1;
+        let f0 = PitchAlgorithm::Yin.detector(rate, Some(bass)).detect(&a4).unwrap();
+        assert!((f0 - 220.0).abs() < 2.0, "found {}", f0);
+
+        assert_eq!(bass.preset(), Some("Bass"));
+        assert_eq!(F0Range::new(Some(80.0), None).preset(), None);
+        assert_eq!(F0Range::default(), F0Range { min: MIN_F0, max: MAX_F0 });
+        assert!(F0Range::default().validate().is_ok());
+        assert!(F0Range::new(Some(500.0), Some(400.0)).validate().is_err());
+        assert!(F0Range::new(Some(10.0), None).validate().is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use eframe::egui;
+
+use crate::classifier::{PitchFrame, PitchTrack, note_number};
+use crate::format;
+use crate::pipeline::PitchCurves;
+use crate::theme::PlotColors;
+use crate::ui;
+
+const HEIGHT: f32 = 160.0;
+// Semitones shown above and below the pitches in the curves
+const MARGIN_SEMITONES: f32 = 2.0;
+// Narrower ranges are widened to this, so a steady note isn't blown up to fill the plot
+const MIN_SPAN_SEMITONES: f32 = 12.0;
+
+// "A4 +12 ct", or why there is no pitch
+fn describe(frame: Option<&PitchFrame>) -> String {
+    match frame {
+        Some(PitchFrame { f0: Some(f0), .. }) => {
+            let note = note_number(*f0);
+            let nearest = note.round();
+            format!(
+                "{}{} {:+.0} ct",
+                ui::get_note_name(nearest as i32 % 12),
+                nearest as i32 / 12 - 1,
+                (note - nearest) * 100.0
+            )
+        }
+        Some(frame) if !frame.is_silent() => "no pitch found".to_string(),
+        _ => "silent".to_string(),
+    }
+}
+
+/// Plot the detected input pitch and the corrected output pitch on a note grid, with the
+/// stretches where no pitch was found marked along the bottom. Returns the time clicked,
+/// if any.
+pub fn show(ui: &mut egui::Ui, curves: &PitchCurves, colors: &PlotColors) -> Option<f32> {
+    let detected_color = colors.input;
+    let corrected_color = colors.output;
+    let warn_color = colors.warning;
+    ui.horizontal(|ui| {
+        ui.colored_label(detected_color, "━ Detected");
+        ui.colored_label(corrected_color, "━ Corrected");
+        ui.colored_label(warn_color, "▮ No pitch found");
+    });
+
+    let tracks = [&curves.detected, &curves.corrected];
+    let duration = tracks
+        .iter()
+        .map(|track| track.offset_secs + track.frames.len() as f32 * track.hop_secs)
+        .fold(0.0f32, f32::max);
+    let notes =
+        tracks.iter().flat_map(|track| &track.frames).filter_map(|frame| frame.f0.map(note_number));
+    let (low, high) =
+        notes.fold((f32::MAX, f32::MIN), |(low, high), note| (low.min(note), high.max(note)));
+
+    let width = ui.available_width();
+    let (area, response) = ui.allocate_exact_size(egui::vec2(width, HEIGHT), egui::Sense::click());
+    let painter = ui.painter_at(area);
+    painter.rect_filled(area, 2.0, ui.visuals().extreme_bg_color);
+    if duration <= 0.0 || low > high {
+        painter.text(
+            area.center(),
+            egui::Align2::CENTER_CENTER,
+            "No pitch detected",
+            egui::FontId::proportional(12.0),
+            ui.visuals().weak_text_color(),
+        );
+        return None;
+    }
+
+    let middle = (low + high) / 2.0;
+    let half_span = ((high - low) / 2.0 + MARGIN_SEMITONES).max(MIN_SPAN_SEMITONES / 2.0);
+    let (low, high) = (middle - half_span, middle + half_span);
+    let x = |secs: f32| area.left() + secs / duration * area.width();
+    let y = |note: f32| area.bottom() - (note - low) / (high - low) * area.height();
+
+    // A line per semitone, stronger and labelled for each C
+    let grid = ui.visuals().widgets.noninteractive.bg_stroke;
+    for note in low.ceil() as i32..=high.floor() as i32 {
+        let octave_line = note.rem_euclid(12) == 0;
+        let color = if octave_line {
+            grid.color
+        } else {
+            grid.color.gamma_multiply(0.4)
+        };
+        painter.hline(area.x_range(), y(note as f32), egui::Stroke::new(grid.width, color));
+        if octave_line {
+            painter.text(
+                egui::pos2(area.left() + 2.0, y(note as f32)),
+                egui::Align2::LEFT_BOTTOM,
+                format!("C{}", note / 12 - 1),
+                egui::FontId::proportional(10.0),
+                ui.visuals().weak_text_color(),
+            );
+        }
+    }
+
+    let frame_secs =
+        |track: &PitchTrack, index: usize| track.offset_secs + index as f32 * track.hop_secs;
+    let detected = &curves.detected;
+    for (index, frame) in detected.frames.iter().enumerate() {
+        if frame.f0.is_none() && !frame.is_silent() {
+            let secs = frame_secs(detected, index);
+            let (left, right) =
+                (x(secs - detected.hop_secs / 2.0), x(secs + detected.hop_secs / 2.0));
+            let marker = egui::Rect::from_min_max(
+                egui::pos2(left, area.bottom() - 4.0),
+                egui::pos2(right.max(left + 1.0), area.bottom()),
+            );
+            painter.rect_filled(marker, 0.0, warn_color);
+        }
+    }
+
+    // Lines between neighbouring frames with a pitch, so gaps stay visible
+    for (track, stroke) in [
+        (detected, egui::Stroke::new(1.0, detected_color)),
+        (&curves.corrected, egui::Stroke::new(1.5, corrected_color)),
+    ] {
+        for (index, pair) in track.frames.windows(2).enumerate() {
+            if let (Some(a), Some(b)) = (pair[0].f0, pair[1].f0) {
+                let from = egui::pos2(x(frame_secs(track, index)), y(note_number(a)));
+                let to = egui::pos2(x(frame_secs(track, index + 1)), y(note_number(b)));
+                painter.line_segment([from, to], stroke);
+            }
+        }
+    }
+
+    let pointer_secs = |pos: egui::Pos2| (pos.x - area.left()) / area.width() * duration;
+    let clicked = response.interact_pointer_pos().filter(|_| response.clicked()).map(pointer_secs);
+    if let Some(pos) = response.hover_pos() {
+        let secs = pointer_secs(pos);
+        response.on_hover_text(format!(
+            "{}\nDetected: {}\nCorrected: {}",
+            format::duration(secs),
+            describe(curves.detected.at(secs)),
+            describe(curves.corrected.at(secs))
+        ));
+    }
+    clicked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_descriptions() {
+        let frame = |rms, f0| PitchFrame { rms, harmonicity: 0.9, f0 };
+        assert_eq!(describe(Some(&frame(0.5, Some(440.0)))), "A4 +0 ct");
+        assert_eq!(describe(Some(&frame(0.5, Some(440.0 * 2f32.powf(0.2 / 12.0))))), "A4 +20 ct");
+        assert_eq!(describe(Some(&frame(0.5, None))), "no pitch found");
+        assert_eq!(describe(Some(&frame(0.0, None))), "silent");
+        assert_eq!(describe(None), "silent");
+    }
+}
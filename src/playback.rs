@@ -0,0 +1,186 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering};
+
+use crate::{decoder, monitor};
+
+/// Which render the player is playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Original,
+    Processed,
+}
+
+/// Original and processed audio of one job, both mono at the same sample rate.
+pub struct Tracks {
+    pub original: Vec<f32>,
+    pub processed: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+impl Tracks {
+    pub fn load(original: &Path, processed: &Path) -> Result<Self, String> {
+        let (original, info) = decoder::read_mono(original, usize::MAX)?;
+        let (processed, processed_info) = decoder::read_mono(processed, usize::MAX)?;
+        if processed_info.sample_rate != info.sample_rate {
+            return Err(format!(
+                "Sample rates differ ({}Hz vs {}Hz), can't compare",
+                info.sample_rate, processed_info.sample_rate
+            ));
+        }
+        Ok(Self { original, processed, sample_rate: info.sample_rate })
+    }
+}
+
+// State shared with the audio callback
+struct PlayerState {
+    tracks: Tracks,
+    playing: AtomicBool,
+    source: AtomicU8,
+    // Position in source samples, as f64 bits so it can be fractional
+    position: AtomicU64,
+}
+
+impl PlayerState {
+    fn position(&self) -> f64 {
+        f64::from_bits(self.position.load(Ordering::Relaxed))
+    }
+
+    fn track(&self) -> &[f32] {
+        match self.source.load(Ordering::Relaxed) {
+            0 => &self.tracks.original,
+            _ => &self.tracks.processed,
+        }
+    }
+
+    /// Fill `buffer` from the current track, `step` source samples per output sample.
+    fn render(&self, buffer: &mut [f32], step: f64) {
+        if !self.playing.load(Ordering::Relaxed) {
+            buffer.fill(0.0);
+            return;
+        }
+
+        let track = self.track();
+        let mut position = self.position();
+        for sample in buffer.iter_mut() {
+            let index = position as usize;
+            *sample = match (track.get(index), track.get(index + 1)) {
+                (Some(&a), Some(&b)) => a + (b - a) * (position - index as f64) as f32,
+                (Some(&a), None) => a,
+                _ => 0.0,
+            };
+            position += step;
+        }
+
+        if position >= track.len() as f64 {
+            self.playing.store(false, Ordering::Relaxed);
+            position = track.len() as f64;
+        }
+        self.position.store(position.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Plays back a job's original and processed audio with a shared position, so switching
+/// between them (A/B) continues at the same point in the song.
+pub struct Player {
+    _stream: cpal::Stream,
+    state: Arc<PlayerState>,
+}
+
+impl Player {
+    pub fn open(tracks: Tracks) -> Result<Self, String> {
+        let sample_rate = tracks.sample_rate;
+        let state = Arc::new(PlayerState {
+            tracks,
+            playing: AtomicBool::new(false),
+            source: AtomicU8::new(Source::Processed as u8),
+            position: AtomicU64::new(0.0f64.to_bits()),
+        });
+
+        // The device rate is only known once the stream is open
+        let step = Arc::new(AtomicU64::new(1.0f64.to_bits()));
+        let (callback_state, callback_step) = (state.clone(), step.clone());
+        let (stream, device_rate) = monitor::open_output(None, move |buffer| {
+            callback_state.render(buffer, f64::from_bits(callback_step.load(Ordering::Relaxed)));
+        })?;
+        step.store((sample_rate as f64 / device_rate as f64).to_bits(), Ordering::Relaxed);
+
+        Ok(Self { _stream: stream, state })
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state.playing.load(Ordering::Relaxed)
+    }
+
+    pub fn toggle_playing(&self) {
+        // Playing again from the end starts over
+        if !self.is_playing() && self.position_secs() >= self.duration_secs() {
+            self.seek(0.0);
+        }
+        self.state.playing.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    pub fn source(&self) -> Source {
+        match self.state.source.load(Ordering::Relaxed) {
+            0 => Source::Original,
+            _ => Source::Processed,
+        }
+    }
+
+    pub fn set_source(&self, source: Source) {
+        self.state.source.store(source as u8, Ordering::Relaxed);
+    }
+
+    pub fn position_secs(&self) -> f32 {
+        (self.state.position() / self.state.tracks.sample_rate as f64) as f32
+    }
+
+    pub fn seek(&self, secs: f32) {
+        let position = (secs.max(0.0) as f64 * self.state.tracks.sample_rate as f64)
+            .min(self.state.track().len() as f64);
+        self.state.position.store(position.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Length of the currently selected track.
+    pub fn duration_secs(&self) -> f32 {
+        self.state.track().len() as f32 / self.state.tracks.sample_rate as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(original: Vec<f32>, processed: Vec<f32>) -> PlayerState {
+        PlayerState {
+            tracks: Tracks { original, processed, sample_rate: 4 },
+            playing: AtomicBool::new(true),
+            source: AtomicU8::new(Source::Processed as u8),
+            position: AtomicU64::new(0.0f64.to_bits()),
+        }
+    }
+
+    #[test]
+    fn test_switching_source_keeps_position() {
+        let state = state(vec![1.0; 8], vec![2.0; 8]);
+        let mut buffer = [0.0; 3];
+        state.render(&mut buffer, 1.0);
+        assert_eq!(buffer, [2.0; 3]);
+
+        state.source.store(Source::Original as u8, Ordering::Relaxed);
+        state.render(&mut buffer, 1.0);
+        assert_eq!(buffer, [1.0; 3]);
+        assert_eq!(state.position(), 6.0);
+    }
+
+    #[test]
+    fn test_render_stops_at_end() {
+        let state = state(vec![], vec![0.0, 1.0, 2.0]);
+        let mut buffer = [9.0; 8];
+        state.render(&mut buffer, 0.5);
+        assert_eq!(&buffer[..5], &[0.0, 0.5, 1.0, 1.5, 2.0]);
+        assert_eq!(&buffer[6..], &[0.0, 0.0]);
+        assert!(!state.playing.load(Ordering::Relaxed));
+        assert_eq!(state.position(), 3.0);
+    }
+}
@@ -0,0 +1,175 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use std::ops::RangeInclusive;
+
+use crate::effects::Effect;
+
+/// Cutoffs the high-pass can be set to: above the rumble of stands and air conditioning,
+/// below the lowest notes of most voices.
+pub const HIGH_PASS_RANGE: RangeInclusive<f32> = 20.0..=300.0;
+pub const DEFAULT_HIGH_PASS_HZ: f32 = 80.0;
+
+// How quickly the gate's level detector falls after a peak, slow enough to carry it over
+// the cycles of a low note
+const DETECTOR_RELEASE_MS: f32 = 20.0;
+
+/// A noise gate: while the input stays below `threshold_db` it is muted, so the hiss and
+/// room noise between phrases isn't taken for pitch. The gate opens over `attack_ms` once
+/// the input rises above the threshold and closes over `release_ms` after it falls below.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Gate {
+    pub threshold_db: f32,
+    pub attack_ms: f32,
+    pub release_ms: f32,
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        Self { threshold_db: -50.0, attack_ms: 2.0, release_ms: 80.0 }
+    }
+}
+
+impl Gate {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(-96.0..=0.0).contains(&self.threshold_db) {
+            return Err("Gate threshold must be between -96 and 0 dBFS".to_string());
+        }
+        if !(0.1..=100.0).contains(&self.attack_ms) {
+            return Err("Gate attack must be between 0.1 and 100 ms".to_string());
+        }
+        if !(5.0..=2000.0).contains(&self.release_ms) {
+            return Err("Gate release must be between 5 and 2000 ms".to_string());
+        }
+        Ok(())
+    }
+}
+
+pub fn validate_high_pass(hz: f32) -> Result<(), String> {
+    if !HIGH_PASS_RANGE.contains(&hz) {
+        return Err(format!(
+            "High-pass cutoff must be between {} and {} Hz",
+            HIGH_PASS_RANGE.start(),
+            HIGH_PASS_RANGE.end()
+        ));
+    }
+    Ok(())
+}
+
+/// A second-order Butterworth high-pass that takes the rumble below its cutoff out, 12 dB
+/// per octave.
+#[derive(Debug, Clone)]
+pub struct HighPass {
+    b: [f32; 3],
+    a: [f32; 2],
+    state: [f32; 2],
+}
+
+impl HighPass {
+    pub fn new(cutoff_hz: f32, sample_rate: f32) -> Self {
+        let k = (PI * cutoff_hz.min(sample_rate * 0.45) / sample_rate).tan();
+        let a0 = 1.0 + k / FRAC_1_SQRT_2 + k * k;
+        Self {
+            b: [1.0 / a0, -2.0 / a0, 1.0 / a0],
+            a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / FRAC_1_SQRT_2 + k * k) / a0],
+            state: [0.0; 2],
+        }
+    }
+}
+
+impl Effect for HighPass {
+    fn process(&mut self, samples: &mut [f32]) {
+        for x in samples {
+            let y = self.b[0] * *x + self.state[0];
+            self.state[0] = self.b[1] * *x - self.a[0] * y + self.state[1];
+            self.state[1] = self.b[2] * *x - self.a[1] * y;
+            *x = y;
+        }
+    }
+}
+
+/// A [`Gate`] running over audio at one sample rate.
+#[derive(Debug, Clone)]
+pub struct NoiseGate {
+    threshold: f32,
+    // Share of the way the gain moves toward open or closed each sample
+    attack: f32,
+    release: f32,
+    // Factor the detected level falls by each sample
+    detector_release: f32,
+    level: f32,
+    gain: f32,
+}
+
+impl NoiseGate {
+    pub fn new(gate: Gate, sample_rate: f32) -> Self {
+        let step = |ms: f32| 1.0 - (-1000.0 / (ms * sample_rate)).exp();
+        Self {
+            threshold: 10f32.powf(gate.threshold_db / 20.0),
+            attack: step(gate.attack_ms),
+            release: step(gate.release_ms),
+            detector_release: 1.0 - step(DETECTOR_RELEASE_MS),
+            level: 0.0,
+            gain: 0.0,
+        }
+    }
+}
+
+impl Effect for NoiseGate {
+    fn process(&mut self, samples: &mut [f32]) {
+        for sample in samples {
+            self.level = sample.abs().max(self.level * self.detector_release);
+            if self.level >= self.threshold {
+                self.gain += (1.0 - self.gain) * self.attack;
+            } else {
+                self.gain -= self.gain * self.release;
+            }
+            *sample *= self.gain;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rumble_and_hiss_are_removed() {
+        let rate = 48000.0;
+        let tone = |hz: f32, level: f32, i: usize| level * (2.0 * PI * hz * i as f32 / rate).sin();
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+
+        // Share of the level of a sine left after the high-pass: 20 Hz rumble is taken down
+        // by more than 20 dB, a 440 Hz note is kept
+        let high_passed = |hz: f32| {
+            let mut samples: Vec<f32> = (0..48000).map(|i| tone(hz, 0.5, i)).collect();
+            HighPass::new(DEFAULT_HIGH_PASS_HZ, rate).process(&mut samples);
+            rms(&samples[4800..]) / (0.5 / 2f32.sqrt())
+        };
+        assert!(high_passed(20.0) < 0.1);
+        assert!(high_passed(440.0) > 0.95);
+
+        // Hiss at -60 dBFS after a sung note: the note passes, the hiss is muted once the
+        // gate has let go of it
+        let mut gated: Vec<f32> = (0..48000)
+            .map(|i| {
+                if i < 24000 {
+                    tone(220.0, 0.3, i)
+                } else {
+                    0.001 * (-1f32).powi(i as i32)
+                }
+            })
+            .collect();
+        let mut gate = NoiseGate::new(Gate::default(), rate);
+        // Handed over in blocks like the stream, so state carries across them
+        for block in gated.chunks_mut(1000) {
+            gate.process(block);
+        }
+        assert!(rms(&gated[12000..24000]) > 0.2);
+        assert!(rms(&gated[40000..]) < 1e-4);
+
+        assert!(Gate { release_ms: 1.0, ..Gate::default() }.validate().is_err());
+        assert!(validate_high_pass(1000.0).is_err());
+    }
+}
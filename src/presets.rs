@@ -0,0 +1,98 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::app_config::ConfigLocation;
+
+const PRESETS_FILE: &str = "presets.json";
+
+/// A named set of correction settings. Presets describe how a voice is corrected, not what
+/// it is corrected to, so they leave the key and scale alone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub strength: f32,
+    pub transition: f32,
+    pub formant: i32,
+}
+
+impl Preset {
+    pub fn new(name: &str, strength: f32, transition: f32, formant: i32) -> Self {
+        Self { name: name.to_string(), strength, transition, formant }
+    }
+
+    /// Whether `name` refers to this preset, ignoring case, spaces, and punctuation, so
+    /// "hard-tune" finds "Hard-Tune".
+    pub fn is_named(&self, name: &str) -> bool {
+        let key = |name: &str| -> String {
+            name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+        };
+        key(&self.name) == key(name)
+    }
+}
+
+/// The presets that ship with the app.
+pub fn factory_presets() -> Vec<Preset> {
+    vec![
+        Preset::new("Subtle", 0.4, 0.1, 0),
+        // Instant, complete snapping to the note, the T-Pain effect
+        Preset::new("Hard-Tune", 1.0, 1.0, 0),
+        Preset::new("Natural Vocal", 0.65, 0.15, 0),
+        Preset::new("Spoken Word", 0.3, 0.3, 0),
+    ]
+}
+
+/// Presets saved by the user, an empty list until the first one is saved.
+pub fn load_user_presets(config: &ConfigLocation) -> Vec<Preset> {
+    config.load(PRESETS_FILE).unwrap_or_default()
+}
+
+pub fn save_user_presets(config: &ConfigLocation, presets: &[Preset]) -> io::Result<()> {
+    config.save(PRESETS_FILE, &presets)
+}
+
+/// The preset called `name`. User presets shadow factory presets of the same name.
+// Only the CLI looks presets up by name, the GUI lists them all
+#[allow(dead_code)]
+pub fn find(user: &[Preset], name: &str) -> Option<Preset> {
+    user.iter()
+        .find(|preset| preset.is_named(name))
+        .cloned()
+        .or_else(|| factory_presets().into_iter().find(|preset| preset.is_named(name)))
+}
+
+/// Add `preset` to the user presets, replacing the one with the same name.
+pub fn upsert(user: &mut Vec<Preset>, preset: Preset) {
+    match user.iter_mut().find(|existing| existing.is_named(&preset.name)) {
+        Some(existing) => *existing = preset,
+        None => user.push(preset),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preset_lookup() {
+        let hard = find(&[], "hard tune").unwrap();
+        assert_eq!((hard.strength, hard.transition), (1.0, 1.0));
+        assert!(find(&[], "Spoken-Word").is_some());
+        assert!(find(&[], "robot").is_none());
+
+        let mut user = Vec::new();
+        upsert(&mut user, Preset::new("My Lead", 0.9, 0.05, 1));
+        upsert(&mut user, Preset::new("Subtle", 0.5, 0.2, 0));
+        upsert(&mut user, Preset::new("my lead", 0.85, 0.05, 2));
+        assert_eq!(user.len(), 2);
+        assert_eq!(find(&user, "MY LEAD").unwrap().formant, 2);
+        // Saved over a factory preset
+        assert_eq!(find(&user, "subtle").unwrap().strength, 0.5);
+
+        let dir = std::env::temp_dir().join("autotune-presets-test");
+        let config = ConfigLocation { dir: dir.clone(), portable: true };
+        assert!(load_user_presets(&config).is_empty());
+        save_user_presets(&config, &user).unwrap();
+        assert_eq!(load_user_presets(&config), user);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+/// Open a span for the rest of the enclosing block. Compiles to nothing without the
+/// `profiling` feature, and costs one atomic load when no trace is being recorded.
+#[cfg(feature = "profiling")]
+macro_rules! profile_span {
+    ($($args:tt)*) => {
+        let _span = tracing::trace_span!($($args)*).entered();
+    };
+}
+
+#[cfg(not(feature = "profiling"))]
+macro_rules! profile_span {
+    ($($args:tt)*) => {};
+}
+
+pub(crate) use profile_span;
+
+/// Keeps a trace recording; the trace file is flushed and closed when this is dropped.
+pub struct TraceGuard {
+    #[cfg(feature = "profiling")]
+    _flush: tracing_chrome::FlushGuard,
+}
+
+/// Record all spans to a Chrome trace file at `path`, viewable in Perfetto or
+/// chrome://tracing (including as a flame graph).
+#[cfg(feature = "profiling")]
+pub fn start_trace(path: &Path) -> Result<TraceGuard, String> {
+    use tracing_subscriber::prelude::*;
+
+    let file = std::fs::File::create(path)
+        .map_err(|e| format!("Failed to create trace file {}: {}", path.display(), e))?;
+    let (layer, flush) =
+        tracing_chrome::ChromeLayerBuilder::new().writer(file).include_args(true).build();
+    tracing_subscriber::registry()
+        .with(layer)
+        .try_init()
+        .map_err(|e| format!("Failed to start trace recording: {}", e))?;
+
+    Ok(TraceGuard { _flush: flush })
+}
+
+#[cfg(not(feature = "profiling"))]
+pub fn start_trace(_path: &Path) -> Result<TraceGuard, String> {
+    Err("This build has no profiling support (rebuild with the `profiling` feature)".to_string())
+}
@@ -0,0 +1,125 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::format;
+use crate::report;
+
+// Without a terminal to draw the bar on, a line every this many percent
+const LINE_PERCENT: usize = 10;
+// Longest the speed and ETA next to the bar go without being worked out again
+const MESSAGE_INTERVAL: Duration = Duration::from_millis(250);
+
+static SHOWN: AtomicBool = AtomicBool::new(true);
+
+/// Show how far renders have got from here on, or not when stdout is taken by something
+/// else, like piped audio. JSON output reports progress as events instead either way.
+pub fn set_shown(shown: bool) {
+    SHOWN.store(shown, Ordering::Relaxed);
+}
+
+/// How far a render has got on stdout: a bar with the percent, elapsed time, ETA, and speed
+/// on a terminal, and the same as a line every tenth of the way when stdout is a log or a
+/// pipe. The bar is cleared when this is dropped.
+pub struct RenderProgress {
+    bar: Option<ProgressBar>,
+    // Rate of the frames counted, for the speed in realtime
+    sample_rate: f32,
+    start: Instant,
+    message_at: Option<Instant>,
+    line_percent: usize,
+}
+
+impl RenderProgress {
+    /// Progress of a render of frames at `sample_rate` starting now, None when it isn't
+    /// shown.
+    pub fn start(sample_rate: f32) -> Option<Self> {
+        if !SHOWN.load(Ordering::Relaxed) || report::json() {
+            return None;
+        }
+        let bar = io::stdout().is_terminal().then(|| {
+            let bar = ProgressBar::with_draw_target(None, ProgressDrawTarget::stdout());
+            if let Ok(style) = ProgressStyle::with_template("   {bar:30} {percent:>3}% {msg}") {
+                bar.set_style(style.progress_chars("=> "));
+            }
+            bar
+        });
+        Some(Self { bar, sample_rate, start: Instant::now(), message_at: None, line_percent: 0 })
+    }
+
+    pub fn update(&mut self, done: usize, total: usize) {
+        let now = Instant::now();
+        let elapsed = now - self.start;
+        match &self.bar {
+            Some(bar) => {
+                bar.set_length(total as u64);
+                bar.set_position(done as u64);
+                let due = match self.message_at {
+                    Some(at) => now - at >= MESSAGE_INTERVAL,
+                    None => true,
+                };
+                if due {
+                    self.message_at = Some(now);
+                    bar.set_message(status(done, total, elapsed, self.sample_rate));
+                }
+            }
+            None => {
+                let percent = done * 100 / total.max(1);
+                if percent / LINE_PERCENT > self.line_percent / LINE_PERCENT {
+                    self.line_percent = percent;
+                    let status = status(done, total, elapsed, self.sample_rate);
+                    println!("   Progress: {}% | {}", percent, status);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for RenderProgress {
+    fn drop(&mut self) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+// Time taken and left, and how many times realtime the render runs at, once it's under way
+fn status(done: usize, total: usize, elapsed: Duration, sample_rate: f32) -> String {
+    let secs = elapsed.as_secs_f32();
+    if done == 0 || secs <= 0.0 {
+        return format!("{} elapsed", format::duration(secs));
+    }
+    let left = secs * total.saturating_sub(done) as f32 / done as f32;
+    let speed = done as f64 / sample_rate.max(1.0) as f64 / secs as f64;
+    format!(
+        "{} elapsed | ETA {} | {}x realtime",
+        format::duration(secs),
+        format::duration(left),
+        format::number(speed, 1)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_estimates_time_left_and_speed() {
+        // A quarter of 40 seconds of audio in 2 seconds, 5x realtime
+        let quarter = status(480_000, 1_920_000, Duration::from_secs(2), 48000.0);
+        assert_eq!(
+            quarter,
+            format!(
+                "{} elapsed | ETA {} | {}x realtime",
+                format::duration(2.0),
+                format::duration(6.0),
+                format::number(5.0, 1)
+            )
+        );
+        assert_eq!(
+            status(0, 100, Duration::ZERO, 48000.0),
+            format!("{} elapsed", format::duration(0.0))
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::bookmarks::Bookmark;
+use crate::dynamics::Compressor;
+use crate::effects::ChainOrder;
+use crate::energy::EnergyCurve;
+use crate::engine::{self, Padding, Window};
+use crate::equalizer::Equalizer;
+use crate::loudness::Normalization;
+use crate::paths;
+use crate::pcm::BitDepth;
+use crate::pipeline::{self, OutputLength};
+use crate::pitch::{F0Range, PitchAlgorithm};
+use crate::prefilter::Gate;
+use crate::scale::Scale;
+
+pub const EXTENSION: &str = "atproj";
+// Bumped when a change would make older versions misread a project
+const VERSION: u32 = 1;
+
+/// The files and settings of a render, saved as JSON so it can be repeated later with
+/// identical parameters. Relative paths are relative to the project file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectFile {
+    pub version: u32,
+    pub input: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub key: i32,
+    pub note: i32,
+    pub octave: i32,
+    pub formant: i32,
+    // Overrides the key's notes when set
+    pub scale: Option<Scale>,
+    pub strength: f32,
+    pub transition: f32,
+    pub fft_size: usize,
+    pub hop_size: usize,
+    // Window the corrected frames are crossfaded with
+    pub window: Window,
+    // What the frames past the end of the input are filled out with
+    pub padding: Padding,
+    // How the pitch the note is chosen from is found, and the frequencies it is looked for in
+    pub pitch_algorithm: PitchAlgorithm,
+    pub f0_range: Option<F0Range>,
+    // Concert pitch the notes are tuned to, in Hz
+    pub a4: f32,
+    // Correct vocal fry instead of passing it through
+    pub correct_fry: bool,
+    // Keep less of the correction where the pitch is found less surely
+    pub confidence_strength: bool,
+    // How readily unvoiced frames pass through uncorrected
+    pub unvoiced_sensitivity: f32,
+    // Reference vocal whose tone the output is matched to, and how far
+    pub tint: Option<PathBuf>,
+    pub tint_amount: f32,
+    // Rate the output is written at, the input's own when None
+    pub output_rate: Option<u32>,
+    // Frames the output is written with
+    pub length: OutputLength,
+    // Sample format and channel count of the output, the input's when None
+    pub output_depth: Option<BitDepth>,
+    pub output_channels: Option<u16>,
+    // How the level of the output is set, and the ceiling of the limiter when it is on
+    pub normalization: Normalization,
+    pub limit_db: Option<f32>,
+    // Strength follows the level of the voice along this curve when set
+    pub energy: Option<EnergyCurve>,
+    // Order of the effects around the correction, and the settings of those that are on
+    pub chain: ChainOrder,
+    pub high_pass_hz: Option<f32>,
+    pub gate: Option<Gate>,
+    pub eq: Option<Equalizer>,
+    pub compressor: Option<Compressor>,
+    // MIDI melody the notes are corrected to instead of the key's
+    pub midi: Option<PathBuf>,
+    // Spots in the input marked to jump back to
+    pub bookmarks: Vec<Bookmark>,
+}
+
+impl Default for ProjectFile {
+    fn default() -> Self {
+        Self {
+            version: VERSION,
+            input: None,
+            output: None,
+            key: 0,
+            note: 0,
+            octave: 2,
+            formant: 0,
+            scale: None,
+            strength: 0.8,
+            transition: 0.1,
+            fft_size: engine::DEFAULT_FFT_SIZE,
+            hop_size: engine::DEFAULT_HOP_SIZE,
+            window: Window::default(),
+            padding: Padding::default(),
+            pitch_algorithm: PitchAlgorithm::default(),
+            f0_range: None,
+            a4: pipeline::STANDARD_A4,
+            correct_fry: false,
+            confidence_strength: false,
+            unvoiced_sensitivity: pipeline::DEFAULT_UNVOICED_SENSITIVITY,
+            tint: None,
+            tint_amount: 1.0,
+            output_rate: None,
+            length: OutputLength::Input,
+            output_depth: None,
+            output_channels: None,
+            normalization: Normalization::default(),
+            limit_db: None,
+            energy: None,
+            chain: ChainOrder::default(),
+            high_pass_hz: None,
+            gate: None,
+            eq: None,
+            compressor: None,
+            midi: None,
+            bookmarks: Vec::new(),
+        }
+    }
+}
+
+impl ProjectFile {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let json = fs::read_to_string(paths::normalize_for_io(path))
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut project: Self = serde_json::from_str(&json)
+            .map_err(|e| format!("{} isn't a valid project file: {}", path.display(), e))?;
+        if project.version > VERSION {
+            return Err(format!(
+                "{} was saved by a newer version of the app (project version {})",
+                paths::display_name(path),
+                project.version
+            ));
+        }
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for file in [&mut project.input, &mut project.output, &mut project.tint, &mut project.midi]
+            .into_iter()
+            .flatten()
+        {
+            if file.is_relative() {
+                *file = dir.join(&*file);
+            }
+        }
+        project.version = VERSION;
+        Ok(project)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        // Write then rename so a crash never leaves a half-written project behind
+        let io_path = paths::normalize_for_io(path);
+        let temp_path = io_path.with_extension("tmp");
+        fs::write(&temp_path, json)
+            .and_then(|()| fs::rename(&temp_path, &io_path))
+            .map_err(|e| format!("Failed to save {}: {}", path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_project_round_trip() {
+        let dir = std::env::temp_dir().join("autotune-project-test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("take.atproj");
+
+        let project = ProjectFile {
+            input: Some(dir.join("vocal.wav")),
+            output: Some(PathBuf::from("renders/vocal_tuned.wav")),
+            key: 13,
+            formant: -2,
+            scale: Some("9,0,2,3,4,7".parse().unwrap()),
+            strength: 0.65,
+            bookmarks: vec![Bookmark { secs: 754.5, name: "Second verse".to_string() }],
+            ..Default::default()
+        };
+        project.save(&path).unwrap();
+        let loaded = ProjectFile::load(&path).unwrap();
+        assert_eq!(loaded.input, project.input);
+        // Relative to the project file
+        assert_eq!(loaded.output, Some(dir.join("renders/vocal_tuned.wav")));
+        assert_eq!(loaded.scale, project.scale);
+        assert_eq!((loaded.key, loaded.formant, loaded.strength), (13, -2, 0.65));
+        assert_eq!(loaded.bookmarks, project.bookmarks);
+
+        // Missing settings keep their defaults, newer versions are refused
+        fs::write(&path, r#"{"key": 4}"#).unwrap();
+        let loaded = ProjectFile::load(&path).unwrap();
+        assert_eq!((loaded.key, loaded.octave, loaded.hop_size), (4, 2, 256));
+        fs::write(&path, r#"{"version": 99}"#).unwrap();
+        assert!(ProjectFile::load(&path).unwrap_err().contains("newer version"));
+        fs::write(&path, "not json").unwrap();
+        assert!(ProjectFile::load(&path).is_err());
+
+        let _ = fs::remove_dir_all(dir);
+    }
+}
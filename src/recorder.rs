@@ -0,0 +1,223 @@
+use cpal::traits::{DeviceTrait, StreamTrait};
+use cpal::{FromSample, Sample, SampleFormat, SizedSample};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::{devices, paths};
+
+// Channels kept of the input device, enough for a stereo pair
+const MAX_CHANNELS: u16 = 2;
+// Level at which a sample counts as clipped
+const CLIP_LEVEL: f32 = 0.999;
+// How fast the meters fall back after a peak, in dB per second
+const FALL_DB_PER_SEC: f32 = 24.0;
+/// Bottom of the level meters, in dBFS.
+pub const METER_FLOOR_DB: f32 = -60.0;
+/// Peaks above this leave little headroom, in dBFS.
+pub const HOT_DB: f32 = -6.0;
+
+/// Directory that recorded takes are written to before processing.
+pub fn take_dir() -> PathBuf {
+    std::env::temp_dir().join("autotune-recordings")
+}
+
+/// Records the chosen input device into memory until it is finished into a WAV file.
+pub struct Recorder {
+    _stream: cpal::Stream,
+    // Interleaved, `channels` to a frame
+    samples: Arc<Mutex<Vec<f32>>>,
+    // Loudest sample of each channel since the last `peaks`, as f32 bits
+    peaks: Arc<Vec<AtomicU32>>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl Recorder {
+    pub fn start() -> Result<Self, String> {
+        let device = devices::input_device()?;
+        let supported = devices::input_config(&device)?;
+        let sample_format = supported.sample_format();
+        let config: cpal::StreamConfig = supported.into();
+        let channels = config.channels.clamp(1, MAX_CHANNELS);
+
+        let samples = Arc::new(Mutex::new(Vec::new()));
+        let peaks = Arc::new((0..channels).map(|_| AtomicU32::new(0)).collect::<Vec<_>>());
+        let capture = Capture { samples: samples.clone(), peaks: peaks.clone(), channels };
+        let stream = match sample_format {
+            SampleFormat::F32 => build_input::<f32>(&device, &config, capture),
+            SampleFormat::I16 => build_input::<i16>(&device, &config, capture),
+            SampleFormat::U16 => build_input::<u16>(&device, &config, capture),
+            other => Err(format!("Unsupported input sample format: {}", other)),
+        }?;
+        stream.play().map_err(|e| format!("Failed to start recording: {}", e))?;
+
+        Ok(Self { _stream: stream, samples, peaks, sample_rate: config.sample_rate.0, channels })
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        let len = self.samples.lock().map_or(0, |samples| samples.len());
+        len as f32 / self.channels as f32 / self.sample_rate as f32
+    }
+
+    /// Loudest sample of each channel since the last call.
+    pub fn peaks(&self) -> Vec<f32> {
+        self.peaks.iter().map(|peak| f32::from_bits(peak.swap(0, Ordering::Relaxed))).collect()
+    }
+
+    /// Stop recording and write the take to a new WAV file in `dir`.
+    pub fn finish(self, dir: &Path) -> Result<PathBuf, String> {
+        let Self { _stream: stream, samples, sample_rate, channels, .. } = self;
+        drop(stream);
+        let samples = std::mem::take(&mut *samples.lock().map_err(|_| "Recording was lost")?);
+        if samples.is_empty() {
+            return Err("Nothing was recorded".to_string());
+        }
+
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        let stamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        let path = dir.join(format!("take_{}.wav", stamp));
+        write_take(&path, &samples, channels, sample_rate)?;
+        Ok(path)
+    }
+}
+
+// Everything the input callback owns
+struct Capture {
+    samples: Arc<Mutex<Vec<f32>>>,
+    peaks: Arc<Vec<AtomicU32>>,
+    channels: u16,
+}
+
+impl Capture {
+    // Keep the first `channels` of each device frame
+    fn push(&self, frames: impl Iterator<Item = impl Iterator<Item = f32>>) {
+        let Ok(mut samples) = self.samples.lock() else {
+            return;
+        };
+        let mut loudest = vec![0.0f32; self.channels as usize];
+        for frame in frames {
+            for (channel, sample) in frame.take(self.channels as usize).enumerate() {
+                samples.push(sample);
+                loudest[channel] = loudest[channel].max(sample.abs());
+            }
+        }
+        // Positive floats order the same as their bits
+        for (peak, loudest) in self.peaks.iter().zip(loudest) {
+            peak.fetch_max(loudest.to_bits(), Ordering::Relaxed);
+        }
+    }
+}
+
+fn build_input<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    capture: Capture,
+) -> Result<cpal::Stream, String>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    let device_channels = config.channels.max(1) as usize;
+    device
+        .build_input_stream(
+            config,
+            move |data: &[T], _| {
+                capture.push(
+                    data.chunks(device_channels)
+                        .map(|frame| frame.iter().map(|&sample| f32::from_sample(sample))),
+                );
+            },
+            |e| log::warn!("Audio input error: {}", e),
+            None,
+        )
+        .map_err(|e| format!("Failed to open input stream: {}", e))
+}
+
+// 32-bit float, so nothing of the device's resolution is lost before processing
+fn write_take(path: &Path, samples: &[f32], channels: u16, sample_rate: u32) -> Result<(), String> {
+    let spec = hound::WavSpec {
+        channels,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let failed = |e: hound::Error| format!("Failed to write {}: {}", path.display(), e);
+    let mut writer = paths::create_wav(path, spec).map_err(failed)?;
+    for &sample in samples {
+        writer.write_sample(sample).map_err(failed)?;
+    }
+    writer.finalize().map_err(failed)
+}
+
+/// Peak meter for one channel, falling back slowly after each peak so it can be read.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LevelMeter {
+    level: f32,
+    // Stays set once a sample clipped, until the meter is replaced
+    pub clipped: bool,
+}
+
+impl LevelMeter {
+    /// Take in the loudest sample since the last update, `elapsed_secs` ago.
+    pub fn update(&mut self, peak: f32, elapsed_secs: f32) {
+        let fallen = self.level * 10f32.powf(-FALL_DB_PER_SEC * elapsed_secs / 20.0);
+        self.level = fallen.max(peak);
+        self.clipped |= peak >= CLIP_LEVEL;
+    }
+
+    pub fn db(&self) -> f32 {
+        20.0 * self.level.max(1e-6).log10()
+    }
+
+    /// How far up the meter the level is, from 0 at `METER_FLOOR_DB` to 1 at full scale.
+    pub fn fraction(&self) -> f32 {
+        (1.0 - self.db() / METER_FLOOR_DB).clamp(0.0, 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_keeps_two_channels_and_their_peaks() {
+        let peaks = Arc::new((0..2).map(|_| AtomicU32::new(0)).collect());
+        let capture = Capture { samples: Arc::default(), peaks, channels: 2 };
+        let frames = [[0.1, -0.5, 0.9], [-0.3, 0.2, 0.9]];
+        capture.push(frames.iter().map(|frame| frame.iter().copied()));
+        assert_eq!(*capture.samples.lock().unwrap(), vec![0.1, -0.5, -0.3, 0.2]);
+        let peaks: Vec<f32> =
+            capture.peaks.iter().map(|peak| f32::from_bits(peak.load(Ordering::Relaxed))).collect();
+        assert_eq!(peaks, vec![0.3, 0.5]);
+
+        let path = std::env::temp_dir().join(format!("autotune_take_{}.wav", std::process::id()));
+        write_take(&path, &capture.samples.lock().unwrap(), 2, 48000).unwrap();
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        assert_eq!((reader.spec().channels, reader.spec().sample_rate), (2, 48000));
+        let written: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+        assert_eq!(written, vec![0.1, -0.5, -0.3, 0.2]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_meter_holds_peaks_and_falls_back() {
+        let mut meter = LevelMeter::default();
+        assert_eq!(meter.fraction(), 0.0);
+        meter.update(0.5, 0.0);
+        assert!((meter.db() + 6.02).abs() < 0.01);
+        // A quieter block a second later: down 24 dB from the peak, not to the block's level
+        meter.update(0.001, 1.0);
+        assert!((meter.db() + 30.02).abs() < 0.01);
+        assert!((meter.fraction() - 0.5).abs() < 0.01);
+        assert!(!meter.clipped);
+        meter.update(1.0, 0.1);
+        assert!(meter.clipped && meter.fraction() == 1.0);
+        meter.update(0.0, 0.1);
+        assert!(meter.clipped);
+    }
+}
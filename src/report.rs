@@ -0,0 +1,143 @@
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Something a run reports. With JSON output on, each is written to stdout as one line of
+/// JSON with its kind in "event", like {"event":"warning","message":"..."}.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    // The input as it was opened
+    Input {
+        path: PathBuf,
+        sample_rate: u32,
+        channels: u16,
+        bits_per_sample: u16,
+        compressed: bool,
+        duration_secs: Option<f32>,
+    },
+    // Frames of the render done so far, counting every pass
+    Progress {
+        done: usize,
+        total: usize,
+        percent: f32,
+    },
+    Warning {
+        message: String,
+    },
+    // An identical earlier render of the input, and whether it was copied instead of
+    // rendering again
+    Duplicate {
+        render: PathBuf,
+        reused: bool,
+    },
+    // The output once it was written and checked
+    Summary(Summary),
+    // What the run failed with, and the exit code it ends with
+    Error {
+        message: String,
+        code: i32,
+    },
+}
+
+/// The output of a finished run.
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub samples_processed: usize,
+    pub frames: usize,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub peak_dbfs: f32,
+    pub true_peak_dbtp: f32,
+    // Integrated loudness in LUFS, None for audio too short or quiet to measure
+    pub loudness_lufs: Option<f32>,
+    pub input_loudness_lufs: Option<f32>,
+    // How the level was set, the gain that took, and the ceiling of the limiter if it was on
+    pub normalization: String,
+    pub normalization_gain: Option<f32>,
+    pub limit_db: Option<f32>,
+    pub processing_secs: f32,
+    pub realtime_factor: f32,
+    // Side files written next to the output
+    pub stems: Vec<PathBuf>,
+    pub master: Option<PathBuf>,
+}
+
+/// Report events as JSON on stdout instead of as text from here on.
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+}
+
+/// Whether events are reported as JSON, in which case stdout holds nothing else.
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// Write `event` to stdout as a line of JSON, straight away so a wrapper reading the lines
+/// as they come sees it.
+pub fn emit(event: &Event) {
+    let Ok(line) = serde_json::to_string(event) else {
+        return;
+    };
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", line);
+    let _ = stdout.flush();
+}
+
+/// Report `message` as a warning: an event with JSON output, otherwise on stderr.
+pub fn warning(message: impl fmt::Display) {
+    if json() {
+        emit(&Event::Warning { message: message.to_string() });
+    } else {
+        eprintln!("Warning: {}", message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events_are_tagged() {
+        let warning = Event::Warning { message: "clipped".to_string() };
+        assert_eq!(
+            serde_json::to_string(&warning).unwrap(),
+            r#"{"event":"warning","message":"clipped"}"#
+        );
+        let progress = Event::Progress { done: 10, total: 40, percent: 25.0 };
+        assert_eq!(
+            serde_json::to_string(&progress).unwrap(),
+            r#"{"event":"progress","done":10,"total":40,"percent":25.0}"#
+        );
+        let summary = Event::Summary(Summary {
+            input: PathBuf::from("take.wav"),
+            output: PathBuf::from("tuned.wav"),
+            samples_processed: 88200,
+            frames: 44100,
+            sample_rate: 44100,
+            channels: 2,
+            peak_dbfs: -1.0,
+            true_peak_dbtp: -0.8,
+            loudness_lufs: None,
+            input_loudness_lufs: Some(-20.0),
+            normalization: "peak".to_string(),
+            normalization_gain: Some(1.5),
+            limit_db: None,
+            processing_secs: 0.5,
+            realtime_factor: 2.0,
+            stems: Vec::new(),
+            master: None,
+        });
+        let json: serde_json::Value = serde_json::to_value(&summary).unwrap();
+        assert_eq!(json["event"], "summary");
+        assert_eq!(json["samples_processed"], 88200);
+        assert!(json["loudness_lufs"].is_null());
+        assert!(!json.to_string().contains('\n'));
+    }
+}
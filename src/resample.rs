@@ -0,0 +1,192 @@
+use rubato::{FftFixedInOut, Resampler as _};
+use std::ops::RangeInclusive;
+
+/// Sample rates the pitch detector and the 1024-point FFT are tuned for. Files outside this
+/// range get too coarse (low rates) or too short (high rates) an analysis window.
+pub const PREFERRED_RATES: RangeInclusive<u32> = 44_100..=48_000;
+
+/// Rates an output can be written at.
+pub const OUTPUT_RATES: RangeInclusive<u32> = 8_000..=192_000;
+
+// Input frames rubato is asked to take at a time, rounded up to a whole number of periods
+// of the ratio
+const CHUNK_FRAMES: usize = 1024;
+
+/// Rate to process a file at `sample_rate` at, or `None` if it can be processed as is.
+/// Rates of the 48 kHz family stay in it, so common ratios like 96k -> 48k are exact.
+pub fn analysis_rate(sample_rate: u32) -> Option<u32> {
+    if PREFERRED_RATES.contains(&sample_rate) {
+        None
+    } else if sample_rate.is_multiple_of(8_000) {
+        Some(48_000)
+    } else {
+        Some(44_100)
+    }
+}
+
+/// Length of `len` samples at `from` Hz once resampled to `to` Hz.
+pub fn resampled_len(len: usize, from: u32, to: u32) -> usize {
+    (len as f64 * to as f64 / from as f64).round() as usize
+}
+
+/// Band-limited resampling from `from` Hz to `to` Hz with rubato's FFT resampler, producing
+/// exactly `len` samples (zero past the end of the input).
+pub fn resample(samples: &[f32], from: u32, to: u32, len: usize) -> Vec<f32> {
+    let mut output = Vec::with_capacity(len);
+    let mut resampler = Resampler::new(from, to);
+    resampler.process(samples, &mut output);
+    resampler.finish(&mut output, len);
+    output.truncate(len);
+    output
+}
+
+/// Streaming version of [`resample`]: input goes in as blocks of any size and is handed to
+/// rubato a fixed chunk at a time, so the output is identical to resampling the whole
+/// signal at once. Rubato's delay is dropped from the start, keeping the output aligned
+/// with the input.
+pub struct Resampler {
+    // None when the rates match
+    fft: Option<FftFixedInOut<f32>>,
+    // Input not making up a whole chunk yet
+    input: Vec<f32>,
+    chunk_output: Vec<Vec<f32>>,
+    // Output samples still to drop for rubato's delay
+    delay: usize,
+    produced: usize,
+}
+
+impl Resampler {
+    /// Both rates must be nonzero.
+    pub fn new(from: u32, to: u32) -> Self {
+        let fft = (from != to).then(|| {
+            FftFixedInOut::new(from as usize, to as usize, CHUNK_FRAMES, 1)
+                .expect("sample rates are nonzero")
+        });
+        let chunk_output =
+            fft.as_ref().map_or_else(Vec::new, |fft| fft.output_buffer_allocate(true));
+        let delay = fft.as_ref().map_or(0, |fft| fft.output_delay());
+        Self { fft, input: Vec::new(), chunk_output, delay, produced: 0 }
+    }
+
+    /// Append `samples` and write every output sample they complete to `output`.
+    pub fn process(&mut self, samples: &[f32], output: &mut Vec<f32>) {
+        let Some(chunk) = self.fft.as_ref().map(|fft| fft.input_frames_next()) else {
+            output.extend_from_slice(samples);
+            self.produced += samples.len();
+            return;
+        };
+
+        self.input.extend_from_slice(samples);
+        let mut start = 0;
+        while self.input.len() - start >= chunk {
+            self.run_chunk(start, output);
+            start += chunk;
+        }
+        self.input.drain(..start);
+    }
+
+    /// End of the input: write the remaining output, up to `len` samples in total.
+    pub fn finish(&mut self, output: &mut Vec<f32>, len: usize) {
+        let Some(chunk) = self.fft.as_ref().map(|fft| fft.input_frames_next()) else {
+            output.resize(output.len() + len.saturating_sub(self.produced), 0.0);
+            self.produced = self.produced.max(len);
+            return;
+        };
+
+        // Zeros flush out what rubato still holds, then stand in past the end of the input
+        while self.produced < len {
+            self.input.resize(chunk, 0.0);
+            self.run_chunk(0, output);
+            self.input.clear();
+        }
+        output.truncate(output.len() - (self.produced - len));
+        self.produced = len;
+    }
+
+    // Resample the chunk of input from `start` on, writing what's past the delay to `output`
+    fn run_chunk(&mut self, start: usize, output: &mut Vec<f32>) {
+        let Some(fft) = &mut self.fft else {
+            return;
+        };
+        let (_, written) = fft
+            .process_into_buffer(&[&self.input[start..]], &mut self.chunk_output, None)
+            .expect("chunk and output buffer are the sizes rubato asked for");
+        let skip = self.delay.min(written);
+        output.extend_from_slice(&self.chunk_output[0][skip..written]);
+        self.delay -= skip;
+        self.produced += written - skip;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analysis_rate() {
+        assert_eq!(analysis_rate(44_100), None);
+        assert_eq!(analysis_rate(48_000), None);
+        assert_eq!(analysis_rate(22_050), Some(44_100));
+        assert_eq!(analysis_rate(96_000), Some(48_000));
+        assert_eq!(analysis_rate(16_000), Some(48_000));
+    }
+
+    #[test]
+    fn test_round_trip_preserves_signal() {
+        let (from, to) = (22_050, 44_100);
+        let input: Vec<f32> = (0..4_000)
+            .map(|i| (2.0 * std::f32::consts::PI * 440.0 * i as f32 / 22_050.0).sin())
+            .collect();
+
+        let up = resample(&input, from, to, resampled_len(input.len(), from, to));
+        assert_eq!(up.len(), 8_000);
+        let back = resample(&up, to, from, input.len());
+        assert_eq!(back.len(), input.len());
+
+        // Away from the edges, where the kernel runs off the signal
+        for (a, b) in back.iter().zip(&input).skip(100).take(3_800) {
+            assert!((a - b).abs() < 0.01, "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_streaming_matches_whole_signal() {
+        let input: Vec<f32> =
+            (0..5_000).map(|i| ((i * 7919) % 1000) as f32 / 1000.0 - 0.5).collect();
+        for (from, to) in [(96_000, 48_000), (22_050, 44_100), (44_100, 44_100)] {
+            let len = resampled_len(input.len(), from, to);
+            let whole = resample(&input, from, to, len);
+
+            let mut resampler = Resampler::new(from, to);
+            let mut streamed = Vec::new();
+            for block in input.chunks(333) {
+                resampler.process(block, &mut streamed);
+            }
+            resampler.finish(&mut streamed, len);
+            assert_eq!(streamed, whole);
+        }
+    }
+
+    #[test]
+    fn test_downsampling_rejects_tones_above_nyquist() {
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+        for (from, to) in [(96_000, 48_000), (192_000, 44_100), (48_000, 8_000)] {
+            let nyquist = to as f64 / 2.0;
+            // Just above the new Nyquist has to come out below -80 dB
+            for (freq, gain) in [(0.5 * nyquist, 0.99..1.01), (1.1 * nyquist, 0.0..1e-4)] {
+                // Phase in f64, f32 loses too much of it over a long tone
+                let input: Vec<f32> = (0..from as usize / 2)
+                    .map(|i| (2.0 * std::f64::consts::PI * freq * i as f64 / from as f64).sin())
+                    .map(|x| x as f32)
+                    .collect();
+                let output = resample(&input, from, to, resampled_len(input.len(), from, to));
+                // Away from the edges, where the filter runs off the signal
+                let middle = &output[output.len() / 4..output.len() * 3 / 4];
+                let level = rms(middle) / rms(&input);
+                assert!(gain.contains(&level), "{} Hz at {} -> {}: {}", freq, from, to, level);
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use crate::harmony::Interval;
+
+/// Widths the harmony voices can be spread across, from all centered to hard left and right.
+pub const WIDTH_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+// Lowest level change a mono fold-down gets away with, in dB, half the level where
+// unrelated channels lose about 3
+const MONO_SAFE_DB: f32 = -6.0;
+
+/// A voice of a render that can be routed on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Voice {
+    Lead,
+    Harmony(Interval),
+}
+
+impl FromStr for Voice {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        if name.trim().eq_ignore_ascii_case("lead") {
+            return Ok(Voice::Lead);
+        }
+        name.parse().map(Voice::Harmony)
+    }
+}
+
+impl fmt::Display for Voice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Voice::Lead => write!(f, "lead"),
+            Voice::Harmony(interval) => write!(f, "{}", interval.degree()),
+        }
+    }
+}
+
+/// The level a voice of a stereo render goes into the left and right channel at, 0.0 to
+/// 1.0. A harmony voice's levels scale the harmony level, so 1,0 puts it hard left at it.
+/// Written as "voice=left,right", like "lead=1,1" or "3=0.8,0.3".
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Route {
+    pub voice: Voice,
+    pub left: f32,
+    pub right: f32,
+}
+
+impl Route {
+    /// Full level in both channels, the route of a voice with none of its own.
+    pub fn centered(voice: Voice) -> Self {
+        Self { voice, left: 1.0, right: 1.0 }
+    }
+
+    /// `voice` at `pan`, from -1.0 for hard left to 1.0 for hard right, turning down only
+    /// the far channel so the center keeps its full level.
+    pub fn panned(voice: Voice, pan: f32) -> Self {
+        let pan = pan.clamp(-1.0, 1.0);
+        Self { voice, left: (1.0 - pan).min(1.0), right: (1.0 + pan).min(1.0) }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.left) || !(0.0..=1.0).contains(&self.right) {
+            return Err(format!(
+                "Route levels of the {} voice must be between 0.0 and 1.0",
+                self.voice
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Route {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "Invalid route '{}': expected a voice (lead or a harmony degree) and its left \
+                 and right levels, like lead=1,1 or 3=0.8,0.3",
+                text
+            )
+        };
+        let (voice, levels) = text.split_once('=').ok_or_else(invalid)?;
+        let voice = voice.parse().map_err(|_| invalid())?;
+        let (left, right) = levels.split_once(',').ok_or_else(invalid)?;
+        let level = |level: &str| level.trim().parse::<f32>().map_err(|_| invalid());
+        let route = Self { voice, left: level(left)?, right: level(right)? };
+        route.validate()?;
+        Ok(route)
+    }
+}
+
+impl fmt::Display for Route {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}={},{}", self.voice, self.left, self.right)
+    }
+}
+
+/// The left and right levels `routes` give `voice`, the last route for it where there are
+/// several, and full in both channels where there is none.
+pub fn levels(routes: &[Route], voice: Voice) -> [f32; 2] {
+    let route = routes.iter().rev().find(|route| route.voice == voice);
+    let route = route.copied().unwrap_or(Route::centered(voice));
+    [route.left, route.right]
+}
+
+/// Routes spreading the harmony voices `harmony` across the stereo field around the
+/// centered lead, `width` of the way out: alternately to the left and right, each pair
+/// further out than the one before, so a single voice, like a double, goes all the way.
+pub fn spread(harmony: &[Interval], width: f32) -> Vec<Route> {
+    let width = width.clamp(*WIDTH_RANGE.start(), *WIDTH_RANGE.end());
+    let pairs = harmony.len().div_ceil(2) as f32;
+    (0..)
+        .zip(harmony)
+        .map(|(i, &interval)| {
+            let side = if i % 2 == 0 { -1.0 } else { 1.0 };
+            let pan = side * width * (i / 2 + 1) as f32 / pairs;
+            Route::panned(Voice::Harmony(interval), pan)
+        })
+        .collect()
+}
+
+/// How a stereo render holds up played back in mono.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MonoCheck {
+    // Of the left and right channel, from -1.0 for opposite polarity to 1.0 for the same
+    // signal in both
+    pub correlation: f32,
+    // Level of the mono fold-down against that of the stereo channels, in dB: 0.0 where
+    // they are the same, about -3.0 for unrelated ones and falling as they cancel
+    pub fold_down_db: f32,
+}
+
+impl MonoCheck {
+    /// Whether the mono fold-down keeps close enough to the stereo render's level.
+    pub fn is_safe(&self) -> bool {
+        self.correlation >= 0.0 && self.fold_down_db >= MONO_SAFE_DB
+    }
+}
+
+/// Measures the [`MonoCheck`] of stereo frames as they are pushed.
+#[derive(Debug, Default)]
+pub struct MonoMeter {
+    // Sums of the products of the channels
+    left: f64,
+    right: f64,
+    both: f64,
+}
+
+impl MonoMeter {
+    pub fn push(&mut self, [left, right]: [f32; 2]) {
+        let (left, right) = (left as f64, right as f64);
+        self.left += left * left;
+        self.right += right * right;
+        self.both += left * right;
+    }
+
+    /// None for silence.
+    pub fn finish(&self) -> Option<MonoCheck> {
+        let stereo = self.left + self.right;
+        if stereo <= 0.0 {
+            return None;
+        }
+        // Where one channel is silent there is nothing for it to agree with
+        let correlation = match (self.left * self.right).sqrt() {
+            norm if norm > 0.0 => self.both / norm,
+            _ => 0.0,
+        };
+        // Mean square of (left + right) / 2 over that of the channels
+        let fold_down = (stereo + 2.0 * self.both) / (2.0 * stereo);
+        Some(MonoCheck {
+            correlation: correlation.clamp(-1.0, 1.0) as f32,
+            fold_down_db: (10.0 * fold_down.max(1e-12).log10()) as f32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_routes() {
+        let third = Voice::Harmony(Interval::THIRD);
+        let routes: Vec<Route> = ["lead=1,0.9", "3=1,0", " 3 = 0.2, 0.8", "-8=0.5,0.5"]
+            .iter()
+            .map(|text| text.parse().unwrap())
+            .collect();
+        assert_eq!(levels(&routes, Voice::Lead), [1.0, 0.9]);
+        assert_eq!(levels(&routes, third), [0.2, 0.8]);
+        assert_eq!(levels(&routes, Voice::Harmony(Interval::FIFTH)), [1.0, 1.0]);
+        assert_eq!(routes[3].to_string(), "-8=0.5,0.5");
+        assert_eq!(routes[3].to_string().parse::<Route>(), Ok(routes[3]));
+
+        assert!("lead=1".parse::<Route>().is_err());
+        assert!("lead=1,2".parse::<Route>().is_err());
+        assert!("alto=1,1".parse::<Route>().is_err());
+    }
+
+    #[test]
+    fn test_spread_and_mono_check() {
+        let harmony = [Interval::THIRD, Interval::FIFTH, Interval::OCTAVE];
+        let routes = spread(&harmony, 1.0);
+        assert_eq!(routes[0], Route { voice: Voice::Harmony(harmony[0]), left: 1.0, right: 0.5 });
+        assert_eq!(routes[1], Route { voice: Voice::Harmony(harmony[1]), left: 0.5, right: 1.0 });
+        assert_eq!(routes[2], Route { voice: Voice::Harmony(harmony[2]), left: 1.0, right: 0.0 });
+        assert_eq!(spread(&harmony[..1], 0.4)[0], Route::panned(Voice::Harmony(harmony[0]), -0.4));
+        assert!(spread(&harmony, 0.0).iter().all(|route| route.left == 1.0 && route.right == 1.0));
+
+        let check = |frames: &[[f32; 2]]| {
+            let mut meter = MonoMeter::default();
+            frames.iter().for_each(|&frame| meter.push(frame));
+            meter.finish()
+        };
+        assert_eq!(check(&[[0.0, 0.0]]), None);
+        let same = check(&[[0.5, 0.5], [-0.2, -0.2]]).unwrap();
+        assert!((same.correlation - 1.0).abs() < 1e-6 && same.fold_down_db.abs() < 1e-4);
+        assert!(same.is_safe());
+        let opposite = check(&[[0.5, -0.5], [-0.2, 0.2]]).unwrap();
+        assert!((opposite.correlation + 1.0).abs() < 1e-6 && opposite.fold_down_db < -60.0);
+        assert!(!opposite.is_safe());
+        let hard_left = check(&[[0.5, 0.0], [-0.2, 0.0]]).unwrap();
+        assert_eq!(hard_left.correlation, 0.0);
+        assert!((hard_left.fold_down_db + 3.01).abs() < 0.01 && hard_left.is_safe());
+    }
+}
@@ -0,0 +1,93 @@
+use std::ffi::OsString;
+use std::path::{Component, Path};
+
+/// A sandbox the app can run in on Linux. Files outside it are only reachable through the
+/// XDG desktop portals: a file picked in the portal's file chooser is exported into the
+/// sandbox by the document portal, under a path of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sandbox {
+    Flatpak,
+    Snap,
+}
+
+impl Sandbox {
+    /// The sandbox this process runs in, if any.
+    pub fn detect() -> Option<Self> {
+        Self::from_env(|name| std::env::var_os(name), Path::new("/.flatpak-info").exists())
+    }
+
+    // Flatpak sets FLATPAK_ID and mounts its info file at the root, snapd sets SNAP
+    fn from_env(var: impl Fn(&str) -> Option<OsString>, flatpak_info: bool) -> Option<Self> {
+        if flatpak_info || var("FLATPAK_ID").is_some() {
+            Some(Sandbox::Flatpak)
+        } else if var("SNAP").is_some() {
+            Some(Sandbox::Snap)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sandbox::Flatpak => "Flatpak",
+            Sandbox::Snap => "Snap",
+        }
+    }
+
+    /// Why opening `path` may have failed, to add to the error: in a sandbox, files are
+    /// only reachable once a portal file chooser has granted them.
+    pub fn access_hint(self, path: &Path) -> Option<String> {
+        (!is_portal_document(path)).then(|| {
+            format!(
+                "running as a {}, files outside the sandbox can only be opened by picking them \
+                 with Browse",
+                self.name()
+            )
+        })
+    }
+}
+
+/// Whether `path` is a file the document portal exported into the sandbox, under
+/// /run/user/<uid>/doc/<id>/ (or /run/flatpak/doc/<id>/ inside a Flatpak). Only the file
+/// itself is reachable there, not others in its folder, so nothing new can be written
+/// beside it.
+pub fn is_portal_document(path: &Path) -> bool {
+    let parts: Vec<Component> = path.components().collect();
+    let name = |index: usize| parts.get(index).and_then(|part| part.as_os_str().to_str());
+    if parts.first() != Some(&Component::RootDir) || name(1) != Some("run") {
+        return false;
+    }
+    let doc = match name(2) {
+        Some("user") if name(3).is_some_and(|uid| uid.parse::<u32>().is_ok()) => 4,
+        Some("flatpak") => 3,
+        _ => return false,
+    };
+    // The id folder and the file in it
+    name(doc) == Some("doc") && parts.len() >= doc + 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sandbox_and_portal_documents() {
+        let env = |vars: &'static [&'static str]| {
+            move |name: &str| vars.contains(&name).then(|| OsString::from("1"))
+        };
+        assert_eq!(Sandbox::from_env(env(&["FLATPAK_ID"]), false), Some(Sandbox::Flatpak));
+        assert_eq!(Sandbox::from_env(env(&[]), true), Some(Sandbox::Flatpak));
+        assert_eq!(Sandbox::from_env(env(&["SNAP"]), false), Some(Sandbox::Snap));
+        assert_eq!(Sandbox::from_env(env(&["HOME"]), false), None);
+
+        assert!(is_portal_document(Path::new("/run/user/1000/doc/3f2a9c1b/take 1.wav")));
+        assert!(is_portal_document(Path::new("/run/flatpak/doc/3f2a9c1b/take.wav")));
+        assert!(!is_portal_document(Path::new("/run/user/1000/doc/3f2a9c1b")));
+        assert!(!is_portal_document(Path::new("/home/me/take.wav")));
+        assert!(!is_portal_document(Path::new("/run/user/me/doc/3f2a9c1b/take.wav")));
+        let outside = Path::new("/home/me/take.wav");
+        assert!(Sandbox::Flatpak.access_hint(outside).unwrap().contains("Flatpak"));
+        let picked = Path::new("/run/user/1000/doc/3f2a9c1b/take.wav");
+        assert_eq!(Sandbox::Snap.access_hint(picked), None);
+    }
+}
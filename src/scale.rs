@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use synthphone_vocals::MusicalSettings;
+
+use crate::classifier::{self, OctaveFilter};
+use crate::pitch::PitchDetector;
+
+// Tonic pitch class (0 = C) of each key index, majors then their relative minors, in the
+// order of `KEY_NAMES`
+pub const TONICS: [usize; 24] = [
+    0, 7, 2, 9, 4, 11, 6, 1, 5, 10, 3, 8, // C G D A E B F# C# F Bb Eb Ab major
+    9, 4, 11, 6, 1, 8, 3, 10, 2, 7, 0, 5, // A E B F# C# G# D# A# D G C F minor
+];
+
+/// Common scales as semitones above their tonic, offered as starting points for a custom
+/// scale.
+pub const PRESETS: [(&str, &[usize]); 8] = [
+    ("Major", &[0, 2, 4, 5, 7, 9, 11]),
+    ("Natural Minor", &[0, 2, 3, 5, 7, 8, 10]),
+    ("Harmonic Minor", &[0, 2, 3, 5, 7, 8, 11]),
+    ("Dorian", &[0, 2, 3, 5, 7, 9, 10]),
+    ("Major Pentatonic", &[0, 2, 4, 7, 9]),
+    ("Minor Pentatonic", &[0, 3, 5, 7, 10]),
+    ("Blues", &[0, 3, 5, 6, 7, 10]),
+    ("Chromatic", &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]),
+];
+
+// A pitch has to get this much closer to another scale note before the target leaves the
+// current one, so a voice wavering between two notes doesn't flip back and forth
+const HOLD_SEMITONES: f32 = 0.2;
+
+/// The pitch classes notes are corrected to. Written as a comma separated list of pitch
+/// classes, 0 = C up to 11 = B, such as "0,2,3,5,7,8,10".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Scale {
+    // Indexed by pitch class, 0 = C
+    pub notes: [bool; 12],
+}
+
+impl Scale {
+    /// `pattern`'s semitones counted up from `tonic`.
+    pub fn from_pattern(tonic: usize, pattern: &[usize]) -> Self {
+        let mut notes = [false; 12];
+        for &step in pattern {
+            notes[(tonic + step) % 12] = true;
+        }
+        Self { notes }
+    }
+
+    /// The major or natural minor scale of an entry of `KEY_NAMES`.
+    pub fn from_key(key: usize) -> Self {
+        let pattern = if key < 12 { PRESETS[0].1 } else { PRESETS[1].1 };
+        Self::from_pattern(TONICS[key % 24], pattern)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.notes.contains(&true)
+    }
+
+    /// The MIDI note in the scale closest to a fractional note number.
+    pub fn nearest(&self, note: f32) -> Option<i32> {
+        let rounded = note.round() as i32;
+        // Every pitch class is within six semitones of any note
+        (rounded - 6..=rounded + 6)
+            .filter(|candidate| self.notes[candidate.rem_euclid(12) as usize])
+            .min_by(|a, b| (*a as f32 - note).abs().total_cmp(&(*b as f32 - note).abs()))
+    }
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self::from_key(0)
+    }
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    fn from_str(list: &str) -> Result<Self, String> {
+        let mut notes = [false; 12];
+        for item in list.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item.parse::<usize>() {
+                Ok(pitch_class) if pitch_class < 12 => notes[pitch_class] = true,
+                _ => {
+                    return Err(format!(
+                        "Invalid scale note '{}': expected a pitch class from 0 (C) to 11 (B)",
+                        item
+                    ));
+                }
+            }
+        }
+        let scale = Self { notes };
+        if scale.is_empty() {
+            return Err("A scale needs at least one pitch class".to_string());
+        }
+        Ok(scale)
+    }
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let pitch_classes: Vec<String> = (0..12)
+            .filter(|&pitch_class| self.notes[pitch_class])
+            .map(|pitch_class| pitch_class.to_string())
+            .collect();
+        write!(f, "{}", pitch_classes.join(","))
+    }
+}
+
+impl TryFrom<String> for Scale {
+    type Error = String;
+
+    fn try_from(list: String) -> Result<Self, String> {
+        list.parse()
+    }
+}
+
+impl From<Scale> for String {
+    fn from(scale: Scale) -> Self {
+        scale.to_string()
+    }
+}
+
+/// Chooses each frame's correction target from a scale. The library only snaps to its
+/// built-in keys, so the nearest scale note to the frame's pitch is forced through the
+/// note setting instead. Frames without a pitch keep the last target, and octave errors of
+/// the detector are ignored so they don't knock the target off its note.
+pub struct ScaleTarget {
+    scale: Scale,
+    detector: Box<dyn PitchDetector>,
+    octaves: OctaveFilter,
+    target: Option<i32>,
+}
+
+impl ScaleTarget {
+    /// Choosing from `scale` by the pitch `detector` finds.
+    pub fn new(scale: Scale, detector: Box<dyn PitchDetector>) -> Self {
+        Self { scale, detector, octaves: OctaveFilter::default(), target: None }
+    }
+
+    /// Choose from `scale` from now on, keeping the current target until the pitch moves.
+    pub fn set_scale(&mut self, scale: Scale) {
+        self.scale = scale;
+    }
+
+    /// The MIDI note the last frame was corrected to, None until the first pitch is found.
+    pub fn note(&self) -> Option<i32> {
+        self.target
+    }
+
+    /// `musical` with the note forced to the scale note closest to `frame`'s pitch.
+    pub fn settings(&mut self, frame: &[f32], musical: &MusicalSettings) -> MusicalSettings {
+        if let Some(f0) = self.detector.detect(frame) {
+            let note = self.octaves.push(classifier::note_number(f0));
+            let held = self.target.filter(|&target| {
+                self.scale.nearest(note).is_some_and(|nearest| {
+                    (target as f32 - note).abs() < (nearest as f32 - note).abs() + HOLD_SEMITONES
+                })
+            });
+            self.target = held.or_else(|| self.scale.nearest(note));
+        }
+        MusicalSettings {
+            key: musical.key,
+            // 1 = C up to 12 = B, or auto until the first pitch is found
+            note: self.target.map_or(0, |target| target.rem_euclid(12) + 1),
+            octave: musical.octave,
+            formant: musical.formant,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pitch::PitchAlgorithm;
+
+    #[test]
+    fn test_scale_parsing() {
+        let scale: Scale = "0, 2,3,5,7,8,10".parse().unwrap();
+        assert_eq!(scale, Scale::from_key(22)); // C minor
+        assert_eq!(scale.to_string(), "0,2,3,5,7,8,10");
+        assert_eq!(Scale::from_key(1).to_string(), "0,2,4,6,7,9,11"); // G major
+
+        assert!("".parse::<Scale>().is_err());
+        assert!("0,12".parse::<Scale>().is_err());
+        assert!("0,two".parse::<Scale>().is_err());
+        assert!(Scale { notes: [false; 12] }.is_empty());
+    }
+
+    #[test]
+    fn test_nearest_scale_note() {
+        let blues = Scale::from_pattern(9, PRESETS[6].1); // A C D D# E G
+        assert_eq!(blues.nearest(69.2), Some(69)); // A4
+        assert_eq!(blues.nearest(70.4), Some(69)); // Bb is closer to A than to C
+        assert_eq!(blues.nearest(71.6), Some(72));
+        assert_eq!(blues.nearest(65.4), Some(64)); // F goes down to E
+        let c_only = Scale::from_pattern(0, &[0]);
+        assert_eq!(c_only.nearest(66.2), Some(72));
+        assert_eq!(Scale { notes: [false; 12] }.nearest(60.0), None);
+    }
+
+    #[test]
+    fn test_target_follows_the_sung_pitch() {
+        let rate = 44100.0;
+        let tone = |frequency: f32| -> Vec<f32> {
+            (0..1024)
+                .map(|i| {
+                    let t = i as f32 / rate;
+                    (0.5 * (std::f32::consts::TAU * frequency * t).sin())
+                        + 0.2 * (std::f32::consts::TAU * 2.0 * frequency * t).sin()
+                })
+                .collect()
+        };
+        let musical = MusicalSettings { key: 0, note: 0, octave: 2, formant: 3 };
+        let detector = PitchAlgorithm::Fft.detector(rate, None);
+        let mut target = ScaleTarget::new("2,5,9".parse().unwrap(), detector);
+
+        // Nothing sung yet
+        assert_eq!(target.settings(&[0.0; 1024], &musical).note, 0);
+        // Slightly sharp of G3 snaps to A, the nearest of D F A
+        let settings = target.settings(&tone(200.0), &musical);
+        assert_eq!((settings.note, settings.formant), (10, 3));
+        // Silence holds the last note
+        assert_eq!(target.settings(&[0.0; 1024], &musical).note, 10);
+        // D4
+        assert_eq!(target.settings(&tone(294.0), &musical).note, 3);
+    }
+}
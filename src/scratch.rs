@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::format;
+
+// Leftovers this old are from a run that crashed or was killed, not from one still going
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+static CURRENT: RwLock<ScratchSettings> = RwLock::new(ScratchSettings { dir: None, cap_mib: None });
+static NAMES: AtomicUsize = AtomicUsize::new(0);
+
+/// Where renders spool their audio and jobs keep their intermediates, and how much room
+/// they may take up there.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScratchSettings {
+    // A directory of its own in the system's temp directory when None
+    pub dir: Option<PathBuf>,
+    // Most the scratch files may take up together, in MiB, unlimited when None
+    pub cap_mib: Option<u64>,
+}
+
+impl ScratchSettings {
+    pub fn dir(&self) -> PathBuf {
+        self.dir.clone().unwrap_or_else(|| std::env::temp_dir().join("autotune-scratch"))
+    }
+
+    fn cap_bytes(&self) -> Option<u64> {
+        self.cap_mib.map(|mib| mib.saturating_mul(1 << 20))
+    }
+}
+
+/// Use `settings` for every scratch file created from now on.
+pub fn set(settings: ScratchSettings) {
+    *CURRENT.write().unwrap_or_else(|e| e.into_inner()) = settings;
+}
+
+pub fn current() -> ScratchSettings {
+    CURRENT.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// A path of its own in the scratch directory for a file named after `name`, with the
+/// directory created and what crashed runs left in it cleaned up. Fails when the scratch
+/// files already take up the cap.
+pub fn file(name: &str) -> Result<PathBuf, String> {
+    Ok(prepare(&current())?.join(unique(name)))
+}
+
+/// Bytes scratch files may still take up before they reach the cap, None without one.
+pub fn room() -> Option<u64> {
+    let settings = current();
+    settings.cap_bytes().map(|cap| cap.saturating_sub(usage(&settings.dir())))
+}
+
+/// Bytes the files in `dir` take up, those of the job directories in it included.
+pub fn usage(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => usage(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Remove what other runs left in `dir` and haven't touched in a day, which a crash or a
+/// kill kept them from cleaning up. Returns the bytes freed.
+pub fn clean_stale(dir: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut freed = 0;
+    for entry in entries.flatten() {
+        if is_ours(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        let age = metadata.modified().ok().and_then(|modified| modified.elapsed().ok());
+        let stale = age.is_some_and(|age| age >= STALE_AFTER);
+        if !stale {
+            continue;
+        }
+        let path = entry.path();
+        let (size, removed) = if metadata.is_dir() {
+            (usage(&path), fs::remove_dir_all(&path))
+        } else {
+            (metadata.len(), fs::remove_file(&path))
+        };
+        if removed.is_ok() {
+            freed += size;
+        }
+    }
+    freed
+}
+
+/// A directory of its own in the scratch directory for what a job stages or extracts on
+/// the way, deleted with everything in it when this is dropped.
+#[derive(Debug)]
+pub struct JobDir {
+    path: PathBuf,
+}
+
+impl JobDir {
+    pub fn create(name: &str) -> Result<Self, String> {
+        let path = file(name)?;
+        fs::create_dir_all(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for JobDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// The scratch directory ready for a new file
+fn prepare(settings: &ScratchSettings) -> Result<PathBuf, String> {
+    let dir = settings.dir();
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create scratch directory {}: {}", dir.display(), e))?;
+    clean_stale(&dir);
+    if let Some(cap) = settings.cap_bytes() {
+        let used = usage(&dir);
+        if used >= cap {
+            return Err(full(&dir, cap));
+        }
+    }
+    Ok(dir)
+}
+
+/// The error for scratch space in `dir` that has reached its `cap` in bytes.
+pub fn full(dir: &Path, cap: u64) -> String {
+    format!(
+        "Scratch space in {} is full, its files take up the {} cap",
+        dir.display(),
+        format::file_size(cap)
+    )
+}
+
+// Names of this process start with its id, so other processes running at the same time
+// leave them alone
+fn unique(name: &str) -> String {
+    format!("{}-{}-{}", process::id(), NAMES.fetch_add(1, Ordering::Relaxed), name)
+}
+
+fn is_ours(name: &str) -> bool {
+    name.strip_prefix(&process::id().to_string()).is_some_and(|rest| rest.starts_with('-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scratch_files_stay_under_the_cap() {
+        let dir = std::env::temp_dir().join("autotune-scratch-test");
+        let _ = fs::remove_dir_all(&dir);
+        let settings = ScratchSettings { dir: Some(dir.clone()), cap_mib: Some(1) };
+        let ready = prepare(&settings).unwrap();
+        assert_eq!(ready, dir);
+
+        let a = ready.join(unique("take.wav"));
+        let b = ready.join(unique("take.wav"));
+        assert_ne!(a, b);
+        assert!(is_ours(&a.file_name().unwrap().to_string_lossy()));
+        assert!(!is_ours(&format!("{}0-take.wav", process::id())));
+        fs::write(&a, vec![0u8; 1 << 19]).unwrap();
+        fs::create_dir_all(dir.join("job")).unwrap();
+        fs::write(dir.join("job").join("input.wav"), vec![0u8; 1 << 19]).unwrap();
+        assert_eq!(usage(&dir), 1 << 20);
+        assert!(prepare(&settings).unwrap_err().contains("full"));
+        assert!(prepare(&ScratchSettings { cap_mib: None, ..settings.clone() }).is_ok());
+
+        // Nothing here is a day old
+        assert_eq!(clean_stale(&dir), 0);
+        assert!(a.exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,123 @@
+use rhai::{Array, Dynamic, Engine, EvalAltResult, INT, Map, Scope};
+use std::fmt;
+use std::path::Path;
+
+use crate::cli::{self, ProcessParams, SideFiles};
+use crate::failure::{self, Failed, Failure};
+use crate::keys;
+
+/// Run the Rhai job script at `path`, with `args` in its `ARGS` array. Besides what the
+/// language has built in, scripts can call:
+///
+/// - `audio_files(dir)`: the files in `dir` that can be processed, sorted
+/// - `stem(path)`, `join(dir, name)`, `exists(path)`, and `create_dir(dir)`
+/// - `parse_key(text)`: the key a name like "Am" or "F# major" is, () when it is none
+/// - `detect_key(path)`: the key that fits a file best
+/// - `process(input, output)` and `process(input, output, params)`: autotune a file, with
+///   settings in a map that takes the same fields as a job ticket's `params`
+///
+/// A file that fails to process stops the script, unless it is caught with `try`.
+pub fn run(path: &Path, args: &[String], verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let engine = engine(verbose);
+    let ast = engine.compile_file(path.to_path_buf()).map_err(|e| {
+        Failed::new(Failure::Arguments, format!("Failed to load {}: {}", path.display(), e))
+    })?;
+    let mut scope = Scope::new();
+    let args: Array = args.iter().cloned().map(Dynamic::from).collect();
+    scope.push_constant("ARGS", args);
+
+    let result = engine.run_ast_with_scope(&mut scope, &ast);
+    failure::check_interrupted()?;
+    result.map_err(|e| format!("{} stopped: {}", path.display(), e).into())
+}
+
+fn engine(verbose: bool) -> Engine {
+    let mut engine = Engine::new();
+    // Ctrl-C stops a script between any two of its steps, not just between files
+    engine.on_progress(|_| failure::interrupted().then_some(Dynamic::UNIT));
+
+    engine.register_fn("audio_files", |dir: &str| -> Result<Array, Box<EvalAltResult>> {
+        let files = cli::audio_files(Path::new(dir))?;
+        Ok(files.iter().map(|file| Dynamic::from(path_text(file))).collect())
+    });
+    engine.register_fn("stem", |path: &str| {
+        Path::new(path)
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+    });
+    engine.register_fn("join", |dir: &str, name: &str| path_text(&Path::new(dir).join(name)));
+    engine.register_fn("exists", |path: &str| Path::new(path).exists());
+    engine.register_fn("create_dir", |dir: &str| -> Result<(), Box<EvalAltResult>> {
+        std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir, e).into())
+    });
+
+    engine.register_fn("parse_key", |text: &str| {
+        keys::parse_key_name(text).map_or(Dynamic::UNIT, |key| Dynamic::from(key as INT))
+    });
+    engine.register_fn("detect_key", |path: &str| -> Result<INT, Box<EvalAltResult>> {
+        let matches = keys::detect_file(Path::new(path))?;
+        let best = matches.first().ok_or("Not enough pitched audio to detect a key")?;
+        Ok(best.key as INT)
+    });
+
+    engine.register_fn("process", move |input: &str, output: &str| {
+        process(input, output, Map::new(), verbose)
+    });
+    engine.register_fn("process", move |input: &str, output: &str, params: Map| {
+        process(input, output, params, verbose)
+    });
+    engine
+}
+
+fn process(
+    input: &str,
+    output: &str,
+    params: Map,
+    verbose: bool,
+) -> Result<(), Box<EvalAltResult>> {
+    let params = params_from(params)?;
+    println!("{} -> {}", input, output);
+    let side_files = SideFiles::default();
+    cli::process_location(Path::new(input), Path::new(output), &params, &side_files, verbose)
+        .map_err(|e| format!("Failed {}: {}", input, e).into())
+}
+
+// Read like a job ticket's params, so fields left out keep their defaults
+fn params_from(map: Map) -> Result<ProcessParams, String> {
+    let invalid = |e: &dyn fmt::Display| format!("Invalid params: {}", e);
+    let json: serde_json::Value =
+        rhai::serde::from_dynamic(&Dynamic::from_map(map)).map_err(|e| invalid(&e))?;
+    let params: ProcessParams = serde_json::from_value(json).map_err(|e| invalid(&e))?;
+    params.validate()?;
+    Ok(params)
+}
+
+fn path_text(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_work_out_settings() {
+        let engine = engine(false);
+        let key = engine.eval::<INT>(r#"parse_key(stem("takes/lead_F#m.wav").split("_")[1])"#);
+        assert_eq!(key.unwrap(), keys::parse_key_name("F# minor").unwrap() as INT);
+        assert!(engine.eval::<()>(r#"parse_key("lead")"#).is_ok());
+        assert_eq!(
+            engine.eval::<String>(r#"join("tuned", "lead.wav")"#).unwrap(),
+            path_text(&Path::new("tuned").join("lead.wav"))
+        );
+
+        let params = params_from(engine.eval::<Map>("#{ key: 12, strength: 1 }").unwrap());
+        let params = params.unwrap();
+        assert_eq!((params.key, params.strength), (12, 1.0));
+        assert_eq!(params.octave, ProcessParams::default().octave);
+        let out_of_range = engine.eval::<Map>("#{ strength: 2.0 }").unwrap();
+        assert!(params_from(out_of_range).unwrap_err().contains("strength"));
+        let wrong_type = engine.eval::<Map>(r#"#{ key: "Am" }"#).unwrap();
+        assert!(params_from(wrong_type).unwrap_err().starts_with("Invalid params"));
+    }
+}
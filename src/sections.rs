@@ -0,0 +1,148 @@
+use synthphone_vocals::MusicalSettings;
+
+use crate::keys;
+use crate::labels::Label;
+
+// Keys of the library: 0-11 major, 12-23 minor
+const KEYS: i32 = 24;
+// Lowest and highest octave reference and formant shift of the library
+const OCTAVES: (i32, i32) = (0, 4);
+const FORMANTS: (i32, i32) = (-12, 12);
+
+/// A stretch of a take corrected with its own musical settings, for a song that modulates
+/// or a bridge sung with another character. Outside of sections the main settings apply.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Section {
+    pub start_secs: f32,
+    pub end_secs: f32,
+    pub key: i32,
+    pub note: i32,
+    pub octave: i32,
+    pub formant: i32,
+}
+
+impl Section {
+    /// A section from `start_secs` to `end_secs` with the settings of `musical`.
+    pub fn new(start_secs: f32, end_secs: f32, musical: &MusicalSettings) -> Self {
+        Self {
+            start_secs,
+            end_secs,
+            key: musical.key,
+            note: musical.note,
+            octave: musical.octave,
+            formant: musical.formant,
+        }
+    }
+
+    pub fn musical(&self) -> MusicalSettings {
+        MusicalSettings {
+            key: self.key,
+            note: self.note,
+            octave: self.octave,
+            formant: self.formant,
+        }
+    }
+
+    pub fn contains(&self, secs: f32) -> bool {
+        self.start_secs <= secs && secs < self.end_secs
+    }
+
+    // Change the setting `name` to `value`, as a section label writes it
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let number = |range: (i32, i32)| {
+            value
+                .parse()
+                .ok()
+                .filter(|number| (range.0..=range.1).contains(number))
+                .ok_or_else(|| format!("{} must be from {} to {}", name, range.0, range.1))
+        };
+        match name {
+            "key" => {
+                self.key = match keys::parse_key_name(value) {
+                    Some(key) => key as i32,
+                    None => number((0, KEYS - 1))?,
+                }
+            }
+            "note" => {
+                self.note = match keys::parse_note_name(value) {
+                    Some(pitch_class) => pitch_class as i32 + 1,
+                    None if value.eq_ignore_ascii_case("auto") => 0,
+                    None => number((0, 12))?,
+                }
+            }
+            "octave" => self.octave = number(OCTAVES)?,
+            "formant" => self.formant = number(FORMANTS)?,
+            _ => return Err(format!("unknown setting '{}'", name)),
+        }
+        Ok(())
+    }
+}
+
+/// The sections the regions of `labels` describe, in time order. A region's text sets the
+/// settings that differ from `musical`'s as name=value pairs, like "Bridge key=Ebm
+/// formant=-2": key takes a name like D or F#m or its number, note auto, a note name, or
+/// its number, then octave and formant. Other words name the section and are ignored.
+pub fn from_labels(labels: &[Label], musical: &MusicalSettings) -> Result<Vec<Section>, String> {
+    let mut sections = Vec::new();
+    for label in labels.iter().filter(|label| !label.is_point()) {
+        let mut section = Section::new(label.start_secs, label.end_secs, musical);
+        let settings =
+            label.text.split([' ', '\t', ',', ';']).filter_map(|word| word.split_once('='));
+        for (name, value) in settings {
+            section.set(&name.trim().to_lowercase(), value.trim()).map_err(|e| {
+                format!("Section '{}' at {:.2}s: {}", label.text, label.start_secs, e)
+            })?;
+        }
+        sections.push(section);
+    }
+    sections.sort_by(|a, b| a.start_secs.total_cmp(&b.start_secs));
+    Ok(sections)
+}
+
+/// The section in effect at `secs`, the one that started last where they overlap.
+pub fn section_at(sections: &[Section], secs: f32) -> Option<&Section> {
+    sections.iter().rev().find(|section| section.contains(secs))
+}
+
+/// The first edge of a section after `secs`, as long as it is no more than `within` away.
+pub fn next_edge(sections: &[Section], secs: f32, within: f32) -> Option<f32> {
+    sections
+        .iter()
+        .flat_map(|section| [section.start_secs, section.end_secs])
+        .filter(|&edge| edge > secs && edge <= secs + within)
+        .min_by(f32::total_cmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_from_labels() {
+        let musical = MusicalSettings { key: 0, note: 0, octave: 2, formant: 0 };
+        let label = |start_secs, end_secs, text: &str| Label {
+            start_secs,
+            end_secs,
+            text: text.to_string(),
+        };
+        let labels = [
+            label(30.0, 45.0, "Bridge key=Ebm, formant=-2"),
+            label(10.0, 20.0, "key=7 note=A octave=3"),
+            label(12.0, 12.0, "Key: G"),
+        ];
+        let sections = from_labels(&labels, &musical).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!((sections[0].key, sections[0].note, sections[0].octave), (7, 10, 3));
+        assert_eq!((sections[1].key, sections[1].note, sections[1].formant), (18, 0, -2));
+        assert_eq!(sections[1].octave, 2);
+
+        assert_eq!(section_at(&sections, 15.0), Some(&sections[0]));
+        assert_eq!(section_at(&sections, 20.0), None);
+        assert_eq!(next_edge(&sections, 19.98, 0.05), Some(20.0));
+        assert_eq!(next_edge(&sections, 25.0, 0.05), None);
+
+        assert!(from_labels(&[label(0.0, 1.0, "formant=20")], &musical).is_err());
+        assert!(from_labels(&[label(0.0, 1.0, "tempo=90")], &musical).is_err());
+        assert!(from_labels(&[label(0.0, 1.0, "key=H")], &musical).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::app_config::ConfigLocation;
+
+const INSTANCE_FILE: &str = "instance.json";
+// A live instance answers at once; anything slower is a stale port
+const ANSWER_TIMEOUT: Duration = Duration::from_millis(500);
+const ACCEPTED: &str = "ok";
+
+// Where the running instance listens. The token shows a caller could read the settings
+// directory, so other local users and programs can't push files into the app.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct InstanceInfo {
+    port: u16,
+    token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Request {
+    token: String,
+    open: Option<PathBuf>,
+}
+
+/// What a later launch asked the running instance to do.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Handoff {
+    // Launched without a file: just come to the front
+    Focus,
+    Open(PathBuf),
+}
+
+/// The running instance, listening on a local socket for later launches. Only one per
+/// settings directory, so a portable copy runs alongside the installed one.
+pub struct Instance {
+    info_path: PathBuf,
+    info: InstanceInfo,
+    receiver: mpsc::Receiver<Handoff>,
+}
+
+/// Hand `file` to the instance already running, which opens it and comes to the front.
+/// Returns false when no instance answers, and this launch should become the instance.
+pub fn hand_off(config: &ConfigLocation, file: Option<&Path>) -> bool {
+    let Some(info) = config.load::<InstanceInfo>(INSTANCE_FILE) else {
+        return false;
+    };
+    // Relative to where this launch was started, which the instance doesn't know
+    let file = file.map(|file| std::path::absolute(file).unwrap_or_else(|_| file.to_path_buf()));
+    let request = Request { token: info.token, open: file };
+    send(info.port, &request).is_ok_and(|answer| answer == ACCEPTED)
+}
+
+fn send(port: u16, request: &Request) -> io::Result<String> {
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&address, ANSWER_TIMEOUT)?;
+    stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+    let json = serde_json::to_string(request).map_err(io::Error::other)?;
+    writeln!(stream, "{}", json)?;
+
+    let mut answer = String::new();
+    BufReader::new(stream).read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+impl Instance {
+    /// Become the running instance, calling `wake` whenever a later launch hands something
+    /// over so an idle window notices.
+    pub fn listen(config: &ConfigLocation, wake: impl Fn() + Send + 'static) -> io::Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+        let info = InstanceInfo { port: listener.local_addr()?.port(), token: new_token() };
+        config.save(INSTANCE_FILE, &info)?;
+
+        let (sender, receiver) = mpsc::channel();
+        let token = info.token.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                match accept(stream, &token) {
+                    Ok(handoff) => {
+                        if sender.send(handoff).is_err() {
+                            break;
+                        }
+                        wake();
+                    }
+                    Err(e) => log::warn!("Ignored a connection from another launch: {}", e),
+                }
+            }
+        });
+        Ok(Self { info_path: config.file(INSTANCE_FILE), info, receiver })
+    }
+
+    /// The next handoff from a later launch, if any arrived.
+    pub fn try_recv(&self) -> Option<Handoff> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for Instance {
+    fn drop(&mut self) {
+        // Unless a newer instance took over in the meantime
+        let ours = std::fs::read_to_string(&self.info_path)
+            .ok()
+            .and_then(|json| serde_json::from_str::<InstanceInfo>(&json).ok())
+            .is_some_and(|info| info == self.info);
+        if ours {
+            let _ = std::fs::remove_file(&self.info_path);
+        }
+    }
+}
+
+fn accept(stream: TcpStream, token: &str) -> io::Result<Handoff> {
+    stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let request: Request = serde_json::from_str(&line).map_err(io::Error::other)?;
+    if request.token != token {
+        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "wrong token"));
+    }
+    writeln!(&stream, "{}", ACCEPTED)?;
+    Ok(request.open.map_or(Handoff::Focus, Handoff::Open))
+}
+
+// Unguessable enough for a local handoff, without a dependency just for random numbers
+fn new_token() -> String {
+    let nanos = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+    let seed = (nanos.as_nanos(), std::process::id());
+    let halves = [RandomState::new().hash_one(seed), RandomState::new().hash_one(seed)];
+    format!("{:016x}{:016x}", halves[0], halves[1])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_later_launch_hands_its_file_over() {
+        let dir = std::env::temp_dir().join("autotune-single-instance-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = ConfigLocation { dir: dir.clone(), portable: true };
+        assert!(!hand_off(&config, None));
+
+        let (woken, wakes) = mpsc::channel();
+        let instance = Instance::listen(&config, move || woken.send(()).unwrap()).unwrap();
+        let file = dir.join("take1.wav");
+        assert!(hand_off(&config, Some(&file)));
+        wakes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(instance.try_recv(), Some(Handoff::Open(file)));
+        assert!(hand_off(&config, None));
+        wakes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(instance.try_recv(), Some(Handoff::Focus));
+
+        // Without the token nothing is handed over
+        let forged = Request { token: "guess".to_string(), open: None };
+        assert_ne!(send(instance.info.port, &forged).unwrap_or_default(), ACCEPTED);
+        assert_eq!(instance.try_recv(), None);
+
+        // The info file goes with the instance
+        drop(instance);
+        assert!(!config.file(INSTANCE_FILE).exists());
+        assert!(!hand_off(&config, None));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
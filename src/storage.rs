@@ -0,0 +1,217 @@
+use std::path::{Path, PathBuf};
+
+use crate::pipe;
+
+/// Where an input comes from or an output goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Location {
+    Local(PathBuf),
+    // Plain or presigned http(s) URL: downloaded with GET, uploaded with PUT
+    Url(String),
+    // Object in S3-compatible storage, signed with credentials from the environment
+    S3 { bucket: String, key: String },
+    // Raw PCM piped in through stdin or out through stdout, converted by the caller
+    Stdio,
+}
+
+impl Location {
+    pub fn from_path(path: &Path) -> Result<Self, String> {
+        if pipe::is_stdio(path) {
+            return Ok(Location::Stdio);
+        }
+        let Some(text) = path.to_str() else {
+            return Ok(Location::Local(path.to_path_buf()));
+        };
+
+        if let Some(rest) = text.strip_prefix("s3://") {
+            return match rest.split_once('/') {
+                Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() => {
+                    Ok(Location::S3 { bucket: bucket.to_string(), key: key.to_string() })
+                }
+                _ => Err(format!("Invalid S3 location '{}', expected s3://bucket/key", text)),
+            };
+        }
+
+        if text.starts_with("https://") || text.starts_with("http://") {
+            return Ok(Location::Url(text.to_string()));
+        }
+
+        Ok(Location::Local(path.to_path_buf()))
+    }
+
+    pub fn is_local(&self) -> bool {
+        matches!(self, Location::Local(_))
+    }
+
+    /// File name to use for a local copy of this location.
+    pub fn file_name(&self) -> String {
+        let name = match self {
+            Location::Local(path) => return crate::paths::display_name(path),
+            // Presigned URLs carry their signature in the query string
+            Location::Url(url) => url.split(['?', '#']).next().unwrap_or_default(),
+            Location::S3 { key, .. } => key,
+            Location::Stdio => "piped.wav",
+        };
+
+        let name = name.rsplit('/').find(|segment| !segment.is_empty()).unwrap_or("audio.wav");
+        name.chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Local(path) => write!(f, "{}", path.display()),
+            // Never print presigned query strings, they are credentials
+            Location::Url(url) => write!(f, "{}", url.split('?').next().unwrap_or_default()),
+            Location::S3 { bucket, key } => write!(f, "s3://{}/{}", bucket, key),
+            Location::Stdio => f.write_str(pipe::STDIO),
+        }
+    }
+}
+
+/// Make `location` available as a local file, downloading it into `staging` when remote.
+pub fn fetch(location: &Location, staging: &Path) -> Result<PathBuf, String> {
+    match location {
+        Location::Local(path) => Ok(path.clone()),
+        Location::Stdio => Err(PIPED.to_string()),
+        #[cfg(feature = "object-storage")]
+        remote => {
+            let path = staged_path(staging, "input", remote)?;
+            remote::download(remote, &path)?;
+            Ok(path)
+        }
+        #[cfg(not(feature = "object-storage"))]
+        _ => {
+            let _ = staging;
+            Err(NOT_ENABLED.to_string())
+        }
+    }
+}
+
+/// Local path to write output for `location` to before calling [`store`].
+pub fn output_path(location: &Location, staging: &Path) -> Result<PathBuf, String> {
+    match location {
+        Location::Local(path) => Ok(path.clone()),
+        remote => staged_path(staging, "output", remote),
+    }
+}
+
+/// Upload the local file written for a remote `location`. No-op for local outputs.
+pub fn store(local: &Path, location: &Location) -> Result<(), String> {
+    match location {
+        Location::Local(_) => Ok(()),
+        Location::Stdio => Err(PIPED.to_string()),
+        #[cfg(feature = "object-storage")]
+        remote => remote::upload(local, remote),
+        #[cfg(not(feature = "object-storage"))]
+        _ => {
+            let _ = local;
+            Err(NOT_ENABLED.to_string())
+        }
+    }
+}
+
+// Piped audio is streamed as raw PCM rather than staged as a file
+const PIPED: &str = "stdin and stdout only carry raw PCM";
+
+#[cfg(not(feature = "object-storage"))]
+const NOT_ENABLED: &str = "Remote locations need a build with the object-storage feature enabled";
+
+fn staged_path(staging: &Path, role: &str, location: &Location) -> Result<PathBuf, String> {
+    std::fs::create_dir_all(staging)
+        .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+
+    // Several jobs (or processes) can stage files with the same name at once
+    let unique = format!("{}-{}-{}", std::process::id(), role, location.file_name());
+    Ok(staging.join(unique))
+}
+
+#[cfg(feature = "object-storage")]
+mod remote {
+    use super::Location;
+    use std::fs::File;
+    use std::io;
+    use std::path::Path;
+
+    pub fn download(location: &Location, dest: &Path) -> Result<(), String> {
+        let request = match location {
+            Location::Url(url) => ureq::get(url),
+            Location::S3 { bucket, key } => crate::s3::request("GET", bucket, key)?,
+            Location::Local(_) | Location::Stdio => return Ok(()),
+        };
+
+        let response =
+            request.call().map_err(|e| format!("Failed to download {}: {}", location, e))?;
+        let mut file = File::create(dest)
+            .map_err(|e| format!("Failed to create {}: {}", dest.display(), e))?;
+        io::copy(&mut response.into_reader(), &mut file)
+            .map_err(|e| format!("Failed to download {}: {}", location, e))?;
+        Ok(())
+    }
+
+    pub fn upload(local: &Path, location: &Location) -> Result<(), String> {
+        let request = match location {
+            Location::Url(url) => ureq::put(url),
+            Location::S3 { bucket, key } => crate::s3::request("PUT", bucket, key)?,
+            Location::Local(_) | Location::Stdio => return Ok(()),
+        };
+
+        let file =
+            File::open(local).map_err(|e| format!("Failed to open {}: {}", local.display(), e))?;
+        let size = file
+            .metadata()
+            .map_err(|e| format!("Failed to read {}: {}", local.display(), e))?
+            .len();
+
+        // Object stores reject chunked uploads, so always send a length
+        request
+            .set("Content-Length", &size.to_string())
+            .set("Content-Type", "audio/wav")
+            .send(file)
+            .map_err(|e| format!("Failed to upload {}: {}", location, e))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_locations() {
+        assert_eq!(
+            Location::from_path(Path::new("s3://bucket/takes/lead.wav")).unwrap(),
+            Location::S3 { bucket: "bucket".to_string(), key: "takes/lead.wav".to_string() }
+        );
+        assert!(Location::from_path(Path::new("s3://bucket")).is_err());
+        assert!(
+            Location::from_path(Path::new("https://example.com/a.wav?X-Amz-Signature=1"))
+                .is_ok_and(|location| matches!(location, Location::Url(_)))
+        );
+        assert!(Location::from_path(Path::new("take.wav")).unwrap().is_local());
+        assert_eq!(Location::from_path(Path::new("-")).unwrap(), Location::Stdio);
+    }
+
+    #[test]
+    fn test_remote_names_hide_signatures() {
+        let url =
+            Location::Url("https://example.com/out/Lead%20Take.wav?X-Amz-Signature=abc".into());
+        assert_eq!(url.file_name(), "Lead_20Take.wav");
+        assert_eq!(url.to_string(), "https://example.com/out/Lead%20Take.wav");
+
+        let staging = std::env::temp_dir().join("autotune-staging-test");
+        let staged = output_path(&url, &staging).unwrap();
+        assert!(staged.starts_with(&staging));
+        assert!(staged.to_string_lossy().ends_with("output-Lead_20Take.wav"));
+        let _ = std::fs::remove_dir_all(staging);
+    }
+}
@@ -0,0 +1,201 @@
+use std::f32::consts::PI;
+
+/// Time-stretch ratios clips can be given, as output length over input length.
+pub const MIN_STRETCH: f32 = 0.5;
+pub const MAX_STRETCH: f32 = 2.0;
+
+// Same framing as the pitch correction, so stretched clips keep its time resolution
+const FFT_SIZE: usize = 1024;
+const SYNTHESIS_HOP: usize = 256;
+// Output samples covered by less window than this are from the very edges, keep them quiet
+const MIN_WINDOW_SUM: f32 = 1e-3;
+
+/// Length of `len` samples once stretched by `ratio`.
+pub fn stretched_len(len: usize, ratio: f32) -> usize {
+    (len as f64 * ratio.clamp(MIN_STRETCH, MAX_STRETCH) as f64).round() as usize
+}
+
+/// Stretch `samples` to `ratio` times their length without changing their pitch, with a
+/// phase vocoder. Frames are read `SYNTHESIS_HOP / ratio` apart and written
+/// `SYNTHESIS_HOP` apart, with each bin's phase advanced by its measured frequency.
+pub fn time_stretch(samples: &[f32], ratio: f32) -> Vec<f32> {
+    let ratio = ratio.clamp(MIN_STRETCH, MAX_STRETCH);
+    if ratio == 1.0 || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let len = stretched_len(samples.len(), ratio);
+    let half = FFT_SIZE / 2;
+    let window: Vec<f32> =
+        (0..FFT_SIZE).map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos()).collect();
+    // Phase advance of each bin's center frequency over one sample
+    let bin_step: Vec<f32> =
+        (0..=half).map(|bin| 2.0 * PI * bin as f32 / FFT_SIZE as f32).collect();
+
+    let mut output = vec![0.0; len + FFT_SIZE];
+    let mut window_sum = vec![0.0; len + FFT_SIZE];
+    let mut re = vec![0.0; FFT_SIZE];
+    let mut im = vec![0.0; FFT_SIZE];
+    let mut last_phase = vec![0.0; half + 1];
+    let mut phase = vec![0.0; half + 1];
+    let mut last_position = 0;
+
+    // Frame m is centered at m * SYNTHESIS_HOP in the output, at `position` in the input
+    for frame in 0..=(len + half) / SYNTHESIS_HOP {
+        let position = (frame as f64 * SYNTHESIS_HOP as f64 / ratio as f64).round() as usize;
+        for (i, (re, im)) in re.iter_mut().zip(&mut im).enumerate() {
+            *re = (position + i)
+                .checked_sub(half)
+                .and_then(|index| samples.get(index))
+                .map_or(0.0, |&sample| sample * window[i]);
+            *im = 0.0;
+        }
+        fft(&mut re, &mut im, false);
+
+        let hop = (position - last_position) as f32;
+        for bin in 0..=half {
+            let magnitude = re[bin].hypot(im[bin]);
+            let measured = im[bin].atan2(re[bin]);
+            if frame == 0 {
+                phase[bin] = measured;
+            } else {
+                // Deviation from the bin's center frequency, wrapped to -PI..PI
+                let deviation = measured - last_phase[bin] - bin_step[bin] * hop;
+                let deviation = deviation - 2.0 * PI * (deviation / (2.0 * PI)).round();
+                let frequency = bin_step[bin] + deviation / hop;
+                phase[bin] = (phase[bin] + frequency * SYNTHESIS_HOP as f32).rem_euclid(2.0 * PI);
+            }
+            last_phase[bin] = measured;
+            re[bin] = magnitude * phase[bin].cos();
+            im[bin] = magnitude * phase[bin].sin();
+        }
+        // Mirror the upper half so the inverse is real
+        for bin in half + 1..FFT_SIZE {
+            re[bin] = re[FFT_SIZE - bin];
+            im[bin] = -im[FFT_SIZE - bin];
+        }
+        fft(&mut re, &mut im, true);
+        last_position = position;
+
+        // Output index shifted by `half`, so frame 0 can start before the first sample
+        let start = frame * SYNTHESIS_HOP;
+        for (i, &w) in window.iter().enumerate() {
+            if let (Some(out), Some(sum)) =
+                (output.get_mut(start + i), window_sum.get_mut(start + i))
+            {
+                *out += re[i] * w;
+                *sum += w * w;
+            }
+        }
+    }
+
+    output
+        .iter()
+        .zip(&window_sum)
+        .skip(half)
+        .take(len)
+        .map(|(&sample, &sum)| {
+            if sum > MIN_WINDOW_SUM {
+                sample / sum
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// In-place radix-2 FFT of a power-of-two length. The inverse is scaled by 1/n.
+pub fn fft(re: &mut [f32], im: &mut [f32], inverse: bool) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut size = 2;
+    while size <= n {
+        let angle = sign * 2.0 * PI / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..size / 2 {
+                let (w_im, w_re) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + size / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        size <<= 1;
+    }
+
+    if inverse {
+        for (re, im) in re.iter_mut().zip(im) {
+            *re /= n as f32;
+            *im /= n as f32;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(frequency: f32, len: usize) -> Vec<f32> {
+        (0..len).map(|i| 0.5 * (2.0 * PI * frequency * i as f32 / 44_100.0).sin()).collect()
+    }
+
+    // Frequency of a sine from its zero crossings
+    fn frequency(samples: &[f32]) -> f32 {
+        let crossings = samples.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count();
+        crossings as f32 / 2.0 / (samples.len() as f32 / 44_100.0)
+    }
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn test_stretch_keeps_pitch_and_level() {
+        let input = sine(440.0, 44_100);
+        for ratio in [0.8, 1.5] {
+            let output = time_stretch(&input, ratio);
+            assert_eq!(output.len(), stretched_len(input.len(), ratio));
+
+            // Away from the edges, where frames are missing on one side
+            let middle = &output[4096..output.len() - 4096];
+            assert!((frequency(middle) - 440.0).abs() < 440.0 * 0.02, "ratio {}", ratio);
+            assert!((rms(middle) - rms(&input)).abs() < rms(&input) * 0.1, "ratio {}", ratio);
+        }
+    }
+
+    #[test]
+    fn test_unit_ratio_is_unchanged() {
+        let input = sine(440.0, 1000);
+        assert_eq!(time_stretch(&input, 1.0), input);
+        assert_eq!(stretched_len(1000, 10.0), 2000);
+    }
+
+    #[test]
+    fn test_fft_round_trip() {
+        let signal: Vec<f32> = (0..16).map(|i| (i as f32 * 0.7).sin()).collect();
+        let mut re = signal.clone();
+        let mut im = vec![0.0; 16];
+        fft(&mut re, &mut im, false);
+        // A real signal's spectrum is conjugate symmetric
+        assert!((re[3] - re[13]).abs() < 1e-4 && (im[3] + im[13]).abs() < 1e-4);
+        fft(&mut re, &mut im, true);
+        assert!(re.iter().zip(&signal).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+}
@@ -0,0 +1,189 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// Repaint interval used while processing when animations are disabled
+const REDUCED_MOTION_REPAINT: Duration = Duration::from_millis(500);
+
+/// Colors for the pitch and waveform plots and for status text. The alternatives to the
+/// standard one keep apart what red-green color blindness (deuteranopia and protanopia)
+/// would run together.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Palette {
+    #[default]
+    Standard,
+    OkabeIto,
+    Ibm,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 3] = [Palette::Standard, Palette::OkabeIto, Palette::Ibm];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Palette::Standard => "Standard",
+            Palette::OkabeIto => "Okabe-Ito (color-blind safe)",
+            Palette::Ibm => "IBM (color-blind safe)",
+        }
+    }
+
+    /// The colors to draw with on top of `visuals`, which the standard palette takes its
+    /// own from.
+    pub fn colors(self, visuals: &egui::Visuals) -> PlotColors {
+        let input = visuals.widgets.inactive.fg_stroke.color.gamma_multiply(0.6);
+        let rgb = egui::Color32::from_rgb;
+        match self {
+            Palette::Standard => PlotColors {
+                input,
+                output: visuals.selection.bg_fill,
+                good: egui::Color32::GREEN,
+                warning: visuals.warn_fg_color,
+                error: visuals.error_fg_color,
+            },
+            Palette::OkabeIto => PlotColors {
+                input,
+                output: rgb(86, 180, 233),
+                good: rgb(0, 158, 115),
+                warning: rgb(230, 159, 0),
+                error: rgb(213, 94, 0),
+            },
+            Palette::Ibm => PlotColors {
+                input,
+                output: rgb(100, 143, 255),
+                good: rgb(100, 143, 255),
+                warning: rgb(255, 176, 0),
+                error: rgb(254, 97, 0),
+            },
+        }
+    }
+}
+
+/// What each kind of line and label is drawn in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlotColors {
+    // The unprocessed take
+    pub input: egui::Color32,
+    // The take once corrected
+    pub output: egui::Color32,
+    pub good: egui::Color32,
+    pub warning: egui::Color32,
+    pub error: egui::Color32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThemeSettings {
+    pub high_contrast: bool,
+    pub reduced_motion: bool,
+    #[serde(default)]
+    pub palette: Palette,
+}
+
+impl ThemeSettings {
+    pub fn apply(&self, ctx: &egui::Context) {
+        let mut style = (*ctx.style()).clone();
+        style.visuals = if self.high_contrast {
+            high_contrast_visuals()
+        } else {
+            egui::Visuals::dark()
+        };
+        // Warnings and errors anywhere in the window follow the palette too
+        let colors = self.palette.colors(&style.visuals);
+        style.visuals.warn_fg_color = colors.warning;
+        style.visuals.error_fg_color = colors.error;
+        style.animation_time = if self.reduced_motion {
+            0.0
+        } else {
+            egui::Style::default().animation_time
+        };
+        ctx.set_style(style);
+    }
+
+    /// Ask for the next frame while work is in progress, throttled when motion is reduced.
+    pub fn request_progress_repaint(&self, ctx: &egui::Context) {
+        if self.reduced_motion {
+            ctx.request_repaint_after(REDUCED_MOTION_REPAINT);
+        } else {
+            ctx.request_repaint();
+        }
+    }
+
+    pub fn show_spinner(&self) -> bool {
+        !self.reduced_motion
+    }
+}
+
+fn high_contrast_visuals() -> egui::Visuals {
+    let mut visuals = egui::Visuals::dark();
+    let foreground = egui::Color32::WHITE;
+    let accent = egui::Color32::from_rgb(255, 221, 0);
+
+    visuals.override_text_color = Some(foreground);
+    visuals.panel_fill = egui::Color32::BLACK;
+    visuals.window_fill = egui::Color32::BLACK;
+    visuals.extreme_bg_color = egui::Color32::BLACK;
+    visuals.faint_bg_color = egui::Color32::from_gray(24);
+    visuals.hyperlink_color = accent;
+    visuals.selection.bg_fill = accent;
+    visuals.selection.stroke = egui::Stroke::new(2.0, egui::Color32::BLACK);
+    visuals.window_stroke = egui::Stroke::new(2.0, foreground);
+
+    for widget in [
+        &mut visuals.widgets.noninteractive,
+        &mut visuals.widgets.inactive,
+        &mut visuals.widgets.hovered,
+        &mut visuals.widgets.active,
+        &mut visuals.widgets.open,
+    ] {
+        widget.bg_fill = egui::Color32::BLACK;
+        widget.weak_bg_fill = egui::Color32::BLACK;
+        widget.bg_stroke = egui::Stroke::new(1.5, foreground);
+        widget.fg_stroke = egui::Stroke::new(1.5, foreground);
+    }
+
+    visuals.widgets.hovered.bg_stroke = egui::Stroke::new(2.0, accent);
+    visuals.widgets.active.bg_stroke = egui::Stroke::new(2.0, accent);
+    visuals.widgets.active.fg_stroke = egui::Stroke::new(2.0, accent);
+
+    visuals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Machado et al.'s simulations of deuteranopia and protanopia
+    const DEUTERANOPIA: [[f32; 3]; 3] =
+        [[0.367, 0.861, -0.228], [0.280, 0.673, 0.047], [-0.012, 0.043, 0.969]];
+    const PROTANOPIA: [[f32; 3]; 3] =
+        [[0.152, 1.053, -0.205], [0.115, 0.786, 0.099], [-0.004, -0.048, 1.052]];
+
+    fn seen_apart(simulation: &[[f32; 3]; 3], a: egui::Color32, b: egui::Color32) -> f32 {
+        let seen = |color: egui::Color32| {
+            let rgb = [color.r() as f32, color.g() as f32, color.b() as f32];
+            simulation
+                .map(|row| row.iter().zip(rgb).map(|(weight, value)| weight * value).sum::<f32>())
+        };
+        let (a, b) = (seen(a), seen(b));
+        a.iter().zip(b).map(|(a, b)| (a - b).powi(2)).sum::<f32>().sqrt()
+    }
+
+    #[test]
+    fn test_color_blind_palettes_stay_distinct() {
+        let visuals = egui::Visuals::dark();
+        for palette in [Palette::OkabeIto, Palette::Ibm] {
+            let colors = palette.colors(&visuals);
+            for simulation in [&DEUTERANOPIA, &PROTANOPIA] {
+                for (a, b) in [
+                    (colors.input, colors.output),
+                    (colors.input, colors.error),
+                    (colors.output, colors.error),
+                    (colors.good, colors.error),
+                    (colors.good, colors.warning),
+                ] {
+                    assert!(seen_apart(simulation, a, b) > 80.0, "{:?}: {:?} {:?}", palette, a, b);
+                }
+            }
+        }
+        assert_eq!(Palette::Standard.colors(&visuals).warning, visuals.warn_fg_color);
+    }
+}
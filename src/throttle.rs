@@ -0,0 +1,67 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+// Shorter sleeps cost more in scheduling overhead than they save
+const MIN_SLEEP: Duration = Duration::from_millis(2);
+
+/// Limits processing to roughly `max_speed` times realtime by sleeping between chunks, so
+/// background jobs leave CPU time for other applications.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    max_speed: f64,
+    sample_rate: f64,
+    start: Instant,
+    processed_samples: u64,
+}
+
+impl Throttle {
+    pub fn new(max_speed: f32, sample_rate: f32) -> Self {
+        Self {
+            max_speed: max_speed as f64,
+            sample_rate: sample_rate as f64,
+            start: Instant::now(),
+            processed_samples: 0,
+        }
+    }
+
+    /// Record `samples` more processed (per channel) and sleep if ahead of the speed limit.
+    pub fn advance(&mut self, samples: usize) {
+        self.processed_samples += samples as u64;
+        let delay = self.delay_after(self.start.elapsed());
+        if delay >= MIN_SLEEP {
+            thread::sleep(delay);
+        }
+    }
+
+    /// How far ahead of the speed limit processing is after `elapsed`.
+    fn delay_after(&self, elapsed: Duration) -> Duration {
+        let allowed = self.processed_samples as f64 / (self.sample_rate * self.max_speed);
+        Duration::from_secs_f64(allowed).saturating_sub(elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay_after() {
+        let mut throttle = Throttle::new(2.0, 1000.0);
+        throttle.processed_samples = 1000;
+
+        // One second of audio at 2x realtime may take at least half a second
+        assert_eq!(throttle.delay_after(Duration::from_millis(100)), Duration::from_millis(400));
+        assert_eq!(throttle.delay_after(Duration::from_millis(600)), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_advance_limits_speed() {
+        let mut throttle = Throttle::new(10.0, 1000.0);
+        let start = Instant::now();
+        for _ in 0..10 {
+            throttle.advance(100);
+        }
+        // 1 second of audio at 10x realtime
+        assert!(start.elapsed() >= Duration::from_millis(95));
+    }
+}
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::f32::consts::FRAC_PI_2;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use synthphone_vocals::{AutotuneConfig, MusicalSettings};
+
+use crate::align::{self, AlignLimits};
+use crate::audio_processor::{normalize, write_output};
+use crate::effects::ChainOrder;
+use crate::engine::{Padding, Window};
+use crate::pipeline::{self, OutputLength, RenderSettings};
+use crate::pitch::PitchAlgorithm;
+use crate::{decoder, paths, resample, stretch};
+
+/// Correction settings of one clip, copied from the main settings when it is added.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipSettings {
+    pub key: i32,
+    pub note: i32,
+    pub octave: i32,
+    pub formant: i32,
+    pub strength: f32,
+    pub transition: f32,
+    // Concert pitch the notes are tuned to, in Hz
+    pub a4: f32,
+}
+
+impl ClipSettings {
+    pub fn config(&self, sample_rate: u32) -> AutotuneConfig {
+        AutotuneConfig {
+            fft_size: 1024,
+            hop_size: 256,
+            sample_rate: sample_rate as f32,
+            pitch_correction_strength: self.strength,
+            transition_speed: self.transition,
+            ..Default::default()
+        }
+    }
+
+    pub fn musical(&self) -> MusicalSettings {
+        MusicalSettings {
+            key: self.key,
+            note: self.note,
+            octave: self.octave,
+            formant: self.formant,
+        }
+    }
+
+    /// How to correct mono audio at `sample_rate` with these settings.
+    pub fn render_settings(&self, sample_rate: u32) -> RenderSettings {
+        RenderSettings {
+            config: self.config(sample_rate),
+            window: Window::default(),
+            padding: Padding::default(),
+            pitch_algorithm: PitchAlgorithm::default(),
+            f0_range: None,
+            musical: self.musical(),
+            scale: None,
+            key_changes: Vec::new(),
+            sections: Vec::new(),
+            melody: None,
+            processing_rate: sample_rate,
+            output_rate: None,
+            length: OutputLength::Input,
+            threads: 1,
+            regions: Vec::new(),
+            correct_fry: false,
+            confidence_strength: false,
+            unvoiced_sensitivity: pipeline::DEFAULT_UNVOICED_SENSITIVITY,
+            a4: self.a4,
+            energy: None,
+            chain: ChainOrder::default(),
+            high_pass_hz: None,
+            gate: None,
+            eq: None,
+            compressor: None,
+            meters: None,
+            stems: false,
+            harmony: Vec::new(),
+            harmony_level: 0.0,
+            routing: Vec::new(),
+        }
+    }
+}
+
+/// One audio file placed on the timeline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub path: PathBuf,
+    pub name: String,
+    pub offset_secs: f32,
+    pub duration_secs: f32,
+    pub sample_rate: u32,
+    pub settings: ClipSettings,
+    pub gain_db: f32,
+    pub fade_in_secs: f32,
+    pub fade_out_secs: f32,
+    // Output length over source length, with the pitch kept
+    pub stretch: f32,
+    // Index of a lead clip this one is a double of, tightened against it when rendering
+    pub align_to: Option<usize>,
+    pub align_limits: AlignLimits,
+}
+
+impl Clip {
+    /// Length on the timeline, after the time-stretch.
+    pub fn length_secs(&self) -> f32 {
+        self.duration_secs * self.stretch
+    }
+
+    pub fn end_secs(&self) -> f32 {
+        self.offset_secs + self.length_secs()
+    }
+
+    /// Keep the fades inside the clip and from overlapping each other.
+    pub fn clamp_fades(&mut self) {
+        let length = self.length_secs();
+        self.fade_in_secs = self.fade_in_secs.clamp(0.0, length);
+        self.fade_out_secs = self.fade_out_secs.clamp(0.0, length - self.fade_in_secs);
+    }
+}
+
+/// Clips placed at offsets, rendered into a single file. Clips may overlap; overlapping
+/// audio is mixed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timeline {
+    pub clips: Vec<Clip>,
+}
+
+impl Timeline {
+    /// Add `path` right after the last clip.
+    pub fn add(&mut self, path: &Path, settings: ClipSettings) -> Result<(), String> {
+        let info = decoder::probe(path)?;
+        // Compressed streams without a frame count need a full decode to measure
+        let frames = match info.frames {
+            Some(frames) => frames as usize,
+            None => decoder::read_mono(path, usize::MAX)?.0.len(),
+        };
+
+        self.clips.push(Clip {
+            path: path.to_path_buf(),
+            name: paths::display_name(path),
+            offset_secs: self.duration_secs(),
+            duration_secs: frames as f32 / info.sample_rate as f32,
+            sample_rate: info.sample_rate,
+            settings,
+            gain_db: 0.0,
+            fade_in_secs: 0.0,
+            fade_out_secs: 0.0,
+            stretch: 1.0,
+            align_to: None,
+            align_limits: AlignLimits::default(),
+        });
+        Ok(())
+    }
+
+    /// Remove clip `index`. Doubles aligned to it are no longer aligned.
+    pub fn remove(&mut self, index: usize) {
+        self.clips.remove(index);
+        for clip in &mut self.clips {
+            clip.align_to = match clip.align_to {
+                Some(lead) if lead == index => None,
+                Some(lead) if lead > index => Some(lead - 1),
+                lead => lead,
+            };
+        }
+    }
+
+    /// Whether any clip is aligned to clip `index`.
+    pub fn is_lead(&self, index: usize) -> bool {
+        self.clips.iter().any(|clip| clip.align_to == Some(index))
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.clips.iter().map(Clip::end_secs).fold(0.0, f32::max)
+    }
+
+    /// The timeline renders at the highest clip rate, so no clip loses bandwidth.
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.clips.iter().map(|clip| clip.sample_rate).max()
+    }
+}
+
+pub enum TimelineEvent {
+    Progress(f32), // 0.0 to 1.0
+    Done(TimelineRender),
+    Failed(String),
+}
+
+/// Outcome of a finished timeline render.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineRender {
+    pub duration_secs: f32,
+    pub failed_frames: usize,
+}
+
+/// Correct every clip with its own settings, stretch it to its length on the timeline,
+/// tighten doubles against their lead, place each clip at its offset, and write the mix to
+/// `output`. `on_progress` gets 0.0 to 1.0; setting `cancel` stops without writing.
+pub fn render(
+    timeline: &Timeline,
+    output: &Path,
+    cancel: &AtomicBool,
+    mut on_progress: impl FnMut(f32),
+) -> Result<TimelineRender, String> {
+    let sample_rate = timeline.sample_rate().ok_or("The timeline is empty")?;
+    let total_secs: f32 = timeline.clips.iter().map(|clip| clip.duration_secs).sum();
+    let mut done_secs = 0.0;
+    let mut mix = Vec::new();
+    let mut failed_frames = 0;
+
+    // Leads go first, so their doubles can be aligned to the corrected lead
+    let mut order: Vec<usize> = (0..timeline.clips.len()).collect();
+    order.sort_by_key(|&index| timeline.clips[index].align_to.is_some());
+    let mut leads: HashMap<usize, Vec<f32>> = HashMap::new();
+
+    for index in order {
+        let clip = &timeline.clips[index];
+        let (samples, info) = decoder::read_mono(&clip.path, usize::MAX)?;
+        let samples = if info.sample_rate == sample_rate {
+            samples
+        } else {
+            let len = resample::resampled_len(samples.len(), info.sample_rate, sample_rate);
+            resample::resample(&samples, info.sample_rate, sample_rate, len)
+        };
+
+        let settings = clip.settings.render_settings(sample_rate);
+        let processed = pipeline::process_samples(&samples, &settings, |clip_done| {
+            on_progress((done_secs + clip_done * clip.duration_secs) / total_secs);
+            if cancel.load(Ordering::Relaxed) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        })?;
+        if processed.samples.len() < samples.len() {
+            return Err("Timeline render stopped".to_string());
+        }
+
+        for (position, e) in &processed.failed_frames {
+            log::warn!("Processing error in {} at sample {}: {}", clip.name, position, e);
+        }
+        failed_frames += processed.failed_frames.len();
+        let mut clip_audio = stretch::time_stretch(&processed.samples, clip.stretch);
+        let to_samples = |secs: f32| (secs * sample_rate as f32) as usize;
+
+        match clip.align_to.map(|lead| (lead, leads.get(&lead))) {
+            Some((lead, Some(lead_audio))) => {
+                let lead_clip = &timeline.clips[lead];
+                // The part of the lead under this clip on the timeline
+                let start =
+                    ((clip.offset_secs - lead_clip.offset_secs) * sample_rate as f32) as isize;
+                let under = segment(lead_audio, start, clip_audio.len());
+                let (aligned, report) =
+                    align::align_double(&under, &clip_audio, sample_rate, &clip.align_limits);
+                log::info!(
+                    "Aligned {} to {}: {:+.1} ms mean shift, {:.1} ms max",
+                    clip.name,
+                    lead_clip.name,
+                    report.mean_shift_secs * 1000.0,
+                    report.max_shift_secs * 1000.0
+                );
+                log::info!(
+                    "Pitch-matched {:.1}s of {} by {:.0} cents on average",
+                    report.pitch_matched_secs,
+                    clip.name,
+                    report.mean_cents
+                );
+                clip_audio = aligned;
+            }
+            Some((lead, None)) => {
+                log::warn!(
+                    "{} is not aligned, its lead (clip {}) is itself a double",
+                    clip.name,
+                    lead + 1
+                );
+            }
+            None => {}
+        }
+        if timeline.is_lead(index) {
+            leads.insert(index, clip_audio.clone());
+        }
+
+        apply_envelope(
+            &mut clip_audio,
+            10f32.powf(clip.gain_db / 20.0),
+            to_samples(clip.fade_in_secs),
+            to_samples(clip.fade_out_secs),
+        );
+        place(&mut mix, &clip_audio, to_samples(clip.offset_secs));
+        done_secs += clip.duration_secs;
+    }
+
+    normalize(&mut mix);
+    let spec = hound::WavSpec {
+        channels: 1,
+        sample_rate,
+        bits_per_sample: 24,
+        sample_format: hound::SampleFormat::Int,
+    };
+    write_output(output, spec, &mix)?;
+
+    Ok(TimelineRender { duration_secs: mix.len() as f32 / sample_rate as f32, failed_frames })
+}
+
+/// Scale by `gain`, with quarter-sine fades over the first `fade_in` and last `fade_out`
+/// samples.
+fn apply_envelope(samples: &mut [f32], gain: f32, fade_in: usize, fade_out: usize) {
+    let len = samples.len();
+    let fade_in = fade_in.min(len);
+    let fade_out = fade_out.min(len - fade_in);
+    let curve = |i: usize, fade: usize| (FRAC_PI_2 * (i as f32 + 0.5) / fade as f32).sin();
+
+    for (i, sample) in samples.iter_mut().enumerate() {
+        let mut envelope = gain;
+        if i < fade_in {
+            envelope *= curve(i, fade_in);
+        }
+        if i >= len - fade_out {
+            envelope *= curve(len - 1 - i, fade_out);
+        }
+        *sample *= envelope;
+    }
+}
+
+/// `len` samples of `audio` from `start`, which may lie outside it, zero-padded.
+fn segment(audio: &[f32], start: isize, len: usize) -> Vec<f32> {
+    (0..len)
+        .map(|i| {
+            usize::try_from(start + i as isize).ok().and_then(|j| audio.get(j)).map_or(0.0, |&s| s)
+        })
+        .collect()
+}
+
+/// Mix `clip` into `mix` starting at sample `offset`, growing `mix` as needed.
+fn place(mix: &mut Vec<f32>, clip: &[f32], offset: usize) {
+    if mix.len() < offset + clip.len() {
+        mix.resize(offset + clip.len(), 0.0);
+    }
+    for (dst, &sample) in mix[offset..].iter_mut().zip(clip) {
+        *dst += sample;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clip(offset_secs: f32, duration_secs: f32, sample_rate: u32) -> Clip {
+        Clip {
+            path: PathBuf::from("take.wav"),
+            name: "take.wav".to_string(),
+            offset_secs,
+            duration_secs,
+            sample_rate,
+            settings: ClipSettings {
+                key: 0,
+                note: 0,
+                octave: 2,
+                formant: 0,
+                strength: 0.8,
+                transition: 0.1,
+                a4: pipeline::STANDARD_A4,
+            },
+            gain_db: 0.0,
+            fade_in_secs: 0.0,
+            fade_out_secs: 0.0,
+            stretch: 1.0,
+            align_to: None,
+            align_limits: AlignLimits::default(),
+        }
+    }
+
+    #[test]
+    fn test_envelope_gain_and_fades() {
+        let mut samples = vec![1.0; 10];
+        apply_envelope(&mut samples, 0.5, 4, 2);
+        // Rising over the fade-in, flat at the gain, falling over the fade-out
+        assert!(samples[..4].windows(2).all(|w| w[0] < w[1]));
+        assert!(samples[0] > 0.0 && samples[3] < 0.5);
+        assert_eq!(&samples[4..8], &[0.5; 4]);
+        assert!(samples[8] > samples[9] && samples[9] > 0.0);
+
+        // Fades longer than the clip are cut to fit
+        let mut short = vec![1.0; 3];
+        apply_envelope(&mut short, 1.0, 10, 10);
+        assert!(short.windows(2).all(|w| w[0] < w[1]));
+    }
+
+    #[test]
+    fn test_clamp_fades() {
+        let mut clip = clip(0.0, 2.0, 44_100);
+        clip.fade_in_secs = 1.5;
+        clip.fade_out_secs = 1.0;
+        clip.clamp_fades();
+        assert_eq!((clip.fade_in_secs, clip.fade_out_secs), (1.5, 0.5));
+    }
+
+    #[test]
+    fn test_place_mixes_overlapping_clips() {
+        let mut mix = Vec::new();
+        place(&mut mix, &[1.0, 1.0, 1.0], 2);
+        place(&mut mix, &[0.5, 0.5], 4);
+        assert_eq!(mix, vec![0.0, 0.0, 1.0, 1.0, 1.5, 0.5]);
+    }
+
+    #[test]
+    fn test_segment_pads_outside_audio() {
+        assert_eq!(segment(&[1.0, 2.0, 3.0], -1, 3), vec![0.0, 1.0, 2.0]);
+        assert_eq!(segment(&[1.0, 2.0, 3.0], 2, 3), vec![3.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_remove_keeps_alignments() {
+        let mut timeline = Timeline { clips: vec![clip(0.0, 1.0, 44_100); 4] };
+        timeline.clips[1].align_to = Some(0);
+        timeline.clips[3].align_to = Some(2);
+        assert!(timeline.is_lead(2) && !timeline.is_lead(1));
+
+        timeline.remove(0);
+        assert_eq!(timeline.clips[0].align_to, None);
+        assert_eq!(timeline.clips[2].align_to, Some(1));
+        assert!(timeline.is_lead(1));
+    }
+
+    #[test]
+    fn test_timeline_extent() {
+        let timeline = Timeline {
+            clips: vec![clip(0.0, 2.0, 44_100), clip(5.0, 1.5, 48_000), clip(1.0, 1.0, 22_050)],
+        };
+        assert_eq!(timeline.duration_secs(), 6.5);
+        let mut stretched = timeline.clone();
+        stretched.clips[1].stretch = 2.0;
+        assert_eq!(stretched.duration_secs(), 8.0);
+        assert_eq!(timeline.sample_rate(), Some(48_000));
+        assert_eq!(Timeline::default().sample_rate(), None);
+    }
+}
@@ -0,0 +1,354 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use crate::decoder::MonoReader;
+use crate::stretch::fft;
+
+// Analysis frames of the long-term spectrum, half overlapped
+const MEASURE_SIZE: usize = 4096;
+// Frames quieter than this (RMS) are breaths and room noise, not the voice's tone
+const SILENCE_RMS: f32 = 1e-3;
+// Third-octave bands from 25Hz up to 20kHz
+const BANDS_PER_OCTAVE: f32 = 3.0;
+const LOWEST_BAND_HZ: f32 = 25.0;
+const BAND_COUNT: usize = 30;
+// Only bands where a voice has energy are matched, the rest are left alone
+const MATCH_LOW_HZ: f32 = 80.0;
+const MATCH_HIGH_HZ: f32 = 12_000.0;
+// Largest boost or cut of any band, so a band missing from one recording isn't chased
+const MAX_GAIN_DB: f32 = 12.0;
+// Length of the matching filter, and the FFT size it is applied with
+const FILTER_TAPS: usize = 2048;
+const CONVOLUTION_SIZE: usize = 8192;
+// Frames read per block when measuring a file
+const READ_BLOCK: usize = 1 << 16;
+
+fn band_center(band: usize) -> f32 {
+    LOWEST_BAND_HZ * 2f32.powf(band as f32 / BANDS_PER_OCTAVE)
+}
+
+/// Long-term average spectrum of a recording in third-octave bands, the tone it has
+/// regardless of what is sung.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    // Mean power in dB of each band, None above the recording's Nyquist frequency
+    levels: Vec<Option<f32>>,
+}
+
+impl Spectrum {
+    /// Measure the decoded file at `path`.
+    pub fn measure_file(path: &Path) -> Result<Self, String> {
+        let mut reader = MonoReader::open(path)?;
+        let mut meter = SpectrumMeter::new(reader.info().sample_rate);
+        let mut block = Vec::with_capacity(READ_BLOCK);
+        loop {
+            block.clear();
+            if reader.read(&mut block, READ_BLOCK)? == 0 {
+                break;
+            }
+            meter.push(&block);
+        }
+        meter.finish().ok_or_else(|| format!("{} has no audible audio", path.display()))
+    }
+
+    /// Gain in dB per band that gives this spectrum the tint of `reference`, `amount`
+    /// (0.0 to 1.0) of the way. The overall level is left as it is.
+    pub fn matching_gains(&self, reference: &Spectrum, amount: f32) -> Vec<f32> {
+        let differences: Vec<Option<f32>> = (0..BAND_COUNT)
+            .map(|band| {
+                let center = band_center(band);
+                if !(MATCH_LOW_HZ..=MATCH_HIGH_HZ).contains(&center) {
+                    return None;
+                }
+                Some(reference.levels[band]? - self.levels[band]?)
+            })
+            .collect();
+        let matched: Vec<f32> = differences.iter().flatten().copied().collect();
+        if matched.is_empty() {
+            return vec![0.0; BAND_COUNT];
+        }
+        let offset = matched.iter().sum::<f32>() / matched.len() as f32;
+        differences
+            .iter()
+            .map(|difference| {
+                difference.map_or(0.0, |difference| {
+                    (difference - offset).clamp(-MAX_GAIN_DB, MAX_GAIN_DB) * amount.clamp(0.0, 1.0)
+                })
+            })
+            .collect()
+    }
+}
+
+/// Builds a [`Spectrum`] from audio pushed a block at a time.
+pub struct SpectrumMeter {
+    sample_rate: u32,
+    window: Vec<f32>,
+    pending: Vec<f32>,
+    // Summed power of each FFT bin over the audible frames
+    power: Vec<f64>,
+    frames: usize,
+}
+
+impl SpectrumMeter {
+    pub fn new(sample_rate: u32) -> Self {
+        let window = (0..MEASURE_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / MEASURE_SIZE as f32).cos())
+            .collect();
+        Self {
+            sample_rate,
+            window,
+            pending: Vec::with_capacity(2 * MEASURE_SIZE),
+            power: vec![0.0; MEASURE_SIZE / 2 + 1],
+            frames: 0,
+        }
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        self.pending.extend_from_slice(samples);
+        let mut start = 0;
+        let mut re = vec![0.0; MEASURE_SIZE];
+        let mut im = vec![0.0; MEASURE_SIZE];
+        while start + MEASURE_SIZE <= self.pending.len() {
+            let frame = &self.pending[start..start + MEASURE_SIZE];
+            start += MEASURE_SIZE / 2;
+            let rms = (frame.iter().map(|s| s * s).sum::<f32>() / MEASURE_SIZE as f32).sqrt();
+            if rms < SILENCE_RMS {
+                continue;
+            }
+            for (i, (re, im)) in re.iter_mut().zip(&mut im).enumerate() {
+                *re = frame[i] * self.window[i];
+                *im = 0.0;
+            }
+            fft(&mut re, &mut im, false);
+            for (bin, power) in self.power.iter_mut().enumerate() {
+                *power += (re[bin] * re[bin] + im[bin] * im[bin]) as f64;
+            }
+            self.frames += 1;
+        }
+        self.pending.drain(..start);
+    }
+
+    /// The spectrum of everything pushed, or None if it was all silent.
+    pub fn finish(self) -> Option<Spectrum> {
+        if self.frames == 0 {
+            return None;
+        }
+        let bin_hz = self.sample_rate as f32 / MEASURE_SIZE as f32;
+        let nyquist = self.sample_rate as f32 / 2.0;
+        let levels = (0..BAND_COUNT)
+            .map(|band| {
+                let center = band_center(band);
+                let edge = 2f32.powf(0.5 / BANDS_PER_OCTAVE);
+                if center * edge > nyquist {
+                    return None;
+                }
+                let low = ((center / edge / bin_hz).ceil() as usize).max(1);
+                let high = ((center * edge / bin_hz).floor() as usize).min(self.power.len() - 1);
+                // Low bands can fall between two bins, those take the nearest one
+                let bins = if low <= high {
+                    low..=high
+                } else {
+                    let nearest = (center / bin_hz).round() as usize;
+                    nearest..=nearest
+                };
+                let count = bins.clone().count() as f64;
+                let power = bins.map(|bin| self.power[bin]).sum::<f64>() / count;
+                Some(10.0 * ((power / self.frames as f64) as f32 + 1e-12).log10())
+            })
+            .collect();
+        Some(Spectrum { levels })
+    }
+}
+
+/// Linear-phase FIR filter applying per-band gains, run a block at a time by FFT
+/// convolution. Its delay is compensated, so the output lines up with the input.
+pub struct TintFilter {
+    // Spectrum of the filter, at CONVOLUTION_SIZE
+    response_re: Vec<f32>,
+    response_im: Vec<f32>,
+    pending: Vec<f32>,
+    // Tail of the last block, added to the start of the next
+    overlap: Vec<f32>,
+    // Output still to drop for the filter's delay
+    delay: usize,
+    read: usize,
+    written: usize,
+}
+
+impl TintFilter {
+    /// Filter for audio at `sample_rate`, from gains in dB per band as returned by
+    /// [`Spectrum::matching_gains`].
+    pub fn new(gains: &[f32], sample_rate: u32) -> Self {
+        let half = FILTER_TAPS / 2;
+        let bin_hz = sample_rate as f32 / FILTER_TAPS as f32;
+        let bands = gains.len().min(BAND_COUNT);
+        // Gains are interpolated in dB over log frequency between band centers
+        let gain_at = |frequency: f32| -> f32 {
+            if bands == 0 {
+                return 0.0;
+            }
+            let position = BANDS_PER_OCTAVE * (frequency.max(1.0) / LOWEST_BAND_HZ).log2();
+            let position = position.clamp(0.0, (bands - 1) as f32);
+            let below = position.floor() as usize;
+            let above = (below + 1).min(bands - 1);
+            let fraction = position - below as f32;
+            gains[below] + (gains[above] - gains[below]) * fraction
+        };
+
+        // Zero-phase response, turned into a windowed kernel centered on `half`
+        let mut re: Vec<f32> = (0..FILTER_TAPS)
+            .map(|bin| {
+                let mirrored = bin.min(FILTER_TAPS - bin);
+                10f32.powf(gain_at(mirrored as f32 * bin_hz) / 20.0)
+            })
+            .collect();
+        let mut im = vec![0.0; FILTER_TAPS];
+        fft(&mut re, &mut im, true);
+
+        let mut response_re = vec![0.0; CONVOLUTION_SIZE];
+        let mut response_im = vec![0.0; CONVOLUTION_SIZE];
+        for (n, tap) in response_re.iter_mut().take(FILTER_TAPS).enumerate() {
+            let window = 0.5 - 0.5 * (2.0 * PI * n as f32 / FILTER_TAPS as f32).cos();
+            *tap = re[(n + half) % FILTER_TAPS] * window;
+        }
+        fft(&mut response_re, &mut response_im, false);
+
+        Self {
+            response_re,
+            response_im,
+            pending: Vec::new(),
+            overlap: vec![0.0; FILTER_TAPS - 1],
+            delay: half,
+            read: 0,
+            written: 0,
+        }
+    }
+
+    /// Filter `samples`, appending what is ready to `output`.
+    pub fn process(&mut self, samples: &[f32], output: &mut Vec<f32>) {
+        let block = CONVOLUTION_SIZE - FILTER_TAPS + 1;
+        self.read += samples.len();
+        self.pending.extend_from_slice(samples);
+        let mut start = 0;
+        while start + block <= self.pending.len() {
+            let input = self.pending[start..start + block].to_vec();
+            self.convolve(&input, output);
+            start += block;
+        }
+        self.pending.drain(..start);
+    }
+
+    /// Filter what is left and end the output as long as the input.
+    pub fn finish(&mut self, output: &mut Vec<f32>) {
+        let input = std::mem::take(&mut self.pending);
+        self.convolve(&input, output);
+        let tail = std::mem::take(&mut self.overlap);
+        self.emit(&tail, output);
+    }
+
+    fn convolve(&mut self, input: &[f32], output: &mut Vec<f32>) {
+        let mut re = vec![0.0; CONVOLUTION_SIZE];
+        let mut im = vec![0.0; CONVOLUTION_SIZE];
+        re[..input.len()].copy_from_slice(input);
+        fft(&mut re, &mut im, false);
+        for bin in 0..CONVOLUTION_SIZE {
+            let (a, b) = (re[bin], im[bin]);
+            let (c, d) = (self.response_re[bin], self.response_im[bin]);
+            re[bin] = a * c - b * d;
+            im[bin] = a * d + b * c;
+        }
+        fft(&mut re, &mut im, true);
+
+        for (sample, overlap) in re.iter_mut().zip(&self.overlap) {
+            *sample += overlap;
+        }
+        let tail = FILTER_TAPS - 1;
+        self.overlap.clear();
+        self.overlap.extend_from_slice(&re[input.len()..input.len() + tail]);
+        self.emit(&re[..input.len()], output);
+    }
+
+    fn emit(&mut self, samples: &[f32], output: &mut Vec<f32>) {
+        let skip = self.delay.min(samples.len());
+        self.delay -= skip;
+        let take = (samples.len() - skip).min(self.read - self.written);
+        output.extend_from_slice(&samples[skip..skip + take]);
+        self.written += take;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 44_100;
+
+    fn noise(len: usize) -> Vec<f32> {
+        let mut state = 12345u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    fn spectrum(samples: &[f32]) -> Spectrum {
+        let mut meter = SpectrumMeter::new(RATE);
+        meter.push(samples);
+        meter.finish().unwrap()
+    }
+
+    fn filter(samples: &[f32], gains: &[f32]) -> Vec<f32> {
+        let mut filter = TintFilter::new(gains, RATE);
+        let mut output = Vec::new();
+        // In uneven blocks, as they come from a file
+        for block in samples.chunks(5000) {
+            filter.process(block, &mut output);
+        }
+        filter.finish(&mut output);
+        output
+    }
+
+    // Level of the band around 4kHz over the one around 400Hz
+    fn brightness(spectrum: &Spectrum) -> f32 {
+        spectrum.levels[22].unwrap() - spectrum.levels[12].unwrap()
+    }
+
+    #[test]
+    fn test_flat_filter_passes_audio_unchanged() {
+        let input = noise(20_000);
+        let output = filter(&input, &[0.0; BAND_COUNT]);
+        assert_eq!(output.len(), input.len());
+        assert!(input.iter().zip(&output).all(|(a, b)| (a - b).abs() < 1e-4));
+
+        let mut meter = SpectrumMeter::new(RATE);
+        meter.push(&[0.0; 10_000]);
+        assert!(meter.finish().is_none());
+    }
+
+    #[test]
+    fn test_dull_take_is_brightened_toward_the_reference() {
+        let reference = noise(RATE as usize);
+        // One-pole lowpass, a take recorded on a darker mic
+        let mut last = 0.0;
+        let take: Vec<f32> = reference
+            .iter()
+            .map(|&s| {
+                last += 0.1 * (s - last);
+                last
+            })
+            .collect();
+        let (reference, before) = (spectrum(&reference), spectrum(&take));
+        assert!(brightness(&reference) - brightness(&before) > 10.0);
+
+        let gains = before.matching_gains(&reference, 1.0);
+        assert!(gains.iter().all(|gain| gain.abs() <= MAX_GAIN_DB));
+        let after = spectrum(&filter(&take, &gains));
+        assert!((brightness(&reference) - brightness(&after)).abs() < 3.0);
+
+        let half = spectrum(&filter(&take, &before.matching_gains(&reference, 0.5)));
+        assert!(brightness(&half) > brightness(&before) + 3.0);
+        assert!(brightness(&half) < brightness(&after) - 3.0);
+    }
+}
@@ -0,0 +1,189 @@
+use serde::Deserialize;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const RELEASES_URL: &str =
+    "https://api.github.com/repos/nathansbradshaw/desktop-autotune-app/releases/latest";
+pub const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    pub name: Option<String>,
+    pub body: Option<String>,
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub enum UpdateEvent {
+    Available(Release),
+    UpToDate,
+    Downloaded(PathBuf),
+    Failed(String),
+}
+
+impl Release {
+    pub fn title(&self) -> &str {
+        self.name.as_deref().filter(|name| !name.is_empty()).unwrap_or(&self.tag_name)
+    }
+
+    pub fn changelog(&self) -> &str {
+        self.body.as_deref().unwrap_or("No changelog provided.")
+    }
+
+    /// Pick the download matching this OS (and architecture, when the asset names say so).
+    pub fn asset_for_platform(&self) -> Option<&ReleaseAsset> {
+        select_asset(&self.assets, std::env::consts::OS, std::env::consts::ARCH)
+    }
+}
+
+fn select_asset<'a>(assets: &'a [ReleaseAsset], os: &str, arch: &str) -> Option<&'a ReleaseAsset> {
+    let os_keywords: &[&str] = match os {
+        "windows" => &["windows", "win64", "msvc"],
+        "macos" => &["macos", "darwin", "apple"],
+        "linux" => &["linux"],
+        _ => return None,
+    };
+
+    let matching_os = assets.iter().filter(|asset| {
+        let name = asset.name.to_lowercase();
+        os_keywords.iter().any(|keyword| name.contains(keyword))
+    });
+
+    let mut fallback = None;
+    for asset in matching_os {
+        if asset.name.to_lowercase().contains(arch) {
+            return Some(asset);
+        }
+        fallback.get_or_insert(asset);
+    }
+    fallback
+}
+
+/// Query the release feed and report whether a newer version exists.
+pub fn check_for_update() -> UpdateEvent {
+    match fetch_latest() {
+        Ok(release) if is_newer(CURRENT_VERSION, &release.tag_name) => {
+            UpdateEvent::Available(release)
+        }
+        Ok(_) => UpdateEvent::UpToDate,
+        Err(e) => UpdateEvent::Failed(e),
+    }
+}
+
+fn fetch_latest() -> Result<Release, String> {
+    let body = ureq::get(RELEASES_URL)
+        .set("User-Agent", concat!("desktop-autotune-app/", env!("CARGO_PKG_VERSION")))
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .map_err(|e| format!("Update check failed: {}", e))?
+        .into_string()
+        .map_err(|e| format!("Update check failed: {}", e))?;
+
+    serde_json::from_str(&body).map_err(|e| format!("Unexpected release feed format: {}", e))
+}
+
+/// Download a release asset into `dest_dir`.
+pub fn download_asset(asset: &ReleaseAsset, dest_dir: &Path) -> UpdateEvent {
+    let result = (|| -> Result<PathBuf, String> {
+        let response = ureq::get(&asset.browser_download_url)
+            .call()
+            .map_err(|e| format!("Download failed: {}", e))?;
+
+        std::fs::create_dir_all(dest_dir)
+            .map_err(|e| format!("Failed to create {}: {}", dest_dir.display(), e))?;
+
+        // Asset names come from the network, never let them escape the download directory
+        let file_name = Path::new(&asset.name).file_name().ok_or("Invalid asset name")?;
+        let path = dest_dir.join(file_name);
+        let mut file = File::create(&path)
+            .map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+
+        io::copy(&mut response.into_reader(), &mut file)
+            .map_err(|e| format!("Download interrupted: {}", e))?;
+        Ok(path)
+    })();
+
+    match result {
+        Ok(path) => UpdateEvent::Downloaded(path),
+        Err(e) => UpdateEvent::Failed(e),
+    }
+}
+
+/// Compare dotted version numbers, ignoring a leading `v` and any pre-release suffix.
+pub fn is_newer(current: &str, candidate: &str) -> bool {
+    fn parse(version: &str) -> Vec<u64> {
+        version
+            .trim()
+            .trim_start_matches(['v', 'V'])
+            .split(['-', '+'])
+            .next()
+            .unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    }
+
+    let (current, candidate) = (parse(current), parse(candidate));
+    let len = current.len().max(candidate.len());
+    let padded = |v: &[u64], i: usize| v.get(i).copied().unwrap_or(0);
+
+    for i in 0..len {
+        match padded(&candidate, i).cmp(&padded(&current, i)) {
+            std::cmp::Ordering::Greater => return true,
+            std::cmp::Ordering::Less => return false,
+            std::cmp::Ordering::Equal => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn asset(name: &str) -> ReleaseAsset {
+        ReleaseAsset {
+            name: name.to_string(),
+            browser_download_url: format!("https://example.com/{}", name),
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.1.0", "v0.2.0"));
+        assert!(is_newer("0.1.0", "0.1.1"));
+        assert!(is_newer("0.1", "0.1.1"));
+        assert!(!is_newer("0.1.0", "v0.1.0"));
+        assert!(!is_newer("0.2.0", "0.1.9"));
+        assert!(!is_newer("0.1.0", "0.1.0-beta"));
+    }
+
+    #[test]
+    fn test_select_asset() {
+        let assets = vec![
+            asset("autotune-windows-x86_64.zip"),
+            asset("autotune-macOS.dmg"),
+            asset("autotune-linux-x86_64.tar.gz"),
+            asset("autotune-linux-aarch64.tar.gz"),
+        ];
+
+        let name = |os, arch| select_asset(&assets, os, arch).map(|a| a.name.as_str());
+        assert_eq!(name("linux", "aarch64"), Some("autotune-linux-aarch64.tar.gz"));
+        assert_eq!(name("linux", "riscv64"), Some("autotune-linux-x86_64.tar.gz"));
+        assert_eq!(name("macos", "aarch64"), Some("autotune-macOS.dmg"));
+        assert_eq!(name("windows", "x86_64"), Some("autotune-windows-x86_64.zip"));
+        assert_eq!(name("freebsd", "x86_64"), None);
+    }
+}
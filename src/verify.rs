@@ -0,0 +1,251 @@
+use std::f32::consts::PI;
+use std::path::Path;
+
+use crate::loudness::LoudnessMeter;
+use crate::{paths, pcm};
+
+/// Highest acceptable true peak (0 dBTP).
+pub const TRUE_PEAK_CEILING: f32 = 1.0;
+
+// True peak is estimated by 4x oversampling with a windowed-sinc interpolator
+const OVERSAMPLE: usize = 4;
+const HALF_TAPS: usize = 6;
+
+/// What re-reading a written output file found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Verification {
+    pub expected_frames: usize,
+    pub frames: usize,
+    pub sample_peak: f32,
+    pub true_peak: f32,
+    // Integrated loudness in LUFS, None for a file too short or quiet to measure
+    pub loudness: Option<f32>,
+    // NaN/inf in the rendered audio, which integer PCM silently turns into zeros
+    pub non_finite: usize,
+}
+
+impl Verification {
+    /// Signs of a broken file, which should not be delivered.
+    pub fn errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if self.frames != self.expected_frames {
+            errors.push(format!(
+                "output has {} frames, expected {}",
+                self.frames, self.expected_frames
+            ));
+        }
+        if self.non_finite > 0 {
+            errors.push(format!("{} NaN/inf samples in the rendered audio", self.non_finite));
+        }
+        errors
+    }
+
+    /// Problems that leave the file usable but may need attention.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if self.true_peak > TRUE_PEAK_CEILING {
+            warnings.push(format!(
+                "true peak {:+.1} dBTP is above the {:+.1} dBTP ceiling",
+                to_db(self.true_peak),
+                to_db(TRUE_PEAK_CEILING)
+            ));
+        }
+        warnings
+    }
+
+    /// Sample peak in dBFS.
+    pub fn peak_db(&self) -> f32 {
+        to_db(self.sample_peak)
+    }
+
+    /// True peak in dBTP.
+    pub fn true_peak_db(&self) -> f32 {
+        to_db(self.true_peak)
+    }
+
+    pub fn summary(&self) -> String {
+        let problems: Vec<String> = self.errors().into_iter().chain(self.warnings()).collect();
+        if problems.is_empty() {
+            let loudness =
+                self.loudness.map_or(String::new(), |lufs| format!(", {:.1} LUFS", lufs));
+            format!(
+                "verified, peak {:+.1} dBFS, true peak {:+.1} dBTP{}",
+                self.peak_db(),
+                self.true_peak_db(),
+                loudness
+            )
+        } else {
+            problems.join("; ")
+        }
+    }
+}
+
+/// Count NaN/inf samples, to be passed on to [`verify_output`].
+pub fn count_non_finite(samples: &[f32]) -> usize {
+    samples.iter().filter(|sample| !sample.is_finite()).count()
+}
+
+/// Re-open a written WAV and check its length and peaks, reading it sample by sample.
+pub fn verify_output(
+    path: &Path,
+    expected_frames: usize,
+    non_finite: usize,
+) -> Result<Verification, String> {
+    let mut reader =
+        paths::open_wav(path).map_err(|e| format!("Failed to re-open output file: {}", e))?;
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+
+    let samples: Box<dyn Iterator<Item = Result<f32, hound::Error>>> = match spec.sample_format {
+        hound::SampleFormat::Float => Box::new(reader.samples::<f32>()),
+        hound::SampleFormat::Int => {
+            let scale = pcm::input_scale(spec.bits_per_sample).unwrap_or(0.0);
+            Box::new(reader.samples::<i32>().map(move |sample| sample.map(|s| s as f32 * scale)))
+        }
+    };
+
+    let mut meters: Vec<TruePeakMeter> = (0..channels).map(|_| TruePeakMeter::default()).collect();
+    let mut loudness = LoudnessMeter::new(spec.sample_rate, spec.channels);
+    let mut count = 0;
+    let mut found_non_finite = 0;
+    for sample in samples {
+        let sample = sample.map_err(|e| format!("Failed to re-read output file: {}", e))?;
+        if !sample.is_finite() {
+            found_non_finite += 1;
+        }
+        let sample = if sample.is_finite() { sample } else { 0.0 };
+        meters[count % channels].push(sample);
+        loudness.push(&[sample]);
+        count += 1;
+    }
+
+    let mut verification = Verification {
+        expected_frames,
+        frames: count / channels,
+        sample_peak: 0.0,
+        true_peak: 0.0,
+        loudness: loudness.finish(),
+        non_finite: non_finite + found_non_finite,
+    };
+    for meter in meters {
+        verification.sample_peak = verification.sample_peak.max(meter.sample_peak);
+        verification.true_peak = verification.true_peak.max(meter.finish());
+    }
+    Ok(verification)
+}
+
+/// Peak including the inter-sample peaks a DAC would reconstruct, measured one sample at
+/// a time so a file never has to be loaded whole.
+pub struct TruePeakMeter {
+    taps: [[f32; 2 * HALF_TAPS]; OVERSAMPLE - 1],
+    // The last 2 * HALF_TAPS samples, zero before the start and past the end of the signal
+    history: [f32; 2 * HALF_TAPS],
+    received: usize,
+    // Including the zeros shifted in by `finish`
+    shifted: usize,
+    sample_peak: f32,
+    peak: f32,
+}
+
+impl Default for TruePeakMeter {
+    fn default() -> Self {
+        Self {
+            taps: interpolation_taps(),
+            history: [0.0; 2 * HALF_TAPS],
+            received: 0,
+            shifted: 0,
+            sample_peak: 0.0,
+            peak: 0.0,
+        }
+    }
+}
+
+impl TruePeakMeter {
+    pub fn push(&mut self, sample: f32) {
+        self.sample_peak = self.sample_peak.max(sample.abs());
+        self.peak = self.peak.max(sample.abs());
+        self.received += 1;
+        self.shift_in(sample);
+    }
+
+    /// Interpolate the last few sample gaps, which need samples past the end, and return
+    /// the true peak.
+    pub fn finish(mut self) -> f32 {
+        while self.shifted + 1 < self.received + HALF_TAPS {
+            self.shift_in(0.0);
+        }
+        self.peak
+    }
+
+    fn shift_in(&mut self, sample: f32) {
+        self.history.copy_within(1.., 0);
+        self.history[2 * HALF_TAPS - 1] = sample;
+        self.shifted += 1;
+
+        // The history now covers samples i - HALF_TAPS + 1 ..= i + HALF_TAPS around the gap
+        // between samples i and i + 1
+        if self.shifted > HALF_TAPS {
+            for phase_taps in &self.taps {
+                let value: f32 = phase_taps.iter().zip(&self.history).map(|(tap, x)| tap * x).sum();
+                self.peak = self.peak.max(value.abs());
+            }
+        }
+    }
+}
+
+// Hann-windowed sinc taps for the fractional positions 1/4, 2/4, and 3/4
+fn interpolation_taps() -> [[f32; 2 * HALF_TAPS]; OVERSAMPLE - 1] {
+    let mut taps = [[0.0; 2 * HALF_TAPS]; OVERSAMPLE - 1];
+    for (phase, phase_taps) in taps.iter_mut().enumerate() {
+        let fraction = (phase + 1) as f32 / OVERSAMPLE as f32;
+        for (k, tap) in phase_taps.iter_mut().enumerate() {
+            let t = k as f32 + 1.0 - HALF_TAPS as f32 - fraction;
+            let sinc = (PI * t).sin() / (PI * t);
+            let window = 0.5 + 0.5 * (PI * t / HALF_TAPS as f32).cos();
+            *tap = sinc * window;
+        }
+    }
+    taps
+}
+
+fn to_db(amplitude: f32) -> f32 {
+    20.0 * amplitude.max(1e-10).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_true_peak_finds_inter_sample_peaks() {
+        // Quarter-rate sine sampled 45 degrees off its peaks: samples only reach 0.707
+        let samples: Vec<f32> = (0..64).map(|i| (PI / 2.0 * i as f32 + PI / 4.0).sin()).collect();
+        assert!(samples.iter().all(|s| s.abs() < 0.71));
+
+        let mut meter = TruePeakMeter::default();
+        for &sample in &samples {
+            meter.push(sample);
+        }
+        assert!((meter.finish() - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_verification_problems() {
+        let mut verification = Verification {
+            expected_frames: 100,
+            frames: 100,
+            sample_peak: 0.9,
+            true_peak: 0.95,
+            loudness: Some(-14.2),
+            non_finite: 0,
+        };
+        assert!(verification.errors().is_empty() && verification.warnings().is_empty());
+        assert!(verification.summary().ends_with("-14.2 LUFS"));
+
+        verification.frames = 99;
+        verification.non_finite = 3;
+        verification.true_peak = 1.2;
+        assert_eq!(verification.errors().len(), 2);
+        assert_eq!(verification.warnings().len(), 1);
+    }
+}
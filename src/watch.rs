@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::paths;
+
+/// What a file looked like when last checked. A file being copied in keeps growing or
+/// getting newer, so one that stays the same for a while has been written completely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Snapshot {
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl Snapshot {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = fs::metadata(paths::normalize_for_io(path)).ok()?;
+        Some(Self { len: metadata.len(), modified: metadata.modified().ok() })
+    }
+}
+
+/// Files appearing in a watched folder, handed out once each has stopped changing for the
+/// settle time and can be opened. Windows refuses to open a file another program still has
+/// open for writing, other systems only show it by the size and time changing. A file that
+/// changes again after being handed out, like one copied over, is handed out again.
+#[derive(Debug)]
+pub struct StableFiles {
+    settle: Duration,
+    // Files not handed out yet, as last seen and since when they looked like that
+    settling: HashMap<PathBuf, (Snapshot, Instant)>,
+    // Files handed out, as they were then
+    handed_out: HashMap<PathBuf, Snapshot>,
+}
+
+impl StableFiles {
+    pub fn new(settle: Duration) -> Self {
+        Self { settle, settling: HashMap::new(), handed_out: HashMap::new() }
+    }
+
+    /// Which of the files now in the folder are ready, in the order given, as of `now`.
+    pub fn ready(&mut self, files: &[PathBuf], now: Instant) -> Vec<PathBuf> {
+        // Forget files that went away, so one that comes back is new again
+        self.settling.retain(|path, _| files.contains(path));
+        self.handed_out.retain(|path, _| files.contains(path));
+
+        let mut ready = Vec::new();
+        for path in files {
+            let Some(snapshot) = Snapshot::of(path) else {
+                continue;
+            };
+            if self.handed_out.get(path) == Some(&snapshot) {
+                continue;
+            }
+            let since = match self.settling.get(path) {
+                Some(&(seen, since)) if seen == snapshot => since,
+                _ => {
+                    self.settling.insert(path.clone(), (snapshot, now));
+                    now
+                }
+            };
+            if now.duration_since(since) >= self.settle && can_open(path) {
+                self.settling.remove(path);
+                self.handed_out.insert(path.clone(), snapshot);
+                ready.push(path.clone());
+            }
+        }
+        ready
+    }
+
+    /// Whether `path` changed since it was handed out, as a file still being written does.
+    /// It is watched again from now on, so it's retried once it settles.
+    pub fn changed_since_ready(&mut self, path: &Path) -> bool {
+        let changed = self.handed_out.get(path) != Snapshot::of(path).as_ref();
+        if changed {
+            self.handed_out.remove(path);
+        }
+        changed
+    }
+}
+
+fn can_open(path: &Path) -> bool {
+    File::open(paths::normalize_for_io(path)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_are_ready_once_they_stop_changing() {
+        let dir = std::env::temp_dir().join("autotune-watch-test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("take.wav");
+        let files = [path.clone()];
+        let settle = Duration::from_secs(2);
+        let mut watched = StableFiles::new(settle);
+        let start = Instant::now();
+
+        // Still being copied: it grows between polls
+        fs::write(&path, [0; 10]).unwrap();
+        assert!(watched.ready(&files, start).is_empty());
+        fs::write(&path, [0; 20]).unwrap();
+        assert!(watched.ready(&files, start + settle).is_empty());
+
+        // The same size for the settle time
+        assert_eq!(watched.ready(&files, start + settle * 2), files);
+        assert!(watched.ready(&files, start + settle * 3).is_empty());
+        assert!(!watched.changed_since_ready(&path));
+
+        // Written to again while it was processed: retried once it settles
+        fs::write(&path, [0; 30]).unwrap();
+        assert!(watched.changed_since_ready(&path));
+        assert!(watched.ready(&files, start + settle * 4).is_empty());
+        assert_eq!(watched.ready(&files, start + settle * 5), files);
+
+        assert!(watched.ready(&[dir.join("gone.wav")], start + settle * 6).is_empty());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,291 @@
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::bookmarks::Bookmark;
+use crate::decoder::MonoReader;
+use crate::labels::Label;
+use crate::theme::PlotColors;
+
+// Buckets kept per file when its length is known, plenty for a wide window
+const BUCKETS: usize = 4096;
+// Frames decoded at a time while measuring a file
+const READ_BLOCK: usize = 1 << 16;
+// Samples this close to full scale count as clipped
+const CLIP_LEVEL: f32 = 0.999;
+// A file whose peak stays below -60 dBFS counts as silent
+const SILENCE_PEAK: f32 = 0.001;
+const HEIGHT: f32 = 96.0;
+
+/// How the input and processed waveforms share the widget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum View {
+    #[default]
+    Overlay,
+    Split,
+}
+
+/// What is marked on the time axis besides the waveforms.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Marks<'a> {
+    // Shaded, the stretches that are corrected
+    pub regions: &'a [Label],
+    // A line each, with its name along the top
+    pub bookmarks: &'a [Bookmark],
+    // Where the waveform was last clicked or jumped to
+    pub cursor: Option<f32>,
+}
+
+/// Downsampled peak envelope of a mono signal: the lowest and highest sample of each
+/// bucket, plus what's needed to tell a clipped or silent file at a glance.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    buckets: Vec<(f32, f32)>,
+    bucket_len: usize,
+    // Samples in the last bucket so far
+    filled: usize,
+    pub sample_rate: u32,
+    pub frames: usize,
+    pub peak: f32,
+    pub clipped: usize,
+}
+
+impl Envelope {
+    pub fn new(bucket_len: usize, sample_rate: u32) -> Self {
+        Self {
+            buckets: Vec::new(),
+            bucket_len: bucket_len.max(1),
+            filled: 0,
+            sample_rate,
+            frames: 0,
+            peak: 0.0,
+            clipped: 0,
+        }
+    }
+
+    /// Decode `path` a block at a time and measure its envelope.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut reader = MonoReader::open(path)?;
+        let info = *reader.info();
+        // Streams of unknown length get 10 ms buckets
+        let bucket_len =
+            info.frames.map_or(info.sample_rate as usize / 100, |frames| frames as usize / BUCKETS);
+        let mut envelope = Self::new(bucket_len, info.sample_rate);
+
+        let mut block = Vec::with_capacity(READ_BLOCK);
+        loop {
+            block.clear();
+            if reader.read(&mut block, READ_BLOCK)? == 0 {
+                return Ok(envelope);
+            }
+            envelope.push(&block);
+        }
+    }
+
+    /// [`load`](Self::load) on a background thread.
+    pub fn load_in_background(path: PathBuf) -> mpsc::Receiver<Result<Self, String>> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(Self::load(&path));
+        });
+        receiver
+    }
+
+    pub fn push(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            // NaN and inf are reported by the output check, draw them as silence
+            let sample = if sample.is_finite() { sample } else { 0.0 };
+            match self.buckets.last_mut() {
+                Some((min, max)) if self.filled > 0 => {
+                    *min = min.min(sample);
+                    *max = max.max(sample);
+                }
+                _ => self.buckets.push((sample, sample)),
+            }
+            self.filled = (self.filled + 1) % self.bucket_len;
+            self.peak = self.peak.max(sample.abs());
+            if sample.abs() >= CLIP_LEVEL {
+                self.clipped += 1;
+            }
+        }
+        self.frames += samples.len();
+    }
+
+    pub fn duration_secs(&self) -> f32 {
+        self.frames as f32 / self.sample_rate as f32
+    }
+
+    pub fn is_silent(&self) -> bool {
+        self.peak < SILENCE_PEAK
+    }
+
+    /// Lowest and highest sample between `from_secs` and `to_secs`, at bucket resolution.
+    /// Never empty while the range starts inside the signal.
+    pub fn range(&self, from_secs: f32, to_secs: f32) -> Option<(f32, f32)> {
+        let to_bucket = |secs: f32| secs * self.sample_rate as f32 / self.bucket_len as f32;
+        let start = to_bucket(from_secs).max(0.0) as usize;
+        let end = (to_bucket(to_secs).ceil() as usize).max(start + 1).min(self.buckets.len());
+        self.buckets
+            .get(start..end)
+            .and_then(|buckets| buckets.iter().copied().reduce(|a, b| (a.0.min(b.0), a.1.max(b.1))))
+    }
+
+    /// Peak level, with a warning for clipping or silence.
+    pub fn summary(&self) -> String {
+        let peak = if self.peak > 0.0 {
+            format!("{:.1} dBFS peak", 20.0 * self.peak.log10())
+        } else {
+            "-inf dBFS peak".to_string()
+        };
+        if self.clipped > 0 {
+            format!("{}, ⚠ {} clipped samples", peak, self.clipped)
+        } else if self.is_silent() {
+            format!("{}, ⚠ silent", peak)
+        } else {
+            peak
+        }
+    }
+}
+
+/// Draw the input waveform and, once there is one, the processed waveform on a shared time
+/// axis, with clipped stretches in the error color and `marks` drawn over them. Dragging
+/// across it sets `selection`, the in and out points in seconds, drawn between two lines.
+/// Returns the time clicked, if any.
+pub fn show(
+    ui: &mut egui::Ui,
+    before: Option<&Envelope>,
+    after: Option<&Envelope>,
+    view: View,
+    marks: &Marks,
+    selection: &mut Option<(f32, f32)>,
+    colors: &PlotColors,
+) -> Option<f32> {
+    let duration =
+        [before, after].into_iter().flatten().map(Envelope::duration_secs).fold(0.0, f32::max);
+    let width = ui.available_width();
+    let (area, response) =
+        ui.allocate_exact_size(egui::vec2(width, HEIGHT), egui::Sense::click_and_drag());
+    let painter = ui.painter_at(area);
+    painter.rect_filled(area, 2.0, ui.visuals().extreme_bg_color);
+    if duration <= 0.0 {
+        return None;
+    }
+
+    let region_color = ui.visuals().selection.bg_fill.gamma_multiply(0.2);
+    let x = |secs: f32| area.left() + (secs / duration).clamp(0.0, 1.0) * area.width();
+    for region in marks.regions.iter().filter(|region| !region.is_point()) {
+        let rect =
+            egui::Rect::from_x_y_ranges(x(region.start_secs)..=x(region.end_secs), area.y_range());
+        painter.rect_filled(rect, 0.0, region_color);
+    }
+
+    let pointer_secs =
+        |pos: egui::Pos2| ((pos.x - area.left()) / area.width() * duration).clamp(0.0, duration);
+    if response.dragged() {
+        let origin = ui.input(|input| input.pointer.press_origin());
+        if let (Some(origin), Some(pos)) = (origin, response.interact_pointer_pos()) {
+            let (from, to) = (pointer_secs(origin), pointer_secs(pos));
+            *selection = Some((from.min(to), from.max(to)));
+        }
+    }
+    if let Some((start, end)) = *selection {
+        let stroke = ui.visuals().selection.stroke;
+        let rect = egui::Rect::from_x_y_ranges(x(start)..=x(end), area.y_range());
+        painter.rect_filled(rect, 0.0, ui.visuals().selection.bg_fill.gamma_multiply(0.35));
+        painter.vline(x(start), area.y_range(), stroke);
+        painter.vline(x(end), area.y_range(), stroke);
+    }
+
+    let before_color = colors.input;
+    let after_color = colors.output.gamma_multiply(0.8);
+    let lanes = match (view, after) {
+        (View::Split, Some(after)) => {
+            let middle = area.center().y;
+            let top = egui::Rect::from_min_max(area.min, egui::pos2(area.right(), middle));
+            let bottom = egui::Rect::from_min_max(egui::pos2(area.left(), middle), area.max);
+            vec![(before, top, before_color), (Some(after), bottom, after_color)]
+        }
+        _ => vec![(before, area, before_color), (after, area, after_color)],
+    };
+
+    let error_color = colors.error;
+    for (envelope, rect, color) in lanes {
+        let Some(envelope) = envelope else {
+            continue;
+        };
+        let half_height = rect.height() / 2.0 - 1.0;
+        let y = |sample: f32| rect.center().y - sample.clamp(-1.0, 1.0) * half_height;
+        for column in 0..rect.width() as usize {
+            let secs = |column: usize| column as f32 / rect.width() * duration;
+            let Some((min, max)) = envelope.range(secs(column), secs(column + 1)) else {
+                break;
+            };
+            let x = rect.left() + column as f32 + 0.5;
+            let clipped = max >= CLIP_LEVEL || min <= -CLIP_LEVEL;
+            // At least a pixel, so silence still shows where the signal is
+            let (top, bottom) = (y(max), y(min).max(y(max) + 1.0));
+            painter.line_segment(
+                [egui::pos2(x, top), egui::pos2(x, bottom)],
+                egui::Stroke::new(1.0, if clipped { error_color } else { color }),
+            );
+        }
+    }
+
+    let mark_color = colors.warning;
+    for bookmark in marks.bookmarks {
+        painter.vline(x(bookmark.secs), area.y_range(), egui::Stroke::new(1.0, mark_color));
+        painter.text(
+            egui::pos2(x(bookmark.secs) + 2.0, area.top()),
+            egui::Align2::LEFT_TOP,
+            &bookmark.name,
+            egui::FontId::proportional(10.0),
+            mark_color,
+        );
+    }
+    if let Some(cursor) = marks.cursor {
+        painter.vline(x(cursor), area.y_range(), ui.visuals().widgets.active.fg_stroke);
+    }
+
+    let clicked = response.interact_pointer_pos().filter(|_| response.clicked()).map(pointer_secs);
+    if let Some(pos) = response.hover_pos() {
+        response.on_hover_text(crate::format::duration(pointer_secs(pos)));
+    }
+    clicked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope_buckets_and_ranges() {
+        let mut envelope = Envelope::new(4, 8);
+        // Streamed in uneven blocks, buckets still hold 4 samples each
+        envelope.push(&[0.1, -0.2, 0.3]);
+        envelope.push(&[0.0, 0.5, 0.4, -0.6, 0.2, f32::NAN]);
+        assert_eq!(envelope.buckets, vec![(-0.2, 0.3), (-0.6, 0.5), (0.0, 0.0)]);
+        assert_eq!(envelope.duration_secs(), 9.0 / 8.0);
+
+        // Half a second is one bucket, a full second two
+        assert_eq!(envelope.range(0.5, 1.0), Some((-0.6, 0.5)));
+        assert_eq!(envelope.range(0.0, 1.0), Some((-0.6, 0.5)));
+        // Narrower than a bucket still hits one
+        assert_eq!(envelope.range(0.1, 0.2), Some((-0.2, 0.3)));
+        assert_eq!(envelope.range(2.0, 3.0), None);
+    }
+
+    #[test]
+    fn test_clipped_and_silent_summaries() {
+        let mut clipped = Envelope::new(2, 8);
+        clipped.push(&[1.0, -1.0, 0.5]);
+        assert_eq!(clipped.clipped, 2);
+        assert!(clipped.summary().contains("2 clipped samples"));
+
+        let mut silent = Envelope::new(2, 8);
+        silent.push(&[0.0; 16]);
+        assert!(silent.is_silent());
+        assert!(silent.summary().contains("silent"));
+    }
+}